@@ -9,10 +9,11 @@ use kube::Client;
 use tracing::{debug, instrument};
 
 use crate::crd::{
-    ECStripe, ECStripeSpec, LbaRange as CrdLbaRange, ShardLocation as CrdShardLocation,
+    ECStripe, ECStripeSpec, LbaRange as CrdLbaRange, ShardHealth,
+    ShardLocation as CrdShardLocation, ShardState, StripeState,
 };
 use crate::domain::ports::{
-    LbaRange, ShardLocation, StripeId, StripeMetadata, StripeRepository, VolumeId,
+    LbaRange, ShardHealthState, ShardLocation, StripeId, StripeMetadata, StripeRepository, VolumeId,
 };
 use crate::error::{Error, Result};
 
@@ -46,26 +47,76 @@ impl KubernetesStripeRepository {
         format!("{}-stripe-{}", volume_id.0, stripe_id.0)
     }
 
+    /// Find the Kubernetes resource for a stripe of a volume.
+    async fn find_resource(
+        &self,
+        volume_id: &VolumeId,
+        stripe_id: &StripeId,
+    ) -> Result<Option<ECStripe>> {
+        let params = ListParams::default()
+            .labels(&format!("volume={},stripe-id={}", volume_id.0, stripe_id.0));
+        let list = self.stripes_api().list(&params).await?;
+        Ok(list.items.into_iter().next())
+    }
+
+    /// List the IDs of all volumes that have ECStripe resources.
+    pub async fn list_volumes(&self) -> Result<Vec<VolumeId>> {
+        let list = self.stripes_api().list(&ListParams::default()).await?;
+
+        let volumes: std::collections::BTreeSet<String> =
+            list.items.into_iter().map(|s| s.spec.volume_ref).collect();
+        Ok(volumes.into_iter().map(VolumeId).collect())
+    }
+
+    /// Convert a domain ShardLocation to its CRD form.
+    fn to_crd_location(s: &ShardLocation) -> CrdShardLocation {
+        CrdShardLocation {
+            shard_index: s.shard_index as u8,
+            is_data_shard: s.is_data_shard,
+            pool_name: s.device_id.clone(),
+            node_name: s.node_id.clone(),
+            offset: s.offset,
+            size_bytes: s.size,
+            checksum: s.checksum.clone(),
+        }
+    }
+
+    /// Convert a domain shard health state to its CRD form.
+    fn to_crd_shard_state(health: ShardHealthState) -> ShardState {
+        match health {
+            ShardHealthState::Healthy => ShardState::Healthy,
+            ShardHealthState::Missing => ShardState::Missing,
+            ShardHealthState::Corrupted => ShardState::Corrupted,
+            ShardHealthState::Rebuilding => ShardState::Rebuilding,
+        }
+    }
+
+    /// Convert a CRD shard state to the domain health state.
+    fn from_crd_shard_state(state: &ShardState) -> ShardHealthState {
+        match state {
+            ShardState::Healthy => ShardHealthState::Healthy,
+            ShardState::Missing => ShardHealthState::Missing,
+            ShardState::Corrupted => ShardHealthState::Corrupted,
+            ShardState::Rebuilding => ShardHealthState::Rebuilding,
+        }
+    }
+
     /// Convert domain StripeMetadata to CRD spec.
     fn to_crd_spec(&self, stripe: &StripeMetadata) -> ECStripeSpec {
         let shard_locations: Vec<CrdShardLocation> = stripe
             .shard_locations
             .iter()
-            .map(|s| CrdShardLocation {
-                shard_index: s.shard_index as u8,
-                is_data_shard: true, // TODO: track this properly
-                pool_name: s.device_id.clone(),
-                node_name: s.device_id.clone(),
-                offset: s.offset,
-                size_bytes: s.size,
-                checksum: None,
-            })
+            .map(Self::to_crd_location)
             .collect();
 
         ECStripeSpec {
             volume_ref: stripe.volume_id.0.clone(),
             stripe_id: stripe.stripe_id.0,
-            policy_ref: self.policy_ref.clone(),
+            policy_ref: if stripe.policy_ref.is_empty() {
+                self.policy_ref.clone()
+            } else {
+                stripe.policy_ref.clone()
+            },
             shard_locations,
             lba_range: CrdLbaRange {
                 start_lba: stripe.lba_range.start,
@@ -80,20 +131,37 @@ impl KubernetesStripeRepository {
     fn from_crd(stripe: &ECStripe) -> Option<StripeMetadata> {
         let spec = &stripe.spec;
 
+        let shard_health = stripe
+            .status
+            .as_ref()
+            .map(|s| s.shard_health.as_slice())
+            .unwrap_or_default();
+
         let shard_locations: Vec<ShardLocation> = spec
             .shard_locations
             .iter()
-            .map(|s| ShardLocation {
-                shard_index: s.shard_index as usize,
-                device_id: s.pool_name.clone(),
-                offset: s.offset,
-                size: s.size_bytes,
+            .map(|s| {
+                let health = shard_health.iter().find(|h| h.shard_index == s.shard_index);
+                ShardLocation {
+                    shard_index: s.shard_index as usize,
+                    device_id: s.pool_name.clone(),
+                    node_id: s.node_name.clone(),
+                    offset: s.offset,
+                    size: s.size_bytes,
+                    is_data_shard: s.is_data_shard,
+                    checksum: s.checksum.clone(),
+                    health: health
+                        .map(|h| Self::from_crd_shard_state(&h.state))
+                        .unwrap_or_default(),
+                    failed_pool: health.and_then(|h| h.failed_pool.clone()),
+                }
             })
             .collect();
 
         Some(StripeMetadata {
             stripe_id: StripeId(spec.stripe_id),
             volume_id: VolumeId(spec.volume_ref.clone()),
+            policy_ref: spec.policy_ref.clone(),
             lba_range: LbaRange {
                 start: spec.lba_range.start_lba,
                 end: spec.lba_range.end_lba,
@@ -132,19 +200,24 @@ impl StripeRepository for KubernetesStripeRepository {
         // Check if exists
         match api.get(&name).await {
             Ok(_) => {
-                // Update existing
-                let patch = serde_json::json!({ "spec": spec });
+                // Update existing, keeping the policy label in step with the spec
+                let patch = serde_json::json!({
+                    "metadata": { "labels": { "policy": spec.policy_ref } },
+                    "spec": spec
+                });
                 let params = PatchParams::apply("couchestor");
                 api.patch(&name, &params, &Patch::Merge(&patch)).await?;
                 debug!(name = %name, "Updated ECStripe");
             }
             Err(kube::Error::Api(e)) if e.code == 404 => {
                 // Create new
+                let policy = spec.policy_ref.clone();
                 let mut ec_stripe = ECStripe::new(&name, spec);
                 ec_stripe.metadata.labels = Some(
                     [
                         ("volume".to_string(), stripe.volume_id.0.clone()),
                         ("stripe-id".to_string(), stripe.stripe_id.0.to_string()),
+                        ("policy".to_string(), policy),
                     ]
                     .into(),
                 );
@@ -208,13 +281,12 @@ impl StripeRepository for KubernetesStripeRepository {
     }
 
     #[instrument(skip(self))]
-    async fn delete(&self, stripe_id: &StripeId) -> Result<()> {
+    async fn delete(&self, volume_id: &VolumeId, stripe_id: &StripeId) -> Result<()> {
         let api = self.stripes_api();
 
         // Find the stripe first
-        let stripe = self.find_by_id(stripe_id).await?;
-        if let Some(s) = stripe {
-            let name = Self::stripe_name(&s.volume_id, stripe_id);
+        let stripe = self.find_resource(volume_id, stripe_id).await?;
+        if let Some(name) = stripe.and_then(|s| s.metadata.name) {
             api.delete(&name, &Default::default()).await?;
             debug!(name = %name, "Deleted ECStripe");
         }
@@ -242,34 +314,150 @@ impl StripeRepository for KubernetesStripeRepository {
     }
 
     #[instrument(skip(self))]
-    async fn update_generation(&self, stripe_id: &StripeId, new_generation: u64) -> Result<bool> {
+    async fn update_generation(
+        &self,
+        volume_id: &VolumeId,
+        stripe_id: &StripeId,
+        new_generation: u64,
+    ) -> Result<bool> {
         let api = self.stripes_api();
 
-        // Find the stripe first
-        let stripe = self.find_by_id(stripe_id).await?;
-        if let Some(s) = stripe {
-            let name = Self::stripe_name(&s.volume_id, stripe_id);
+        let Some(stripe) = self.find_resource(volume_id, stripe_id).await? else {
+            return Ok(false);
+        };
+        if stripe.spec.generation.saturating_add(1) != new_generation {
+            debug!(
+                stripe = stripe_id.0,
+                stored = stripe.spec.generation,
+                requested = new_generation,
+                "Generation conflict"
+            );
+            return Ok(false);
+        }
 
-            let patch = serde_json::json!({
-                "spec": {
-                    "generation": new_generation
-                }
-            });
-            let params = PatchParams::apply("couchestor");
-            api.patch(&name, &params, &Patch::Merge(&patch)).await?;
+        let name = stripe.name().to_string();
 
-            debug!(name = %name, generation = new_generation, "Updated generation");
-            Ok(true)
-        } else {
-            Ok(false)
+        // Pin the resourceVersion so a concurrent writer makes the patch fail
+        let patch = serde_json::json!({
+            "metadata": {
+                "resourceVersion": stripe.metadata.resource_version
+            },
+            "spec": {
+                "generation": new_generation
+            }
+        });
+        let params = PatchParams::apply("couchestor");
+        match api.patch(&name, &params, &Patch::Merge(&patch)).await {
+            Ok(_) => {
+                debug!(name = %name, generation = new_generation, "Updated generation");
+                Ok(true)
+            }
+            Err(kube::Error::Api(e)) if e.code == 409 => Ok(false),
+            Err(e) => Err(Error::Kube(e)),
         }
     }
 
+    #[instrument(skip(self, stripe))]
+    async fn save_if_generation(&self, stripe: &StripeMetadata) -> Result<bool> {
+        let api = self.stripes_api();
+
+        let Some(current) = self
+            .find_resource(&stripe.volume_id, &stripe.stripe_id)
+            .await?
+        else {
+            return Ok(false);
+        };
+        if current.spec.generation.saturating_add(1) != stripe.generation {
+            debug!(
+                stripe = stripe.stripe_id.0,
+                stored = current.spec.generation,
+                requested = stripe.generation,
+                "Generation conflict"
+            );
+            return Ok(false);
+        }
+        let name = current.name().to_string();
+
+        // Spec (shards and generation) in one patch, pinned to the
+        // resourceVersion so a concurrent writer makes it fail
+        let spec = self.to_crd_spec(stripe);
+        let patch = serde_json::json!({
+            "metadata": {
+                "resourceVersion": current.metadata.resource_version,
+                "labels": { "policy": spec.policy_ref }
+            },
+            "spec": spec
+        });
+        let params = PatchParams::apply("couchestor");
+        match api.patch(&name, &params, &Patch::Merge(&patch)).await {
+            Ok(_) => {}
+            Err(kube::Error::Api(e)) if e.code == 409 => return Ok(false),
+            Err(e) => return Err(Error::Kube(e)),
+        }
+
+        // Shard health lives in the status subresource and is derived from
+        // the spec, so a failure here leaves it stale but not inconsistent
+        let shard_locations = &stripe.shard_locations;
+        let now = Utc::now();
+        let shard_health: Vec<ShardHealth> = shard_locations
+            .iter()
+            .map(|s| ShardHealth {
+                shard_index: s.shard_index as u8,
+                state: Self::to_crd_shard_state(s.health),
+                last_verified: Some(now),
+                error: None,
+                failed_pool: s.failed_pool.clone(),
+            })
+            .collect();
+        let healthy = shard_locations
+            .iter()
+            .filter(|s| s.health == ShardHealthState::Healthy);
+        let healthy_data = healthy.clone().filter(|s| s.is_data_shard).count() as u8;
+        let healthy_shards = healthy.count() as u8;
+        let state = if healthy_shards as usize == shard_locations.len() {
+            StripeState::Healthy
+        } else {
+            StripeState::Degraded
+        };
+
+        let status_patch = serde_json::json!({
+            "status": {
+                "state": state,
+                "healthyShards": healthy_shards,
+                "healthyDataShards": healthy_data,
+                "healthyParityShards": healthy_shards - healthy_data,
+                "lastModifiedTime": now,
+                "shardHealth": shard_health,
+            }
+        });
+        api.patch_status(&name, &params, &Patch::Merge(&status_patch))
+            .await?;
+
+        debug!(
+            name = %name,
+            generation = stripe.generation,
+            shards = shard_locations.len(),
+            "Replaced stripe"
+        );
+        Ok(true)
+    }
+
     #[instrument(skip(self))]
     async fn count_by_volume(&self, volume_id: &VolumeId) -> Result<u64> {
         let stripes = self.find_by_volume(volume_id).await?;
         Ok(stripes.len() as u64)
     }
+
+    /// ECStripe CRDs hold no per-volume settings. A volume loaded from them
+    /// takes the policy of its newest stripe, which is the new policy once
+    /// every stripe has been re-striped.
+    async fn save_volume_policy(&self, _volume_id: &VolumeId, _policy_ref: &str) -> Result<()> {
+        Ok(())
+    }
+
+    async fn find_volume_policy(&self, _volume_id: &VolumeId) -> Result<Option<String>> {
+        Ok(None)
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,219 @@
+//! In-Memory Stripe Repository Adapter
+//!
+//! Implements the `StripeRepository` port in process memory. Intended for
+//! tests and single-node experiments where no Kubernetes API is available.
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+
+use crate::domain::ports::{LbaRange, StripeId, StripeMetadata, StripeRepository, VolumeId};
+use crate::error::Result;
+
+/// In-memory stripe repository keyed by (volume, stripe).
+#[allow(dead_code)]
+#[derive(Debug, Default)]
+pub struct InMemoryStripeRepository {
+    stripes: DashMap<(String, u64), StripeMetadata>,
+    policies: DashMap<String, String>,
+}
+
+#[allow(dead_code)]
+impl InMemoryStripeRepository {
+    /// Create an empty repository.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get a stripe by volume and ID.
+    pub fn get(&self, volume_id: &VolumeId, stripe_id: &StripeId) -> Option<StripeMetadata> {
+        self.stripes
+            .get(&(volume_id.0.clone(), stripe_id.0))
+            .map(|s| s.clone())
+    }
+
+    /// Total number of stripes stored.
+    pub fn len(&self) -> usize {
+        self.stripes.len()
+    }
+
+    /// Check if the repository is empty.
+    pub fn is_empty(&self) -> bool {
+        self.stripes.is_empty()
+    }
+}
+
+#[async_trait]
+impl StripeRepository for InMemoryStripeRepository {
+    async fn save(&self, stripe: &StripeMetadata) -> Result<()> {
+        self.stripes.insert(
+            (stripe.volume_id.0.clone(), stripe.stripe_id.0),
+            stripe.clone(),
+        );
+        Ok(())
+    }
+
+    async fn find_by_id(&self, stripe_id: &StripeId) -> Result<Option<StripeMetadata>> {
+        Ok(self
+            .stripes
+            .iter()
+            .find(|e| e.key().1 == stripe_id.0)
+            .map(|e| e.value().clone()))
+    }
+
+    async fn find_by_lba(&self, volume_id: &VolumeId, lba: u64) -> Result<Option<StripeMetadata>> {
+        Ok(self
+            .stripes
+            .iter()
+            .find(|e| e.key().0 == volume_id.0 && e.value().lba_range.contains(lba))
+            .map(|e| e.value().clone()))
+    }
+
+    async fn find_by_volume(&self, volume_id: &VolumeId) -> Result<Vec<StripeMetadata>> {
+        let mut stripes: Vec<StripeMetadata> = self
+            .stripes
+            .iter()
+            .filter(|e| e.key().0 == volume_id.0)
+            .map(|e| e.value().clone())
+            .collect();
+        stripes.sort_by_key(|s| s.stripe_id.0);
+        Ok(stripes)
+    }
+
+    async fn find_by_lba_range(
+        &self,
+        volume_id: &VolumeId,
+        range: &LbaRange,
+    ) -> Result<Vec<StripeMetadata>> {
+        Ok(self
+            .find_by_volume(volume_id)
+            .await?
+            .into_iter()
+            .filter(|s| s.lba_range.overlaps(range))
+            .collect())
+    }
+
+    async fn delete(&self, volume_id: &VolumeId, stripe_id: &StripeId) -> Result<()> {
+        self.stripes.remove(&(volume_id.0.clone(), stripe_id.0));
+        Ok(())
+    }
+
+    async fn delete_by_volume(&self, volume_id: &VolumeId) -> Result<u64> {
+        let before = self.stripes.len();
+        self.stripes.retain(|k, _| k.0 != volume_id.0);
+        self.policies.remove(&volume_id.0);
+        Ok((before - self.stripes.len()) as u64)
+    }
+
+    async fn update_generation(
+        &self,
+        volume_id: &VolumeId,
+        stripe_id: &StripeId,
+        new_generation: u64,
+    ) -> Result<bool> {
+        match self.stripes.get_mut(&(volume_id.0.clone(), stripe_id.0)) {
+            Some(mut stripe) if stripe.generation.saturating_add(1) == new_generation => {
+                stripe.generation = new_generation;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn save_if_generation(&self, stripe: &StripeMetadata) -> Result<bool> {
+        match self
+            .stripes
+            .get_mut(&(stripe.volume_id.0.clone(), stripe.stripe_id.0))
+        {
+            Some(mut stored) if stored.generation.saturating_add(1) == stripe.generation => {
+                *stored = stripe.clone();
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn count_by_volume(&self, volume_id: &VolumeId) -> Result<u64> {
+        Ok(self
+            .stripes
+            .iter()
+            .filter(|e| e.key().0 == volume_id.0)
+            .count() as u64)
+    }
+
+    async fn save_volume_policy(&self, volume_id: &VolumeId, policy_ref: &str) -> Result<()> {
+        self.policies
+            .insert(volume_id.0.clone(), policy_ref.to_string());
+        Ok(())
+    }
+
+    async fn find_volume_policy(&self, volume_id: &VolumeId) -> Result<Option<String>> {
+        Ok(self.policies.get(&volume_id.0).map(|p| p.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stripe(volume: &str, id: u64, start: u64, end: u64) -> StripeMetadata {
+        StripeMetadata {
+            stripe_id: StripeId(id),
+            volume_id: VolumeId::new(volume),
+            policy_ref: "ec-4-2".to_string(),
+            lba_range: LbaRange::new(start, end),
+            shard_locations: vec![],
+            generation: 0,
+            created_at: chrono::Utc::now(),
+            is_compressed: false,
+            original_size: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_save_and_find() {
+        let repo = InMemoryStripeRepository::new();
+        repo.save(&stripe("vol-1", 0, 0, 100)).await.unwrap();
+        repo.save(&stripe("vol-1", 1, 100, 200)).await.unwrap();
+        repo.save(&stripe("vol-2", 0, 0, 100)).await.unwrap();
+
+        let vol = VolumeId::new("vol-1");
+        assert_eq!(repo.count_by_volume(&vol).await.unwrap(), 2);
+        assert_eq!(
+            repo.find_by_lba(&vol, 150)
+                .await
+                .unwrap()
+                .unwrap()
+                .stripe_id,
+            StripeId(1)
+        );
+        assert_eq!(
+            repo.find_by_lba_range(&vol, &LbaRange::new(50, 150))
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(repo.delete_by_volume(&vol).await.unwrap(), 2);
+        assert_eq!(repo.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_update_generation_is_optimistic() {
+        let repo = InMemoryStripeRepository::new();
+        repo.save(&stripe("vol-1", 7, 0, 100)).await.unwrap();
+
+        let vol = VolumeId::new("vol-1");
+        assert!(repo.update_generation(&vol, &StripeId(7), 1).await.unwrap());
+        // A second writer racing for generation 1 loses
+        assert!(!repo.update_generation(&vol, &StripeId(7), 1).await.unwrap());
+        // Unknown stripes are reported, not created
+        assert!(!repo.update_generation(&vol, &StripeId(8), 1).await.unwrap());
+        assert_eq!(repo.get(&vol, &StripeId(7)).unwrap().generation, 1);
+
+        let mut moved = stripe("vol-1", 7, 0, 100);
+        moved.generation = 2;
+        assert!(repo.save_if_generation(&moved).await.unwrap());
+        assert!(!repo.save_if_generation(&moved).await.unwrap());
+        assert_eq!(repo.get(&vol, &StripeId(7)).unwrap().generation, 2);
+    }
+}
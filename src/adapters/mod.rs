@@ -19,7 +19,8 @@
 //! │                     Adapters (This Module)                       │
 //! │  ┌────────────────────────────────────────────────────────────┐ │
 //! │  │ PrometheusAdapter │ MayastorAdapter │ ReedSolomonAdapter  │ │
-//! │  │ KubernetesStripeRepository │ LoggingEventPublisher        │ │
+//! │  │ KubernetesStripeRepository │ InMemoryStripeRepository     │ │
+//! │  │ LogStripeRepository │ LoggingEventPublisher                │ │
 //! │  └────────────────────────────────────────────────────────────┘ │
 //! └─────────────────────────────────────────────────────────────────┘
 //! ```
@@ -44,17 +45,23 @@
 
 mod kubernetes;
 mod mayastor;
+mod memory;
 mod prometheus;
 mod reed_solomon;
+mod stripe_log;
 
 #[allow(unused_imports)]
 pub use kubernetes::KubernetesStripeRepository;
 #[allow(unused_imports)]
 pub use mayastor::MayastorVolumeAdapter;
 #[allow(unused_imports)]
+pub use memory::InMemoryStripeRepository;
+#[allow(unused_imports)]
 pub use prometheus::PrometheusMetricsAdapter;
 #[allow(unused_imports)]
 pub use reed_solomon::ReedSolomonCodecAdapter;
+#[allow(unused_imports)]
+pub use stripe_log::LogStripeRepository;
 
 // Re-export event publishers for convenience
 mod event_publisher;
@@ -143,7 +143,7 @@ mod tests {
         let mut shards: Vec<Option<Vec<u8>>> = encoded
             .data_shards
             .into_iter()
-            .chain(encoded.parity_shards.into_iter())
+            .chain(encoded.parity_shards)
             .map(Some)
             .collect();
 
@@ -163,7 +163,7 @@ mod tests {
         let mut shards: Vec<Option<Vec<u8>>> = encoded
             .data_shards
             .into_iter()
-            .chain(encoded.parity_shards.into_iter())
+            .chain(encoded.parity_shards)
             .map(Some)
             .collect();
 
@@ -187,7 +187,7 @@ mod tests {
         let mut shards: Vec<Option<Vec<u8>>> = encoded
             .data_shards
             .into_iter()
-            .chain(encoded.parity_shards.into_iter())
+            .chain(encoded.parity_shards)
             .map(Some)
             .collect();
 
@@ -0,0 +1,926 @@
+//! Log-Structured Stripe Repository Adapter
+//!
+//! Implements the `StripeRepository` port with a local append-only log
+//! instead of one `ECStripe` object per stripe. At 1 MiB stripes a 1 TiB
+//! volume needs about a million stripe records, which is far too many etcd
+//! objects but only tens of megabytes of log.
+//!
+//! Every mutation is appended to `stripes.log` as one line,
+//! `<crc32 hex> <json record>`, and applied to an in-memory index that keeps
+//! each volume's stripes ordered by start LBA. When superseded records
+//! dominate, the log is rewritten as one record per live stripe. A torn or
+//! corrupt tail (e.g. a crash mid-append) is truncated when the log is opened.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{debug, info, instrument, warn};
+
+use crate::domain::ports::{
+    LbaRange, ShardHealthState, ShardLocation, StripeId, StripeMetadata, StripeRepository, VolumeId,
+};
+use crate::ec::record_log::RecordLog;
+use crate::error::{Error, Result};
+
+/// Log file name inside the store directory
+const LOG_FILE: &str = "stripes.log";
+
+/// Never compact logs with fewer records than this
+const COMPACT_MIN_RECORDS: u64 = 4096;
+
+/// Compact once the log holds this many records per live stripe
+const COMPACT_RATIO: u64 = 4;
+
+// =============================================================================
+// Log Records
+// =============================================================================
+
+/// One mutation of the stripe table
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum LogRecord {
+    Put {
+        stripe: StoredStripe,
+    },
+    Delete {
+        volume: String,
+        stripe: u64,
+    },
+    DeleteVolume {
+        volume: String,
+    },
+    Generation {
+        volume: String,
+        stripe: u64,
+        generation: u64,
+    },
+    Policy {
+        volume: String,
+        policy: String,
+    },
+}
+
+/// Stripe as stored in the log (short keys keep records small)
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredStripe {
+    #[serde(rename = "v")]
+    volume: String,
+    #[serde(rename = "id")]
+    stripe_id: u64,
+    #[serde(rename = "p", default)]
+    policy: String,
+    #[serde(rename = "s")]
+    start_lba: u64,
+    #[serde(rename = "e")]
+    end_lba: u64,
+    #[serde(rename = "g")]
+    generation: u64,
+    #[serde(rename = "t")]
+    created_at: DateTime<Utc>,
+    #[serde(rename = "c", default, skip_serializing_if = "is_false")]
+    compressed: bool,
+    #[serde(rename = "z", default, skip_serializing_if = "Option::is_none")]
+    original_size: Option<u64>,
+    #[serde(rename = "sh")]
+    shards: Vec<StoredShard>,
+}
+
+/// Shard location as stored in the log
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredShard {
+    #[serde(rename = "i")]
+    index: usize,
+    #[serde(rename = "d")]
+    device: String,
+    #[serde(rename = "n")]
+    node: String,
+    #[serde(rename = "o")]
+    offset: u64,
+    #[serde(rename = "l")]
+    size: u64,
+    #[serde(rename = "x")]
+    is_data: bool,
+    #[serde(rename = "k", default, skip_serializing_if = "Option::is_none")]
+    checksum: Option<String>,
+    #[serde(rename = "h", default, skip_serializing_if = "is_zero")]
+    health: u8,
+    #[serde(rename = "p", default, skip_serializing_if = "Option::is_none")]
+    failed_pool: Option<String>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+fn is_zero(value: &u8) -> bool {
+    *value == 0
+}
+
+impl From<&ShardLocation> for StoredShard {
+    fn from(s: &ShardLocation) -> Self {
+        Self {
+            index: s.shard_index,
+            device: s.device_id.clone(),
+            node: s.node_id.clone(),
+            offset: s.offset,
+            size: s.size,
+            is_data: s.is_data_shard,
+            checksum: s.checksum.clone(),
+            health: match s.health {
+                ShardHealthState::Healthy => 0,
+                ShardHealthState::Missing => 1,
+                ShardHealthState::Corrupted => 2,
+                ShardHealthState::Rebuilding => 3,
+            },
+            failed_pool: s.failed_pool.clone(),
+        }
+    }
+}
+
+impl From<StoredShard> for ShardLocation {
+    fn from(s: StoredShard) -> Self {
+        Self {
+            shard_index: s.index,
+            device_id: s.device,
+            node_id: s.node,
+            offset: s.offset,
+            size: s.size,
+            is_data_shard: s.is_data,
+            checksum: s.checksum,
+            health: match s.health {
+                1 => ShardHealthState::Missing,
+                2 => ShardHealthState::Corrupted,
+                3 => ShardHealthState::Rebuilding,
+                _ => ShardHealthState::Healthy,
+            },
+            failed_pool: s.failed_pool,
+        }
+    }
+}
+
+impl From<&StripeMetadata> for StoredStripe {
+    fn from(s: &StripeMetadata) -> Self {
+        Self {
+            volume: s.volume_id.0.clone(),
+            stripe_id: s.stripe_id.0,
+            policy: s.policy_ref.clone(),
+            start_lba: s.lba_range.start,
+            end_lba: s.lba_range.end,
+            generation: s.generation,
+            created_at: s.created_at,
+            compressed: s.is_compressed,
+            original_size: s.original_size,
+            shards: s.shard_locations.iter().map(StoredShard::from).collect(),
+        }
+    }
+}
+
+impl From<StoredStripe> for StripeMetadata {
+    fn from(s: StoredStripe) -> Self {
+        Self {
+            stripe_id: StripeId(s.stripe_id),
+            volume_id: VolumeId(s.volume),
+            policy_ref: s.policy,
+            lba_range: LbaRange::new(s.start_lba, s.end_lba),
+            shard_locations: s.shards.into_iter().map(ShardLocation::from).collect(),
+            generation: s.generation,
+            created_at: s.created_at,
+            is_compressed: s.compressed,
+            original_size: s.original_size,
+        }
+    }
+}
+
+// =============================================================================
+// In-Memory Index
+// =============================================================================
+
+/// Stripes of one volume, indexed by start LBA
+#[derive(Debug, Default)]
+struct VolumeTable {
+    stripes: HashMap<u64, StripeMetadata>,
+
+    /// Start LBA -> IDs of stripes starting there
+    by_start: BTreeMap<u64, BTreeSet<u64>>,
+
+    /// Longest stripe indexed so far; bounds how far back a range scan looks
+    max_len: u64,
+}
+
+impl VolumeTable {
+    fn insert(&mut self, stripe: StripeMetadata) {
+        self.remove(stripe.stripe_id.0);
+        self.max_len = self.max_len.max(stripe.lba_range.size());
+        self.by_start
+            .entry(stripe.lba_range.start)
+            .or_default()
+            .insert(stripe.stripe_id.0);
+        self.stripes.insert(stripe.stripe_id.0, stripe);
+    }
+
+    fn remove(&mut self, stripe_id: u64) -> Option<StripeMetadata> {
+        let stripe = self.stripes.remove(&stripe_id)?;
+        if let Some(ids) = self.by_start.get_mut(&stripe.lba_range.start) {
+            ids.remove(&stripe_id);
+            if ids.is_empty() {
+                self.by_start.remove(&stripe.lba_range.start);
+            }
+        }
+        Some(stripe)
+    }
+
+    /// Stripes overlapping `range`, in start-LBA order
+    fn overlapping(&self, range: &LbaRange) -> Vec<StripeMetadata> {
+        if range.end <= range.start {
+            return vec![];
+        }
+        let from = range.start.saturating_sub(self.max_len);
+
+        self.by_start
+            .range(from..range.end)
+            .flat_map(|(_, ids)| ids.iter())
+            .filter_map(|id| self.stripes.get(id))
+            .filter(|s| s.lba_range.overlaps(range))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Stripes and volume policies described by the log
+#[derive(Debug, Default)]
+struct StripeTables {
+    volumes: HashMap<String, VolumeTable>,
+
+    /// Policy recorded for each volume
+    policies: HashMap<String, String>,
+}
+
+impl StripeTables {
+    fn live_stripes(&self) -> u64 {
+        self.volumes.values().map(|v| v.stripes.len() as u64).sum()
+    }
+
+    /// Apply a record to the index
+    fn apply(&mut self, record: LogRecord) {
+        match record {
+            LogRecord::Put { stripe } => {
+                let stripe = StripeMetadata::from(stripe);
+                self.volumes
+                    .entry(stripe.volume_id.0.clone())
+                    .or_default()
+                    .insert(stripe);
+            }
+            LogRecord::Delete { volume, stripe } => {
+                if let Some(table) = self.volumes.get_mut(&volume) {
+                    table.remove(stripe);
+                    if table.stripes.is_empty() {
+                        self.volumes.remove(&volume);
+                    }
+                }
+            }
+            LogRecord::DeleteVolume { volume } => {
+                self.volumes.remove(&volume);
+                self.policies.remove(&volume);
+            }
+            LogRecord::Generation {
+                volume,
+                stripe,
+                generation,
+            } => {
+                if let Some(s) = self.stripe_mut(&volume, stripe) {
+                    s.generation = generation;
+                }
+            }
+            LogRecord::Policy { volume, policy } => {
+                self.policies.insert(volume, policy);
+            }
+        }
+    }
+
+    fn stripe(&self, volume: &str, stripe_id: u64) -> Option<&StripeMetadata> {
+        self.volumes.get(volume)?.stripes.get(&stripe_id)
+    }
+
+    fn stripe_mut(&mut self, volume: &str, stripe_id: u64) -> Option<&mut StripeMetadata> {
+        self.volumes.get_mut(volume)?.stripes.get_mut(&stripe_id)
+    }
+}
+
+/// Log file plus the tables it describes
+#[derive(Debug)]
+struct LogState {
+    log: RecordLog,
+    tables: StripeTables,
+}
+
+impl LogState {
+    /// Append a record to the log, then apply it
+    fn commit(&mut self, record: LogRecord) -> Result<()> {
+        self.log.append(std::slice::from_ref(&record))?;
+        self.tables.apply(record);
+
+        let records = self.log.records();
+        if records >= COMPACT_MIN_RECORDS
+            && records > self.tables.live_stripes().max(1) * COMPACT_RATIO
+        {
+            if let Err(e) = self.compact() {
+                warn!("Stripe log compaction failed: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Rewrite the log as one record per live stripe and volume policy
+    fn compact(&mut self) -> Result<()> {
+        let before = self.log.records();
+        let tables = &self.tables;
+        let policies = tables
+            .policies
+            .iter()
+            .map(|(volume, policy)| LogRecord::Policy {
+                volume: volume.clone(),
+                policy: policy.clone(),
+            });
+        let stripes = tables
+            .volumes
+            .values()
+            .flat_map(|table| table.stripes.values())
+            .map(|stripe| LogRecord::Put {
+                stripe: StoredStripe::from(stripe),
+            });
+        self.log.rewrite(policies.chain(stripes))?;
+
+        info!(
+            "Compacted stripe log {} from {} to {} records",
+            self.log.path().display(),
+            before,
+            self.log.records()
+        );
+        Ok(())
+    }
+}
+
+// =============================================================================
+// Log Stripe Repository
+// =============================================================================
+
+/// Stripe repository backed by a local append-only log.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct LogStripeRepository {
+    state: Arc<Mutex<LogState>>,
+}
+
+#[allow(dead_code)]
+impl LogStripeRepository {
+    /// Open (or create) a store in `dir`, replaying its log.
+    ///
+    /// Appends are fsynced unless `sync_writes` is false.
+    pub fn open(dir: impl AsRef<Path>, sync_writes: bool) -> Result<Self> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(LOG_FILE);
+
+        let mut tables = StripeTables::default();
+        let log = RecordLog::open(&path, sync_writes, |record| tables.apply(record))?;
+        let state = LogState { log, tables };
+
+        info!(
+            "Opened stripe log {} with {} stripes in {} volumes",
+            path.display(),
+            state.tables.live_stripes(),
+            state.tables.volumes.len()
+        );
+
+        Ok(Self {
+            state: Arc::new(Mutex::new(state)),
+        })
+    }
+
+    /// Run a log mutation on the blocking pool.
+    async fn write<T: Send + 'static>(
+        &self,
+        op: impl FnOnce(&mut LogState) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let state = self.state.clone();
+        tokio::task::spawn_blocking(move || op(&mut state.lock()))
+            .await
+            .map_err(|e| Error::Internal(format!("Stripe log task failed: {}", e)))?
+    }
+
+    /// Rewrite the log as one record per live stripe.
+    pub async fn compact(&self) -> Result<()> {
+        self.write(|state| state.compact()).await
+    }
+
+    /// Number of records in the log, including superseded ones.
+    pub fn log_records(&self) -> u64 {
+        self.state.lock().log.records()
+    }
+
+    /// Total number of stripes stored.
+    pub fn len(&self) -> usize {
+        self.state.lock().tables.live_stripes() as usize
+    }
+
+    /// IDs of all volumes with stored stripes.
+    pub fn volume_ids(&self) -> Vec<VolumeId> {
+        let mut volumes: Vec<VolumeId> = self
+            .state
+            .lock()
+            .tables
+            .volumes
+            .keys()
+            .cloned()
+            .map(VolumeId)
+            .collect();
+        volumes.sort_by(|a, b| a.0.cmp(&b.0));
+        volumes
+    }
+
+    /// Check if the store holds no stripes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Move the stripes of `volumes` from another repository into this one.
+    ///
+    /// Each volume is copied before it is deleted from `source`, and stripes
+    /// already held at the same or a newer generation are kept, so an
+    /// interrupted migration can be re-run. Run it before stripes are
+    /// written through either repository. Returns the stripes imported.
+    ///
+    /// The log must live on durable storage: after this returns, it is the
+    /// only copy of the migrated stripes.
+    pub async fn migrate_volumes(
+        &self,
+        source: &dyn StripeRepository,
+        volumes: &[VolumeId],
+    ) -> Result<u64> {
+        let mut imported = 0u64;
+
+        for volume_id in volumes {
+            for stripe in source.find_by_volume(volume_id).await? {
+                let current = self
+                    .state
+                    .lock()
+                    .tables
+                    .stripe(&volume_id.0, stripe.stripe_id.0)
+                    .map(|s| s.generation);
+                if current.is_some_and(|g| g >= stripe.generation) {
+                    continue;
+                }
+                self.save(&stripe).await?;
+                imported += 1;
+            }
+
+            let removed = source.delete_by_volume(volume_id).await?;
+            debug!(
+                volume = %volume_id.0,
+                removed,
+                "Migrated volume stripes into stripe log"
+            );
+        }
+
+        Ok(imported)
+    }
+}
+
+#[async_trait]
+impl StripeRepository for LogStripeRepository {
+    async fn save(&self, stripe: &StripeMetadata) -> Result<()> {
+        let record = LogRecord::Put {
+            stripe: StoredStripe::from(stripe),
+        };
+        self.write(move |state| state.commit(record)).await
+    }
+
+    async fn find_by_id(&self, stripe_id: &StripeId) -> Result<Option<StripeMetadata>> {
+        Ok(self
+            .state
+            .lock()
+            .tables
+            .volumes
+            .values()
+            .find_map(|v| v.stripes.get(&stripe_id.0).cloned()))
+    }
+
+    async fn find_by_lba(&self, volume_id: &VolumeId, lba: u64) -> Result<Option<StripeMetadata>> {
+        // The newest stripe wins where stripes overlap
+        Ok(self
+            .find_by_lba_range(volume_id, &LbaRange::new(lba, lba.saturating_add(1)))
+            .await?
+            .into_iter()
+            .max_by_key(|s| s.stripe_id.0))
+    }
+
+    async fn find_by_volume(&self, volume_id: &VolumeId) -> Result<Vec<StripeMetadata>> {
+        let mut stripes: Vec<StripeMetadata> = self
+            .state
+            .lock()
+            .tables
+            .volumes
+            .get(&volume_id.0)
+            .map(|v| v.stripes.values().cloned().collect())
+            .unwrap_or_default();
+        stripes.sort_by_key(|s| s.stripe_id.0);
+        Ok(stripes)
+    }
+
+    #[instrument(skip(self))]
+    async fn find_by_lba_range(
+        &self,
+        volume_id: &VolumeId,
+        range: &LbaRange,
+    ) -> Result<Vec<StripeMetadata>> {
+        Ok(self
+            .state
+            .lock()
+            .tables
+            .volumes
+            .get(&volume_id.0)
+            .map(|v| v.overlapping(range))
+            .unwrap_or_default())
+    }
+
+    async fn delete(&self, volume_id: &VolumeId, stripe_id: &StripeId) -> Result<()> {
+        let volume = volume_id.0.clone();
+        let stripe = stripe_id.0;
+        self.write(move |state| {
+            if state.tables.stripe(&volume, stripe).is_none() {
+                return Ok(());
+            }
+            state.commit(LogRecord::Delete { volume, stripe })
+        })
+        .await
+    }
+
+    async fn delete_by_volume(&self, volume_id: &VolumeId) -> Result<u64> {
+        let volume = volume_id.0.clone();
+        self.write(move |state| {
+            let count = state
+                .tables
+                .volumes
+                .get(&volume)
+                .map_or(0, |v| v.stripes.len() as u64);
+            if count > 0 || state.tables.policies.contains_key(&volume) {
+                state.commit(LogRecord::DeleteVolume { volume })?;
+            }
+            Ok(count)
+        })
+        .await
+    }
+
+    async fn update_generation(
+        &self,
+        volume_id: &VolumeId,
+        stripe_id: &StripeId,
+        new_generation: u64,
+    ) -> Result<bool> {
+        let volume = volume_id.0.clone();
+        let stripe = stripe_id.0;
+        self.write(move |state| {
+            // Check and append under one lock, so exactly one claimant wins
+            match state.tables.stripe(&volume, stripe) {
+                Some(s) if s.generation.saturating_add(1) == new_generation => {
+                    state.commit(LogRecord::Generation {
+                        volume,
+                        stripe,
+                        generation: new_generation,
+                    })?;
+                    Ok(true)
+                }
+                _ => Ok(false),
+            }
+        })
+        .await
+    }
+
+    async fn save_if_generation(&self, stripe: &StripeMetadata) -> Result<bool> {
+        let volume = stripe.volume_id.0.clone();
+        let id = stripe.stripe_id.0;
+        let generation = stripe.generation;
+        let record = LogRecord::Put {
+            stripe: StoredStripe::from(stripe),
+        };
+        self.write(move |state| {
+            // Check and append under one lock, so exactly one claimant wins
+            match state.tables.stripe(&volume, id) {
+                Some(s) if s.generation.saturating_add(1) == generation => {
+                    state.commit(record)?;
+                    Ok(true)
+                }
+                _ => Ok(false),
+            }
+        })
+        .await
+    }
+
+    async fn count_by_volume(&self, volume_id: &VolumeId) -> Result<u64> {
+        Ok(self
+            .state
+            .lock()
+            .tables
+            .volumes
+            .get(&volume_id.0)
+            .map_or(0, |v| v.stripes.len() as u64))
+    }
+
+    async fn save_volume_policy(&self, volume_id: &VolumeId, policy_ref: &str) -> Result<()> {
+        let record = LogRecord::Policy {
+            volume: volume_id.0.clone(),
+            policy: policy_ref.to_string(),
+        };
+        self.write(move |state| state.commit(record)).await
+    }
+
+    async fn find_volume_policy(&self, volume_id: &VolumeId) -> Result<Option<String>> {
+        Ok(self.state.lock().tables.policies.get(&volume_id.0).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::InMemoryStripeRepository;
+    use crate::ec::record_log::encode_record;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    fn shard(index: usize) -> ShardLocation {
+        ShardLocation {
+            shard_index: index,
+            device_id: format!("pool-{}", index),
+            node_id: "node-a".to_string(),
+            offset: index as u64 * 4096,
+            size: 4096,
+            is_data_shard: index < 4,
+            checksum: Some(format!("crc32:{:08x}", index)),
+            health: ShardHealthState::Healthy,
+            failed_pool: None,
+        }
+    }
+
+    fn stripe(volume: &str, id: u64, start: u64, end: u64) -> StripeMetadata {
+        StripeMetadata {
+            stripe_id: StripeId(id),
+            volume_id: VolumeId::new(volume),
+            policy_ref: "ec-4-2".to_string(),
+            lba_range: LbaRange::new(start, end),
+            shard_locations: (0..6).map(shard).collect(),
+            generation: 0,
+            created_at: Utc::now(),
+            is_compressed: false,
+            original_size: None,
+        }
+    }
+
+    fn ids(stripes: &[StripeMetadata]) -> Vec<u64> {
+        stripes.iter().map(|s| s.stripe_id.0).collect()
+    }
+
+    #[tokio::test]
+    async fn test_save_find_and_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let vol = VolumeId::new("vol-1");
+        {
+            let repo = LogStripeRepository::open(dir.path(), true).unwrap();
+            for i in 0..10 {
+                repo.save(&stripe("vol-1", i, i * 8, i * 8 + 8))
+                    .await
+                    .unwrap();
+            }
+            repo.save(&stripe("vol-2", 0, 0, 8)).await.unwrap();
+            repo.delete(&vol, &StripeId(3)).await.unwrap();
+        }
+
+        let repo = LogStripeRepository::open(dir.path(), true).unwrap();
+        assert_eq!(repo.count_by_volume(&vol).await.unwrap(), 9);
+        assert_eq!(repo.len(), 10);
+
+        let found = repo
+            .find_by_lba_range(&vol, &LbaRange::new(20, 41))
+            .await
+            .unwrap();
+        assert_eq!(ids(&found), vec![2, 4, 5]);
+        assert!(repo.find_by_lba(&vol, 26).await.unwrap().is_none());
+
+        let restored = repo.find_by_lba(&vol, 9).await.unwrap().unwrap();
+        assert_eq!(restored.stripe_id, StripeId(1));
+        assert_eq!(restored.shard_locations.len(), 6);
+        assert_eq!(restored.shard_locations[3].device_id, "pool-3");
+        assert_eq!(
+            restored.shard_locations[3].checksum.as_deref(),
+            Some("crc32:00000003")
+        );
+        assert!(!restored.shard_locations[4].is_data_shard);
+        assert_eq!(restored.policy_ref, "ec-4-2");
+    }
+
+    #[tokio::test]
+    async fn test_range_lookup_sees_long_stripes() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = LogStripeRepository::open(dir.path(), false).unwrap();
+        let vol = VolumeId::new("vol-1");
+
+        repo.save(&stripe("vol-1", 0, 0, 1000)).await.unwrap();
+        repo.save(&stripe("vol-1", 1, 500, 508)).await.unwrap();
+        repo.save(&stripe("vol-1", 2, 1000, 1008)).await.unwrap();
+
+        let found = repo
+            .find_by_lba_range(&vol, &LbaRange::new(900, 1001))
+            .await
+            .unwrap();
+        assert_eq!(ids(&found), vec![0, 2]);
+        // Overlapping stripes resolve to the newest
+        assert_eq!(
+            repo.find_by_lba(&vol, 503)
+                .await
+                .unwrap()
+                .unwrap()
+                .stripe_id,
+            StripeId(1)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_generation_and_shard_updates_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let vol = VolumeId::new("vol-1");
+        {
+            let repo = LogStripeRepository::open(dir.path(), true).unwrap();
+            repo.save(&stripe("vol-1", 7, 0, 8)).await.unwrap();
+
+            assert!(repo.update_generation(&vol, &StripeId(7), 1).await.unwrap());
+            // A second writer racing for generation 1 loses
+            assert!(!repo.update_generation(&vol, &StripeId(7), 1).await.unwrap());
+            assert!(!repo.update_generation(&vol, &StripeId(8), 1).await.unwrap());
+
+            let mut rebuilt = stripe("vol-1", 7, 0, 8);
+            rebuilt.generation = 2;
+            rebuilt.shard_locations[2].device_id = "pool-9".to_string();
+            rebuilt.shard_locations[5].health = ShardHealthState::Missing;
+            rebuilt.shard_locations[5].failed_pool = Some("pool-5".to_string());
+            assert!(repo.save_if_generation(&rebuilt).await.unwrap());
+            // Shards and generation move together, so a stale claim fails
+            assert!(!repo.save_if_generation(&rebuilt).await.unwrap());
+            rebuilt.stripe_id = StripeId(8);
+            assert!(!repo.save_if_generation(&rebuilt).await.unwrap());
+        }
+
+        let repo = LogStripeRepository::open(dir.path(), true).unwrap();
+        let stored = repo.find_by_lba(&vol, 0).await.unwrap().unwrap();
+        assert_eq!(stored.generation, 2);
+        assert_eq!(stored.shard_locations[2].device_id, "pool-9");
+        assert_eq!(stored.shard_locations[5].health, ShardHealthState::Missing);
+        assert_eq!(
+            stored.shard_locations[5].failed_pool.as_deref(),
+            Some("pool-5")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_torn_tail_is_truncated() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let repo = LogStripeRepository::open(dir.path(), true).unwrap();
+            repo.save(&stripe("vol-1", 0, 0, 8)).await.unwrap();
+            repo.save(&stripe("vol-1", 1, 8, 16)).await.unwrap();
+        }
+
+        // Simulate a crash in the middle of an append
+        let path = dir.path().join(LOG_FILE);
+        let good_len = std::fs::metadata(&path).unwrap().len();
+        let line = encode_record(&LogRecord::Put {
+            stripe: StoredStripe::from(&stripe("vol-1", 2, 16, 24)),
+        })
+        .unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&line.as_bytes()[..line.len() / 2]).unwrap();
+        drop(file);
+
+        let repo = LogStripeRepository::open(dir.path(), true).unwrap();
+        assert_eq!(repo.len(), 2);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), good_len);
+
+        // New appends land after the last good record
+        repo.save(&stripe("vol-1", 2, 16, 24)).await.unwrap();
+        drop(repo);
+        assert_eq!(
+            LogStripeRepository::open(dir.path(), true).unwrap().len(),
+            3
+        );
+    }
+
+    #[tokio::test]
+    async fn test_compact_drops_superseded_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let vol = VolumeId::new("vol-1");
+        let repo = LogStripeRepository::open(dir.path(), false).unwrap();
+
+        repo.save(&stripe("vol-1", 0, 0, 8)).await.unwrap();
+        repo.save(&stripe("vol-1", 1, 8, 16)).await.unwrap();
+        for generation in 1..=20 {
+            assert!(repo
+                .update_generation(&vol, &StripeId(0), generation)
+                .await
+                .unwrap());
+        }
+        repo.delete(&vol, &StripeId(1)).await.unwrap();
+        assert_eq!(repo.log_records(), 23);
+
+        repo.compact().await.unwrap();
+        assert_eq!(repo.log_records(), 1);
+
+        // The compacted log is still appendable and replays the same state
+        repo.save(&stripe("vol-1", 2, 16, 24)).await.unwrap();
+        drop(repo);
+        let repo = LogStripeRepository::open(dir.path(), false).unwrap();
+        assert_eq!(repo.log_records(), 2);
+        assert_eq!(ids(&repo.find_by_volume(&vol).await.unwrap()), vec![0, 2]);
+        assert_eq!(
+            repo.find_by_lba(&vol, 0).await.unwrap().unwrap().generation,
+            20
+        );
+    }
+
+    #[tokio::test]
+    async fn test_volume_policy_survives_reopen_and_compaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let vol = VolumeId::new("vol-1");
+        let repo = LogStripeRepository::open(dir.path(), true).unwrap();
+
+        repo.save(&stripe("vol-1", 0, 0, 8)).await.unwrap();
+        repo.save_volume_policy(&vol, "ec-6-3").await.unwrap();
+        repo.save_volume_policy(&VolumeId::new("vol-2"), "ec-6-3")
+            .await
+            .unwrap();
+        repo.compact().await.unwrap();
+        drop(repo);
+
+        let repo = LogStripeRepository::open(dir.path(), true).unwrap();
+        assert_eq!(
+            repo.find_volume_policy(&vol).await.unwrap().as_deref(),
+            Some("ec-6-3")
+        );
+
+        // Deleting a volume forgets its policy, even without stripes
+        repo.delete_by_volume(&VolumeId::new("vol-2"))
+            .await
+            .unwrap();
+        drop(repo);
+        let repo = LogStripeRepository::open(dir.path(), true).unwrap();
+        assert!(repo
+            .find_volume_policy(&VolumeId::new("vol-2"))
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_migrate_volumes_is_resumable() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = LogStripeRepository::open(dir.path(), false).unwrap();
+        let source = InMemoryStripeRepository::new();
+        let vol1 = VolumeId::new("vol-1");
+        let vol2 = VolumeId::new("vol-2");
+
+        for i in 0..3 {
+            source
+                .save(&stripe("vol-1", i, i * 8, i * 8 + 8))
+                .await
+                .unwrap();
+        }
+        source.save(&stripe("vol-2", 0, 0, 8)).await.unwrap();
+
+        // A previous, interrupted run already imported a newer copy
+        let mut newer = stripe("vol-1", 1, 8, 16);
+        newer.generation = 5;
+        repo.save(&newer).await.unwrap();
+
+        let imported = repo
+            .migrate_volumes(&source, &[vol1.clone(), vol2.clone()])
+            .await
+            .unwrap();
+
+        assert_eq!(imported, 3);
+        assert!(source.is_empty());
+        assert_eq!(repo.count_by_volume(&vol1).await.unwrap(), 3);
+        assert_eq!(repo.count_by_volume(&vol2).await.unwrap(), 1);
+        assert_eq!(
+            repo.find_by_lba(&vol1, 8)
+                .await
+                .unwrap()
+                .unwrap()
+                .generation,
+            5
+        );
+
+        // Re-running finds nothing left to move
+        assert_eq!(repo.migrate_volumes(&source, &[vol1]).await.unwrap(), 0);
+    }
+}
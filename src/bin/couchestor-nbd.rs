@@ -0,0 +1,196 @@
+//! Standalone NBD Server
+//!
+//! Serves EC volumes over NBD from local disks only: policies come from a
+//! YAML file, shards, stripe metadata, the dedup index and the write
+//! journal live in local directories, and no Kubernetes API, CRDs or
+//! Prometheus are needed. Meant for mounting and benchmarking an EC volume
+//! on a plain Linux box:
+//!
+//! ```text
+//! couchestor-nbd --policy-file deploy/examples/erasurecodingpolicy-examples.yaml \
+//!     --data-dir /srv/couchestor --nbd-exports vol-1:standard-ec:10G
+//! nbd-client -N vol-1 localhost 10809 /dev/nbd0 -b 4096
+//! ```
+
+use clap::Parser;
+use couchestor::adapters::LogStripeRepository;
+use couchestor::ec::{
+    DedupIndex, EcMetadataManager, LocalShardStore, NbdConfig, NbdExport, NbdServer,
+    ReconstructionConfig, ReconstructionEngine, ShardStore, StripeManager, StripeManagerConfig,
+    WriteJournal,
+};
+use couchestor::error::{Error, Result};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+use tracing_subscriber::EnvFilter;
+
+/// Serve EC volumes over NBD without Kubernetes
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// YAML file of ErasureCodingPolicy manifests (comma-separated for several)
+    #[arg(long, env = "POLICY_FILE", value_delimiter = ',', required = true)]
+    policy_file: Vec<PathBuf>,
+
+    /// EC volumes to export as <volume>:<policy>:<size> (comma-separated)
+    #[arg(long, env = "NBD_EXPORTS", value_delimiter = ',', required = true)]
+    nbd_exports: Vec<NbdExport>,
+
+    /// NBD server bind address
+    #[arg(long, env = "NBD_LISTEN_ADDR", default_value = "127.0.0.1:10809")]
+    nbd_listen_addr: String,
+
+    /// Let the unauthenticated NBD server listen on a non-loopback address
+    #[arg(long, env = "NBD_ALLOW_REMOTE")]
+    nbd_allow_remote: bool,
+
+    /// Directory holding shards/, stripes/, dedup/ and journal/ unless
+    /// their own paths are given
+    #[arg(long, env = "DATA_DIR", default_value = "/var/lib/couchestor")]
+    data_dir: PathBuf,
+
+    /// Root directory for EC shard storage; each subdirectory is a pool
+    /// [default: <data dir>/shards]
+    #[arg(long, env = "SHARD_STORE_PATH")]
+    shard_store_path: Option<PathBuf>,
+
+    /// Directory of the EC stripe metadata log [default: <data dir>/stripes]
+    #[arg(long, env = "STRIPE_STORE_PATH")]
+    stripe_store_path: Option<PathBuf>,
+
+    /// Directory of the EC dedup fingerprint index [default: <data dir>/dedup]
+    #[arg(long, env = "DEDUP_INDEX_PATH")]
+    dedup_index_path: Option<PathBuf>,
+
+    /// EC write journal replica directories (comma-separated, one per disk)
+    /// [default: <data dir>/journal]
+    #[arg(long, env = "JOURNAL_PATHS", value_delimiter = ',')]
+    journal_paths: Vec<PathBuf>,
+
+    /// Seconds an append to a full EC write journal waits for destaging before failing
+    #[arg(long, env = "JOURNAL_FULL_WAIT_SECONDS", default_value = "5")]
+    journal_full_wait_seconds: u64,
+
+    /// Name recorded as the node of every shard pool
+    #[arg(long, env = "NODE_NAME", default_value = "localhost")]
+    node_name: String,
+
+    /// Log level (trace, debug, info, warn, error)
+    #[arg(long, env = "LOG_LEVEL", default_value = "info")]
+    log_level: String,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let filter = EnvFilter::try_new(&args.log_level)
+        .map_err(|e| Error::Config(format!("Invalid log level {}: {}", args.log_level, e)))?;
+    tracing_subscriber::fmt().with_env_filter(filter).init();
+
+    info!("Starting standalone NBD server");
+
+    let metadata_manager = EcMetadataManager::standalone();
+    for path in &args.policy_file {
+        let names = metadata_manager.register_policy_file(path)?;
+        info!("  Policies from {}: {}", path.display(), names.join(", "));
+    }
+
+    let shard_store_path = args
+        .shard_store_path
+        .unwrap_or_else(|| args.data_dir.join("shards"));
+    let shard_store: Arc<dyn ShardStore> =
+        Arc::new(LocalShardStore::open(&shard_store_path, &args.node_name)?);
+    info!("  Shard store: {}", shard_store_path.display());
+
+    let stripe_store_path = args
+        .stripe_store_path
+        .unwrap_or_else(|| args.data_dir.join("stripes"));
+    let stripe_log = LogStripeRepository::open(&stripe_store_path, true)?;
+    info!("  Stripe store: {}", stripe_store_path.display());
+    for volume_id in stripe_log.volume_ids() {
+        if let Err(e) = metadata_manager
+            .sync_from_repository(&stripe_log, &volume_id.0)
+            .await
+        {
+            warn!("Failed to load EC stripes of volume {}: {}", volume_id, e);
+        }
+    }
+    let stripe_repository = Arc::new(stripe_log);
+
+    let dedup_index_path = args
+        .dedup_index_path
+        .unwrap_or_else(|| args.data_dir.join("dedup"));
+    let dedup_index = Arc::new(DedupIndex::open(&dedup_index_path, true)?);
+    info!("  Dedup index: {}", dedup_index_path.display());
+
+    let journal_paths = if args.journal_paths.is_empty() {
+        vec![args.data_dir.join("journal")]
+    } else {
+        args.journal_paths
+    };
+    // Replay writes that were journaled but not yet destaged
+    let write_journal = WriteJournal::open_with_full_wait(
+        journal_paths.clone(),
+        true,
+        Duration::from_secs(args.journal_full_wait_seconds),
+        metadata_manager.clone(),
+    )
+    .await?;
+    info!("  Write journal: {:?}", journal_paths);
+
+    let stripe_manager = StripeManager::with_dedup(
+        StripeManagerConfig::default(),
+        metadata_manager.clone(),
+        shard_store.clone(),
+        stripe_repository.clone(),
+        write_journal.clone(),
+        dedup_index.clone(),
+    );
+    let reconstruction_engine = ReconstructionEngine::new(
+        ReconstructionConfig::default(),
+        metadata_manager.clone(),
+        shard_store,
+        stripe_repository,
+    );
+
+    let nbd_config = NbdConfig {
+        listen_addr: args.nbd_listen_addr,
+        allow_remote: args.nbd_allow_remote,
+        ..Default::default()
+    };
+    let nbd_server = NbdServer::new(
+        nbd_config,
+        &args.nbd_exports,
+        &metadata_manager,
+        write_journal,
+        reconstruction_engine.clone(),
+        Some(dedup_index),
+    )?;
+
+    let stripe_manager_handle = stripe_manager.clone();
+    tokio::spawn(async move {
+        stripe_manager_handle.run().await;
+    });
+    let reconstruction_handle = reconstruction_engine.clone();
+    tokio::spawn(async move {
+        reconstruction_handle.run().await;
+    });
+
+    tokio::select! {
+        result = nbd_server.run() => {
+            if let Err(e) = &result {
+                error!("NBD server error: {}", e);
+            }
+            result
+        }
+        _ = tokio::signal::ctrl_c() => {
+            info!("Shutting down");
+            stripe_manager.shutdown().await;
+            reconstruction_engine.shutdown();
+            Ok(())
+        }
+    }
+}
@@ -2,7 +2,12 @@
 //!
 //! Reconciliation logic for ErasureCodingPolicy resources.
 
-use crate::crd::{EcPolicyPhase, ErasureCodingPolicy, ErasureCodingPolicyStatus};
+use crate::crd::{
+    DiskPool, EcPolicyPhase, ErasureCodingPolicy, ErasureCodingPolicyStatus, StoragePolicy,
+};
+use crate::ec::dedup::{DedupIndex, DedupStats};
+use crate::ec::durability::{self, DurabilityConfig, DurabilityEstimate};
+use crate::ec::ReconstructionEngine;
 use crate::error::{Error, Result};
 
 use chrono::Utc;
@@ -11,20 +16,78 @@ use kube::api::{Api, ListParams, Patch, PatchParams};
 use kube::runtime::controller::{Action, Controller};
 use kube::runtime::watcher::Config;
 use kube::{Client, ResourceExt};
+use once_cell::sync::Lazy;
+use prometheus::GaugeVec;
 use std::sync::Arc;
 use std::time::Duration;
 use tracing::{debug, error, info, instrument, warn};
 
+static POLICY_MTTDL_HOURS: Lazy<GaugeVec> = Lazy::new(|| {
+    prometheus::register_gauge_vec!(
+        "storage_operator_ec_policy_mttdl_hours",
+        "Estimated mean time to data loss of an EC policy in hours",
+        &["policy"]
+    )
+    .expect("failed to register EC policy MTTDL gauge")
+});
+
+static POLICY_ANNUAL_LOSS_PROBABILITY: Lazy<GaugeVec> = Lazy::new(|| {
+    prometheus::register_gauge_vec!(
+        "storage_operator_ec_policy_annual_data_loss_probability",
+        "Estimated probability that an EC policy loses data within a year",
+        &["policy"]
+    )
+    .expect("failed to register EC policy data loss gauge")
+});
+
+static POLICY_FAILURE_DOMAINS: Lazy<GaugeVec> = Lazy::new(|| {
+    prometheus::register_gauge_vec!(
+        "storage_operator_ec_policy_failure_domains",
+        "Failure domains available to the shards of an EC policy",
+        &["policy"]
+    )
+    .expect("failed to register EC policy failure domain gauge")
+});
+
+static POLICY_DEDUP_RATIO: Lazy<GaugeVec> = Lazy::new(|| {
+    prometheus::register_gauge_vec!(
+        "storage_operator_ec_policy_dedup_ratio",
+        "Logical bytes per stored byte of deduplicated EC volumes",
+        &["policy"]
+    )
+    .expect("failed to register EC policy dedup ratio gauge")
+});
+
 /// Context for the EC policy controller
 pub struct EcPolicyContext {
     /// Kubernetes client
     pub client: Client,
+
+    /// Durability model parameters
+    pub durability: DurabilityConfig,
+
+    /// Source of observed rebuild throughput
+    pub reconstruction_engine: Option<Arc<ReconstructionEngine>>,
+
+    /// Source of dedup savings
+    pub dedup: Option<Arc<DedupIndex>>,
 }
 
 impl EcPolicyContext {
-    /// Create a new EC policy context
-    pub fn new(client: Client) -> Arc<Self> {
-        Arc::new(Self { client })
+    /// Create a context that reports durability using observed rebuild
+    /// rates, and dedup savings from `dedup`
+    pub fn with_durability(
+        client: Client,
+        durability: DurabilityConfig,
+        reconstruction_engine: Arc<ReconstructionEngine>,
+        dedup: Arc<DedupIndex>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            client,
+            durability,
+            reconstruction_engine: Some(reconstruction_engine),
+            dedup: Some(dedup),
+        })
     }
 }
 
@@ -71,7 +134,7 @@ async fn reconcile(
     // Validate the policy configuration
     let validation_result = policy.validate();
 
-    let (phase, message) = match validation_result {
+    let (phase, mut message) = match validation_result {
         Ok(()) => {
             debug!("ErasureCodingPolicy {} is valid", name);
 
@@ -123,6 +186,45 @@ async fn reconcile(
             rebuilding: 0,
         });
 
+    // Estimate durability over the failure domains currently available
+    let estimate = match estimate_durability(&ctx, &policy).await {
+        Ok(estimate) => estimate,
+        Err(e) => {
+            warn!("Failed to estimate durability of {}: {}", name, e);
+            None
+        }
+    };
+    publish_durability_metrics(&name, estimate.as_ref());
+
+    let target = ctx.durability.target_annual_loss_probability;
+    if let Some(warning) = estimate
+        .as_ref()
+        .and_then(|e| durability_warning(&policy, e, target))
+    {
+        warn!("ErasureCodingPolicy {}: {}", name, warning);
+        message = Some(match message {
+            Some(m) => format!("{}; {}", m, warning),
+            None => warning,
+        });
+    }
+
+    // Dedup savings of the policy's volumes
+    let dedup_stats = ctx.dedup.as_ref().and_then(|dedup| dedup.stats(&name));
+    if let (Some(config), Some(stats)) = (&policy.spec.dedup, &dedup_stats) {
+        debug!(
+            "Policy {} dedup ({} chunks of {} bytes): {} logical bytes in {} stored",
+            name, config.chunking, config.chunk_size_bytes, stats.logical_bytes, stats.stored_bytes
+        );
+    }
+    publish_dedup_metrics(&name, dedup_stats.as_ref());
+
+    // Rebuild rate limits may have changed on this or any other policy
+    if let Some(engine) = &ctx.reconstruction_engine {
+        if let Err(e) = apply_rebuild_rate(&ctx.client, engine).await {
+            warn!("Failed to apply EC rebuild rate: {}", e);
+        }
+    }
+
     // Update status
     let status = ErasureCodingPolicyStatus {
         phase,
@@ -132,6 +234,21 @@ async fn reconcile(
         degraded_stripes: stripe_stats.degraded,
         rebuilding_stripes: stripe_stats.rebuilding,
         storage_efficiency: efficiency,
+        failure_domains: estimate.as_ref().map_or(0, |e| e.failure_domains as u32),
+        mttdl_hours: estimate
+            .as_ref()
+            .map(|e| e.mttdl_hours)
+            .filter(|v| v.is_finite()),
+        annual_data_loss_probability: estimate.as_ref().map(|e| e.annual_loss_probability),
+        durability_nines: estimate
+            .as_ref()
+            .map(|e| e.nines())
+            .filter(|v| v.is_finite()),
+        dedup_enabled: policy.spec.dedup.is_some(),
+        dedup_chunk_size_bytes: policy.spec.dedup.as_ref().map(|d| d.chunk_size_bytes),
+        dedup_ratio: dedup_stats.as_ref().and_then(|s| s.ratio()),
+        dedup_logical_bytes: dedup_stats.as_ref().map_or(0, |s| s.logical_bytes),
+        dedup_stored_bytes: dedup_stats.as_ref().map_or(0, |s| s.stored_bytes),
         last_validation_time: Some(Utc::now()),
         message,
     };
@@ -151,6 +268,133 @@ async fn reconcile(
     Ok(Action::requeue(Duration::from_secs(300)))
 }
 
+/// Estimate a policy's durability from the cold pools it can place shards on
+///
+/// Uses the cold pool selectors of the StoragePolicies referencing the
+/// policy, or every online pool if none does.
+async fn estimate_durability(
+    ctx: &EcPolicyContext,
+    policy: &ErasureCodingPolicy,
+) -> Result<Option<DurabilityEstimate>> {
+    let name = policy.name_any();
+
+    let storage_policies: Api<StoragePolicy> = Api::all(ctx.client.clone());
+    let storage_policies = storage_policies.list(&ListParams::default()).await?;
+    let selectors: Vec<_> = storage_policies
+        .items
+        .iter()
+        .filter(|p| p.ec_policy_ref() == Some(name.as_str()))
+        .filter_map(|p| p.cold_pool_selector())
+        .collect();
+
+    let pools: Api<DiskPool> = Api::all(ctx.client.clone());
+    let pools = pools.list(&ListParams::default()).await?;
+    let domains = durability::failure_domains(&pools.items, &selectors);
+
+    let throughput = ctx
+        .reconstruction_engine
+        .as_ref()
+        .and_then(|engine| engine.observed_rebuild_throughput());
+
+    let estimate = durability::estimate(
+        &ctx.durability,
+        policy.spec.data_shards as usize,
+        policy.spec.parity_shards as usize,
+        &domains,
+        throughput,
+    );
+
+    if let Some(estimate) = &estimate {
+        debug!(
+            "Policy {} durability: {} domains, tolerates {}, repair {:.2}h, MTTDL {:.3e}h, {:.1} nines",
+            name,
+            estimate.failure_domains,
+            estimate.tolerated_domain_failures,
+            estimate.repair_hours,
+            estimate.mttdl_hours,
+            estimate.nines()
+        );
+    }
+
+    Ok(estimate)
+}
+
+/// Warning for a policy whose estimated annual loss probability exceeds
+/// `target`, or None if it meets it
+fn durability_warning(
+    policy: &ErasureCodingPolicy,
+    estimate: &DurabilityEstimate,
+    target: f64,
+) -> Option<String> {
+    if estimate.meets_target(target) {
+        return None;
+    }
+    Some(format!(
+        "Annual data loss probability {:.2e} exceeds target {:.2e} ({} failure domains, {}+{})",
+        estimate.annual_loss_probability,
+        target,
+        estimate.failure_domains,
+        policy.spec.data_shards,
+        policy.spec.parity_shards
+    ))
+}
+
+/// Export a policy's durability estimate as Prometheus gauges
+fn publish_durability_metrics(policy_name: &str, estimate: Option<&DurabilityEstimate>) {
+    let labels = [policy_name];
+    match estimate {
+        Some(estimate) => {
+            POLICY_MTTDL_HOURS
+                .with_label_values(&labels)
+                .set(estimate.mttdl_hours);
+            POLICY_ANNUAL_LOSS_PROBABILITY
+                .with_label_values(&labels)
+                .set(estimate.annual_loss_probability);
+            POLICY_FAILURE_DOMAINS
+                .with_label_values(&labels)
+                .set(estimate.failure_domains as f64);
+        }
+        None => {
+            let _ = POLICY_MTTDL_HOURS.remove_label_values(&labels);
+            let _ = POLICY_ANNUAL_LOSS_PROBABILITY.remove_label_values(&labels);
+            POLICY_FAILURE_DOMAINS.with_label_values(&labels).set(0.0);
+        }
+    }
+}
+
+/// Export a policy's dedup ratio as a Prometheus gauge
+fn publish_dedup_metrics(policy_name: &str, stats: Option<&DedupStats>) {
+    let labels = [policy_name];
+    match stats.and_then(|s| s.ratio()) {
+        Some(ratio) => POLICY_DEDUP_RATIO.with_label_values(&labels).set(ratio),
+        None => {
+            let _ = POLICY_DEDUP_RATIO.remove_label_values(&labels);
+        }
+    }
+}
+
+/// Set the engine's rebuild rate from the policies' limits
+async fn apply_rebuild_rate(client: &Client, engine: &ReconstructionEngine) -> Result<()> {
+    let policies: Api<ErasureCodingPolicy> = Api::all(client.clone());
+    let policies = policies.list(&ListParams::default()).await?;
+
+    let rate = effective_rebuild_rate(&policies.items, engine.config().rebuild_rate_bytes_per_sec);
+    if rate != engine.rebuild_rate() {
+        engine.set_rebuild_rate(rate);
+    }
+    Ok(())
+}
+
+/// Lowest rebuild rate limit set by any policy, or `default` if none sets one
+fn effective_rebuild_rate(policies: &[ErasureCodingPolicy], default: u64) -> u64 {
+    policies
+        .iter()
+        .filter_map(|p| p.spec.rebuild_rate_bytes_per_sec)
+        .filter(|&rate| rate > 0)
+        .min()
+        .unwrap_or(default)
+}
+
 /// Count volumes using a specific EC policy
 async fn count_active_volumes(client: &Client, policy_name: &str) -> Result<u32> {
     use crate::crd::StoragePolicy;
@@ -267,10 +511,61 @@ fn error_policy(
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
+    #[allow(clippy::assertions_on_constants)]
     fn test_ec_policy_context_creation() {
         // This test would require a mock client
         // For now, just verify the struct exists
         assert!(true);
     }
+
+    #[test]
+    fn test_durability_warning() {
+        let mut spec: crate::crd::ErasureCodingPolicySpec =
+            serde_json::from_value(serde_json::json!({})).unwrap();
+        spec.data_shards = 4;
+        spec.parity_shards = 2;
+        let policy = ErasureCodingPolicy::new("ec-4-2", spec);
+        let estimate = DurabilityEstimate {
+            failure_domains: 3,
+            tolerated_domain_failures: 1,
+            repair_hours: 2.0,
+            mttdl_hours: 1e6,
+            annual_loss_probability: 1e-3,
+        };
+
+        assert!(durability_warning(&policy, &estimate, 1e-3).is_none());
+        let warning = durability_warning(&policy, &estimate, 1e-9).unwrap();
+        assert!(warning.contains("exceeds target"), "{}", warning);
+        assert!(warning.contains("3 failure domains, 4+2"), "{}", warning);
+    }
+
+    #[test]
+    fn test_effective_rebuild_rate() {
+        let policy = |name: &str, rate: Option<u64>| {
+            let mut spec: crate::crd::ErasureCodingPolicySpec =
+                serde_json::from_value(serde_json::json!({})).unwrap();
+            spec.rebuild_rate_bytes_per_sec = rate;
+            ErasureCodingPolicy::new(name, spec)
+        };
+
+        assert_eq!(effective_rebuild_rate(&[], 100), 100);
+        assert_eq!(
+            effective_rebuild_rate(&[policy("a", None), policy("b", Some(0))], 100),
+            100
+        );
+        assert_eq!(
+            effective_rebuild_rate(
+                &[
+                    policy("a", Some(500)),
+                    policy("b", None),
+                    policy("c", Some(200))
+                ],
+                100
+            ),
+            200
+        );
+    }
 }
@@ -279,7 +279,45 @@ async fn reconcile(
                 if use_ec {
                     // Migrate to EC storage
                     if let Some(ec_policy_ref) = policy.ec_policy_ref() {
-                        if !ctx.migrator.is_migrating(&volume_id) {
+                        let current_ec_policy = ctx.migrator.ec_volume_policy(&volume_id);
+
+                        if current_ec_policy
+                            .as_deref()
+                            .is_some_and(|current| current != ec_policy_ref)
+                            && !ctx.migrator.is_migrating(&volume_id)
+                        {
+                            // Already erasure-coded: re-stripe onto the new policy
+                            let _permit = ctx.migration_semaphore.acquire().await;
+                            info!(
+                                "Re-striping {} onto EC policy {} (currently {:?})",
+                                volume_id, ec_policy_ref, current_ec_policy
+                            );
+
+                            if !policy.is_dry_run() {
+                                match ctx.migrator.rebalance_ec(&volume_id, ec_policy_ref).await {
+                                    Ok(result) => {
+                                        info!("EC rebalance completed: {:?}", result.state);
+                                        record_migration_history(
+                                            &ctx.client,
+                                            &name,
+                                            &result,
+                                            iops.into(),
+                                            "cold-ec",
+                                            "cold-ec",
+                                        )
+                                        .await;
+                                    }
+                                    Err(e) => error!("EC rebalance failed: {}", e),
+                                }
+                            } else {
+                                info!(
+                                    "[DRY-RUN] Would re-stripe {} onto EC policy {}",
+                                    volume_id, ec_policy_ref
+                                );
+                            }
+                        } else if current_ec_policy.is_none()
+                            && !ctx.migrator.is_migrating(&volume_id)
+                        {
                             let _permit = ctx.migration_semaphore.acquire().await;
                             info!(
                                 "Migrating {} to EC cold tier (policy: {}, IOPS: {}, size: {} bytes)",
@@ -529,7 +567,7 @@ async fn record_migration_history(
 
     // Fetch current policy to get existing status
     let policies: Api<StoragePolicy> = Api::all(client.clone());
-    if let Ok(mut policy) = policies.get(policy_name).await {
+    if let Ok(policy) = policies.get(policy_name).await {
         // Add to history and update counters
         let mut status = policy.status.clone().unwrap_or_default();
         status.add_migration_history(entry);
@@ -586,9 +624,7 @@ fn error_policy(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use k8s_openapi::api::core::v1::{
-        PersistentVolume, PersistentVolumeSpec, PersistentVolumeStatus,
-    };
+    use k8s_openapi::api::core::v1::{PersistentVolume, PersistentVolumeSpec};
     use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
     use kube::api::ObjectMeta;
     use std::collections::BTreeMap;
@@ -1183,7 +1219,6 @@ mod tests {
     async fn test_reconcile_with_prometheus_unavailable() {
         // Test that reconciliation continues gracefully when Prometheus is down
         use crate::metrics::{MetricsConfig, MetricsWatcher};
-        use std::sync::Arc;
 
         // Create metrics watcher pointing to non-existent Prometheus
         let config = MetricsConfig {
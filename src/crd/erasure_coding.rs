@@ -75,6 +75,16 @@ pub struct ErasureCodingPolicySpec {
     /// Interval for background scrubbing. Uses Go-style duration format.
     #[serde(default = "default_scrub_interval")]
     pub scrub_interval: String,
+
+    /// Block-level deduplication before EC encoding. Disabled if not set.
+    #[serde(default)]
+    pub dedup: Option<DedupConfig>,
+
+    /// Rebuild I/O rate limit in bytes per second. Rebuilds share one limit
+    /// per operator: the lowest rate set by any policy applies, and policies
+    /// without one (or with 0) fall back to the operator's default.
+    #[serde(default)]
+    pub rebuild_rate_bytes_per_sec: Option<u64>,
 }
 
 /// Erasure coding algorithms
@@ -131,6 +141,52 @@ impl Default for JournalConfig {
     }
 }
 
+/// Deduplication configuration for EC volumes
+///
+/// Destaged data is split into chunks and only chunks not already stored
+/// under the policy are erasure coded; duplicate chunks are shared.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DedupConfig {
+    /// How data is split into chunks.
+    #[serde(default)]
+    pub chunking: DedupChunking,
+
+    /// Chunk size in bytes (the average size for content-defined chunking).
+    /// Must be a multiple of 512 between 4KB and the stripe size.
+    #[serde(default = "default_dedup_chunk_size")]
+    pub chunk_size_bytes: u64,
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        Self {
+            chunking: DedupChunking::default(),
+            chunk_size_bytes: default_dedup_chunk_size(),
+        }
+    }
+}
+
+/// Chunking strategies for deduplication
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+pub enum DedupChunking {
+    /// Fixed-size chunks aligned to the volume's LBAs
+    #[default]
+    Fixed,
+    /// Content-defined chunk boundaries, so inserted data does not shift
+    /// every following chunk
+    ContentDefined,
+}
+
+impl std::fmt::Display for DedupChunking {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DedupChunking::Fixed => write!(f, "Fixed"),
+            DedupChunking::ContentDefined => write!(f, "ContentDefined"),
+        }
+    }
+}
+
 /// Status of an ErasureCodingPolicy
 #[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
@@ -163,6 +219,42 @@ pub struct ErasureCodingPolicyStatus {
     #[serde(default)]
     pub storage_efficiency: f64,
 
+    /// Distinct failure domains (nodes) available to the policy's shards
+    #[serde(default)]
+    pub failure_domains: u32,
+
+    /// Estimated mean time to data loss in hours
+    #[serde(default)]
+    pub mttdl_hours: Option<f64>,
+
+    /// Estimated probability of losing data within a year
+    #[serde(default)]
+    pub annual_data_loss_probability: Option<f64>,
+
+    /// Durability as a number of nines (e.g. 11 for 99.999999999%)
+    #[serde(default)]
+    pub durability_nines: Option<f64>,
+
+    /// Whether deduplication is enabled
+    #[serde(default)]
+    pub dedup_enabled: bool,
+
+    /// Deduplication chunk size in bytes
+    #[serde(default)]
+    pub dedup_chunk_size_bytes: Option<u64>,
+
+    /// Logical bytes referenced by volumes per byte of unique chunk data
+    #[serde(default)]
+    pub dedup_ratio: Option<f64>,
+
+    /// Logical bytes mapped by deduplicated volumes
+    #[serde(default)]
+    pub dedup_logical_bytes: u64,
+
+    /// Unique chunk bytes stored in EC stripes
+    #[serde(default)]
+    pub dedup_stored_bytes: u64,
+
     /// Last time the policy was validated
     #[serde(default)]
     pub last_validation_time: Option<DateTime<Utc>>,
@@ -362,6 +454,10 @@ pub struct ShardHealth {
     /// Error message if unhealthy
     #[serde(default)]
     pub error: Option<String>,
+
+    /// DiskPool whose failure marked the shard missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failed_pool: Option<String>,
 }
 
 /// State of a stripe
@@ -452,6 +548,10 @@ fn default_scrub_interval() -> String {
     "7d".to_string()
 }
 
+fn default_dedup_chunk_size() -> u64 {
+    65536 // 64KB
+}
+
 // =============================================================================
 // Implementations
 // =============================================================================
@@ -540,6 +640,20 @@ impl ErasureCodingPolicy {
             }
         }
 
+        // Validate dedup config if present
+        if let Some(dedup) = &self.spec.dedup {
+            let chunk_size = dedup.chunk_size_bytes;
+            if chunk_size < 4096 || !chunk_size.is_multiple_of(512) {
+                return Err(
+                    "dedup chunk_size_bytes must be a multiple of 512 and at least 4096"
+                        .to_string(),
+                );
+            }
+            if chunk_size > self.spec.stripe_size_bytes {
+                return Err("dedup chunk_size_bytes must not exceed stripe_size_bytes".to_string());
+            }
+        }
+
         Ok(())
     }
 }
@@ -640,12 +754,42 @@ mod tests {
             min_healthy_shards: None,
             scrubbing_enabled: false,
             scrub_interval: "7d".to_string(),
+            dedup: None,
+            rebuild_rate_bytes_per_sec: None,
         };
 
         let efficiency = spec.data_shards as f64 / (spec.data_shards + spec.parity_shards) as f64;
         assert!((efficiency - 0.6666).abs() < 0.01);
     }
 
+    #[test]
+    fn test_dedup_config_parsing_and_validation() {
+        let spec: ErasureCodingPolicySpec =
+            serde_json::from_str(r#"{"dedup": {"chunking": "ContentDefined"}}"#).unwrap();
+        let dedup = spec.dedup.clone().unwrap();
+        assert_eq!(dedup.chunking, DedupChunking::ContentDefined);
+        assert_eq!(dedup.chunk_size_bytes, 65536);
+        assert!(ErasureCodingPolicy::new("dedup", spec.clone())
+            .validate()
+            .is_ok());
+
+        let with_chunk_size = |chunk_size_bytes: u64| {
+            let mut spec = spec.clone();
+            spec.dedup = Some(DedupConfig {
+                chunk_size_bytes,
+                ..Default::default()
+            });
+            ErasureCodingPolicy::new("dedup", spec).validate()
+        };
+        assert!(with_chunk_size(4096).is_ok());
+        assert!(with_chunk_size(5000).is_err());
+        assert!(with_chunk_size(2048).is_err());
+        assert!(with_chunk_size(2 * 1048576).is_err());
+
+        let spec: ErasureCodingPolicySpec = serde_json::from_str("{}").unwrap();
+        assert!(spec.dedup.is_none());
+    }
+
     #[test]
     fn test_storage_overhead_4_2() {
         let k = 4.0_f64;
@@ -22,7 +22,7 @@ pub use storage_policy::{
 
 #[allow(unused_imports)]
 pub use erasure_coding::{
-    ECStripe, ECStripeSpec, ECStripeStatus, EcAlgorithm, EcPolicyPhase, ErasureCodingPolicy,
-    ErasureCodingPolicySpec, ErasureCodingPolicyStatus, JournalConfig, LbaRange, ShardHealth,
-    ShardLocation, ShardState, StripeState,
+    DedupChunking, DedupConfig, ECStripe, ECStripeSpec, ECStripeStatus, EcAlgorithm, EcPolicyPhase,
+    ErasureCodingPolicy, ErasureCodingPolicySpec, ErasureCodingPolicyStatus, JournalConfig,
+    LbaRange, ShardHealth, ShardLocation, ShardState, StripeState,
 };
@@ -306,6 +306,7 @@ pub struct MigrationHistoryEntry {
 
 impl MigrationHistoryEntry {
     /// Create a new migration history entry
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         volume_name: String,
         timestamp: DateTime<Utc>,
@@ -49,6 +49,7 @@ pub use ports::{
     MetricsProvider,
     ReplicaInfo,
     ReplicaState,
+    ShardHealthState,
     ShardLocation,
     StorageTier,
     StripeId,
@@ -363,6 +363,8 @@ pub trait EcCodec: Send + Sync {
 pub struct StripeMetadata {
     pub stripe_id: StripeId,
     pub volume_id: VolumeId,
+    /// EC policy the stripe was encoded with (empty = repository default).
+    pub policy_ref: String,
     pub lba_range: LbaRange,
     pub shard_locations: Vec<ShardLocation>,
     pub generation: u64,
@@ -376,8 +378,24 @@ pub struct StripeMetadata {
 pub struct ShardLocation {
     pub shard_index: usize,
     pub device_id: String,
+    pub node_id: String,
     pub offset: u64,
     pub size: u64,
+    pub is_data_shard: bool,
+    pub checksum: Option<String>,
+    pub health: ShardHealthState,
+    /// DiskPool whose failure marked the shard missing, if that is why.
+    pub failed_pool: Option<String>,
+}
+
+/// Health of a single shard (value object).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ShardHealthState {
+    #[default]
+    Healthy,
+    Missing,
+    Corrupted,
+    Rebuilding,
 }
 
 /// Port for stripe metadata persistence.
@@ -405,17 +423,42 @@ pub trait StripeRepository: Send + Sync {
         range: &LbaRange,
     ) -> Result<Vec<StripeMetadata>>;
 
-    /// Delete stripe by ID.
-    async fn delete(&self, stripe_id: &StripeId) -> Result<()>;
+    /// Delete a stripe of a volume.
+    ///
+    /// Stripe IDs are only unique within a volume.
+    async fn delete(&self, volume_id: &VolumeId, stripe_id: &StripeId) -> Result<()>;
 
     /// Delete all stripes for a volume.
     async fn delete_by_volume(&self, volume_id: &VolumeId) -> Result<u64>;
 
     /// Update stripe generation (for optimistic locking).
-    async fn update_generation(&self, stripe_id: &StripeId, new_generation: u64) -> Result<bool>;
+    ///
+    /// Succeeds only if the stored generation is `new_generation - 1`.
+    /// Returns `false` if the stripe is missing or another writer bumped
+    /// the generation first.
+    async fn update_generation(
+        &self,
+        volume_id: &VolumeId,
+        stripe_id: &StripeId,
+        new_generation: u64,
+    ) -> Result<bool>;
+
+    /// Replace a stripe, including its shard locations and shard health,
+    /// and bump its generation in one write.
+    ///
+    /// Succeeds only if the stored generation is `stripe.generation - 1`.
+    /// Returns `false` if the stripe is missing or another writer bumped
+    /// the generation first; nothing is written then.
+    async fn save_if_generation(&self, stripe: &StripeMetadata) -> Result<bool>;
 
     /// Count stripes for a volume.
     async fn count_by_volume(&self, volume_id: &VolumeId) -> Result<u64>;
+
+    /// Record the policy new stripes of a volume are encoded with.
+    async fn save_volume_policy(&self, volume_id: &VolumeId, policy_ref: &str) -> Result<()>;
+
+    /// Find the policy recorded for a volume.
+    async fn find_volume_policy(&self, volume_id: &VolumeId) -> Result<Option<String>>;
 }
 
 // =============================================================================
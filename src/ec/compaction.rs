@@ -0,0 +1,896 @@
+//! Stripe Compaction - Garbage Collection for Overwritten EC Data
+//!
+//! Destaging an LBA range that is already erasure-coded adds a new stripe
+//! and leaves the older one in place. The compactor reclaims that space:
+//! stripes fully shadowed by newer stripes are deleted, and stripes whose
+//! live fraction falls below a threshold are repacked into fresh stripes.
+//!
+//! In dedup chunk volumes, the LBAs of released chunks count as dead too.
+
+use crate::crd::{ErasureCodingPolicy, LbaRange, StripeState};
+use crate::domain::ports::{StripeId, StripeRepository, VolumeId};
+use crate::ec::dedup::DedupIndex;
+use crate::ec::encoder::{EcDecoder, EcEncoder};
+use crate::ec::metadata::{
+    to_domain_stripe, EcMetadataManager, StripeMetadata, StripeStatus, VolumeEcState,
+};
+use crate::ec::shard_store::{discard_shards, place_shards, read_stripe_data, ShardStore};
+use crate::ec::stripe_manager::LBA_SIZE;
+use crate::error::{Error, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::interval;
+use tracing::{debug, error, info, instrument, warn};
+
+pub(crate) type SharedVolumeState = Arc<parking_lot::RwLock<VolumeEcState>>;
+
+// =============================================================================
+// Configuration
+// =============================================================================
+
+/// Configuration for the stripe compactor
+#[derive(Debug, Clone)]
+pub struct CompactionConfig {
+    /// Interval between compaction passes
+    pub interval: Duration,
+
+    /// Repack stripes whose live fraction is below this ratio (0.0 disables repacking)
+    pub live_ratio_threshold: f64,
+
+    /// Maximum stripes repacked per volume in one pass
+    pub max_repack_stripes: usize,
+
+    /// Whether running in dry-run mode
+    pub dry_run: bool,
+}
+
+impl Default for CompactionConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(600),
+            live_ratio_threshold: 0.5,
+            max_repack_stripes: 32,
+            dry_run: false,
+        }
+    }
+}
+
+// =============================================================================
+// Compaction Result
+// =============================================================================
+
+/// Result of compacting one volume
+#[derive(Debug, Default)]
+pub struct CompactionResult {
+    /// Volume ID
+    pub volume_id: String,
+
+    /// Fully shadowed stripes that were deleted
+    pub stripes_deleted: Vec<u64>,
+
+    /// Partially-live stripes whose live data was moved to new stripes
+    pub stripes_repacked: Vec<u64>,
+
+    /// Stripes created by repacking
+    pub stripes_created: Vec<u64>,
+
+    /// Shard bytes released on the cold pools
+    pub bytes_reclaimed: u64,
+
+    /// Any errors encountered
+    pub errors: Vec<String>,
+}
+
+/// A live LBA range and its current contents
+pub(crate) struct LiveSegment {
+    pub(crate) range: LbaRange,
+    pub(crate) data: Vec<u8>,
+}
+
+// =============================================================================
+// Stripe Compactor
+// =============================================================================
+
+/// Reclaims cold-tier space held by overwritten stripes
+pub struct StripeCompactor {
+    /// Configuration
+    config: CompactionConfig,
+
+    /// Metadata manager
+    metadata_manager: Arc<EcMetadataManager>,
+
+    /// Shard I/O backend
+    shard_store: Arc<dyn ShardStore>,
+
+    /// Persistent stripe metadata
+    stripe_repository: Arc<dyn StripeRepository>,
+
+    /// Dedup index whose released chunks are dead data
+    dedup: Option<Arc<DedupIndex>>,
+
+    /// Shutdown signal
+    shutdown: AtomicBool,
+}
+
+impl StripeCompactor {
+    /// Create a new stripe compactor
+    pub fn new(
+        config: CompactionConfig,
+        metadata_manager: Arc<EcMetadataManager>,
+        shard_store: Arc<dyn ShardStore>,
+        stripe_repository: Arc<dyn StripeRepository>,
+    ) -> Arc<Self> {
+        Self::build(
+            config,
+            metadata_manager,
+            shard_store,
+            stripe_repository,
+            None,
+        )
+    }
+
+    /// Create a stripe compactor that also reclaims released dedup chunks
+    pub fn with_dedup(
+        config: CompactionConfig,
+        metadata_manager: Arc<EcMetadataManager>,
+        shard_store: Arc<dyn ShardStore>,
+        stripe_repository: Arc<dyn StripeRepository>,
+        dedup: Arc<DedupIndex>,
+    ) -> Arc<Self> {
+        Self::build(
+            config,
+            metadata_manager,
+            shard_store,
+            stripe_repository,
+            Some(dedup),
+        )
+    }
+
+    fn build(
+        config: CompactionConfig,
+        metadata_manager: Arc<EcMetadataManager>,
+        shard_store: Arc<dyn ShardStore>,
+        stripe_repository: Arc<dyn StripeRepository>,
+        dedup: Option<Arc<DedupIndex>>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            config,
+            metadata_manager,
+            shard_store,
+            stripe_repository,
+            dedup,
+            shutdown: AtomicBool::new(false),
+        })
+    }
+
+    /// Live ranges of a volume's stripes, newest first
+    ///
+    /// Callers hold the volume's read lock, so a chunk reusing a freed range
+    /// cannot publish its stripe between reading the free list and the stripes.
+    fn live_ranges(
+        &self,
+        volume_id: &str,
+        state: &VolumeEcState,
+    ) -> Vec<(StripeMetadata, Vec<LbaRange>)> {
+        let dead = self
+            .dedup
+            .as_ref()
+            .map(|dedup| dedup.free_chunk_ranges(volume_id))
+            .unwrap_or_default();
+        state.live_ranges_excluding(&dead)
+    }
+
+    /// Run the compactor background loop
+    #[instrument(skip(self))]
+    pub async fn run(self: Arc<Self>) {
+        info!("Starting stripe compactor with {:?}", self.config);
+
+        let mut tick = interval(self.config.interval);
+
+        loop {
+            tick.tick().await;
+
+            if self.shutdown.load(Ordering::SeqCst) {
+                info!("Stripe compactor shutting down");
+                break;
+            }
+
+            for volume_id in self.metadata_manager.volume_ids() {
+                match self.compact_volume(&volume_id).await {
+                    Ok(result) => {
+                        if !result.stripes_deleted.is_empty() || !result.errors.is_empty() {
+                            info!(
+                                "Compacted volume {}: {} stripes deleted, {} repacked into {}, {} bytes reclaimed, {} errors",
+                                volume_id,
+                                result.stripes_deleted.len(),
+                                result.stripes_repacked.len(),
+                                result.stripes_created.len(),
+                                result.bytes_reclaimed,
+                                result.errors.len()
+                            );
+                        }
+                    }
+                    Err(e) => error!("Compaction of volume {} failed: {}", volume_id, e),
+                }
+            }
+        }
+    }
+
+    /// Signal shutdown
+    pub fn shutdown(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+    }
+
+    /// Get configuration
+    pub fn config(&self) -> &CompactionConfig {
+        &self.config
+    }
+
+    /// Compact a single volume
+    ///
+    /// Partially-live stripes below the live-ratio threshold are repacked
+    /// first, so the stripes they leave fully shadowed are collected in the
+    /// same pass.
+    #[instrument(skip(self))]
+    pub async fn compact_volume(&self, volume_id: &str) -> Result<CompactionResult> {
+        let mut result = CompactionResult {
+            volume_id: volume_id.to_string(),
+            ..Default::default()
+        };
+
+        let volume_state = self.metadata_manager.get_volume(volume_id).ok_or_else(|| {
+            Error::EcCompactionFailed {
+                volume_id: volume_id.to_string(),
+                reason: "Volume not found in EC state".to_string(),
+            }
+        })?;
+
+        // Extract liveness in a block to ensure guard is dropped before await
+        let (liveness, policy_ref) = {
+            let state = volume_state.read();
+            (
+                self.live_ranges(volume_id, &state),
+                state.policy_ref.clone(),
+            )
+        };
+
+        let candidates: Vec<(StripeMetadata, Vec<LbaRange>)> = liveness
+            .into_iter()
+            .filter(|(stripe, live)| {
+                let live_lbas: u64 = live.iter().map(LbaRange::size).sum();
+                !live.is_empty()
+                    && is_settled(stripe)
+                    && (live_lbas as f64) < self.config.live_ratio_threshold * stripe_lbas(stripe)
+            })
+            .take(self.config.max_repack_stripes)
+            .collect();
+
+        if !candidates.is_empty() {
+            if self.config.dry_run {
+                info!(
+                    "[DRY-RUN] Would repack {} stripes for volume {}",
+                    candidates.len(),
+                    volume_id
+                );
+            } else {
+                let policy = self.metadata_manager.load_policy(&policy_ref).await?;
+                self.repack(&volume_state, &policy, &candidates, &mut result)
+                    .await;
+            }
+        }
+
+        // Collect stripes with nothing live left
+        let shadowed: Vec<StripeMetadata> = self
+            .live_ranges(volume_id, &volume_state.read())
+            .into_iter()
+            .filter(|(stripe, live)| live.is_empty() && is_settled(stripe))
+            .map(|(stripe, _)| stripe)
+            .collect();
+
+        for stripe in shadowed {
+            let repacked = candidates
+                .iter()
+                .any(|(c, _)| c.stripe_id == stripe.stripe_id);
+
+            if self.config.dry_run {
+                info!(
+                    "[DRY-RUN] Would delete shadowed stripe {} of volume {}",
+                    stripe.stripe_id, volume_id
+                );
+                continue;
+            }
+
+            match retire_stripe(
+                self.shard_store.as_ref(),
+                self.stripe_repository.as_ref(),
+                &volume_state,
+                &stripe,
+            )
+            .await
+            {
+                Ok(bytes) => {
+                    if repacked {
+                        result.stripes_repacked.push(stripe.stripe_id);
+                    }
+                    result.stripes_deleted.push(stripe.stripe_id);
+                    result.bytes_reclaimed += bytes;
+                }
+                Err(e) => result
+                    .errors
+                    .push(format!("stripe {}: {}", stripe.stripe_id, e)),
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Move the live data of `candidates` into new, fully-live stripes
+    ///
+    /// A new stripe is only published if neither its source stripes nor its
+    /// LBA range changed while it was being written; otherwise its shards
+    /// are discarded and the sources stay live.
+    async fn repack(
+        &self,
+        volume_state: &SharedVolumeState,
+        policy: &ErasureCodingPolicy,
+        candidates: &[(StripeMetadata, Vec<LbaRange>)],
+        result: &mut CompactionResult,
+    ) {
+        let k = policy.spec.data_shards as usize;
+        let m = policy.spec.parity_shards as usize;
+        let encoder = match EcEncoder::new(k, m) {
+            Ok(encoder) => encoder,
+            Err(e) => {
+                result.errors.push(format!("invalid policy: {}", e));
+                return;
+            }
+        };
+
+        // Anything written after this point has a higher stripe ID
+        let newest_seen = volume_state
+            .read()
+            .all_stripes()
+            .iter()
+            .map(|s| s.stripe_id)
+            .max()
+            .unwrap_or(0);
+
+        let mut segments: Vec<LiveSegment> = Vec::new();
+        let mut sources: Vec<&StripeMetadata> = Vec::new();
+        for (stripe, live) in candidates {
+            let data = match self.read_stripe_data(stripe).await {
+                Ok(data) => data,
+                Err(e) => {
+                    result
+                        .errors
+                        .push(format!("stripe {}: {}", stripe.stripe_id, e));
+                    continue;
+                }
+            };
+            segments.extend(live.iter().map(|range| LiveSegment {
+                range: range.clone(),
+                data: slice_lbas(&data, stripe.lba_range.start_lba, range),
+            }));
+            sources.push(stripe);
+        }
+
+        let lbas_per_stripe = (policy.spec.stripe_size_bytes / LBA_SIZE).max(1);
+        for chunk in pack_segments(segments, lbas_per_stripe) {
+            match self
+                .write_packed_stripe(
+                    volume_state,
+                    policy,
+                    &encoder,
+                    &chunk,
+                    &sources,
+                    newest_seen,
+                )
+                .await
+            {
+                Ok(stripe_id) => result.stripes_created.push(stripe_id),
+                Err(e) => result.errors.push(format!(
+                    "repack of LBAs {}..{}: {}",
+                    chunk.range.start_lba, chunk.range.end_lba, e
+                )),
+            }
+        }
+    }
+
+    /// Encode, place and publish one repacked stripe
+    async fn write_packed_stripe(
+        &self,
+        volume_state: &SharedVolumeState,
+        policy: &ErasureCodingPolicy,
+        encoder: &EcEncoder,
+        chunk: &LiveSegment,
+        sources: &[&StripeMetadata],
+        newest_seen: u64,
+    ) -> Result<u64> {
+        let shards = encoder.encode(&chunk.data)?;
+        let shard_locations = place_shards(
+            self.shard_store.as_ref(),
+            &shards,
+            policy.spec.data_shards as usize,
+        )
+        .await?;
+
+        // Check for racing writers, including destages whose stripe IDs are
+        // reserved but not yet published, and publish under the write lock
+        let metadata = {
+            let mut state = volume_state.write();
+            let sources_changed = sources.iter().any(|source| {
+                source.lba_range.overlaps(&chunk.range)
+                    && state
+                        .get_stripe(source.stripe_id)
+                        .is_none_or(|current| current.generation != source.generation)
+            });
+
+            if sources_changed || state.has_newer_overlap(&chunk.range, newest_seen) {
+                None
+            } else {
+                let metadata = StripeMetadata {
+                    stripe_id: state.next_stripe_id(),
+                    volume_id: state.volume_id.clone(),
+                    policy_ref: policy.name().to_string(),
+                    lba_range: chunk.range.clone(),
+                    shard_locations: shard_locations.clone(),
+                    status: StripeStatus {
+                        state: StripeState::Healthy,
+                        healthy_shards: shards.len() as u8,
+                        shard_health: vec![],
+                    },
+                    generation: 0,
+                    checksum: None,
+                };
+                state.add_stripe(metadata.clone());
+                Some(metadata)
+            }
+        };
+
+        let Some(metadata) = metadata else {
+            discard_shards(self.shard_store.as_ref(), &shard_locations).await;
+            return Err(Error::EcCompactionFailed {
+                volume_id: volume_state.read().volume_id.clone(),
+                reason: "LBA range was rewritten during compaction".to_string(),
+            });
+        };
+
+        if let Err(e) = self
+            .stripe_repository
+            .save(&to_domain_stripe(&metadata))
+            .await
+        {
+            volume_state.write().remove_stripe(metadata.stripe_id);
+            discard_shards(self.shard_store.as_ref(), &shard_locations).await;
+            return Err(e);
+        }
+
+        debug!(
+            "Repacked LBA range {:?} of volume {} into stripe {}",
+            metadata.lba_range, metadata.volume_id, metadata.stripe_id
+        );
+
+        Ok(metadata.stripe_id)
+    }
+
+    /// Read the data portion of a stripe using the policy it was encoded with
+    async fn read_stripe_data(&self, stripe: &StripeMetadata) -> Result<Vec<u8>> {
+        let policy = self
+            .metadata_manager
+            .load_policy(&stripe.policy_ref)
+            .await?;
+        let decoder = EcDecoder::new(
+            policy.spec.data_shards as usize,
+            policy.spec.parity_shards as usize,
+        )?;
+        read_stripe_data(self.shard_store.as_ref(), stripe, &decoder).await
+    }
+}
+
+// =============================================================================
+// Helpers
+// =============================================================================
+
+/// Delete a stripe from the repository, memory and its pools
+///
+/// Returns the number of shard bytes released.
+pub(crate) async fn retire_stripe(
+    store: &dyn ShardStore,
+    repository: &dyn StripeRepository,
+    volume_state: &SharedVolumeState,
+    stripe: &StripeMetadata,
+) -> Result<u64> {
+    repository
+        .delete(
+            &VolumeId::new(stripe.volume_id.clone()),
+            &StripeId::new(stripe.stripe_id),
+        )
+        .await?;
+
+    let removed = volume_state.write().remove_stripe(stripe.stripe_id);
+    let Some(removed) = removed else {
+        warn!(
+            "Stripe {} of volume {} vanished before deletion",
+            stripe.stripe_id, stripe.volume_id
+        );
+        return Ok(0);
+    };
+
+    // Reads that looked up the stripe may still be using its shards
+    let readers = volume_state.read().readers();
+    readers.wait_for_readers().await;
+    discard_shards(store, &removed.shard_locations).await;
+
+    debug!(
+        "Deleted stripe {} of volume {}",
+        removed.stripe_id, removed.volume_id
+    );
+
+    Ok(removed.shard_locations.iter().map(|l| l.size_bytes).sum())
+}
+
+/// Whether a stripe is safe to compact (not mid-write or mid-rebuild)
+pub(crate) fn is_settled(stripe: &StripeMetadata) -> bool {
+    !matches!(
+        stripe.status.state,
+        StripeState::Writing | StripeState::Rebuilding
+    )
+}
+
+/// Number of LBAs a stripe covers, as a float for ratio checks
+fn stripe_lbas(stripe: &StripeMetadata) -> f64 {
+    stripe.lba_range.size() as f64
+}
+
+/// Bytes of `range` within stripe data starting at `stripe_start`, zero-padded
+pub(crate) fn slice_lbas(data: &[u8], stripe_start: u64, range: &LbaRange) -> Vec<u8> {
+    let len = (range.size() * LBA_SIZE) as usize;
+    let offset = ((range.start_lba - stripe_start) * LBA_SIZE) as usize;
+
+    let mut bytes = vec![0u8; len];
+    if offset < data.len() {
+        let end = (offset + len).min(data.len());
+        bytes[..end - offset].copy_from_slice(&data[offset..end]);
+    }
+    bytes
+}
+
+/// Merge adjacent live segments and split them into stripe-sized chunks
+pub(crate) fn pack_segments(
+    mut segments: Vec<LiveSegment>,
+    lbas_per_stripe: u64,
+) -> Vec<LiveSegment> {
+    segments.sort_by_key(|s| s.range.start_lba);
+
+    let mut runs: Vec<LiveSegment> = Vec::new();
+    for segment in segments {
+        match runs.last_mut() {
+            Some(run) if run.range.end_lba == segment.range.start_lba => {
+                run.range.end_lba = segment.range.end_lba;
+                run.data.extend_from_slice(&segment.data);
+            }
+            _ => runs.push(segment),
+        }
+    }
+
+    let chunk_bytes = (lbas_per_stripe * LBA_SIZE) as usize;
+    runs.into_iter()
+        .flat_map(|run| {
+            let start = run.range.start_lba;
+            run.data
+                .chunks(chunk_bytes)
+                .enumerate()
+                .map(|(i, bytes)| {
+                    let chunk_start = start + i as u64 * lbas_per_stripe;
+                    LiveSegment {
+                        range: LbaRange::new(
+                            chunk_start,
+                            chunk_start + bytes.len() as u64 / LBA_SIZE,
+                        ),
+                        data: bytes.to_vec(),
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crd::{DedupChunking, DedupConfig, ShardLocation};
+    use crate::ec::dedup::{chunk_volume_id, ChunkedWrite};
+    use crate::ec::test_support::{self, EcFixture};
+
+    const VOLUME: &str = "vol-1";
+    const POLICY: &str = "ec-4-2";
+
+    struct Fixture {
+        compactor: Arc<StripeCompactor>,
+        ec: EcFixture,
+    }
+
+    /// A 4+2 policy with 4096-byte (8 LBA) stripes and no stripes yet
+    async fn fixture(config: CompactionConfig) -> Fixture {
+        let ec = EcFixture::new(8, 4, [test_support::policy(POLICY, 4, 2, 4096)]);
+        ec.metadata_manager.get_or_create_volume(VOLUME, POLICY);
+
+        let compactor = StripeCompactor::new(
+            config,
+            ec.metadata_manager.clone(),
+            ec.store.clone(),
+            ec.repository.clone(),
+        );
+
+        Fixture { compactor, ec }
+    }
+
+    async fn destage(f: &Fixture, start_lba: u64, data: &[u8]) -> StripeMetadata {
+        destage_to(f, VOLUME, start_lba, data).await
+    }
+
+    async fn destage_to(f: &Fixture, volume: &str, start_lba: u64, data: &[u8]) -> StripeMetadata {
+        f.ec.destage(volume, POLICY, start_lba, data).await
+    }
+
+    async fn read_lba(f: &Fixture, lba: u64) -> Vec<u8> {
+        f.ec.read_lba(VOLUME, lba).await
+    }
+
+    fn shard_bytes(locations: &[ShardLocation]) -> u64 {
+        locations.iter().map(|l| l.size_bytes).sum()
+    }
+
+    fn filled(lbas: u64, byte: u8) -> Vec<u8> {
+        vec![byte; (lbas * LBA_SIZE) as usize]
+    }
+
+    // =========================================================================
+    // Helper Tests
+    // =========================================================================
+
+    #[test]
+    fn test_slice_lbas_pads_past_stripe_data() {
+        let data = vec![7u8; 600];
+        let bytes = slice_lbas(&data, 10, &LbaRange::new(11, 12));
+        assert_eq!(bytes.len(), LBA_SIZE as usize);
+        assert_eq!(&bytes[..88], &[7u8; 88][..]);
+        assert!(bytes[88..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_pack_segments_merges_and_splits() {
+        let segment = |start: u64, end: u64| LiveSegment {
+            range: LbaRange::new(start, end),
+            data: filled(end - start, start as u8),
+        };
+        let chunks = pack_segments(vec![segment(6, 10), segment(0, 4), segment(4, 6)], 8);
+
+        let ranges: Vec<(u64, u64)> = chunks
+            .iter()
+            .map(|c| (c.range.start_lba, c.range.end_lba))
+            .collect();
+        assert_eq!(ranges, vec![(0, 8), (8, 10)]);
+        assert_eq!(chunks[0].data[..LBA_SIZE as usize], filled(1, 0)[..]);
+        assert_eq!(chunks[1].data, filled(2, 6));
+    }
+
+    // =========================================================================
+    // Compaction Tests
+    // =========================================================================
+
+    #[tokio::test]
+    async fn test_compact_deletes_fully_shadowed_stripe() {
+        let f = fixture(CompactionConfig {
+            live_ratio_threshold: 0.0,
+            ..Default::default()
+        })
+        .await;
+        let old = destage(&f, 0, &filled(8, 1)).await;
+        let new = destage(&f, 0, &filled(8, 2)).await;
+        let shards_before = f.ec.store.shard_count();
+
+        let result = f.compactor.compact_volume(VOLUME).await.unwrap();
+
+        assert_eq!(result.stripes_deleted, vec![old.stripe_id]);
+        assert!(result.stripes_created.is_empty());
+        assert_eq!(result.bytes_reclaimed, shard_bytes(&old.shard_locations));
+        assert_eq!(f.ec.store.shard_count(), shards_before - 6);
+        assert_eq!(f.ec.repository.len(), 1);
+        assert!(f
+            .ec
+            .repository
+            .get(&VolumeId::new(VOLUME), &StripeId::new(new.stripe_id))
+            .is_some());
+
+        let state = f.ec.metadata_manager.get_volume(VOLUME).unwrap();
+        assert_eq!(state.read().stripe_count(), 1);
+        assert_eq!(
+            state.read().find_stripe_for_lba(3).unwrap().stripe_id,
+            new.stripe_id
+        );
+        assert_eq!(read_lba(&f, 3).await, filled(1, 2));
+    }
+
+    #[tokio::test]
+    async fn test_compact_reclaims_released_dedup_chunks() {
+        let f = fixture(CompactionConfig::default()).await;
+        let dir = tempfile::tempdir().unwrap();
+        let dedup = Arc::new(DedupIndex::open(dir.path(), false).unwrap());
+        let compactor = StripeCompactor::with_dedup(
+            CompactionConfig::default(),
+            f.ec.metadata_manager.clone(),
+            f.ec.store.clone(),
+            f.ec.repository.clone(),
+            dedup.clone(),
+        );
+        let chunk_volume = chunk_volume_id(POLICY);
+        let config = DedupConfig {
+            chunking: DedupChunking::Fixed,
+            chunk_size_bytes: 4096,
+        };
+
+        // Two chunks at chunk volume LBAs 0..8 and 8..16, one stripe each
+        let mut stripes = Vec::new();
+        for byte in [1u8, 2] {
+            let write = ChunkedWrite::new(0, filled(8, byte), &config);
+            let plan = dedup.plan("vm-a", POLICY, &[write]).unwrap();
+            for chunk in &plan.unique {
+                stripes.push(
+                    destage_to(&f, &chunk_volume, chunk.lba_range.start_lba, &chunk.data).await,
+                );
+            }
+            dedup.commit(plan).await.unwrap();
+        }
+
+        // Without the dedup index the released chunk's stripe looks live
+        let result = f.compactor.compact_volume(&chunk_volume).await.unwrap();
+        assert!(result.stripes_deleted.is_empty());
+
+        // The overwrite released the first chunk
+        let result = compactor.compact_volume(&chunk_volume).await.unwrap();
+        assert_eq!(result.stripes_deleted, vec![stripes[0].stripe_id]);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+    }
+
+    #[tokio::test]
+    async fn test_compact_repacks_mostly_dead_stripes() {
+        let f = fixture(CompactionConfig::default()).await;
+        // Two stripes with only LBAs 0..2 and 8..10 still live
+        let a = destage(&f, 0, &filled(8, 1)).await;
+        let b = destage(&f, 8, &filled(8, 2)).await;
+        destage(&f, 2, &filled(6, 3)).await;
+        destage(&f, 10, &filled(6, 4)).await;
+
+        let result = f.compactor.compact_volume(VOLUME).await.unwrap();
+
+        let mut repacked = result.stripes_repacked.clone();
+        repacked.sort();
+        assert_eq!(repacked, vec![a.stripe_id, b.stripe_id]);
+        assert_eq!(result.stripes_created.len(), 2);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+
+        // Every LBA still reads its latest contents
+        for (lba, byte) in [
+            (0, 1),
+            (1, 1),
+            (2, 3),
+            (7, 3),
+            (8, 2),
+            (9, 2),
+            (10, 4),
+            (15, 4),
+        ] {
+            assert_eq!(read_lba(&f, lba).await, filled(1, byte), "LBA {}", lba);
+        }
+        assert_eq!(
+            f.ec.repository
+                .count_by_volume(&VolumeId::new(VOLUME))
+                .await
+                .unwrap(),
+            4
+        );
+    }
+
+    #[tokio::test]
+    async fn test_compact_keeps_stripes_above_threshold() {
+        let f = fixture(CompactionConfig::default()).await;
+        destage(&f, 0, &filled(8, 1)).await;
+        destage(&f, 0, &filled(2, 2)).await;
+
+        let result = f.compactor.compact_volume(VOLUME).await.unwrap();
+
+        assert!(result.stripes_repacked.is_empty());
+        assert!(result.stripes_deleted.is_empty());
+        assert_eq!(
+            f.ec.metadata_manager
+                .get_volume(VOLUME)
+                .unwrap()
+                .read()
+                .stripe_count(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_repack_aborts_when_range_is_rewritten() {
+        let f = fixture(CompactionConfig::default()).await;
+        let old = destage(&f, 0, &filled(8, 1)).await;
+        destage(&f, 2, &filled(6, 2)).await;
+        let shards_before = f.ec.store.shard_count();
+
+        // A destage lands after the compactor took its snapshot
+        let volume_state = f.ec.metadata_manager.get_volume(VOLUME).unwrap();
+        let policy = f.ec.metadata_manager.load_policy(POLICY).await.unwrap();
+        let newest_seen = 1;
+        destage(&f, 0, &filled(2, 9)).await;
+
+        let chunk = LiveSegment {
+            range: LbaRange::new(0, 2),
+            data: filled(2, 1),
+        };
+        let err = f
+            .compactor
+            .write_packed_stripe(
+                &volume_state,
+                &policy,
+                &EcEncoder::new(4, 2).unwrap(),
+                &chunk,
+                &[&old],
+                newest_seen,
+            )
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, Error::EcCompactionFailed { .. }));
+        assert_eq!(f.ec.store.shard_count(), shards_before + 6);
+        assert_eq!(volume_state.read().stripe_count(), 3);
+        assert_eq!(read_lba(&f, 0).await, filled(1, 9));
+    }
+
+    #[tokio::test]
+    async fn test_repack_aborts_over_in_flight_destage() {
+        let f = fixture(CompactionConfig::default()).await;
+        destage(&f, 0, &filled(8, 1)).await;
+        destage(&f, 2, &filled(6, 2)).await;
+
+        // A destage of LBAs 0..2 has its stripe ID but no stripe yet
+        let volume_state = f.ec.metadata_manager.get_volume(VOLUME).unwrap();
+        let reserved = volume_state.read().reserve_stripe_id(LbaRange::new(0, 2));
+
+        let result = f.compactor.compact_volume(VOLUME).await.unwrap();
+        assert!(result.stripes_created.is_empty());
+        assert_eq!(result.errors.len(), 1, "{:?}", result.errors);
+        assert_eq!(volume_state.read().stripe_count(), 2);
+
+        // Once the destage gave up, the stripe repacks as usual
+        volume_state.read().release_stripe_id(reserved);
+        let result = f.compactor.compact_volume(VOLUME).await.unwrap();
+        assert_eq!(result.stripes_created.len(), 1);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(read_lba(&f, 0).await, filled(1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_compact_dry_run_changes_nothing() {
+        let f = fixture(CompactionConfig {
+            dry_run: true,
+            ..Default::default()
+        })
+        .await;
+        destage(&f, 0, &filled(8, 1)).await;
+        destage(&f, 0, &filled(8, 2)).await;
+        let shards_before = f.ec.store.shard_count();
+
+        let result = f.compactor.compact_volume(VOLUME).await.unwrap();
+
+        assert!(result.stripes_deleted.is_empty());
+        assert_eq!(f.ec.store.shard_count(), shards_before);
+        assert_eq!(f.ec.repository.len(), 2);
+    }
+}
@@ -0,0 +1,1304 @@
+//! Block-Level Deduplication
+//!
+//! Optional layer in front of EC stripe creation, enabled per policy with
+//! `ErasureCodingPolicySpec::dedup`. Destaged writes are split into chunks,
+//! either fixed-size or content-defined (boundaries fall on block ends), and
+//! each chunk is fingerprinted with SHA-256.
+//!
+//! Only chunks whose fingerprint is not indexed yet are stored. They are
+//! appended to the policy's chunk volume (`dedup-<policy>`) and erasure coded
+//! like any other volume data, so placement, rebuild and scrubbing work
+//! unchanged. The written volume's LBAs map to extents of shared chunks
+//! instead of stripes of their own; `DedupIndex::resolve` translates them.
+//!
+//! Chunks are reference counted by the extents that point at them and are
+//! dropped from the index once nothing refers to them. Their chunk volume
+//! LBAs go to a free list: new chunks reuse them first, and the stripe
+//! compactor treats them as dead so the old chunk data is reclaimed. The free
+//! list is not logged; on open it is rebuilt from the gaps between indexed
+//! chunks. Every change is
+//! appended to `dedup.log` as `<crc32 hex> <json record>` lines (like the
+//! stripe log), replayed on open and compacted when superseded records
+//! dominate.
+
+use crate::crd::{DedupChunking, DedupConfig, LbaRange};
+use crate::ec::record_log::RecordLog;
+use crate::ec::stripe_manager::{JournalEntry, LBA_SIZE};
+use crate::error::{Error, Result};
+use chrono::Utc;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, Weak};
+use tracing::{debug, info, warn};
+
+/// Log file name inside the index directory
+const LOG_FILE: &str = "dedup.log";
+
+/// Never compact logs with fewer records than this
+const COMPACT_MIN_RECORDS: u64 = 4096;
+
+/// Compact once the log holds this many records per live chunk or extent
+const COMPACT_RATIO: u64 = 4;
+
+/// Prefix of the volumes holding each policy's unique chunks
+const CHUNK_VOLUME_PREFIX: &str = "dedup-";
+
+/// Volume whose stripes hold the unique chunks of a policy
+pub fn chunk_volume_id(policy: &str) -> String {
+    format!("{}{}", CHUNK_VOLUME_PREFIX, policy)
+}
+
+// =============================================================================
+// Fingerprints and Chunking
+// =============================================================================
+
+/// SHA-256 fingerprint of a chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fingerprint([u8; 32]);
+
+impl Fingerprint {
+    /// Fingerprint `data`
+    pub fn of(data: &[u8]) -> Self {
+        Self(Sha256::digest(data).into())
+    }
+
+    /// Lowercase hex form
+    pub fn to_hex(self) -> String {
+        self.0.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Parse the hex form
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        if hex.len() != 64 {
+            return None;
+        }
+        let mut bytes = [0u8; 32];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(hex.get(2 * i..2 * i + 2)?, 16).ok()?;
+        }
+        Some(Self(bytes))
+    }
+}
+
+impl std::fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+/// Random table for the gear rolling hash (splitmix64 of the byte index)
+const GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state = 0u64;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// Split data into chunk lengths according to `config`
+///
+/// Every chunk but the last is a whole number of blocks, so chunks map onto
+/// LBA ranges.
+pub fn chunk_lengths(data: &[u8], config: &DedupConfig) -> Vec<usize> {
+    let chunk_size = (config.chunk_size_bytes / LBA_SIZE).max(1) as usize * LBA_SIZE as usize;
+    match config.chunking {
+        DedupChunking::Fixed => data.chunks(chunk_size).map(|c| c.len()).collect(),
+        DedupChunking::ContentDefined => content_defined_lengths(data, chunk_size),
+    }
+}
+
+/// Gear-hash chunking with boundaries restricted to block ends
+///
+/// A chunk is cut after a block whose trailing bytes hash to zero under a
+/// mask sized for `avg` bytes. Chunks stay within [avg/4, avg*4].
+fn content_defined_lengths(data: &[u8], avg: usize) -> Vec<usize> {
+    let block = LBA_SIZE as usize;
+    let avg_blocks = (avg / block).max(1);
+    let min = (avg_blocks / 4).max(1) * block;
+    let max = avg_blocks * 4 * block;
+    let bits = avg_blocks.next_power_of_two().trailing_zeros();
+    let mask = if bits == 0 {
+        0
+    } else {
+        u64::MAX << (64 - bits)
+    };
+
+    let mut lengths = Vec::new();
+    let mut start = 0;
+    let mut hash = 0u64;
+    for (i, &byte) in data.iter().enumerate() {
+        hash = (hash << 1).wrapping_add(GEAR[byte as usize]);
+        let len = i + 1 - start;
+        if !len.is_multiple_of(block) {
+            continue;
+        }
+        if len >= max || (len >= min && hash & mask == 0) {
+            lengths.push(len);
+            start = i + 1;
+        }
+    }
+    if start < data.len() {
+        lengths.push(data.len() - start);
+    }
+    lengths
+}
+
+/// A contiguous write split into fingerprinted chunks
+#[derive(Debug, Clone)]
+pub struct ChunkedWrite {
+    /// First LBA written
+    pub start_lba: u64,
+
+    /// Written data
+    pub data: Vec<u8>,
+
+    /// Length and fingerprint of each chunk, in order
+    pub chunks: Vec<(usize, Fingerprint)>,
+}
+
+impl ChunkedWrite {
+    /// Chunk and fingerprint a write
+    pub fn new(start_lba: u64, data: Vec<u8>, config: &DedupConfig) -> Self {
+        let mut offset = 0;
+        let chunks = chunk_lengths(&data, config)
+            .into_iter()
+            .map(|len| {
+                let fingerprint = Fingerprint::of(&data[offset..offset + len]);
+                offset += len;
+                (len, fingerprint)
+            })
+            .collect();
+        Self {
+            start_lba,
+            data,
+            chunks,
+        }
+    }
+}
+
+/// Merge journal entries into contiguous writes, in order
+///
+/// A gap or an overwrite starts a new write, so later data still wins.
+pub fn contiguous_writes(entries: &[JournalEntry]) -> Vec<(u64, Vec<u8>)> {
+    let mut writes: Vec<(u64, Vec<u8>)> = Vec::new();
+    for entry in entries {
+        if let Some((start, data)) = writes.last_mut() {
+            let len = data.len() as u64;
+            if len.is_multiple_of(LBA_SIZE) && entry.lba_range.start_lba == *start + len / LBA_SIZE
+            {
+                data.extend_from_slice(&entry.data);
+                continue;
+            }
+        }
+        writes.push((entry.lba_range.start_lba, entry.data.clone()));
+    }
+    writes
+}
+
+// =============================================================================
+// Log Records
+// =============================================================================
+
+/// One mutation of the dedup index (short keys keep records small)
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum LogRecord {
+    /// A unique chunk was stored in the policy's chunk volume
+    Chunk {
+        #[serde(rename = "p")]
+        policy: String,
+        #[serde(rename = "f")]
+        fingerprint: String,
+        #[serde(rename = "l")]
+        chunk_lba: u64,
+        #[serde(rename = "n")]
+        lba_count: u64,
+    },
+    /// Volume LBAs now point into a chunk
+    Map {
+        #[serde(rename = "v")]
+        volume: String,
+        #[serde(rename = "p")]
+        policy: String,
+        #[serde(rename = "s")]
+        start_lba: u64,
+        #[serde(rename = "n")]
+        lba_count: u64,
+        #[serde(rename = "f")]
+        fingerprint: String,
+        #[serde(rename = "o")]
+        offset: u64,
+    },
+    /// Next free LBA of a chunk volume (written by compaction)
+    Cursor {
+        #[serde(rename = "p")]
+        policy: String,
+        #[serde(rename = "l")]
+        next_chunk_lba: u64,
+    },
+}
+
+// =============================================================================
+// In-Memory Index
+// =============================================================================
+
+/// A unique chunk stored in a chunk volume
+#[derive(Debug)]
+struct Chunk {
+    chunk_lba: u64,
+    lba_count: u64,
+
+    /// Volume extents pointing into the chunk
+    refs: u64,
+}
+
+/// Volume LBAs backed by (part of) a chunk
+#[derive(Debug, Clone, Copy)]
+struct Extent {
+    lba_count: u64,
+    fingerprint: Fingerprint,
+
+    /// Blocks into the chunk where the extent starts
+    offset: u64,
+}
+
+/// Chunks of one policy
+#[derive(Debug, Default)]
+struct PolicyIndex {
+    chunks: HashMap<Fingerprint, Chunk>,
+    next_chunk_lba: u64,
+
+    /// Released chunk volume ranges below `next_chunk_lba`, start -> end
+    free: BTreeMap<u64, u64>,
+
+    /// Blocks mapped by volume extents
+    logical_blocks: u64,
+
+    /// Blocks of unique chunk data
+    stored_blocks: u64,
+}
+
+impl PolicyIndex {
+    fn retain(&mut self, fingerprint: &Fingerprint) {
+        if let Some(chunk) = self.chunks.get_mut(fingerprint) {
+            chunk.refs += 1;
+        }
+    }
+
+    fn release(&mut self, fingerprint: &Fingerprint) {
+        let Some(chunk) = self.chunks.get_mut(fingerprint) else {
+            return;
+        };
+        chunk.refs = chunk.refs.saturating_sub(1);
+        if chunk.refs == 0 {
+            let (start, count) = (chunk.chunk_lba, chunk.lba_count);
+            self.stored_blocks -= count;
+            self.chunks.remove(fingerprint);
+            self.free_range(start, count);
+        }
+    }
+
+    /// Return chunk volume LBAs to the free list, merging neighbours
+    fn free_range(&mut self, start: u64, count: u64) {
+        if count == 0 {
+            return;
+        }
+        let mut start = start;
+        let mut end = start + count;
+        if let Some((&s, &e)) = self.free.range(..=start).next_back() {
+            if e >= start {
+                self.free.remove(&s);
+                start = s;
+                end = end.max(e);
+            }
+        }
+        while let Some((&s, &e)) = self.free.range(start..=end).next() {
+            self.free.remove(&s);
+            end = end.max(e);
+        }
+        self.free.insert(start, end);
+    }
+
+    /// Reserve `count` chunk volume LBAs, reusing freed space first
+    fn reserve(&mut self, count: u64) -> u64 {
+        let fit = self
+            .free
+            .iter()
+            .find(|(s, e)| *e - *s >= count)
+            .map(|(s, e)| (*s, *e));
+        match fit {
+            Some((start, end)) => {
+                self.free.remove(&start);
+                if end > start + count {
+                    self.free.insert(start + count, end);
+                }
+                start
+            }
+            None => {
+                let start = self.next_chunk_lba;
+                self.next_chunk_lba += count;
+                start
+            }
+        }
+    }
+
+    /// Rebuild the free list as the gaps between indexed chunks
+    fn rebuild_free(&mut self) {
+        let mut used: Vec<(u64, u64)> = self
+            .chunks
+            .values()
+            .map(|c| (c.chunk_lba, c.chunk_lba + c.lba_count))
+            .collect();
+        used.sort_unstable();
+
+        self.free.clear();
+        let mut cursor = 0;
+        for (start, end) in used {
+            if start > cursor {
+                self.free.insert(cursor, start);
+            }
+            cursor = cursor.max(end);
+        }
+        if self.next_chunk_lba > cursor {
+            self.free.insert(cursor, self.next_chunk_lba);
+        }
+    }
+}
+
+/// Extents of one volume, keyed by start LBA (never overlapping)
+#[derive(Debug)]
+struct VolumeMap {
+    policy: String,
+    extents: BTreeMap<u64, Extent>,
+}
+
+/// Start LBAs of the extents overlapping `[start, end)`, in descending order
+fn overlapping(extents: &BTreeMap<u64, Extent>, start: u64, end: u64) -> Vec<u64> {
+    extents
+        .range(..end)
+        .rev()
+        .take_while(|(s, e)| *s + e.lba_count > start)
+        .map(|(s, _)| *s)
+        .collect()
+}
+
+/// Chunks and volume maps described by the log
+#[derive(Default)]
+struct DedupTables {
+    policies: HashMap<String, PolicyIndex>,
+    volumes: HashMap<String, VolumeMap>,
+}
+
+impl DedupTables {
+    fn live_records(&self) -> u64 {
+        let chunks: usize = self.policies.values().map(|p| p.chunks.len()).sum();
+        let extents: usize = self.volumes.values().map(|v| v.extents.len()).sum();
+        (chunks + extents) as u64
+    }
+
+    fn apply(&mut self, record: LogRecord) {
+        match record {
+            LogRecord::Chunk {
+                policy,
+                fingerprint,
+                chunk_lba,
+                lba_count,
+            } => {
+                let Some(fingerprint) = Fingerprint::from_hex(&fingerprint) else {
+                    warn!("Skipping dedup chunk with invalid fingerprint");
+                    return;
+                };
+                let index = self.policies.entry(policy).or_default();
+                index.next_chunk_lba = index.next_chunk_lba.max(chunk_lba + lba_count);
+                if !index.chunks.contains_key(&fingerprint) {
+                    index.stored_blocks += lba_count;
+                    index.chunks.insert(
+                        fingerprint,
+                        Chunk {
+                            chunk_lba,
+                            lba_count,
+                            refs: 0,
+                        },
+                    );
+                }
+            }
+            LogRecord::Map {
+                volume,
+                policy,
+                start_lba,
+                lba_count,
+                fingerprint,
+                offset,
+            } => {
+                let Some(fingerprint) = Fingerprint::from_hex(&fingerprint) else {
+                    warn!("Skipping dedup extent with invalid fingerprint");
+                    return;
+                };
+                let extent = Extent {
+                    lba_count,
+                    fingerprint,
+                    offset,
+                };
+                if !self.map_extent(&volume, &policy, start_lba, extent) {
+                    warn!(
+                        "Skipping dedup extent of volume {} at LBA {}: chunk {} unknown",
+                        volume, start_lba, fingerprint
+                    );
+                }
+            }
+            LogRecord::Cursor {
+                policy,
+                next_chunk_lba,
+            } => {
+                let index = self.policies.entry(policy).or_default();
+                index.next_chunk_lba = index.next_chunk_lba.max(next_chunk_lba);
+            }
+        }
+    }
+
+    /// Point `[start, start + extent.lba_count)` of a volume at a chunk,
+    /// releasing whatever it overwrites. Returns false if the chunk is not
+    /// indexed.
+    fn map_extent(&mut self, volume: &str, policy: &str, start: u64, extent: Extent) -> bool {
+        let Some(index) = self.policies.get_mut(policy) else {
+            return false;
+        };
+        if !index.chunks.contains_key(&extent.fingerprint) {
+            return false;
+        }
+        let map = self
+            .volumes
+            .entry(volume.to_string())
+            .or_insert_with(|| VolumeMap {
+                policy: policy.to_string(),
+                extents: BTreeMap::new(),
+            });
+
+        // Retain first, in case the overwritten extents share the chunk
+        index.retain(&extent.fingerprint);
+        index.logical_blocks += extent.lba_count;
+
+        let end = start + extent.lba_count;
+        for s in overlapping(&map.extents, start, end) {
+            let Some(old) = map.extents.remove(&s) else {
+                continue;
+            };
+            let old_end = s + old.lba_count;
+            if s < start {
+                let head = Extent {
+                    lba_count: start - s,
+                    ..old
+                };
+                map.extents.insert(s, head);
+                index.retain(&old.fingerprint);
+            }
+            if old_end > end {
+                let tail = Extent {
+                    lba_count: old_end - end,
+                    offset: old.offset + (end - s),
+                    ..old
+                };
+                map.extents.insert(end, tail);
+                index.retain(&old.fingerprint);
+            }
+            index.logical_blocks -= old_end.min(end) - s.max(start);
+            index.release(&old.fingerprint);
+        }
+
+        map.extents.insert(start, extent);
+        true
+    }
+
+    /// Drop chunks no extent points at (left by a torn commit) and rebuild
+    /// the free lists
+    fn sweep(&mut self) -> usize {
+        let mut swept = 0;
+        for index in self.policies.values_mut() {
+            let before = index.chunks.len();
+            let mut freed = 0;
+            index.chunks.retain(|_, chunk| {
+                if chunk.refs == 0 {
+                    freed += chunk.lba_count;
+                }
+                chunk.refs > 0
+            });
+            index.stored_blocks -= freed;
+            index.rebuild_free();
+            swept += before - index.chunks.len();
+        }
+        swept
+    }
+}
+
+/// Log file plus the tables it describes
+struct DedupState {
+    log: RecordLog,
+    tables: DedupTables,
+}
+
+impl DedupState {
+    /// Append records to the log, then apply them
+    fn commit(&mut self, records: Vec<LogRecord>) -> Result<()> {
+        self.log.append(&records)?;
+        for record in records {
+            self.tables.apply(record);
+        }
+
+        let logged = self.log.records();
+        if logged >= COMPACT_MIN_RECORDS
+            && logged > self.tables.live_records().max(1) * COMPACT_RATIO
+        {
+            if let Err(e) = self.compact() {
+                warn!("Dedup log compaction failed: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Rewrite the log as one record per live chunk and extent
+    fn compact(&mut self) -> Result<()> {
+        let before = self.log.records();
+        let tables = &self.tables;
+        let chunks = tables.policies.iter().flat_map(|(policy, index)| {
+            let cursor = LogRecord::Cursor {
+                policy: policy.clone(),
+                next_chunk_lba: index.next_chunk_lba,
+            };
+            std::iter::once(cursor).chain(index.chunks.iter().map(|(fingerprint, chunk)| {
+                LogRecord::Chunk {
+                    policy: policy.clone(),
+                    fingerprint: fingerprint.to_hex(),
+                    chunk_lba: chunk.chunk_lba,
+                    lba_count: chunk.lba_count,
+                }
+            }))
+        });
+        let extents = tables.volumes.iter().flat_map(|(volume, map)| {
+            map.extents.iter().map(|(start, extent)| LogRecord::Map {
+                volume: volume.clone(),
+                policy: map.policy.clone(),
+                start_lba: *start,
+                lba_count: extent.lba_count,
+                fingerprint: extent.fingerprint.to_hex(),
+                offset: extent.offset,
+            })
+        });
+        self.log.rewrite(chunks.chain(extents))?;
+
+        info!(
+            "Compacted dedup log {} from {} to {} records",
+            self.log.path().display(),
+            before,
+            self.log.records()
+        );
+        Ok(())
+    }
+}
+
+// =============================================================================
+// Dedup Index
+// =============================================================================
+
+/// Dedup savings of one policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DedupStats {
+    /// Unique chunks stored
+    pub chunks: u64,
+
+    /// Bytes mapped by deduplicated volumes
+    pub logical_bytes: u64,
+
+    /// Bytes of unique chunk data
+    pub stored_bytes: u64,
+}
+
+impl DedupStats {
+    /// Logical bytes per stored byte (None until something is stored)
+    pub fn ratio(&self) -> Option<f64> {
+        (self.stored_bytes > 0).then(|| self.logical_bytes as f64 / self.stored_bytes as f64)
+    }
+}
+
+/// Volume LBAs resolved to the chunk volume LBAs holding their data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedExtent {
+    /// First volume LBA of the extent
+    pub start_lba: u64,
+
+    /// Number of blocks
+    pub lba_count: u64,
+
+    /// Volume whose stripes hold the data
+    pub chunk_volume: String,
+
+    /// LBA of the extent's first block in the chunk volume
+    pub chunk_lba: u64,
+}
+
+/// A chunk of a planned write
+#[derive(Debug, Clone)]
+struct PlannedChunk {
+    start_lba: u64,
+    lba_count: u64,
+    fingerprint: Fingerprint,
+
+    /// Chunk volume LBA reserved for it, if the chunk is new
+    new_at: Option<u64>,
+}
+
+/// Chunk volume LBAs a plan reserved for its new chunks
+///
+/// Whatever the plan does not commit goes back to the free list, also when
+/// the plan is dropped uncommitted.
+#[derive(Debug)]
+struct Reservation {
+    state: Weak<Mutex<DedupState>>,
+    policy: String,
+
+    /// Reserved ranges not committed or returned yet, as (start, count)
+    ranges: Vec<(u64, u64)>,
+}
+
+impl Reservation {
+    /// Return the outstanding ranges to the policy's free list
+    fn release(&mut self, tables: &mut DedupTables) {
+        if let Some(index) = tables.policies.get_mut(&self.policy) {
+            for (start, count) in self.ranges.drain(..) {
+                index.free_range(start, count);
+            }
+        }
+        self.ranges.clear();
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if self.ranges.is_empty() {
+            return;
+        }
+        if let Some(state) = self.state.upgrade() {
+            self.release(&mut state.lock().tables);
+        }
+    }
+}
+
+/// Deduplicated form of a destage, ready to commit once its unique chunks
+/// are durable
+#[derive(Debug)]
+pub struct DedupPlan {
+    volume_id: String,
+    policy: String,
+
+    /// Volume the unique chunks are written to
+    pub chunk_volume: String,
+
+    chunks: Vec<PlannedChunk>,
+
+    /// Chunks not stored yet, as contiguous writes to the chunk volume
+    pub unique: Vec<JournalEntry>,
+
+    reservation: Reservation,
+}
+
+impl DedupPlan {
+    /// Bytes of volume data covered by the plan
+    pub fn logical_bytes(&self) -> u64 {
+        self.chunks.iter().map(|c| c.lba_count * LBA_SIZE).sum()
+    }
+
+    /// Bytes of new chunk data to store
+    pub fn unique_bytes(&self) -> u64 {
+        self.unique.iter().map(|e| e.data.len() as u64).sum()
+    }
+
+    /// Log records committing the plan against `index`, plus the reserved
+    /// chunk LBAs they use
+    fn records(&self, index: Option<&PolicyIndex>) -> Result<(Vec<LogRecord>, HashSet<u64>)> {
+        let mut created = HashSet::new();
+        let mut used = HashSet::new();
+        let mut records = Vec::with_capacity(self.chunks.len() * 2);
+
+        for chunk in &self.chunks {
+            let indexed = index.is_some_and(|i| i.chunks.contains_key(&chunk.fingerprint));
+            if !indexed && !created.contains(&chunk.fingerprint) {
+                let Some(chunk_lba) = chunk.new_at else {
+                    return Err(Error::EcDestageFailed {
+                        volume_id: self.volume_id.clone(),
+                        reason: format!("chunk {} was released concurrently", chunk.fingerprint),
+                    });
+                };
+                created.insert(chunk.fingerprint);
+                used.insert(chunk_lba);
+                records.push(LogRecord::Chunk {
+                    policy: self.policy.clone(),
+                    fingerprint: chunk.fingerprint.to_hex(),
+                    chunk_lba,
+                    lba_count: chunk.lba_count,
+                });
+            }
+            records.push(LogRecord::Map {
+                volume: self.volume_id.clone(),
+                policy: self.policy.clone(),
+                start_lba: chunk.start_lba,
+                lba_count: chunk.lba_count,
+                fingerprint: chunk.fingerprint.to_hex(),
+                offset: 0,
+            });
+        }
+        Ok((records, used))
+    }
+}
+
+/// Reference-counted fingerprint index with per-volume LBA maps
+#[derive(Clone)]
+pub struct DedupIndex {
+    state: Arc<Mutex<DedupState>>,
+}
+
+impl DedupIndex {
+    /// Open (or create) the index in `dir`, replaying its log.
+    ///
+    /// Appends are fsynced unless `sync_writes` is false.
+    pub fn open(dir: impl AsRef<Path>, sync_writes: bool) -> Result<Self> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(LOG_FILE);
+
+        let mut tables = DedupTables::default();
+        let log = RecordLog::open(&path, sync_writes, |record| tables.apply(record))?;
+        let mut state = DedupState { log, tables };
+        let swept = state.tables.sweep();
+        if swept > 0 {
+            warn!("Dropped {} unreferenced dedup chunks", swept);
+        }
+
+        info!(
+            "Opened dedup index {} with {} volumes across {} policies",
+            path.display(),
+            state.tables.volumes.len(),
+            state.tables.policies.len()
+        );
+
+        Ok(Self {
+            state: Arc::new(Mutex::new(state)),
+        })
+    }
+
+    /// Look up the chunks of `writes` and reserve room for unseen ones.
+    ///
+    /// Nothing is logged until `commit`. The reserved room is held by the
+    /// plan and returned to the free list if it is dropped uncommitted, so
+    /// its chunk data, if written, is left as dead space for compaction.
+    pub fn plan(
+        &self,
+        volume_id: &str,
+        policy: &str,
+        writes: &[ChunkedWrite],
+    ) -> Result<DedupPlan> {
+        let mut state = self.state.lock();
+        if let Some(map) = state.tables.volumes.get(volume_id) {
+            if map.policy != policy {
+                return Err(Error::EcDestageFailed {
+                    volume_id: volume_id.to_string(),
+                    reason: format!(
+                        "volume is deduplicated under policy {}, not {}",
+                        map.policy, policy
+                    ),
+                });
+            }
+        }
+
+        let index = state.tables.policies.entry(policy.to_string()).or_default();
+        let mut plan = DedupPlan {
+            volume_id: volume_id.to_string(),
+            policy: policy.to_string(),
+            chunk_volume: chunk_volume_id(policy),
+            chunks: Vec::new(),
+            unique: Vec::new(),
+            reservation: Reservation {
+                state: Arc::downgrade(&self.state),
+                policy: policy.to_string(),
+                ranges: Vec::new(),
+            },
+        };
+        let mut planned_new = HashSet::new();
+
+        for write in writes {
+            let mut offset = 0;
+            let mut lba = write.start_lba;
+            for &(len, fingerprint) in &write.chunks {
+                let lba_count = (len as u64).div_ceil(LBA_SIZE);
+                let new_at = if index.chunks.contains_key(&fingerprint)
+                    || !planned_new.insert(fingerprint)
+                {
+                    None
+                } else {
+                    let chunk_lba = index.reserve(lba_count);
+                    plan.reservation.ranges.push((chunk_lba, lba_count));
+
+                    // Pad to whole blocks so unique chunks pack contiguously
+                    let mut data = write.data[offset..offset + len].to_vec();
+                    data.resize((lba_count * LBA_SIZE) as usize, 0);
+                    plan.unique.push(JournalEntry {
+                        lba_range: LbaRange::new(chunk_lba, chunk_lba + lba_count),
+                        data,
+                        timestamp: Utc::now(),
+                    });
+                    Some(chunk_lba)
+                };
+
+                plan.chunks.push(PlannedChunk {
+                    start_lba: lba,
+                    lba_count,
+                    fingerprint,
+                    new_at,
+                });
+                offset += len;
+                lba += lba_count;
+            }
+        }
+
+        debug!(
+            "Dedup plan for volume {}: {} chunks, {} new ({} of {} bytes unique)",
+            volume_id,
+            plan.chunks.len(),
+            plan.unique.len(),
+            plan.unique_bytes(),
+            plan.logical_bytes()
+        );
+        Ok(plan)
+    }
+
+    /// Record a plan once its unique chunks are durable in the chunk volume
+    pub async fn commit(&self, plan: DedupPlan) -> Result<()> {
+        let state = self.state.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut plan = plan;
+            let mut state = state.lock();
+            let state = &mut *state;
+            let result = plan
+                .records(state.tables.policies.get(&plan.policy))
+                .and_then(|(records, used)| state.commit(records).map(|()| used));
+
+            // Reservations of chunks another plan indexed first are unused
+            if let Ok(used) = &result {
+                plan.reservation
+                    .ranges
+                    .retain(|(start, _)| !used.contains(start));
+            }
+            plan.reservation.release(&mut state.tables);
+            result.map(|_| ())
+        })
+        .await
+        .map_err(|e| Error::Internal(format!("Dedup commit task failed: {}", e)))?
+    }
+
+    /// Resolve a volume's LBA range to the chunk data holding it, in LBA
+    /// order. Unmapped LBAs are left out.
+    pub fn resolve(&self, volume_id: &str, range: &LbaRange) -> Vec<ResolvedExtent> {
+        let state = self.state.lock();
+        let Some(map) = state.tables.volumes.get(volume_id) else {
+            return vec![];
+        };
+        let Some(index) = state.tables.policies.get(&map.policy) else {
+            return vec![];
+        };
+        let chunk_volume = chunk_volume_id(&map.policy);
+
+        let mut resolved: Vec<ResolvedExtent> =
+            overlapping(&map.extents, range.start_lba, range.end_lba)
+                .into_iter()
+                .rev()
+                .filter_map(|s| {
+                    let extent = &map.extents[&s];
+                    let chunk = index.chunks.get(&extent.fingerprint)?;
+                    let from = s.max(range.start_lba);
+                    let to = (s + extent.lba_count).min(range.end_lba);
+                    Some(ResolvedExtent {
+                        start_lba: from,
+                        lba_count: to - from,
+                        chunk_volume: chunk_volume.clone(),
+                        chunk_lba: chunk.chunk_lba + extent.offset + (from - s),
+                    })
+                })
+                .collect();
+        resolved.sort_by_key(|r| r.start_lba);
+        resolved
+    }
+
+    /// Released LBA ranges of a chunk volume, whose stripe data is dead.
+    ///
+    /// Empty for volumes that are not chunk volumes.
+    pub fn free_chunk_ranges(&self, volume_id: &str) -> Vec<LbaRange> {
+        let Some(policy) = volume_id.strip_prefix(CHUNK_VOLUME_PREFIX) else {
+            return vec![];
+        };
+        self.state
+            .lock()
+            .tables
+            .policies
+            .get(policy)
+            .map(|index| {
+                index
+                    .free
+                    .iter()
+                    .map(|(s, e)| LbaRange::new(*s, *e))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Dedup savings of a policy, if anything was deduplicated under it
+    pub fn stats(&self, policy: &str) -> Option<DedupStats> {
+        let state = self.state.lock();
+        state.tables.policies.get(policy).map(|index| DedupStats {
+            chunks: index.chunks.len() as u64,
+            logical_bytes: index.logical_blocks * LBA_SIZE,
+            stored_bytes: index.stored_blocks * LBA_SIZE,
+        })
+    }
+
+    /// Rewrite the log as one record per live chunk and extent
+    pub async fn compact(&self) -> Result<()> {
+        let state = self.state.clone();
+        tokio::task::spawn_blocking(move || state.lock().compact())
+            .await
+            .map_err(|e| Error::Internal(format!("Dedup log task failed: {}", e)))?
+    }
+
+    /// Number of records in the log, including superseded ones
+    pub fn log_records(&self) -> u64 {
+        self.state.lock().log.records()
+    }
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ec::record_log::encode_record;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    const POLICY: &str = "ec-4-2";
+
+    fn fixed(chunk_size: u64) -> DedupConfig {
+        DedupConfig {
+            chunking: DedupChunking::Fixed,
+            chunk_size_bytes: chunk_size,
+        }
+    }
+
+    /// Deterministic pseudo-random bytes
+    fn noise(seed: u64, len: usize) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (state >> 56) as u8
+            })
+            .collect()
+    }
+
+    /// Plan and commit a write, returning the new chunk data
+    async fn write(index: &DedupIndex, volume: &str, lba: u64, data: Vec<u8>) -> Vec<JournalEntry> {
+        let chunked = ChunkedWrite::new(lba, data, &fixed(4096));
+        let plan = index.plan(volume, POLICY, &[chunked]).unwrap();
+        let unique = plan.unique.clone();
+        index.commit(plan).await.unwrap();
+        unique
+    }
+
+    #[test]
+    fn test_fingerprint_hex_roundtrip() {
+        let fingerprint = Fingerprint::of(b"chunk");
+        assert_eq!(
+            Fingerprint::from_hex(&fingerprint.to_hex()),
+            Some(fingerprint)
+        );
+        assert_eq!(Fingerprint::from_hex("abc"), None);
+        assert_ne!(fingerprint, Fingerprint::of(b"chunk2"));
+    }
+
+    #[test]
+    fn test_fixed_chunking() {
+        let lengths = chunk_lengths(&[0u8; 10240], &fixed(4096));
+        assert_eq!(lengths, vec![4096, 4096, 2048]);
+    }
+
+    #[test]
+    fn test_content_defined_chunking_resyncs_after_insert() {
+        let config = DedupConfig {
+            chunking: DedupChunking::ContentDefined,
+            chunk_size_bytes: 8192,
+        };
+        let data = noise(7, 512 * 1024);
+        let lengths = chunk_lengths(&data, &config);
+        assert_eq!(lengths.iter().sum::<usize>(), data.len());
+        assert!(lengths.iter().all(|l| l % 512 == 0));
+        let (_, full) = lengths.split_last().unwrap();
+        assert!(full.iter().all(|l| (2048..=32768).contains(l)));
+
+        // Prepending a block shifts the data, but later chunks are found again
+        let mut shifted = noise(8, 512);
+        shifted.extend_from_slice(&data);
+        let fingerprints = |data: &[u8]| -> HashSet<Fingerprint> {
+            ChunkedWrite::new(0, data.to_vec(), &config)
+                .chunks
+                .into_iter()
+                .map(|(_, f)| f)
+                .collect()
+        };
+        let original = fingerprints(&data);
+        let shared = fingerprints(&shifted).intersection(&original).count();
+        assert!(
+            shared * 10 >= original.len() * 8,
+            "{} of {}",
+            shared,
+            original.len()
+        );
+    }
+
+    #[test]
+    fn test_contiguous_writes_split_on_gaps_and_overwrites() {
+        let entry = |lba: u64, byte: u8| JournalEntry {
+            lba_range: LbaRange::new(lba, lba + 1),
+            data: vec![byte; 512],
+            timestamp: Utc::now(),
+        };
+        let writes = contiguous_writes(&[entry(0, 1), entry(1, 2), entry(5, 3), entry(1, 4)]);
+        let summary: Vec<(u64, usize)> = writes.iter().map(|(l, d)| (*l, d.len())).collect();
+        assert_eq!(summary, vec![(0, 1024), (5, 512), (1, 512)]);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_chunks_are_stored_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = DedupIndex::open(dir.path(), false).unwrap();
+
+        // Two chunks, the second repeating the first
+        let chunk = noise(1, 4096);
+        let data = [chunk.clone(), chunk.clone()].concat();
+        let unique = write(&index, "vm-a", 0, data).await;
+        assert_eq!(unique.len(), 1);
+        assert_eq!(unique[0].data, chunk);
+
+        // Another volume with the same image stores nothing new
+        let unique = write(
+            &index,
+            "vm-b",
+            100,
+            [chunk.clone(), noise(2, 4096)].concat(),
+        )
+        .await;
+        assert_eq!(unique.len(), 1);
+        assert_eq!(unique[0].lba_range.start_lba, 8);
+
+        let stats = index.stats(POLICY).unwrap();
+        assert_eq!(stats.chunks, 2);
+        assert_eq!(stats.logical_bytes, 4 * 4096);
+        assert_eq!(stats.stored_bytes, 2 * 4096);
+        assert_eq!(stats.ratio(), Some(2.0));
+
+        let resolved = index.resolve("vm-b", &LbaRange::new(102, 110));
+        assert_eq!(
+            resolved,
+            vec![
+                ResolvedExtent {
+                    start_lba: 102,
+                    lba_count: 6,
+                    chunk_volume: chunk_volume_id(POLICY),
+                    chunk_lba: 2,
+                },
+                ResolvedExtent {
+                    start_lba: 108,
+                    lba_count: 2,
+                    chunk_volume: chunk_volume_id(POLICY),
+                    chunk_lba: 8,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_overwrites_release_unreferenced_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = DedupIndex::open(dir.path(), false).unwrap();
+
+        write(&index, "vm-a", 0, noise(1, 8192)).await;
+        assert_eq!(index.stats(POLICY).unwrap().chunks, 2);
+
+        // Overwrite the middle of the first chunk: it stays referenced by
+        // the head and tail that remain
+        write(&index, "vm-a", 2, noise(3, 4096)).await;
+        let stats = index.stats(POLICY).unwrap();
+        assert_eq!(stats.chunks, 3);
+        assert_eq!(stats.logical_bytes, 8192);
+        let resolved = index.resolve("vm-a", &LbaRange::new(0, 16));
+        let summary: Vec<(u64, u64, u64)> = resolved
+            .iter()
+            .map(|r| (r.start_lba, r.lba_count, r.chunk_lba))
+            .collect();
+        assert_eq!(summary, vec![(0, 2, 0), (2, 8, 16), (10, 6, 10)]);
+
+        // Overwriting everything drops the original chunks
+        write(&index, "vm-a", 0, vec![0u8; 8192]).await;
+        let stats = index.stats(POLICY).unwrap();
+        assert_eq!(stats.chunks, 1);
+        assert_eq!(stats.logical_bytes, 8192);
+        assert_eq!(stats.stored_bytes, 4096);
+    }
+
+    #[tokio::test]
+    async fn test_released_chunk_space_is_reused() {
+        let dir = tempfile::tempdir().unwrap();
+        let chunk_volume = chunk_volume_id(POLICY);
+        let free = |index: &DedupIndex| -> Vec<(u64, u64)> {
+            index
+                .free_chunk_ranges(&chunk_volume)
+                .iter()
+                .map(|r| (r.start_lba, r.end_lba))
+                .collect()
+        };
+        {
+            let index = DedupIndex::open(dir.path(), false).unwrap();
+            write(&index, "vm-a", 0, noise(1, 8192)).await;
+            write(&index, "vm-a", 16, noise(2, 4096)).await;
+            assert!(free(&index).is_empty());
+
+            // Overwriting the first write frees chunk LBAs 0..16
+            write(&index, "vm-a", 0, noise(3, 8192)).await;
+            assert_eq!(free(&index), vec![(0, 16)]);
+            assert!(index.free_chunk_ranges("vm-a").is_empty());
+
+            // New chunks fill the hole before growing the chunk volume
+            let unique = write(&index, "vm-b", 0, noise(4, 4096)).await;
+            assert_eq!(unique[0].lba_range.start_lba, 0);
+            assert_eq!(free(&index), vec![(8, 16)]);
+        }
+
+        // The free list is rebuilt from the chunks on reopen
+        let index = DedupIndex::open(dir.path(), false).unwrap();
+        assert_eq!(free(&index), vec![(8, 16)]);
+        let unique = write(&index, "vm-c", 0, noise(5, 8192)).await;
+        let placed: Vec<u64> = unique.iter().map(|e| e.lba_range.start_lba).collect();
+        assert_eq!(placed, vec![8, 40]);
+    }
+
+    #[tokio::test]
+    async fn test_uncommitted_reservations_are_returned() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = DedupIndex::open(dir.path(), false).unwrap();
+        let chunk_volume = chunk_volume_id(POLICY);
+        let free = |index: &DedupIndex| -> Vec<(u64, u64)> {
+            index
+                .free_chunk_ranges(&chunk_volume)
+                .iter()
+                .map(|r| (r.start_lba, r.end_lba))
+                .collect()
+        };
+        let config = fixed(4096);
+        write(&index, "vm-a", 0, noise(1, 4096)).await;
+
+        // A plan dropped without committing hands its room back
+        let dropped = ChunkedWrite::new(0, noise(2, 8192), &config);
+        drop(index.plan("vm-b", POLICY, &[dropped]).unwrap());
+        assert_eq!(free(&index), vec![(8, 24)]);
+
+        // Two plans racing to store the same chunk: the loser's room is freed
+        let chunked = ChunkedWrite::new(0, noise(3, 4096), &config);
+        let first = index
+            .plan("vm-c", POLICY, std::slice::from_ref(&chunked))
+            .unwrap();
+        let second = index.plan("vm-d", POLICY, &[chunked]).unwrap();
+        assert_eq!(first.unique[0].lba_range.start_lba, 8);
+        assert_eq!(second.unique[0].lba_range.start_lba, 16);
+        index.commit(first).await.unwrap();
+        index.commit(second).await.unwrap();
+        assert_eq!(free(&index), vec![(16, 24)]);
+        assert_eq!(index.stats(POLICY).unwrap().chunks, 2);
+    }
+
+    #[tokio::test]
+    async fn test_replay_and_compaction_restore_index() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let index = DedupIndex::open(dir.path(), true).unwrap();
+            write(&index, "vm-a", 0, noise(1, 8192)).await;
+            write(&index, "vm-b", 0, noise(1, 8192)).await;
+            write(&index, "vm-a", 8, noise(2, 4096)).await;
+        }
+
+        let index = DedupIndex::open(dir.path(), false).unwrap();
+        let before = index.stats(POLICY).unwrap();
+        assert_eq!(before.chunks, 3);
+        let resolved = index.resolve("vm-a", &LbaRange::new(0, 24));
+
+        index.compact().await.unwrap();
+        drop(index);
+        let index = DedupIndex::open(dir.path(), false).unwrap();
+        assert_eq!(index.stats(POLICY).unwrap(), before);
+        assert_eq!(index.resolve("vm-a", &LbaRange::new(0, 24)), resolved);
+
+        // New chunks keep landing after the old ones
+        let unique = write(&index, "vm-c", 0, noise(9, 4096)).await;
+        assert_eq!(unique[0].lba_range.start_lba, 24);
+    }
+
+    #[tokio::test]
+    async fn test_torn_commit_is_dropped_on_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let index = DedupIndex::open(dir.path(), false).unwrap();
+            write(&index, "vm-a", 0, noise(1, 4096)).await;
+        }
+
+        // A chunk record whose extent never made it to disk
+        let chunk = LogRecord::Chunk {
+            policy: POLICY.to_string(),
+            fingerprint: Fingerprint::of(b"orphan").to_hex(),
+            chunk_lba: 8,
+            lba_count: 8,
+        };
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(dir.path().join(LOG_FILE))
+            .unwrap();
+        file.write_all(encode_record(&chunk).unwrap().as_bytes())
+            .unwrap();
+        file.write_all(b"0badc0de {\"op\":\"map\"").unwrap();
+
+        let index = DedupIndex::open(dir.path(), false).unwrap();
+        assert_eq!(index.stats(POLICY).unwrap().chunks, 1);
+        assert_eq!(index.resolve("vm-a", &LbaRange::new(0, 8)).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_plan_rejects_policy_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = DedupIndex::open(dir.path(), false).unwrap();
+        let chunked = ChunkedWrite::new(0, noise(1, 4096), &fixed(4096));
+        let plan = index
+            .plan("vm-a", POLICY, std::slice::from_ref(&chunked))
+            .unwrap();
+        index.commit(plan).await.unwrap();
+        assert!(index.plan("vm-a", "other", &[chunked]).is_err());
+    }
+}
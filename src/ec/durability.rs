@@ -0,0 +1,337 @@
+//! Durability Estimation for EC Policies
+//!
+//! Estimates the mean time to data loss (MTTDL) of a k+m layout with the
+//! classic Markov approximation: data is lost when more failure domains fail
+//! than the code tolerates before the first failure has been repaired.
+//!
+//! ```text
+//! MTTDL = μ^f / (λ^(f+1) · D · (D-1) · … · (D-f))
+//! ```
+//!
+//! where `D` is the number of failure domains (nodes hosting eligible pools),
+//! `f` the number of whole-domain failures the layout survives, `λ` the
+//! failure rate of one domain and `μ = 1 / repair time`. When there are
+//! fewer domains than shards, one domain holds several shards of a stripe
+//! and `f` shrinks accordingly.
+
+use crate::crd::{DiskPool, LabelSelector};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+const HOURS_PER_YEAR: f64 = 8760.0;
+
+// =============================================================================
+// Configuration
+// =============================================================================
+
+/// Inputs to the durability model that are not observed from the cluster
+#[derive(Debug, Clone)]
+pub struct DurabilityConfig {
+    /// Annualized failure rate of a single disk (0.02 = 2% per year)
+    pub disk_afr: f64,
+
+    /// Annual data-loss probability above which a policy is flagged
+    pub target_annual_loss_probability: f64,
+
+    /// Rebuild throughput assumed until rebuilds have been observed
+    pub default_rebuild_bytes_per_sec: f64,
+
+    /// Delay before a failure is acted on (the shard failure grace period)
+    pub detection_delay: Duration,
+}
+
+impl Default for DurabilityConfig {
+    fn default() -> Self {
+        Self {
+            disk_afr: 0.02,
+            target_annual_loss_probability: 1e-6,
+            default_rebuild_bytes_per_sec: 50.0 * 1024.0 * 1024.0,
+            detection_delay: Duration::from_secs(300),
+        }
+    }
+}
+
+// =============================================================================
+// Failure Domains
+// =============================================================================
+
+/// Pools that fail together (all pools on one node)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailureDomain {
+    /// Node name
+    pub name: String,
+
+    /// Disks backing the domain's pools
+    pub disks: usize,
+
+    /// Bytes stored on the domain's pools
+    pub used_bytes: u64,
+}
+
+/// Group the online pools matching any of `selectors` by node
+///
+/// With no selectors, every online pool is eligible.
+pub fn failure_domains(pools: &[DiskPool], selectors: &[&LabelSelector]) -> Vec<FailureDomain> {
+    let mut domains: BTreeMap<String, FailureDomain> = BTreeMap::new();
+
+    for pool in pools {
+        if !pool.is_online() {
+            continue;
+        }
+        let labels = pool.labels();
+        if !selectors.is_empty() && !selectors.iter().any(|s| s.matches(&labels)) {
+            continue;
+        }
+
+        let domain = domains
+            .entry(pool.spec.node.clone())
+            .or_insert_with(|| FailureDomain {
+                name: pool.spec.node.clone(),
+                disks: 0,
+                used_bytes: 0,
+            });
+        domain.disks += pool.spec.disks.len().max(1);
+        domain.used_bytes += pool.status.as_ref().map_or(0, |s| s.used);
+    }
+
+    domains.into_values().collect()
+}
+
+// =============================================================================
+// Estimate
+// =============================================================================
+
+/// Durability estimate for one policy
+#[derive(Debug, Clone, PartialEq)]
+pub struct DurabilityEstimate {
+    /// Failure domains the shards can be spread over
+    pub failure_domains: usize,
+
+    /// Whole-domain failures a stripe survives
+    pub tolerated_domain_failures: usize,
+
+    /// Time to detect and repair a failed domain, in hours
+    pub repair_hours: f64,
+
+    /// Mean time to data loss, in hours
+    pub mttdl_hours: f64,
+
+    /// Probability of losing data within a year
+    pub annual_loss_probability: f64,
+}
+
+impl DurabilityEstimate {
+    /// Durability as a number of nines (e.g. 11.0 for 99.999999999%)
+    pub fn nines(&self) -> f64 {
+        -self.annual_loss_probability.log10()
+    }
+
+    /// Whether the loss probability is within `target`
+    pub fn meets_target(&self, target: f64) -> bool {
+        self.annual_loss_probability <= target
+    }
+}
+
+/// Estimate the durability of a k+m layout over `domains`
+///
+/// Returns None if there is no failure domain to place shards on.
+pub fn estimate(
+    config: &DurabilityConfig,
+    data_shards: usize,
+    parity_shards: usize,
+    domains: &[FailureDomain],
+    rebuild_bytes_per_sec: Option<f64>,
+) -> Option<DurabilityEstimate> {
+    let domain_count = domains.len();
+    if domain_count == 0 || data_shards == 0 {
+        return None;
+    }
+
+    // A domain holding several shards of a stripe loses them all at once
+    let total_shards = data_shards + parity_shards;
+    let shards_per_domain = total_shards.div_ceil(domain_count);
+    let tolerated = parity_shards / shards_per_domain;
+
+    // Per-domain failure rate from the disk AFR, averaged over domains
+    let disk_rate = -(1.0 - config.disk_afr.clamp(0.0, 0.999_999)).ln() / HOURS_PER_YEAR;
+    let avg_disks =
+        domains.iter().map(|d| d.disks.max(1)).sum::<usize>() as f64 / domain_count as f64;
+    let lambda = disk_rate * avg_disks;
+
+    // Repair re-creates the busiest domain's data at the rebuild throughput
+    let throughput = rebuild_bytes_per_sec
+        .filter(|t| *t > 0.0)
+        .unwrap_or(config.default_rebuild_bytes_per_sec)
+        .max(1.0);
+    let max_used = domains.iter().map(|d| d.used_bytes).max().unwrap_or(0) as f64;
+    let repair_secs = (config.detection_delay.as_secs_f64() + max_used / throughput).max(1.0);
+    let repair_hours = repair_secs / 3600.0;
+
+    let mttdl_hours = if lambda <= 0.0 {
+        f64::INFINITY
+    } else {
+        let mu = 1.0 / repair_hours;
+        let ways: f64 = (0..=tolerated).map(|i| (domain_count - i) as f64).product();
+        mu.powi(tolerated as i32) / (lambda.powi(tolerated as i32 + 1) * ways)
+    };
+
+    Some(DurabilityEstimate {
+        failure_domains: domain_count,
+        tolerated_domain_failures: tolerated,
+        repair_hours,
+        mttdl_hours,
+        annual_loss_probability: -(-HOURS_PER_YEAR / mttdl_hours).exp_m1(),
+    })
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crd::{DiskPoolSpec, DiskPoolStatus, PoolState};
+
+    fn domains(count: usize, used_bytes: u64) -> Vec<FailureDomain> {
+        (0..count)
+            .map(|i| FailureDomain {
+                name: format!("node-{}", i),
+                disks: 1,
+                used_bytes,
+            })
+            .collect()
+    }
+
+    fn config() -> DurabilityConfig {
+        DurabilityConfig {
+            disk_afr: 0.02,
+            target_annual_loss_probability: 1e-6,
+            default_rebuild_bytes_per_sec: 100.0 * 1024.0 * 1024.0,
+            detection_delay: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_durability_config_default() {
+        let config = DurabilityConfig::default();
+        assert_eq!(config.disk_afr, 0.02);
+        assert_eq!(config.target_annual_loss_probability, 1e-6);
+        assert_eq!(config.detection_delay, Duration::from_secs(300));
+    }
+
+    #[test]
+    fn test_estimate_matches_closed_form() {
+        // 1 TiB per domain at 100 MiB/s takes 10485.76 s to repair
+        let tib = 1024u64 * 1024 * 1024 * 1024;
+        let estimate = estimate(&config(), 4, 2, &domains(8, tib), None).unwrap();
+
+        let lambda = -(0.98f64).ln() / 8760.0;
+        let mu = 3600.0 / 10485.76;
+        let expected = mu * mu / (lambda.powi(3) * 8.0 * 7.0 * 6.0);
+
+        assert_eq!(estimate.failure_domains, 8);
+        assert_eq!(estimate.tolerated_domain_failures, 2);
+        assert!((estimate.repair_hours - 10485.76 / 3600.0).abs() < 1e-9);
+        assert!((estimate.mttdl_hours / expected - 1.0).abs() < 1e-9);
+        assert!(estimate.annual_loss_probability > 0.0);
+        assert!(estimate.nines() > 6.0);
+        assert!(estimate.meets_target(1e-6));
+    }
+
+    #[test]
+    fn test_estimate_shared_domains_reduce_tolerance() {
+        let tib = 1u64 << 40;
+
+        // 4+2 over three nodes: each node holds two shards of a stripe
+        let three = estimate(&config(), 4, 2, &domains(3, tib), None).unwrap();
+        assert_eq!(three.tolerated_domain_failures, 1);
+
+        // ... and over two nodes a single node failure loses data
+        let two = estimate(&config(), 4, 2, &domains(2, tib), None).unwrap();
+        assert_eq!(two.tolerated_domain_failures, 0);
+        assert!(!two.meets_target(1e-6));
+
+        let six = estimate(&config(), 4, 2, &domains(6, tib), None).unwrap();
+        assert!(two.mttdl_hours < three.mttdl_hours);
+        assert!(three.mttdl_hours < six.mttdl_hours);
+    }
+
+    #[test]
+    fn test_estimate_depends_on_parity_and_rebuild_speed() {
+        let tib = 1u64 << 40;
+        let d = domains(12, tib);
+
+        let m2 = estimate(&config(), 8, 2, &d, None).unwrap();
+        let m3 = estimate(&config(), 8, 3, &d, None).unwrap();
+        assert!(m3.mttdl_hours > m2.mttdl_hours);
+
+        let slow = estimate(&config(), 8, 2, &d, Some(10.0 * 1024.0 * 1024.0)).unwrap();
+        assert!(slow.repair_hours > m2.repair_hours);
+        assert!(slow.annual_loss_probability > m2.annual_loss_probability);
+
+        // The detection delay adds to every repair
+        let delayed = DurabilityConfig {
+            detection_delay: Duration::from_secs(3600),
+            ..config()
+        };
+        let late = estimate(&delayed, 8, 2, &d, None).unwrap();
+        assert!((late.repair_hours - m2.repair_hours - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_estimate_without_domains() {
+        assert!(estimate(&config(), 4, 2, &[], None).is_none());
+    }
+
+    #[test]
+    fn test_failure_domains_group_pools_by_node() {
+        let pool = |name: &str, node: &str, tier: &str, state: PoolState| {
+            let mut pool = DiskPool::new(
+                name,
+                DiskPoolSpec {
+                    node: node.to_string(),
+                    disks: vec!["/dev/sda".to_string(), "/dev/sdb".to_string()],
+                },
+            );
+            pool.metadata.labels = Some([("tier".to_string(), tier.to_string())].into());
+            pool.status = Some(DiskPoolStatus {
+                state,
+                used: 100,
+                ..Default::default()
+            });
+            pool
+        };
+        let pools = vec![
+            pool("cold-a1", "node-a", "cold", PoolState::Online),
+            pool("cold-a2", "node-a", "cold", PoolState::Online),
+            pool("cold-b1", "node-b", "cold", PoolState::Online),
+            pool("cold-c1", "node-c", "cold", PoolState::Faulted),
+            pool("hot-d1", "node-d", "hot", PoolState::Online),
+        ];
+        let cold = LabelSelector {
+            match_labels: [("tier".to_string(), "cold".to_string())].into(),
+            ..Default::default()
+        };
+
+        let selected = failure_domains(&pools, &[&cold]);
+        assert_eq!(
+            selected,
+            vec![
+                FailureDomain {
+                    name: "node-a".to_string(),
+                    disks: 4,
+                    used_bytes: 200,
+                },
+                FailureDomain {
+                    name: "node-b".to_string(),
+                    disks: 2,
+                    used_bytes: 100,
+                },
+            ]
+        );
+
+        // Without a selector every online pool counts
+        assert_eq!(failure_domains(&pools, &[]).len(), 3);
+    }
+}
@@ -169,6 +169,70 @@ impl EcEncoder {
 
         Ok(result)
     }
+
+    /// Update parity shards for a change to a single data shard
+    ///
+    /// Applies the parity delta for `old_data` -> `new_data` at `shard_index`
+    /// without reading the other data shards. This is the pure-Rust
+    /// counterpart of ISA-L's `ec_encode_data_update`.
+    ///
+    /// # Arguments
+    /// * `shard_index` - Index of the data shard being changed
+    /// * `old_data` - Current contents of the data shard
+    /// * `new_data` - New contents of the data shard
+    /// * `parity` - The stripe's parity shards, updated in place
+    #[instrument(skip(self, old_data, new_data, parity))]
+    pub fn update_parity(
+        &self,
+        shard_index: usize,
+        old_data: &[u8],
+        new_data: &[u8],
+        parity: &mut [Vec<u8>],
+    ) -> Result<()> {
+        if shard_index >= self.data_shards {
+            return Err(Error::InvalidEcConfig(format!(
+                "Data shard index {} out of range (k={})",
+                shard_index, self.data_shards
+            )));
+        }
+        if parity.len() != self.parity_shards {
+            return Err(Error::InvalidEcConfig(format!(
+                "Expected {} parity shards, got {}",
+                self.parity_shards,
+                parity.len()
+            )));
+        }
+        let shard_size = old_data.len();
+        if new_data.len() != shard_size || parity.iter().any(|p| p.len() != shard_size) {
+            return Err(Error::InvalidEcConfig(format!(
+                "All shards must be {} bytes for a parity update",
+                shard_size
+            )));
+        }
+
+        let delta: Vec<u8> = old_data
+            .iter()
+            .zip(new_data)
+            .map(|(old, new)| old ^ new)
+            .collect();
+        if delta.iter().all(|&b| b == 0) {
+            return Ok(());
+        }
+
+        // The code is linear, so encoding the delta alone yields the parity delta
+        let mut parity_delta = vec![vec![0u8; shard_size]; self.parity_shards];
+        self.rs
+            .encode_single_sep(shard_index, &delta, &mut parity_delta)
+            .map_err(|e| Error::EcEncodingFailed(format!("Parity delta failed: {}", e)))?;
+
+        for (shard, shard_delta) in parity.iter_mut().zip(&parity_delta) {
+            for (byte, d) in shard.iter_mut().zip(shard_delta) {
+                *byte ^= d;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 // =============================================================================
@@ -524,6 +588,44 @@ mod tests {
         assert!((storage_efficiency(10, 2) - 0.833).abs() < 0.01);
     }
 
+    #[test]
+    fn test_update_parity_matches_full_encode() {
+        let encoder = EcEncoder::new(4, 2).unwrap();
+        let mut data: Vec<u8> = (0..4000u32).map(|i| (i * 7 % 256) as u8).collect();
+        let shards = encoder.encode(&data).unwrap();
+        let mut parity = shards[4..].to_vec();
+
+        // Overwrite part of data shard 2 (0 exercises the overwrite path too)
+        for index in [2usize, 0] {
+            let old = shards[index].clone();
+            let mut new = old.clone();
+            new[10..50].fill(0xAB);
+            encoder
+                .update_parity(index, &old, &new, &mut parity)
+                .unwrap();
+            data[index * 1000 + 10..index * 1000 + 50].fill(0xAB);
+        }
+
+        let expected = encoder.encode(&data).unwrap();
+        assert_eq!(parity, expected[4..].to_vec());
+    }
+
+    #[test]
+    fn test_update_parity_rejects_bad_input() {
+        let encoder = EcEncoder::new(4, 2).unwrap();
+        let mut parity = vec![vec![0u8; 8]; 2];
+
+        assert!(encoder
+            .update_parity(4, &[0; 8], &[1; 8], &mut parity)
+            .is_err());
+        assert!(encoder
+            .update_parity(0, &[0; 8], &[1; 4], &mut parity)
+            .is_err());
+        assert!(encoder
+            .update_parity(0, &[0; 8], &[1; 8], &mut parity[..1])
+            .is_err());
+    }
+
     #[test]
     fn test_create_codec() {
         let (encoder, decoder) = create_codec(4, 2).unwrap();
@@ -569,8 +671,8 @@ mod tests {
             let mut optional: Vec<Option<Vec<u8>>> = shards.into_iter().map(Some).collect();
 
             // Remove m shards
-            for i in 0..m {
-                optional[i] = None;
+            for shard in optional.iter_mut().take(m) {
+                *shard = None;
             }
 
             let recovered = decoder.decode(&mut optional, data.len()).unwrap();
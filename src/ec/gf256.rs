@@ -0,0 +1,608 @@
+//! GF(2^8) Arithmetic with SIMD Kernels
+//!
+//! Field arithmetic over GF(2^8) with the generating polynomial
+//! x^8 + x^4 + x^3 + x^2 + 1 (0x11D), the field used by
+//! `reed-solomon-erasure`, so matrices built here match the ones it uses.
+//!
+//! The hot loop of Reed-Solomon coding is `out ^= c * input` over whole
+//! shards. It is vectorised with split tables: `c * x` is the XOR of
+//! `c * (x & 0x0F)` and `c * (x & 0xF0)`, and both 16-entry tables fit in a
+//! single byte-shuffle register (`pshufb` on SSSE3/AVX2, `tbl` on NEON).
+//! The best kernel is picked at runtime with [`GfSimdLevel::detect`].
+
+use crate::error::{Error, Result};
+
+/// Generating polynomial (without the x^8 term)
+const POLYNOMIAL: u16 = 0x1D;
+
+/// log table: LOG[x] = n such that 2^n = x (LOG[0] is unused)
+static LOG: [u8; 256] = build_log_table();
+
+/// exp table, doubled so `EXP[LOG[a] + LOG[b]]` needs no reduction
+static EXP: [u8; 510] = build_exp_table();
+
+const fn build_exp_table() -> [u8; 510] {
+    let mut table = [0u8; 510];
+    let mut x: u16 = 1;
+    let mut i = 0;
+    while i < 255 {
+        table[i] = x as u8;
+        table[i + 255] = x as u8;
+        x <<= 1;
+        if x & 0x100 != 0 {
+            x ^= 0x100 | POLYNOMIAL;
+        }
+        i += 1;
+    }
+    table
+}
+
+const fn build_log_table() -> [u8; 256] {
+    let exp = build_exp_table();
+    let mut table = [0u8; 256];
+    let mut i = 0;
+    while i < 255 {
+        table[exp[i] as usize] = i as u8;
+        i += 1;
+    }
+    table
+}
+
+// =============================================================================
+// Field Operations
+// =============================================================================
+
+/// Multiply two field elements
+#[inline]
+pub fn mul(a: u8, b: u8) -> u8 {
+    if a == 0 || b == 0 {
+        0
+    } else {
+        EXP[LOG[a as usize] as usize + LOG[b as usize] as usize]
+    }
+}
+
+/// Divide `a` by `b`; `b` must not be zero
+#[inline]
+pub fn div(a: u8, b: u8) -> u8 {
+    assert!(b != 0, "division by zero in GF(2^8)");
+    if a == 0 {
+        0
+    } else {
+        EXP[LOG[a as usize] as usize + 255 - LOG[b as usize] as usize]
+    }
+}
+
+/// Raise `a` to the power `n`
+pub fn exp(a: u8, n: usize) -> u8 {
+    if n == 0 {
+        1
+    } else if a == 0 {
+        0
+    } else {
+        EXP[(LOG[a as usize] as usize * n) % 255]
+    }
+}
+
+// =============================================================================
+// Matrices
+// =============================================================================
+
+/// Row-major matrix over GF(2^8)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GfMatrix {
+    rows: usize,
+    cols: usize,
+    data: Vec<u8>,
+}
+
+impl GfMatrix {
+    /// All-zero matrix
+    pub fn zero(rows: usize, cols: usize) -> Self {
+        Self {
+            rows,
+            cols,
+            data: vec![0; rows * cols],
+        }
+    }
+
+    /// Identity matrix
+    pub fn identity(size: usize) -> Self {
+        let mut m = Self::zero(size, size);
+        for i in 0..size {
+            m.set(i, i, 1);
+        }
+        m
+    }
+
+    /// Vandermonde matrix with row `r`, column `c` = r^c
+    pub fn vandermonde(rows: usize, cols: usize) -> Self {
+        let mut m = Self::zero(rows, cols);
+        for r in 0..rows {
+            for c in 0..cols {
+                m.set(r, c, exp(r as u8, c));
+            }
+        }
+        m
+    }
+
+    /// Systematic Reed-Solomon encoding matrix for k+m shards
+    ///
+    /// Built exactly like `reed-solomon-erasure`: a `total x k` Vandermonde
+    /// matrix multiplied by the inverse of its top `k x k` square, so the
+    /// top rows are the identity and the rest generate parity.
+    pub fn systematic(data_shards: usize, total_shards: usize) -> Result<Self> {
+        let vandermonde = Self::vandermonde(total_shards, data_shards);
+        let top = vandermonde.select_rows(&(0..data_shards).collect::<Vec<_>>());
+        let inverse = top.invert().ok_or_else(|| {
+            Error::InvalidEcConfig(format!(
+                "no systematic matrix for {} of {} shards",
+                data_shards, total_shards
+            ))
+        })?;
+        Ok(vandermonde.multiply(&inverse))
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    #[inline]
+    pub fn get(&self, r: usize, c: usize) -> u8 {
+        self.data[r * self.cols + c]
+    }
+
+    #[inline]
+    pub fn set(&mut self, r: usize, c: usize, value: u8) {
+        self.data[r * self.cols + c] = value;
+    }
+
+    /// One row as a slice
+    pub fn row(&self, r: usize) -> &[u8] {
+        &self.data[r * self.cols..(r + 1) * self.cols]
+    }
+
+    /// Matrix made of the given rows, in order
+    pub fn select_rows(&self, rows: &[usize]) -> Self {
+        let mut m = Self::zero(rows.len(), self.cols);
+        for (i, &r) in rows.iter().enumerate() {
+            m.data[i * self.cols..(i + 1) * self.cols].copy_from_slice(self.row(r));
+        }
+        m
+    }
+
+    /// Matrix product `self * rhs`
+    pub fn multiply(&self, rhs: &Self) -> Self {
+        assert_eq!(self.cols, rhs.rows, "matrix dimensions do not match");
+        let mut m = Self::zero(self.rows, rhs.cols);
+        for r in 0..self.rows {
+            for c in 0..rhs.cols {
+                let mut value = 0;
+                for i in 0..self.cols {
+                    value ^= mul(self.get(r, i), rhs.get(i, c));
+                }
+                m.set(r, c, value);
+            }
+        }
+        m
+    }
+
+    /// Inverse by Gauss-Jordan elimination, or None if it is singular
+    pub fn invert(&self) -> Option<Self> {
+        assert_eq!(self.rows, self.cols, "only square matrices can be inverted");
+        let n = self.rows;
+        let mut work = self.clone();
+        let mut inverse = Self::identity(n);
+
+        for col in 0..n {
+            let pivot = (col..n).find(|&r| work.get(r, col) != 0)?;
+            if pivot != col {
+                work.swap_rows(pivot, col);
+                inverse.swap_rows(pivot, col);
+            }
+
+            let scale = div(1, work.get(col, col));
+            work.scale_row(col, scale);
+            inverse.scale_row(col, scale);
+
+            for r in 0..n {
+                let factor = work.get(r, col);
+                if r != col && factor != 0 {
+                    work.add_scaled_row(r, col, factor);
+                    inverse.add_scaled_row(r, col, factor);
+                }
+            }
+        }
+        Some(inverse)
+    }
+
+    fn swap_rows(&mut self, a: usize, b: usize) {
+        for c in 0..self.cols {
+            self.data.swap(a * self.cols + c, b * self.cols + c);
+        }
+    }
+
+    fn scale_row(&mut self, r: usize, factor: u8) {
+        for c in 0..self.cols {
+            let value = mul(self.get(r, c), factor);
+            self.set(r, c, value);
+        }
+    }
+
+    /// row[dst] ^= factor * row[src]
+    fn add_scaled_row(&mut self, dst: usize, src: usize, factor: u8) {
+        for c in 0..self.cols {
+            let value = self.get(dst, c) ^ mul(self.get(src, c), factor);
+            self.set(dst, c, value);
+        }
+    }
+}
+
+// =============================================================================
+// SIMD Level
+// =============================================================================
+
+/// Instruction set used for bulk GF(2^8) multiplication
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GfSimdLevel {
+    /// Portable table lookups
+    Scalar,
+    /// x86 SSSE3 `pshufb`, 16 bytes per step
+    Ssse3,
+    /// x86 AVX2 `vpshufb`, 32 bytes per step
+    Avx2,
+    /// AArch64 NEON `tbl`, 16 bytes per step
+    Neon,
+}
+
+impl GfSimdLevel {
+    /// Detect the best level supported by this CPU
+    pub fn detect() -> Self {
+        [Self::Avx2, Self::Ssse3, Self::Neon]
+            .into_iter()
+            .find(|level| level.is_supported())
+            .unwrap_or(Self::Scalar)
+    }
+
+    /// Whether this CPU can run the level's kernel
+    pub fn is_supported(self) -> bool {
+        match self {
+            Self::Scalar => true,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Self::Ssse3 => std::arch::is_x86_feature_detected!("ssse3"),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Self::Avx2 => std::arch::is_x86_feature_detected!("avx2"),
+            #[cfg(target_arch = "aarch64")]
+            Self::Neon => std::arch::is_aarch64_feature_detected!("neon"),
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+
+    /// Every level this CPU supports, scalar first
+    pub fn supported() -> Vec<Self> {
+        [Self::Scalar, Self::Ssse3, Self::Avx2, Self::Neon]
+            .into_iter()
+            .filter(|level| level.is_supported())
+            .collect()
+    }
+}
+
+impl std::fmt::Display for GfSimdLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GfSimdLevel::Scalar => write!(f, "Scalar"),
+            GfSimdLevel::Ssse3 => write!(f, "SSSE3 (128-bit)"),
+            GfSimdLevel::Avx2 => write!(f, "AVX2 (256-bit)"),
+            GfSimdLevel::Neon => write!(f, "NEON (128-bit)"),
+        }
+    }
+}
+
+// =============================================================================
+// Bulk Multiplication
+// =============================================================================
+
+/// Split multiplication tables for one coefficient
+///
+/// `low[x] = c * x` and `high[x] = c * (x << 4)` for x in 0..16.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MulTable {
+    coefficient: u8,
+    low: [u8; 16],
+    high: [u8; 16],
+}
+
+impl MulTable {
+    pub fn new(coefficient: u8) -> Self {
+        let mut low = [0u8; 16];
+        let mut high = [0u8; 16];
+        for x in 0..16u8 {
+            low[x as usize] = mul(coefficient, x);
+            high[x as usize] = mul(coefficient, x << 4);
+        }
+        Self {
+            coefficient,
+            low,
+            high,
+        }
+    }
+
+    pub fn coefficient(&self) -> u8 {
+        self.coefficient
+    }
+
+    #[inline]
+    fn apply(&self, x: u8) -> u8 {
+        self.low[(x & 0x0F) as usize] ^ self.high[(x >> 4) as usize]
+    }
+}
+
+/// `out ^= c * input` (or `out = c * input` when `overwrite`)
+///
+/// `level` must be supported by the CPU; `input` and `out` must have the
+/// same length.
+pub fn mul_slice(
+    level: GfSimdLevel,
+    table: &MulTable,
+    input: &[u8],
+    out: &mut [u8],
+    overwrite: bool,
+) {
+    assert_eq!(input.len(), out.len(), "slice lengths differ");
+
+    // Trivial coefficients need no tables
+    match (table.coefficient, overwrite) {
+        (0, true) => return out.fill(0),
+        (0, false) => return,
+        (1, true) => return out.copy_from_slice(input),
+        (1, false) => {
+            for (o, i) in out.iter_mut().zip(input) {
+                *o ^= i;
+            }
+            return;
+        }
+        _ => {}
+    }
+
+    let done = match level {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        GfSimdLevel::Avx2 if level.is_supported() => {
+            // SAFETY: AVX2 support was checked at runtime
+            unsafe { x86::mul_avx2(table, input, out, overwrite) }
+        }
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        GfSimdLevel::Ssse3 if level.is_supported() => {
+            // SAFETY: SSSE3 support was checked at runtime
+            unsafe { x86::mul_ssse3(table, input, out, overwrite) }
+        }
+        #[cfg(target_arch = "aarch64")]
+        GfSimdLevel::Neon if level.is_supported() => {
+            // SAFETY: NEON support was checked at runtime
+            unsafe { neon::mul_neon(table, input, out, overwrite) }
+        }
+        _ => 0,
+    };
+
+    mul_scalar(table, &input[done..], &mut out[done..], overwrite);
+}
+
+fn mul_scalar(table: &MulTable, input: &[u8], out: &mut [u8], overwrite: bool) {
+    if overwrite {
+        for (o, &i) in out.iter_mut().zip(input) {
+            *o = table.apply(i);
+        }
+    } else {
+        for (o, &i) in out.iter_mut().zip(input) {
+            *o ^= table.apply(i);
+        }
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod x86 {
+    use super::MulTable;
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    /// Returns the number of bytes processed (a multiple of 32)
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn mul_avx2(
+        table: &MulTable,
+        input: &[u8],
+        out: &mut [u8],
+        overwrite: bool,
+    ) -> usize {
+        let low = _mm256_broadcastsi128_si256(_mm_loadu_si128(table.low.as_ptr().cast()));
+        let high = _mm256_broadcastsi128_si256(_mm_loadu_si128(table.high.as_ptr().cast()));
+        let mask = _mm256_set1_epi8(0x0F);
+
+        let blocks = input.len() / 32;
+        for b in 0..blocks {
+            let src = input.as_ptr().add(b * 32).cast::<__m256i>();
+            let dst = out.as_mut_ptr().add(b * 32).cast::<__m256i>();
+
+            let x = _mm256_loadu_si256(src);
+            let lo = _mm256_and_si256(x, mask);
+            let hi = _mm256_and_si256(_mm256_srli_epi64(x, 4), mask);
+            let mut product =
+                _mm256_xor_si256(_mm256_shuffle_epi8(low, lo), _mm256_shuffle_epi8(high, hi));
+            if !overwrite {
+                product = _mm256_xor_si256(product, _mm256_loadu_si256(dst));
+            }
+            _mm256_storeu_si256(dst, product);
+        }
+        blocks * 32
+    }
+
+    /// Returns the number of bytes processed (a multiple of 16)
+    #[target_feature(enable = "ssse3")]
+    pub(super) unsafe fn mul_ssse3(
+        table: &MulTable,
+        input: &[u8],
+        out: &mut [u8],
+        overwrite: bool,
+    ) -> usize {
+        let low = _mm_loadu_si128(table.low.as_ptr().cast());
+        let high = _mm_loadu_si128(table.high.as_ptr().cast());
+        let mask = _mm_set1_epi8(0x0F);
+
+        let blocks = input.len() / 16;
+        for b in 0..blocks {
+            let src = input.as_ptr().add(b * 16).cast::<__m128i>();
+            let dst = out.as_mut_ptr().add(b * 16).cast::<__m128i>();
+
+            let x = _mm_loadu_si128(src);
+            let lo = _mm_and_si128(x, mask);
+            let hi = _mm_and_si128(_mm_srli_epi64(x, 4), mask);
+            let mut product = _mm_xor_si128(_mm_shuffle_epi8(low, lo), _mm_shuffle_epi8(high, hi));
+            if !overwrite {
+                product = _mm_xor_si128(product, _mm_loadu_si128(dst));
+            }
+            _mm_storeu_si128(dst, product);
+        }
+        blocks * 16
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use super::MulTable;
+    use std::arch::aarch64::*;
+
+    /// Returns the number of bytes processed (a multiple of 16)
+    #[target_feature(enable = "neon")]
+    pub(super) unsafe fn mul_neon(
+        table: &MulTable,
+        input: &[u8],
+        out: &mut [u8],
+        overwrite: bool,
+    ) -> usize {
+        let low = vld1q_u8(table.low.as_ptr());
+        let high = vld1q_u8(table.high.as_ptr());
+        let mask = vdupq_n_u8(0x0F);
+
+        let blocks = input.len() / 16;
+        for b in 0..blocks {
+            let src = input.as_ptr().add(b * 16);
+            let dst = out.as_mut_ptr().add(b * 16);
+
+            let x = vld1q_u8(src);
+            let lo = vandq_u8(x, mask);
+            let hi = vshrq_n_u8(x, 4);
+            let mut product = veorq_u8(vqtbl1q_u8(low, lo), vqtbl1q_u8(high, hi));
+            if !overwrite {
+                product = veorq_u8(product, vld1q_u8(dst));
+            }
+            vst1q_u8(dst, product);
+        }
+        blocks * 16
+    }
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Carry-less multiply reduced by the generating polynomial
+    fn slow_mul(mut a: u8, mut b: u8) -> u8 {
+        let mut product = 0u8;
+        while b != 0 {
+            if b & 1 != 0 {
+                product ^= a;
+            }
+            let carry = a & 0x80 != 0;
+            a <<= 1;
+            if carry {
+                a ^= POLYNOMIAL as u8;
+            }
+            b >>= 1;
+        }
+        product
+    }
+
+    #[test]
+    fn test_mul_matches_polynomial_arithmetic() {
+        for a in 0..=255u8 {
+            for b in 0..=255u8 {
+                assert_eq!(mul(a, b), slow_mul(a, b), "{} * {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_div_inverts_mul() {
+        for a in 0..=255u8 {
+            for b in 1..=255u8 {
+                assert_eq!(div(mul(a, b), b), a);
+            }
+        }
+        assert_eq!(exp(2, 8), 0x1D);
+        assert_eq!(exp(0, 0), 1);
+        assert_eq!(exp(0, 3), 0);
+    }
+
+    #[test]
+    fn test_matrix_invert() {
+        let m = GfMatrix::vandermonde(5, 5);
+        let inverse = m.invert().unwrap();
+        assert_eq!(m.multiply(&inverse), GfMatrix::identity(5));
+
+        let mut singular = GfMatrix::identity(3);
+        singular.set(2, 2, 0);
+        assert!(singular.invert().is_none());
+    }
+
+    #[test]
+    fn test_systematic_matrix_has_identity_top() {
+        let m = GfMatrix::systematic(4, 6).unwrap();
+        assert_eq!(m.select_rows(&[0, 1, 2, 3]), GfMatrix::identity(4));
+        // Parity rows of the Backblaze/reed-solomon-erasure 4+2 layout
+        assert_eq!(m.row(4), &[27, 28, 18, 20]);
+        assert_eq!(m.row(5), &[28, 27, 20, 18]);
+    }
+
+    #[test]
+    fn test_every_simd_level_matches_scalar() {
+        let input: Vec<u8> = (0..1000u32).map(|i| (i * 7 + 3) as u8).collect();
+        let base: Vec<u8> = (0..1000u32).map(|i| (i * 13) as u8).collect();
+
+        for coefficient in [0u8, 1, 2, 0x1D, 0x8E, 0xFF] {
+            let table = MulTable::new(coefficient);
+            let expected_set: Vec<u8> = input.iter().map(|&x| mul(coefficient, x)).collect();
+            let expected_add: Vec<u8> =
+                expected_set.iter().zip(&base).map(|(p, b)| p ^ b).collect();
+
+            for level in GfSimdLevel::supported() {
+                // Odd lengths exercise the scalar tail
+                for len in [0, 1, 15, 16, 33, 999, 1000] {
+                    let mut out = base[..len].to_vec();
+                    mul_slice(level, &table, &input[..len], &mut out, true);
+                    assert_eq!(out, expected_set[..len], "{} set c={}", level, coefficient);
+
+                    let mut out = base[..len].to_vec();
+                    mul_slice(level, &table, &input[..len], &mut out, false);
+                    assert_eq!(out, expected_add[..len], "{} add c={}", level, coefficient);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_detect_is_supported() {
+        let level = GfSimdLevel::detect();
+        assert!(level.is_supported());
+        assert!(GfSimdLevel::supported().contains(&level));
+        assert!(GfSimdLevel::Scalar.is_supported());
+    }
+}
@@ -0,0 +1,1324 @@
+//! Shard Health Watcher - Failure Detection from Pool and Node State
+//!
+//! Watches Mayastor `DiskPool` and Kubernetes `Node` objects and maps failed
+//! pools to the shards stored on them. A pool counts as failed when it is
+//! `Faulted`, deleted, or hosted on a node that is not Ready.
+//!
+//! Shards on a failed pool are marked `Missing` right away, so reads take
+//! the degraded path. Rebuilds are only queued once the pool has stayed
+//! failed for a grace period; a node reboot that brings its pools back
+//! within that window restores the shards without any rebuild traffic.
+//!
+//! Shard locations name the pools of the shard store, which need not match
+//! DiskPool names (`LocalShardStore` pools are directories). Each shard pool
+//! is mapped to the DiskPool backing it; unmapped shard pools are assumed to
+//! share their DiskPool's name. Shard pools on a failed DiskPool are also
+//! taken offline in the shard store, so no new shards are placed there.
+//!
+//! Shard health changes are written to the stripe repository, so degraded
+//! stripes show up in ECStripe status and survive a restart. Pool and node
+//! state are tracked in memory only and re-listed from the API server
+//! whenever the watcher (re)starts. Each shard the watcher marks `Missing`
+//! records the failed DiskPool, so a pool that is usable when first seen has
+//! the shards an earlier run marked for it restored; shards missing for any
+//! other reason are left alone.
+
+use crate::crd::{DiskPool, PoolState, ShardHealth, ShardState, StripeState};
+use crate::domain::events::DomainEvent;
+use crate::domain::ports::{EventPublisher, StripeRepository};
+use crate::ec::metadata::{
+    commit_shard_placement, EcMetadataManager, StripeMetadata, StripeStatus,
+};
+use crate::ec::reconstruction::ReconstructionEngine;
+use crate::ec::shard_store::ShardStore;
+use crate::error::{Error, Result};
+use chrono::Utc;
+use futures::StreamExt;
+use k8s_openapi::api::core::v1::Node;
+use kube::runtime::watcher::{self, watcher, Event};
+use kube::runtime::WatchStreamExt;
+use kube::{Api, Client, ResourceExt};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::{interval, Instant};
+use tracing::{debug, error, info, instrument, warn};
+
+/// Stripe health updates written to the repository concurrently
+const PERSIST_CONCURRENCY: usize = 16;
+
+// =============================================================================
+// Configuration
+// =============================================================================
+
+/// Configuration for the shard health watcher
+#[derive(Debug, Clone)]
+pub struct HealthWatcherConfig {
+    /// How long a pool must stay failed before its shards are rebuilt
+    pub grace_period: Duration,
+
+    /// Interval between checks for pools whose grace period has expired
+    pub check_interval: Duration,
+
+    /// DiskPools backing shard store pools
+    pub pool_mappings: Vec<PoolMapping>,
+}
+
+impl Default for HealthWatcherConfig {
+    fn default() -> Self {
+        Self {
+            grace_period: Duration::from_secs(300),
+            check_interval: Duration::from_secs(10),
+            pool_mappings: Vec::new(),
+        }
+    }
+}
+
+impl HealthWatcherConfig {
+    /// Shard store pools backed by a DiskPool
+    fn shard_pools(&self, disk_pool: &str) -> Vec<String> {
+        let mapped: Vec<String> = self
+            .pool_mappings
+            .iter()
+            .filter(|m| m.disk_pool == disk_pool)
+            .map(|m| m.shard_pool.clone())
+            .collect();
+        if mapped.is_empty() && !self.pool_mappings.iter().any(|m| m.shard_pool == disk_pool) {
+            return vec![disk_pool.to_string()];
+        }
+        mapped
+    }
+}
+
+/// A shard store pool and the DiskPool it lives on, as `<shard pool>=<DiskPool>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolMapping {
+    /// Pool name used in shard locations
+    pub shard_pool: String,
+
+    /// Mayastor DiskPool whose failure takes the shard pool down
+    pub disk_pool: String,
+}
+
+impl FromStr for PoolMapping {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once('=') {
+            Some((shard_pool, disk_pool)) if !shard_pool.is_empty() && !disk_pool.is_empty() => {
+                Ok(Self {
+                    shard_pool: shard_pool.to_string(),
+                    disk_pool: disk_pool.to_string(),
+                })
+            }
+            _ => Err(Error::InvalidEcConfig(format!(
+                "pool mapping '{}' is not <shard pool>=<DiskPool>",
+                s
+            ))),
+        }
+    }
+}
+
+// =============================================================================
+// Watch State
+// =============================================================================
+
+/// Last observed state of a pool
+#[derive(Debug, Clone)]
+struct PoolRecord {
+    node: String,
+    state: PoolState,
+    deleted: bool,
+
+    /// State reported before the latest update (None when first seen)
+    previous: Option<PoolState>,
+}
+
+/// A pool currently considered failed
+#[derive(Debug, Clone)]
+struct FailedPool {
+    since: Instant,
+    reason: String,
+    rebuild_queued: bool,
+}
+
+#[derive(Debug, Default)]
+struct WatchState {
+    pools: HashMap<String, PoolRecord>,
+
+    /// Node name -> Ready (deleted nodes are kept as not ready)
+    nodes: HashMap<String, bool>,
+
+    failed: HashMap<String, FailedPool>,
+
+    /// Pools reconciled at least once
+    seen: HashSet<String>,
+
+    /// Objects seen since the current re-list started
+    pool_listing: Option<HashSet<String>>,
+    node_listing: Option<HashSet<String>>,
+}
+
+impl WatchState {
+    /// Status a pool had before it failed
+    ///
+    /// A faulted pool reported its earlier state before the fault; pools
+    /// lost through deletion or their node still report their own.
+    fn status_before_failure(pool: &PoolRecord) -> String {
+        let state = if pool.state == PoolState::Faulted && !pool.deleted {
+            pool.previous.clone().unwrap_or_default()
+        } else {
+            pool.state.clone()
+        };
+        format!("{:?}", state)
+    }
+
+    /// Why a pool is failed, or None if it is usable
+    fn failure_reason(&self, pool: &PoolRecord) -> Option<String> {
+        if pool.deleted {
+            return Some("pool deleted".to_string());
+        }
+        if pool.state == PoolState::Faulted {
+            return Some("pool faulted".to_string());
+        }
+        match self.nodes.get(&pool.node) {
+            Some(false) => Some(format!("node {} not ready", pool.node)),
+            _ => None,
+        }
+    }
+}
+
+/// Volume ID and stripe ID of a stripe
+type StripeKey = (String, u64);
+
+/// A shard whose health was changed by the watcher
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ShardRef {
+    volume_id: String,
+    stripe_id: u64,
+    shard_index: u8,
+}
+
+// =============================================================================
+// Shard Health Watcher
+// =============================================================================
+
+/// Marks shards on failed pools missing and schedules their rebuild
+pub struct ShardHealthWatcher {
+    /// Configuration
+    config: HealthWatcherConfig,
+
+    /// Metadata manager
+    metadata_manager: Arc<EcMetadataManager>,
+
+    /// Engine that rebuilds missing shards
+    reconstruction_engine: Arc<ReconstructionEngine>,
+
+    /// Shard store whose pools are taken offline on failure
+    shard_store: Arc<dyn ShardStore>,
+
+    /// Repository shard health changes are written to
+    stripe_repository: Arc<dyn StripeRepository>,
+
+    /// Domain event sink
+    events: Arc<dyn EventPublisher>,
+
+    /// Observed pools and nodes
+    state: parking_lot::Mutex<WatchState>,
+
+    /// Shutdown signal
+    shutdown: AtomicBool,
+}
+
+impl ShardHealthWatcher {
+    /// Create a new shard health watcher
+    pub fn new(
+        config: HealthWatcherConfig,
+        metadata_manager: Arc<EcMetadataManager>,
+        reconstruction_engine: Arc<ReconstructionEngine>,
+        shard_store: Arc<dyn ShardStore>,
+        stripe_repository: Arc<dyn StripeRepository>,
+        events: Arc<dyn EventPublisher>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            config,
+            metadata_manager,
+            reconstruction_engine,
+            shard_store,
+            stripe_repository,
+            events,
+            state: parking_lot::Mutex::new(WatchState::default()),
+            shutdown: AtomicBool::new(false),
+        })
+    }
+
+    /// Watch DiskPools and Nodes until shutdown
+    #[instrument(skip(self, client))]
+    pub async fn run(self: Arc<Self>, client: Client) {
+        info!("Starting shard health watcher with {:?}", self.config);
+
+        let pools = watcher(
+            Api::<DiskPool>::all(client.clone()),
+            watcher::Config::default(),
+        )
+        .default_backoff();
+        let nodes = watcher(Api::<Node>::all(client), watcher::Config::default()).default_backoff();
+        futures::pin_mut!(pools, nodes);
+
+        let mut tick = interval(self.config.check_interval);
+
+        loop {
+            tokio::select! {
+                Some(event) = pools.next() => match event {
+                    Ok(event) => self.handle_pool_event(event).await,
+                    Err(e) => warn!("DiskPool watch error: {}", e),
+                },
+
+                Some(event) = nodes.next() => match event {
+                    Ok(event) => self.handle_node_event(event).await,
+                    Err(e) => warn!("Node watch error: {}", e),
+                },
+
+                _ = tick.tick() => {
+                    if self.shutdown.load(Ordering::SeqCst) {
+                        info!("Shard health watcher shutting down");
+                        break;
+                    }
+
+                    if let Err(e) = self.queue_due_rebuilds().await {
+                        error!("Failed to queue shard rebuilds: {}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Signal shutdown
+    pub fn shutdown(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+    }
+
+    /// Get configuration
+    pub fn config(&self) -> &HealthWatcherConfig {
+        &self.config
+    }
+
+    /// Names of the pools currently considered failed
+    pub fn failed_pools(&self) -> Vec<String> {
+        let mut pools: Vec<String> = self.state.lock().failed.keys().cloned().collect();
+        pools.sort();
+        pools
+    }
+
+    async fn handle_pool_event(&self, event: Event<DiskPool>) {
+        match event {
+            Event::Apply(pool) => self.observe_pool(&pool).await,
+            Event::Delete(pool) => self.remove_pool(&pool.name_any()).await,
+            Event::Init => self.state.lock().pool_listing = Some(HashSet::new()),
+            Event::InitApply(pool) => {
+                if let Some(seen) = self.state.lock().pool_listing.as_mut() {
+                    seen.insert(pool.name_any());
+                }
+                self.observe_pool(&pool).await;
+            }
+            Event::InitDone => {
+                // Pools missing from the re-list were deleted while unwatched
+                let gone: Vec<String> = {
+                    let mut state = self.state.lock();
+                    let seen = state.pool_listing.take().unwrap_or_default();
+                    state
+                        .pools
+                        .iter()
+                        .filter(|(name, pool)| !pool.deleted && !seen.contains(*name))
+                        .map(|(name, _)| name.clone())
+                        .collect()
+                };
+                for name in gone {
+                    self.remove_pool(&name).await;
+                }
+            }
+        }
+    }
+
+    async fn handle_node_event(&self, event: Event<Node>) {
+        match event {
+            Event::Apply(node) => {
+                self.set_node_ready(&node.name_any(), node_is_ready(&node))
+                    .await
+            }
+            Event::Delete(node) => self.set_node_ready(&node.name_any(), false).await,
+            Event::Init => self.state.lock().node_listing = Some(HashSet::new()),
+            Event::InitApply(node) => {
+                if let Some(seen) = self.state.lock().node_listing.as_mut() {
+                    seen.insert(node.name_any());
+                }
+                self.set_node_ready(&node.name_any(), node_is_ready(&node))
+                    .await;
+            }
+            Event::InitDone => {
+                let gone: Vec<String> = {
+                    let mut state = self.state.lock();
+                    let seen = state.node_listing.take().unwrap_or_default();
+                    state
+                        .nodes
+                        .iter()
+                        .filter(|(name, ready)| **ready && !seen.contains(*name))
+                        .map(|(name, _)| name.clone())
+                        .collect()
+                };
+                for name in gone {
+                    self.set_node_ready(&name, false).await;
+                }
+            }
+        }
+    }
+
+    /// Record the current state of a DiskPool
+    pub async fn observe_pool(&self, pool: &DiskPool) {
+        {
+            let mut state = self.state.lock();
+            let previous = state.pools.get(&pool.name_any()).map(|p| p.state.clone());
+            let record = PoolRecord {
+                node: pool.spec.node.clone(),
+                state: pool
+                    .status
+                    .as_ref()
+                    .map(|s| s.state.clone())
+                    .unwrap_or_default(),
+                deleted: false,
+                previous,
+            };
+            state.pools.insert(pool.name_any(), record);
+        }
+        self.reconcile().await;
+    }
+
+    /// Record that a DiskPool was deleted
+    pub async fn remove_pool(&self, name: &str) {
+        {
+            let mut state = self.state.lock();
+            let Some(pool) = state.pools.get_mut(name) else {
+                return;
+            };
+            pool.deleted = true;
+        }
+        self.reconcile().await;
+    }
+
+    /// Record a node's readiness (false for deleted nodes)
+    pub async fn set_node_ready(&self, name: &str, ready: bool) {
+        let changed = self.state.lock().nodes.insert(name.to_string(), ready) != Some(ready);
+        if changed {
+            debug!("Node {} ready: {}", name, ready);
+            self.reconcile().await;
+        }
+    }
+
+    /// Mark shards on newly failed pools missing and restore recovered ones
+    async fn reconcile(&self) {
+        let (newly_failed, recovered, first_seen) = {
+            let mut guard = self.state.lock();
+            let state = &mut *guard;
+            let down: BTreeMap<String, String> = state
+                .pools
+                .iter()
+                .filter_map(|(name, pool)| {
+                    state
+                        .failure_reason(pool)
+                        .map(|reason| (name.clone(), reason))
+                })
+                .collect();
+
+            let first_seen: Vec<String> = state
+                .pools
+                .keys()
+                .filter(|name| !down.contains_key(*name) && !state.seen.contains(*name))
+                .cloned()
+                .collect();
+            state.seen.extend(state.pools.keys().cloned());
+
+            let recovered: Vec<(String, String)> = state
+                .failed
+                .keys()
+                .filter(|name| !down.contains_key(*name))
+                .map(|name| {
+                    let status = state.pools.get(name).map(|p| p.state.clone());
+                    (name.clone(), format!("{:?}", status.unwrap_or_default()))
+                })
+                .collect();
+            for (name, _) in &recovered {
+                state.failed.remove(name);
+            }
+
+            let now = Instant::now();
+            let mut newly_failed = Vec::new();
+            for (name, reason) in down {
+                if !state.failed.contains_key(&name) {
+                    state.failed.insert(
+                        name.clone(),
+                        FailedPool {
+                            since: now,
+                            reason: reason.clone(),
+                            rebuild_queued: false,
+                        },
+                    );
+                    let status = WatchState::status_before_failure(&state.pools[&name]);
+                    newly_failed.push((name, reason, status));
+                }
+            }
+            (newly_failed, recovered, first_seen)
+        };
+
+        for pool in first_seen {
+            let shards = self.set_pool_shards(&pool, ShardState::Healthy).await;
+            if !shards.is_empty() {
+                info!(
+                    "Pool {} is online; {} shards marked missing earlier are healthy again",
+                    pool,
+                    shards.len()
+                );
+            }
+        }
+
+        for (pool, reason, old_status) in newly_failed {
+            let shards = self.set_pool_shards(&pool, ShardState::Missing).await;
+            warn!(
+                "Pool {} failed ({}); {} shards marked missing, rebuild in {:?}",
+                pool,
+                reason,
+                shards.len(),
+                self.config.grace_period
+            );
+
+            let now = Utc::now();
+            let mut events = vec![DomainEvent::HealthChanged {
+                component: format!("pool/{}", pool),
+                old_status,
+                new_status: "Failed".to_string(),
+                reason: Some(reason.clone()),
+                timestamp: now,
+            }];
+            events.extend(shards.into_iter().map(|s| DomainEvent::ShardFailed {
+                volume_id: s.volume_id,
+                stripe_id: s.stripe_id,
+                shard_index: s.shard_index as usize,
+                device_id: pool.clone(),
+                reason: reason.clone(),
+                timestamp: now,
+            }));
+            self.publish(events).await;
+        }
+
+        for (pool, new_status) in recovered {
+            let shards = self.set_pool_shards(&pool, ShardState::Healthy).await;
+            info!(
+                "Pool {} recovered; {} shards marked healthy",
+                pool,
+                shards.len()
+            );
+
+            self.publish(vec![DomainEvent::HealthChanged {
+                component: format!("pool/{}", pool),
+                old_status: "Failed".to_string(),
+                new_status,
+                reason: None,
+                timestamp: Utc::now(),
+            }])
+            .await;
+        }
+    }
+
+    /// Queue rebuilds for stripes on pools failed for longer than the grace
+    /// period. Returns the number of stripes queued.
+    pub async fn queue_due_rebuilds(&self) -> Result<usize> {
+        // A pool is only flagged once all its stripes are queued, so a
+        // failure part way is retried on the next check
+        let due: Vec<(String, Instant)> = {
+            let state = self.state.lock();
+            let now = Instant::now();
+            state
+                .failed
+                .iter()
+                .filter(|(_, f)| {
+                    !f.rebuild_queued && now.duration_since(f.since) >= self.config.grace_period
+                })
+                .map(|(name, f)| {
+                    info!(
+                        "Pool {} failed for {:?} ({}); queueing rebuilds",
+                        name,
+                        now.duration_since(f.since),
+                        f.reason
+                    );
+                    (name.clone(), f.since)
+                })
+                .collect()
+        };
+        if due.is_empty() {
+            return Ok(0);
+        }
+
+        // Shard pool -> the due DiskPool backing it
+        let shard_pools: HashMap<String, &str> = due
+            .iter()
+            .flat_map(|(pool, _)| {
+                self.config
+                    .shard_pools(pool)
+                    .into_iter()
+                    .map(move |shard_pool| (shard_pool, pool.as_str()))
+            })
+            .collect();
+
+        // Stripe -> unhealthy shard indices and the due pools it is on
+        let mut stripes: BTreeMap<StripeKey, (Vec<u8>, HashSet<&str>)> = BTreeMap::new();
+        for volume_id in self.metadata_manager.volume_ids() {
+            let Some(volume_state) = self.metadata_manager.get_volume(&volume_id) else {
+                continue;
+            };
+            for stripe in volume_state.read().all_stripes() {
+                let pools: HashSet<&str> = stripe
+                    .shard_locations
+                    .iter()
+                    .filter_map(|l| shard_pools.get(&l.pool_name).copied())
+                    .collect();
+                if pools.is_empty() {
+                    continue;
+                }
+                let missing: Vec<u8> = stripe
+                    .status
+                    .shard_health
+                    .iter()
+                    .filter(|h| h.state != ShardState::Healthy)
+                    .map(|h| h.shard_index)
+                    .collect();
+                if !missing.is_empty() {
+                    stripes.insert((volume_id.clone(), stripe.stripe_id), (missing, pools));
+                }
+            }
+        }
+
+        let mut events = Vec::new();
+        let mut unfinished: HashSet<&str> = HashSet::new();
+        let mut first_error = None;
+        for ((volume_id, stripe_id), (missing, pools)) in &stripes {
+            let queued = self
+                .reconstruction_engine
+                .queue_background_rebuild(volume_id.clone(), *stripe_id, missing.clone())
+                .await;
+            if let Err(e) = queued {
+                warn!(
+                    "Failed to queue rebuild of stripe {} of volume {}: {}",
+                    stripe_id, volume_id, e
+                );
+                unfinished.extend(pools);
+                first_error.get_or_insert(e);
+                continue;
+            }
+            events.push(DomainEvent::ReconstructionTriggered {
+                volume_id: volume_id.clone(),
+                stripe_id: *stripe_id,
+                missing_shards: missing.iter().map(|&i| i as usize).collect(),
+                timestamp: Utc::now(),
+            });
+        }
+        let queued = events.len();
+        self.publish(events).await;
+
+        {
+            let mut state = self.state.lock();
+            for (pool, since) in &due {
+                if unfinished.contains(pool.as_str()) {
+                    continue;
+                }
+                // Skip a pool that recovered and failed again meanwhile
+                if let Some(f) = state.failed.get_mut(pool).filter(|f| f.since == *since) {
+                    f.rebuild_queued = true;
+                }
+            }
+        }
+
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(queued),
+        }
+    }
+
+    /// Set the health of every shard stored on a DiskPool, and take its
+    /// shard pools offline (or back online) in the shard store
+    ///
+    /// Only shards whose health actually changes are returned.
+    async fn set_pool_shards(&self, disk_pool: &str, health: ShardState) -> Vec<ShardRef> {
+        let pools = self.config.shard_pools(disk_pool);
+        for pool in &pools {
+            self.shard_store
+                .set_pool_online(pool, health == ShardState::Healthy);
+        }
+
+        let mut changed = Vec::new();
+        let mut updated = Vec::new();
+
+        for volume_id in self.metadata_manager.volume_ids() {
+            let Some(volume_state) = self.metadata_manager.get_volume(&volume_id) else {
+                continue;
+            };
+            let state = volume_state.read();
+
+            for stripe in state.all_stripes() {
+                let on_pool: Vec<u8> = stripe
+                    .shard_locations
+                    .iter()
+                    .filter(|l| pools.contains(&l.pool_name))
+                    .map(|l| l.shard_index)
+                    .collect();
+                if on_pool.is_empty() {
+                    continue;
+                }
+
+                let mut status = stripe.status.clone();
+                let mut touched = false;
+                for &index in &on_pool {
+                    let current = status.shard_health.iter().find(|h| h.shard_index == index);
+                    let state = current.map(|h| h.state.clone()).unwrap_or_default();
+                    // Recovery only undoes what the watcher marked for this
+                    // pool; shards missing after failed reads stay missing
+                    let applies = match health {
+                        ShardState::Healthy => {
+                            state == ShardState::Missing
+                                && current.and_then(|h| h.failed_pool.as_deref()) == Some(disk_pool)
+                        }
+                        _ => state == ShardState::Healthy,
+                    };
+                    if !applies {
+                        continue;
+                    }
+
+                    let failed_pool =
+                        (health == ShardState::Missing).then(|| disk_pool.to_string());
+                    set_shard_health(&mut status, index, health.clone(), failed_pool);
+                    touched = true;
+                    changed.push(ShardRef {
+                        volume_id: volume_id.clone(),
+                        stripe_id: stripe.stripe_id,
+                        shard_index: index,
+                    });
+                }
+                if !touched {
+                    continue;
+                }
+
+                let total = stripe.shard_locations.len();
+                let parity = stripe
+                    .shard_locations
+                    .iter()
+                    .filter(|l| !l.is_data_shard)
+                    .count();
+                let unhealthy = status
+                    .shard_health
+                    .iter()
+                    .filter(|h| h.state != ShardState::Healthy)
+                    .count();
+                status.healthy_shards = total.saturating_sub(unhealthy) as u8;
+                status.state = match (unhealthy, &status.state) {
+                    (n, _) if n > parity => StripeState::Failed,
+                    // A running rebuild reports its own completion
+                    (_, StripeState::Rebuilding) => StripeState::Rebuilding,
+                    (0, _) => StripeState::Healthy,
+                    _ => StripeState::Degraded,
+                };
+                state.update_stripe_status(stripe.stripe_id, status.clone());
+                updated.push(StripeMetadata { status, ..stripe });
+            }
+        }
+
+        self.persist_health(updated).await;
+        changed
+    }
+
+    /// Write the shard health of updated stripes to the repository
+    ///
+    /// Each write claims the stripe's next generation. A stripe another
+    /// writer (e.g. a rebuild) committed in the meantime is skipped, as
+    /// that writer stored its own health.
+    async fn persist_health(&self, stripes: Vec<StripeMetadata>) {
+        futures::stream::iter(stripes)
+            .for_each_concurrent(PERSIST_CONCURRENCY, |stripe| async move {
+                let committed = commit_shard_placement(
+                    self.stripe_repository.as_ref(),
+                    &stripe,
+                    &stripe.shard_locations,
+                    &stripe.status.shard_health,
+                )
+                .await;
+                match committed {
+                    Ok(generation) => {
+                        if let Some(volume_state) =
+                            self.metadata_manager.get_volume(&stripe.volume_id)
+                        {
+                            volume_state.read().advance_generation(
+                                stripe.stripe_id,
+                                stripe.generation,
+                                generation,
+                            );
+                        }
+                    }
+                    Err(Error::EcGenerationConflict { .. }) => debug!(
+                        "Stripe {} of volume {} changed before its health was stored",
+                        stripe.stripe_id, stripe.volume_id
+                    ),
+                    Err(e) => warn!(
+                        "Failed to store shard health of stripe {} of volume {}: {}",
+                        stripe.stripe_id, stripe.volume_id, e
+                    ),
+                }
+            })
+            .await;
+    }
+
+    async fn publish(&self, events: Vec<DomainEvent>) {
+        if events.is_empty() {
+            return;
+        }
+        if let Err(e) = self.events.publish_all(events).await {
+            warn!("Failed to publish shard health events: {}", e);
+        }
+    }
+}
+
+/// Set one shard's health and the DiskPool that caused it, adding an entry
+/// if the shard has none
+fn set_shard_health(
+    status: &mut StripeStatus,
+    shard_index: u8,
+    state: ShardState,
+    failed_pool: Option<String>,
+) {
+    match status
+        .shard_health
+        .iter_mut()
+        .find(|h| h.shard_index == shard_index)
+    {
+        Some(entry) => {
+            entry.state = state;
+            entry.error = None;
+            entry.failed_pool = failed_pool;
+        }
+        None => status.shard_health.push(ShardHealth {
+            shard_index,
+            state,
+            last_verified: None,
+            error: None,
+            failed_pool,
+        }),
+    }
+}
+
+/// Whether a node reports the Ready condition as True
+fn node_is_ready(node: &Node) -> bool {
+    node.status
+        .as_ref()
+        .and_then(|s| s.conditions.as_ref())
+        .and_then(|c| c.iter().find(|c| c.type_ == "Ready"))
+        .is_some_and(|c| c.status == "True")
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::InMemoryEventCollector;
+    use crate::crd::{DiskPoolSpec, DiskPoolStatus, LbaRange, ShardLocation};
+    use crate::domain::ports::{StripeId, VolumeId};
+    use crate::ec::metadata::{from_domain_stripe, to_domain_stripe};
+    use crate::ec::reconstruction::ReconstructionConfig;
+    use crate::ec::test_support::{self, EcFixture};
+    use k8s_openapi::api::core::v1::{NodeCondition, NodeStatus};
+
+    const VOLUME: &str = "vol-1";
+
+    struct Fixture {
+        watcher: Arc<ShardHealthWatcher>,
+        engine: Arc<ReconstructionEngine>,
+        ec: EcFixture,
+        events: Arc<InMemoryEventCollector>,
+    }
+
+    /// Two 4+2 stripes; stripe 0 uses pools 0-5 and stripe 1 uses pools 2-7.
+    /// Pools 0-3 live on node-a, pools 4-7 on node-b.
+    async fn fixture(grace_period: Duration) -> Fixture {
+        fixture_with(HealthWatcherConfig {
+            grace_period,
+            ..Default::default()
+        })
+        .await
+    }
+
+    async fn fixture_with(config: HealthWatcherConfig) -> Fixture {
+        let ec = EcFixture::new(8, 2, []);
+
+        let volume = ec.metadata_manager.get_or_create_volume(VOLUME, "ec-4-2");
+        for stripe_id in 0..2u64 {
+            let first_pool = stripe_id as usize * 2;
+            let stripe = StripeMetadata {
+                stripe_id,
+                volume_id: VOLUME.to_string(),
+                policy_ref: "ec-4-2".to_string(),
+                lba_range: LbaRange::new(stripe_id * 8, stripe_id * 8 + 8),
+                shard_locations: (0..6u8)
+                    .map(|i| {
+                        let pool = first_pool + i as usize;
+                        ShardLocation {
+                            shard_index: i,
+                            is_data_shard: i < 4,
+                            pool_name: format!("pool-{}", pool),
+                            node_name: pool_node(pool).to_string(),
+                            offset: 0,
+                            size_bytes: 1024,
+                            checksum: None,
+                        }
+                    })
+                    .collect(),
+                status: StripeStatus {
+                    state: StripeState::Healthy,
+                    healthy_shards: 6,
+                    shard_health: vec![],
+                },
+                generation: 0,
+                checksum: None,
+            };
+            ec.repository
+                .save(&to_domain_stripe(&stripe))
+                .await
+                .unwrap();
+            volume.write().add_stripe(stripe);
+        }
+
+        let engine = ReconstructionEngine::new(
+            ReconstructionConfig::default(),
+            ec.metadata_manager.clone(),
+            ec.store.clone(),
+            ec.repository.clone(),
+        );
+        let events = Arc::new(InMemoryEventCollector::new());
+        let watcher = ShardHealthWatcher::new(
+            config,
+            ec.metadata_manager.clone(),
+            engine.clone(),
+            ec.store.clone(),
+            ec.repository.clone(),
+            events.clone(),
+        );
+
+        for pool in 0..8 {
+            watcher
+                .observe_pool(&disk_pool(pool, PoolState::Online))
+                .await;
+        }
+        watcher.set_node_ready("node-a", true).await;
+        watcher.set_node_ready("node-b", true).await;
+
+        Fixture {
+            watcher,
+            engine,
+            ec,
+            events,
+        }
+    }
+
+    fn pool_node(pool: usize) -> &'static str {
+        if pool < 4 {
+            "node-a"
+        } else {
+            "node-b"
+        }
+    }
+
+    fn disk_pool(pool: usize, state: PoolState) -> DiskPool {
+        named_disk_pool(&format!("pool-{}", pool), pool_node(pool), state)
+    }
+
+    fn named_disk_pool(name: &str, node: &str, state: PoolState) -> DiskPool {
+        let mut disk_pool = DiskPool::new(
+            name,
+            DiskPoolSpec {
+                node: node.to_string(),
+                disks: vec!["/dev/nvme0n1".to_string()],
+            },
+        );
+        disk_pool.status = Some(DiskPoolStatus {
+            state,
+            ..Default::default()
+        });
+        disk_pool
+    }
+
+    fn stripe(f: &Fixture, stripe_id: u64) -> StripeMetadata {
+        f.ec.metadata_manager
+            .get_volume(VOLUME)
+            .unwrap()
+            .read()
+            .get_stripe(stripe_id)
+            .unwrap()
+    }
+
+    fn stored_stripe(f: &Fixture, stripe_id: u64) -> StripeMetadata {
+        let stored =
+            f.ec.repository
+                .get(&VolumeId::new(VOLUME), &StripeId::new(stripe_id))
+                .unwrap();
+        from_domain_stripe(&stored)
+    }
+
+    fn missing_shards(stripe: &StripeMetadata) -> Vec<u8> {
+        let mut missing: Vec<u8> = stripe
+            .status
+            .shard_health
+            .iter()
+            .filter(|h| h.state == ShardState::Missing)
+            .map(|h| h.shard_index)
+            .collect();
+        missing.sort_unstable();
+        missing
+    }
+
+    #[test]
+    fn test_health_watcher_config_default() {
+        let config = HealthWatcherConfig::default();
+        assert_eq!(config.grace_period, Duration::from_secs(300));
+        assert_eq!(config.check_interval, Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_node_is_ready() {
+        let node = |status: &str| Node {
+            status: Some(NodeStatus {
+                conditions: Some(vec![NodeCondition {
+                    type_: "Ready".to_string(),
+                    status: status.to_string(),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        assert!(node_is_ready(&node("True")));
+        assert!(!node_is_ready(&node("Unknown")));
+        assert!(!node_is_ready(&Node::default()));
+    }
+
+    // =========================================================================
+    // Failure Detection Tests
+    // =========================================================================
+
+    #[tokio::test]
+    async fn test_faulted_pool_marks_shards_missing() {
+        let f = fixture(Duration::from_secs(300)).await;
+
+        f.watcher
+            .observe_pool(&disk_pool(3, PoolState::Faulted))
+            .await;
+
+        assert_eq!(f.watcher.failed_pools(), vec!["pool-3"]);
+        let s0 = stripe(&f, 0);
+        assert_eq!(missing_shards(&s0), vec![3]);
+        assert_eq!(s0.status.state, StripeState::Degraded);
+        assert_eq!(s0.status.healthy_shards, 5);
+        assert_eq!(missing_shards(&stripe(&f, 1)), vec![1]);
+
+        let failed = f.events.events_of_type("ShardFailed");
+        assert_eq!(failed.len(), 2);
+        assert!(matches!(
+            &failed[0],
+            DomainEvent::ShardFailed { device_id, .. } if device_id == "pool-3"
+        ));
+
+        // Still inside the grace period: nothing is rebuilt yet
+        assert_eq!(f.watcher.queue_due_rebuilds().await.unwrap(), 0);
+        assert_eq!(f.engine.active_task_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_health_changed_reports_previous_status() {
+        let f = fixture(Duration::from_secs(300)).await;
+        let statuses = || -> Vec<(String, String)> {
+            f.events
+                .events_of_type("HealthChanged")
+                .into_iter()
+                .map(|e| match e {
+                    DomainEvent::HealthChanged {
+                        old_status,
+                        new_status,
+                        ..
+                    } => (old_status, new_status),
+                    _ => unreachable!(),
+                })
+                .collect()
+        };
+
+        f.watcher
+            .observe_pool(&disk_pool(3, PoolState::Degraded))
+            .await;
+        f.watcher
+            .observe_pool(&disk_pool(3, PoolState::Faulted))
+            .await;
+        f.watcher.set_node_ready("node-b", false).await;
+        f.watcher
+            .observe_pool(&disk_pool(3, PoolState::Degraded))
+            .await;
+
+        let pair = |old: &str, new: &str| (old.to_string(), new.to_string());
+        assert_eq!(
+            statuses(),
+            vec![
+                pair("Degraded", "Failed"),
+                pair("Online", "Failed"),
+                pair("Online", "Failed"),
+                pair("Online", "Failed"),
+                pair("Online", "Failed"),
+                pair("Failed", "Degraded"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_shard_health_is_stored() {
+        let f = fixture(Duration::from_secs(300)).await;
+
+        f.watcher
+            .observe_pool(&disk_pool(3, PoolState::Faulted))
+            .await;
+        let stored = stored_stripe(&f, 0);
+        assert_eq!(missing_shards(&stored), vec![3]);
+        assert_eq!(stored.status.state, StripeState::Degraded);
+        assert_eq!(stored.generation, 1);
+        assert_eq!(stripe(&f, 0).generation, 1);
+        assert_eq!(missing_shards(&stored_stripe(&f, 1)), vec![1]);
+
+        f.watcher
+            .observe_pool(&disk_pool(3, PoolState::Online))
+            .await;
+        let stored = stored_stripe(&f, 0);
+        assert!(missing_shards(&stored).is_empty());
+        assert_eq!(stored.generation, 2);
+        assert_eq!(stripe(&f, 0).generation, 2);
+    }
+
+    /// A watcher and metadata manager reloaded from the fixture's repository,
+    /// as after a restart
+    async fn restart(f: &Fixture) -> (Arc<EcMetadataManager>, Arc<ShardHealthWatcher>) {
+        let metadata_manager = test_support::metadata_manager();
+        metadata_manager.get_or_create_volume(VOLUME, "ec-4-2");
+        metadata_manager
+            .sync_from_repository(f.ec.repository.as_ref(), VOLUME)
+            .await
+            .unwrap();
+
+        let engine = ReconstructionEngine::new(
+            ReconstructionConfig::default(),
+            metadata_manager.clone(),
+            f.ec.store.clone(),
+            f.ec.repository.clone(),
+        );
+        let watcher = ShardHealthWatcher::new(
+            HealthWatcherConfig::default(),
+            metadata_manager.clone(),
+            engine,
+            f.ec.store.clone(),
+            f.ec.repository.clone(),
+            f.events.clone(),
+        );
+        (metadata_manager, watcher)
+    }
+
+    #[tokio::test]
+    async fn test_restart_restores_shards_of_recovered_pools() {
+        let f = fixture(Duration::from_secs(300)).await;
+        f.watcher
+            .observe_pool(&disk_pool(3, PoolState::Faulted))
+            .await;
+
+        // Restart: stripes come back from the repository, still degraded
+        let (metadata_manager, watcher) = restart(&f).await;
+        let reloaded = |id| {
+            metadata_manager
+                .get_volume(VOLUME)
+                .unwrap()
+                .read()
+                .get_stripe(id)
+                .unwrap()
+        };
+        assert_eq!(missing_shards(&reloaded(0)), vec![3]);
+
+        // The pool came back while the watcher was down
+        watcher.observe_pool(&disk_pool(3, PoolState::Online)).await;
+        assert!(missing_shards(&reloaded(0)).is_empty());
+        assert!(missing_shards(&reloaded(1)).is_empty());
+        assert!(missing_shards(&stored_stripe(&f, 0)).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_restart_keeps_shards_missing_for_other_reasons() {
+        let f = fixture(Duration::from_secs(300)).await;
+
+        // A failed read marked shard 2 of stripe 0 (on pool-2) missing
+        let mut failed_read = stripe(&f, 0);
+        set_shard_health(&mut failed_read.status, 2, ShardState::Missing, None);
+        f.ec.repository
+            .save(&to_domain_stripe(&failed_read))
+            .await
+            .unwrap();
+
+        let (metadata_manager, watcher) = restart(&f).await;
+        watcher.observe_pool(&disk_pool(2, PoolState::Online)).await;
+
+        let reloaded = metadata_manager
+            .get_volume(VOLUME)
+            .unwrap()
+            .read()
+            .get_stripe(0)
+            .unwrap();
+        assert_eq!(missing_shards(&reloaded), vec![2]);
+        assert_eq!(missing_shards(&stored_stripe(&f, 0)), vec![2]);
+    }
+
+    #[test]
+    fn test_pool_mapping_parse() {
+        let mapping: PoolMapping = "pool-0=diskpool-a".parse().unwrap();
+        assert_eq!(mapping.shard_pool, "pool-0");
+        assert_eq!(mapping.disk_pool, "diskpool-a");
+
+        assert!("pool-0".parse::<PoolMapping>().is_err());
+        assert!("=diskpool-a".parse::<PoolMapping>().is_err());
+        assert!("pool-0=".parse::<PoolMapping>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mapped_disk_pool_failure_takes_shard_pools_offline() {
+        // Pools 0-3 are directories on DiskPool dp-a, pools 4-7 on dp-b
+        let f = fixture_with(HealthWatcherConfig {
+            grace_period: Duration::ZERO,
+            pool_mappings: (0..8)
+                .map(|pool| PoolMapping {
+                    shard_pool: format!("pool-{}", pool),
+                    disk_pool: if pool < 4 { "dp-a" } else { "dp-b" }.to_string(),
+                })
+                .collect(),
+            ..Default::default()
+        })
+        .await;
+        f.watcher
+            .observe_pool(&named_disk_pool("dp-b", "node-b", PoolState::Online))
+            .await;
+
+        f.watcher
+            .observe_pool(&named_disk_pool("dp-b", "node-b", PoolState::Faulted))
+            .await;
+
+        assert_eq!(f.watcher.failed_pools(), vec!["dp-b"]);
+        assert_eq!(missing_shards(&stripe(&f, 0)), vec![4, 5]);
+        assert_eq!(missing_shards(&stripe(&f, 1)), vec![2, 3, 4, 5]);
+        assert_eq!(
+            f.ec.store.healthy_pools(),
+            vec!["pool-0", "pool-1", "pool-2", "pool-3"]
+        );
+        assert_eq!(f.watcher.queue_due_rebuilds().await.unwrap(), 2);
+
+        f.watcher
+            .observe_pool(&named_disk_pool("dp-b", "node-b", PoolState::Online))
+            .await;
+        assert!(missing_shards(&stripe(&f, 0)).is_empty());
+        assert_eq!(f.ec.store.healthy_pools().len(), 8);
+    }
+
+    #[tokio::test]
+    async fn test_recovery_within_grace_period_skips_rebuild() {
+        let f = fixture(Duration::from_secs(300)).await;
+
+        f.watcher.set_node_ready("node-a", false).await;
+        assert_eq!(
+            f.watcher.failed_pools(),
+            vec!["pool-0", "pool-1", "pool-2", "pool-3"]
+        );
+        assert_eq!(missing_shards(&stripe(&f, 0)), vec![0, 1, 2, 3]);
+        assert_eq!(stripe(&f, 0).status.state, StripeState::Failed);
+        assert_eq!(missing_shards(&stripe(&f, 1)), vec![0, 1]);
+
+        // The node comes back from its reboot
+        f.watcher.set_node_ready("node-a", true).await;
+
+        assert!(f.watcher.failed_pools().is_empty());
+        for id in 0..2 {
+            let s = stripe(&f, id);
+            assert!(missing_shards(&s).is_empty());
+            assert_eq!(s.status.state, StripeState::Healthy);
+            assert_eq!(s.status.healthy_shards, 6);
+        }
+        assert_eq!(f.watcher.queue_due_rebuilds().await.unwrap(), 0);
+        assert_eq!(f.engine.active_task_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_rebuild_queued_after_grace_period() {
+        let f = fixture(Duration::ZERO).await;
+
+        f.watcher.remove_pool("pool-5").await;
+        f.watcher
+            .observe_pool(&disk_pool(6, PoolState::Faulted))
+            .await;
+
+        assert_eq!(f.watcher.queue_due_rebuilds().await.unwrap(), 2);
+        let mut tasks = f.engine.get_active_tasks();
+        tasks.sort_by_key(|t| t.stripe_id);
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].missing_shards, vec![5]);
+        assert_eq!(tasks[1].missing_shards, vec![3, 4]);
+        assert_eq!(f.events.events_of_type("ReconstructionTriggered").len(), 2);
+
+        // Each failure is only queued once
+        assert_eq!(f.watcher.queue_due_rebuilds().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_relist_treats_unlisted_pools_as_deleted() {
+        let f = fixture(Duration::from_secs(300)).await;
+
+        f.watcher.handle_pool_event(Event::Init).await;
+        for pool in (0..8).filter(|&p| p != 7) {
+            f.watcher
+                .handle_pool_event(Event::InitApply(disk_pool(pool, PoolState::Online)))
+                .await;
+        }
+        f.watcher.handle_pool_event(Event::InitDone).await;
+
+        assert_eq!(f.watcher.failed_pools(), vec!["pool-7"]);
+        assert_eq!(missing_shards(&stripe(&f, 1)), vec![5]);
+        assert!(missing_shards(&stripe(&f, 0)).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_recovery_keeps_corrupted_shards() {
+        let f = fixture(Duration::from_secs(300)).await;
+        {
+            let volume = f.ec.metadata_manager.get_volume(VOLUME).unwrap();
+            let volume = volume.read();
+            let mut status = volume.get_stripe(0).unwrap().status;
+            set_shard_health(&mut status, 1, ShardState::Corrupted, None);
+            volume.update_stripe_status(0, status);
+        }
+
+        f.watcher
+            .observe_pool(&disk_pool(1, PoolState::Faulted))
+            .await;
+        f.watcher
+            .observe_pool(&disk_pool(1, PoolState::Online))
+            .await;
+
+        let s0 = stripe(&f, 0);
+        assert_eq!(s0.status.shard_health[0].state, ShardState::Corrupted);
+        assert!(f.events.events_of_type("ShardFailed").is_empty());
+    }
+}
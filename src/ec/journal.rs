@@ -0,0 +1,1426 @@
+//! Replicated Write Journal
+//!
+//! Buffers writes to EC volumes in replicated journal storage until the
+//! stripe manager destages them into erasure-coded stripes. Each volume
+//! follows the `JournalConfig` of its policy:
+//!
+//! - `replication_factor`: every entry is appended to that many replica
+//!   directories before it is acknowledged. Replicas are directories of this
+//!   process (e.g. mounts of different disks in the same pod), not separate
+//!   nodes. With fewer directories than the factor, every directory is used
+//!   and a warning is logged once per volume
+//! - `journal_size_bytes`: appends wait for destaging (up to the journal's
+//!   full-wait timeout) and are rejected once pending entries would still
+//!   exceed it
+//! - `destage_threshold_percent` / `destage_interval`: the volume becomes due
+//!   for destaging when it is that full, or when its oldest pending entry is
+//!   that old
+//!
+//! Each replica keeps one directory per volume:
+//!
+//! ```text
+//! <replica>/<volume>/volume.json          policy the volume is journaled under
+//! <replica>/<volume>/checkpoint           highest sequence number destaged
+//! <replica>/<volume>/<first seq>.seg      frames: [len u32][crc32 u32][payload]
+//! ```
+//!
+//! Segments are deleted once every entry in them has been destaged. On
+//! restart the journal is replayed from all replicas: entries above the
+//! highest checkpoint are pending again, and a torn or corrupt segment tail
+//! is truncated.
+
+use crate::crd::{parse_duration, JournalConfig, LbaRange};
+use crate::ec::metadata::EcMetadataManager;
+use crate::ec::stripe_manager::{JournalEntry, LBA_SIZE};
+use crate::error::{Error, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::{debug, info, warn};
+
+/// Per-volume metadata file
+const VOLUME_FILE: &str = "volume.json";
+
+/// Per-volume destage checkpoint file
+const CHECKPOINT_FILE: &str = "checkpoint";
+
+/// Segment file extension
+const SEGMENT_EXT: &str = "seg";
+
+/// Start a new segment once the active one reaches this size
+const SEGMENT_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Frame header: payload length and payload checksum
+const FRAME_HEADER_BYTES: u64 = 8;
+
+/// Entry header inside the payload: sequence, start LBA, timestamp
+const ENTRY_HEADER_BYTES: u64 = 24;
+
+// =============================================================================
+// Volume Settings
+// =============================================================================
+
+/// Journal settings of one volume, resolved from its policy
+#[derive(Debug, Clone)]
+struct VolumeSettings {
+    capacity_bytes: u64,
+    replication: usize,
+    threshold_percent: u8,
+    interval: Duration,
+}
+
+impl VolumeSettings {
+    fn from_config(config: &JournalConfig) -> Result<Self> {
+        Ok(Self {
+            capacity_bytes: config.journal_size_bytes,
+            replication: (config.replication_factor as usize).max(1),
+            threshold_percent: config.destage_threshold_percent,
+            interval: parse_duration(&config.destage_interval)?,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VolumeFile {
+    policy: String,
+}
+
+// =============================================================================
+// Journal Stats
+// =============================================================================
+
+/// Fill level of one volume's journal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalStats {
+    /// Entries not yet destaged
+    pub entries: usize,
+
+    /// Journal bytes held by those entries (including framing)
+    pub used_bytes: u64,
+
+    /// Configured journal size
+    pub capacity_bytes: u64,
+
+    /// `used_bytes` as a percentage of `capacity_bytes`
+    pub fill_percent: u8,
+
+    /// Highest sequence number already destaged
+    pub destaged_through: u64,
+}
+
+// =============================================================================
+// Volume Journal
+// =============================================================================
+
+/// One stored copy of an entry
+#[derive(Debug, Clone)]
+struct EntryCopy {
+    replica: usize,
+    segment: u64,
+    offset: u64,
+}
+
+/// Index record of a pending entry; the data stays on disk
+#[derive(Debug, Clone)]
+struct IndexedEntry {
+    start_lba: u64,
+    data_len: u64,
+    timestamp: DateTime<Utc>,
+    copies: Vec<EntryCopy>,
+}
+
+impl IndexedEntry {
+    fn frame_len(&self) -> u64 {
+        FRAME_HEADER_BYTES + ENTRY_HEADER_BYTES + self.data_len
+    }
+}
+
+/// Segment currently being appended to
+struct ActiveSegment {
+    first_seq: u64,
+
+    /// (replica, file, length) for each replica of the volume
+    files: Vec<(usize, File, u64)>,
+}
+
+struct VolumeJournal {
+    volume_id: String,
+    settings: VolumeSettings,
+
+    /// Replicas receiving new appends
+    replicas: Vec<usize>,
+
+    /// Pending entries by sequence number
+    entries: BTreeMap<u64, IndexedEntry>,
+    used_bytes: u64,
+    next_seq: u64,
+    destaged_through: u64,
+
+    /// Segment first sequence -> replicas holding a file for it
+    segments: BTreeMap<u64, BTreeSet<usize>>,
+    active: Option<ActiveSegment>,
+}
+
+impl VolumeJournal {
+    fn stats(&self) -> JournalStats {
+        JournalStats {
+            entries: self.entries.len(),
+            used_bytes: self.used_bytes,
+            capacity_bytes: self.settings.capacity_bytes,
+            fill_percent: fill_percent(self.used_bytes, self.settings.capacity_bytes),
+            destaged_through: self.destaged_through,
+        }
+    }
+
+    fn over_threshold(&self) -> bool {
+        !self.entries.is_empty()
+            && fill_percent(self.used_bytes, self.settings.capacity_bytes)
+                >= self.settings.threshold_percent
+    }
+
+    fn is_due(&self, now: DateTime<Utc>) -> bool {
+        let Some(oldest) = self.entries.values().next() else {
+            return false;
+        };
+        let age = (now - oldest.timestamp).to_std().unwrap_or_default();
+        self.over_threshold() || age >= self.settings.interval
+    }
+
+    /// Every replica directory that holds data of this volume
+    fn all_replicas(&self) -> BTreeSet<usize> {
+        self.segments
+            .values()
+            .flatten()
+            .chain(&self.replicas)
+            .copied()
+            .collect()
+    }
+
+    fn failed(&self, reason: impl Into<String>) -> Error {
+        Error::EcJournalFailed {
+            volume_id: self.volume_id.clone(),
+            reason: reason.into(),
+        }
+    }
+}
+
+fn fill_percent(used: u64, capacity: u64) -> u8 {
+    if capacity == 0 {
+        return 100;
+    }
+    (used.saturating_mul(100) / capacity).min(100) as u8
+}
+
+// =============================================================================
+// Frames
+// =============================================================================
+
+fn encode_frame(seq: u64, start_lba: u64, timestamp: DateTime<Utc>, data: &[u8]) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(ENTRY_HEADER_BYTES as usize + data.len());
+    payload.extend_from_slice(&seq.to_le_bytes());
+    payload.extend_from_slice(&start_lba.to_le_bytes());
+    payload.extend_from_slice(&timestamp.timestamp_millis().to_le_bytes());
+    payload.extend_from_slice(data);
+
+    let mut frame = Vec::with_capacity(FRAME_HEADER_BYTES as usize + payload.len());
+    frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+    frame.extend_from_slice(&payload);
+    frame
+}
+
+/// A decoded frame payload
+struct Frame {
+    seq: u64,
+    start_lba: u64,
+    timestamp: DateTime<Utc>,
+    data: Vec<u8>,
+}
+
+/// Read the next frame, returning None at the end or on a torn/corrupt frame
+fn read_frame(reader: &mut impl Read) -> Option<Frame> {
+    let mut header = [0u8; FRAME_HEADER_BYTES as usize];
+    reader.read_exact(&mut header).ok()?;
+    let len = u32::from_le_bytes(header[..4].try_into().ok()?) as u64;
+    let crc = u32::from_le_bytes(header[4..].try_into().ok()?);
+    if len < ENTRY_HEADER_BYTES {
+        return None;
+    }
+
+    let mut payload = vec![0u8; len as usize];
+    reader.read_exact(&mut payload).ok()?;
+    if crc32fast::hash(&payload) != crc {
+        return None;
+    }
+
+    let field = |i: usize| payload[i * 8..(i + 1) * 8].try_into().ok();
+    Some(Frame {
+        seq: u64::from_le_bytes(field(0)?),
+        start_lba: u64::from_le_bytes(field(1)?),
+        timestamp: Utc
+            .timestamp_millis_opt(i64::from_le_bytes(field(2)?))
+            .single()?,
+        data: payload.split_off(ENTRY_HEADER_BYTES as usize),
+    })
+}
+
+// =============================================================================
+// File Helpers
+// =============================================================================
+
+fn segment_name(first_seq: u64) -> String {
+    format!("{:020}.{}", first_seq, SEGMENT_EXT)
+}
+
+fn parse_segment_name(name: &str) -> Option<u64> {
+    name.strip_suffix(SEGMENT_EXT)?
+        .strip_suffix('.')?
+        .parse()
+        .ok()
+}
+
+/// Replace `path` with `contents` via a temporary file and rename
+fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    {
+        let mut file = File::create(&tmp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+    }
+    std::fs::rename(&tmp, path)?;
+    if let Some(dir) = path.parent() {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+fn read_checkpoint(dir: &Path) -> u64 {
+    std::fs::read_to_string(dir.join(CHECKPOINT_FILE))
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Pick `count` of `total` replicas, starting at a position derived from
+/// the volume ID so volumes spread evenly
+fn spread_replicas(volume_id: &str, count: usize, total: usize) -> Vec<usize> {
+    let first = crc32fast::hash(volume_id.as_bytes()) as usize % total;
+    (0..count).map(|i| (first + i) % total).collect()
+}
+
+/// Replicas receiving a volume's appends, clamped to the configured directories
+fn choose_replicas(volume_id: &str, wanted: usize, total: usize) -> Vec<usize> {
+    if wanted > total {
+        warn!(
+            "Journal of volume {} wants {} replicas but only {} directories are configured; \
+             using all of them",
+            volume_id, wanted, total
+        );
+    }
+    spread_replicas(volume_id, wanted.min(total), total)
+}
+
+/// Volume IDs become directory names, so they must be a single path component
+fn validate_volume_id(volume_id: &str) -> Result<()> {
+    if volume_id.is_empty()
+        || volume_id == "."
+        || volume_id == ".."
+        || volume_id.contains(['/', '\\'])
+    {
+        return Err(Error::EcJournalFailed {
+            volume_id: volume_id.to_string(),
+            reason: "volume ID is not a valid journal directory name".to_string(),
+        });
+    }
+    Ok(())
+}
+
+// =============================================================================
+// Write Journal
+// =============================================================================
+
+/// Replicated per-volume write journal
+pub struct WriteJournal {
+    /// Replica directories
+    replicas: Vec<PathBuf>,
+
+    /// Whether appends are fsynced before they are acknowledged
+    sync_writes: bool,
+
+    /// Metadata manager (volume -> policy)
+    metadata_manager: Arc<EcMetadataManager>,
+
+    /// Open volume journals
+    volumes: DashMap<String, Arc<Mutex<VolumeJournal>>>,
+
+    /// Signalled when a volume crosses its destage threshold
+    destage_wanted: Notify,
+
+    /// How long an append to a full journal waits for destaging to free room
+    full_wait: Duration,
+
+    /// Signalled whenever entries are truncated
+    space_freed: Notify,
+}
+
+impl WriteJournal {
+    /// Open the journal over `replicas`, replaying any un-destaged entries.
+    ///
+    /// Replayed volumes are registered with the metadata manager under the
+    /// policy they were journaled with.
+    pub async fn open(
+        replicas: Vec<PathBuf>,
+        sync_writes: bool,
+        metadata_manager: Arc<EcMetadataManager>,
+    ) -> Result<Arc<Self>> {
+        Self::open_with_full_wait(replicas, sync_writes, Duration::ZERO, metadata_manager).await
+    }
+
+    /// Open the journal with backpressure on full volumes.
+    ///
+    /// An append that does not fit waits up to `full_wait` for destaging to
+    /// truncate enough entries before failing with `EcJournalFull`.
+    pub async fn open_with_full_wait(
+        replicas: Vec<PathBuf>,
+        sync_writes: bool,
+        full_wait: Duration,
+        metadata_manager: Arc<EcMetadataManager>,
+    ) -> Result<Arc<Self>> {
+        if replicas.is_empty() {
+            return Err(Error::InvalidEcConfig(
+                "write journal needs at least one replica directory".to_string(),
+            ));
+        }
+        for dir in &replicas {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let journal = Arc::new(Self {
+            replicas,
+            sync_writes,
+            metadata_manager,
+            volumes: DashMap::new(),
+            destage_wanted: Notify::new(),
+            full_wait,
+            space_freed: Notify::new(),
+        });
+        journal.replay().await?;
+        Ok(journal)
+    }
+
+    /// Replay every volume found in any replica
+    async fn replay(&self) -> Result<()> {
+        let mut found: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for (index, dir) in self.replicas.iter().enumerate() {
+            for entry in std::fs::read_dir(dir)? {
+                let entry = entry?;
+                if entry.file_type()?.is_dir() {
+                    if let Some(name) = entry.file_name().to_str() {
+                        found.entry(name.to_string()).or_default().push(index);
+                    }
+                }
+            }
+        }
+
+        for (volume_id, holders) in found {
+            let policy = holders.iter().find_map(|&r| {
+                let path = self.replicas[r].join(&volume_id).join(VOLUME_FILE);
+                let bytes = std::fs::read(path).ok()?;
+                serde_json::from_slice::<VolumeFile>(&bytes).ok()
+            });
+            let Some(VolumeFile { policy }) = policy else {
+                warn!(
+                    "Ignoring journal directory {} without volume metadata",
+                    volume_id
+                );
+                continue;
+            };
+
+            let settings = self.resolve_settings(&volume_id, &policy).await;
+            let replicas_dirs = self.replicas.clone();
+            let volume = tokio::task::spawn_blocking(move || {
+                replay_volume(&replicas_dirs, &volume_id, &holders, settings)
+            })
+            .await
+            .map_err(|e| Error::Internal(format!("Journal replay task failed: {}", e)))??;
+
+            self.metadata_manager
+                .get_or_create_volume(&volume.volume_id, &policy);
+            if !volume.entries.is_empty() {
+                info!(
+                    "Replayed {} journal entries ({} bytes) for volume {}",
+                    volume.entries.len(),
+                    volume.used_bytes,
+                    volume.volume_id
+                );
+            }
+            let due = volume.over_threshold();
+            self.volumes
+                .insert(volume.volume_id.clone(), Arc::new(Mutex::new(volume)));
+            if due {
+                self.destage_wanted.notify_one();
+            }
+        }
+        Ok(())
+    }
+
+    /// Journal settings for a volume's policy, falling back to the defaults
+    async fn resolve_settings(&self, volume_id: &str, policy_ref: &str) -> VolumeSettings {
+        let config = match self.metadata_manager.load_policy(policy_ref).await {
+            Ok(policy) => policy.spec.journal_config.unwrap_or_default(),
+            Err(e) => {
+                warn!(
+                    "Using default journal settings for volume {}: {}",
+                    volume_id, e
+                );
+                JournalConfig::default()
+            }
+        };
+        VolumeSettings::from_config(&config).unwrap_or_else(|e| {
+            warn!(
+                "Invalid journal config of policy {}, using defaults: {}",
+                policy_ref, e
+            );
+            VolumeSettings::from_config(&JournalConfig::default())
+                .expect("default journal config is valid")
+        })
+    }
+
+    /// Get the journal of a volume, creating it on first use
+    async fn volume(&self, volume_id: &str) -> Result<Arc<Mutex<VolumeJournal>>> {
+        if let Some(volume) = self.volumes.get(volume_id) {
+            return Ok(volume.clone());
+        }
+        validate_volume_id(volume_id)?;
+
+        let policy_ref = self
+            .metadata_manager
+            .get_volume(volume_id)
+            .map(|state| state.read().policy_ref.clone())
+            .ok_or_else(|| Error::EcJournalFailed {
+                volume_id: volume_id.to_string(),
+                reason: "volume is not registered for erasure coding".to_string(),
+            })?;
+        let policy = self.metadata_manager.load_policy(&policy_ref).await?;
+        let settings =
+            VolumeSettings::from_config(&policy.spec.journal_config.unwrap_or_default())?;
+        let replicas = choose_replicas(volume_id, settings.replication, self.replicas.len());
+
+        let volume_file = serde_json::to_vec(&VolumeFile {
+            policy: policy_ref.clone(),
+        })
+        .map_err(|e| Error::Internal(format!("Failed to encode journal metadata: {}", e)))?;
+        for &r in &replicas {
+            let dir = self.replicas[r].join(volume_id);
+            std::fs::create_dir_all(&dir)?;
+            write_atomic(&dir.join(VOLUME_FILE), &volume_file)?;
+        }
+
+        debug!(
+            "Opened journal for volume {} on replicas {:?}",
+            volume_id, replicas
+        );
+        let volume = VolumeJournal {
+            volume_id: volume_id.to_string(),
+            settings,
+            replicas,
+            entries: BTreeMap::new(),
+            used_bytes: 0,
+            next_seq: 1,
+            destaged_through: 0,
+            segments: BTreeMap::new(),
+            active: None,
+        };
+        Ok(self
+            .volumes
+            .entry(volume_id.to_string())
+            .or_insert_with(|| Arc::new(Mutex::new(volume)))
+            .clone())
+    }
+
+    /// Append a write to a volume's journal, returning its sequence number.
+    ///
+    /// The write is acknowledged once it is stored on every replica of the
+    /// volume. `data` must be a whole number of logical blocks.
+    pub async fn append(&self, volume_id: &str, start_lba: u64, data: &[u8]) -> Result<u64> {
+        if data.is_empty() || !(data.len() as u64).is_multiple_of(LBA_SIZE) {
+            return Err(Error::EcJournalFailed {
+                volume_id: volume_id.to_string(),
+                reason: format!(
+                    "write of {} bytes is not a whole number of {}-byte blocks",
+                    data.len(),
+                    LBA_SIZE
+                ),
+            });
+        }
+        if data.len() as u64 + ENTRY_HEADER_BYTES > u32::MAX as u64 {
+            return Err(Error::EcJournalFailed {
+                volume_id: volume_id.to_string(),
+                reason: format!("write of {} bytes exceeds the entry size limit", data.len()),
+            });
+        }
+
+        let volume = self.volume(volume_id).await?;
+        let data = Arc::new(data.to_vec());
+        let deadline = tokio::time::Instant::now() + self.full_wait;
+
+        loop {
+            // Register before trying so a truncate in between is not missed
+            let space_freed = self.space_freed.notified();
+            tokio::pin!(space_freed);
+            space_freed.as_mut().enable();
+
+            let dirs = self.replicas.clone();
+            let sync_writes = self.sync_writes;
+            let (volume, data) = (volume.clone(), data.clone());
+            let appended = tokio::task::spawn_blocking(move || {
+                let mut volume = volume.lock();
+                append_entry(&mut volume, &dirs, sync_writes, start_lba, &data)
+                    .map(|seq| (seq, volume.over_threshold()))
+            })
+            .await
+            .map_err(|e| Error::Internal(format!("Journal append task failed: {}", e)))?;
+
+            match appended {
+                Ok((seq, due)) => {
+                    if due {
+                        self.destage_wanted.notify_one();
+                    }
+                    return Ok(seq);
+                }
+                Err(Error::EcJournalFull { .. }) if tokio::time::Instant::now() < deadline => {
+                    // Hold the writer back until destaging frees room
+                    self.destage_wanted.notify_one();
+                    let _ = tokio::time::timeout_at(deadline, space_freed).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Fill level of a volume's journal
+    pub fn stats(&self, volume_id: &str) -> Option<JournalStats> {
+        self.volumes.get(volume_id).map(|v| v.lock().stats())
+    }
+
+    /// Journal fill percentage of a volume (0 if it has no journal)
+    pub fn fill_percent(&self, volume_id: &str) -> u8 {
+        self.stats(volume_id).map_or(0, |s| s.fill_percent)
+    }
+
+    /// Whether a volume has reached its destage threshold or interval
+    pub fn is_due(&self, volume_id: &str) -> bool {
+        self.volumes
+            .get(volume_id)
+            .is_some_and(|v| v.lock().is_due(Utc::now()))
+    }
+
+    /// Volumes due for destaging
+    pub fn due_volumes(&self) -> Vec<String> {
+        let now = Utc::now();
+        let mut due: Vec<String> = self
+            .volumes
+            .iter()
+            .filter(|v| v.value().lock().is_due(now))
+            .map(|v| v.key().clone())
+            .collect();
+        due.sort();
+        due
+    }
+
+    /// Volumes with a journal
+    pub fn volume_ids(&self) -> Vec<String> {
+        self.volumes.iter().map(|v| v.key().clone()).collect()
+    }
+
+    /// Wait until a volume crosses its destage threshold
+    pub async fn destage_wanted(&self) {
+        self.destage_wanted.notified().await
+    }
+
+    /// Ask the destage loop to run again
+    pub fn request_destage(&self) {
+        self.destage_wanted.notify_one();
+    }
+
+    /// Read the oldest pending entries of a volume, in sequence order.
+    ///
+    /// Stops once `max_bytes` of data has been collected, but always
+    /// returns at least one entry if any are pending.
+    pub async fn read_pending(
+        &self,
+        volume_id: &str,
+        max_bytes: u64,
+    ) -> Result<Vec<(u64, JournalEntry)>> {
+        let Some(volume) = self.volumes.get(volume_id).map(|v| v.clone()) else {
+            return Ok(vec![]);
+        };
+
+        // Snapshot the index so appends are not blocked on the reads
+        let pending: Vec<(u64, IndexedEntry)> = {
+            let volume = volume.lock();
+            let mut bytes = 0;
+            volume
+                .entries
+                .iter()
+                .take_while(|(_, e)| {
+                    let take = bytes == 0 || bytes + e.data_len <= max_bytes;
+                    bytes += e.data_len;
+                    take
+                })
+                .map(|(seq, e)| (*seq, e.clone()))
+                .collect()
+        };
+
+        let dirs = self.replicas.clone();
+        let volume_id = volume_id.to_string();
+        tokio::task::spawn_blocking(move || read_entries(&dirs, &volume_id, pending))
+            .await
+            .map_err(|e| Error::Internal(format!("Journal read task failed: {}", e)))?
+    }
+
+    /// Read the pending entries of a volume that overlap `range`, in
+    /// sequence order.
+    ///
+    /// The volume stays locked while they are read, so a concurrent
+    /// truncate cannot remove their segments midway.
+    pub async fn read_overlapping(
+        &self,
+        volume_id: &str,
+        range: &LbaRange,
+    ) -> Result<Vec<(u64, JournalEntry)>> {
+        let Some(volume) = self.volumes.get(volume_id).map(|v| v.clone()) else {
+            return Ok(vec![]);
+        };
+        let dirs = self.replicas.clone();
+        let volume_id = volume_id.to_string();
+        let (start, end) = (range.start_lba, range.end_lba);
+
+        tokio::task::spawn_blocking(move || {
+            let volume = volume.lock();
+            let overlapping: Vec<(u64, IndexedEntry)> = volume
+                .entries
+                .iter()
+                .filter(|(_, e)| e.start_lba < end && e.start_lba + e.data_len / LBA_SIZE > start)
+                .map(|(seq, e)| (*seq, e.clone()))
+                .collect();
+            read_entries(&dirs, &volume_id, overlapping)
+        })
+        .await
+        .map_err(|e| Error::Internal(format!("Journal read task failed: {}", e)))?
+    }
+
+    /// Drop entries up to and including `through_seq` once they are durable
+    /// in EC stripes.
+    pub async fn truncate(&self, volume_id: &str, through_seq: u64) -> Result<()> {
+        let Some(volume) = self.volumes.get(volume_id).map(|v| v.clone()) else {
+            return Ok(());
+        };
+        let dirs = self.replicas.clone();
+
+        tokio::task::spawn_blocking(move || {
+            truncate_volume(&mut volume.lock(), &dirs, through_seq)
+        })
+        .await
+        .map_err(|e| Error::Internal(format!("Journal truncate task failed: {}", e)))??;
+        self.space_freed.notify_waiters();
+        Ok(())
+    }
+}
+
+// =============================================================================
+// Blocking Journal Operations
+// =============================================================================
+
+fn append_entry(
+    volume: &mut VolumeJournal,
+    dirs: &[PathBuf],
+    sync_writes: bool,
+    start_lba: u64,
+    data: &[u8],
+) -> Result<u64> {
+    let frame_len = FRAME_HEADER_BYTES + ENTRY_HEADER_BYTES + data.len() as u64;
+    if volume.used_bytes + frame_len > volume.settings.capacity_bytes {
+        return Err(Error::EcJournalFull {
+            volume_id: volume.volume_id.clone(),
+            used_bytes: volume.used_bytes,
+            capacity_bytes: volume.settings.capacity_bytes,
+        });
+    }
+
+    let seq = volume.next_seq;
+    let timestamp = Utc::now();
+    let frame = encode_frame(seq, start_lba, timestamp, data);
+
+    if volume.active.is_none() {
+        let mut files = Vec::with_capacity(volume.replicas.len());
+        for &r in &volume.replicas {
+            let path = dirs[r].join(&volume.volume_id).join(segment_name(seq));
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .map_err(|e| volume.failed(format!("cannot open {}: {}", path.display(), e)))?;
+            // The new segment's directory entry must be durable before any
+            // entry in it is acknowledged
+            if sync_writes {
+                if let Some(dir) = path.parent() {
+                    File::open(dir).and_then(|d| d.sync_all()).map_err(|e| {
+                        volume.failed(format!("cannot sync {}: {}", dir.display(), e))
+                    })?;
+                }
+            }
+            files.push((r, file, 0));
+        }
+        volume
+            .segments
+            .insert(seq, volume.replicas.iter().copied().collect());
+        volume.active = Some(ActiveSegment {
+            first_seq: seq,
+            files,
+        });
+    }
+    let active = volume.active.as_mut().expect("active segment");
+
+    // Write every replica in parallel; the entry is only durable on all
+    let write = |file: &mut File| -> std::io::Result<()> {
+        file.write_all(&frame)?;
+        if sync_writes {
+            file.sync_data()?;
+        }
+        Ok(())
+    };
+    let results: Vec<std::io::Result<()>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = active
+            .files
+            .iter_mut()
+            .map(|(_, file, _)| {
+                let write = &write;
+                scope.spawn(move || write(file))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|h| {
+                h.join()
+                    .unwrap_or_else(|_| Err(std::io::Error::other("journal writer panicked")))
+            })
+            .collect()
+    });
+
+    if let Some((i, e)) = results
+        .iter()
+        .enumerate()
+        .find_map(|(i, r)| r.as_ref().err().map(|e| (i, e.to_string())))
+    {
+        // Best-effort rollback so no replica keeps an unacknowledged entry
+        for (_, file, len) in active.files.iter_mut() {
+            let _ = file.set_len(*len);
+        }
+        let replica = active.files[i].0;
+        volume.active = None;
+        return Err(volume.failed(format!(
+            "append to replica {} failed: {}",
+            dirs[replica].display(),
+            e
+        )));
+    }
+
+    let first_seq = active.first_seq;
+    let mut copies = Vec::with_capacity(active.files.len());
+    for (replica, _, len) in active.files.iter_mut() {
+        copies.push(EntryCopy {
+            replica: *replica,
+            segment: first_seq,
+            offset: *len,
+        });
+        *len += frame_len;
+    }
+    if active
+        .files
+        .iter()
+        .any(|(_, _, len)| *len >= SEGMENT_MAX_BYTES)
+    {
+        volume.active = None;
+    }
+
+    volume.entries.insert(
+        seq,
+        IndexedEntry {
+            start_lba,
+            data_len: data.len() as u64,
+            timestamp,
+            copies,
+        },
+    );
+    volume.used_bytes += frame_len;
+    volume.next_seq += 1;
+    Ok(seq)
+}
+
+fn read_entries(
+    dirs: &[PathBuf],
+    volume_id: &str,
+    pending: Vec<(u64, IndexedEntry)>,
+) -> Result<Vec<(u64, JournalEntry)>> {
+    let mut files: HashMap<(usize, u64), Option<File>> = HashMap::new();
+    let mut entries = Vec::with_capacity(pending.len());
+
+    'entries: for (seq, entry) in pending {
+        for copy in &entry.copies {
+            let file = files
+                .entry((copy.replica, copy.segment))
+                .or_insert_with(|| {
+                    File::open(
+                        dirs[copy.replica]
+                            .join(volume_id)
+                            .join(segment_name(copy.segment)),
+                    )
+                    .ok()
+                });
+            let Some(file) = file else {
+                continue;
+            };
+            if file.seek(SeekFrom::Start(copy.offset)).is_err() {
+                continue;
+            }
+            match read_frame(file) {
+                Some(frame) if frame.seq == seq => {
+                    let end_lba = frame.start_lba + frame.data.len() as u64 / LBA_SIZE;
+                    entries.push((
+                        seq,
+                        JournalEntry {
+                            lba_range: LbaRange::new(frame.start_lba, end_lba),
+                            data: frame.data,
+                            timestamp: frame.timestamp,
+                        },
+                    ));
+                    continue 'entries;
+                }
+                _ => warn!(
+                    "Journal entry {} of volume {} is unreadable on replica {}",
+                    seq,
+                    volume_id,
+                    dirs[copy.replica].display()
+                ),
+            }
+        }
+        return Err(Error::EcJournalFailed {
+            volume_id: volume_id.to_string(),
+            reason: format!("entry {} is unreadable on every replica", seq),
+        });
+    }
+    Ok(entries)
+}
+
+fn truncate_volume(volume: &mut VolumeJournal, dirs: &[PathBuf], through_seq: u64) -> Result<()> {
+    let through_seq = through_seq.min(volume.next_seq.saturating_sub(1));
+    if through_seq <= volume.destaged_through {
+        return Ok(());
+    }
+
+    // Record the checkpoint first so replay never resurrects destaged data
+    let checkpoint = format!("{}\n", through_seq);
+    let mut recorded = 0;
+    for r in volume.all_replicas() {
+        let path = dirs[r].join(&volume.volume_id).join(CHECKPOINT_FILE);
+        match write_atomic(&path, checkpoint.as_bytes()) {
+            Ok(()) => recorded += 1,
+            Err(e) => warn!(
+                "Failed to write journal checkpoint {}: {}",
+                path.display(),
+                e
+            ),
+        }
+    }
+    if recorded == 0 {
+        return Err(volume.failed("checkpoint could not be written to any replica"));
+    }
+
+    volume.destaged_through = through_seq;
+    let kept = volume.entries.split_off(&(through_seq + 1));
+    let dropped = std::mem::replace(&mut volume.entries, kept);
+    volume.used_bytes -= dropped.values().map(|e| e.frame_len()).sum::<u64>();
+
+    // Delete segments whose entries have all been destaged
+    let firsts: Vec<u64> = volume.segments.keys().copied().collect();
+    for (i, first) in firsts.iter().enumerate() {
+        let last = firsts
+            .get(i + 1)
+            .copied()
+            .unwrap_or(volume.next_seq)
+            .saturating_sub(1);
+        if last > through_seq {
+            break;
+        }
+        if volume
+            .active
+            .as_ref()
+            .is_some_and(|a| a.first_seq == *first)
+        {
+            volume.active = None;
+        }
+        for r in volume.segments.remove(first).unwrap_or_default() {
+            let path = dirs[r].join(&volume.volume_id).join(segment_name(*first));
+            if let Err(e) = std::fs::remove_file(&path) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Failed to remove journal segment {}: {}", path.display(), e);
+                }
+            }
+        }
+    }
+
+    debug!(
+        "Truncated journal of volume {} through entry {} ({} entries pending)",
+        volume.volume_id,
+        through_seq,
+        volume.entries.len()
+    );
+    Ok(())
+}
+
+fn replay_volume(
+    dirs: &[PathBuf],
+    volume_id: &str,
+    holders: &[usize],
+    settings: VolumeSettings,
+) -> Result<VolumeJournal> {
+    let destaged_through = holders
+        .iter()
+        .map(|&r| read_checkpoint(&dirs[r].join(volume_id)))
+        .max()
+        .unwrap_or(0);
+
+    let mut entries: BTreeMap<u64, IndexedEntry> = BTreeMap::new();
+    let mut segments: BTreeMap<u64, BTreeSet<usize>> = BTreeMap::new();
+    let mut max_seq = destaged_through;
+
+    for &r in holders {
+        let dir = dirs[r].join(volume_id);
+        for file in std::fs::read_dir(&dir)? {
+            let file = file?;
+            let Some(first) = file.file_name().to_str().and_then(parse_segment_name) else {
+                continue;
+            };
+            segments.entry(first).or_default().insert(r);
+
+            let path = file.path();
+            let mut reader = BufReader::new(File::open(&path)?);
+            let mut offset = 0u64;
+            while let Some(frame) = read_frame(&mut reader) {
+                let frame_len = FRAME_HEADER_BYTES + ENTRY_HEADER_BYTES + frame.data.len() as u64;
+                max_seq = max_seq.max(frame.seq);
+                if frame.seq > destaged_through {
+                    let copy = EntryCopy {
+                        replica: r,
+                        segment: first,
+                        offset,
+                    };
+                    entries
+                        .entry(frame.seq)
+                        .or_insert_with(|| IndexedEntry {
+                            start_lba: frame.start_lba,
+                            data_len: frame.data.len() as u64,
+                            timestamp: frame.timestamp,
+                            copies: vec![],
+                        })
+                        .copies
+                        .push(copy);
+                }
+                offset += frame_len;
+            }
+
+            let file_len = std::fs::metadata(&path)?.len();
+            if offset < file_len {
+                warn!(
+                    "Truncating {} bytes of torn or corrupt journal segment {}",
+                    file_len - offset,
+                    path.display()
+                );
+                OpenOptions::new()
+                    .write(true)
+                    .open(&path)?
+                    .set_len(offset)?;
+            }
+        }
+    }
+
+    let used_bytes = entries.values().map(|e| e.frame_len()).sum();
+    let replicas = choose_replicas(volume_id, settings.replication, dirs.len());
+
+    Ok(VolumeJournal {
+        volume_id: volume_id.to_string(),
+        settings,
+        replicas,
+        entries,
+        used_bytes,
+        next_seq: max_seq + 1,
+        destaged_through,
+        segments,
+        active: None,
+    })
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crd::{ErasureCodingPolicy, ErasureCodingPolicySpec};
+    use crate::ec::test_support;
+    use tempfile::TempDir;
+
+    const VOLUME: &str = "vol-1";
+    const POLICY: &str = "ec-4-2";
+
+    fn metadata_manager(journal: JournalConfig) -> Arc<EcMetadataManager> {
+        let manager = test_support::metadata_manager();
+        manager.register_policy(ErasureCodingPolicy::new(
+            POLICY,
+            ErasureCodingPolicySpec {
+                journal_config: Some(journal),
+                ..test_support::policy_spec(4, 2, 4096)
+            },
+        ));
+        manager.get_or_create_volume(VOLUME, POLICY);
+        manager
+    }
+
+    fn journal_config(size: u64, replication: u8, interval: &str) -> JournalConfig {
+        JournalConfig {
+            journal_size_bytes: size,
+            replication_factor: replication,
+            destage_threshold_percent: 80,
+            destage_interval: interval.to_string(),
+        }
+    }
+
+    fn replica_dirs(count: usize) -> (Vec<TempDir>, Vec<PathBuf>) {
+        let dirs: Vec<TempDir> = (0..count).map(|_| tempfile::tempdir().unwrap()).collect();
+        let paths = dirs.iter().map(|d| d.path().to_path_buf()).collect();
+        (dirs, paths)
+    }
+
+    fn segments(dir: &Path) -> Vec<PathBuf> {
+        let Ok(files) = std::fs::read_dir(dir.join(VOLUME)) else {
+            return vec![];
+        };
+        let mut segments: Vec<PathBuf> = files
+            .map(|f| f.unwrap().path())
+            .filter(|p| p.extension().is_some_and(|e| e == SEGMENT_EXT))
+            .collect();
+        segments.sort();
+        segments
+    }
+
+    const FRAME: u64 = FRAME_HEADER_BYTES + ENTRY_HEADER_BYTES;
+
+    #[tokio::test]
+    async fn test_append_replicates_to_configured_replicas() {
+        let (_dirs, paths) = replica_dirs(3);
+        let manager = metadata_manager(journal_config(1 << 20, 2, "1h"));
+        let journal = WriteJournal::open(paths.clone(), false, manager)
+            .await
+            .unwrap();
+
+        assert_eq!(journal.append(VOLUME, 0, &[1u8; 1024]).await.unwrap(), 1);
+        assert_eq!(journal.append(VOLUME, 2, &[2u8; 512]).await.unwrap(), 2);
+
+        let holders: Vec<&PathBuf> = paths.iter().filter(|p| !segments(p).is_empty()).collect();
+        assert_eq!(holders.len(), 2);
+        for holder in holders {
+            let segment = &segments(holder)[0];
+            assert_eq!(std::fs::metadata(segment).unwrap().len(), 2 * FRAME + 1536);
+        }
+
+        let stats = journal.stats(VOLUME).unwrap();
+        assert_eq!(stats.entries, 2);
+        assert_eq!(stats.used_bytes, 2 * FRAME + 1536);
+        assert_eq!(stats.destaged_through, 0);
+        assert!(!journal.is_due(VOLUME));
+
+        let pending = journal.read_pending(VOLUME, u64::MAX).await.unwrap();
+        assert_eq!(pending.len(), 2);
+        let range = &pending[0].1.lba_range;
+        assert_eq!((range.start_lba, range.end_lba), (0, 2));
+        assert_eq!(pending[1].1.data, vec![2u8; 512]);
+    }
+
+    #[tokio::test]
+    async fn test_synced_appends_survive_reopen() {
+        let (_dirs, paths) = replica_dirs(2);
+        let manager = metadata_manager(journal_config(1 << 20, 2, "1h"));
+        {
+            let journal = WriteJournal::open(paths.clone(), true, manager.clone())
+                .await
+                .unwrap();
+            journal.append(VOLUME, 0, &[1u8; 1024]).await.unwrap();
+            journal.append(VOLUME, 2, &[2u8; 512]).await.unwrap();
+        }
+
+        let journal = WriteJournal::open(paths, true, manager).await.unwrap();
+        let pending = journal.read_pending(VOLUME, u64::MAX).await.unwrap();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[1].1.data, vec![2u8; 512]);
+    }
+
+    #[tokio::test]
+    async fn test_read_overlapping_entries() {
+        let (_dirs, paths) = replica_dirs(1);
+        let manager = metadata_manager(journal_config(1 << 20, 1, "1h"));
+        let journal = WriteJournal::open(paths, false, manager).await.unwrap();
+
+        journal.append(VOLUME, 0, &[1u8; 1024]).await.unwrap();
+        journal.append(VOLUME, 4, &[2u8; 512]).await.unwrap();
+        journal.append(VOLUME, 1, &[3u8; 512]).await.unwrap();
+
+        let seqs = |entries: Vec<(u64, JournalEntry)>| -> Vec<u64> {
+            entries.into_iter().map(|(seq, _)| seq).collect()
+        };
+        let overlapping = journal
+            .read_overlapping(VOLUME, &LbaRange::new(1, 2))
+            .await
+            .unwrap();
+        assert_eq!(overlapping[1].1.data, vec![3u8; 512]);
+        assert_eq!(seqs(overlapping), vec![1, 3]);
+
+        let overlapping = journal
+            .read_overlapping(VOLUME, &LbaRange::new(2, 5))
+            .await
+            .unwrap();
+        assert_eq!(seqs(overlapping), vec![2]);
+
+        // Destaged entries are no longer returned
+        journal.truncate(VOLUME, 2).await.unwrap();
+        let overlapping = journal
+            .read_overlapping(VOLUME, &LbaRange::new(0, 8))
+            .await
+            .unwrap();
+        assert_eq!(seqs(overlapping), vec![3]);
+        assert!(journal
+            .read_overlapping("unknown", &LbaRange::new(0, 8))
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_append_rejects_invalid_writes() {
+        let (_dirs, paths) = replica_dirs(1);
+        let manager = metadata_manager(journal_config(1 << 20, 3, "1h"));
+        let journal = WriteJournal::open(paths, false, manager).await.unwrap();
+
+        // More replicas than configured directories: every directory is used
+        journal.append(VOLUME, 0, &[0u8; 512]).await.unwrap();
+        assert_eq!(journal.stats(VOLUME).unwrap().entries, 1);
+
+        let (_dirs, paths) = replica_dirs(1);
+        let manager = metadata_manager(journal_config(1 << 20, 1, "1h"));
+        let journal = WriteJournal::open(paths, false, manager).await.unwrap();
+        assert!(journal.append(VOLUME, 0, &[0u8; 100]).await.is_err());
+        assert!(journal.append(VOLUME, 0, &[]).await.is_err());
+        assert!(journal.append("unknown", 0, &[0u8; 512]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fill_threshold_and_capacity() {
+        let (_dirs, paths) = replica_dirs(1);
+        let capacity = 4 * (FRAME + 1024);
+        let manager = metadata_manager(journal_config(capacity, 1, "1h"));
+        let journal = WriteJournal::open(paths, false, manager).await.unwrap();
+
+        for i in 0..3 {
+            journal.append(VOLUME, i * 2, &[0u8; 1024]).await.unwrap();
+        }
+        assert_eq!(journal.fill_percent(VOLUME), 75);
+        assert!(!journal.is_due(VOLUME));
+
+        journal.append(VOLUME, 6, &[0u8; 1024]).await.unwrap();
+        assert_eq!(journal.fill_percent(VOLUME), 100);
+        assert!(journal.is_due(VOLUME));
+        assert_eq!(journal.due_volumes(), vec![VOLUME.to_string()]);
+
+        let err = journal.append(VOLUME, 8, &[0u8; 512]).await.unwrap_err();
+        assert!(matches!(err, Error::EcJournalFull { .. }));
+
+        // Destaging frees room again
+        journal.truncate(VOLUME, 2).await.unwrap();
+        assert_eq!(journal.fill_percent(VOLUME), 50);
+        journal.append(VOLUME, 8, &[0u8; 512]).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_full_append_waits_for_truncate() {
+        let (_dirs, paths) = replica_dirs(1);
+        let capacity = 2 * (FRAME + 1024);
+        let manager = metadata_manager(journal_config(capacity, 1, "1h"));
+        let journal =
+            WriteJournal::open_with_full_wait(paths, false, Duration::from_secs(10), manager)
+                .await
+                .unwrap();
+        journal.append(VOLUME, 0, &[0u8; 1024]).await.unwrap();
+        journal.append(VOLUME, 2, &[0u8; 1024]).await.unwrap();
+
+        // The blocked writer resumes once destaging truncates
+        let writer = {
+            let journal = journal.clone();
+            tokio::spawn(async move { journal.append(VOLUME, 4, &[0u8; 1024]).await })
+        };
+        // One wakeup for crossing the threshold, one from the blocked writer
+        journal.destage_wanted().await;
+        journal.destage_wanted().await;
+        assert!(!writer.is_finished());
+        journal.truncate(VOLUME, 1).await.unwrap();
+        assert_eq!(writer.await.unwrap().unwrap(), 3);
+
+        // Without room the wait runs out
+        let journal = WriteJournal::open_with_full_wait(
+            journal.replicas.clone(),
+            false,
+            Duration::from_millis(50),
+            journal.metadata_manager.clone(),
+        )
+        .await
+        .unwrap();
+        let err = journal.append(VOLUME, 6, &[0u8; 1024]).await.unwrap_err();
+        assert!(matches!(err, Error::EcJournalFull { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_interval_makes_volume_due() {
+        let (_dirs, paths) = replica_dirs(1);
+        let manager = metadata_manager(journal_config(1 << 20, 1, "1s"));
+        let journal = WriteJournal::open(paths, false, manager).await.unwrap();
+
+        assert!(!journal.is_due(VOLUME));
+        journal.append(VOLUME, 0, &[0u8; 512]).await.unwrap();
+        assert_eq!(journal.fill_percent(VOLUME), 0);
+        assert!(!journal.is_due(VOLUME));
+
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert!(journal.is_due(VOLUME));
+    }
+
+    #[tokio::test]
+    async fn test_truncate_removes_destaged_segments() {
+        let (_dirs, paths) = replica_dirs(2);
+        let manager = metadata_manager(journal_config(1 << 20, 2, "1h"));
+        let journal = WriteJournal::open(paths.clone(), false, manager.clone())
+            .await
+            .unwrap();
+
+        journal.append(VOLUME, 0, &[1u8; 512]).await.unwrap();
+        journal.append(VOLUME, 1, &[2u8; 512]).await.unwrap();
+        journal.truncate(VOLUME, 1).await.unwrap();
+
+        // The segment still holds entry 2
+        assert_eq!(segments(&paths[0]).len(), 1);
+        let pending = journal.read_pending(VOLUME, u64::MAX).await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].0, 2);
+
+        journal.truncate(VOLUME, 2).await.unwrap();
+        assert!(segments(&paths[0]).is_empty());
+        assert!(segments(&paths[1]).is_empty());
+
+        let stats = journal.stats(VOLUME).unwrap();
+        assert_eq!(stats.entries, 0);
+        assert_eq!(stats.used_bytes, 0);
+        assert_eq!(stats.destaged_through, 2);
+
+        // Sequence numbers continue in a new segment after reopening
+        drop(journal);
+        let journal = WriteJournal::open(paths.clone(), false, manager)
+            .await
+            .unwrap();
+        assert_eq!(journal.stats(VOLUME).unwrap().entries, 0);
+        assert_eq!(journal.append(VOLUME, 2, &[3u8; 512]).await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_replay_after_restart() {
+        let (_dirs, paths) = replica_dirs(2);
+        let manager = metadata_manager(journal_config(1 << 20, 2, "1h"));
+        let journal = WriteJournal::open(paths.clone(), false, manager)
+            .await
+            .unwrap();
+
+        for i in 0..3u8 {
+            journal
+                .append(VOLUME, i as u64, &[i + 1; 512])
+                .await
+                .unwrap();
+        }
+        journal.truncate(VOLUME, 1).await.unwrap();
+        drop(journal);
+
+        // A torn append on one replica, and a lost segment on the other
+        let segment = segments(&paths[0])[0].clone();
+        let mut file = OpenOptions::new().append(true).open(&segment).unwrap();
+        file.write_all(&[0xAB; 20]).unwrap();
+        std::fs::remove_file(&segments(&paths[1])[0]).unwrap();
+
+        // A fresh process learns the volume from the journal itself
+        let manager = metadata_manager(journal_config(1 << 20, 2, "1h"));
+        let journal = WriteJournal::open(paths.clone(), false, manager.clone())
+            .await
+            .unwrap();
+
+        let stats = journal.stats(VOLUME).unwrap();
+        assert_eq!(stats.entries, 2);
+        assert_eq!(stats.used_bytes, 2 * (FRAME + 512));
+        assert_eq!(stats.destaged_through, 1);
+        assert_eq!(
+            std::fs::metadata(&segment).unwrap().len(),
+            3 * (FRAME + 512)
+        );
+
+        let pending = journal.read_pending(VOLUME, u64::MAX).await.unwrap();
+        let seqs: Vec<u64> = pending.iter().map(|(seq, _)| *seq).collect();
+        assert_eq!(seqs, vec![2, 3]);
+        assert_eq!(pending[0].1.data, vec![2u8; 512]);
+        let range = &pending[1].1.lba_range;
+        assert_eq!((range.start_lba, range.end_lba), (2, 3));
+
+        assert_eq!(journal.append(VOLUME, 3, &[4u8; 512]).await.unwrap(), 4);
+
+        // read_pending honours the byte budget but returns at least one entry
+        assert_eq!(journal.read_pending(VOLUME, 1).await.unwrap().len(), 1);
+        assert_eq!(journal.read_pending(VOLUME, 1024).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_replay_registers_unknown_volume() {
+        let (_dirs, paths) = replica_dirs(1);
+        let manager = metadata_manager(journal_config(1 << 20, 1, "1h"));
+        let journal = WriteJournal::open(paths.clone(), false, manager)
+            .await
+            .unwrap();
+        journal.append(VOLUME, 0, &[0u8; 512]).await.unwrap();
+        drop(journal);
+
+        let manager = test_support::metadata_manager();
+        let journal = WriteJournal::open(paths, false, manager.clone())
+            .await
+            .unwrap();
+
+        let volume = manager.get_volume(VOLUME).unwrap();
+        assert_eq!(volume.read().policy_ref, POLICY);
+        assert_eq!(journal.stats(VOLUME).unwrap().entries, 1);
+        // The policy is unavailable, so the default settings apply
+        assert_eq!(
+            journal.stats(VOLUME).unwrap().capacity_bytes,
+            JournalConfig::default().journal_size_bytes
+        );
+    }
+}
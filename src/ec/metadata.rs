@@ -7,13 +7,20 @@ use crate::crd::{
     ECStripe, ECStripeSpec, ECStripeStatus, ErasureCodingPolicy, LbaRange, ShardHealth,
     ShardLocation, ShardState, StripeState,
 };
+use crate::domain::ports::{
+    LbaRange as DomainLbaRange, ShardHealthState, ShardLocation as DomainShardLocation, StripeId,
+    StripeMetadata as DomainStripeMetadata, StripeRepository, VolumeId,
+};
 use crate::error::{Error, Result};
 use chrono::Utc;
 use dashmap::DashMap;
 use kube::api::{Api, ListParams, Patch, PatchParams, PostParams};
 use kube::Client;
+use serde::Deserialize;
 use std::collections::BTreeMap;
+use std::path::Path;
 use std::sync::Arc;
+use tokio::sync::Notify;
 use tracing::{debug, info, instrument};
 
 // =============================================================================
@@ -61,6 +68,89 @@ pub struct StripeStatus {
     pub shard_health: Vec<ShardHealth>,
 }
 
+// =============================================================================
+// Read Tracking
+// =============================================================================
+
+/// Tracks reads that may still use the shard locations they looked up
+///
+/// Shards a stripe no longer references (replaced by a re-stripe, or left
+/// behind by a retired stripe) are only deleted once every read that started
+/// before the change has finished.
+#[derive(Debug, Default)]
+pub struct ReadTracker {
+    /// Current epoch and the number of active reads per epoch
+    epochs: parking_lot::Mutex<ReadEpochs>,
+
+    /// Woken whenever a read finishes
+    finished: Notify,
+}
+
+#[derive(Debug, Default)]
+struct ReadEpochs {
+    current: u64,
+    active: BTreeMap<u64, usize>,
+}
+
+/// An active read, registered until dropped
+#[derive(Debug)]
+pub struct ReadGuard {
+    tracker: Arc<ReadTracker>,
+    epoch: u64,
+}
+
+impl ReadTracker {
+    /// Register a read; take it before looking up stripes
+    pub fn begin(self: &Arc<Self>) -> ReadGuard {
+        let mut epochs = self.epochs.lock();
+        let epoch = epochs.current;
+        *epochs.active.entry(epoch).or_default() += 1;
+        ReadGuard {
+            tracker: self.clone(),
+            epoch,
+        }
+    }
+
+    /// Wait until every read registered before this call has finished
+    ///
+    /// Call it after the change is visible in the volume state; reads that
+    /// begin afterwards see the new state.
+    pub async fn wait_for_readers(&self) {
+        let epoch = {
+            let mut epochs = self.epochs.lock();
+            epochs.current += 1;
+            epochs.current - 1
+        };
+
+        loop {
+            // Enabled before checking, so a read finishing in between is seen
+            let finished = self.finished.notified();
+            tokio::pin!(finished);
+            finished.as_mut().enable();
+
+            if self.epochs.lock().active.range(..=epoch).next().is_none() {
+                return;
+            }
+            finished.await;
+        }
+    }
+}
+
+impl Drop for ReadGuard {
+    fn drop(&mut self) {
+        {
+            let mut epochs = self.tracker.epochs.lock();
+            if let Some(count) = epochs.active.get_mut(&self.epoch) {
+                *count -= 1;
+                if *count == 0 {
+                    epochs.active.remove(&self.epoch);
+                }
+            }
+        }
+        self.tracker.finished.notify_waiters();
+    }
+}
+
 // =============================================================================
 // Volume EC State
 // =============================================================================
@@ -83,8 +173,14 @@ pub struct VolumeEcState {
     /// Next stripe ID
     next_stripe_id: std::sync::atomic::AtomicU64,
 
+    /// LBA ranges of stripes whose IDs are reserved but not yet added
+    reserved: DashMap<u64, LbaRange>,
+
     /// Total data size
     pub data_size: u64,
+
+    /// Reads in flight against this volume
+    readers: Arc<ReadTracker>,
 }
 
 impl VolumeEcState {
@@ -96,23 +192,127 @@ impl VolumeEcState {
             lba_map: BTreeMap::new(),
             stripes: DashMap::new(),
             next_stripe_id: std::sync::atomic::AtomicU64::new(0),
+            reserved: DashMap::new(),
             data_size: 0,
+            readers: Arc::default(),
         }
     }
 
+    /// Register a read of this volume's shards
+    pub fn begin_read(&self) -> ReadGuard {
+        self.readers.begin()
+    }
+
+    /// Get the tracker of reads in flight against this volume
+    pub fn readers(&self) -> Arc<ReadTracker> {
+        self.readers.clone()
+    }
+
     /// Get the next stripe ID
     pub fn next_stripe_id(&self) -> u64 {
         self.next_stripe_id
             .fetch_add(1, std::sync::atomic::Ordering::SeqCst)
     }
 
+    /// Reserve the next stripe ID for data about to be written to `range`
+    ///
+    /// Until the stripe is added (or the reservation released), the range
+    /// counts as newer data in `has_newer_overlap`, so nothing written
+    /// meanwhile can take a higher ID and shadow it with older contents.
+    pub fn reserve_stripe_id(&self, range: LbaRange) -> u64 {
+        let stripe_id = self.next_stripe_id();
+        self.reserved.insert(stripe_id, range);
+        stripe_id
+    }
+
+    /// Drop the reservation of a stripe that will never be added
+    pub fn release_stripe_id(&self, stripe_id: u64) {
+        self.reserved.remove(&stripe_id);
+    }
+
     /// Add a stripe to the volume
+    ///
+    /// Stripe IDs are allocated in write order, so a stripe with a higher ID
+    /// shadows older stripes wherever their LBA ranges overlap.
     pub fn add_stripe(&mut self, metadata: StripeMetadata) {
+        // Keep IDs monotonic for stripes loaded from CRDs
+        self.next_stripe_id
+            .fetch_max(metadata.stripe_id + 1, std::sync::atomic::Ordering::SeqCst);
+        self.reserved.remove(&metadata.stripe_id);
         self.lba_map
             .insert(metadata.lba_range.start_lba, metadata.stripe_id);
         self.stripes.insert(metadata.stripe_id, metadata);
     }
 
+    /// Remove a stripe from the volume
+    ///
+    /// If the stripe owned its start LBA in the map, the newest remaining
+    /// stripe starting at the same LBA takes over.
+    pub fn remove_stripe(&mut self, stripe_id: u64) -> Option<StripeMetadata> {
+        let (_, removed) = self.stripes.remove(&stripe_id)?;
+        let start = removed.lba_range.start_lba;
+
+        if self.lba_map.get(&start) == Some(&stripe_id) {
+            let successor = self
+                .stripes
+                .iter()
+                .filter(|e| e.value().lba_range.start_lba == start)
+                .map(|e| *e.key())
+                .max();
+            match successor {
+                Some(id) => {
+                    self.lba_map.insert(start, id);
+                }
+                None => {
+                    self.lba_map.remove(&start);
+                }
+            }
+        }
+
+        Some(removed)
+    }
+
+    /// Check whether a stripe newer than `stripe_id` overlaps `range`
+    ///
+    /// Reserved stripes count whatever their ID: their data is not visible
+    /// yet, so the caller's copy of `range` cannot include it.
+    pub fn has_newer_overlap(&self, range: &LbaRange, stripe_id: u64) -> bool {
+        self.stripes
+            .iter()
+            .any(|e| *e.key() > stripe_id && e.value().lba_range.overlaps(range))
+            || self.reserved.iter().any(|e| e.value().overlaps(range))
+    }
+
+    /// Compute the LBA ranges of each stripe not shadowed by a newer stripe
+    ///
+    /// Returns every stripe (newest first) with its live ranges; a stripe
+    /// whose list is empty is fully overwritten.
+    pub fn live_ranges(&self) -> Vec<(StripeMetadata, Vec<LbaRange>)> {
+        self.live_ranges_excluding(&[])
+    }
+
+    /// Like `live_ranges`, but with `dead` ranges treated as shadowed (e.g.
+    /// released dedup chunks)
+    pub fn live_ranges_excluding(&self, dead: &[LbaRange]) -> Vec<(StripeMetadata, Vec<LbaRange>)> {
+        let mut stripes = self.all_stripes();
+        stripes.sort_by_key(|s| std::cmp::Reverse(s.stripe_id));
+
+        // Disjoint, merged intervals already claimed by newer stripes
+        let mut covered: BTreeMap<u64, u64> = BTreeMap::new();
+        for range in dead {
+            cover_range(&mut covered, range);
+        }
+
+        stripes
+            .into_iter()
+            .map(|stripe| {
+                let live = uncovered_ranges(&covered, &stripe.lba_range);
+                cover_range(&mut covered, &stripe.lba_range);
+                (stripe, live)
+            })
+            .collect()
+    }
+
     /// Find stripe containing an LBA
     pub fn find_stripe_for_lba(&self, lba: u64) -> Option<StripeMetadata> {
         // Find the stripe with the largest start_lba <= lba
@@ -135,6 +335,19 @@ impl VolumeEcState {
         self.stripes.get(&stripe_id).map(|s| s.clone())
     }
 
+    /// Replace a stripe's metadata (e.g. after shards were rebuilt)
+    ///
+    /// The LBA range must be unchanged; returns false if the stripe is unknown.
+    pub fn update_stripe(&self, metadata: StripeMetadata) -> bool {
+        match self.stripes.get_mut(&metadata.stripe_id) {
+            Some(mut stripe) => {
+                *stripe = metadata;
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Update stripe status
     pub fn update_stripe_status(&self, stripe_id: u64, status: StripeStatus) {
         if let Some(mut stripe) = self.stripes.get_mut(&stripe_id) {
@@ -142,6 +355,18 @@ impl VolumeEcState {
         }
     }
 
+    /// Record a stripe committed at `generation`, unless another writer
+    /// replaced it since it was read at `previous`
+    pub fn advance_generation(&self, stripe_id: u64, previous: u64, generation: u64) -> bool {
+        match self.stripes.get_mut(&stripe_id) {
+            Some(mut stripe) if stripe.generation == previous => {
+                stripe.generation = generation;
+                true
+            }
+            _ => false,
+        }
+    }
+
     /// Get count of stripes by state
     pub fn stripe_counts(&self) -> StripeStateCounts {
         let mut counts = StripeStateCounts::default();
@@ -170,6 +395,61 @@ impl VolumeEcState {
     }
 }
 
+/// Parts of `range` not present in the `covered` interval set
+fn uncovered_ranges(covered: &BTreeMap<u64, u64>, range: &LbaRange) -> Vec<LbaRange> {
+    let mut live = Vec::new();
+    let mut cursor = range.start_lba;
+
+    // The interval starting at or before the range may extend into it
+    let first = covered
+        .range(..=range.start_lba)
+        .next_back()
+        .map(|(s, e)| (*s, *e));
+    let rest = covered
+        .range(range.start_lba.saturating_add(1)..range.end_lba)
+        .map(|(s, e)| (*s, *e));
+
+    for (start, end) in first.into_iter().chain(rest) {
+        if cursor >= range.end_lba {
+            break;
+        }
+        if start > cursor {
+            live.push(LbaRange::new(cursor, start.min(range.end_lba)));
+        }
+        cursor = cursor.max(end);
+    }
+
+    if cursor < range.end_lba {
+        live.push(LbaRange::new(cursor, range.end_lba));
+    }
+
+    live
+}
+
+/// Add `range` to the `covered` interval set, merging touching intervals
+fn cover_range(covered: &mut BTreeMap<u64, u64>, range: &LbaRange) {
+    if range.size() == 0 {
+        return;
+    }
+
+    let mut start = range.start_lba;
+    let mut end = range.end_lba;
+
+    let touching: Vec<(u64, u64)> = covered
+        .range(..=end)
+        .filter(|(_, e)| **e >= start)
+        .map(|(s, e)| (*s, *e))
+        .collect();
+
+    for (s, e) in touching {
+        covered.remove(&s);
+        start = start.min(s);
+        end = end.max(e);
+    }
+
+    covered.insert(start, end);
+}
+
 /// Counts of stripes by state
 #[derive(Debug, Default)]
 pub struct StripeStateCounts {
@@ -186,8 +466,8 @@ pub struct StripeStateCounts {
 
 /// Manages EC metadata for all volumes
 pub struct EcMetadataManager {
-    /// Kubernetes client
-    client: Client,
+    /// Kubernetes client; None for a standalone manager
+    client: Option<Client>,
 
     /// Per-volume EC state
     volumes: DashMap<String, Arc<parking_lot::RwLock<VolumeEcState>>>,
@@ -199,6 +479,18 @@ pub struct EcMetadataManager {
 impl EcMetadataManager {
     /// Create a new metadata manager
     pub fn new(client: Client) -> Arc<Self> {
+        Self::build(Some(client))
+    }
+
+    /// Create a metadata manager that never talks to Kubernetes
+    ///
+    /// Policies must be registered up front, and the ECStripe CRD
+    /// operations fail.
+    pub fn standalone() -> Arc<Self> {
+        Self::build(None)
+    }
+
+    fn build(client: Option<Client>) -> Arc<Self> {
         Arc::new(Self {
             client,
             volumes: DashMap::new(),
@@ -206,6 +498,13 @@ impl EcMetadataManager {
         })
     }
 
+    /// The Kubernetes client, or an error for a standalone manager
+    fn client(&self) -> Result<Client> {
+        self.client
+            .clone()
+            .ok_or_else(|| Error::Config("EC metadata manager runs without Kubernetes".to_string()))
+    }
+
     /// Get or create volume EC state
     pub fn get_or_create_volume(
         &self,
@@ -228,6 +527,11 @@ impl EcMetadataManager {
         self.volumes.get(volume_id).map(|v| v.clone())
     }
 
+    /// IDs of all volumes with EC state
+    pub fn volume_ids(&self) -> Vec<String> {
+        self.volumes.iter().map(|e| e.key().clone()).collect()
+    }
+
     /// Check if a volume has EC enabled
     pub fn volume_has_ec(&self, volume_id: &str) -> bool {
         self.volumes.contains_key(volume_id)
@@ -242,7 +546,10 @@ impl EcMetadataManager {
         }
 
         // Load from Kubernetes
-        let policies_api: Api<ErasureCodingPolicy> = Api::all(self.client.clone());
+        let client = self
+            .client()
+            .map_err(|_| Error::EcPolicyNotFound(policy_name.to_string()))?;
+        let policies_api: Api<ErasureCodingPolicy> = Api::all(client);
         let policy = policies_api
             .get(policy_name)
             .await
@@ -255,10 +562,53 @@ impl EcMetadataManager {
         Ok(policy)
     }
 
+    /// Insert a policy into the cache (e.g. one already fetched by a controller)
+    pub fn register_policy(&self, policy: ErasureCodingPolicy) {
+        self.policies.insert(policy.name().to_string(), policy);
+    }
+
+    /// Register the ErasureCodingPolicy manifests in a YAML file
+    ///
+    /// The file may hold several `---`-separated documents, as applied with
+    /// kubectl. Returns the names of the registered policies.
+    pub fn register_policy_file(&self, path: impl AsRef<Path>) -> Result<Vec<String>> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        let invalid = |e: &dyn std::fmt::Display| {
+            Error::InvalidEcConfig(format!("{}: {}", path.display(), e))
+        };
+
+        let mut policies = Vec::new();
+        for document in serde_yaml::Deserializer::from_str(&contents) {
+            let value = serde_yaml::Value::deserialize(document).map_err(|e| invalid(&e))?;
+            if value.is_null() {
+                continue;
+            }
+            let policy: ErasureCodingPolicy =
+                serde_yaml::from_value(value).map_err(|e| invalid(&e))?;
+            policy
+                .validate()
+                .map_err(|e| invalid(&format!("policy {}: {}", policy.name(), e)))?;
+            policies.push(policy);
+        }
+        if policies.is_empty() {
+            return Err(invalid(&"no ErasureCodingPolicy found"));
+        }
+
+        Ok(policies
+            .into_iter()
+            .map(|policy| {
+                let name = policy.name().to_string();
+                self.register_policy(policy);
+                name
+            })
+            .collect())
+    }
+
     /// Refresh EC policy cache
     #[instrument(skip(self))]
     pub async fn refresh_policies(&self) -> Result<()> {
-        let policies_api: Api<ErasureCodingPolicy> = Api::all(self.client.clone());
+        let policies_api: Api<ErasureCodingPolicy> = Api::all(self.client()?);
         let policies = policies_api.list(&ListParams::default()).await?;
 
         for policy in policies.items {
@@ -274,7 +624,7 @@ impl EcMetadataManager {
     /// Create an ECStripe CRD in Kubernetes
     #[instrument(skip(self, metadata))]
     pub async fn create_stripe_crd(&self, metadata: &StripeMetadata) -> Result<ECStripe> {
-        let stripes_api: Api<ECStripe> = Api::all(self.client.clone());
+        let stripes_api: Api<ECStripe> = Api::all(self.client()?);
 
         // Generate CRD name
         let name = format!(
@@ -284,7 +634,7 @@ impl EcMetadataManager {
         );
 
         // Build the ECStripe spec
-        let stripe = ECStripe::new(
+        let mut stripe = ECStripe::new(
             &name,
             ECStripeSpec {
                 volume_ref: metadata.volume_id.clone(),
@@ -296,6 +646,14 @@ impl EcMetadataManager {
                 generation: metadata.generation,
             },
         );
+        stripe.metadata.labels = Some(
+            [
+                ("volume".to_string(), metadata.volume_id.clone()),
+                ("stripe-id".to_string(), metadata.stripe_id.to_string()),
+                ("policy".to_string(), metadata.policy_ref.clone()),
+            ]
+            .into(),
+        );
 
         // Create in Kubernetes
         let created = stripes_api
@@ -316,7 +674,7 @@ impl EcMetadataManager {
         healthy_shards: u8,
         shard_health: Vec<ShardHealth>,
     ) -> Result<()> {
-        let stripes_api: Api<ECStripe> = Api::all(self.client.clone());
+        let stripes_api: Api<ECStripe> = Api::all(self.client()?);
 
         let healthy_data = shard_health
             .iter()
@@ -356,7 +714,7 @@ impl EcMetadataManager {
     /// Load all ECStripe CRDs for a volume
     #[instrument(skip(self))]
     pub async fn load_volume_stripes(&self, volume_id: &str) -> Result<Vec<ECStripe>> {
-        let stripes_api: Api<ECStripe> = Api::all(self.client.clone());
+        let stripes_api: Api<ECStripe> = Api::all(self.client()?);
 
         // List all stripes and filter by volume
         // In a real implementation, we'd use a label selector
@@ -380,7 +738,7 @@ impl EcMetadataManager {
     /// Delete an ECStripe CRD
     #[instrument(skip(self))]
     pub async fn delete_stripe_crd(&self, stripe_name: &str) -> Result<()> {
-        let stripes_api: Api<ECStripe> = Api::all(self.client.clone());
+        let stripes_api: Api<ECStripe> = Api::all(self.client()?);
 
         stripes_api
             .delete(stripe_name, &Default::default())
@@ -439,6 +797,44 @@ impl EcMetadataManager {
         Ok(())
     }
 
+    /// Sync in-memory state for a volume from a stripe repository
+    ///
+    /// The volume takes the policy recorded for it, or else the policy of
+    /// its newest stripe. Returns the number of stripes loaded.
+    #[instrument(skip(self, repository))]
+    pub async fn sync_from_repository(
+        &self,
+        repository: &dyn StripeRepository,
+        volume_id: &str,
+    ) -> Result<usize> {
+        let volume = VolumeId::new(volume_id);
+        let stripes = repository.find_by_volume(&volume).await?;
+        let Some(newest) = stripes.iter().max_by_key(|s| s.stripe_id.0) else {
+            return Ok(0);
+        };
+
+        // A recorded policy wins over the newest stripe's, which still has
+        // the old policy while the volume is part-way re-striped
+        let policy_ref = match repository.find_volume_policy(&volume).await? {
+            Some(policy_ref) => policy_ref,
+            None => newest.policy_ref.clone(),
+        };
+        let volume_state = self.get_or_create_volume(volume_id, &policy_ref);
+        let mut state = volume_state.write();
+
+        for stripe in &stripes {
+            state.add_stripe(from_domain_stripe(stripe));
+        }
+
+        info!(
+            "Synced {} stripes for volume {} from stripe repository",
+            stripes.len(),
+            volume_id
+        );
+
+        Ok(stripes.len())
+    }
+
     /// Get aggregate stats across all volumes
     pub fn aggregate_stats(&self) -> AggregateEcStats {
         let mut stats = AggregateEcStats::default();
@@ -470,6 +866,171 @@ pub struct AggregateEcStats {
     pub failed_stripes: u64,
 }
 
+// =============================================================================
+// Placement Commit
+// =============================================================================
+
+/// Convert CRD shard locations to the domain form used by `StripeRepository`
+///
+/// Shards without an entry in `shard_health` are reported healthy.
+pub fn to_domain_locations(
+    locations: &[ShardLocation],
+    shard_health: &[ShardHealth],
+) -> Vec<DomainShardLocation> {
+    locations
+        .iter()
+        .map(|l| {
+            let health = shard_health.iter().find(|h| h.shard_index == l.shard_index);
+            let state = health.map(|h| h.state.clone()).unwrap_or_default();
+
+            DomainShardLocation {
+                shard_index: l.shard_index as usize,
+                device_id: l.pool_name.clone(),
+                node_id: l.node_name.clone(),
+                offset: l.offset,
+                size: l.size_bytes,
+                is_data_shard: l.is_data_shard,
+                checksum: l.checksum.clone(),
+                health: match state {
+                    ShardState::Healthy => ShardHealthState::Healthy,
+                    ShardState::Missing => ShardHealthState::Missing,
+                    ShardState::Corrupted => ShardHealthState::Corrupted,
+                    ShardState::Rebuilding => ShardHealthState::Rebuilding,
+                },
+                failed_pool: health.and_then(|h| h.failed_pool.clone()),
+            }
+        })
+        .collect()
+}
+
+/// Convert an in-memory stripe to the form persisted by `StripeRepository`
+pub fn to_domain_stripe(stripe: &StripeMetadata) -> DomainStripeMetadata {
+    DomainStripeMetadata {
+        stripe_id: StripeId::new(stripe.stripe_id),
+        volume_id: VolumeId::new(stripe.volume_id.clone()),
+        policy_ref: stripe.policy_ref.clone(),
+        lba_range: DomainLbaRange::new(stripe.lba_range.start_lba, stripe.lba_range.end_lba),
+        shard_locations: to_domain_locations(&stripe.shard_locations, &stripe.status.shard_health),
+        generation: stripe.generation,
+        created_at: Utc::now(),
+        is_compressed: false,
+        original_size: None,
+    }
+}
+
+/// Convert a stripe loaded from a `StripeRepository` to its in-memory form
+///
+/// The stripe is reported degraded if any of its shards is not healthy.
+pub fn from_domain_stripe(stripe: &DomainStripeMetadata) -> StripeMetadata {
+    let shard_health: Vec<ShardHealth> = stripe
+        .shard_locations
+        .iter()
+        .map(|s| ShardHealth {
+            shard_index: s.shard_index as u8,
+            state: match s.health {
+                ShardHealthState::Healthy => ShardState::Healthy,
+                ShardHealthState::Missing => ShardState::Missing,
+                ShardHealthState::Corrupted => ShardState::Corrupted,
+                ShardHealthState::Rebuilding => ShardState::Rebuilding,
+            },
+            last_verified: None,
+            error: None,
+            failed_pool: s.failed_pool.clone(),
+        })
+        .collect();
+    let healthy_shards = shard_health
+        .iter()
+        .filter(|h| h.state == ShardState::Healthy)
+        .count();
+
+    StripeMetadata {
+        stripe_id: stripe.stripe_id.0,
+        volume_id: stripe.volume_id.0.clone(),
+        policy_ref: stripe.policy_ref.clone(),
+        lba_range: LbaRange {
+            start_lba: stripe.lba_range.start,
+            end_lba: stripe.lba_range.end,
+        },
+        shard_locations: stripe
+            .shard_locations
+            .iter()
+            .map(|s| ShardLocation {
+                shard_index: s.shard_index as u8,
+                is_data_shard: s.is_data_shard,
+                pool_name: s.device_id.clone(),
+                node_name: s.node_id.clone(),
+                offset: s.offset,
+                size_bytes: s.size,
+                checksum: s.checksum.clone(),
+            })
+            .collect(),
+        status: StripeStatus {
+            state: if healthy_shards == shard_health.len() {
+                StripeState::Healthy
+            } else {
+                StripeState::Degraded
+            },
+            healthy_shards: healthy_shards as u8,
+            shard_health,
+        },
+        generation: stripe.generation,
+        checksum: None,
+    }
+}
+
+/// Publish a new shard placement for a stripe
+///
+/// Writes the placement together with generation `stripe.generation + 1`,
+/// so a writer that committed a newer generation first makes this fail with
+/// `EcGenerationConflict`. Returns the claimed generation.
+pub async fn commit_shard_placement(
+    repository: &dyn StripeRepository,
+    stripe: &StripeMetadata,
+    locations: &[ShardLocation],
+    shard_health: &[ShardHealth],
+) -> Result<u64> {
+    let mut stored = to_domain_stripe(stripe);
+    stored.shard_locations = to_domain_locations(locations, shard_health);
+    commit_stripe(repository, stripe.stripe_id, stripe.generation, stored).await
+}
+
+/// Publish a stripe re-encoded under a different policy
+///
+/// Stores `restriped` (policy, LBA range and shards) together with
+/// generation `current.generation + 1`, like `commit_shard_placement`.
+/// Returns the claimed generation.
+pub async fn commit_restripe(
+    repository: &dyn StripeRepository,
+    current: &StripeMetadata,
+    restriped: &StripeMetadata,
+) -> Result<u64> {
+    commit_stripe(
+        repository,
+        current.stripe_id,
+        current.generation,
+        to_domain_stripe(restriped),
+    )
+    .await
+}
+
+/// Store `stored` at the generation after `generation` in one write
+async fn commit_stripe(
+    repository: &dyn StripeRepository,
+    stripe_id: u64,
+    generation: u64,
+    mut stored: DomainStripeMetadata,
+) -> Result<u64> {
+    let new_generation = generation + 1;
+    stored.generation = new_generation;
+    if !repository.save_if_generation(&stored).await? {
+        return Err(Error::EcGenerationConflict {
+            stripe_id,
+            generation: new_generation,
+        });
+    }
+    Ok(new_generation)
+}
+
 // =============================================================================
 // LBA Stripe Map
 // =============================================================================
@@ -528,6 +1089,7 @@ impl LbaStripeMap {
 mod tests {
     use super::*;
     use crate::crd::LbaRange;
+    use crate::ec::test_support;
 
     // =========================================================================
     // LbaStripeMap Tests
@@ -695,6 +1257,119 @@ mod tests {
         assert_eq!(counts.failed, 0);
     }
 
+    fn ranged_stripe(stripe_id: u64, start: u64, end: u64) -> StripeMetadata {
+        StripeMetadata {
+            stripe_id,
+            volume_id: "vol-1".to_string(),
+            policy_ref: "policy-1".to_string(),
+            lba_range: LbaRange::new(start, end),
+            shard_locations: vec![],
+            status: StripeStatus::default(),
+            generation: 0,
+            checksum: None,
+        }
+    }
+
+    fn live_of(state: &VolumeEcState, stripe_id: u64) -> Vec<(u64, u64)> {
+        state
+            .live_ranges()
+            .into_iter()
+            .find(|(s, _)| s.stripe_id == stripe_id)
+            .map(|(_, live)| live.iter().map(|r| (r.start_lba, r.end_lba)).collect())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_volume_ec_state_live_ranges() {
+        let mut state = VolumeEcState::new("vol-1".to_string(), "policy-1".to_string());
+        state.add_stripe(ranged_stripe(0, 0, 100));
+        state.add_stripe(ranged_stripe(1, 20, 40));
+        state.add_stripe(ranged_stripe(2, 60, 120));
+        state.add_stripe(ranged_stripe(3, 30, 50));
+
+        assert_eq!(live_of(&state, 3), vec![(30, 50)]);
+        assert_eq!(live_of(&state, 2), vec![(60, 120)]);
+        assert_eq!(live_of(&state, 1), vec![(20, 30)]);
+        assert_eq!(live_of(&state, 0), vec![(0, 20), (50, 60)]);
+
+        // A full overwrite leaves nothing live in the older stripes
+        state.add_stripe(ranged_stripe(4, 0, 200));
+        for id in 0..4 {
+            assert!(live_of(&state, id).is_empty());
+        }
+        assert_eq!(live_of(&state, 4), vec![(0, 200)]);
+    }
+
+    #[test]
+    fn test_volume_ec_state_remove_stripe() {
+        let mut state = VolumeEcState::new("vol-1".to_string(), "policy-1".to_string());
+        state.add_stripe(ranged_stripe(0, 0, 100));
+        state.add_stripe(ranged_stripe(1, 0, 100));
+
+        // The newer stripe owns the LBA; removing it hands back to the older one
+        assert_eq!(state.find_stripe_for_lba(10).unwrap().stripe_id, 1);
+        assert!(state.has_newer_overlap(&LbaRange::new(50, 60), 0));
+        assert!(!state.has_newer_overlap(&LbaRange::new(50, 60), 1));
+
+        assert_eq!(state.remove_stripe(1).unwrap().stripe_id, 1);
+        assert_eq!(state.find_stripe_for_lba(10).unwrap().stripe_id, 0);
+        assert!(state.remove_stripe(1).is_none());
+
+        state.remove_stripe(0);
+        assert!(state.find_stripe_for_lba(10).is_none());
+        assert_eq!(state.stripe_count(), 0);
+    }
+
+    #[test]
+    fn test_volume_ec_state_ids_follow_loaded_stripes() {
+        let mut state = VolumeEcState::new("vol-1".to_string(), "policy-1".to_string());
+        state.add_stripe(ranged_stripe(41, 0, 100));
+        assert_eq!(state.next_stripe_id(), 42);
+    }
+
+    #[test]
+    fn test_volume_ec_state_reserved_ids_count_as_newer() {
+        let mut state = VolumeEcState::new("vol-1".to_string(), "policy-1".to_string());
+        state.add_stripe(ranged_stripe(0, 0, 100));
+        let reserved = state.reserve_stripe_id(LbaRange::new(40, 60));
+        assert_eq!(reserved, 1);
+
+        // Even a caller that saw newer stripes must not write over it
+        state.add_stripe(ranged_stripe(2, 200, 300));
+        assert!(state.has_newer_overlap(&LbaRange::new(50, 51), 2));
+        assert!(!state.has_newer_overlap(&LbaRange::new(60, 100), 2));
+
+        state.add_stripe(ranged_stripe(reserved, 40, 60));
+        assert!(!state.has_newer_overlap(&LbaRange::new(50, 51), 2));
+
+        let released = state.reserve_stripe_id(LbaRange::new(0, 10));
+        state.release_stripe_id(released);
+        assert!(!state.has_newer_overlap(&LbaRange::new(0, 10), 2));
+        assert_eq!(state.next_stripe_id(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_read_tracker_waits_for_earlier_reads() {
+        let state = VolumeEcState::new("vol-1".to_string(), "policy-1".to_string());
+        let readers = state.readers();
+        let early = state.begin_read();
+
+        let waiter = tokio::spawn({
+            let readers = readers.clone();
+            async move { readers.wait_for_readers().await }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+
+        // Reads that begin after the wait started do not hold it up
+        let _late = state.begin_read();
+        drop(early);
+        tokio::time::timeout(std::time::Duration::from_secs(1), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
     // =========================================================================
     // StripeStatus Tests
     // =========================================================================
@@ -728,4 +1403,130 @@ mod tests {
         assert_eq!(metadata.lba_range.size(), 1048576);
         assert!(metadata.checksum.is_some());
     }
+
+    #[test]
+    fn test_domain_stripe_round_trip() {
+        let mut stripe = ranged_stripe(7, 64, 128);
+        stripe.generation = 3;
+        stripe.shard_locations = (0..3u8)
+            .map(|i| ShardLocation {
+                shard_index: i,
+                is_data_shard: i < 2,
+                pool_name: format!("pool-{}", i),
+                node_name: "node-a".to_string(),
+                offset: 0,
+                size_bytes: 4096,
+                checksum: None,
+            })
+            .collect();
+        stripe.status.shard_health = vec![ShardHealth {
+            shard_index: 1,
+            state: ShardState::Missing,
+            last_verified: None,
+            error: None,
+            failed_pool: Some("pool-1".to_string()),
+        }];
+
+        let restored = from_domain_stripe(&to_domain_stripe(&stripe));
+
+        assert_eq!(restored.stripe_id, 7);
+        assert_eq!(restored.generation, 3);
+        assert_eq!(
+            (restored.lba_range.start_lba, restored.lba_range.end_lba),
+            (64, 128)
+        );
+        assert_eq!(restored.shard_locations[2].pool_name, "pool-2");
+        assert_eq!(restored.status.state, StripeState::Degraded);
+        assert_eq!(restored.status.healthy_shards, 2);
+        let missing = restored
+            .status
+            .shard_health
+            .iter()
+            .find(|h| h.shard_index == 1)
+            .unwrap();
+        assert_eq!(missing.failed_pool.as_deref(), Some("pool-1"));
+    }
+
+    #[tokio::test]
+    async fn test_standalone_manager_uses_policy_file() {
+        let manager = EcMetadataManager::standalone();
+        assert!(matches!(
+            manager.load_policy("standard-ec").await,
+            Err(Error::EcPolicyNotFound(_))
+        ));
+
+        let names = manager
+            .register_policy_file(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/deploy/examples/erasurecodingpolicy-examples.yaml"
+            ))
+            .unwrap();
+        assert_eq!(names.len(), 6);
+        let policy = manager.load_policy("standard-ec").await.unwrap();
+        assert_eq!((policy.spec.data_shards, policy.spec.parity_shards), (4, 2));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("policy.yaml");
+        std::fs::write(&path, "# nothing here\n").unwrap();
+        assert!(manager.register_policy_file(&path).is_err());
+        assert!(manager
+            .create_stripe_crd(&ranged_stripe(0, 0, 8))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sync_from_repository() {
+        let manager = test_support::metadata_manager();
+        let repository = crate::adapters::InMemoryStripeRepository::new();
+
+        let mut old = ranged_stripe(0, 0, 100);
+        old.policy_ref = "policy-old".to_string();
+        repository.save(&to_domain_stripe(&old)).await.unwrap();
+        let mut new = ranged_stripe(1, 50, 150);
+        new.policy_ref = "policy-new".to_string();
+        repository.save(&to_domain_stripe(&new)).await.unwrap();
+
+        let loaded = manager
+            .sync_from_repository(&repository, "vol-1")
+            .await
+            .unwrap();
+        assert_eq!(loaded, 2);
+        assert_eq!(
+            manager
+                .sync_from_repository(&repository, "vol-2")
+                .await
+                .unwrap(),
+            0
+        );
+
+        let volume = manager.get_volume("vol-1").unwrap();
+        let state = volume.read();
+        assert_eq!(state.policy_ref, "policy-new");
+        assert_eq!(state.stripe_count(), 2);
+        assert_eq!(state.next_stripe_id(), 2);
+        assert!(manager.get_volume("vol-2").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sync_from_repository_prefers_recorded_policy() {
+        let manager = test_support::metadata_manager();
+        let repository = crate::adapters::InMemoryStripeRepository::new();
+
+        // Re-striping switched the volume before moving its stripes
+        let mut stripe = ranged_stripe(0, 0, 100);
+        stripe.policy_ref = "policy-old".to_string();
+        repository.save(&to_domain_stripe(&stripe)).await.unwrap();
+        repository
+            .save_volume_policy(&VolumeId::new("vol-1"), "policy-new")
+            .await
+            .unwrap();
+
+        manager
+            .sync_from_repository(&repository, "vol-1")
+            .await
+            .unwrap();
+        let volume = manager.get_volume("vol-1").unwrap();
+        assert_eq!(volume.read().policy_ref, "policy-new");
+    }
 }
@@ -36,21 +36,65 @@
 //!   - Reconstruct missing shards from survivors
 //!   - Verify stripe integrity
 //!
+//! - **SIMD Codec** (`simd_codec.rs`, `gf256.rs`): Pure-Rust Reed-Solomon:
+//!   - GF(2^8) split-table kernels for SSSE3, AVX2 and NEON
+//!   - Kernel chosen at runtime, with a scalar fallback
+//!   - Bit-identical to the `reed-solomon-erasure` encoder
+//!
 //! - **Metadata Manager** (`metadata.rs`): Manages EC metadata including:
 //!   - LBA-to-stripe mappings for fast lookup
 //!   - ECStripe CRD persistence to Kubernetes
 //!   - Volume EC state tracking
 //!
+//! - **Write Journal** (`journal.rs`): Replicated write buffering:
+//!   - Appends writes to `replicationFactor` replica directories
+//!   - Tracks per-volume fill level against the policy's journal size
+//!   - Replays un-destaged entries on restart
+//!
 //! - **Stripe Manager** (`stripe_manager.rs`): Handles background destaging:
-//!   - Monitors journal fill level
+//!   - Destages journals at their fill threshold or interval
 //!   - Batches writes into full stripes
 //!   - Encodes and distributes shards to pools
+//!   - Updates existing stripes in place (read-modify-write with parity deltas)
 //!
 //! - **Reconstruction Engine** (`reconstruction.rs`): Handles degraded operations:
 //!   - Degraded reads with transparent reconstruction
-//!   - Background stripe rebuilds
+//!   - Background stripe rebuilds, ordered by remaining redundancy
 //!   - Scrub verification for bit rot detection
 //!
+//! - **Shard Health Watcher** (`health.rs`): Failure detection:
+//!   - Watches DiskPool and Node state
+//!   - Marks shards on failed pools missing and emits `ShardFailed` events
+//!   - Queues rebuilds once a failure outlasts a grace period
+//!
+//! - **Deduplication** (`dedup.rs`): Optional chunk-level dedup:
+//!   - Fixed or content-defined chunks fingerprinted with SHA-256
+//!   - Unique chunks stored once per policy, in a shared chunk volume
+//!   - Reference-counted index and per-volume LBA maps in a local log
+//!
+//! - **NBD Server** (`nbd.rs`): Block device frontend:
+//!   - Exports EC volumes over the Network Block Device protocol
+//!   - Writes go to the journal, reads through the degraded-read path
+//!   - Lets `nbd-client` attach a volume without Mayastor
+//!
+//! - **Durability Model** (`durability.rs`): Data-loss risk per policy:
+//!   - Counts failure domains among eligible cold pools
+//!   - Estimates MTTDL from the k+m layout, disk AFR and rebuild throughput
+//!
+//! - **Stripe Compactor** (`compaction.rs`): Reclaims overwritten EC data:
+//!   - Deletes stripes fully shadowed by newer stripes
+//!   - Repacks partially-live stripes below a live-ratio threshold
+//!
+//! - **Stripe Restriper** (`restripe.rs`): Online EC profile changes:
+//!   - Re-encodes a volume's stripes onto a different policy
+//!   - Flips each stripe to the new policy in a single commit
+//!   - Tracks per-stripe progress
+//!
+//! - **Shard Store** (`shard_store.rs`): Shard I/O backends:
+//!   - Placement of shards on healthy pools
+//!   - Checksummed reads and writes
+//!   - Local-directory and in-memory implementations
+//!
 //! # Usage
 //!
 //! ```rust,ignore
@@ -75,15 +119,36 @@
 //! assert_eq!(recovered, data);
 //! ```
 
+pub mod compaction;
+pub mod dedup;
+pub mod durability;
 pub mod encoder;
+pub mod gf256;
+pub mod health;
+pub mod journal;
 pub mod metadata;
+pub mod nbd;
 pub mod reconstruction;
+pub(crate) mod record_log;
+pub mod restripe;
+pub mod shard_store;
+pub mod simd_codec;
 pub mod stripe_manager;
 
 #[cfg(test)]
 mod proptest;
+#[cfg(test)]
+mod test_support;
 
 // Re-export types used by main.rs
+pub use compaction::{CompactionConfig, StripeCompactor};
+pub use dedup::DedupIndex;
+pub use durability::DurabilityConfig;
+pub use health::{HealthWatcherConfig, PoolMapping, ShardHealthWatcher};
+pub use journal::WriteJournal;
 pub use metadata::EcMetadataManager;
+pub use nbd::{NbdConfig, NbdExport, NbdServer};
 pub use reconstruction::{ReconstructionConfig, ReconstructionEngine};
+pub use restripe::StripeRestriper;
+pub use shard_store::{LocalShardStore, ShardStore};
 pub use stripe_manager::{StripeManager, StripeManagerConfig};
@@ -0,0 +1,1180 @@
+//! NBD Server
+//!
+//! Serves EC volumes as Linux block devices over the Network Block Device
+//! protocol (fixed newstyle negotiation), so a volume can be attached with
+//! plain `nbd-client -N <volume> <host> 10809 /dev/nbd0 -b 4096`. Each
+//! export maps the NBD commands onto the EC write path:
+//!
+//! - `READ`: blocks come from the volume's stripes (or its deduplicated
+//!   chunks), read through the degraded-read path, overlaid with journal
+//!   entries not yet destaged
+//! - `WRITE`: appended to the volume's write journal and acknowledged once
+//!   stored on every journal replica
+//! - `FLUSH`: acknowledged writes are already durable in the journal, so a
+//!   flush only wakes the destage loop
+//! - `TRIM`: zeroes are journaled over the range, so it reads as zeros;
+//!   with deduplication they collapse into a single shared chunk
+//!
+//! Exports advertise a 4 KiB block size. Clients selecting an export with
+//! `NBD_OPT_EXPORT_NAME` cannot receive it and must be told (`-b 4096`).
+//!
+//! Requests of a connection are served concurrently, up to
+//! `max_in_flight`, and replied to in completion order.
+
+use crate::crd::LbaRange;
+use crate::ec::dedup::DedupIndex;
+use crate::ec::journal::WriteJournal;
+use crate::ec::metadata::EcMetadataManager;
+use crate::ec::reconstruction::{ReadRequest, ReconstructionEngine};
+use crate::ec::stripe_manager::LBA_SIZE;
+use crate::error::{Error, Result};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::{Mutex, Semaphore};
+use tracing::{debug, info, warn};
+
+// =============================================================================
+// Protocol Constants
+// =============================================================================
+
+const NBD_MAGIC: u64 = 0x4e42_444d_4147_4943; // "NBDMAGIC"
+const NBD_IHAVEOPT: u64 = 0x4948_4156_454f_5054; // "IHAVEOPT"
+const NBD_OPTION_REPLY_MAGIC: u64 = 0x0003_e889_0455_65a9;
+const NBD_REQUEST_MAGIC: u32 = 0x2560_9513;
+const NBD_SIMPLE_REPLY_MAGIC: u32 = 0x6744_6698;
+
+// Handshake flags (server) and client flags
+const NBD_FLAG_FIXED_NEWSTYLE: u16 = 1 << 0;
+const NBD_FLAG_NO_ZEROES: u16 = 1 << 1;
+const NBD_FLAG_C_FIXED_NEWSTYLE: u32 = 1 << 0;
+const NBD_FLAG_C_NO_ZEROES: u32 = 1 << 1;
+
+// Transmission flags
+const NBD_FLAG_HAS_FLAGS: u16 = 1 << 0;
+const NBD_FLAG_SEND_FLUSH: u16 = 1 << 2;
+const NBD_FLAG_SEND_FUA: u16 = 1 << 3;
+const NBD_FLAG_SEND_TRIM: u16 = 1 << 5;
+
+// Options
+const NBD_OPT_EXPORT_NAME: u32 = 1;
+const NBD_OPT_ABORT: u32 = 2;
+const NBD_OPT_LIST: u32 = 3;
+const NBD_OPT_INFO: u32 = 6;
+const NBD_OPT_GO: u32 = 7;
+
+// Option replies
+const NBD_REP_ACK: u32 = 1;
+const NBD_REP_SERVER: u32 = 2;
+const NBD_REP_INFO: u32 = 3;
+const NBD_REP_ERR_UNSUP: u32 = (1 << 31) + 1;
+const NBD_REP_ERR_INVALID: u32 = (1 << 31) + 3;
+const NBD_REP_ERR_UNKNOWN: u32 = (1 << 31) + 6;
+
+// Information types
+const NBD_INFO_EXPORT: u16 = 0;
+const NBD_INFO_BLOCK_SIZE: u16 = 3;
+
+// Commands
+const NBD_CMD_READ: u16 = 0;
+const NBD_CMD_WRITE: u16 = 1;
+const NBD_CMD_DISC: u16 = 2;
+const NBD_CMD_FLUSH: u16 = 3;
+const NBD_CMD_TRIM: u16 = 4;
+
+// Error values carried in replies
+const NBD_EIO: u32 = 5;
+const NBD_EINVAL: u32 = 22;
+const NBD_ENOSPC: u32 = 28;
+
+/// Largest journal append a trim is split into
+const TRIM_CHUNK_BYTES: u64 = 1024 * 1024;
+
+/// Largest option payload accepted during negotiation
+const MAX_OPTION_BYTES: u32 = 64 * 1024;
+
+/// Minimum and preferred I/O size advertised to clients, a multiple of
+/// the block size so kernel requests are always aligned
+const ADVERTISED_BLOCK_BYTES: u32 = 4096;
+
+// =============================================================================
+// Configuration
+// =============================================================================
+
+/// Configuration for the NBD server
+#[derive(Debug, Clone)]
+pub struct NbdConfig {
+    /// Address to listen on
+    pub listen_addr: String,
+
+    /// Accept clients beyond the local host; NBD has no authentication
+    pub allow_remote: bool,
+
+    /// Largest read or write accepted in a single request
+    pub max_request_bytes: u32,
+
+    /// Requests served concurrently per connection
+    pub max_in_flight: usize,
+}
+
+impl Default for NbdConfig {
+    fn default() -> Self {
+        Self {
+            listen_addr: "127.0.0.1:10809".to_string(),
+            allow_remote: false,
+            max_request_bytes: 32 * 1024 * 1024,
+            max_in_flight: 64,
+        }
+    }
+}
+
+/// An EC volume exported as a block device
+///
+/// Parsed from `<volume>:<policy>:<size>`, where the size is in bytes and
+/// may carry a `K`, `M`, `G` or `T` (binary) suffix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NbdExport {
+    /// Volume ID, also the export name
+    pub volume_id: String,
+
+    /// Policy the volume is erasure-coded under
+    pub policy: String,
+
+    /// Device size; a whole number of blocks
+    pub size_bytes: u64,
+}
+
+impl FromStr for NbdExport {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid =
+            |reason: &str| Error::InvalidEcConfig(format!("NBD export '{}' {}", s, reason));
+
+        let mut parts = s.split(':');
+        let (Some(volume_id), Some(policy), Some(size), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid("is not <volume>:<policy>:<size>"));
+        };
+        if volume_id.is_empty() || policy.is_empty() {
+            return Err(invalid("needs a volume and a policy"));
+        }
+
+        let size = size.trim();
+        let (digits, suffix) = size.split_at(
+            size.find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(size.len()),
+        );
+        let shift = match suffix {
+            "" | "B" => 0,
+            "K" | "Ki" | "KiB" => 10,
+            "M" | "Mi" | "MiB" => 20,
+            "G" | "Gi" | "GiB" => 30,
+            "T" | "Ti" | "TiB" => 40,
+            _ => return Err(invalid("has an invalid size suffix")),
+        };
+        let size_bytes = digits
+            .parse::<u64>()
+            .ok()
+            .and_then(|n| n.checked_mul(1u64 << shift))
+            .ok_or_else(|| invalid("has an invalid size"))?;
+        if size_bytes == 0 || !size_bytes.is_multiple_of(LBA_SIZE) {
+            return Err(invalid(&format!(
+                "size must be a non-zero multiple of {} bytes",
+                LBA_SIZE
+            )));
+        }
+
+        Ok(Self {
+            volume_id: volume_id.to_string(),
+            policy: policy.to_string(),
+            size_bytes,
+        })
+    }
+}
+
+// =============================================================================
+// Block Device
+// =============================================================================
+
+/// Block-level view of one EC volume
+pub struct EcBlockDevice {
+    volume_id: String,
+    size_bytes: u64,
+    journal: Arc<WriteJournal>,
+    engine: Arc<ReconstructionEngine>,
+    dedup: Option<Arc<DedupIndex>>,
+}
+
+impl EcBlockDevice {
+    /// Create a block device over a volume registered for erasure coding
+    pub fn new(
+        volume_id: impl Into<String>,
+        size_bytes: u64,
+        journal: Arc<WriteJournal>,
+        engine: Arc<ReconstructionEngine>,
+        dedup: Option<Arc<DedupIndex>>,
+    ) -> Self {
+        Self {
+            volume_id: volume_id.into(),
+            size_bytes,
+            journal,
+            engine,
+            dedup,
+        }
+    }
+
+    /// Volume behind the device
+    pub fn volume_id(&self) -> &str {
+        &self.volume_id
+    }
+
+    /// Device size in bytes
+    pub fn size_bytes(&self) -> u64 {
+        self.size_bytes
+    }
+
+    /// Read `length` bytes at `offset`; blocks never written read as zeros
+    pub async fn read(&self, offset: u64, length: u64) -> Result<Vec<u8>> {
+        let range = self.block_range(offset, length)?;
+        if length == 0 {
+            return Ok(vec![]);
+        }
+
+        // Snapshot the journal first: entries destaged after this point are
+        // already in the stripes read below
+        let pending = self
+            .journal
+            .read_overlapping(&self.volume_id, &range)
+            .await?;
+
+        let mut data = self.read_stripes(&self.volume_id, &range).await?;
+        if let Some(dedup) = &self.dedup {
+            for extent in dedup.resolve(&self.volume_id, &range) {
+                let chunk_range =
+                    LbaRange::new(extent.chunk_lba, extent.chunk_lba + extent.lba_count);
+                let chunk = self
+                    .read_stripes(&extent.chunk_volume, &chunk_range)
+                    .await?;
+                overlay(&mut data, &range, extent.start_lba, &chunk);
+            }
+        }
+
+        // Pending entries are newer than anything destaged, in sequence order
+        for (_, entry) in pending {
+            overlay(&mut data, &range, entry.lba_range.start_lba, &entry.data);
+        }
+        Ok(data)
+    }
+
+    /// Write whole blocks at `offset` through the volume's journal
+    pub async fn write(&self, offset: u64, data: &[u8]) -> Result<()> {
+        let range = self.block_range(offset, data.len() as u64)?;
+        if data.is_empty() {
+            return Ok(());
+        }
+        self.journal
+            .append(&self.volume_id, range.start_lba, data)
+            .await
+            .map(|_| ())
+    }
+
+    /// Make acknowledged writes durable
+    ///
+    /// Journal appends are durable once acknowledged; this only asks the
+    /// stripe manager to destage whatever is due.
+    pub fn flush(&self) {
+        self.journal.request_destage();
+    }
+
+    /// Discard a range, which reads as zeros afterwards
+    ///
+    /// The zeroes go through the journal like any write, in appends of at
+    /// most `TRIM_CHUNK_BYTES`.
+    pub async fn trim(&self, offset: u64, length: u64) -> Result<()> {
+        let range = self.block_range(offset, length)?;
+        let zeroes = vec![0u8; length.min(TRIM_CHUNK_BYTES) as usize];
+        let mut lba = range.start_lba;
+        while lba < range.end_lba {
+            let blocks = (range.end_lba - lba).min(TRIM_CHUNK_BYTES / LBA_SIZE);
+            self.journal
+                .append(
+                    &self.volume_id,
+                    lba,
+                    &zeroes[..(blocks * LBA_SIZE) as usize],
+                )
+                .await?;
+            lba += blocks;
+        }
+        Ok(())
+    }
+
+    /// Validate a request and convert it to blocks
+    fn block_range(&self, offset: u64, length: u64) -> Result<LbaRange> {
+        let invalid = |reason: &str| Error::InvalidBlockRequest {
+            offset,
+            length,
+            reason: reason.to_string(),
+        };
+        if !offset.is_multiple_of(LBA_SIZE) || !length.is_multiple_of(LBA_SIZE) {
+            return Err(invalid("not aligned to the block size"));
+        }
+        if offset
+            .checked_add(length)
+            .is_none_or(|end| end > self.size_bytes)
+        {
+            return Err(invalid("beyond the end of the device"));
+        }
+        Ok(LbaRange::new(
+            offset / LBA_SIZE,
+            (offset + length) / LBA_SIZE,
+        ))
+    }
+
+    /// Read a range of a volume's stripes, reconstructing as needed
+    async fn read_stripes(&self, volume_id: &str, range: &LbaRange) -> Result<Vec<u8>> {
+        let request = ReadRequest {
+            volume_id: volume_id.to_string(),
+            lba_range: range.clone(),
+            allow_degraded: true,
+        };
+        match self.engine.handle_degraded_read(request).await {
+            Ok(result) => Ok(result.data),
+            Err(Error::EcStripeNotFound(_)) => Ok(vec![0u8; (range.size() * LBA_SIZE) as usize]),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Copy the blocks of `src` (starting at `start_lba`) that fall in `range`
+/// into `buf`, which holds `range`
+fn overlay(buf: &mut [u8], range: &LbaRange, start_lba: u64, src: &[u8]) {
+    let src_end = start_lba + src.len() as u64 / LBA_SIZE;
+    let from = start_lba.max(range.start_lba);
+    let to = src_end.min(range.end_lba);
+    if from >= to {
+        return;
+    }
+    let src_offset = ((from - start_lba) * LBA_SIZE) as usize;
+    let dst_offset = ((from - range.start_lba) * LBA_SIZE) as usize;
+    let len = ((to - from) * LBA_SIZE) as usize;
+    buf[dst_offset..dst_offset + len].copy_from_slice(&src[src_offset..src_offset + len]);
+}
+
+// =============================================================================
+// NBD Server
+// =============================================================================
+
+/// A transmission-phase request
+#[derive(Debug, Clone, Copy)]
+struct NbdRequest {
+    flags: u16,
+    command: u16,
+    handle: u64,
+    offset: u64,
+    length: u32,
+}
+
+/// Embedded NBD server exporting EC volumes
+pub struct NbdServer {
+    config: NbdConfig,
+    exports: HashMap<String, Arc<EcBlockDevice>>,
+}
+
+impl NbdServer {
+    /// Create a server for `exports`, registering each volume under its
+    /// policy for erasure coding
+    pub fn new(
+        config: NbdConfig,
+        exports: &[NbdExport],
+        metadata_manager: &EcMetadataManager,
+        journal: Arc<WriteJournal>,
+        engine: Arc<ReconstructionEngine>,
+        dedup: Option<Arc<DedupIndex>>,
+    ) -> Result<Arc<Self>> {
+        let mut devices = HashMap::new();
+        for export in exports {
+            let state = metadata_manager.get_or_create_volume(&export.volume_id, &export.policy);
+            let policy_ref = state.read().policy_ref.clone();
+            if policy_ref != export.policy {
+                return Err(Error::InvalidEcConfig(format!(
+                    "NBD export {} uses policy {}, but the volume is erasure-coded under {}",
+                    export.volume_id, export.policy, policy_ref
+                )));
+            }
+
+            let device = EcBlockDevice::new(
+                export.volume_id.clone(),
+                export.size_bytes,
+                journal.clone(),
+                engine.clone(),
+                dedup.clone(),
+            );
+            if devices
+                .insert(export.volume_id.clone(), Arc::new(device))
+                .is_some()
+            {
+                return Err(Error::InvalidEcConfig(format!(
+                    "NBD export {} is listed twice",
+                    export.volume_id
+                )));
+            }
+        }
+
+        Ok(Arc::new(Self {
+            config,
+            exports: devices,
+        }))
+    }
+
+    /// Listen on the configured address and serve clients until an error
+    pub async fn run(self: Arc<Self>) -> Result<()> {
+        let listener = TcpListener::bind(&self.config.listen_addr).await?;
+        self.check_exposure(listener.local_addr()?)?;
+        info!(
+            "NBD server listening on {} with {} exports",
+            self.config.listen_addr,
+            self.exports.len()
+        );
+        self.serve(listener).await
+    }
+
+    /// Refuse to hand out unauthenticated block access beyond the local host
+    fn check_exposure(&self, addr: SocketAddr) -> Result<()> {
+        if !self.config.allow_remote && !addr.ip().is_loopback() {
+            return Err(Error::Config(format!(
+                "NBD server on {} is unauthenticated; listen on a loopback address or allow remote clients",
+                addr
+            )));
+        }
+        Ok(())
+    }
+
+    /// Accept and serve clients from `listener`
+    pub async fn serve(self: Arc<Self>, listener: TcpListener) -> Result<()> {
+        loop {
+            let (stream, peer) = listener.accept().await?;
+            let _ = stream.set_nodelay(true);
+            let server = self.clone();
+            tokio::spawn(async move {
+                debug!("NBD client {} connected", peer);
+                match server.handle_connection(stream).await {
+                    Ok(()) => debug!("NBD client {} disconnected", peer),
+                    Err(e) => warn!("NBD client {} dropped: {}", peer, e),
+                }
+            });
+        }
+    }
+
+    /// Negotiate with one client and serve its requests
+    pub async fn handle_connection<S>(&self, stream: S) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (mut reader, mut writer) = tokio::io::split(stream);
+        match self.negotiate(&mut reader, &mut writer).await? {
+            Some(device) => self.transmit(device, reader, writer).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Default export: the only one, if there is exactly one
+    fn export(&self, name: &str) -> Option<Arc<EcBlockDevice>> {
+        if name.is_empty() && self.exports.len() == 1 {
+            return self.exports.values().next().cloned();
+        }
+        self.exports.get(name).cloned()
+    }
+
+    fn transmission_flags(&self) -> u16 {
+        NBD_FLAG_HAS_FLAGS | NBD_FLAG_SEND_FLUSH | NBD_FLAG_SEND_FUA | NBD_FLAG_SEND_TRIM
+    }
+
+    // -------------------------------------------------------------------------
+    // Negotiation
+    // -------------------------------------------------------------------------
+
+    /// Run the fixed newstyle handshake, returning the chosen export (None
+    /// if the client aborted)
+    async fn negotiate<R, W>(
+        &self,
+        reader: &mut R,
+        writer: &mut W,
+    ) -> Result<Option<Arc<EcBlockDevice>>>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut hello = Vec::with_capacity(18);
+        hello.extend_from_slice(&NBD_MAGIC.to_be_bytes());
+        hello.extend_from_slice(&NBD_IHAVEOPT.to_be_bytes());
+        hello.extend_from_slice(&(NBD_FLAG_FIXED_NEWSTYLE | NBD_FLAG_NO_ZEROES).to_be_bytes());
+        writer.write_all(&hello).await?;
+        writer.flush().await?;
+
+        let client_flags = reader.read_u32().await?;
+        if client_flags & NBD_FLAG_C_FIXED_NEWSTYLE == 0 {
+            return Err(Error::NbdProtocol(
+                "client does not support fixed newstyle negotiation".to_string(),
+            ));
+        }
+        let no_zeroes = client_flags & NBD_FLAG_C_NO_ZEROES != 0;
+
+        loop {
+            if reader.read_u64().await? != NBD_IHAVEOPT {
+                return Err(Error::NbdProtocol("bad option magic".to_string()));
+            }
+            let option = reader.read_u32().await?;
+            let length = reader.read_u32().await?;
+            if length > MAX_OPTION_BYTES {
+                return Err(Error::NbdProtocol(format!(
+                    "option {} carries {} bytes",
+                    option, length
+                )));
+            }
+            let mut data = vec![0u8; length as usize];
+            reader.read_exact(&mut data).await?;
+
+            match option {
+                NBD_OPT_EXPORT_NAME => {
+                    let name = String::from_utf8_lossy(&data);
+                    let device = self
+                        .export(&name)
+                        .ok_or_else(|| Error::NbdProtocol(format!("unknown export '{}'", name)))?;
+                    let mut reply = Vec::with_capacity(134);
+                    reply.extend_from_slice(&device.size_bytes().to_be_bytes());
+                    reply.extend_from_slice(&self.transmission_flags().to_be_bytes());
+                    if !no_zeroes {
+                        reply.extend_from_slice(&[0u8; 124]);
+                    }
+                    writer.write_all(&reply).await?;
+                    writer.flush().await?;
+                    return Ok(Some(device));
+                }
+                NBD_OPT_ABORT => {
+                    write_option_reply(writer, option, NBD_REP_ACK, &[]).await?;
+                    return Ok(None);
+                }
+                NBD_OPT_LIST if data.is_empty() => {
+                    let mut names: Vec<&String> = self.exports.keys().collect();
+                    names.sort();
+                    for name in names {
+                        let mut entry = Vec::with_capacity(4 + name.len());
+                        entry.extend_from_slice(&(name.len() as u32).to_be_bytes());
+                        entry.extend_from_slice(name.as_bytes());
+                        write_option_reply(writer, option, NBD_REP_SERVER, &entry).await?;
+                    }
+                    write_option_reply(writer, option, NBD_REP_ACK, &[]).await?;
+                }
+                NBD_OPT_INFO | NBD_OPT_GO => {
+                    let Some(name) = parse_info_request(&data) else {
+                        write_option_reply(writer, option, NBD_REP_ERR_INVALID, &[]).await?;
+                        continue;
+                    };
+                    let Some(device) = self.export(&name) else {
+                        write_option_reply(writer, option, NBD_REP_ERR_UNKNOWN, &[]).await?;
+                        continue;
+                    };
+
+                    let mut export = Vec::with_capacity(12);
+                    export.extend_from_slice(&NBD_INFO_EXPORT.to_be_bytes());
+                    export.extend_from_slice(&device.size_bytes().to_be_bytes());
+                    export.extend_from_slice(&self.transmission_flags().to_be_bytes());
+                    write_option_reply(writer, option, NBD_REP_INFO, &export).await?;
+
+                    // Sent whether or not the client asked, so it never issues
+                    // requests smaller than a block
+                    let mut sizes = Vec::with_capacity(14);
+                    sizes.extend_from_slice(&NBD_INFO_BLOCK_SIZE.to_be_bytes());
+                    sizes.extend_from_slice(&ADVERTISED_BLOCK_BYTES.to_be_bytes());
+                    sizes.extend_from_slice(&ADVERTISED_BLOCK_BYTES.to_be_bytes());
+                    sizes.extend_from_slice(&self.config.max_request_bytes.to_be_bytes());
+                    write_option_reply(writer, option, NBD_REP_INFO, &sizes).await?;
+
+                    write_option_reply(writer, option, NBD_REP_ACK, &[]).await?;
+                    if option == NBD_OPT_GO {
+                        return Ok(Some(device));
+                    }
+                }
+                NBD_OPT_LIST => {
+                    write_option_reply(writer, option, NBD_REP_ERR_INVALID, &[]).await?;
+                }
+                _ => {
+                    write_option_reply(writer, option, NBD_REP_ERR_UNSUP, &[]).await?;
+                }
+            }
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    // Transmission
+    // -------------------------------------------------------------------------
+
+    /// Serve requests until the client disconnects
+    async fn transmit<R, W>(
+        &self,
+        device: Arc<EcBlockDevice>,
+        mut reader: R,
+        writer: W,
+    ) -> Result<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        info!(
+            "NBD client attached to volume {} ({} bytes)",
+            device.volume_id(),
+            device.size_bytes()
+        );
+        let writer = Arc::new(Mutex::new(writer));
+        let max_in_flight = self.config.max_in_flight.max(1);
+        let in_flight = Arc::new(Semaphore::new(max_in_flight));
+
+        loop {
+            let request = match read_request(&mut reader).await {
+                Ok(request) => request,
+                // Hanging up without NBD_CMD_DISC is common enough to not warn
+                Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            };
+            if request.command == NBD_CMD_DISC {
+                break;
+            }
+
+            let mut payload = Vec::new();
+            if request.command == NBD_CMD_WRITE {
+                if request.length > self.config.max_request_bytes {
+                    // Drain the payload so the stream stays in sync
+                    let mut rest = (&mut reader).take(request.length as u64);
+                    tokio::io::copy(&mut rest, &mut tokio::io::sink()).await?;
+                    write_simple_reply(&mut *writer.lock().await, NBD_EINVAL, request.handle, &[])
+                        .await?;
+                    continue;
+                }
+                payload = vec![0u8; request.length as usize];
+                reader.read_exact(&mut payload).await?;
+            }
+
+            let permit = in_flight
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("semaphore is never closed");
+            let device = device.clone();
+            let writer = writer.clone();
+            let max_request_bytes = self.config.max_request_bytes;
+            tokio::spawn(async move {
+                let _permit = permit;
+                let (error, data) =
+                    match execute(&device, request, payload, max_request_bytes).await {
+                        Ok(data) => (0, data),
+                        Err(error) => (error, vec![]),
+                    };
+                let mut writer = writer.lock().await;
+                if let Err(e) = write_simple_reply(&mut *writer, error, request.handle, &data).await
+                {
+                    debug!("Failed to reply to NBD request {}: {}", request.handle, e);
+                }
+            });
+        }
+
+        // Let requests still in flight reply before hanging up
+        let _ = in_flight.acquire_many(max_in_flight as u32).await;
+        writer.lock().await.shutdown().await?;
+        Ok(())
+    }
+}
+
+/// Run one request, returning the reply data or an NBD error value
+async fn execute(
+    device: &EcBlockDevice,
+    request: NbdRequest,
+    payload: Vec<u8>,
+    max_request_bytes: u32,
+) -> std::result::Result<Vec<u8>, u32> {
+    let (offset, length) = (request.offset, request.length as u64);
+    let result = match request.command {
+        NBD_CMD_READ if request.length > max_request_bytes => return Err(NBD_EINVAL),
+        NBD_CMD_READ => device.read(offset, length).await,
+        NBD_CMD_WRITE if offset.saturating_add(length) > device.size_bytes() => {
+            return Err(NBD_ENOSPC)
+        }
+        // Acknowledged appends are durable, so FUA needs nothing extra
+        NBD_CMD_WRITE => device.write(offset, &payload).await.map(|()| vec![]),
+        NBD_CMD_FLUSH => {
+            device.flush();
+            Ok(vec![])
+        }
+        NBD_CMD_TRIM => device.trim(offset, length).await.map(|()| vec![]),
+        _ => return Err(NBD_EINVAL),
+    };
+
+    result.map_err(|e| {
+        debug!(
+            "NBD command {} (flags {:#x}) on volume {} failed: {}",
+            request.command,
+            request.flags,
+            device.volume_id(),
+            e
+        );
+        match e {
+            Error::InvalidBlockRequest { .. } => NBD_EINVAL,
+            Error::EcJournalFull { .. } => NBD_ENOSPC,
+            _ => NBD_EIO,
+        }
+    })
+}
+
+/// Parse the payload of `NBD_OPT_INFO` / `NBD_OPT_GO`, returning the export
+/// name; the information types requested are checked but not needed, as
+/// every reply carries the export and block size information
+fn parse_info_request(data: &[u8]) -> Option<String> {
+    let name_len = u32::from_be_bytes(data.get(..4)?.try_into().ok()?) as usize;
+    let name = data.get(4..4 + name_len)?;
+    let rest = &data[4 + name_len..];
+    let count = u16::from_be_bytes(rest.get(..2)?.try_into().ok()?) as usize;
+    if rest.get(2..)?.len() != count * 2 {
+        return None;
+    }
+    Some(String::from_utf8_lossy(name).into_owned())
+}
+
+async fn write_option_reply<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    option: u32,
+    reply: u32,
+    data: &[u8],
+) -> Result<()> {
+    let mut buf = Vec::with_capacity(20 + data.len());
+    buf.extend_from_slice(&NBD_OPTION_REPLY_MAGIC.to_be_bytes());
+    buf.extend_from_slice(&option.to_be_bytes());
+    buf.extend_from_slice(&reply.to_be_bytes());
+    buf.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    buf.extend_from_slice(data);
+    writer.write_all(&buf).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+async fn read_request<R: AsyncRead + Unpin>(reader: &mut R) -> Result<NbdRequest> {
+    let mut header = [0u8; 28];
+    reader.read_exact(&mut header).await?;
+    let magic = u32::from_be_bytes(header[0..4].try_into().unwrap());
+    if magic != NBD_REQUEST_MAGIC {
+        return Err(Error::NbdProtocol(format!(
+            "bad request magic {:#x}",
+            magic
+        )));
+    }
+    Ok(NbdRequest {
+        flags: u16::from_be_bytes(header[4..6].try_into().unwrap()),
+        command: u16::from_be_bytes(header[6..8].try_into().unwrap()),
+        handle: u64::from_be_bytes(header[8..16].try_into().unwrap()),
+        offset: u64::from_be_bytes(header[16..24].try_into().unwrap()),
+        length: u32::from_be_bytes(header[24..28].try_into().unwrap()),
+    })
+}
+
+async fn write_simple_reply<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    error: u32,
+    handle: u64,
+    data: &[u8],
+) -> Result<()> {
+    let mut header = [0u8; 16];
+    header[0..4].copy_from_slice(&NBD_SIMPLE_REPLY_MAGIC.to_be_bytes());
+    header[4..8].copy_from_slice(&error.to_be_bytes());
+    header[8..16].copy_from_slice(&handle.to_be_bytes());
+    writer.write_all(&header).await?;
+    writer.write_all(data).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crd::{ErasureCodingPolicy, ErasureCodingPolicySpec, JournalConfig};
+    use crate::ec::reconstruction::ReconstructionConfig;
+    use crate::ec::shard_store::ShardStore;
+    use crate::ec::stripe_manager::{StripeManager, StripeManagerConfig};
+    use crate::ec::test_support::{self, EcFixture};
+    use std::time::Duration;
+    use tempfile::TempDir;
+    use tokio::io::DuplexStream;
+
+    const POLICY: &str = "ec-4-2";
+    const VOLUME: &str = "nbd-vol";
+    const SIZE: u64 = 1 << 20;
+
+    struct Fixture {
+        _journal_dir: TempDir,
+        ec: EcFixture,
+        journal: Arc<WriteJournal>,
+        server: Arc<NbdServer>,
+    }
+
+    async fn fixture() -> Fixture {
+        let policy = ErasureCodingPolicy::new(
+            POLICY,
+            ErasureCodingPolicySpec {
+                // Every append makes the volume due for destaging
+                journal_config: Some(JournalConfig {
+                    journal_size_bytes: 1 << 20,
+                    replication_factor: 1,
+                    destage_threshold_percent: 0,
+                    destage_interval: "1h".to_string(),
+                }),
+                ..test_support::policy_spec(4, 2, 4096)
+            },
+        );
+        let ec = EcFixture::new(8, 4, [policy]);
+
+        let journal_dir = tempfile::tempdir().unwrap();
+        let journal = WriteJournal::open(
+            vec![journal_dir.path().to_path_buf()],
+            false,
+            ec.metadata_manager.clone(),
+        )
+        .await
+        .unwrap();
+        let engine = ReconstructionEngine::new(
+            ReconstructionConfig::default(),
+            ec.metadata_manager.clone(),
+            ec.store.clone(),
+            ec.repository.clone(),
+        );
+        let export = NbdExport {
+            volume_id: VOLUME.to_string(),
+            policy: POLICY.to_string(),
+            size_bytes: SIZE,
+        };
+        let server = NbdServer::new(
+            NbdConfig::default(),
+            &[export],
+            &ec.metadata_manager,
+            journal.clone(),
+            engine,
+            None,
+        )
+        .unwrap();
+
+        Fixture {
+            _journal_dir: journal_dir,
+            ec,
+            journal,
+            server,
+        }
+    }
+
+    fn connect(server: &Arc<NbdServer>) -> DuplexStream {
+        let (client, server_side) = tokio::io::duplex(4 << 20);
+        let server = server.clone();
+        tokio::spawn(async move { server.handle_connection(server_side).await });
+        client
+    }
+
+    /// Read the server greeting and send the client flags
+    async fn greet(stream: &mut DuplexStream, client_flags: u32) {
+        assert_eq!(stream.read_u64().await.unwrap(), NBD_MAGIC);
+        assert_eq!(stream.read_u64().await.unwrap(), NBD_IHAVEOPT);
+        let flags = stream.read_u16().await.unwrap();
+        assert_eq!(flags, NBD_FLAG_FIXED_NEWSTYLE | NBD_FLAG_NO_ZEROES);
+        stream.write_u32(client_flags).await.unwrap();
+    }
+
+    async fn send_option(stream: &mut DuplexStream, option: u32, data: &[u8]) {
+        stream.write_u64(NBD_IHAVEOPT).await.unwrap();
+        stream.write_u32(option).await.unwrap();
+        stream.write_u32(data.len() as u32).await.unwrap();
+        stream.write_all(data).await.unwrap();
+    }
+
+    /// Read one option reply: (reply type, data)
+    async fn option_reply(stream: &mut DuplexStream, option: u32) -> (u32, Vec<u8>) {
+        assert_eq!(stream.read_u64().await.unwrap(), NBD_OPTION_REPLY_MAGIC);
+        assert_eq!(stream.read_u32().await.unwrap(), option);
+        let reply = stream.read_u32().await.unwrap();
+        let mut data = vec![0u8; stream.read_u32().await.unwrap() as usize];
+        stream.read_exact(&mut data).await.unwrap();
+        (reply, data)
+    }
+
+    fn info_request(name: &str, requests: &[u16]) -> Vec<u8> {
+        let mut data = (name.len() as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(name.as_bytes());
+        data.extend_from_slice(&(requests.len() as u16).to_be_bytes());
+        for r in requests {
+            data.extend_from_slice(&r.to_be_bytes());
+        }
+        data
+    }
+
+    /// Connect and select the export with NBD_OPT_GO
+    async fn attach(server: &Arc<NbdServer>) -> DuplexStream {
+        let mut stream = connect(server);
+        greet(
+            &mut stream,
+            NBD_FLAG_C_FIXED_NEWSTYLE | NBD_FLAG_C_NO_ZEROES,
+        )
+        .await;
+        send_option(&mut stream, NBD_OPT_GO, &info_request(VOLUME, &[])).await;
+        loop {
+            match option_reply(&mut stream, NBD_OPT_GO).await {
+                (NBD_REP_INFO, _) => continue,
+                (NBD_REP_ACK, _) => return stream,
+                (reply, _) => panic!("unexpected reply {:#x}", reply),
+            }
+        }
+    }
+
+    /// Send one request and wait for its reply: (error, data)
+    async fn request(
+        stream: &mut DuplexStream,
+        command: u16,
+        offset: u64,
+        length: u32,
+        payload: &[u8],
+    ) -> (u32, Vec<u8>) {
+        let mut header = Vec::with_capacity(28);
+        header.extend_from_slice(&NBD_REQUEST_MAGIC.to_be_bytes());
+        header.extend_from_slice(&0u16.to_be_bytes());
+        header.extend_from_slice(&command.to_be_bytes());
+        header.extend_from_slice(&0xfeedu64.to_be_bytes());
+        header.extend_from_slice(&offset.to_be_bytes());
+        header.extend_from_slice(&length.to_be_bytes());
+        stream.write_all(&header).await.unwrap();
+        stream.write_all(payload).await.unwrap();
+
+        assert_eq!(stream.read_u32().await.unwrap(), NBD_SIMPLE_REPLY_MAGIC);
+        let error = stream.read_u32().await.unwrap();
+        assert_eq!(stream.read_u64().await.unwrap(), 0xfeed);
+        let mut data = vec![];
+        if command == NBD_CMD_READ && error == 0 {
+            data = vec![0u8; length as usize];
+            stream.read_exact(&mut data).await.unwrap();
+        }
+        (error, data)
+    }
+
+    async fn read(stream: &mut DuplexStream, offset: u64, length: u32) -> Vec<u8> {
+        let (error, data) = request(stream, NBD_CMD_READ, offset, length, &[]).await;
+        assert_eq!(error, 0);
+        data
+    }
+
+    async fn write(stream: &mut DuplexStream, offset: u64, data: &[u8]) {
+        let (error, _) = request(stream, NBD_CMD_WRITE, offset, data.len() as u32, data).await;
+        assert_eq!(error, 0);
+    }
+
+    fn pattern(len: usize, seed: u8) -> Vec<u8> {
+        (0..len)
+            .map(|i| (i as u8).wrapping_mul(31) ^ seed)
+            .collect()
+    }
+
+    #[test]
+    fn test_export_parsing() {
+        let export: NbdExport = "vol-a:ec-4-2:10GiB".parse().unwrap();
+        assert_eq!(export.volume_id, "vol-a");
+        assert_eq!(export.policy, "ec-4-2");
+        assert_eq!(export.size_bytes, 10 << 30);
+        assert_eq!(
+            "vol-a:ec-4-2:1048576"
+                .parse::<NbdExport>()
+                .unwrap()
+                .size_bytes,
+            1 << 20
+        );
+        assert_eq!(
+            "vol-a:ec-4-2:64M".parse::<NbdExport>().unwrap().size_bytes,
+            64 << 20
+        );
+
+        for invalid in [
+            "vol-a:10G",
+            "vol-a:ec-4-2:10G:x",
+            ":ec-4-2:1G",
+            "vol-a:ec-4-2:1000",
+        ] {
+            assert!(invalid.parse::<NbdExport>().is_err(), "{}", invalid);
+        }
+        assert!("vol-a:ec-4-2:10X".parse::<NbdExport>().is_err());
+        assert!("vol-a:ec-4-2:0".parse::<NbdExport>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_remote_clients_need_opt_in() {
+        let f = fixture().await;
+        let loopback: SocketAddr = "127.0.0.1:10809".parse().unwrap();
+        let public: SocketAddr = "0.0.0.0:10809".parse().unwrap();
+        assert!(f.server.check_exposure(loopback).is_ok());
+        assert!(f.server.check_exposure(public).is_err());
+
+        let server = NbdServer::new(
+            NbdConfig {
+                allow_remote: true,
+                ..Default::default()
+            },
+            &[],
+            &f.ec.metadata_manager,
+            f.journal.clone(),
+            f.server.exports[VOLUME].engine.clone(),
+            None,
+        )
+        .unwrap();
+        assert!(server.check_exposure(public).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_export_rejects_conflicting_policy() {
+        let f = fixture().await;
+        let export: NbdExport = format!("{}:other-policy:1M", VOLUME).parse().unwrap();
+        let err = NbdServer::new(
+            NbdConfig::default(),
+            &[export],
+            &f.ec.metadata_manager,
+            f.journal.clone(),
+            f.server.exports[VOLUME].engine.clone(),
+            None,
+        )
+        .err()
+        .unwrap();
+        assert!(matches!(err, Error::InvalidEcConfig(_)));
+    }
+
+    #[tokio::test]
+    async fn test_negotiation_options() {
+        let f = fixture().await;
+        let mut stream = connect(&f.server);
+        greet(&mut stream, NBD_FLAG_C_FIXED_NEWSTYLE).await;
+
+        send_option(&mut stream, NBD_OPT_LIST, &[]).await;
+        let (reply, data) = option_reply(&mut stream, NBD_OPT_LIST).await;
+        assert_eq!(reply, NBD_REP_SERVER);
+        assert_eq!(&data[4..], VOLUME.as_bytes());
+        assert_eq!(option_reply(&mut stream, NBD_OPT_LIST).await.0, NBD_REP_ACK);
+
+        // Structured replies are not supported
+        send_option(&mut stream, 8, &[]).await;
+        assert_eq!(option_reply(&mut stream, 8).await.0, NBD_REP_ERR_UNSUP);
+
+        send_option(&mut stream, NBD_OPT_INFO, &info_request("missing", &[])).await;
+        let (reply, _) = option_reply(&mut stream, NBD_OPT_INFO).await;
+        assert_eq!(reply, NBD_REP_ERR_UNKNOWN);
+
+        // Block sizes come back even when not requested
+        send_option(&mut stream, NBD_OPT_INFO, &info_request(VOLUME, &[])).await;
+        let (reply, data) = option_reply(&mut stream, NBD_OPT_INFO).await;
+        assert_eq!(reply, NBD_REP_INFO);
+        assert_eq!(u16::from_be_bytes([data[0], data[1]]), NBD_INFO_EXPORT);
+        assert_eq!(u64::from_be_bytes(data[2..10].try_into().unwrap()), SIZE);
+        let (reply, data) = option_reply(&mut stream, NBD_OPT_INFO).await;
+        assert_eq!(reply, NBD_REP_INFO);
+        assert_eq!(u16::from_be_bytes([data[0], data[1]]), NBD_INFO_BLOCK_SIZE);
+        assert_eq!(u32::from_be_bytes(data[2..6].try_into().unwrap()), 4096);
+        assert_eq!(u32::from_be_bytes(data[6..10].try_into().unwrap()), 4096);
+        assert_eq!(option_reply(&mut stream, NBD_OPT_INFO).await.0, NBD_REP_ACK);
+
+        // Old-style export selection, padded since NO_ZEROES was not set
+        send_option(&mut stream, NBD_OPT_EXPORT_NAME, VOLUME.as_bytes()).await;
+        assert_eq!(stream.read_u64().await.unwrap(), SIZE);
+        let flags = stream.read_u16().await.unwrap();
+        assert_ne!(flags & NBD_FLAG_SEND_FLUSH, 0);
+        assert_ne!(flags & NBD_FLAG_SEND_TRIM, 0);
+        let mut zeroes = [1u8; 124];
+        stream.read_exact(&mut zeroes).await.unwrap();
+        assert_eq!(zeroes, [0u8; 124]);
+
+        // The export now serves requests
+        assert_eq!(read(&mut stream, 0, 512).await, vec![0u8; 512]);
+    }
+
+    #[tokio::test]
+    async fn test_write_read_roundtrip_through_journal() {
+        let f = fixture().await;
+        let mut stream = attach(&f.server).await;
+
+        let data = pattern(8192, 7);
+        write(&mut stream, 4096, &data).await;
+        write(&mut stream, 4608, &[0xEE; 512]).await;
+        assert_eq!(f.journal.stats(VOLUME).unwrap().entries, 2);
+
+        // Unwritten blocks read as zeros; the newer write wins
+        let mut expected = vec![0u8; 16384];
+        expected[4096..12288].copy_from_slice(&data);
+        expected[4608..5120].fill(0xEE);
+        assert_eq!(read(&mut stream, 0, 16384).await, expected);
+        assert_eq!(read(&mut stream, 4608, 512).await, vec![0xEE; 512]);
+
+        let (error, _) = request(&mut stream, NBD_CMD_READ, 100, 512, &[]).await;
+        assert_eq!(error, NBD_EINVAL);
+        let (error, _) = request(&mut stream, NBD_CMD_WRITE, SIZE, 512, &[0u8; 512]).await;
+        assert_eq!(error, NBD_ENOSPC);
+        assert_eq!(request(&mut stream, NBD_CMD_FLUSH, 0, 0, &[]).await.0, 0);
+
+        // Trimmed blocks read as zeros
+        assert_eq!(
+            request(&mut stream, NBD_CMD_TRIM, 4096, 4096, &[]).await.0,
+            0
+        );
+        expected[4096..8192].fill(0);
+        assert_eq!(read(&mut stream, 0, 16384).await, expected);
+        let (error, _) = request(&mut stream, NBD_CMD_TRIM, SIZE, 4096, &[]).await;
+        assert_eq!(error, NBD_EINVAL);
+
+        // Disconnecting closes the stream
+        let mut header = NBD_REQUEST_MAGIC.to_be_bytes().to_vec();
+        header.extend_from_slice(&0u16.to_be_bytes());
+        header.extend_from_slice(&NBD_CMD_DISC.to_be_bytes());
+        header.extend_from_slice(&[0u8; 20]);
+        stream.write_all(&header).await.unwrap();
+        let mut rest = vec![];
+        stream.read_to_end(&mut rest).await.unwrap();
+        assert!(rest.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reads_destaged_and_degraded_stripes() {
+        let f = fixture().await;
+        let manager = StripeManager::with_journal(
+            StripeManagerConfig::default(),
+            f.ec.metadata_manager.clone(),
+            f.ec.store.clone(),
+            f.ec.repository.clone(),
+            f.journal.clone(),
+        );
+        tokio::spawn(manager.run());
+
+        let mut stream = attach(&f.server).await;
+        let data = pattern(12288, 3);
+        write(&mut stream, 0, &data).await;
+        write(&mut stream, 8192, &[0x55; 1024]).await;
+        assert_eq!(request(&mut stream, NBD_CMD_FLUSH, 0, 0, &[]).await.0, 0);
+
+        // Wait for the destage loop to empty the journal
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while f.journal.stats(VOLUME).unwrap().entries > 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        let mut expected = data.clone();
+        expected[8192..9216].fill(0x55);
+        assert_eq!(read(&mut stream, 0, 12288).await, expected);
+
+        // Losing the pool of a data shard still reads the same blocks
+        let volume = f.ec.metadata_manager.get_volume(VOLUME).unwrap();
+        let stripe = volume.read().all_stripes().remove(0);
+        f.ec.store
+            .set_pool_online(&stripe.shard_locations[0].pool_name, false);
+        assert_eq!(read(&mut stream, 0, 12288).await, expected);
+    }
+}
@@ -9,12 +9,19 @@
 //! 2. **Fault Tolerance**: Can recover from up to m shard losses
 //! 3. **Determinism**: Same input always produces same output
 //! 4. **Shard Independence**: Any k shards can reconstruct data
+//! 5. **Parity Updates**: Delta-updated parity equals a full re-encode
+//! 6. **SIMD Codec Equivalence**: `SimdCodec` output is bit-identical to
+//!    `EcEncoder`/`EcDecoder` for every supported kernel
 
 #![cfg(test)]
+#![allow(clippy::needless_range_loop)]
 
 use proptest::prelude::*;
 
 use super::encoder::{calculate_shard_size, EcDecoder, EcEncoder};
+use super::gf256::GfSimdLevel;
+use super::simd_codec::SimdCodec;
+use crate::domain::ports::EcCodec;
 
 // =============================================================================
 // Property Strategies
@@ -343,6 +350,102 @@ proptest! {
     }
 }
 
+// =============================================================================
+// Parity Update Properties
+// =============================================================================
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(50))]
+
+    /// Property: Updating parity with a delta matches re-encoding the new data.
+    #[test]
+    fn prop_update_parity_matches_encode(
+        (k, m) in ec_config_strategy(),
+        data in small_data_strategy(),
+        index_seed in any::<usize>(),
+        new_bytes in prop::collection::vec(any::<u8>(), 1..1000),
+    ) {
+        let encoder = EcEncoder::new(k, m)?;
+        let shards = encoder.encode(&data)?;
+        let shard_size = shards[0].len();
+        let index = index_seed % k;
+
+        // Replace the data shard with arbitrary bytes of the same size
+        let mut new_shard = shards[index].clone();
+        for (byte, new) in new_shard.iter_mut().zip(new_bytes.iter().cycle()) {
+            *byte = *new;
+        }
+
+        let mut parity = shards[k..].to_vec();
+        encoder.update_parity(index, &shards[index], &new_shard, &mut parity)?;
+
+        let mut expected = shards.clone();
+        expected[index] = new_shard;
+        for shard in expected.iter_mut().skip(k) {
+            *shard = vec![0u8; shard_size];
+        }
+        encoder.encode_shards(&mut expected)?;
+
+        prop_assert_eq!(parity, expected[k..].to_vec());
+    }
+}
+
+// =============================================================================
+// SIMD Codec Equivalence Properties
+// =============================================================================
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(50))]
+
+    /// Property: The SIMD codec encodes exactly like the reference encoder.
+    #[test]
+    fn prop_simd_encode_matches_encoder(
+        (k, m) in ec_config_strategy(),
+        data in data_strategy(),
+    ) {
+        let expected = EcEncoder::new(k, m)?.encode(&data)?;
+
+        for level in GfSimdLevel::supported() {
+            let codec = SimdCodec::with_level(k, m, level)?;
+            prop_assert_eq!(
+                &codec.encode_to_shards(&data)?,
+                &expected,
+                "Encoding differs for k={}, m={}, level={}", k, m, level
+            );
+        }
+    }
+
+    /// Property: The SIMD codec reconstructs exactly like the reference decoder.
+    #[test]
+    fn prop_simd_reconstruct_matches_decoder(
+        (k, m) in ec_config_strategy(),
+        data in small_data_strategy(),
+        erasures in erasure_strategy(12, 4),
+    ) {
+        let erasures: Vec<usize> = erasures.into_iter().filter(|&i| i < k + m).take(m).collect();
+        let shards = EcEncoder::new(k, m)?.encode(&data)?;
+
+        let mut expected: Vec<Option<Vec<u8>>> = shards.iter().cloned().map(Some).collect();
+        for &i in &erasures {
+            expected[i] = None;
+        }
+        let damaged = expected.clone();
+        EcDecoder::new(k, m)?.reconstruct(&mut expected)?;
+
+        for level in GfSimdLevel::supported() {
+            let codec = SimdCodec::with_level(k, m, level)?;
+            let mut recovered = damaged.clone();
+            codec.reconstruct(&mut recovered)?;
+            prop_assert_eq!(&recovered, &expected,
+                "Reconstruction differs for k={}, m={}, erasures={:?}, level={}",
+                k, m, erasures, level);
+
+            let mut decoded = damaged.clone();
+            prop_assert_eq!(codec.decode(&mut decoded, data.len())?, data.clone());
+        }
+    }
+}
+
 // =============================================================================
 // Failure Mode Properties
 // =============================================================================
@@ -3,17 +3,27 @@
 //! Handles degraded reads and background stripe rebuilds for
 //! erasure-coded volumes.
 
-use crate::crd::{LbaRange, ShardState, StripeState};
+use crate::crd::{LbaRange, ShardHealth, ShardLocation, ShardState, StripeState};
+use crate::domain::ports::StripeRepository;
 use crate::ec::encoder::EcDecoder;
-use crate::ec::metadata::{EcMetadataManager, StripeMetadata};
+use crate::ec::metadata::{
+    commit_shard_placement, EcMetadataManager, StripeMetadata, StripeStatus,
+};
+use crate::ec::shard_store::{
+    discard_shards, read_stripe_shards_timeout, shard_checksum, ShardStore,
+};
+use crate::ec::stripe_manager::LBA_SIZE;
 use crate::error::{Error, Result};
 use chrono::{DateTime, Utc};
 use dashmap::DashMap;
-use std::sync::atomic::{AtomicU64, Ordering};
+use once_cell::sync::Lazy;
+use prometheus::IntGaugeVec;
+use std::collections::BinaryHeap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::{mpsc, Semaphore};
-use tokio::time::interval;
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore};
+use tokio::time::{interval, sleep, timeout, Instant};
 use tracing::{debug, error, info, instrument};
 
 // =============================================================================
@@ -37,6 +47,9 @@ pub struct ReconstructionConfig {
 
     /// Whether to prioritize read requests over rebuilds
     pub prioritize_reads: bool,
+
+    /// Rebuild I/O rate limit in bytes per second (0 = unlimited)
+    pub rebuild_rate_bytes_per_sec: u64,
 }
 
 impl Default for ReconstructionConfig {
@@ -47,6 +60,7 @@ impl Default for ReconstructionConfig {
             stripe_timeout: Duration::from_secs(300), // 5 minutes
             rebuild_check_interval: Duration::from_secs(60),
             prioritize_reads: true,
+            rebuild_rate_bytes_per_sec: 0,
         }
     }
 }
@@ -76,6 +90,9 @@ pub struct ReconstructionTask {
     /// Priority (higher = more urgent)
     pub priority: u8,
 
+    /// Data-loss risk of the stripe when the task was queued
+    pub risk: RebuildRisk,
+
     /// When the task was created
     pub created_at: DateTime<Utc>,
 
@@ -107,6 +124,139 @@ pub enum TaskStatus {
     Cancelled,
 }
 
+// =============================================================================
+// Rebuild Scheduling
+// =============================================================================
+
+/// Data-loss risk of a stripe awaiting rebuild, by remaining redundancy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RebuildRisk {
+    /// Two or more further shard failures can be tolerated
+    Low,
+    /// Exactly one further shard failure can be tolerated
+    Elevated,
+    /// No redundancy left; the next shard failure loses data
+    Critical,
+}
+
+impl RebuildRisk {
+    /// All risk levels, lowest first
+    pub const ALL: [RebuildRisk; 3] = [
+        RebuildRisk::Low,
+        RebuildRisk::Elevated,
+        RebuildRisk::Critical,
+    ];
+
+    /// Classify a stripe from its parity count and number of missing shards
+    pub fn from_redundancy(parity_shards: usize, missing_shards: usize) -> Self {
+        match parity_shards.saturating_sub(missing_shards) {
+            0 => RebuildRisk::Critical,
+            1 => RebuildRisk::Elevated,
+            _ => RebuildRisk::Low,
+        }
+    }
+
+    /// Label used for metrics
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RebuildRisk::Low => "low",
+            RebuildRisk::Elevated => "elevated",
+            RebuildRisk::Critical => "critical",
+        }
+    }
+}
+
+/// Compute the queue priority of a task.
+///
+/// Tasks order by (read-triggered, risk): with `prioritize_reads` set, any
+/// repair a client is waiting on runs before every background rebuild, and
+/// risk orders tasks within each group.
+fn task_priority(risk: RebuildRisk, task_type: ReconstructionType, prioritize_reads: bool) -> u8 {
+    let read_first = prioritize_reads && task_type == ReconstructionType::DegradedRead;
+    let levels = RebuildRisk::ALL.len() as u8;
+    u8::from(read_first) * levels + risk as u8
+}
+
+/// Heap entry for a pending task; higher priority first, then oldest first
+#[derive(Debug, PartialEq, Eq)]
+struct QueueEntry {
+    priority: u8,
+    task_id: u64,
+}
+
+impl Ord for QueueEntry {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.task_id.cmp(&self.task_id))
+    }
+}
+
+impl PartialOrd for QueueEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Pending rebuilds per risk level
+static REBUILD_QUEUE_DEPTH: Lazy<IntGaugeVec> = Lazy::new(|| {
+    prometheus::register_int_gauge_vec!(
+        "storage_operator_ec_rebuild_queue_depth",
+        "Number of EC stripe rebuilds waiting to run, by risk level",
+        &["risk"]
+    )
+    .expect("failed to register EC rebuild queue gauge")
+});
+
+/// Paces rebuild I/O to a byte rate that can be changed at runtime
+#[derive(Debug)]
+struct RebuildThrottle {
+    /// Allowed bytes per second (0 = unlimited)
+    bytes_per_sec: AtomicU64,
+
+    /// Earliest time the next transfer may start
+    next_slot: parking_lot::Mutex<Instant>,
+}
+
+impl RebuildThrottle {
+    fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec: AtomicU64::new(bytes_per_sec),
+            next_slot: parking_lot::Mutex::new(Instant::now()),
+        }
+    }
+
+    fn set_rate(&self, bytes_per_sec: u64) {
+        self.bytes_per_sec.store(bytes_per_sec, Ordering::Relaxed);
+        // Drop any debt accrued at the old rate
+        *self.next_slot.lock() = Instant::now();
+    }
+
+    fn rate(&self) -> u64 {
+        self.bytes_per_sec.load(Ordering::Relaxed)
+    }
+
+    /// Wait until `bytes` may be transferred at the configured rate
+    async fn consume(&self, bytes: u64) {
+        let rate = self.rate();
+        if rate == 0 || bytes == 0 {
+            return;
+        }
+
+        let delay = {
+            let mut next_slot = self.next_slot.lock();
+            let now = Instant::now();
+            let start = (*next_slot).max(now);
+            *next_slot = start + Duration::from_secs_f64(bytes as f64 / rate as f64);
+            start - now
+        };
+
+        if !delay.is_zero() {
+            sleep(delay).await;
+        }
+    }
+}
+
 // =============================================================================
 // Read Request/Result
 // =============================================================================
@@ -152,8 +302,14 @@ pub struct ReconstructionEngine {
     /// Metadata manager
     metadata_manager: Arc<EcMetadataManager>,
 
+    /// Shard I/O backend
+    shard_store: Arc<dyn ShardStore>,
+
+    /// Persistent stripe metadata (generation and shard placement)
+    stripe_repository: Arc<dyn StripeRepository>,
+
     /// Active reconstruction tasks
-    active_tasks: DashMap<u64, ReconstructionTask>,
+    active_tasks: Arc<DashMap<u64, ReconstructionTask>>,
 
     /// Task ID counter
     next_task_id: AtomicU64,
@@ -164,11 +320,20 @@ pub struct ReconstructionEngine {
     /// Shutdown flag
     shutdown: Arc<std::sync::atomic::AtomicBool>,
 
-    /// Task sender
-    task_tx: mpsc::Sender<ReconstructionTask>,
+    /// Pending tasks ordered by priority
+    queue: parking_lot::Mutex<BinaryHeap<QueueEntry>>,
+
+    /// Number of pending tasks per risk level, indexed by `RebuildRisk as usize`
+    pending_by_risk: [AtomicUsize; 3],
+
+    /// Wakes the run loop when a task is queued
+    queue_notify: Notify,
 
-    /// Task receiver
-    task_rx: Arc<tokio::sync::RwLock<mpsc::Receiver<ReconstructionTask>>>,
+    /// Rebuild I/O pacing
+    throttle: RebuildThrottle,
+
+    /// Smoothed rebuild throughput in bytes per second, once observed
+    observed_throughput: parking_lot::Mutex<Option<f64>>,
 }
 
 impl ReconstructionEngine {
@@ -176,28 +341,36 @@ impl ReconstructionEngine {
     pub fn new(
         config: ReconstructionConfig,
         metadata_manager: Arc<EcMetadataManager>,
+        shard_store: Arc<dyn ShardStore>,
+        stripe_repository: Arc<dyn StripeRepository>,
     ) -> Arc<Self> {
-        let (tx, rx) = mpsc::channel(1000);
-
         Arc::new(Self {
             semaphore: Arc::new(Semaphore::new(config.max_concurrent)),
+            throttle: RebuildThrottle::new(config.rebuild_rate_bytes_per_sec),
             config,
             metadata_manager,
-            active_tasks: DashMap::new(),
+            shard_store,
+            stripe_repository,
+            active_tasks: Arc::new(DashMap::new()),
             next_task_id: AtomicU64::new(0),
             shutdown: Arc::new(std::sync::atomic::AtomicBool::new(false)),
-            task_tx: tx,
-            task_rx: Arc::new(tokio::sync::RwLock::new(rx)),
+            queue: parking_lot::Mutex::new(BinaryHeap::new()),
+            pending_by_risk: Default::default(),
+            queue_notify: Notify::new(),
+            observed_throughput: parking_lot::Mutex::new(None),
         })
     }
 
     /// Handle a degraded read request
     ///
     /// Reads available shards and reconstructs missing ones to serve the read.
+    /// Returns exactly the requested blocks: where stripes overlap the newest
+    /// one wins, and blocks no stripe covers read as zeros.
     #[instrument(skip(self, request))]
     pub async fn handle_degraded_read(&self, request: ReadRequest) -> Result<ReadResult> {
         let start = std::time::Instant::now();
         let mut reconstructed_stripes = Vec::new();
+        let range = &request.lba_range;
 
         // Get volume state
         let volume_state = self
@@ -205,13 +378,10 @@ impl ReconstructionEngine {
             .get_volume(&request.volume_id)
             .ok_or_else(|| Error::Internal(format!("Volume {} not found", request.volume_id)))?;
 
-        // Get stripes and policy_ref from the locked state in a scope block
-        let (stripes, policy_ref) = {
-            let state = volume_state.read();
-            let stripes = state.find_stripes_in_range(&request.lba_range);
-            let policy_ref = state.policy_ref.clone();
-            (stripes, policy_ref)
-        };
+        // Registered before the lookup, so the shards found are kept until
+        // the read finishes
+        let _read = volume_state.read().begin_read();
+        let mut stripes = volume_state.read().find_stripes_in_range(range);
 
         if stripes.is_empty() {
             return Err(Error::EcStripeNotFound(format!(
@@ -220,16 +390,21 @@ impl ReconstructionEngine {
             )));
         }
 
-        // Load policy for decoder configuration
-        let policy = self.metadata_manager.load_policy(&policy_ref).await?;
-        let decoder = EcDecoder::new(
-            policy.spec.data_shards as usize,
-            policy.spec.parity_shards as usize,
-        )?;
-
-        let mut result_data = Vec::new();
+        // Oldest first, so newer stripes overwrite the blocks they shadow
+        stripes.sort_by_key(|s| s.stripe_id);
+        let mut result_data = vec![0u8; (range.size() * LBA_SIZE) as usize];
 
         for stripe in stripes {
+            // Load the stripe's own policy for decoder configuration
+            let policy = self
+                .metadata_manager
+                .load_policy(&stripe.policy_ref)
+                .await?;
+            let decoder = EcDecoder::new(
+                policy.spec.data_shards as usize,
+                policy.spec.parity_shards as usize,
+            )?;
+
             // Check if stripe is degraded
             let is_degraded = stripe.status.state == StripeState::Degraded;
 
@@ -240,28 +415,31 @@ impl ReconstructionEngine {
                 });
             }
 
-            // Read shards (simulate - in real implementation would read from storage)
             let shards_result = self.read_stripe_shards(&stripe, &decoder).await?;
 
             if shards_result.needs_reconstruction {
                 reconstructed_stripes.push(stripe.stripe_id);
 
-                // Create reconstruction task for background repair
-                self.queue_background_rebuild(
+                // Queue a read-triggered repair
+                self.queue_rebuild(
                     stripe.volume_id.clone(),
                     stripe.stripe_id,
                     shards_result.missing_indices.clone(),
+                    ReconstructionType::DegradedRead,
                 )
                 .await?;
             }
 
-            // Append data from this stripe
-            result_data.extend_from_slice(&shards_result.data);
+            // Copy the blocks this stripe holds within the requested range
+            let from = stripe.lba_range.start_lba.max(range.start_lba);
+            let to = stripe.lba_range.end_lba.min(range.end_lba);
+            let src = ((from - stripe.lba_range.start_lba) * LBA_SIZE) as usize;
+            let dst = ((from - range.start_lba) * LBA_SIZE) as usize;
+            let len = (((to - from) * LBA_SIZE) as usize)
+                .min(shards_result.data.len().saturating_sub(src));
+            result_data[dst..dst + len].copy_from_slice(&shards_result.data[src..src + len]);
         }
 
-        // Trim to requested range
-        // (In a real implementation, we'd calculate exact offsets)
-
         Ok(ReadResult {
             data: result_data,
             degraded: !reconstructed_stripes.is_empty(),
@@ -276,31 +454,17 @@ impl ReconstructionEngine {
         stripe: &StripeMetadata,
         decoder: &EcDecoder,
     ) -> Result<ShardReadResult> {
-        let total_shards = decoder.total_shards();
-        let mut shards: Vec<Option<Vec<u8>>> = vec![None; total_shards];
-        let mut missing_indices = Vec::new();
-
-        // Check shard health from status
-        for (i, location) in stripe.shard_locations.iter().enumerate() {
-            let shard_healthy = stripe
-                .status
-                .shard_health
-                .get(i)
-                .map(|h| h.state == ShardState::Healthy)
-                .unwrap_or(true); // Assume healthy if no status
-
-            if shard_healthy {
-                // Simulate reading shard (in real implementation, read from storage)
-                let shard_data = self.simulate_shard_read(location).await;
-                if let Some(data) = shard_data {
-                    shards[i] = Some(data);
-                } else {
-                    missing_indices.push(i as u8);
-                }
-            } else {
-                missing_indices.push(i as u8);
-            }
-        }
+        let total = decoder.total_shards();
+        let indices: Vec<usize> = (0..total).collect();
+        let mut shards = read_stripe_shards_timeout(
+            self.shard_store.as_ref(),
+            stripe,
+            total,
+            &indices,
+            self.config.shard_read_timeout,
+        )
+        .await;
+        let missing_indices = unread_indices(&shards);
 
         let needs_reconstruction = !missing_indices.is_empty();
         let available = shards.iter().filter(|s| s.is_some()).count();
@@ -336,18 +500,6 @@ impl ReconstructionEngine {
         })
     }
 
-    /// Simulate reading a shard from storage
-    async fn simulate_shard_read(&self, _location: &crate::crd::ShardLocation) -> Option<Vec<u8>> {
-        // In a real implementation, this would:
-        // 1. Connect to the storage node
-        // 2. Read the shard data from the specified pool/offset
-        // 3. Verify checksum
-        // 4. Return the data or None if unavailable
-
-        // For now, return simulated data
-        Some(vec![0u8; 256 * 1024]) // 256KB shard
-    }
-
     /// Queue a background rebuild task
     #[instrument(skip(self))]
     pub async fn queue_background_rebuild(
@@ -356,35 +508,226 @@ impl ReconstructionEngine {
         stripe_id: u64,
         missing_shards: Vec<u8>,
     ) -> Result<u64> {
+        self.queue_rebuild(
+            volume_id,
+            stripe_id,
+            missing_shards,
+            ReconstructionType::BackgroundRebuild,
+        )
+        .await
+    }
+
+    /// Queue a rebuild task, ordered by the stripe's remaining redundancy.
+    ///
+    /// If the stripe already has a pending task, the missing shards are
+    /// merged into it and its priority raised if needed.
+    #[instrument(skip(self))]
+    pub async fn queue_rebuild(
+        &self,
+        volume_id: String,
+        stripe_id: u64,
+        missing_shards: Vec<u8>,
+        task_type: ReconstructionType,
+    ) -> Result<u64> {
+        let pending = self
+            .active_tasks
+            .iter()
+            .find(|t| {
+                t.volume_id == volume_id
+                    && t.stripe_id == stripe_id
+                    && t.status == TaskStatus::Pending
+            })
+            .map(|t| t.task_id);
+
+        if let Some(task_id) = pending {
+            // The queue is only locked once the task guard is dropped, as
+            // `pop_task` takes the two in the opposite order
+            let raised = self.active_tasks.get_mut(&task_id).and_then(|mut task| {
+                for index in missing_shards {
+                    if !task.missing_shards.contains(&index) {
+                        task.missing_shards.push(index);
+                    }
+                }
+                task.missing_shards.sort_unstable();
+
+                let risk = self.assess_risk(&volume_id, stripe_id, &task.missing_shards);
+                let priority = task_priority(risk, task_type, self.config.prioritize_reads);
+                if risk > task.risk {
+                    // The task may have started since it was looked up
+                    if task.status == TaskStatus::Pending {
+                        self.count_pending(task.risk, false);
+                        self.count_pending(risk, true);
+                    }
+                    task.risk = risk;
+                }
+                if priority > task.priority {
+                    // The stale lower-priority entry is skipped once this runs
+                    task.priority = priority;
+                    task.task_type = task_type;
+                    return Some(priority);
+                }
+                None
+            });
+            if let Some(priority) = raised {
+                self.queue.lock().push(QueueEntry { priority, task_id });
+                self.queue_notify.notify_one();
+            }
+            return Ok(task_id);
+        }
+
         let task_id = self.next_task_id.fetch_add(1, Ordering::SeqCst);
+        let risk = self.assess_risk(&volume_id, stripe_id, &missing_shards);
+        let priority = task_priority(risk, task_type, self.config.prioritize_reads);
 
         let task = ReconstructionTask {
             task_id,
             volume_id,
             stripe_id,
             missing_shards,
-            task_type: ReconstructionType::BackgroundRebuild,
-            priority: 5, // Medium priority
+            task_type,
+            priority,
+            risk,
             created_at: Utc::now(),
             progress: 0,
             status: TaskStatus::Pending,
         };
 
-        self.task_tx
-            .send(task.clone())
-            .await
-            .map_err(|e| Error::Internal(format!("Failed to queue reconstruction task: {}", e)))?;
-
         self.active_tasks.insert(task_id, task);
+        self.queue.lock().push(QueueEntry { priority, task_id });
+        self.count_pending(risk, true);
+        self.queue_notify.notify_one();
 
         info!(
-            "Queued background rebuild for stripe {} (task {})",
-            stripe_id, task_id
+            "Queued {:?} for stripe {} (task {}, risk {})",
+            task_type,
+            stripe_id,
+            task_id,
+            risk.as_str()
         );
 
         Ok(task_id)
     }
 
+    /// Classify a stripe's risk from its parity count and unhealthy shards
+    fn assess_risk(&self, volume_id: &str, stripe_id: u64, missing_shards: &[u8]) -> RebuildRisk {
+        let stripe = self
+            .metadata_manager
+            .get_volume(volume_id)
+            .and_then(|state| state.read().get_stripe(stripe_id));
+
+        let Some(stripe) = stripe else {
+            return RebuildRisk::from_redundancy(usize::MAX, missing_shards.len());
+        };
+
+        let parity_shards = stripe
+            .shard_locations
+            .iter()
+            .filter(|l| !l.is_data_shard)
+            .count();
+
+        let mut missing: Vec<u8> = missing_shards.to_vec();
+        for health in &stripe.status.shard_health {
+            if health.state != ShardState::Healthy && !missing.contains(&health.shard_index) {
+                missing.push(health.shard_index);
+            }
+        }
+
+        RebuildRisk::from_redundancy(parity_shards, missing.len())
+    }
+
+    /// Take the highest-priority pending task off the queue
+    fn pop_task(&self) -> Option<ReconstructionTask> {
+        let task = {
+            let mut queue = self.queue.lock();
+            loop {
+                let entry = queue.pop()?;
+                let Some(mut task) = self.active_tasks.get_mut(&entry.task_id) else {
+                    continue;
+                };
+                // Cancelled, already started, or superseded by a higher entry
+                if task.status != TaskStatus::Pending || task.priority != entry.priority {
+                    continue;
+                }
+                task.status = TaskStatus::InProgress;
+                break task.clone();
+            }
+        };
+
+        self.count_pending(task.risk, false);
+        Some(task)
+    }
+
+    /// Wait for a rebuild slot, then for the next task to run in it
+    async fn next_task(&self) -> Result<(OwnedSemaphorePermit, ReconstructionTask)> {
+        let permit = Arc::clone(&self.semaphore)
+            .acquire_owned()
+            .await
+            .map_err(|e| {
+                Error::Internal(format!("Failed to acquire reconstruction permit: {}", e))
+            })?;
+
+        loop {
+            let notified = self.queue_notify.notified();
+            if let Some(task) = self.pop_task() {
+                return Ok((permit, task));
+            }
+            notified.await;
+        }
+    }
+
+    /// Count a task entering (or leaving) the pending state at a risk level,
+    /// and publish the new queue depth
+    fn count_pending(&self, risk: RebuildRisk, added: bool) {
+        let counter = &self.pending_by_risk[risk as usize];
+        let depth = if added {
+            counter.fetch_add(1, Ordering::Relaxed) + 1
+        } else {
+            counter.fetch_sub(1, Ordering::Relaxed) - 1
+        };
+        REBUILD_QUEUE_DEPTH
+            .with_label_values(&[risk.as_str()])
+            .set(depth as i64);
+    }
+
+    /// Number of pending tasks at a risk level
+    pub fn queue_depth(&self, risk: RebuildRisk) -> usize {
+        self.pending_by_risk[risk as usize].load(Ordering::Relaxed)
+    }
+
+    /// Change the rebuild I/O rate limit (0 = unlimited)
+    pub fn set_rebuild_rate(&self, bytes_per_sec: u64) {
+        info!("Setting EC rebuild rate to {} bytes/s", bytes_per_sec);
+        self.throttle.set_rate(bytes_per_sec);
+    }
+
+    /// Current rebuild I/O rate limit in bytes per second (0 = unlimited)
+    pub fn rebuild_rate(&self) -> u64 {
+        self.throttle.rate()
+    }
+
+    /// Rebuild throughput observed so far in bytes per second
+    ///
+    /// Counts shard bytes read and written per rebuilt stripe, smoothed over
+    /// recent rebuilds. None until a rebuild has completed.
+    pub fn observed_rebuild_throughput(&self) -> Option<f64> {
+        *self.observed_throughput.lock()
+    }
+
+    /// Fold one completed rebuild into the observed throughput
+    fn record_rebuild(&self, bytes: u64, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        if bytes == 0 || secs <= 0.0 {
+            return;
+        }
+        let sample = bytes as f64 / secs;
+
+        let mut observed = self.observed_throughput.lock();
+        *observed = Some(match *observed {
+            Some(current) => current * 0.8 + sample * 0.2,
+            None => sample,
+        });
+    }
+
     /// Start a stripe reconstruction
     #[instrument(skip(self))]
     pub async fn start_reconstruction(
@@ -425,18 +768,21 @@ impl ReconstructionEngine {
                     }
                 }
 
-                // Process queued tasks
-                task = async {
-                    let mut rx = self.task_rx.write().await;
-                    rx.recv().await
-                } => {
-                    if let Some(task) = task {
-                        let engine = Arc::clone(&self);
-                        tokio::spawn(async move {
-                            if let Err(e) = engine.execute_task(task).await {
-                                error!("Reconstruction task failed: {}", e);
-                            }
-                        });
+                // Dispatch the highest-priority task once a slot is free
+                next = self.next_task() => {
+                    match next {
+                        Ok((permit, task)) => {
+                            let engine = Arc::clone(&self);
+                            tokio::spawn(async move {
+                                if let Err(e) = engine.execute_task(task, permit).await {
+                                    error!("Reconstruction task failed: {}", e);
+                                }
+                            });
+                        }
+                        Err(e) => {
+                            error!("Reconstruction dispatch failed: {}", e);
+                            break;
+                        }
                     }
                 }
             }
@@ -444,22 +790,69 @@ impl ReconstructionEngine {
     }
 
     /// Execute a reconstruction task
-    #[instrument(skip(self))]
-    async fn execute_task(&self, mut task: ReconstructionTask) -> Result<()> {
-        // Acquire semaphore permit
-        let _permit = self.semaphore.acquire().await.map_err(|e| {
-            Error::Internal(format!("Failed to acquire reconstruction permit: {}", e))
-        })?;
-
-        // Update task status
-        task.status = TaskStatus::InProgress;
-        self.active_tasks.insert(task.task_id, task.clone());
-
+    #[instrument(skip(self, _permit))]
+    async fn execute_task(
+        &self,
+        mut task: ReconstructionTask,
+        _permit: OwnedSemaphorePermit,
+    ) -> Result<()> {
         info!(
-            "Starting reconstruction task {} for stripe {}",
-            task.task_id, task.stripe_id
+            "Starting reconstruction task {} for stripe {} (risk {})",
+            task.task_id,
+            task.stripe_id,
+            task.risk.as_str()
         );
 
+        let outcome =
+            match timeout(self.config.stripe_timeout, self.rebuild_stripe(&mut task)).await {
+                Ok(result) => result,
+                Err(_) => Err(Error::EcReconstructionFailed {
+                    stripe_id: task.stripe_id,
+                    reason: format!("timed out after {:?}", self.config.stripe_timeout),
+                }),
+            };
+
+        match outcome {
+            Ok(rebuilt) => {
+                task.status = TaskStatus::Completed;
+                task.progress = 100;
+                self.active_tasks.insert(task.task_id, task.clone());
+
+                info!(
+                    "Completed reconstruction task {} for stripe {} (rebuilt shards {:?})",
+                    task.task_id, task.stripe_id, rebuilt
+                );
+            }
+            Err(e) => {
+                task.status = TaskStatus::Failed;
+                self.active_tasks.insert(task.task_id, task.clone());
+                self.schedule_task_removal(task.task_id);
+                return Err(e);
+            }
+        }
+
+        self.schedule_task_removal(task.task_id);
+        Ok(())
+    }
+
+    /// Remove a finished task after a delay so its outcome stays observable
+    fn schedule_task_removal(&self, task_id: u64) {
+        let tasks = Arc::clone(&self.active_tasks);
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            tasks.remove(&task_id);
+        });
+    }
+
+    /// Rebuild the missing shards of a stripe onto new locations.
+    ///
+    /// Surviving shards are read in parallel, only the missing indices are
+    /// written (each to a freshly allocated location on a healthy pool), and
+    /// the stripe's shard placement is committed under an optimistic
+    /// generation bump. Returns the rebuilt shard indices.
+    async fn rebuild_stripe(&self, task: &mut ReconstructionTask) -> Result<Vec<u8>> {
+        let started = std::time::Instant::now();
+
         // Get volume state
         let volume_state = self
             .metadata_manager
@@ -467,79 +860,220 @@ impl ReconstructionEngine {
             .ok_or_else(|| Error::Internal(format!("Volume {} not found", task.volume_id)))?;
 
         // Extract needed data in a block to ensure guard is dropped before await
-        let (stripe, policy_ref) = {
-            let state = volume_state.read();
-            let stripe = state.get_stripe(task.stripe_id).ok_or_else(|| {
+        let stripe = volume_state
+            .read()
+            .get_stripe(task.stripe_id)
+            .ok_or_else(|| {
                 Error::EcStripeNotFound(format!("Stripe {} not found", task.stripe_id))
             })?;
-            let policy_ref = state.policy_ref.clone();
-            (stripe, policy_ref)
-        };
 
-        // Load policy
-        let policy = self.metadata_manager.load_policy(&policy_ref).await?;
+        // Load the policy the stripe was encoded with
+        let policy = self
+            .metadata_manager
+            .load_policy(&stripe.policy_ref)
+            .await?;
         let decoder = EcDecoder::new(
             policy.spec.data_shards as usize,
             policy.spec.parity_shards as usize,
         )?;
 
-        // Read available shards
-        let mut shards: Vec<Option<Vec<u8>>> = vec![None; decoder.total_shards()];
+        // Read surviving shards; anything else that fails joins the missing set
+        let survivor_bytes: u64 = stripe
+            .shard_locations
+            .iter()
+            .filter(|l| !task.missing_shards.contains(&l.shard_index))
+            .map(|l| l.size_bytes)
+            .sum();
+        self.throttle.consume(survivor_bytes).await;
+        let total = decoder.total_shards();
+        let indices: Vec<usize> = (0..total)
+            .filter(|i| !task.missing_shards.contains(&(*i as u8)))
+            .collect();
+        let mut shards = read_stripe_shards_timeout(
+            self.shard_store.as_ref(),
+            &stripe,
+            total,
+            &indices,
+            self.config.shard_read_timeout,
+        )
+        .await;
+        let missing = unread_indices(&shards);
+        if missing.is_empty() {
+            debug!("Stripe {} has no missing shards", task.stripe_id);
+            return Ok(missing);
+        }
+        task.missing_shards = missing.clone();
 
-        for (i, location) in stripe.shard_locations.iter().enumerate() {
-            if !task.missing_shards.contains(&(i as u8)) {
-                if let Some(data) = self.simulate_shard_read(location).await {
-                    shards[i] = Some(data);
-                }
-            }
+        let previous_status = stripe.status.clone();
+        {
+            let state = volume_state.read();
+            let mut status = stripe.status.clone();
+            status.state = StripeState::Rebuilding;
+            state.update_stripe_status(stripe.stripe_id, status);
         }
 
-        // Reconstruct missing shards
-        decoder.reconstruct(&mut shards)?;
+        let repair = self
+            .repair_shards(task, &stripe, &decoder, &mut shards, &missing)
+            .await;
+        let (new_locations, new_generation, written) = match repair {
+            Ok(repair) => repair,
+            Err(e) => {
+                // Leave the stripe as it was for the next attempt
+                volume_state
+                    .read()
+                    .update_stripe_status(stripe.stripe_id, previous_status);
+                return Err(e);
+            }
+        };
+
+        // Reflect the repair in memory
+        let now = Utc::now();
+        let shard_health = new_locations
+            .iter()
+            .map(|l| ShardHealth {
+                shard_index: l.shard_index,
+                state: ShardState::Healthy,
+                last_verified: Some(now),
+                error: None,
+                failed_pool: None,
+            })
+            .collect();
+        let old_locations: Vec<ShardLocation> = stripe
+            .shard_locations
+            .iter()
+            .filter(|l| missing.contains(&l.shard_index))
+            .cloned()
+            .collect();
+
+        let mut repaired = stripe;
+        repaired.shard_locations = new_locations;
+        repaired.generation = new_generation;
+        repaired.status = StripeStatus {
+            state: StripeState::Healthy,
+            healthy_shards: decoder.total_shards() as u8,
+            shard_health,
+        };
+        volume_state.read().update_stripe(repaired);
+
+        // Old copies may still be reachable (e.g. corrupted shards)
+        discard_shards(self.shard_store.as_ref(), &old_locations).await;
+
+        let written_bytes: u64 = written.iter().map(|l| l.size_bytes).sum();
+        self.record_rebuild(survivor_bytes + written_bytes, started.elapsed());
+
+        Ok(missing)
+    }
+
+    /// Reconstruct the `missing` shards of a stripe, write them out and
+    /// commit the new placement.
+    ///
+    /// Returns the new shard locations, the claimed generation and the
+    /// locations written. On failure, nothing written is left behind.
+    async fn repair_shards(
+        &self,
+        task: &mut ReconstructionTask,
+        stripe: &StripeMetadata,
+        decoder: &EcDecoder,
+        shards: &mut [Option<Vec<u8>>],
+        missing: &[u8],
+    ) -> Result<(Vec<ShardLocation>, u64, Vec<ShardLocation>)> {
+        // Reconstruct only what is needed: data shards alone skip parity math
+        let data_only = missing
+            .iter()
+            .all(|&i| (i as usize) < decoder.data_shards());
+        let reconstructed = if data_only {
+            decoder.reconstruct_data(shards)
+        } else {
+            decoder.reconstruct(shards)
+        };
+        reconstructed.map_err(|e| match e {
+            Error::EcReconstructionFailed { reason, .. } => Error::EcReconstructionFailed {
+                stripe_id: stripe.stripe_id,
+                reason,
+            },
+            other => other,
+        })?;
 
         // Update progress
         task.progress = 50;
         self.active_tasks.insert(task.task_id, task.clone());
 
-        // Write reconstructed shards back to storage
-        for &missing_idx in &task.missing_shards {
-            if let Some(shard_data) = &shards[missing_idx as usize] {
-                // In real implementation, write shard to storage
-                debug!(
-                    "Would write reconstructed shard {} ({} bytes) for stripe {}",
-                    missing_idx,
-                    shard_data.len(),
-                    task.stripe_id
-                );
-            }
+        let mut new_locations = stripe.shard_locations.clone();
+        let mut written: Vec<ShardLocation> = Vec::new();
+        if let Err(e) = self
+            .write_rebuilt_shards(
+                stripe,
+                decoder,
+                shards,
+                missing,
+                &mut new_locations,
+                &mut written,
+            )
+            .await
+        {
+            discard_shards(self.shard_store.as_ref(), &written).await;
+            return Err(e);
         }
+        new_locations.sort_by_key(|l| l.shard_index);
+
+        task.progress = 80;
+        self.active_tasks.insert(task.task_id, task.clone());
 
-        // Update stripe status to healthy
-        let state = volume_state.read();
-        if let Some(mut stripe) = state.get_stripe(task.stripe_id) {
-            stripe.status.state = StripeState::Healthy;
-            stripe.status.healthy_shards = decoder.total_shards() as u8;
-            // In real implementation, update via metadata manager
+        // Claim the next generation before publishing the new placement
+        match commit_shard_placement(self.stripe_repository.as_ref(), stripe, &new_locations, &[])
+            .await
+        {
+            Ok(generation) => Ok((new_locations, generation, written)),
+            Err(e) => {
+                discard_shards(self.shard_store.as_ref(), &written).await;
+                Err(e)
+            }
         }
-        drop(state);
+    }
 
-        // Mark task complete
-        task.status = TaskStatus::Completed;
-        task.progress = 100;
-        self.active_tasks.insert(task.task_id, task.clone());
+    /// Write each rebuilt shard to a new location away from the pools of its
+    /// siblings, recording it in `locations` and `written` as it lands
+    async fn write_rebuilt_shards(
+        &self,
+        stripe: &StripeMetadata,
+        decoder: &EcDecoder,
+        shards: &[Option<Vec<u8>>],
+        missing: &[u8],
+        locations: &mut Vec<ShardLocation>,
+        written: &mut Vec<ShardLocation>,
+    ) -> Result<()> {
+        let mut exclude_pools: Vec<String> = stripe
+            .shard_locations
+            .iter()
+            .map(|l| l.pool_name.clone())
+            .collect();
+
+        for &index in missing {
+            let data =
+                shards[index as usize]
+                    .as_ref()
+                    .ok_or_else(|| Error::EcReconstructionFailed {
+                        stripe_id: stripe.stripe_id,
+                        reason: format!("shard {} was not reconstructed", index),
+                    })?;
+
+            let is_data_shard = (index as usize) < decoder.data_shards();
+            let mut location = self
+                .shard_store
+                .allocate(index, is_data_shard, data.len() as u64, &exclude_pools)
+                .await?;
+            location.checksum = Some(shard_checksum(data));
 
-        info!(
-            "Completed reconstruction task {} for stripe {}",
-            task.task_id, task.stripe_id
-        );
+            self.throttle.consume(data.len() as u64).await;
+            self.shard_store.write_shard(&location, data).await?;
+            exclude_pools.push(location.pool_name.clone());
+            written.push(location.clone());
 
-        // Remove completed task after a delay
-        let task_id = task.task_id;
-        let tasks = self.active_tasks.clone();
-        tokio::spawn(async move {
-            tokio::time::sleep(Duration::from_secs(60)).await;
-            tasks.remove(&task_id);
-        });
+            match locations.iter_mut().find(|l| l.shard_index == index) {
+                Some(slot) => *slot = location,
+                None => locations.push(location),
+            }
+        }
 
         Ok(())
     }
@@ -565,6 +1099,11 @@ impl ReconstructionEngine {
         self.shutdown.store(true, Ordering::Relaxed);
     }
 
+    /// Get configuration
+    pub fn config(&self) -> &ReconstructionConfig {
+        &self.config
+    }
+
     /// Get the number of active tasks
     pub fn active_task_count(&self) -> usize {
         self.active_tasks
@@ -588,6 +1127,9 @@ impl ReconstructionEngine {
         if let Some(mut task) = self.active_tasks.get_mut(&task_id) {
             if task.status == TaskStatus::Pending {
                 task.status = TaskStatus::Cancelled;
+                let risk = task.risk;
+                drop(task);
+                self.count_pending(risk, false);
                 return true;
             }
         }
@@ -602,6 +1144,16 @@ struct ShardReadResult {
     missing_indices: Vec<u8>,
 }
 
+/// Indices of the shard slots that could not be read.
+fn unread_indices(shards: &[Option<Vec<u8>>]) -> Vec<u8> {
+    shards
+        .iter()
+        .enumerate()
+        .filter(|(_, shard)| shard.is_none())
+        .map(|(index, _)| index as u8)
+        .collect()
+}
+
 // =============================================================================
 // Tests
 // =============================================================================
@@ -609,6 +1161,9 @@ struct ShardReadResult {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::domain::ports::{StripeId, VolumeId};
+    use crate::ec::shard_store::verify_shard;
+    use crate::ec::test_support::{self, EcFixture};
 
     // =========================================================================
     // Configuration Tests
@@ -623,6 +1178,7 @@ mod tests {
         assert_eq!(config.stripe_timeout, Duration::from_secs(300));
         assert_eq!(config.rebuild_check_interval, Duration::from_secs(60));
         assert!(config.prioritize_reads);
+        assert_eq!(config.rebuild_rate_bytes_per_sec, 0);
     }
 
     // =========================================================================
@@ -638,6 +1194,7 @@ mod tests {
             missing_shards: vec![2, 4],
             task_type: ReconstructionType::BackgroundRebuild,
             priority: 5,
+            risk: RebuildRisk::Elevated,
             created_at: Utc::now(),
             progress: 0,
             status: TaskStatus::Pending,
@@ -681,6 +1238,63 @@ mod tests {
         assert_ne!(TaskStatus::Completed, TaskStatus::Failed);
     }
 
+    // =========================================================================
+    // Rebuild Scheduling Tests
+    // =========================================================================
+
+    #[test]
+    fn test_rebuild_risk_from_redundancy() {
+        assert_eq!(RebuildRisk::from_redundancy(2, 0), RebuildRisk::Low);
+        assert_eq!(RebuildRisk::from_redundancy(2, 1), RebuildRisk::Elevated);
+        assert_eq!(RebuildRisk::from_redundancy(2, 2), RebuildRisk::Critical);
+        assert_eq!(RebuildRisk::from_redundancy(1, 3), RebuildRisk::Critical);
+        assert!(RebuildRisk::Critical > RebuildRisk::Elevated);
+    }
+
+    #[test]
+    fn test_task_priority() {
+        let read = ReconstructionType::DegradedRead;
+        let background = ReconstructionType::BackgroundRebuild;
+
+        // Reads win within a risk level only when prioritized
+        assert!(
+            task_priority(RebuildRisk::Low, read, true)
+                > task_priority(RebuildRisk::Low, background, true)
+        );
+        assert_eq!(
+            task_priority(RebuildRisk::Low, read, false),
+            task_priority(RebuildRisk::Low, background, false)
+        );
+        // Client reads beat any background rebuild, then risk decides
+        assert!(
+            task_priority(RebuildRisk::Low, read, true)
+                > task_priority(RebuildRisk::Critical, background, true)
+        );
+        assert!(
+            task_priority(RebuildRisk::Critical, read, true)
+                > task_priority(RebuildRisk::Elevated, read, true)
+        );
+        assert!(
+            task_priority(RebuildRisk::Critical, background, false)
+                > task_priority(RebuildRisk::Elevated, read, false)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rebuild_throttle_paces_transfers() {
+        let throttle = RebuildThrottle::new(0);
+        let start = Instant::now();
+        throttle.consume(u64::MAX).await;
+        assert!(start.elapsed() < Duration::from_millis(50));
+
+        throttle.set_rate(1_000_000);
+        assert_eq!(throttle.rate(), 1_000_000);
+        let start = Instant::now();
+        throttle.consume(50_000).await;
+        throttle.consume(50_000).await;
+        assert!(start.elapsed() >= Duration::from_millis(45));
+    }
+
     // =========================================================================
     // ReadRequest Tests
     // =========================================================================
@@ -715,4 +1329,317 @@ mod tests {
         assert!(result.degraded);
         assert_eq!(result.reconstructed_stripes, vec![1, 2, 3]);
     }
+
+    // =========================================================================
+    // Rebuild Tests
+    // =========================================================================
+
+    const VOLUME: &str = "vol-1";
+    const POLICY: &str = "ec-4-2";
+
+    struct Fixture {
+        engine: Arc<ReconstructionEngine>,
+        ec: EcFixture,
+        stripe: StripeMetadata,
+        data: Vec<u8>,
+    }
+
+    async fn fixture() -> Fixture {
+        // Two spare pools beyond the six shards leave room to relocate
+        let ec = EcFixture::new(8, 4, [test_support::policy(POLICY, 4, 2, 4096)]);
+        let data: Vec<u8> = (0..4096u32).map(|i| (i % 251) as u8).collect();
+        let stripe = ec.destage(VOLUME, POLICY, 0, &data).await;
+
+        let engine = ReconstructionEngine::new(
+            ReconstructionConfig::default(),
+            ec.metadata_manager.clone(),
+            ec.store.clone(),
+            ec.repository.clone(),
+        );
+
+        Fixture {
+            engine,
+            ec,
+            stripe,
+            data,
+        }
+    }
+
+    fn rebuild_task(missing_shards: Vec<u8>) -> ReconstructionTask {
+        ReconstructionTask {
+            task_id: 0,
+            volume_id: VOLUME.to_string(),
+            stripe_id: 0,
+            missing_shards,
+            task_type: ReconstructionType::BackgroundRebuild,
+            priority: 0,
+            risk: RebuildRisk::Low,
+            created_at: Utc::now(),
+            progress: 0,
+            status: TaskStatus::InProgress,
+        }
+    }
+
+    /// Add a copy of the fixture stripe under another ID
+    fn add_stripe_copy(f: &Fixture, stripe_id: u64) {
+        let mut stripe = f.stripe.clone();
+        stripe.stripe_id = stripe_id;
+        f.engine
+            .metadata_manager
+            .get_volume(VOLUME)
+            .unwrap()
+            .write()
+            .add_stripe(stripe);
+    }
+
+    #[tokio::test]
+    async fn test_queue_orders_by_risk() {
+        let f = fixture().await;
+        add_stripe_copy(&f, 1);
+
+        let low = f
+            .engine
+            .queue_background_rebuild(VOLUME.to_string(), 99, vec![])
+            .await
+            .unwrap();
+        let elevated = f
+            .engine
+            .queue_background_rebuild(VOLUME.to_string(), 0, vec![3])
+            .await
+            .unwrap();
+        let critical = f
+            .engine
+            .queue_background_rebuild(VOLUME.to_string(), 1, vec![0, 5])
+            .await
+            .unwrap();
+
+        for risk in RebuildRisk::ALL {
+            assert_eq!(f.engine.queue_depth(risk), 1);
+        }
+
+        let order: Vec<u64> = std::iter::from_fn(|| f.engine.pop_task())
+            .map(|t| t.task_id)
+            .collect();
+        assert_eq!(order, vec![critical, elevated, low]);
+        for risk in RebuildRisk::ALL {
+            assert_eq!(f.engine.queue_depth(risk), 0);
+        }
+
+        let task = f
+            .engine
+            .queue_background_rebuild(VOLUME.to_string(), 0, vec![3])
+            .await
+            .unwrap();
+        assert_eq!(f.engine.queue_depth(RebuildRisk::Elevated), 1);
+        assert!(f.engine.cancel_task(task));
+        assert_eq!(f.engine.queue_depth(RebuildRisk::Elevated), 0);
+    }
+
+    #[tokio::test]
+    async fn test_read_triggered_repairs_go_first() {
+        let f = fixture().await;
+        add_stripe_copy(&f, 1);
+
+        // A critical background rebuild still waits behind the client read
+        f.engine
+            .queue_background_rebuild(VOLUME.to_string(), 0, vec![2, 3])
+            .await
+            .unwrap();
+        let read = f
+            .engine
+            .queue_rebuild(
+                VOLUME.to_string(),
+                1,
+                vec![2],
+                ReconstructionType::DegradedRead,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(f.engine.pop_task().unwrap().task_id, read);
+    }
+
+    #[tokio::test]
+    async fn test_requeue_merges_and_escalates() {
+        let f = fixture().await;
+
+        let first = f
+            .engine
+            .queue_background_rebuild(VOLUME.to_string(), 0, vec![1])
+            .await
+            .unwrap();
+        assert_eq!(f.engine.queue_depth(RebuildRisk::Elevated), 1);
+
+        // A second failure on the same stripe escalates the pending task
+        let second = f
+            .engine
+            .queue_background_rebuild(VOLUME.to_string(), 0, vec![4])
+            .await
+            .unwrap();
+        assert_eq!(first, second);
+        assert_eq!(f.engine.queue_depth(RebuildRisk::Elevated), 0);
+        assert_eq!(f.engine.queue_depth(RebuildRisk::Critical), 1);
+
+        let task = f.engine.pop_task().unwrap();
+        assert_eq!(task.missing_shards, vec![1, 4]);
+        assert_eq!(task.risk, RebuildRisk::Critical);
+        // The superseded entry is skipped
+        assert!(f.engine.pop_task().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rebuild_relocates_shard_from_offline_pool() {
+        let f = fixture().await;
+        let lost = f.stripe.shard_locations[1].clone();
+        f.ec.store.set_pool_online(&lost.pool_name, false);
+        assert!(f.engine.observed_rebuild_throughput().is_none());
+
+        let rebuilt = f
+            .engine
+            .rebuild_stripe(&mut rebuild_task(vec![]))
+            .await
+            .unwrap();
+        assert_eq!(rebuilt, vec![1]);
+
+        // The rebuilt shard lives on a pool none of its siblings use
+        let volume = f.engine.metadata_manager.get_volume(VOLUME).unwrap();
+        let repaired = volume.read().get_stripe(0).unwrap();
+        let moved = &repaired.shard_locations[1];
+        assert!(f
+            .stripe
+            .shard_locations
+            .iter()
+            .all(|l| l.pool_name != moved.pool_name));
+        let bytes = f.ec.store.read_shard(moved).await.unwrap();
+        assert!(verify_shard(moved, &bytes));
+
+        assert_eq!(repaired.generation, 1);
+        assert_eq!(repaired.status.state, StripeState::Healthy);
+        assert_eq!(repaired.status.shard_health.len(), 6);
+
+        let stored =
+            f.ec.repository
+                .get(&VolumeId::new(VOLUME), &StripeId::new(0))
+                .unwrap();
+        assert_eq!(stored.generation, 1);
+        assert_eq!(stored.shard_locations[1].device_id, moved.pool_name);
+
+        assert!(f.engine.observed_rebuild_throughput().unwrap() > 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_degraded_read_returns_exact_range() {
+        let f = fixture().await;
+        f.ec.store
+            .set_pool_online(&f.stripe.shard_locations[1].pool_name, false);
+
+        // Blocks past the stripe read as zeros
+        let result = f
+            .engine
+            .handle_degraded_read(ReadRequest {
+                volume_id: VOLUME.to_string(),
+                lba_range: LbaRange::new(6, 10),
+                allow_degraded: true,
+            })
+            .await
+            .unwrap();
+        assert_eq!(result.data.len(), 4 * 512);
+        assert_eq!(&result.data[..1024], &f.data[3072..]);
+        assert!(result.data[1024..].iter().all(|&b| b == 0));
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_shard_detected_and_replaced() {
+        let f = fixture().await;
+        f.ec.store.corrupt(&f.stripe.shard_locations[0]);
+
+        // Degraded read still returns the original data
+        let result = f
+            .engine
+            .handle_degraded_read(ReadRequest {
+                volume_id: VOLUME.to_string(),
+                lba_range: LbaRange::new(0, 8),
+                allow_degraded: true,
+            })
+            .await
+            .unwrap();
+        assert!(result.degraded);
+        assert_eq!(&result.data[..f.data.len()], &f.data[..]);
+
+        let shard_count = f.ec.store.shard_count();
+        let rebuilt = f
+            .engine
+            .rebuild_stripe(&mut rebuild_task(vec![]))
+            .await
+            .unwrap();
+        assert_eq!(rebuilt, vec![0]);
+        // The corrupted copy is removed once the new one is committed
+        assert_eq!(f.ec.store.shard_count(), shard_count);
+        assert!(f
+            .ec
+            .store
+            .read_shard(&f.stripe.shard_locations[0])
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rebuild_aborts_on_generation_conflict() {
+        let f = fixture().await;
+        f.ec.store
+            .set_pool_online(&f.stripe.shard_locations[5].pool_name, false);
+
+        // Another writer claims generation 1 first
+        assert!(f
+            .ec
+            .repository
+            .update_generation(&VolumeId::new(VOLUME), &StripeId::new(0), 1)
+            .await
+            .unwrap());
+
+        let shard_count = f.ec.store.shard_count();
+        let err = f
+            .engine
+            .rebuild_stripe(&mut rebuild_task(vec![5]))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::EcGenerationConflict {
+                stripe_id: 0,
+                generation: 1
+            }
+        ));
+        // Shards written for the losing rebuild are cleaned up
+        assert_eq!(f.ec.store.shard_count(), shard_count);
+        assert_eq!(stripe_state(&f), StripeState::Healthy);
+    }
+
+    #[tokio::test]
+    async fn test_failed_rebuild_restores_stripe_state() {
+        let f = fixture().await;
+        for location in &f.stripe.shard_locations[3..] {
+            f.ec.store.set_pool_online(&location.pool_name, false);
+        }
+
+        let err = f
+            .engine
+            .rebuild_stripe(&mut rebuild_task(vec![3, 4, 5]))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InsufficientShards { .. }), "{:?}", err);
+        assert_eq!(stripe_state(&f), StripeState::Healthy);
+    }
+
+    fn stripe_state(f: &Fixture) -> StripeState {
+        f.engine
+            .metadata_manager
+            .get_volume(VOLUME)
+            .unwrap()
+            .read()
+            .get_stripe(0)
+            .unwrap()
+            .status
+            .state
+    }
 }
@@ -0,0 +1,220 @@
+//! Checksummed Record Logs
+//!
+//! Append-only logs of serde records, one `<crc32 hex> <json>` line each,
+//! shared by the stripe log and the dedup index. Opening a log replays it up
+//! to the first record that fails its checksum and truncates the rest, which
+//! is what a crash mid-append leaves behind. Compaction rewrites the log
+//! through a temporary file that is renamed over it.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use tracing::{error, warn};
+
+use crate::error::{Error, Result};
+
+/// Encode a record as a checksummed log line
+pub(crate) fn encode_record<R: Serialize>(record: &R) -> Result<String> {
+    let json = serde_json::to_string(record)
+        .map_err(|e| Error::Internal(format!("Failed to encode log record: {}", e)))?;
+    Ok(format!(
+        "{:08x} {}\n",
+        crc32fast::hash(json.as_bytes()),
+        json
+    ))
+}
+
+/// Decode a log line, returning None if it is torn or corrupt
+fn decode_record<R: DeserializeOwned>(line: &str) -> Option<R> {
+    let (crc, json) = line.split_once(' ')?;
+    let crc = u32::from_str_radix(crc, 16).ok()?;
+    if crc32fast::hash(json.as_bytes()) != crc {
+        return None;
+    }
+    serde_json::from_str(json).ok()
+}
+
+/// An append-only log file of checksummed records
+#[derive(Debug)]
+pub(crate) struct RecordLog {
+    path: PathBuf,
+    file: File,
+    sync_writes: bool,
+
+    /// Records in the log file, live or superseded
+    records: u64,
+
+    /// Bytes of whole records in the log file
+    len: u64,
+
+    /// Set when a failed append could not be undone
+    broken: bool,
+}
+
+impl RecordLog {
+    /// Open (or create) the log at `path`, passing each intact record to
+    /// `apply` in order
+    ///
+    /// Appends are fsynced unless `sync_writes` is false.
+    pub(crate) fn open<R: DeserializeOwned>(
+        path: &Path,
+        sync_writes: bool,
+        mut apply: impl FnMut(R),
+    ) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let mut records = 0u64;
+
+        // Replay up to the first record that fails its checksum
+        let mut valid_len = 0u64;
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let read = match reader.read_line(&mut line) {
+                Ok(0) => break,
+                Ok(read) => read,
+                // Invalid UTF-8 is a torn write as well
+                Err(e) if e.kind() == std::io::ErrorKind::InvalidData => break,
+                Err(e) => return Err(e.into()),
+            };
+            let Some(record) = line.strip_suffix('\n').and_then(decode_record) else {
+                break;
+            };
+            apply(record);
+            records += 1;
+            valid_len += read as u64;
+        }
+
+        let file_len = file.metadata()?.len();
+        if valid_len < file_len {
+            warn!(
+                "Truncating {} bytes of torn or corrupt log at {}",
+                file_len - valid_len,
+                path.display()
+            );
+            file.set_len(valid_len)?;
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            sync_writes,
+            records,
+            len: valid_len,
+            broken: false,
+        })
+    }
+
+    /// Path of the log file
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Number of records in the log, including superseded ones
+    pub(crate) fn records(&self) -> u64 {
+        self.records
+    }
+
+    /// Append records as one write
+    ///
+    /// A failed write may leave part of a record behind, which would hide
+    /// every later record from replay; it is cut off again, and if that
+    /// fails too the log takes no more appends until it is rewritten.
+    pub(crate) fn append<R: Serialize>(&mut self, records: &[R]) -> Result<()> {
+        if self.broken {
+            return Err(Error::Internal(format!(
+                "Log {} is unusable after a failed write",
+                self.path.display()
+            )));
+        }
+        let mut lines = String::new();
+        for record in records {
+            lines.push_str(&encode_record(record)?);
+        }
+
+        let written = self.file.write_all(lines.as_bytes()).and_then(|()| {
+            if self.sync_writes {
+                self.file.sync_data()
+            } else {
+                Ok(())
+            }
+        });
+        if let Err(e) = written {
+            if let Err(truncate) = self.file.set_len(self.len) {
+                error!(
+                    "Failed to cut a torn record off log {}: {}",
+                    self.path.display(),
+                    truncate
+                );
+                self.broken = true;
+            }
+            return Err(e.into());
+        }
+        self.len += lines.len() as u64;
+        self.records += records.len() as u64;
+        Ok(())
+    }
+
+    /// Replace the log with `records`
+    pub(crate) fn rewrite<R: Serialize>(
+        &mut self,
+        records: impl IntoIterator<Item = R>,
+    ) -> Result<()> {
+        let tmp = self.path.with_extension("log.tmp");
+        let mut written = 0u64;
+        let mut len = 0u64;
+        {
+            let mut out = std::io::BufWriter::new(File::create(&tmp)?);
+            for record in records {
+                let line = encode_record(&record)?;
+                out.write_all(line.as_bytes())?;
+                written += 1;
+                len += line.len() as u64;
+            }
+            out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        }
+
+        std::fs::rename(&tmp, &self.path)?;
+        if let Some(dir) = self.path.parent() {
+            File::open(dir)?.sync_all()?;
+        }
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        self.records = written;
+        self.len = len;
+        self.broken = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replay(path: &Path) -> Vec<u64> {
+        let mut values = Vec::new();
+        RecordLog::open(path, true, |value: u64| values.push(value)).unwrap();
+        values
+    }
+
+    #[test]
+    fn test_failed_append_is_not_replayed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.log");
+        let mut log = RecordLog::open(&path, true, |_: u64| {}).unwrap();
+        log.append(&[1u64, 2]).unwrap();
+
+        // A read-only handle fails every write and cannot be truncated
+        log.file = File::open(&path).unwrap();
+        assert!(log.append(&[3u64]).is_err());
+        assert!(log.broken);
+        assert!(log.append(&[4u64]).is_err());
+        assert_eq!(log.records(), 2);
+
+        // Rewriting makes the log usable again
+        log.rewrite([1u64, 2]).unwrap();
+        log.append(&[5u64]).unwrap();
+        assert_eq!(replay(&path), vec![1, 2, 5]);
+    }
+}
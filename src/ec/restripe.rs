@@ -0,0 +1,805 @@
+//! Stripe Re-striping - Online EC Profile Changes
+//!
+//! Re-encodes the stripes of a volume onto a different `ErasureCodingPolicy`
+//! (shard counts or stripe size) while the volume stays readable. Every
+//! stripe is re-encoded to fresh shard locations and then flipped to the new
+//! policy in a single commit; until the flip, reads use the old shards.
+//!
+//! Stripes that fit in the new stripe size keep their ID and LBA range.
+//! Larger stripes are split: their live data is published as new stripes
+//! that shadow the original, which is then retired.
+
+use crate::crd::{ErasureCodingPolicy, LbaRange, StripeState};
+use crate::domain::ports::{StripeRepository, VolumeId};
+use crate::ec::compaction::{
+    is_settled, pack_segments, retire_stripe, slice_lbas, LiveSegment, SharedVolumeState,
+};
+use crate::ec::encoder::{EcDecoder, EcEncoder};
+use crate::ec::metadata::{
+    commit_restripe, to_domain_stripe, EcMetadataManager, StripeMetadata, StripeStatus,
+};
+use crate::ec::shard_store::{discard_shards, place_shards, read_stripe_data, ShardStore};
+use crate::ec::stripe_manager::LBA_SIZE;
+use crate::error::{Error, Result};
+use chrono::{DateTime, Utc};
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::{debug, info, instrument, warn};
+
+// =============================================================================
+// Progress Tracking
+// =============================================================================
+
+/// Re-striping state of a single stripe
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StripeRestripeState {
+    /// Still encoded with the old policy
+    Pending,
+    /// Flipped to the new policy (or split into new stripes)
+    Restriped,
+    /// Re-encoding failed; the stripe keeps its old policy
+    Failed(String),
+}
+
+/// Progress of re-striping one volume
+#[derive(Debug, Clone)]
+pub struct RestripeProgress {
+    /// Volume ID
+    pub volume_id: String,
+
+    /// Policy new writes used before the change
+    pub from_policy: String,
+
+    /// Policy the stripes are moved to
+    pub to_policy: String,
+
+    /// Per-stripe state, keyed by the stripe ID at the start of the run
+    pub stripes: BTreeMap<u64, StripeRestripeState>,
+
+    /// Stripes created by splitting stripes larger than the new stripe size
+    pub stripes_created: Vec<u64>,
+
+    /// Data bytes re-encoded so far
+    pub bytes_restriped: u64,
+
+    /// When re-striping started
+    pub started_at: DateTime<Utc>,
+
+    /// When re-striping finished (None while running)
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl RestripeProgress {
+    /// Number of stripes to re-stripe
+    pub fn total(&self) -> usize {
+        self.stripes.len()
+    }
+
+    /// Number of stripes in a given state
+    fn count(&self, matches: impl Fn(&StripeRestripeState) -> bool) -> usize {
+        self.stripes.values().filter(|s| matches(s)).count()
+    }
+
+    /// Number of stripes already on the new policy
+    pub fn restriped(&self) -> usize {
+        self.count(|s| *s == StripeRestripeState::Restriped)
+    }
+
+    /// Number of stripes that failed
+    pub fn failed(&self) -> usize {
+        self.count(|s| matches!(s, StripeRestripeState::Failed(_)))
+    }
+
+    /// Completion percentage (0-100)
+    pub fn percent(&self) -> u8 {
+        if self.stripes.is_empty() {
+            return 100;
+        }
+        (self.restriped() * 100 / self.total()) as u8
+    }
+
+    /// Check if every stripe was moved to the new policy
+    pub fn is_complete(&self) -> bool {
+        self.finished_at.is_some() && self.restriped() == self.total()
+    }
+}
+
+// =============================================================================
+// Stripe Restriper
+// =============================================================================
+
+/// Moves EC volumes between erasure coding policies online
+pub struct StripeRestriper {
+    /// Metadata manager
+    metadata_manager: Arc<EcMetadataManager>,
+
+    /// Shard I/O backend
+    shard_store: Arc<dyn ShardStore>,
+
+    /// Persistent stripe metadata
+    stripe_repository: Arc<dyn StripeRepository>,
+
+    /// Progress of current and finished runs by volume
+    progress: DashMap<String, RestripeProgress>,
+}
+
+impl StripeRestriper {
+    /// Create a new restriper
+    pub fn new(
+        metadata_manager: Arc<EcMetadataManager>,
+        shard_store: Arc<dyn ShardStore>,
+        stripe_repository: Arc<dyn StripeRepository>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            metadata_manager,
+            shard_store,
+            stripe_repository,
+            progress: DashMap::new(),
+        })
+    }
+
+    /// Get the progress of the latest run for a volume
+    pub fn progress(&self, volume_id: &str) -> Option<RestripeProgress> {
+        self.progress.get(volume_id).map(|p| p.clone())
+    }
+
+    /// Check if a volume is being re-striped
+    pub fn is_restriping(&self, volume_id: &str) -> bool {
+        self.progress
+            .get(volume_id)
+            .is_some_and(|p| p.finished_at.is_none())
+    }
+
+    /// Get the policy new writes to a volume are encoded with
+    pub fn volume_policy(&self, volume_id: &str) -> Option<String> {
+        self.metadata_manager
+            .get_volume(volume_id)
+            .map(|v| v.read().policy_ref.clone())
+    }
+
+    /// List the stripes of a volume not yet encoded with `policy_name`
+    pub fn pending_stripes(&self, volume_id: &str, policy_name: &str) -> Result<Vec<u64>> {
+        let volume_state = self.volume_state(volume_id)?;
+        let mut pending: Vec<u64> = volume_state
+            .read()
+            .all_stripes()
+            .into_iter()
+            .filter(|s| s.policy_ref != policy_name)
+            .map(|s| s.stripe_id)
+            .collect();
+        pending.sort_unstable();
+        Ok(pending)
+    }
+
+    /// Re-encode all stripes of a volume onto `policy_name`
+    ///
+    /// New writes switch to the new policy immediately. Stripes are then
+    /// moved one at a time, so the volume stays readable throughout; a
+    /// stripe that fails keeps its old policy and is retried by the next run.
+    #[instrument(skip(self))]
+    pub async fn restripe_volume(
+        &self,
+        volume_id: &str,
+        policy_name: &str,
+    ) -> Result<RestripeProgress> {
+        let volume_state = self.volume_state(volume_id)?;
+        let policy = self.metadata_manager.load_policy(policy_name).await?;
+        let encoder = EcEncoder::new(
+            policy.spec.data_shards as usize,
+            policy.spec.parity_shards as usize,
+        )?;
+
+        // Claim the volume; a finished run's progress is replaced
+        let from_policy = volume_state.read().policy_ref.clone();
+        let claim = RestripeProgress {
+            volume_id: volume_id.to_string(),
+            from_policy: from_policy.clone(),
+            to_policy: policy_name.to_string(),
+            stripes: BTreeMap::new(),
+            stripes_created: vec![],
+            bytes_restriped: 0,
+            started_at: Utc::now(),
+            finished_at: None,
+        };
+        match self.progress.entry(volume_id.to_string()) {
+            Entry::Occupied(entry) if entry.get().finished_at.is_none() => {
+                return Err(Error::MigrationInProgress {
+                    volume_name: volume_id.to_string(),
+                });
+            }
+            Entry::Occupied(mut entry) => {
+                entry.insert(claim);
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(claim);
+            }
+        }
+
+        // Route new writes to the new policy before moving existing stripes,
+        // recording the switch first so it survives a restart
+        if let Err(e) = self
+            .stripe_repository
+            .save_volume_policy(&VolumeId::new(volume_id), policy_name)
+            .await
+        {
+            self.progress.remove(volume_id);
+            return Err(e);
+        }
+        volume_state.write().policy_ref = policy_name.to_string();
+        let pending = self.pending_stripes(volume_id, policy_name)?;
+
+        info!(
+            "Re-striping {} stripes of volume {} from {} to {}",
+            pending.len(),
+            volume_id,
+            from_policy,
+            policy_name
+        );
+
+        if let Some(mut progress) = self.progress.get_mut(volume_id) {
+            progress.stripes = pending
+                .iter()
+                .map(|id| (*id, StripeRestripeState::Pending))
+                .collect();
+        }
+
+        for stripe_id in pending {
+            let stripe = volume_state.read().get_stripe(stripe_id);
+            let outcome = match stripe {
+                // Retired in the meantime (e.g. garbage-collected)
+                None => Ok((vec![], 0)),
+                Some(stripe) => {
+                    self.restripe_stripe(&volume_state, &stripe, &policy, &encoder)
+                        .await
+                }
+            };
+
+            if let Some(mut progress) = self.progress.get_mut(volume_id) {
+                let state = match outcome {
+                    Ok((created, bytes)) => {
+                        progress.stripes_created.extend(created);
+                        progress.bytes_restriped += bytes;
+                        StripeRestripeState::Restriped
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Failed to re-stripe stripe {} of volume {}: {}",
+                            stripe_id, volume_id, e
+                        );
+                        StripeRestripeState::Failed(e.to_string())
+                    }
+                };
+                progress.stripes.insert(stripe_id, state);
+            }
+        }
+
+        let mut progress = self
+            .progress
+            .get_mut(volume_id)
+            .ok_or_else(|| Error::Internal(format!("Progress of {} lost", volume_id)))?;
+        progress.finished_at = Some(Utc::now());
+
+        info!(
+            "Re-striped volume {}: {}/{} stripes on {}, {} failed",
+            volume_id,
+            progress.restriped(),
+            progress.total(),
+            policy_name,
+            progress.failed()
+        );
+
+        Ok(progress.clone())
+    }
+
+    /// Re-encode one stripe onto the new policy
+    ///
+    /// Returns the IDs of any stripes created by a split and the number of
+    /// data bytes re-encoded.
+    async fn restripe_stripe(
+        &self,
+        volume_state: &SharedVolumeState,
+        stripe: &StripeMetadata,
+        policy: &ErasureCodingPolicy,
+        encoder: &EcEncoder,
+    ) -> Result<(Vec<u64>, u64)> {
+        let update_failed = |reason: &str| Error::EcStripeUpdateFailed {
+            stripe_id: stripe.stripe_id,
+            reason: reason.to_string(),
+        };
+        if !is_settled(stripe) {
+            return Err(update_failed("stripe is being written or rebuilt"));
+        }
+
+        // Anything written after this point has a higher stripe ID
+        let newest_seen = volume_state
+            .read()
+            .all_stripes()
+            .iter()
+            .map(|s| s.stripe_id)
+            .max()
+            .unwrap_or(stripe.stripe_id);
+
+        let old_policy = self
+            .metadata_manager
+            .load_policy(&stripe.policy_ref)
+            .await?;
+        let decoder = EcDecoder::new(
+            old_policy.spec.data_shards as usize,
+            old_policy.spec.parity_shards as usize,
+        )?;
+        let data = read_stripe_data(self.shard_store.as_ref(), stripe, &decoder).await?;
+
+        let lbas_per_stripe = (policy.spec.stripe_size_bytes / LBA_SIZE).max(1);
+        if stripe.lba_range.size() <= lbas_per_stripe {
+            let bytes = slice_lbas(&data, stripe.lba_range.start_lba, &stripe.lba_range);
+            self.flip_stripe(volume_state, stripe, policy, encoder, &bytes)
+                .await?;
+            return Ok((vec![], bytes.len() as u64));
+        }
+
+        // Only the parts no newer stripe shadows need to move
+        let live: Vec<LbaRange> = volume_state
+            .read()
+            .live_ranges()
+            .into_iter()
+            .find(|(s, _)| s.stripe_id == stripe.stripe_id)
+            .map(|(_, live)| live)
+            .unwrap_or_default();
+        let segments: Vec<LiveSegment> = live
+            .iter()
+            .map(|range| LiveSegment {
+                range: range.clone(),
+                data: slice_lbas(&data, stripe.lba_range.start_lba, range),
+            })
+            .collect();
+        let bytes: u64 = segments.iter().map(|s| s.data.len() as u64).sum();
+
+        let created = self
+            .split_stripe(
+                volume_state,
+                stripe,
+                policy,
+                encoder,
+                pack_segments(segments, lbas_per_stripe),
+                newest_seen,
+            )
+            .await?;
+        Ok((created, bytes))
+    }
+
+    /// Re-encode a stripe in place, keeping its ID and LBA range
+    async fn flip_stripe(
+        &self,
+        volume_state: &SharedVolumeState,
+        stripe: &StripeMetadata,
+        policy: &ErasureCodingPolicy,
+        encoder: &EcEncoder,
+        data: &[u8],
+    ) -> Result<()> {
+        let shards = encoder.encode(data)?;
+        let shard_locations = place_shards(
+            self.shard_store.as_ref(),
+            &shards,
+            policy.spec.data_shards as usize,
+        )
+        .await?;
+
+        let mut restriped = StripeMetadata {
+            policy_ref: policy.name().to_string(),
+            shard_locations: shard_locations.clone(),
+            status: StripeStatus {
+                state: StripeState::Healthy,
+                healthy_shards: shards.len() as u8,
+                shard_health: vec![],
+            },
+            checksum: None,
+            ..stripe.clone()
+        };
+
+        // The generation claim orders this flip against concurrent updates
+        restriped.generation =
+            match commit_restripe(self.stripe_repository.as_ref(), stripe, &restriped).await {
+                Ok(generation) => generation,
+                Err(e) => {
+                    discard_shards(self.shard_store.as_ref(), &shard_locations).await;
+                    return Err(e);
+                }
+            };
+        volume_state.read().update_stripe(restriped);
+
+        // Reads that looked up the old placement may still be using it
+        let readers = volume_state.read().readers();
+        readers.wait_for_readers().await;
+        discard_shards(self.shard_store.as_ref(), &stripe.shard_locations).await;
+
+        debug!(
+            "Re-striped stripe {} of volume {} onto {}",
+            stripe.stripe_id,
+            stripe.volume_id,
+            policy.name()
+        );
+
+        Ok(())
+    }
+
+    /// Replace a stripe with new stripes holding its live data
+    ///
+    /// All replacements are published under one lock, so readers switch
+    /// from the old stripe to its replacements at once.
+    async fn split_stripe(
+        &self,
+        volume_state: &SharedVolumeState,
+        stripe: &StripeMetadata,
+        policy: &ErasureCodingPolicy,
+        encoder: &EcEncoder,
+        chunks: Vec<LiveSegment>,
+        newest_seen: u64,
+    ) -> Result<Vec<u64>> {
+        let store = self.shard_store.as_ref();
+
+        let mut placed = Vec::with_capacity(chunks.len());
+        for chunk in &chunks {
+            let shards = encoder.encode(&chunk.data)?;
+            match place_shards(store, &shards, policy.spec.data_shards as usize).await {
+                Ok(locations) => placed.push((chunk.range.clone(), locations)),
+                Err(e) => {
+                    for (_, locations) in &placed {
+                        discard_shards(store, locations).await;
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        // Publish only if the source is unchanged and nothing newer overlaps
+        // it, counting destages whose stripe IDs are reserved but unpublished
+        let replacements = {
+            let mut state = volume_state.write();
+            let unchanged = state
+                .get_stripe(stripe.stripe_id)
+                .is_some_and(|current| current.generation == stripe.generation);
+
+            if !unchanged || state.has_newer_overlap(&stripe.lba_range, newest_seen) {
+                None
+            } else {
+                let total_shards = encoder.total_shards() as u8;
+                let replacements: Vec<StripeMetadata> = placed
+                    .iter()
+                    .map(|(range, locations)| {
+                        let metadata = StripeMetadata {
+                            stripe_id: state.next_stripe_id(),
+                            volume_id: stripe.volume_id.clone(),
+                            policy_ref: policy.name().to_string(),
+                            lba_range: range.clone(),
+                            shard_locations: locations.clone(),
+                            status: StripeStatus {
+                                state: StripeState::Healthy,
+                                healthy_shards: total_shards,
+                                shard_health: vec![],
+                            },
+                            generation: 0,
+                            checksum: None,
+                        };
+                        state.add_stripe(metadata.clone());
+                        metadata
+                    })
+                    .collect();
+                Some(replacements)
+            }
+        };
+
+        let Some(replacements) = replacements else {
+            for (_, locations) in &placed {
+                discard_shards(store, locations).await;
+            }
+            return Err(Error::EcStripeUpdateFailed {
+                stripe_id: stripe.stripe_id,
+                reason: "stripe was rewritten during re-striping".to_string(),
+            });
+        };
+
+        for metadata in &replacements {
+            if let Err(e) = self
+                .stripe_repository
+                .save(&to_domain_stripe(metadata))
+                .await
+            {
+                // Back out every replacement; the original stays authoritative
+                for metadata in &replacements {
+                    if let Err(e) = retire_stripe(
+                        store,
+                        self.stripe_repository.as_ref(),
+                        volume_state,
+                        metadata,
+                    )
+                    .await
+                    {
+                        warn!("Failed to back out stripe {}: {}", metadata.stripe_id, e);
+                    }
+                }
+                return Err(e);
+            }
+        }
+
+        retire_stripe(store, self.stripe_repository.as_ref(), volume_state, stripe).await?;
+
+        debug!(
+            "Split stripe {} of volume {} into {} stripes on {}",
+            stripe.stripe_id,
+            stripe.volume_id,
+            replacements.len(),
+            policy.name()
+        );
+
+        Ok(replacements.iter().map(|s| s.stripe_id).collect())
+    }
+
+    /// Get volume EC state or fail
+    fn volume_state(&self, volume_id: &str) -> Result<SharedVolumeState> {
+        self.metadata_manager
+            .get_volume(volume_id)
+            .ok_or_else(|| Error::EcStripeNotFound(format!("Volume {} has no EC state", volume_id)))
+    }
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::ports::StripeId;
+    use crate::ec::test_support::{self, EcFixture};
+
+    const VOLUME: &str = "vol-1";
+    const OLD_POLICY: &str = "ec-4-2";
+
+    struct Fixture {
+        restriper: Arc<StripeRestriper>,
+        ec: EcFixture,
+    }
+
+    /// A volume on a 4+2 policy with 4096-byte (8 LBA) stripes
+    async fn fixture() -> Fixture {
+        let ec = EcFixture::new(
+            12,
+            4,
+            [
+                test_support::policy(OLD_POLICY, 4, 2, 4096),
+                test_support::policy("ec-6-3", 6, 3, 4096),
+                test_support::policy("ec-2-1-small", 2, 1, 2048),
+            ],
+        );
+        ec.metadata_manager.get_or_create_volume(VOLUME, OLD_POLICY);
+
+        let restriper = StripeRestriper::new(
+            ec.metadata_manager.clone(),
+            ec.store.clone(),
+            ec.repository.clone(),
+        );
+
+        Fixture { restriper, ec }
+    }
+
+    /// Destage `data` at `start_lba` as a new stripe on the old policy
+    async fn destage(f: &Fixture, start_lba: u64, data: &[u8]) -> StripeMetadata {
+        f.ec.destage(VOLUME, OLD_POLICY, start_lba, data).await
+    }
+
+    async fn read_lba(f: &Fixture, lba: u64) -> Vec<u8> {
+        f.ec.read_lba(VOLUME, lba).await
+    }
+
+    fn pattern(lbas: u64, seed: u8) -> Vec<u8> {
+        (0..lbas * LBA_SIZE)
+            .map(|i| (i % 251) as u8 ^ seed)
+            .collect()
+    }
+
+    fn lba_of(data: &[u8], lba: u64) -> Vec<u8> {
+        data[(lba * LBA_SIZE) as usize..((lba + 1) * LBA_SIZE) as usize].to_vec()
+    }
+
+    // =========================================================================
+    // Progress Tests
+    // =========================================================================
+
+    #[test]
+    fn test_progress_counts() {
+        let progress = RestripeProgress {
+            volume_id: VOLUME.to_string(),
+            from_policy: OLD_POLICY.to_string(),
+            to_policy: "ec-6-3".to_string(),
+            stripes: [
+                (0, StripeRestripeState::Restriped),
+                (1, StripeRestripeState::Failed("boom".to_string())),
+                (2, StripeRestripeState::Pending),
+                (3, StripeRestripeState::Restriped),
+            ]
+            .into(),
+            stripes_created: vec![],
+            bytes_restriped: 0,
+            started_at: Utc::now(),
+            finished_at: None,
+        };
+
+        assert_eq!(progress.total(), 4);
+        assert_eq!(progress.restriped(), 2);
+        assert_eq!(progress.failed(), 1);
+        assert_eq!(progress.percent(), 50);
+        assert!(!progress.is_complete());
+    }
+
+    // =========================================================================
+    // Re-striping Tests
+    // =========================================================================
+
+    #[tokio::test]
+    async fn test_restripe_flips_stripes_in_place() {
+        let f = fixture().await;
+        let data = pattern(16, 0x5a);
+        let first = destage(&f, 0, &data[..4096]).await;
+        destage(&f, 8, &data[4096..]).await;
+
+        let progress = f.restriper.restripe_volume(VOLUME, "ec-6-3").await.unwrap();
+
+        assert!(progress.is_complete());
+        assert_eq!(progress.from_policy, OLD_POLICY);
+        assert_eq!(progress.total(), 2);
+        assert_eq!(progress.bytes_restriped, 8192);
+        assert!(progress.stripes_created.is_empty());
+        assert_eq!(f.restriper.volume_policy(VOLUME).unwrap(), "ec-6-3");
+        assert_eq!(
+            f.ec.repository
+                .find_volume_policy(&VolumeId::new(VOLUME))
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("ec-6-3")
+        );
+
+        let volume_state = f.ec.metadata_manager.get_volume(VOLUME).unwrap();
+        let flipped = volume_state.read().get_stripe(first.stripe_id).unwrap();
+        assert_eq!(flipped.policy_ref, "ec-6-3");
+        assert_eq!(flipped.shard_locations.len(), 9);
+        assert_eq!(flipped.generation, 1);
+
+        let stored =
+            f.ec.repository
+                .get(&VolumeId::new(VOLUME), &StripeId::new(first.stripe_id))
+                .unwrap();
+        assert_eq!(stored.policy_ref, "ec-6-3");
+        assert_eq!(stored.generation, 1);
+        assert_eq!(stored.shard_locations.len(), 9);
+
+        // Old shards are gone, new ones hold the same data
+        assert_eq!(f.ec.store.shard_count(), 18);
+        for lba in 0..16 {
+            assert_eq!(read_lba(&f, lba).await, lba_of(&data, lba), "LBA {}", lba);
+        }
+        assert!(f
+            .restriper
+            .pending_stripes(VOLUME, "ec-6-3")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_restripe_splits_stripes_for_smaller_stripe_size() {
+        let f = fixture().await;
+        let data = pattern(8, 0x33);
+        let old = destage(&f, 0, &data).await;
+        // LBAs 6..8 were overwritten later and stay where they are
+        let newer = destage(&f, 6, &pattern(2, 0x77)).await;
+
+        let progress = f
+            .restriper
+            .restripe_volume(VOLUME, "ec-2-1-small")
+            .await
+            .unwrap();
+
+        assert!(progress.is_complete(), "{:?}", progress.stripes);
+        assert_eq!(progress.stripes_created.len(), 2);
+        let volume_state = f.ec.metadata_manager.get_volume(VOLUME).unwrap();
+        assert!(volume_state.read().get_stripe(old.stripe_id).is_none());
+
+        let mut ranges: Vec<(u64, u64)> = progress
+            .stripes_created
+            .iter()
+            .map(|id| {
+                let s = volume_state.read().get_stripe(*id).unwrap();
+                assert_eq!(s.policy_ref, "ec-2-1-small");
+                (s.lba_range.start_lba, s.lba_range.end_lba)
+            })
+            .collect();
+        ranges.sort();
+        assert_eq!(ranges, vec![(0, 4), (4, 6)]);
+
+        for lba in 0..6 {
+            assert_eq!(read_lba(&f, lba).await, lba_of(&data, lba), "LBA {}", lba);
+        }
+        let newer_data = pattern(2, 0x77);
+        assert_eq!(read_lba(&f, 7).await, lba_of(&newer_data, 1));
+        assert!(volume_state.read().get_stripe(newer.stripe_id).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_restripe_generation_conflict_keeps_old_stripe() {
+        let f = fixture().await;
+        let data = pattern(8, 0x11);
+        let stripe = destage(&f, 0, &data).await;
+
+        // A concurrent writer already claimed the next generation
+        assert!(f
+            .ec
+            .repository
+            .update_generation(&VolumeId::new(VOLUME), &StripeId::new(stripe.stripe_id), 1)
+            .await
+            .unwrap());
+
+        let progress = f.restriper.restripe_volume(VOLUME, "ec-6-3").await.unwrap();
+
+        assert!(!progress.is_complete());
+        assert_eq!(progress.failed(), 1);
+        assert_eq!(f.ec.store.shard_count(), 6);
+        let current =
+            f.ec.metadata_manager
+                .get_volume(VOLUME)
+                .unwrap()
+                .read()
+                .get_stripe(stripe.stripe_id)
+                .unwrap();
+        assert_eq!(current.policy_ref, OLD_POLICY);
+        assert_eq!(read_lba(&f, 3).await, lba_of(&data, 3));
+        assert_eq!(
+            f.restriper.pending_stripes(VOLUME, "ec-6-3").unwrap(),
+            vec![stripe.stripe_id]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_restripe_split_waits_for_in_flight_destage() {
+        let f = fixture().await;
+        let data = pattern(8, 0x33);
+        let old = destage(&f, 0, &data).await;
+
+        // A destage of LBAs 2..3 has its stripe ID but no stripe yet
+        let volume_state = f.ec.metadata_manager.get_volume(VOLUME).unwrap();
+        let reserved = volume_state.read().reserve_stripe_id(LbaRange::new(2, 3));
+
+        let progress = f
+            .restriper
+            .restripe_volume(VOLUME, "ec-2-1-small")
+            .await
+            .unwrap();
+        assert_eq!(progress.failed(), 1);
+        assert!(progress.stripes_created.is_empty());
+        assert_eq!(f.ec.store.shard_count(), 6);
+        assert!(volume_state.read().get_stripe(old.stripe_id).is_some());
+
+        // The retry goes through once the destage has landed
+        volume_state.read().release_stripe_id(reserved);
+        destage(&f, 2, &pattern(1, 0x77)).await;
+
+        let progress = f
+            .restriper
+            .restripe_volume(VOLUME, "ec-2-1-small")
+            .await
+            .unwrap();
+        assert!(progress.is_complete(), "{:?}", progress.stripes);
+        assert_eq!(read_lba(&f, 2).await, pattern(1, 0x77));
+        assert_eq!(read_lba(&f, 5).await, lba_of(&data, 5));
+    }
+
+    #[tokio::test]
+    async fn test_restripe_unknown_volume() {
+        let f = fixture().await;
+        assert!(matches!(
+            f.restriper.restripe_volume("missing", "ec-6-3").await,
+            Err(Error::EcStripeNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,490 @@
+//! SIMD Reed-Solomon Codec
+//!
+//! A self-contained `EcCodec` built on the GF(2^8) kernels in
+//! [`gf256`](super::gf256). It uses the same systematic encoding matrix as
+//! `reed-solomon-erasure`, so shards are bit-identical to those produced by
+//! [`EcEncoder`](super::encoder::EcEncoder) and either codec can decode the
+//! other's stripes.
+//!
+//! Work is done in cache-sized chunks: every parity row of a chunk is
+//! computed while the chunk's data is still in L1, which keeps the
+//! shuffle-based kernels close to memory bandwidth.
+
+use async_trait::async_trait;
+
+use crate::domain::ports::{EcCodec, EncodedData};
+use crate::ec::gf256::{mul_slice, GfMatrix, GfSimdLevel, MulTable};
+use crate::error::{Error, Result};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{debug, instrument};
+
+/// Bytes of each shard processed per pass over the coefficient matrix
+const CHUNK_BYTES: usize = 8 * 1024;
+
+/// Maximum number of cached decode matrices
+const DECODE_CACHE_ENTRIES: usize = 256;
+
+/// Reed-Solomon codec using runtime-selected SIMD GF(2^8) kernels
+pub struct SimdCodec {
+    /// Number of data shards (k)
+    data_shards: usize,
+    /// Number of parity shards (m)
+    parity_shards: usize,
+    /// Kernel used for bulk multiplication
+    level: GfSimdLevel,
+    /// Systematic encoding matrix (k+m rows, k columns)
+    matrix: GfMatrix,
+    /// Multiplication tables of the parity rows, row-major (m x k)
+    parity_tables: Vec<MulTable>,
+    /// Inverted sub-matrices by the indices of the shards they decode from
+    decode_cache: Mutex<HashMap<Vec<usize>, Arc<GfMatrix>>>,
+}
+
+impl std::fmt::Debug for SimdCodec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SimdCodec")
+            .field("data_shards", &self.data_shards)
+            .field("parity_shards", &self.parity_shards)
+            .field("level", &self.level)
+            .finish()
+    }
+}
+
+impl SimdCodec {
+    /// Create a codec for k+m shards using the best kernel for this CPU
+    pub fn new(data_shards: usize, parity_shards: usize) -> Result<Self> {
+        Self::with_level(data_shards, parity_shards, GfSimdLevel::detect())
+    }
+
+    /// Create a codec with a specific kernel
+    ///
+    /// Fails if the CPU does not support `level`.
+    pub fn with_level(
+        data_shards: usize,
+        parity_shards: usize,
+        level: GfSimdLevel,
+    ) -> Result<Self> {
+        if data_shards == 0 {
+            return Err(Error::InvalidEcConfig(
+                "data_shards must be greater than 0".to_string(),
+            ));
+        }
+        if parity_shards == 0 {
+            return Err(Error::InvalidEcConfig(
+                "parity_shards must be greater than 0".to_string(),
+            ));
+        }
+        if data_shards + parity_shards > 256 {
+            return Err(Error::InvalidEcConfig(format!(
+                "{}+{} exceeds the 256 shards GF(2^8) supports",
+                data_shards, parity_shards
+            )));
+        }
+        if !level.is_supported() {
+            return Err(Error::InvalidEcConfig(format!(
+                "{} is not supported on this CPU",
+                level
+            )));
+        }
+
+        let matrix = GfMatrix::systematic(data_shards, data_shards + parity_shards)?;
+        let parity_tables = (data_shards..data_shards + parity_shards)
+            .flat_map(|r| matrix.row(r).iter().map(|&c| MulTable::new(c)))
+            .collect::<Vec<_>>();
+
+        debug!(
+            "Created {}+{} SIMD codec using {}",
+            data_shards, parity_shards, level
+        );
+
+        Ok(Self {
+            data_shards,
+            parity_shards,
+            level,
+            matrix,
+            parity_tables,
+            decode_cache: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Kernel in use
+    pub fn simd_level(&self) -> GfSimdLevel {
+        self.level
+    }
+
+    /// Get the total number of shards
+    pub fn total_shards(&self) -> usize {
+        self.data_shards + self.parity_shards
+    }
+
+    /// Encode data into shards (data + parity), padding like `EcEncoder`
+    #[instrument(skip(self, data), fields(data_len = data.len()))]
+    pub fn encode_to_shards(&self, data: &[u8]) -> Result<Vec<Vec<u8>>> {
+        let shard_size = data.len().div_ceil(self.data_shards);
+        if shard_size == 0 {
+            return Err(Error::EcEncodingFailed(
+                "cannot encode empty data".to_string(),
+            ));
+        }
+
+        let mut shards: Vec<Vec<u8>> = Vec::with_capacity(self.total_shards());
+        for i in 0..self.data_shards {
+            let start = (i * shard_size).min(data.len());
+            let end = ((i + 1) * shard_size).min(data.len());
+            let mut shard = data[start..end].to_vec();
+            shard.resize(shard_size, 0);
+            shards.push(shard);
+        }
+        shards.resize(self.total_shards(), vec![0u8; shard_size]);
+
+        self.encode_shards(&mut shards)?;
+        Ok(shards)
+    }
+
+    /// Compute parity shards in place from pre-split data shards
+    pub fn encode_shards(&self, shards: &mut [Vec<u8>]) -> Result<()> {
+        if shards.len() != self.total_shards() {
+            return Err(Error::InvalidEcConfig(format!(
+                "Expected {} shards, got {}",
+                self.total_shards(),
+                shards.len()
+            )));
+        }
+        let shard_size = shards[0].len();
+        if shard_size == 0 || shards.iter().any(|s| s.len() != shard_size) {
+            return Err(Error::EcEncodingFailed(
+                "shards must be non-empty and of equal size".to_string(),
+            ));
+        }
+
+        let (data, parity) = shards.split_at_mut(self.data_shards);
+        let inputs: Vec<&[u8]> = data.iter().map(|s| s.as_slice()).collect();
+        let mut outputs: Vec<&mut [u8]> = parity.iter_mut().map(|s| s.as_mut_slice()).collect();
+        self.apply(&self.parity_tables, &inputs, &mut outputs);
+        Ok(())
+    }
+
+    /// Check that the parity shards match the data shards
+    pub fn verify(&self, shards: &[Vec<u8>]) -> Result<bool> {
+        let mut expected = shards.to_vec();
+        self.encode_shards(&mut expected)?;
+        Ok(expected[self.data_shards..] == shards[self.data_shards..])
+    }
+
+    /// Reconstruct only the missing data shards
+    pub fn reconstruct_data(&self, shards: &mut [Option<Vec<u8>>]) -> Result<()> {
+        self.reconstruct_internal(shards, true)
+    }
+
+    /// out[r] = sum over c of tables[r][c] * inputs[c], chunk by chunk
+    fn apply(&self, tables: &[MulTable], inputs: &[&[u8]], outputs: &mut [&mut [u8]]) {
+        let len = inputs.first().map_or(0, |i| i.len());
+        let mut start = 0;
+        while start < len {
+            let end = (start + CHUNK_BYTES).min(len);
+            for (r, out) in outputs.iter_mut().enumerate() {
+                let row = &tables[r * inputs.len()..(r + 1) * inputs.len()];
+                for (c, input) in inputs.iter().enumerate() {
+                    mul_slice(
+                        self.level,
+                        &row[c],
+                        &input[start..end],
+                        &mut out[start..end],
+                        c == 0,
+                    );
+                }
+            }
+            start = end;
+        }
+    }
+
+    /// Inverse of the encoding rows of `present`, cached per erasure pattern
+    fn decode_matrix(&self, present: &[usize]) -> Result<Arc<GfMatrix>> {
+        if let Some(matrix) = self.decode_cache.lock().get(present) {
+            return Ok(matrix.clone());
+        }
+
+        let inverse = self.matrix.select_rows(present).invert().ok_or_else(|| {
+            Error::EcReconstructionFailed {
+                stripe_id: 0,
+                reason: "decode matrix is singular".to_string(),
+            }
+        })?;
+        let inverse = Arc::new(inverse);
+
+        let mut cache = self.decode_cache.lock();
+        if cache.len() >= DECODE_CACHE_ENTRIES {
+            cache.clear();
+        }
+        cache.insert(present.to_vec(), inverse.clone());
+        Ok(inverse)
+    }
+
+    #[instrument(skip(self, shards))]
+    fn reconstruct_internal(&self, shards: &mut [Option<Vec<u8>>], data_only: bool) -> Result<()> {
+        if shards.len() != self.total_shards() {
+            return Err(Error::InvalidEcConfig(format!(
+                "Expected {} shards, got {}",
+                self.total_shards(),
+                shards.len()
+            )));
+        }
+
+        let available = shards.iter().filter(|s| s.is_some()).count();
+        if available < self.data_shards {
+            return Err(Error::InsufficientShards {
+                available,
+                required: self.data_shards,
+            });
+        }
+
+        let shard_size = shards.iter().flatten().next().map_or(0, |s| s.len());
+        if shard_size == 0 || shards.iter().flatten().any(|s| s.len() != shard_size) {
+            return Err(Error::EcReconstructionFailed {
+                stripe_id: 0,
+                reason: "shards must be non-empty and of equal size".to_string(),
+            });
+        }
+
+        let k = self.data_shards;
+        let missing_data: Vec<usize> = (0..k).filter(|&i| shards[i].is_none()).collect();
+        let missing_parity: Vec<usize> = if data_only {
+            vec![]
+        } else {
+            (k..self.total_shards())
+                .filter(|&i| shards[i].is_none())
+                .collect()
+        };
+
+        // Data shards from the first k survivors and the inverted sub-matrix
+        if !missing_data.is_empty() {
+            let present: Vec<usize> = (0..self.total_shards())
+                .filter(|&i| shards[i].is_some())
+                .take(k)
+                .collect();
+            let decode = self.decode_matrix(&present)?;
+            let tables: Vec<MulTable> = missing_data
+                .iter()
+                .flat_map(|&i| decode.row(i).iter().map(|&c| MulTable::new(c)))
+                .collect();
+
+            let mut recovered = vec![vec![0u8; shard_size]; missing_data.len()];
+            {
+                let inputs: Vec<&[u8]> = present
+                    .iter()
+                    .map(|&i| shards[i].as_deref().unwrap_or_default())
+                    .collect();
+                let mut outputs: Vec<&mut [u8]> =
+                    recovered.iter_mut().map(|s| s.as_mut_slice()).collect();
+                self.apply(&tables, &inputs, &mut outputs);
+            }
+            for (&i, shard) in missing_data.iter().zip(recovered) {
+                shards[i] = Some(shard);
+            }
+        }
+
+        // Parity shards are re-encoded from the complete data shards
+        if !missing_parity.is_empty() {
+            let tables: Vec<MulTable> = missing_parity
+                .iter()
+                .flat_map(|&r| {
+                    self.parity_tables[(r - k) * k..(r - k + 1) * k]
+                        .iter()
+                        .copied()
+                })
+                .collect();
+
+            let mut recovered = vec![vec![0u8; shard_size]; missing_parity.len()];
+            {
+                let inputs: Vec<&[u8]> = shards[..k]
+                    .iter()
+                    .map(|s| s.as_deref().unwrap_or_default())
+                    .collect();
+                let mut outputs: Vec<&mut [u8]> =
+                    recovered.iter_mut().map(|s| s.as_mut_slice()).collect();
+                self.apply(&tables, &inputs, &mut outputs);
+            }
+            for (&i, shard) in missing_parity.iter().zip(recovered) {
+                shards[i] = Some(shard);
+            }
+        }
+
+        debug!(
+            "Reconstructed {} data and {} parity shards from {}/{} available",
+            missing_data.len(),
+            missing_parity.len(),
+            available,
+            self.total_shards()
+        );
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EcCodec for SimdCodec {
+    fn data_shards(&self) -> usize {
+        self.data_shards
+    }
+
+    fn parity_shards(&self) -> usize {
+        self.parity_shards
+    }
+
+    fn encode(&self, data: &[u8]) -> Result<EncodedData> {
+        let mut shards = self.encode_to_shards(data)?;
+        let parity_shards = shards.split_off(self.data_shards);
+
+        Ok(EncodedData {
+            data_shards: shards,
+            parity_shards,
+            original_len: data.len(),
+        })
+    }
+
+    fn decode(&self, shards: &mut [Option<Vec<u8>>], original_len: usize) -> Result<Vec<u8>> {
+        self.reconstruct_data(shards)?;
+
+        let mut data = Vec::with_capacity(original_len);
+        for s in shards.iter().take(self.data_shards).flatten() {
+            data.extend_from_slice(s);
+        }
+        data.truncate(original_len);
+        Ok(data)
+    }
+
+    fn reconstruct(&self, shards: &mut [Option<Vec<u8>>]) -> Result<()> {
+        self.reconstruct_internal(shards, false)
+    }
+
+    fn calculate_shard_size(&self, data_len: usize) -> usize {
+        data_len.div_ceil(self.data_shards)
+    }
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ec::encoder::{EcDecoder, EcEncoder};
+
+    fn sample(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 31 + 7) as u8).collect()
+    }
+
+    #[test]
+    fn test_codec_creation() {
+        let codec = SimdCodec::new(4, 2).unwrap();
+        assert_eq!(codec.data_shards(), 4);
+        assert_eq!(codec.parity_shards(), 2);
+        assert_eq!(codec.total_shards(), 6);
+        assert_eq!(codec.simd_level(), GfSimdLevel::detect());
+    }
+
+    #[test]
+    fn test_codec_invalid_config() {
+        assert!(SimdCodec::new(0, 2).is_err());
+        assert!(SimdCodec::new(4, 0).is_err());
+        assert!(SimdCodec::new(200, 57).is_err());
+
+        let unsupported = [GfSimdLevel::Ssse3, GfSimdLevel::Avx2, GfSimdLevel::Neon]
+            .into_iter()
+            .find(|level| !level.is_supported());
+        if let Some(level) = unsupported {
+            assert!(SimdCodec::with_level(4, 2, level).is_err());
+        }
+    }
+
+    #[test]
+    fn test_encode_matches_reed_solomon_erasure() {
+        // Larger than one chunk so chunking and the SIMD tails are covered
+        let data = sample(3 * CHUNK_BYTES + 77);
+        for (k, m) in [(4, 2), (8, 3), (10, 4)] {
+            let expected = EcEncoder::new(k, m).unwrap().encode(&data).unwrap();
+            for level in GfSimdLevel::supported() {
+                let codec = SimdCodec::with_level(k, m, level).unwrap();
+                assert_eq!(
+                    codec.encode_to_shards(&data).unwrap(),
+                    expected,
+                    "{}",
+                    level
+                );
+                assert!(codec.verify(&expected).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_reconstruct_matches_decoder() {
+        let data = sample(10_000);
+        let encoder = EcEncoder::new(6, 3).unwrap();
+        let decoder = EcDecoder::new(6, 3).unwrap();
+        let shards = encoder.encode(&data).unwrap();
+        let codec = SimdCodec::new(6, 3).unwrap();
+
+        for lost in [vec![0], vec![2, 7], vec![0, 1, 8], vec![6, 7, 8]] {
+            let mut ours: Vec<Option<Vec<u8>>> = shards.iter().cloned().map(Some).collect();
+            for &i in &lost {
+                ours[i] = None;
+            }
+            let mut theirs = ours.clone();
+
+            codec.reconstruct(&mut ours).unwrap();
+            decoder.reconstruct(&mut theirs).unwrap();
+            assert_eq!(ours, theirs, "lost {:?}", lost);
+            assert_eq!(ours.into_iter().flatten().collect::<Vec<_>>(), shards);
+        }
+    }
+
+    #[test]
+    fn test_decode_roundtrip_through_port() {
+        let codec = SimdCodec::new(4, 2).unwrap();
+        let data = b"Data for the SIMD codec roundtrip test!";
+
+        let encoded = codec.encode(data).unwrap();
+        assert_eq!(encoded.original_len, data.len());
+        let mut shards: Vec<Option<Vec<u8>>> = encoded
+            .data_shards
+            .into_iter()
+            .chain(encoded.parity_shards)
+            .map(Some)
+            .collect();
+        shards[1] = None;
+        shards[3] = None;
+
+        assert_eq!(codec.decode(&mut shards, data.len()).unwrap(), data);
+        // Only data shards are restored by decode
+        assert!(shards[1].is_some());
+        assert_eq!(codec.calculate_shard_size(data.len()), 10);
+    }
+
+    #[test]
+    fn test_reconstruct_errors() {
+        let codec = SimdCodec::new(4, 2).unwrap();
+        let shards = codec.encode_to_shards(&sample(100)).unwrap();
+
+        let mut too_few: Vec<Option<Vec<u8>>> = shards.iter().cloned().map(Some).collect();
+        too_few[0] = None;
+        too_few[1] = None;
+        too_few[2] = None;
+        assert!(matches!(
+            codec.reconstruct(&mut too_few),
+            Err(Error::InsufficientShards {
+                available: 3,
+                required: 4
+            })
+        ));
+
+        let mut uneven: Vec<Option<Vec<u8>>> = shards.iter().cloned().map(Some).collect();
+        uneven[0] = None;
+        uneven[5].as_mut().unwrap().push(0);
+        assert!(codec.reconstruct(&mut uneven).is_err());
+
+        assert!(codec.reconstruct(&mut vec![None; 3]).is_err());
+        assert!(codec.encode_to_shards(&[]).is_err());
+    }
+}
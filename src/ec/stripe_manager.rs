@@ -3,17 +3,33 @@
 //! Manages background destaging from journal (replicated) storage
 //! to erasure-coded stripes for cold tier storage.
 
-use crate::crd::{ErasureCodingPolicy, JournalConfig, LbaRange, ShardLocation, StripeState};
-use crate::ec::encoder::EcEncoder;
-use crate::ec::metadata::{EcMetadataManager, StripeMetadata, StripeStatus};
+use crate::crd::{
+    DedupConfig, ErasureCodingPolicy, JournalConfig, LbaRange, ShardLocation, ShardState,
+    StripeState,
+};
+use crate::domain::ports::StripeRepository;
+use crate::ec::dedup::{contiguous_writes, ChunkedWrite, DedupIndex, DedupPlan};
+use crate::ec::encoder::{EcDecoder, EcEncoder};
+use crate::ec::journal::WriteJournal;
+use crate::ec::metadata::{
+    commit_shard_placement, to_domain_stripe, EcMetadataManager, StripeMetadata, StripeStatus,
+    VolumeEcState,
+};
+use crate::ec::shard_store::{
+    discard_shards, place_shards, read_stripe_shards, shard_checksum, ShardStore,
+};
 use crate::error::{Error, Result};
 use chrono::{DateTime, Utc};
+use futures::stream::{FuturesUnordered, StreamExt};
 use std::collections::VecDeque;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{mpsc, RwLock, Semaphore};
 use tokio::time::interval;
-use tracing::{debug, error, info, instrument};
+use tracing::{debug, error, info, instrument, warn};
+
+/// Logical block size used to map LBAs to stripe bytes
+pub(crate) const LBA_SIZE: u64 = 512;
 
 // =============================================================================
 // Configuration
@@ -31,9 +47,12 @@ pub struct StripeManagerConfig {
     /// Journal fill threshold percentage to trigger destaging
     pub destage_threshold_percent: u8,
 
-    /// Maximum concurrent destage operations
+    /// Maximum stripes encoded at once on the blocking thread pool
     pub max_concurrent: usize,
 
+    /// Maximum stripes of one destage in flight (encoding or being written)
+    pub pipeline_depth: usize,
+
     /// Whether running in dry-run mode
     pub dry_run: bool,
 }
@@ -45,6 +64,7 @@ impl Default for StripeManagerConfig {
             batch_size: 10,
             destage_threshold_percent: 80,
             max_concurrent: 4,
+            pipeline_depth: 8,
             dry_run: false,
         }
     }
@@ -123,6 +143,97 @@ pub struct DestageResult {
     pub errors: Vec<String>,
 }
 
+/// Result of an in-place partial-stripe write
+#[derive(Debug)]
+pub struct PartialWriteResult {
+    /// Volume ID
+    pub volume_id: String,
+
+    /// Updated stripes with their new generation
+    pub stripes_updated: Vec<(u64, u64)>,
+
+    /// Total bytes written by the caller
+    pub bytes_written: u64,
+
+    /// Shards rewritten (data + parity) across all stripes
+    pub shards_written: usize,
+
+    /// Duration of operation
+    pub duration: Duration,
+}
+
+// =============================================================================
+// Destage Pipeline
+// =============================================================================
+
+/// A stripe's worth of journal data waiting to be encoded
+struct PlannedStripe {
+    /// First LBA covered by the stripe
+    start_lba: u64,
+
+    /// Stripe data (at most one stripe size)
+    data: Vec<u8>,
+
+    /// Number of leading entries fully contained in this and earlier stripes
+    entries_done: usize,
+}
+
+/// Journal to truncate as a destage makes entries durable
+struct JournalProgress<'a> {
+    journal: &'a WriteJournal,
+
+    /// Sequence number of each destaged entry, in request order
+    seqs: &'a [u64],
+}
+
+/// Cut journal entries into stripe-sized batches, in write order
+///
+/// Contiguous entries are packed together. A gap or an overwrite starts a
+/// new batch, so later writes land in newer stripes.
+fn plan_stripes(entries: &[JournalEntry], stripe_size: usize) -> Vec<PlannedStripe> {
+    let mut planned = Vec::new();
+    let mut batch: Vec<u8> = Vec::with_capacity(stripe_size);
+    let mut batch_start = 0u64;
+
+    for (i, entry) in entries.iter().enumerate() {
+        let contiguous = (batch.len() as u64).is_multiple_of(LBA_SIZE)
+            && entry.lba_range.start_lba == batch_start + batch.len() as u64 / LBA_SIZE;
+        if !batch.is_empty() && !contiguous {
+            planned.push(PlannedStripe {
+                start_lba: batch_start,
+                data: std::mem::replace(&mut batch, Vec::with_capacity(stripe_size)),
+                entries_done: i,
+            });
+        }
+        if batch.is_empty() {
+            batch_start = entry.lba_range.start_lba;
+        }
+
+        batch.extend_from_slice(&entry.data);
+
+        // Cut full stripes off the front of the batch
+        while batch.len() >= stripe_size {
+            let rest = batch.split_off(stripe_size);
+            planned.push(PlannedStripe {
+                start_lba: batch_start,
+                entries_done: if rest.is_empty() { i + 1 } else { i },
+                data: std::mem::replace(&mut batch, rest),
+            });
+            batch_start += (stripe_size as u64).div_ceil(LBA_SIZE);
+        }
+    }
+
+    // Remaining data (partial stripe)
+    if !batch.is_empty() {
+        planned.push(PlannedStripe {
+            start_lba: batch_start,
+            data: batch,
+            entries_done: entries.len(),
+        });
+    }
+    planned
+}
+
 // =============================================================================
 // Stripe Manager
 // =============================================================================
@@ -135,6 +246,21 @@ pub struct StripeManager {
     /// Metadata manager
     metadata_manager: Arc<EcMetadataManager>,
 
+    /// Shard I/O backend
+    shard_store: Arc<dyn ShardStore>,
+
+    /// Persistent stripe metadata (generation and shard placement)
+    stripe_repository: Arc<dyn StripeRepository>,
+
+    /// Write journal destaged automatically, if any
+    journal: Option<Arc<WriteJournal>>,
+
+    /// Dedup index for policies with dedup enabled, if any
+    dedup: Option<Arc<DedupIndex>>,
+
+    /// Bounds stripe encodes running on the blocking thread pool
+    encode_slots: Arc<Semaphore>,
+
     /// Pending destage requests
     pending_requests: Arc<RwLock<VecDeque<DestageRequest>>>,
 
@@ -150,12 +276,86 @@ pub struct StripeManager {
 
 impl StripeManager {
     /// Create a new stripe manager
-    pub fn new(config: StripeManagerConfig, metadata_manager: Arc<EcMetadataManager>) -> Arc<Self> {
+    pub fn new(
+        config: StripeManagerConfig,
+        metadata_manager: Arc<EcMetadataManager>,
+        shard_store: Arc<dyn ShardStore>,
+        stripe_repository: Arc<dyn StripeRepository>,
+    ) -> Arc<Self> {
+        Self::build(
+            config,
+            metadata_manager,
+            shard_store,
+            stripe_repository,
+            None,
+            None,
+        )
+    }
+
+    /// Create a stripe manager that destages a write journal
+    ///
+    /// A volume's journal is destaged once it reaches its policy's fill
+    /// threshold or destage interval, and truncated after its entries are
+    /// durable in EC stripes.
+    pub fn with_journal(
+        config: StripeManagerConfig,
+        metadata_manager: Arc<EcMetadataManager>,
+        shard_store: Arc<dyn ShardStore>,
+        stripe_repository: Arc<dyn StripeRepository>,
+        journal: Arc<WriteJournal>,
+    ) -> Arc<Self> {
+        Self::build(
+            config,
+            metadata_manager,
+            shard_store,
+            stripe_repository,
+            Some(journal),
+            None,
+        )
+    }
+
+    /// Create a journaled stripe manager that deduplicates volumes whose
+    /// policy enables dedup
+    ///
+    /// Only chunks missing from `dedup` are erasure coded, into the policy's
+    /// chunk volume; the destaged volume's LBAs map to the shared chunks.
+    pub fn with_dedup(
+        config: StripeManagerConfig,
+        metadata_manager: Arc<EcMetadataManager>,
+        shard_store: Arc<dyn ShardStore>,
+        stripe_repository: Arc<dyn StripeRepository>,
+        journal: Arc<WriteJournal>,
+        dedup: Arc<DedupIndex>,
+    ) -> Arc<Self> {
+        Self::build(
+            config,
+            metadata_manager,
+            shard_store,
+            stripe_repository,
+            Some(journal),
+            Some(dedup),
+        )
+    }
+
+    fn build(
+        config: StripeManagerConfig,
+        metadata_manager: Arc<EcMetadataManager>,
+        shard_store: Arc<dyn ShardStore>,
+        stripe_repository: Arc<dyn StripeRepository>,
+        journal: Option<Arc<WriteJournal>>,
+        dedup: Option<Arc<DedupIndex>>,
+    ) -> Arc<Self> {
         let (tx, rx) = mpsc::channel(1000);
+        let encode_slots = Arc::new(Semaphore::new(config.max_concurrent.max(1)));
 
         Arc::new(Self {
             config,
             metadata_manager,
+            shard_store,
+            stripe_repository,
+            journal,
+            dedup,
+            encode_slots,
             pending_requests: Arc::new(RwLock::new(VecDeque::new())),
             shutdown: Arc::new(RwLock::new(false)),
             request_tx: tx,
@@ -196,6 +396,13 @@ impl StripeManager {
                     if let Err(e) = self.process_pending_requests().await {
                         error!("Error processing destage requests: {}", e);
                     }
+
+                    self.destage_journals().await;
+                }
+
+                // A journal crossed its fill threshold
+                _ = Self::journal_wakeup(self.journal.as_deref()) => {
+                    self.destage_journals().await;
                 }
 
                 // Receive new requests
@@ -218,6 +425,89 @@ impl StripeManager {
         *self.shutdown.write().await = true;
     }
 
+    /// Wait for a destage request from the journal (never, without one)
+    async fn journal_wakeup(journal: Option<&WriteJournal>) {
+        match journal {
+            Some(journal) => journal.destage_wanted().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Destage every journaled volume that is due
+    ///
+    /// Each due volume gets one batch per call; volumes still due afterwards
+    /// ask for another pass right away. Returns the number of volumes
+    /// destaged.
+    async fn destage_journals(&self) -> usize {
+        let Some(journal) = &self.journal else {
+            return 0;
+        };
+
+        let mut destaged = 0;
+        for volume_id in journal.due_volumes() {
+            match self.destage_journal_volume(journal, &volume_id).await {
+                Ok(result) => {
+                    destaged += 1;
+                    info!(
+                        "Destaged {} journal entries for volume {}, created {} stripes ({} bytes)",
+                        result.entries_destaged,
+                        volume_id,
+                        result.stripes_created.len(),
+                        result.bytes_destaged
+                    );
+                    if !self.config.dry_run && journal.is_due(&volume_id) {
+                        journal.request_destage();
+                    }
+                }
+                Err(e) => error!("Failed to destage journal of volume {}: {}", volume_id, e),
+            }
+        }
+        destaged
+    }
+
+    /// Destage the oldest journal entries of a volume and truncate them
+    async fn destage_journal_volume(
+        &self,
+        journal: &WriteJournal,
+        volume_id: &str,
+    ) -> Result<DestageResult> {
+        let policy_ref = self
+            .metadata_manager
+            .get_volume(volume_id)
+            .map(|state| state.read().policy_ref.clone())
+            .ok_or_else(|| Error::EcDestageFailed {
+                volume_id: volume_id.to_string(),
+                reason: "Volume not found in EC state".to_string(),
+            })?;
+        let policy = self.metadata_manager.load_policy(&policy_ref).await?;
+        let max_bytes = policy.spec.stripe_size_bytes * self.config.batch_size.max(1) as u64;
+
+        let entries = journal.read_pending(volume_id, max_bytes).await?;
+        let (seqs, journal_entries): (Vec<u64>, Vec<JournalEntry>) = entries.into_iter().unzip();
+
+        let request = DestageRequest {
+            volume_id: volume_id.to_string(),
+            journal_entries,
+            priority: 0,
+            created_at: Utc::now(),
+        };
+        let progress = JournalProgress {
+            journal,
+            seqs: &seqs,
+        };
+        let result = self.destage_entries(&request, Some(progress)).await?;
+
+        if !result.errors.is_empty() {
+            // Entries past the durable stripes stay journaled; the next pass
+            // re-destages them as newer stripes
+            return Err(Error::EcDestageFailed {
+                volume_id: volume_id.to_string(),
+                reason: result.errors.join("; "),
+            });
+        }
+        Ok(result)
+    }
+
     /// Process pending destage requests
     #[instrument(skip(self))]
     async fn process_pending_requests(&self) -> Result<()> {
@@ -261,8 +551,24 @@ impl StripeManager {
     }
 
     /// Destage a volume's journal entries to EC storage
-    #[instrument(skip(self, request), fields(volume = %request.volume_id))]
     async fn destage_volume(&self, request: &DestageRequest) -> Result<DestageResult> {
+        self.destage_entries(request, None).await
+    }
+
+    /// Destage entries through the encode pipeline
+    ///
+    /// Up to `pipeline_depth` stripes are in flight at once: later stripes
+    /// encode on the blocking pool while earlier ones are being written.
+    /// With `progress`, the journal is truncated as soon as a leading run of
+    /// stripes is durable, freeing room for new writes mid-destage. Entries
+    /// of deduplicated volumes are truncated once the whole batch is
+    /// committed to the dedup index.
+    #[instrument(skip(self, request, progress), fields(volume = %request.volume_id))]
+    async fn destage_entries(
+        &self,
+        request: &DestageRequest,
+        progress: Option<JournalProgress<'_>>,
+    ) -> Result<DestageResult> {
         let start = std::time::Instant::now();
         let mut result = DestageResult {
             volume_id: request.volume_id.clone(),
@@ -308,95 +614,214 @@ impl StripeManager {
             return Ok(result);
         }
 
-        // Create encoder
-        let encoder = EcEncoder::new(
+        // Create encoder, shared by the encode tasks
+        let encoder = Arc::new(EcEncoder::new(
             policy.spec.data_shards as usize,
             policy.spec.parity_shards as usize,
-        )?;
-
-        // Group entries into stripe-sized batches
+        )?);
         let stripe_size = policy.spec.stripe_size_bytes as usize;
-        let mut current_batch: Vec<u8> = Vec::with_capacity(stripe_size);
-        let mut current_lba_start: Option<u64> = None;
-
-        for entry in &request.journal_entries {
-            if current_lba_start.is_none() {
-                current_lba_start = Some(entry.lba_range.start_lba);
-            }
-
-            current_batch.extend_from_slice(&entry.data);
-
-            // If batch is full, create a stripe
-            if current_batch.len() >= stripe_size {
-                match self
-                    .create_stripe(
-                        &request.volume_id,
-                        &policy,
-                        &encoder,
-                        &current_batch[..stripe_size],
-                        current_lba_start.unwrap(),
-                    )
-                    .await
-                {
-                    Ok(stripe_id) => {
-                        result.stripes_created.push(stripe_id);
-                        result.bytes_destaged += stripe_size as u64;
-                    }
-                    Err(e) => {
-                        result.errors.push(e.to_string());
+        result.entries_destaged = request.journal_entries.len();
+
+        // Deduplicated volumes only store unseen chunks, in the chunk volume
+        if let (Some(dedup), Some(config)) = (&self.dedup, &policy.spec.dedup) {
+            let plan = self.plan_dedup(dedup, request, &policy, config).await?;
+            let chunk_state = self
+                .metadata_manager
+                .get_or_create_volume(&plan.chunk_volume, policy.name());
+            let planned = plan_stripes(&plan.unique, stripe_size);
+            self.run_pipeline(
+                &plan.chunk_volume,
+                &chunk_state,
+                &policy,
+                &encoder,
+                planned,
+                None,
+                &mut result,
+            )
+            .await;
+
+            // The volume's LBAs switch to the chunks once they are all durable
+            if result.errors.is_empty() {
+                let through_seq = progress.as_ref().and_then(|p| p.seqs.last().copied());
+                match dedup.commit(plan).await {
+                    Ok(()) => {
+                        if let (Some(progress), Some(seq)) = (&progress, through_seq) {
+                            if let Err(e) = progress.journal.truncate(&request.volume_id, seq).await
+                            {
+                                result.errors.push(e.to_string());
+                            }
+                        }
                     }
+                    Err(e) => result.errors.push(e.to_string()),
                 }
+            }
+        } else {
+            let planned = plan_stripes(&request.journal_entries, stripe_size);
+            self.run_pipeline(
+                &request.volume_id,
+                &volume_state,
+                &policy,
+                &encoder,
+                planned,
+                progress.as_ref(),
+                &mut result,
+            )
+            .await;
+        }
 
-                // Keep any overflow for the next stripe
-                current_batch = current_batch[stripe_size..].to_vec();
-                current_lba_start = None;
+        result.stripes_created.sort_unstable();
+        result.duration = start.elapsed();
+        Ok(result)
+    }
+
+    /// Chunk and fingerprint a request's writes, then look them up
+    async fn plan_dedup(
+        &self,
+        dedup: &DedupIndex,
+        request: &DestageRequest,
+        policy: &ErasureCodingPolicy,
+        config: &DedupConfig,
+    ) -> Result<DedupPlan> {
+        let writes = contiguous_writes(&request.journal_entries);
+        let config = config.clone();
+        let chunked = tokio::task::spawn_blocking(move || {
+            writes
+                .into_iter()
+                .map(|(start_lba, data)| ChunkedWrite::new(start_lba, data, &config))
+                .collect::<Vec<_>>()
+        })
+        .await
+        .map_err(|e| Error::Internal(format!("Dedup chunking task failed: {}", e)))?;
+
+        dedup.plan(&request.volume_id, policy.name(), &chunked)
+    }
+
+    /// Encode and write planned stripes of a volume, recording the outcome
+    /// in `result`
+    ///
+    /// Stripe IDs are reserved with their LBA ranges in plan order, so newer
+    /// data shadows older data no matter which stripe finishes first, and
+    /// compaction or re-striping cannot publish over a stripe still in flight.
+    #[allow(clippy::too_many_arguments)]
+    async fn run_pipeline(
+        &self,
+        volume_id: &str,
+        volume_state: &parking_lot::RwLock<VolumeEcState>,
+        policy: &ErasureCodingPolicy,
+        encoder: &Arc<EcEncoder>,
+        planned: Vec<PlannedStripe>,
+        progress: Option<&JournalProgress<'_>>,
+        result: &mut DestageResult,
+    ) {
+        let entries_done: Vec<usize> = planned.iter().map(|p| p.entries_done).collect();
+        let mut outcomes: Vec<Option<bool>> = vec![None; planned.len()];
+        let mut durable = 0;
+        let mut truncated = 0;
+
+        let depth = self.config.pipeline_depth.max(1);
+        let mut planned = planned.into_iter().enumerate();
+        let mut in_flight = FuturesUnordered::new();
+
+        loop {
+            // Keep the pipeline full
+            while in_flight.len() < depth {
+                let Some((index, stripe)) = planned.next() else {
+                    break;
+                };
+                let (start_lba, len) = (stripe.start_lba, stripe.data.len() as u64);
+                let range = LbaRange::new(start_lba, start_lba + len.div_ceil(LBA_SIZE));
+                let stripe_id = volume_state.read().reserve_stripe_id(range);
+                let encoder = encoder.clone();
+                in_flight.push(async move {
+                    let outcome = self
+                        .create_stripe(
+                            volume_id,
+                            policy,
+                            encoder,
+                            stripe.data,
+                            start_lba,
+                            stripe_id,
+                        )
+                        .await;
+                    if outcome.is_err() {
+                        volume_state.read().release_stripe_id(stripe_id);
+                    }
+                    (index, start_lba, len, outcome)
+                });
             }
 
-            result.entries_destaged += 1;
-        }
+            let Some((index, start_lba, len, outcome)) = in_flight.next().await else {
+                break;
+            };
+            match outcome {
+                Ok(stripe_id) => {
+                    result.stripes_created.push(stripe_id);
+                    result.bytes_destaged += len;
+                    outcomes[index] = Some(true);
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to destage LBA {} of volume {}: {}",
+                        start_lba, volume_id, e
+                    );
+                    result.errors.push(e.to_string());
+                    outcomes[index] = Some(false);
+                }
+            }
 
-        // Handle remaining data (partial stripe)
-        if !current_batch.is_empty() {
-            if let Some(lba_start) = current_lba_start {
-                match self
-                    .create_stripe(
-                        &request.volume_id,
-                        &policy,
-                        &encoder,
-                        &current_batch,
-                        lba_start,
-                    )
+            // Truncate the entries covered by the leading durable stripes
+            while durable < outcomes.len() && outcomes[durable] == Some(true) {
+                durable += 1;
+            }
+            let Some(progress) = progress else {
+                continue;
+            };
+            let done = if durable == 0 {
+                0
+            } else {
+                entries_done[durable - 1]
+            };
+            if done > truncated {
+                match progress
+                    .journal
+                    .truncate(volume_id, progress.seqs[done - 1])
                     .await
                 {
-                    Ok(stripe_id) => {
-                        result.stripes_created.push(stripe_id);
-                        result.bytes_destaged += current_batch.len() as u64;
-                    }
-                    Err(e) => {
-                        result.errors.push(e.to_string());
-                    }
+                    Ok(()) => truncated = done,
+                    Err(e) => result.errors.push(e.to_string()),
                 }
             }
         }
-
-        result.duration = start.elapsed();
-        Ok(result)
     }
 
-    /// Create a single EC stripe from data
-    #[instrument(skip(self, policy, encoder, data))]
+    /// Create a single EC stripe from data under a pre-allocated stripe ID
+    #[instrument(skip(self, policy, encoder, data), fields(len = data.len()))]
     async fn create_stripe(
         &self,
         volume_id: &str,
         policy: &ErasureCodingPolicy,
-        encoder: &EcEncoder,
-        data: &[u8],
+        encoder: Arc<EcEncoder>,
+        data: Vec<u8>,
         start_lba: u64,
+        stripe_id: u64,
     ) -> Result<u64> {
-        // Encode data into shards
-        let shards = encoder.encode(data)?;
+        // Calculate LBA range
+        let lba_count = (data.len() as u64).div_ceil(LBA_SIZE); // Round up to whole blocks
+        let lba_range = LbaRange::new(start_lba, start_lba + lba_count);
+
+        // Encode data into shards on the blocking pool, a bounded number at a time
+        let shards = {
+            let _slot = self
+                .encode_slots
+                .acquire()
+                .await
+                .map_err(|e| Error::Internal(format!("Encode pool closed: {}", e)))?;
+            tokio::task::spawn_blocking(move || encoder.encode(&data))
+                .await
+                .map_err(|e| Error::Internal(format!("Stripe encode task failed: {}", e)))??
+        };
 
-        // Get volume state and allocate stripe ID
+        // Get volume state
         let volume_state =
             self.metadata_manager
                 .get_volume(volume_id)
@@ -405,70 +830,359 @@ impl StripeManager {
                     reason: "Volume not found".to_string(),
                 })?;
 
-        // Calculate LBA range
-        // LBA size is typically 512 bytes or 4KB; we'll use the data size
-        let lba_count = (data.len() as u64).div_ceil(512); // Round up to 512-byte blocks
-        let lba_range = LbaRange::new(start_lba, start_lba + lba_count);
+        // Place every shard on a distinct pool and write it out
+        let shard_locations = place_shards(
+            self.shard_store.as_ref(),
+            &shards,
+            policy.spec.data_shards as usize,
+        )
+        .await
+        .map_err(|e| Error::EcDestageFailed {
+            volume_id: volume_id.to_string(),
+            reason: format!("failed to write shards: {}", e),
+        })?;
+
+        // Create stripe metadata
+        let metadata = StripeMetadata {
+            stripe_id,
+            volume_id: volume_id.to_string(),
+            policy_ref: policy.name().to_string(),
+            lba_range: lba_range.clone(),
+            shard_locations,
+            status: StripeStatus {
+                state: StripeState::Healthy,
+                healthy_shards: shards.len() as u8,
+                shard_health: vec![],
+            },
+            generation: 0,
+            checksum: None,
+        };
 
-        // Create shard locations (in a real implementation, these would be
-        // assigned based on pool availability and placement rules)
-        let shard_locations: Vec<ShardLocation> = shards
-            .iter()
-            .enumerate()
-            .map(|(i, shard)| {
-                let is_data = i < policy.spec.data_shards as usize;
-                ShardLocation {
-                    shard_index: i as u8,
-                    is_data_shard: is_data,
-                    pool_name: format!("pool-{}", i % 6), // Placeholder
-                    node_name: format!("node-{}", i % 3), // Placeholder
-                    offset: 0,                            // Would be allocated by storage backend
-                    size_bytes: shard.len() as u64,
-                    checksum: None,
+        // Add to in-memory state in a block to ensure guard is dropped before await
+        {
+            let mut state = volume_state.write();
+            state.add_stripe(metadata.clone());
+        }
+
+        // Persist to the stripe repository
+        self.stripe_repository
+            .save(&to_domain_stripe(&metadata))
+            .await?;
+
+        debug!(
+            "Created stripe {} for volume {}, LBA range {:?}",
+            stripe_id, volume_id, lba_range
+        );
+
+        Ok(stripe_id)
+    }
+
+    /// Overwrite data inside existing stripes (read-modify-write)
+    ///
+    /// Each affected stripe has only the touched data shards and its parity
+    /// shards rewritten. New shard copies are written to fresh locations and
+    /// published under an optimistic generation bump, so a failed or
+    /// conflicting update leaves the previous stripe contents intact.
+    #[instrument(skip(self, data), fields(len = data.len()))]
+    pub async fn write_range(
+        &self,
+        volume_id: &str,
+        start_lba: u64,
+        data: &[u8],
+    ) -> Result<PartialWriteResult> {
+        let start = std::time::Instant::now();
+        let mut result = PartialWriteResult {
+            volume_id: volume_id.to_string(),
+            stripes_updated: vec![],
+            bytes_written: 0,
+            shards_written: 0,
+            duration: Duration::ZERO,
+        };
+
+        if data.is_empty() {
+            return Ok(result);
+        }
+
+        let volume_state =
+            self.metadata_manager
+                .get_volume(volume_id)
+                .ok_or_else(|| Error::EcDestageFailed {
+                    volume_id: volume_id.to_string(),
+                    reason: "Volume not found in EC state".to_string(),
+                })?;
+
+        let range = LbaRange::new(
+            start_lba,
+            start_lba + (data.len() as u64).div_ceil(LBA_SIZE),
+        );
+
+        // Extract stripes in a block to ensure guard is dropped before await
+        let mut stripes = volume_state.read().find_stripes_in_range(&range);
+        stripes.sort_by_key(|s| s.lba_range.start_lba);
+
+        // Partial updates only apply to existing stripes; new data is destaged
+        let mut covered = range.start_lba;
+        for stripe in &stripes {
+            if stripe.lba_range.start_lba > covered {
+                break;
+            }
+            covered = covered.max(stripe.lba_range.end_lba);
+        }
+        if covered < range.end_lba {
+            return Err(Error::EcStripeNotFound(format!(
+                "LBA {} of range {:?} is not covered by an existing stripe",
+                covered, range
+            )));
+        }
+
+        if self.config.dry_run {
+            info!(
+                "[DRY-RUN] Would update {} stripes for volume {}",
+                stripes.len(),
+                volume_id
+            );
+            result.duration = start.elapsed();
+            return Ok(result);
+        }
+
+        let write_start = start_lba * LBA_SIZE;
+        let write_end = write_start + data.len() as u64;
+
+        for stripe in stripes {
+            let stripe_start = stripe.lba_range.start_lba * LBA_SIZE;
+            let stripe_end = stripe.lba_range.end_lba * LBA_SIZE;
+            let overlap_start = write_start.max(stripe_start);
+            let overlap_end = write_end.min(stripe_end);
+
+            let bytes =
+                &data[(overlap_start - write_start) as usize..(overlap_end - write_start) as usize];
+            let stripe_id = stripe.stripe_id;
+
+            // Stripes keep the policy they were encoded with until re-striped
+            let policy = self
+                .metadata_manager
+                .load_policy(&stripe.policy_ref)
+                .await?;
+            let k = policy.spec.data_shards as usize;
+            let m = policy.spec.parity_shards as usize;
+            let encoder = EcEncoder::new(k, m)?;
+            let decoder = EcDecoder::new(k, m)?;
+
+            let (generation, shards_written) = self
+                .update_stripe_range(
+                    &stripe,
+                    &encoder,
+                    &decoder,
+                    overlap_start - stripe_start,
+                    bytes,
+                )
+                .await?;
+
+            result.stripes_updated.push((stripe_id, generation));
+            result.bytes_written += bytes.len() as u64;
+            result.shards_written += shards_written;
+        }
+
+        result.duration = start.elapsed();
+        Ok(result)
+    }
+
+    /// Apply a byte-range overwrite to one stripe
+    ///
+    /// Returns the stripe's new generation and the number of shards written.
+    async fn update_stripe_range(
+        &self,
+        stripe: &StripeMetadata,
+        encoder: &EcEncoder,
+        decoder: &EcDecoder,
+        offset: u64,
+        bytes: &[u8],
+    ) -> Result<(u64, usize)> {
+        let k = encoder.data_shards();
+        let total = encoder.total_shards();
+        let update_failed = |reason: String| Error::EcStripeUpdateFailed {
+            stripe_id: stripe.stripe_id,
+            reason,
+        };
+
+        let shard_size = stripe
+            .shard_locations
+            .first()
+            .map(|l| l.size_bytes)
+            .ok_or_else(|| update_failed("stripe has no shards".to_string()))?;
+        if offset + bytes.len() as u64 > shard_size * k as u64 {
+            return Err(update_failed(format!(
+                "write of {} bytes at offset {} exceeds stripe capacity {}",
+                bytes.len(),
+                offset,
+                shard_size * k as u64
+            )));
+        }
+
+        let first = (offset / shard_size) as usize;
+        let last = ((offset + bytes.len() as u64 - 1) / shard_size) as usize;
+        let affected: Vec<usize> = (first..=last).collect();
+        let rewrite: Vec<usize> = affected.iter().copied().chain(k..total).collect();
+
+        // Read the old data chunks and the parity they contribute to
+        let store = self.shard_store.as_ref();
+        let mut shards = read_stripe_shards(store, stripe, total, &rewrite).await;
+        if rewrite.iter().any(|&i| shards[i].is_none()) {
+            // Fall back to reconstructing the missing pieces from survivors
+            let rest: Vec<usize> = (0..total).filter(|i| !rewrite.contains(i)).collect();
+            for (i, shard) in read_stripe_shards(store, stripe, total, &rest)
+                .await
+                .into_iter()
+                .enumerate()
+            {
+                if shard.is_some() {
+                    shards[i] = shard;
                 }
-            })
-            .collect();
+            }
+            decoder
+                .reconstruct(&mut shards)
+                .map_err(|e| update_failed(format!("cannot recover old contents: {}", e)))?;
+        }
 
-        // Get stripe ID and add metadata in a block to ensure guard is dropped before await
-        let (stripe_id, metadata) = {
-            let mut state = volume_state.write();
-            let stripe_id = state.next_stripe_id();
+        let mut parity: Vec<Vec<u8>> = shards[k..]
+            .iter()
+            .map(|s| s.clone().unwrap_or_default())
+            .collect();
+        let mut new_data: Vec<(usize, Vec<u8>)> = Vec::with_capacity(affected.len());
+        for &index in &affected {
+            let old = shards[index]
+                .as_ref()
+                .ok_or_else(|| update_failed(format!("data shard {} unavailable", index)))?;
+            let shard_start = index as u64 * shard_size;
+            let from = offset.max(shard_start);
+            let to = (offset + bytes.len() as u64).min(shard_start + shard_size);
+
+            let mut new = old.clone();
+            new[(from - shard_start) as usize..(to - shard_start) as usize]
+                .copy_from_slice(&bytes[(from - offset) as usize..(to - offset) as usize]);
+            encoder.update_parity(index, old, &new, &mut parity)?;
+            new_data.push((index, new));
+        }
 
-            // Create stripe metadata
-            let metadata = StripeMetadata {
-                stripe_id,
-                volume_id: volume_id.to_string(),
-                policy_ref: policy.name().to_string(),
-                lba_range: lba_range.clone(),
-                shard_locations: shard_locations.clone(),
-                status: StripeStatus {
-                    state: StripeState::Healthy,
-                    healthy_shards: shards.len() as u8,
-                    shard_health: vec![],
-                },
-                generation: 0,
-                checksum: None,
+        // Write the new shard copies away from the stripe's other pools
+        let outputs: Vec<(usize, Vec<u8>)> = new_data
+            .into_iter()
+            .chain(parity.into_iter().enumerate().map(|(i, p)| (k + i, p)))
+            .collect();
+        let mut new_locations = stripe.shard_locations.clone();
+        let mut written: Vec<ShardLocation> = Vec::with_capacity(outputs.len());
+
+        for (index, shard) in &outputs {
+            let exclude: Vec<String> = new_locations
+                .iter()
+                .filter(|l| l.shard_index as usize != *index)
+                .map(|l| l.pool_name.clone())
+                .collect();
+            let allocated = self
+                .shard_store
+                .allocate(*index as u8, *index < k, shard.len() as u64, &exclude)
+                .await;
+            let mut location = match allocated {
+                Ok(location) => location,
+                Err(e) => {
+                    self.discard_shards(&written).await;
+                    return Err(e);
+                }
             };
+            location.checksum = Some(shard_checksum(shard));
+
+            if let Err(e) = self.shard_store.write_shard(&location, shard).await {
+                self.discard_shards(&written).await;
+                return Err(update_failed(format!(
+                    "failed to write shard {}: {}",
+                    index, e
+                )));
+            }
+            written.push(location.clone());
+
+            match new_locations
+                .iter_mut()
+                .find(|l| l.shard_index as usize == *index)
+            {
+                Some(slot) => *slot = location,
+                None => new_locations.push(location),
+            }
+        }
+        new_locations.sort_by_key(|l| l.shard_index);
 
-            // Add to in-memory state
-            state.add_stripe(metadata.clone());
-            (stripe_id, metadata)
+        // Rewritten shards are healthy; others keep their recorded state
+        let shard_health: Vec<_> = stripe
+            .status
+            .shard_health
+            .iter()
+            .filter(|h| !rewrite.contains(&(h.shard_index as usize)))
+            .cloned()
+            .collect();
+
+        let generation = match commit_shard_placement(
+            self.stripe_repository.as_ref(),
+            stripe,
+            &new_locations,
+            &shard_health,
+        )
+        .await
+        {
+            Ok(generation) => generation,
+            Err(e) => {
+                self.discard_shards(&written).await;
+                return Err(e);
+            }
         };
 
-        // Persist to Kubernetes CRD
-        self.metadata_manager.create_stripe_crd(&metadata).await?;
+        let unhealthy = shard_health
+            .iter()
+            .filter(|h| h.state != ShardState::Healthy)
+            .count();
+        let mut updated = stripe.clone();
+        let old_locations: Vec<ShardLocation> = stripe
+            .shard_locations
+            .iter()
+            .filter(|l| rewrite.contains(&(l.shard_index as usize)))
+            .cloned()
+            .collect();
+        updated.shard_locations = new_locations;
+        updated.generation = generation;
+        updated.status = StripeStatus {
+            state: if unhealthy == 0 {
+                StripeState::Healthy
+            } else {
+                stripe.status.state.clone()
+            },
+            healthy_shards: (total - unhealthy) as u8,
+            shard_health,
+        };
+        if let Some(volume_state) = self.metadata_manager.get_volume(&stripe.volume_id) {
+            volume_state.read().update_stripe(updated);
+        }
+
+        self.discard_shards(&old_locations).await;
 
         debug!(
-            "Created stripe {} for volume {}, LBA range {:?}",
-            stripe_id, volume_id, lba_range
+            "Updated stripe {} in place ({} shards rewritten, generation {})",
+            stripe.stripe_id,
+            outputs.len(),
+            generation
         );
 
-        Ok(stripe_id)
+        Ok((generation, outputs.len()))
     }
 
-    /// Check if a volume needs destaging based on journal fill level
-    pub async fn should_destage(&self, _volume_id: &str, journal_fill_percent: u8) -> bool {
-        journal_fill_percent >= self.config.destage_threshold_percent
+    /// Best-effort removal of shard copies that are no longer referenced
+    async fn discard_shards(&self, locations: &[ShardLocation]) {
+        discard_shards(self.shard_store.as_ref(), locations).await;
+    }
+
+    /// Check if a volume's journal has reached its fill threshold or
+    /// destage interval
+    pub fn should_destage(&self, volume_id: &str) -> bool {
+        self.journal
+            .as_ref()
+            .is_some_and(|journal| journal.is_due(volume_id))
     }
 
     /// Get the number of pending requests
@@ -489,6 +1203,10 @@ impl StripeManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::crd::{DedupChunking, ErasureCodingPolicySpec};
+    use crate::domain::ports::{StripeId, VolumeId};
+    use crate::ec::shard_store::{verify_shard, InMemoryShardStore};
+    use crate::ec::test_support::{self, EcFixture};
 
     // =========================================================================
     // Configuration Tests
@@ -502,6 +1220,7 @@ mod tests {
         assert_eq!(config.batch_size, 10);
         assert_eq!(config.destage_threshold_percent, 80);
         assert_eq!(config.max_concurrent, 4);
+        assert_eq!(config.pipeline_depth, 8);
         assert!(!config.dry_run);
     }
 
@@ -596,4 +1315,528 @@ mod tests {
         // Above threshold
         assert!(90 >= config.destage_threshold_percent);
     }
+
+    // =========================================================================
+    // Partial Write Tests
+    // =========================================================================
+
+    const VOLUME: &str = "vol-1";
+    const POLICY: &str = "ec-4-2";
+
+    struct Fixture {
+        manager: Arc<StripeManager>,
+        ec: EcFixture,
+        stripe: StripeMetadata,
+        data: Vec<u8>,
+    }
+
+    /// One 4+2 stripe of 4096 bytes covering LBAs 0..8
+    async fn fixture() -> Fixture {
+        let ec = EcFixture::new(8, 4, [test_support::policy(POLICY, 4, 2, 4096)]);
+        let data: Vec<u8> = (0..4096u32).map(|i| (i % 253) as u8).collect();
+        let stripe = ec.destage(VOLUME, POLICY, 0, &data).await;
+
+        let manager = StripeManager::new(
+            StripeManagerConfig::default(),
+            ec.metadata_manager.clone(),
+            ec.store.clone(),
+            ec.repository.clone(),
+        );
+
+        Fixture {
+            manager,
+            ec,
+            stripe,
+            data,
+        }
+    }
+
+    /// Read back the current stripe contents through its recorded locations
+    async fn read_back(f: &Fixture) -> (StripeMetadata, Vec<Vec<u8>>) {
+        let stripe =
+            f.ec.metadata_manager
+                .get_volume(VOLUME)
+                .unwrap()
+                .read()
+                .get_stripe(0)
+                .unwrap();
+        let mut shards = Vec::new();
+        for location in &stripe.shard_locations {
+            let bytes = f.ec.store.read_shard(location).await.unwrap();
+            assert!(verify_shard(location, &bytes));
+            shards.push(bytes);
+        }
+        (stripe, shards)
+    }
+
+    #[tokio::test]
+    async fn test_write_range_updates_data_and_parity() {
+        let f = fixture().await;
+
+        // 1500 bytes from LBA 2 touch data shards 1 and 2 only
+        let patch = vec![0xCD; 1500];
+        let result = f.manager.write_range(VOLUME, 2, &patch).await.unwrap();
+        assert_eq!(result.stripes_updated, vec![(0, 1)]);
+        assert_eq!(result.bytes_written, 1500);
+        assert_eq!(result.shards_written, 4);
+
+        let (stripe, shards) = read_back(&f).await;
+        assert_eq!(stripe.generation, 1);
+        // Untouched data shards keep their placement
+        let placement = |s: &StripeMetadata, i: usize| {
+            let l = &s.shard_locations[i];
+            (l.pool_name.clone(), l.offset)
+        };
+        assert_eq!(placement(&stripe, 0), placement(&f.stripe, 0));
+        assert_eq!(placement(&stripe, 3), placement(&f.stripe, 3));
+        assert_ne!(placement(&stripe, 1), placement(&f.stripe, 1));
+
+        let encoder = EcEncoder::new(4, 2).unwrap();
+        assert!(encoder.verify(&shards).unwrap());
+        let mut expected = f.data.clone();
+        expected[1024..2524].copy_from_slice(&patch);
+        assert_eq!(shards[..4].concat(), expected);
+
+        let stored =
+            f.ec.repository
+                .get(&VolumeId::new(VOLUME), &StripeId::new(0))
+                .unwrap();
+        assert_eq!(stored.generation, 1);
+        // Old copies of the rewritten shards are released
+        assert_eq!(f.ec.store.shard_count(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_write_range_recovers_unreadable_shard() {
+        let f = fixture().await;
+        let lost = f.stripe.shard_locations[2].pool_name.clone();
+        f.ec.store.set_pool_online(&lost, false);
+
+        let patch = vec![0x11; 100];
+        f.manager.write_range(VOLUME, 4, &patch).await.unwrap();
+
+        let (stripe, shards) = read_back(&f).await;
+        assert_ne!(stripe.shard_locations[2].pool_name, lost);
+        let mut expected = f.data.clone();
+        expected[2048..2148].copy_from_slice(&patch);
+        assert_eq!(shards[..4].concat(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_write_range_rejects_uncovered_lbas() {
+        let f = fixture().await;
+
+        let err = f
+            .manager
+            .write_range(VOLUME, 6, &[0u8; 2048])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::EcStripeNotFound(_)));
+        assert_eq!(f.ec.store.shard_count(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_write_range_generation_conflict_keeps_old_contents() {
+        let f = fixture().await;
+
+        // A concurrent writer commits generation 1 first
+        assert!(f
+            .ec
+            .repository
+            .update_generation(&VolumeId::new(VOLUME), &StripeId::new(0), 1)
+            .await
+            .unwrap());
+
+        let err = f
+            .manager
+            .write_range(VOLUME, 0, &[0xFF; 512])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::EcGenerationConflict { .. }));
+
+        let (stripe, shards) = read_back(&f).await;
+        assert_eq!(stripe.generation, 0);
+        assert_eq!(shards[..4].concat(), f.data);
+        assert_eq!(f.ec.store.shard_count(), 6);
+    }
+
+    // =========================================================================
+    // Journal Destage Tests
+    // =========================================================================
+
+    #[tokio::test]
+    async fn test_journal_destages_at_threshold_and_truncates() {
+        const JOURNALED: &str = "vol-2";
+        const JOURNALED_POLICY: &str = "ec-4-2-journal";
+
+        let f = fixture().await;
+        f.ec.metadata_manager
+            .register_policy(ErasureCodingPolicy::new(
+                JOURNALED_POLICY,
+                ErasureCodingPolicySpec {
+                    journal_config: Some(JournalConfig {
+                        journal_size_bytes: 8192,
+                        replication_factor: 2,
+                        destage_threshold_percent: 80,
+                        destage_interval: "1h".to_string(),
+                    }),
+                    ..test_support::policy_spec(4, 2, 4096)
+                },
+            ));
+        f.ec.metadata_manager
+            .get_or_create_volume(JOURNALED, JOURNALED_POLICY);
+
+        let dirs: Vec<_> = (0..2).map(|_| tempfile::tempdir().unwrap()).collect();
+        let journal = WriteJournal::open(
+            dirs.iter().map(|d| d.path().to_path_buf()).collect(),
+            false,
+            f.ec.metadata_manager.clone(),
+        )
+        .await
+        .unwrap();
+        let manager = StripeManager::with_journal(
+            StripeManagerConfig::default(),
+            f.ec.metadata_manager.clone(),
+            f.ec.store.clone(),
+            f.ec.repository.clone(),
+            journal.clone(),
+        );
+
+        // LBAs 16..24 in two writes, then a write elsewhere
+        let data: Vec<u8> = (0..6144u32).map(|i| (i % 251) as u8).collect();
+        journal.append(JOURNALED, 16, &data[..2048]).await.unwrap();
+        journal
+            .append(JOURNALED, 20, &data[2048..4096])
+            .await
+            .unwrap();
+        journal.append(JOURNALED, 100, &[0x5A; 512]).await.unwrap();
+        assert!(!manager.should_destage(JOURNALED));
+        assert_eq!(manager.destage_journals().await, 0);
+
+        // Continuing at LBA 24 crosses the 80% threshold
+        journal.append(JOURNALED, 24, &data[4096..]).await.unwrap();
+        assert!(manager.should_destage(JOURNALED));
+        assert!(!manager.should_destage(VOLUME));
+        assert_eq!(manager.destage_journals().await, 1);
+
+        assert!(!manager.should_destage(JOURNALED));
+        let stats = journal.stats(JOURNALED).unwrap();
+        assert_eq!(stats.entries, 0);
+        assert_eq!(stats.destaged_through, 4);
+
+        // One full stripe, the unrelated write, then the contiguous remainder
+        let state = f.ec.metadata_manager.get_volume(JOURNALED).unwrap();
+        let mut stripes = state
+            .read()
+            .find_stripes_in_range(&LbaRange::new(0, u64::MAX));
+        stripes.sort_by_key(|s| s.stripe_id);
+        let ranges: Vec<(u64, u64)> = stripes
+            .iter()
+            .map(|s| (s.lba_range.start_lba, s.lba_range.end_lba))
+            .collect();
+        assert_eq!(ranges, vec![(16, 24), (100, 101), (24, 28)]);
+
+        let read_data = |stripe: StripeMetadata| {
+            let store = f.ec.store.clone();
+            async move {
+                let mut bytes = Vec::new();
+                for location in &stripe.shard_locations[..4] {
+                    bytes.extend(store.read_shard(location).await.unwrap());
+                }
+                bytes
+            }
+        };
+        assert_eq!(read_data(stripes[0].clone()).await, data[..4096]);
+        assert_eq!(read_data(stripes[2].clone()).await[..2048], data[4096..]);
+        assert!(f
+            .ec
+            .repository
+            .get(
+                &VolumeId::new(JOURNALED),
+                &StripeId::new(stripes[2].stripe_id)
+            )
+            .is_some());
+    }
+
+    fn entry(start_lba: u64, data: Vec<u8>) -> JournalEntry {
+        let lba_count = data.len() as u64 / LBA_SIZE;
+        JournalEntry {
+            lba_range: LbaRange::new(start_lba, start_lba + lba_count),
+            data,
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_plan_stripes_tracks_completed_entries() {
+        let entries = vec![
+            entry(0, vec![1; 6144]),
+            entry(12, vec![2; 1024]),
+            entry(4, vec![3; 512]),
+        ];
+        let planned = plan_stripes(&entries, 4096);
+
+        let summary: Vec<(u64, usize, usize)> = planned
+            .iter()
+            .map(|p| (p.start_lba, p.data.len(), p.entries_done))
+            .collect();
+        // The first entry spills past one stripe; the overwrite of LBA 4
+        // starts a new stripe after the contiguous run
+        assert_eq!(summary, vec![(0, 4096, 0), (8, 3072, 2), (4, 512, 3)]);
+        assert_eq!(planned[1].data[..2048], [1; 2048]);
+        assert_eq!(planned[1].data[2048..], [2; 1024]);
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_destage_preserves_write_order() {
+        let f = fixture().await;
+        let manager = StripeManager::new(
+            StripeManagerConfig {
+                max_concurrent: 2,
+                pipeline_depth: 3,
+                ..Default::default()
+            },
+            f.ec.metadata_manager.clone(),
+            f.ec.store.clone(),
+            f.ec.repository.clone(),
+        );
+
+        // Ten sequential stripes, then an overwrite of the second one
+        let mut journal_entries: Vec<JournalEntry> = (0..10u8)
+            .map(|i| entry(8 + 8 * i as u64, vec![i; 4096]))
+            .collect();
+        journal_entries.push(entry(16, vec![0xEE; 4096]));
+        let request = DestageRequest {
+            volume_id: VOLUME.to_string(),
+            journal_entries,
+            priority: 0,
+            created_at: Utc::now(),
+        };
+
+        let result = manager.destage_volume(&request).await.unwrap();
+        assert!(result.errors.is_empty());
+        assert_eq!(result.entries_destaged, 11);
+        assert_eq!(result.stripes_created.len(), 11);
+        assert_eq!(result.bytes_destaged, 11 * 4096);
+
+        // Stripe IDs follow write order regardless of completion order
+        let state = f.ec.metadata_manager.get_volume(VOLUME).unwrap();
+        let starts: Vec<u64> = result
+            .stripes_created
+            .iter()
+            .map(|id| state.read().get_stripe(*id).unwrap().lba_range.start_lba)
+            .collect();
+        let mut expected: Vec<u64> = (0..10).map(|i| 8 + 8 * i).collect();
+        expected.push(16);
+        assert_eq!(starts, expected);
+
+        // The overwrite shadows the original stripe at LBA 16
+        let newest = state
+            .read()
+            .find_stripes_in_range(&LbaRange::new(16, 24))
+            .into_iter()
+            .max_by_key(|s| s.stripe_id)
+            .unwrap();
+        assert_eq!(newest.stripe_id, *result.stripes_created.last().unwrap());
+        let mut bytes = Vec::new();
+        for location in &newest.shard_locations[..4] {
+            bytes.extend(f.ec.store.read_shard(location).await.unwrap());
+        }
+        assert_eq!(bytes, vec![0xEE; 4096]);
+    }
+
+    #[tokio::test]
+    async fn test_dedup_destage_stores_shared_chunks_once() {
+        const DEDUP_POLICY: &str = "ec-4-2-dedup";
+
+        let f = fixture().await;
+        f.ec.metadata_manager
+            .register_policy(ErasureCodingPolicy::new(
+                DEDUP_POLICY,
+                ErasureCodingPolicySpec {
+                    dedup: Some(DedupConfig {
+                        chunking: DedupChunking::Fixed,
+                        chunk_size_bytes: 4096,
+                    }),
+                    ..test_support::policy_spec(4, 2, 8192)
+                },
+            ));
+
+        let journal_dir = tempfile::tempdir().unwrap();
+        let journal = WriteJournal::open(
+            vec![journal_dir.path().to_path_buf()],
+            false,
+            f.ec.metadata_manager.clone(),
+        )
+        .await
+        .unwrap();
+        let dedup_dir = tempfile::tempdir().unwrap();
+        let dedup = Arc::new(DedupIndex::open(dedup_dir.path(), false).unwrap());
+        let manager = StripeManager::with_dedup(
+            StripeManagerConfig::default(),
+            f.ec.metadata_manager.clone(),
+            f.ec.store.clone(),
+            f.ec.repository.clone(),
+            journal,
+            dedup.clone(),
+        );
+
+        // Two images sharing their first 8 KiB
+        let image = |tail: u8| {
+            let mut data: Vec<u8> = (0..8192u32).map(|i| (i % 241) as u8).collect();
+            data.extend(vec![tail; 4096]);
+            data
+        };
+        for (volume, tail) in [("vm-a", 0xA1), ("vm-b", 0xB2)] {
+            f.ec.metadata_manager
+                .get_or_create_volume(volume, DEDUP_POLICY);
+            let request = DestageRequest {
+                volume_id: volume.to_string(),
+                journal_entries: vec![entry(0, image(tail))],
+                priority: 0,
+                created_at: Utc::now(),
+            };
+            let result = manager.destage_volume(&request).await.unwrap();
+            assert!(result.errors.is_empty());
+        }
+
+        // Three chunks for the first image, one for the second
+        let chunk_volume = crate::ec::dedup::chunk_volume_id(DEDUP_POLICY);
+        let chunk_state = f.ec.metadata_manager.get_volume(&chunk_volume).unwrap();
+        let mut stripes = chunk_state
+            .read()
+            .find_stripes_in_range(&LbaRange::new(0, u64::MAX));
+        stripes.sort_by_key(|s| s.stripe_id);
+        let ranges: Vec<(u64, u64)> = stripes
+            .iter()
+            .map(|s| (s.lba_range.start_lba, s.lba_range.end_lba))
+            .collect();
+        assert_eq!(ranges, vec![(0, 16), (16, 24), (24, 32)]);
+        assert_eq!(
+            f.ec.metadata_manager
+                .get_volume("vm-b")
+                .unwrap()
+                .read()
+                .stripe_count(),
+            0
+        );
+
+        let stats = dedup.stats(DEDUP_POLICY).unwrap();
+        assert_eq!(stats.chunks, 4);
+        assert_eq!(stats.logical_bytes, 2 * 12288);
+        assert_eq!(stats.stored_bytes, 16384);
+        assert_eq!(stats.ratio(), Some(1.5));
+
+        // The second image reads back through the shared chunks
+        let mut read = Vec::new();
+        for extent in dedup.resolve("vm-b", &LbaRange::new(0, 24)) {
+            let stripe = stripes
+                .iter()
+                .find(|s| {
+                    s.lba_range.start_lba <= extent.chunk_lba
+                        && extent.chunk_lba < s.lba_range.end_lba
+                })
+                .unwrap();
+            let mut bytes = Vec::new();
+            for location in &stripe.shard_locations[..4] {
+                bytes.extend(f.ec.store.read_shard(location).await.unwrap());
+            }
+            let from = ((extent.chunk_lba - stripe.lba_range.start_lba) * LBA_SIZE) as usize;
+            read.extend_from_slice(&bytes[from..from + (extent.lba_count * LBA_SIZE) as usize]);
+        }
+        assert_eq!(read, image(0xB2));
+    }
+
+    /// Shard store whose writes wait until the gate is opened
+    struct GatedShardStore {
+        inner: Arc<InMemoryShardStore>,
+        entered: tokio::sync::Notify,
+        gate: Semaphore,
+    }
+
+    #[async_trait::async_trait]
+    impl ShardStore for GatedShardStore {
+        async fn read_shard(&self, location: &ShardLocation) -> Result<Vec<u8>> {
+            self.inner.read_shard(location).await
+        }
+
+        async fn write_shard(&self, location: &ShardLocation, data: &[u8]) -> Result<()> {
+            self.entered.notify_one();
+            let _permit = self.gate.acquire().await;
+            self.inner.write_shard(location, data).await
+        }
+
+        async fn delete_shard(&self, location: &ShardLocation) -> Result<bool> {
+            self.inner.delete_shard(location).await
+        }
+
+        async fn allocate(
+            &self,
+            shard_index: u8,
+            is_data_shard: bool,
+            size_bytes: u64,
+            exclude_pools: &[String],
+        ) -> Result<ShardLocation> {
+            self.inner
+                .allocate(shard_index, is_data_shard, size_bytes, exclude_pools)
+                .await
+        }
+
+        fn healthy_pools(&self) -> Vec<String> {
+            self.inner.healthy_pools()
+        }
+
+        fn set_pool_online(&self, pool_name: &str, online: bool) {
+            self.inner.set_pool_online(pool_name, online)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compaction_waits_for_in_flight_destage() {
+        let f = fixture().await;
+        // Only LBAs 0..2 of the fixture stripe stay live
+        f.ec.destage(VOLUME, POLICY, 2, &[0x11; 3072]).await;
+
+        let gated = Arc::new(GatedShardStore {
+            inner: f.ec.store.clone(),
+            entered: tokio::sync::Notify::new(),
+            gate: Semaphore::new(0),
+        });
+        let manager = StripeManager::new(
+            StripeManagerConfig::default(),
+            f.ec.metadata_manager.clone(),
+            gated.clone(),
+            f.ec.repository.clone(),
+        );
+
+        // Overwrite LBAs 0..2; the destage stalls writing its first shard
+        let request = DestageRequest {
+            volume_id: VOLUME.to_string(),
+            journal_entries: vec![entry(0, vec![0xEE; 1024])],
+            priority: 0,
+            created_at: Utc::now(),
+        };
+        let destage = tokio::spawn(async move { manager.destage_volume(&request).await });
+        gated.entered.notified().await;
+
+        // Repacking the old copy of LBAs 0..2 now must not shadow the destage
+        let compactor = crate::ec::compaction::StripeCompactor::new(
+            Default::default(),
+            f.ec.metadata_manager.clone(),
+            f.ec.store.clone(),
+            f.ec.repository.clone(),
+        );
+        let compacted = compactor.compact_volume(VOLUME).await.unwrap();
+        assert!(compacted.stripes_created.is_empty());
+        assert_eq!(compacted.errors.len(), 1);
+
+        gated.gate.add_permits(1);
+        let result = destage.await.unwrap().unwrap();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+
+        let lba = f.ec.read_lba(VOLUME, 0).await;
+        assert_eq!(lba, vec![0xEE; LBA_SIZE as usize]);
+        let lba = f.ec.read_lba(VOLUME, 3).await;
+        assert_eq!(lba, vec![0x11; LBA_SIZE as usize]);
+    }
 }
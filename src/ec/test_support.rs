@@ -0,0 +1,166 @@
+//! Shared fixtures for the EC unit tests
+//!
+//! Builds policies, a standalone metadata manager and, through `EcFixture`,
+//! the shard store and repository every EC component is built on, with
+//! stripes written the way the stripe manager destages them. Each test
+//! module only sets up what is specific to it.
+
+#![cfg(test)]
+
+use std::sync::Arc;
+
+use crate::adapters::InMemoryStripeRepository;
+use crate::crd::{ErasureCodingPolicy, ErasureCodingPolicySpec, LbaRange, StripeState};
+use crate::domain::ports::StripeRepository;
+use crate::ec::compaction::slice_lbas;
+use crate::ec::encoder::{EcDecoder, EcEncoder};
+use crate::ec::metadata::{to_domain_stripe, EcMetadataManager, StripeMetadata, StripeStatus};
+use crate::ec::shard_store::{place_shards, read_stripe_data, InMemoryShardStore};
+use crate::ec::stripe_manager::LBA_SIZE;
+
+/// A metadata manager without Kubernetes and with no policies cached
+pub fn metadata_manager() -> Arc<EcMetadataManager> {
+    EcMetadataManager::standalone()
+}
+
+/// A k+m policy spec with journaling, scrubbing and dedup left off
+pub fn policy_spec(
+    data_shards: u8,
+    parity_shards: u8,
+    stripe_size_bytes: u64,
+) -> ErasureCodingPolicySpec {
+    ErasureCodingPolicySpec {
+        data_shards,
+        parity_shards,
+        stripe_size_bytes,
+        algorithm: Default::default(),
+        journal_config: None,
+        min_healthy_shards: None,
+        scrubbing_enabled: false,
+        scrub_interval: "7d".to_string(),
+        dedup: None,
+        rebuild_rate_bytes_per_sec: None,
+    }
+}
+
+/// A named policy with `policy_spec`
+pub fn policy(
+    name: &str,
+    data_shards: u8,
+    parity_shards: u8,
+    stripe_size_bytes: u64,
+) -> ErasureCodingPolicy {
+    ErasureCodingPolicy::new(
+        name,
+        policy_spec(data_shards, parity_shards, stripe_size_bytes),
+    )
+}
+
+/// A metadata manager, in-memory shard store and stripe repository
+pub struct EcFixture {
+    pub metadata_manager: Arc<EcMetadataManager>,
+    pub store: Arc<InMemoryShardStore>,
+    pub repository: Arc<InMemoryStripeRepository>,
+}
+
+impl EcFixture {
+    /// `pool_count` shard pools spread over `node_count` nodes, with
+    /// `policies` registered
+    pub fn new(
+        pool_count: usize,
+        node_count: usize,
+        policies: impl IntoIterator<Item = ErasureCodingPolicy>,
+    ) -> Self {
+        let metadata_manager = metadata_manager();
+        for policy in policies {
+            metadata_manager.register_policy(policy);
+        }
+        Self {
+            metadata_manager,
+            store: Arc::new(InMemoryShardStore::new(pool_count, node_count)),
+            repository: Arc::new(InMemoryStripeRepository::new()),
+        }
+    }
+
+    /// Destage `data` at `start_lba` as a new stripe of `volume`, as the
+    /// stripe manager would: encode with the registered `policy`, place the
+    /// shards and record the stripe in both the metadata manager and the
+    /// repository.
+    pub async fn destage(
+        &self,
+        volume: &str,
+        policy: &str,
+        start_lba: u64,
+        data: &[u8],
+    ) -> StripeMetadata {
+        let spec = self
+            .metadata_manager
+            .load_policy(policy)
+            .await
+            .unwrap()
+            .spec;
+        let shards = EcEncoder::new(spec.data_shards as usize, spec.parity_shards as usize)
+            .unwrap()
+            .encode(data)
+            .unwrap();
+        let locations = place_shards(self.store.as_ref(), &shards, spec.data_shards as usize)
+            .await
+            .unwrap();
+
+        let volume_state = self.metadata_manager.get_or_create_volume(volume, policy);
+        let metadata = {
+            let mut state = volume_state.write();
+            let metadata = StripeMetadata {
+                stripe_id: state.next_stripe_id(),
+                volume_id: volume.to_string(),
+                policy_ref: policy.to_string(),
+                lba_range: LbaRange::new(start_lba, start_lba + data.len() as u64 / LBA_SIZE),
+                shard_locations: locations,
+                status: StripeStatus {
+                    state: StripeState::Healthy,
+                    healthy_shards: shards.len() as u8,
+                    shard_health: vec![],
+                },
+                generation: 0,
+                checksum: None,
+            };
+            state.add_stripe(metadata.clone());
+            metadata
+        };
+        self.repository
+            .save(&to_domain_stripe(&metadata))
+            .await
+            .unwrap();
+        metadata
+    }
+
+    /// Read one LBA of `volume` through the newest covering stripe, decoding
+    /// with that stripe's policy
+    pub async fn read_lba(&self, volume: &str, lba: u64) -> Vec<u8> {
+        let stripe = self
+            .metadata_manager
+            .get_volume(volume)
+            .unwrap()
+            .read()
+            .find_stripes_in_range(&LbaRange::new(lba, lba + 1))
+            .into_iter()
+            .max_by_key(|s| s.stripe_id)
+            .unwrap();
+        let spec = self
+            .metadata_manager
+            .load_policy(&stripe.policy_ref)
+            .await
+            .unwrap()
+            .spec;
+        let decoder =
+            EcDecoder::new(spec.data_shards as usize, spec.parity_shards as usize).unwrap();
+        let data = read_stripe_data(self.store.as_ref(), &stripe, &decoder)
+            .await
+            .unwrap();
+        slice_lbas(
+            &data,
+            stripe.lba_range.start_lba,
+            &LbaRange::new(lba, lba + 1),
+        )
+    }
+}
@@ -88,10 +88,48 @@ pub enum Error {
     #[error("Invalid EC configuration: {0}")]
     InvalidEcConfig(String),
 
+    /// Stripe was modified concurrently (optimistic generation check failed)
+    #[error(
+        "EC stripe {stripe_id} generation conflict: expected to claim generation {generation}"
+    )]
+    EcGenerationConflict { stripe_id: u64, generation: u64 },
+
+    /// In-place update of an existing stripe failed
+    #[error("EC stripe {stripe_id} update failed: {reason}")]
+    EcStripeUpdateFailed { stripe_id: u64, reason: String },
+
     /// EC destage failed
     #[error("EC destage failed for volume {volume_id}: {reason}")]
     EcDestageFailed { volume_id: String, reason: String },
 
+    /// EC stripe compaction failed
+    #[error("EC compaction failed for volume {volume_id}: {reason}")]
+    EcCompactionFailed { volume_id: String, reason: String },
+
+    /// Write journal has no room for the entry until it is destaged
+    #[error("EC journal for volume {volume_id} is full ({used_bytes} of {capacity_bytes} bytes)")]
+    EcJournalFull {
+        volume_id: String,
+        used_bytes: u64,
+        capacity_bytes: u64,
+    },
+
+    /// Write journal operation failed
+    #[error("EC journal operation failed for volume {volume_id}: {reason}")]
+    EcJournalFailed { volume_id: String, reason: String },
+
+    /// Block I/O request outside the device or not block-aligned
+    #[error("Invalid block request of {length} bytes at offset {offset}: {reason}")]
+    InvalidBlockRequest {
+        offset: u64,
+        length: u64,
+        reason: String,
+    },
+
+    /// NBD client violated the protocol
+    #[error("NBD protocol error: {0}")]
+    NbdProtocol(String),
+
     // =========================================================================
     // SPDK / DMA Errors
     // =========================================================================
@@ -295,6 +295,7 @@ impl NvmeDiscovery {
 
     // Helper functions
 
+    #[allow(clippy::manual_strip)]
     fn extract_controller_path(device: &str) -> Result<String> {
         // /dev/nvme0n1 -> /dev/nvme0
         // /dev/nvme0n1p1 -> /dev/nvme0
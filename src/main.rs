@@ -16,10 +16,12 @@
 //! └─────────────────────────────────────────────────────────────────┘
 //! ```
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use kube::Client;
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
-use tracing::{error, info, Level};
+use tracing::{error, info, warn, Level};
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
 mod adapters;
@@ -33,10 +35,14 @@ mod migrator;
 #[cfg(any(feature = "spdk", feature = "mock-spdk"))]
 mod spdk;
 
+use crate::adapters::{KubernetesStripeRepository, LogStripeRepository, LoggingEventPublisher};
 use crate::controller::{ControllerContext, EcPolicyContext};
+use crate::domain::ports::StripeRepository;
 use crate::ec::{
-    EcMetadataManager, ReconstructionConfig, ReconstructionEngine, StripeManager,
-    StripeManagerConfig,
+    CompactionConfig, DedupIndex, DurabilityConfig, EcMetadataManager, HealthWatcherConfig,
+    LocalShardStore, NbdConfig, NbdExport, NbdServer, PoolMapping, ReconstructionConfig,
+    ReconstructionEngine, ShardHealthWatcher, ShardStore, StripeCompactor, StripeManager,
+    StripeManagerConfig, StripeRestriper, WriteJournal,
 };
 use crate::error::Result;
 use crate::metrics::{MetricsConfig, MetricsWatcher};
@@ -46,6 +52,16 @@ use crate::migrator::{Migrator, MigratorConfig};
 // CLI Arguments
 // =============================================================================
 
+/// Backend holding EC stripe metadata
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum StripeStoreKind {
+    /// ECStripe custom resources
+    Kubernetes,
+    /// Local log under --stripe-store-path; must be on a persistent volume,
+    /// since ECStripe CRDs are deleted once imported into it
+    Log,
+}
+
 /// Smart Storage Operator - Intelligent storage tiering for Mayastor
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -97,6 +113,99 @@ struct Args {
     /// Output logs as JSON
     #[arg(long, env = "LOG_JSON")]
     log_json: bool,
+
+    /// Root directory for EC shard storage; each subdirectory is a pool and
+    /// should be the mount point of a separate disk
+    #[arg(
+        long,
+        env = "SHARD_STORE_PATH",
+        default_value = "/var/lib/couchestor/shards"
+    )]
+    shard_store_path: String,
+
+    /// DiskPool backing each shard pool as <shard pool>=<DiskPool> (comma-separated;
+    /// unmapped shard pools are watched under their own name)
+    #[arg(long, env = "SHARD_POOL_MAP", value_delimiter = ',')]
+    shard_pool_map: Vec<PoolMapping>,
+
+    /// Where EC stripe metadata is stored
+    #[arg(long, env = "STRIPE_STORE", value_enum, default_value = "kubernetes")]
+    stripe_store: StripeStoreKind,
+
+    /// Directory of the local EC stripe metadata log (with --stripe-store=log)
+    #[arg(
+        long,
+        env = "STRIPE_STORE_PATH",
+        default_value = "/var/lib/couchestor/stripes"
+    )]
+    stripe_store_path: String,
+
+    /// Directory of the EC dedup fingerprint index
+    #[arg(
+        long,
+        env = "DEDUP_INDEX_PATH",
+        default_value = "/var/lib/couchestor/dedup"
+    )]
+    dedup_index_path: String,
+
+    /// EC write journal replica directories (comma-separated, one per disk); a
+    /// policy's journal replication factor is capped at their number
+    #[arg(
+        long,
+        env = "JOURNAL_PATHS",
+        value_delimiter = ',',
+        default_value = "/var/lib/couchestor/journal"
+    )]
+    journal_paths: Vec<PathBuf>,
+
+    /// Seconds an append to a full EC write journal waits for destaging before failing
+    #[arg(long, env = "JOURNAL_FULL_WAIT_SECONDS", default_value = "5")]
+    journal_full_wait_seconds: u64,
+
+    /// EC stripes encoded concurrently during destage
+    #[arg(long, env = "DESTAGE_ENCODE_THREADS", default_value = "4")]
+    destage_encode_threads: usize,
+
+    /// EC stripes in flight per volume destage (encoding or being written)
+    #[arg(long, env = "DESTAGE_PIPELINE_DEPTH", default_value = "8")]
+    destage_pipeline_depth: usize,
+
+    /// Default EC rebuild I/O rate limit in bytes per second (0 = unlimited);
+    /// ErasureCodingPolicy rebuildRateBytesPerSec overrides it at runtime
+    #[arg(long, env = "REBUILD_RATE_BYTES_PER_SEC", default_value = "0")]
+    rebuild_rate_bytes_per_sec: u64,
+
+    /// Repack EC stripes whose live data falls below this fraction (0 = only delete dead stripes)
+    #[arg(long, env = "EC_COMPACTION_LIVE_RATIO", default_value = "0.5")]
+    ec_compaction_live_ratio: f64,
+
+    /// Seconds a DiskPool or node must stay failed before its EC shards are rebuilt
+    #[arg(long, env = "SHARD_FAILURE_GRACE_SECONDS", default_value = "300")]
+    shard_failure_grace_seconds: u64,
+
+    /// Annualized failure rate of a single disk, used for EC durability estimates
+    #[arg(long, env = "DISK_AFR", default_value = "0.02")]
+    disk_afr: f64,
+
+    /// Annual EC data-loss probability above which a policy is flagged
+    #[arg(long, env = "EC_DURABILITY_TARGET", default_value = "0.000001")]
+    ec_durability_target: f64,
+
+    /// NBD server bind address for exported EC volumes
+    #[arg(long, env = "NBD_LISTEN_ADDR", default_value = "127.0.0.1:10809")]
+    nbd_listen_addr: String,
+
+    /// Let the unauthenticated NBD server listen on a non-loopback address
+    #[arg(long, env = "NBD_ALLOW_REMOTE")]
+    nbd_allow_remote: bool,
+
+    /// EC volumes to export over NBD as <volume>:<policy>:<size> (comma-separated; none disables the server)
+    #[arg(long, env = "NBD_EXPORTS", value_delimiter = ',')]
+    nbd_exports: Vec<NbdExport>,
+
+    /// Name of the node this operator instance runs on
+    #[arg(long, env = "NODE_NAME", default_value = "localhost")]
+    node_name: String,
 }
 
 // =============================================================================
@@ -154,6 +263,143 @@ async fn main() -> Result<()> {
         info!("Prometheus connection healthy");
     }
 
+    // Initialize EC components
+    let ec_metadata_manager = EcMetadataManager::new(client.clone());
+
+    let shard_store: Arc<dyn ShardStore> = Arc::new(LocalShardStore::open(
+        &args.shard_store_path,
+        &args.node_name,
+    )?);
+    info!("  Shard store: {}", args.shard_store_path);
+
+    let crd_stripes = KubernetesStripeRepository::new(client.clone(), "");
+    let (stripe_repository, volume_ids): (Arc<dyn StripeRepository>, _) = match args.stripe_store {
+        StripeStoreKind::Kubernetes => {
+            info!("  Stripe store: ECStripe CRDs");
+            let volume_ids = crd_stripes.list_volumes().await?;
+            (Arc::new(crd_stripes), volume_ids)
+        }
+        StripeStoreKind::Log => {
+            let log = LogStripeRepository::open(&args.stripe_store_path, true)?;
+            info!("  Stripe store: {}", args.stripe_store_path);
+
+            // Move stripes still stored as ECStripe CRDs into the local log
+            match crd_stripes.list_volumes().await {
+                Ok(volumes) if !volumes.is_empty() => {
+                    match log.migrate_volumes(&crd_stripes, &volumes).await {
+                        Ok(imported) => info!(
+                            "Migrated {} stripes of {} volumes from ECStripe CRDs",
+                            imported,
+                            volumes.len()
+                        ),
+                        Err(e) => warn!("ECStripe migration failed: {}", e),
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Could not list ECStripe CRDs for migration: {}", e),
+            }
+
+            let volume_ids = log.volume_ids();
+            (Arc::new(log), volume_ids)
+        }
+    };
+    let dedup_index = Arc::new(DedupIndex::open(&args.dedup_index_path, true)?);
+    info!("  Dedup index: {}", args.dedup_index_path);
+
+    for volume_id in volume_ids {
+        if let Err(e) = ec_metadata_manager
+            .sync_from_repository(stripe_repository.as_ref(), &volume_id.0)
+            .await
+        {
+            warn!("Failed to load EC stripes of volume {}: {}", volume_id, e);
+        }
+    }
+
+    let stripe_manager_config = StripeManagerConfig {
+        max_concurrent: args.destage_encode_threads,
+        pipeline_depth: args.destage_pipeline_depth,
+        dry_run: args.dry_run,
+        ..Default::default()
+    };
+    // Replay writes that were journaled but not yet destaged
+    let write_journal = WriteJournal::open_with_full_wait(
+        args.journal_paths.clone(),
+        true,
+        Duration::from_secs(args.journal_full_wait_seconds),
+        ec_metadata_manager.clone(),
+    )
+    .await?;
+    info!("  Write journal: {:?}", args.journal_paths);
+
+    let stripe_manager = StripeManager::with_dedup(
+        stripe_manager_config,
+        ec_metadata_manager.clone(),
+        shard_store.clone(),
+        stripe_repository.clone(),
+        write_journal.clone(),
+        dedup_index.clone(),
+    );
+
+    let reconstruction_config = ReconstructionConfig {
+        rebuild_rate_bytes_per_sec: args.rebuild_rate_bytes_per_sec,
+        ..Default::default()
+    };
+    let reconstruction_engine = ReconstructionEngine::new(
+        reconstruction_config,
+        ec_metadata_manager.clone(),
+        shard_store.clone(),
+        stripe_repository.clone(),
+    );
+
+    // Export EC volumes as block devices
+    let nbd_server = if args.nbd_exports.is_empty() {
+        None
+    } else {
+        let nbd_config = NbdConfig {
+            listen_addr: args.nbd_listen_addr.clone(),
+            allow_remote: args.nbd_allow_remote,
+            ..Default::default()
+        };
+        Some(NbdServer::new(
+            nbd_config,
+            &args.nbd_exports,
+            &ec_metadata_manager,
+            write_journal,
+            reconstruction_engine.clone(),
+            Some(dedup_index.clone()),
+        )?)
+    };
+
+    let health_watcher_config = HealthWatcherConfig {
+        grace_period: Duration::from_secs(args.shard_failure_grace_seconds),
+        pool_mappings: args.shard_pool_map.clone(),
+        ..Default::default()
+    };
+    let shard_health_watcher = ShardHealthWatcher::new(
+        health_watcher_config,
+        ec_metadata_manager.clone(),
+        reconstruction_engine.clone(),
+        shard_store.clone(),
+        stripe_repository.clone(),
+        Arc::new(LoggingEventPublisher::info_level()),
+    );
+
+    let compaction_config = CompactionConfig {
+        live_ratio_threshold: args.ec_compaction_live_ratio,
+        dry_run: args.dry_run,
+        ..Default::default()
+    };
+    let stripe_compactor = StripeCompactor::with_dedup(
+        compaction_config,
+        ec_metadata_manager.clone(),
+        shard_store.clone(),
+        stripe_repository.clone(),
+        dedup_index.clone(),
+    );
+
+    let stripe_restriper =
+        StripeRestriper::new(ec_metadata_manager.clone(), shard_store, stripe_repository);
+
     // Initialize migrator
     let migrator_config = MigratorConfig {
         sync_timeout: Duration::from_secs(args.migration_timeout_minutes * 60),
@@ -163,7 +409,7 @@ async fn main() -> Result<()> {
         preservation_mode: args.preservation_mode,
     };
 
-    let migrator = Migrator::new(migrator_config, client.clone());
+    let migrator = Migrator::with_ec_restriper(migrator_config, client.clone(), stripe_restriper);
 
     // Create controller context
     let ctx = ControllerContext::new(
@@ -173,21 +419,19 @@ async fn main() -> Result<()> {
         args.max_concurrent_migrations,
     );
 
-    // Initialize EC components
-    let ec_metadata_manager = EcMetadataManager::new(client.clone());
-
-    let stripe_manager_config = StripeManagerConfig {
-        dry_run: args.dry_run,
+    // Create EC policy controller context
+    let durability_config = DurabilityConfig {
+        disk_afr: args.disk_afr,
+        target_annual_loss_probability: args.ec_durability_target,
+        detection_delay: Duration::from_secs(args.shard_failure_grace_seconds),
         ..Default::default()
     };
-    let stripe_manager = StripeManager::new(stripe_manager_config, ec_metadata_manager.clone());
-
-    let reconstruction_config = ReconstructionConfig::default();
-    let reconstruction_engine =
-        ReconstructionEngine::new(reconstruction_config, ec_metadata_manager.clone());
-
-    // Create EC policy controller context
-    let ec_policy_ctx = EcPolicyContext::new(client.clone());
+    let ec_policy_ctx = EcPolicyContext::with_durability(
+        client.clone(),
+        durability_config,
+        reconstruction_engine.clone(),
+        dedup_index,
+    );
 
     // Spawn EC background tasks
     let stripe_manager_handle = stripe_manager.clone();
@@ -200,6 +444,24 @@ async fn main() -> Result<()> {
         reconstruction_handle.run().await;
     });
 
+    let compactor_handle = stripe_compactor.clone();
+    tokio::spawn(async move {
+        compactor_handle.run().await;
+    });
+
+    let health_watcher_client = client.clone();
+    tokio::spawn(async move {
+        shard_health_watcher.run(health_watcher_client).await;
+    });
+
+    if let Some(nbd_server) = nbd_server {
+        tokio::spawn(async move {
+            if let Err(e) = nbd_server.run().await {
+                error!("NBD server error: {}", e);
+            }
+        });
+    }
+
     // Spawn EC policy controller
     let ec_ctx = ec_policy_ctx.clone();
     tokio::spawn(async move {
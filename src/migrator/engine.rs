@@ -14,8 +14,10 @@
 //! 4. Optional preservation mode never removes old replicas
 
 use crate::crd::{DiskPool, MayastorVolume};
+use crate::ec::restripe::StripeRestriper;
 use crate::error::{Error, Result};
 use chrono::{DateTime, Utc};
+use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
 use kube::{Api, Client};
 use serde::Serialize;
@@ -280,6 +282,8 @@ pub struct Migrator {
     client: Client,
     /// Track active migrations to prevent duplicates
     active_migrations: DashMap<String, ActiveMigration>,
+    /// Re-encodes EC volumes for EcRebalance migrations (None = unsupported)
+    ec_restriper: Option<Arc<StripeRestriper>>,
 }
 
 impl Migrator {
@@ -289,9 +293,31 @@ impl Migrator {
             config,
             client,
             active_migrations: DashMap::new(),
+            ec_restriper: None,
         })
     }
 
+    /// Create a migrator that can also move EC volumes between EC policies
+    pub fn with_ec_restriper(
+        config: MigratorConfig,
+        client: Client,
+        ec_restriper: Arc<StripeRestriper>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            config,
+            client,
+            active_migrations: DashMap::new(),
+            ec_restriper: Some(ec_restriper),
+        })
+    }
+
+    /// Get the EC policy an EC volume's new writes use, if it has EC state
+    pub fn ec_volume_policy(&self, volume_name: &str) -> Option<String> {
+        self.ec_restriper
+            .as_ref()
+            .and_then(|r| r.volume_policy(volume_name))
+    }
+
     /// Check if a volume is currently being migrated
     pub fn is_migrating(&self, volume_name: &str) -> bool {
         self.active_migrations.contains_key(volume_name)
@@ -957,6 +983,165 @@ impl Migrator {
         self.active_migrations.remove(volume_name);
         Ok(result)
     }
+
+    /// Move an EC volume to a different EC policy (EcRebalance)
+    ///
+    /// Re-encodes the volume's stripes onto the new policy's shard counts
+    /// and stripe size while the volume stays readable. Each stripe is
+    /// flipped to the new policy atomically; stripes that fail keep their
+    /// old encoding and are retried by the next rebalance.
+    #[instrument(skip(self), fields(volume = %volume_name, ec_policy = %ec_policy_name))]
+    pub async fn rebalance_ec(
+        self: &Arc<Self>,
+        volume_name: &str,
+        ec_policy_name: &str,
+    ) -> Result<MigrationResult> {
+        let restriper = self
+            .ec_restriper
+            .clone()
+            .ok_or_else(|| Error::MigrationFailed {
+                volume_name: volume_name.to_string(),
+                reason: "EC re-striping is not available".to_string(),
+            })?;
+
+        let current_policy =
+            restriper
+                .volume_policy(volume_name)
+                .ok_or_else(|| Error::MigrationFailed {
+                    volume_name: volume_name.to_string(),
+                    reason: "Volume is not erasure-coded".to_string(),
+                })?;
+
+        // Register active migration, unless one is already running
+        match self.active_migrations.entry(volume_name.to_string()) {
+            Entry::Occupied(_) => {
+                return Err(Error::MigrationInProgress {
+                    volume_name: volume_name.to_string(),
+                });
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(ActiveMigration {
+                    volume_name: volume_name.to_string(),
+                    started_at: Utc::now(),
+                    target_pool: format!("ec:{}", ec_policy_name),
+                });
+            }
+        }
+
+        let result = self
+            .do_rebalance_ec(&restriper, volume_name, &current_policy, ec_policy_name)
+            .await;
+
+        self.active_migrations.remove(volume_name);
+        result
+    }
+
+    /// Internal EC rebalance logic
+    async fn do_rebalance_ec(
+        &self,
+        restriper: &StripeRestriper,
+        volume_name: &str,
+        current_policy: &str,
+        ec_policy_name: &str,
+    ) -> Result<MigrationResult> {
+        let mut result = MigrationResult::new_ec(
+            volume_name,
+            &format!("ec:{}", current_policy),
+            &format!("ec:{}", ec_policy_name),
+            MigrationType::EcRebalance,
+            ec_policy_name,
+        );
+
+        info!(
+            "Starting EC rebalance: {} from policy {} to {}",
+            volume_name, current_policy, ec_policy_name
+        );
+
+        // =====================================================================
+        // Phase 1: Analyze
+        // =====================================================================
+        result.transition(
+            MigrationState::Analyzing,
+            "Finding stripes encoded with another policy",
+        );
+
+        let pending = restriper
+            .pending_stripes(volume_name, ec_policy_name)
+            .map_err(|e| {
+                result.fail(&format!("Failed to list stripes: {}", e));
+                e
+            })?;
+
+        // Dry-run check
+        if self.config.dry_run {
+            info!(
+                "[DRY-RUN] Would re-stripe {} stripes of {} onto EC policy {}",
+                pending.len(),
+                volume_name,
+                ec_policy_name
+            );
+            result.transition(
+                MigrationState::Completed,
+                "Dry-run completed (no changes made)",
+            );
+            return Ok(result);
+        }
+
+        // =====================================================================
+        // Phase 2: Re-encode and flip stripes
+        // =====================================================================
+        result.transition(
+            MigrationState::EcEncoding,
+            &format!(
+                "Re-encoding {} stripes with EC policy {}",
+                pending.len(),
+                ec_policy_name
+            ),
+        );
+
+        let progress = restriper
+            .restripe_volume(volume_name, ec_policy_name)
+            .await
+            .map_err(|e| {
+                result.fail(&format!("Re-striping failed: {}", e));
+                e
+            })?;
+        result.ec_stripes_created = Some(progress.stripes_created.len() as u64);
+
+        if !progress.is_complete() {
+            // Failed stripes still hold their old, intact encoding
+            let reason = format!(
+                "{} of {} stripes could not be re-striped",
+                progress.failed(),
+                progress.total()
+            );
+            result.abort(&reason);
+            return Err(Error::MigrationFailed {
+                volume_name: volume_name.to_string(),
+                reason,
+            });
+        }
+
+        // =====================================================================
+        // Success!
+        // =====================================================================
+        result.transition(
+            MigrationState::Completed,
+            &format!(
+                "Re-striped {} stripes ({} bytes) onto {}",
+                progress.total(),
+                progress.bytes_restriped,
+                ec_policy_name
+            ),
+        );
+
+        info!(
+            "EC rebalance completed: {} moved to policy {} in {:?}",
+            volume_name, ec_policy_name, result.duration
+        );
+
+        Ok(result)
+    }
 }
 
 #[cfg(test)]
@@ -1019,6 +1204,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::clone_on_copy)]
     fn test_migration_state_clone() {
         let state = MigrationState::WaitingSync;
         let cloned = state.clone();
@@ -1263,4 +1449,53 @@ mod tests {
         assert!(json.contains("\"message\":\"Test step\""));
         assert!(json.contains("\"duration_ms\":100"));
     }
+
+    // =========================================================================
+    // EC Rebalance Tests
+    // =========================================================================
+
+    fn test_client() -> Client {
+        Client::try_from(kube::Config::new("http://127.0.0.1:1".parse().unwrap())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_rebalance_ec_requires_restriper() {
+        let migrator = Migrator::new(MigratorConfig::default(), test_client());
+
+        let err = migrator.rebalance_ec("vol-1", "ec-6-3").await.unwrap_err();
+        assert!(matches!(err, Error::MigrationFailed { .. }));
+        assert!(migrator.ec_volume_policy("vol-1").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rebalance_ec_dry_run() {
+        use crate::adapters::InMemoryStripeRepository;
+        use crate::ec::shard_store::InMemoryShardStore;
+        use crate::ec::EcMetadataManager;
+
+        let metadata_manager = EcMetadataManager::new(test_client());
+        metadata_manager.get_or_create_volume("vol-1", "ec-4-2");
+        let restriper = StripeRestriper::new(
+            metadata_manager,
+            Arc::new(InMemoryShardStore::new(8, 4)),
+            Arc::new(InMemoryStripeRepository::new()),
+        );
+        let config = MigratorConfig {
+            dry_run: true,
+            ..Default::default()
+        };
+        let migrator = Migrator::with_ec_restriper(config, test_client(), restriper);
+
+        let result = migrator.rebalance_ec("vol-1", "ec-6-3").await.unwrap();
+        assert_eq!(result.migration_type, MigrationType::EcRebalance);
+        assert_eq!(result.source_pool, "ec:ec-4-2");
+        assert_eq!(result.target_pool, "ec:ec-6-3");
+        assert!(result.is_success());
+        assert!(!migrator.is_migrating("vol-1"));
+        // Dry-run leaves the volume on its current policy
+        assert_eq!(migrator.ec_volume_policy("vol-1").unwrap(), "ec-4-2");
+
+        // Volumes without EC state cannot be rebalanced
+        assert!(migrator.rebalance_ec("vol-2", "ec-6-3").await.is_err());
+    }
 }
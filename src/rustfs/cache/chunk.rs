@@ -0,0 +1,136 @@
+//! Chunked Caching of Large Objects
+//!
+//! Ranged reads cache objects as fixed-size chunk entries, keyed by the
+//! object key and the chunk index, which the tiers cache and evict
+//! independently of each other.
+//!
+//! [`ChunkIndex`] remembers which objects have chunks cached, so a put or
+//! delete of an object can drop them, and hands out epochs so a chunk read
+//! from L3 before such a put is not cached after it.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use parking_lot::Mutex;
+
+use super::entry::CacheKey;
+
+/// Separates the object key from the chunk index in chunk keys
+const CHUNK_SEPARATOR: char = '\0';
+
+/// Key of chunk `index` of the object at `key`
+pub(crate) fn chunk_key(key: &CacheKey, index: u64) -> CacheKey {
+    CacheKey::new(
+        key.bucket(),
+        format!("{}{}{}", key.key(), CHUNK_SEPARATOR, index),
+    )
+}
+
+/// Split a chunk key into its object key and chunk index
+pub(crate) fn parse_chunk_key(key: &CacheKey) -> Option<(CacheKey, u64)> {
+    let (object, index) = key.key().rsplit_once(CHUNK_SEPARATOR)?;
+    let index = index.parse().ok()?;
+    Some((CacheKey::new(key.bucket(), object), index))
+}
+
+/// Cached chunks of one object
+struct ChunkedObject {
+    /// Changes whenever the object's chunks are invalidated
+    epoch: u64,
+    /// One past the highest chunk index cached
+    chunks: u64,
+}
+
+/// Objects with cached chunks
+pub(crate) struct ChunkIndex {
+    objects: Mutex<HashMap<CacheKey, ChunkedObject>>,
+    next_epoch: AtomicU64,
+}
+
+impl ChunkIndex {
+    pub(crate) fn new() -> Self {
+        Self {
+            objects: Mutex::new(HashMap::new()),
+            next_epoch: AtomicU64::new(1),
+        }
+    }
+
+    /// Get the current epoch of `key`'s chunks, to pass to [`Self::record`]
+    /// once a chunk has been read
+    pub(crate) fn epoch(&self, key: &CacheKey) -> u64 {
+        self.objects
+            .lock()
+            .entry(key.clone())
+            .or_insert_with(|| ChunkedObject {
+                epoch: self.next_epoch.fetch_add(1, Ordering::Relaxed),
+                chunks: 0,
+            })
+            .epoch
+    }
+
+    /// Record chunk `index` of `key` as cached, unless the object's chunks
+    /// were invalidated since `epoch`
+    pub(crate) fn record(&self, key: &CacheKey, epoch: u64, index: u64) -> bool {
+        let mut objects = self.objects.lock();
+        match objects.get_mut(key) {
+            Some(object) if object.epoch == epoch => {
+                object.chunks = object.chunks.max(index + 1);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Forget `key`'s chunks, returning the number of chunk indexes that
+    /// may be cached
+    pub(crate) fn invalidate(&self, key: &CacheKey) -> u64 {
+        self.objects
+            .lock()
+            .remove(key)
+            .map_or(0, |object| object.chunks)
+    }
+
+    /// Forget all chunks
+    pub(crate) fn clear(&self) {
+        self.objects.lock().clear();
+    }
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_key_roundtrip() {
+        let key = CacheKey::new("bucket", "dir/object");
+        let chunk = chunk_key(&key, 42);
+        assert_eq!(chunk.bucket(), "bucket");
+        assert_ne!(chunk, key);
+        assert_eq!(parse_chunk_key(&chunk), Some((key.clone(), 42)));
+        assert_eq!(parse_chunk_key(&key), None);
+    }
+
+    #[test]
+    fn test_chunk_index_invalidation() {
+        let index = ChunkIndex::new();
+        let key = CacheKey::new("bucket", "object");
+
+        let epoch = index.epoch(&key);
+        assert!(index.record(&key, epoch, 0));
+        assert!(index.record(&key, epoch, 3));
+        assert_eq!(index.invalidate(&key), 4);
+        assert_eq!(index.invalidate(&key), 0);
+
+        // A chunk read before the invalidation is not recorded
+        assert!(!index.record(&key, epoch, 1));
+        let fresh = index.epoch(&key);
+        assert_ne!(fresh, epoch);
+        assert!(!index.record(&key, epoch, 1));
+        assert!(index.record(&key, fresh, 1));
+        assert_eq!(index.invalidate(&key), 2);
+    }
+}
@@ -18,17 +18,20 @@
 
 use crate::error::{Error, Result};
 use bytes::Bytes;
+use serde::{Deserialize, Serialize};
 
 // =============================================================================
 // Compression Algorithm
 // =============================================================================
 
 /// Supported compression algorithms (CE: None and LZ4 only)
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum CompressionAlgorithm {
     /// No compression
     None,
     /// LZ4 - fast compression
+    #[default]
     Lz4,
 }
 
@@ -55,12 +58,6 @@ impl CompressionAlgorithm {
     }
 }
 
-impl Default for CompressionAlgorithm {
-    fn default() -> Self {
-        CompressionAlgorithm::Lz4
-    }
-}
-
 impl std::fmt::Display for CompressionAlgorithm {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.name())
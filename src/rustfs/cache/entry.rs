@@ -3,9 +3,11 @@
 //! Cache-line aligned data structures for optimal CPU cache utilization.
 
 use std::hash::{Hash, Hasher};
-use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::time::Duration;
 
+use super::compression::{CompressionAlgorithm, CompressionManager};
+
 /// Cache key - composite of bucket and object key
 #[derive(Clone, Debug, Eq)]
 #[repr(C, align(64))] // Cache-line aligned
@@ -111,11 +113,15 @@ pub struct EntryMetadata {
     /// TTL in seconds (0 = no expiry)
     ttl_seconds: u32,
     /// Entry generation (for atomic updates)
-    generation: AtomicU32,
+    generation: AtomicU64,
     /// Content hash (for integrity)
     content_hash: u64,
+    /// Set while the data has not been written to L3
+    dirty: AtomicBool,
+    /// Algorithm the stored data is compressed with
+    compression: CompressionAlgorithm,
     /// Padding to fill cache line
-    _padding: [u8; 4],
+    _padding: [u8; 2],
 }
 
 impl EntryMetadata {
@@ -132,9 +138,11 @@ impl EntryMetadata {
             access_count: AtomicU32::new(1),
             created_at: now,
             ttl_seconds: 0,
-            generation: AtomicU32::new(1),
+            generation: AtomicU64::new(1),
             content_hash,
-            _padding: [0; 4],
+            dirty: AtomicBool::new(false),
+            compression: CompressionAlgorithm::None,
+            _padding: [0; 2],
         }
     }
 
@@ -145,6 +153,29 @@ impl EntryMetadata {
         meta
     }
 
+    /// Rebuild metadata persisted by a cache tier
+    pub(crate) fn restore(
+        size: u64,
+        content_hash: u64,
+        created_at: u64,
+        ttl_seconds: u32,
+        access_count: u32,
+        last_access: u64,
+    ) -> Self {
+        Self {
+            size,
+            last_access: AtomicU64::new(last_access),
+            access_count: AtomicU32::new(access_count),
+            created_at,
+            ttl_seconds,
+            generation: AtomicU64::new(1),
+            content_hash,
+            dirty: AtomicBool::new(false),
+            compression: CompressionAlgorithm::None,
+            _padding: [0; 2],
+        }
+    }
+
     /// Get object size
     #[inline]
     pub fn size(&self) -> u64 {
@@ -180,6 +211,12 @@ impl EntryMetadata {
         self.created_at
     }
 
+    /// Get TTL in seconds (0 = no expiry)
+    #[inline]
+    pub fn ttl_seconds(&self) -> u32 {
+        self.ttl_seconds
+    }
+
     /// Check if entry has expired
     #[inline]
     pub fn is_expired(&self) -> bool {
@@ -201,16 +238,52 @@ impl EntryMetadata {
 
     /// Increment generation (for optimistic locking)
     #[inline]
-    pub fn increment_generation(&self) -> u32 {
+    pub fn increment_generation(&self) -> u64 {
         self.generation.fetch_add(1, Ordering::Release) + 1
     }
 
     /// Get current generation
     #[inline]
-    pub fn generation(&self) -> u32 {
+    pub fn generation(&self) -> u64 {
         self.generation.load(Ordering::Acquire)
     }
 
+    /// Set the generation, e.g. to order writes of the same key
+    #[inline]
+    pub fn set_generation(&self, generation: u64) {
+        self.generation.store(generation, Ordering::Release);
+    }
+
+    /// Get the algorithm the stored data is compressed with
+    #[inline]
+    pub fn compression(&self) -> CompressionAlgorithm {
+        self.compression
+    }
+
+    /// Record the algorithm the stored data is compressed with
+    #[inline]
+    pub(crate) fn set_compression(&mut self, algorithm: CompressionAlgorithm) {
+        self.compression = algorithm;
+    }
+
+    /// Check if the data still has to be written to L3
+    #[inline]
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.load(Ordering::Acquire)
+    }
+
+    /// Mark the data as not yet written to L3
+    #[inline]
+    pub fn mark_dirty(&self) {
+        self.dirty.store(true, Ordering::Release);
+    }
+
+    /// Mark the data as written to L3
+    #[inline]
+    pub fn mark_clean(&self) {
+        self.dirty.store(false, Ordering::Release);
+    }
+
     /// Calculate eviction score (higher = more likely to evict)
     /// Combines recency and frequency (LRU-K / ARC inspired)
     pub fn eviction_score(&self) -> f64 {
@@ -236,9 +309,11 @@ impl Clone for EntryMetadata {
             access_count: AtomicU32::new(self.access_count.load(Ordering::Relaxed)),
             created_at: self.created_at,
             ttl_seconds: self.ttl_seconds,
-            generation: AtomicU32::new(self.generation.load(Ordering::Relaxed)),
+            generation: AtomicU64::new(self.generation.load(Ordering::Relaxed)),
             content_hash: self.content_hash,
-            _padding: [0; 4],
+            dirty: AtomicBool::new(self.dirty.load(Ordering::Relaxed)),
+            compression: self.compression,
+            _padding: [0; 2],
         }
     }
 }
@@ -293,6 +368,56 @@ impl CacheEntry {
         self.metadata.size()
     }
 
+    /// Get the number of bytes the data occupies as stored
+    #[inline]
+    pub fn stored_size(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    /// Check if the data is stored compressed
+    #[inline]
+    pub fn is_compressed(&self) -> bool {
+        self.metadata.compression() != CompressionAlgorithm::None
+    }
+
+    /// Compress the data with `compression` unless it already is
+    ///
+    /// Data the manager skips or cannot shrink stays uncompressed.
+    pub fn compressed(self, compression: &CompressionManager) -> Self {
+        if self.is_compressed() {
+            return self;
+        }
+        let (data, algorithm) = compression.compress(&self.data);
+        if algorithm == CompressionAlgorithm::None {
+            return self;
+        }
+        let mut metadata = self.metadata;
+        metadata.set_compression(algorithm);
+        Self { metadata, data }
+    }
+
+    /// Get the entry with its data decompressed
+    pub fn decompressed(self) -> crate::error::Result<Self> {
+        let algorithm = self.metadata.compression();
+        if algorithm == CompressionAlgorithm::None {
+            return Ok(self);
+        }
+        let data = CompressionManager::new().decompress(&self.data, algorithm)?;
+        if data.len() as u64 != self.metadata.size() {
+            return Err(crate::error::Error::DecompressionFailed {
+                algorithm: algorithm.name().into(),
+                reason: format!(
+                    "expected {} bytes, got {}",
+                    self.metadata.size(),
+                    data.len()
+                ),
+            });
+        }
+        let mut metadata = self.metadata;
+        metadata.set_compression(CompressionAlgorithm::None);
+        Ok(Self { metadata, data })
+    }
+
     /// Record access
     #[inline]
     pub fn record_access(&self) -> u32 {
@@ -306,7 +431,15 @@ impl CacheEntry {
     }
 
     /// Verify content integrity
+    ///
+    /// Compressed data is checked after decompressing it.
     pub fn verify_integrity(&self) -> bool {
+        if self.is_compressed() {
+            return self
+                .clone()
+                .decompressed()
+                .is_ok_and(|entry| entry.verify_integrity());
+        }
         let computed = Self::hash_content(&self.data);
         computed == self.metadata.content_hash()
     }
@@ -370,7 +503,7 @@ mod tests {
         let mut shard_counts = vec![0usize; 1024];
 
         for i in 0..10000 {
-            let key = CacheKey::new("bucket", &format!("key-{}", i));
+            let key = CacheKey::new("bucket", format!("key-{}", i));
             let idx = key.shard_index(1024);
             assert!(idx < 1024);
             shard_counts[idx] += 1;
@@ -427,6 +560,22 @@ mod tests {
         let new_gen = meta.increment_generation();
         assert_eq!(new_gen, 2);
         assert_eq!(meta.generation(), 2);
+
+        meta.set_generation(42);
+        assert_eq!(meta.clone().generation(), 42);
+    }
+
+    #[test]
+    fn test_entry_metadata_dirty() {
+        let meta = EntryMetadata::new(1024, 0);
+        assert!(!meta.is_dirty());
+
+        meta.mark_dirty();
+        assert!(meta.is_dirty());
+        assert!(meta.clone().is_dirty());
+
+        meta.mark_clean();
+        assert!(!meta.is_dirty());
     }
 
     #[test]
@@ -472,6 +621,28 @@ mod tests {
         assert_eq!(count, 2);
     }
 
+    #[test]
+    fn test_cache_entry_compression_roundtrip() {
+        let data = bytes::Bytes::from(b"compressible ".repeat(200));
+        let entry = CacheEntry::new(data.clone());
+        let compression = CompressionManager::new();
+
+        let compressed = entry.compressed(&compression);
+        assert!(compressed.is_compressed());
+        assert_eq!(compressed.metadata.compression(), CompressionAlgorithm::Lz4);
+        assert_eq!(compressed.size(), data.len() as u64);
+        assert!(compressed.stored_size() < compressed.size());
+        assert!(compressed.verify_integrity());
+
+        let decompressed = compressed.decompressed().unwrap();
+        assert!(!decompressed.is_compressed());
+        assert_eq!(decompressed.data(), &data);
+
+        // Small data is left alone
+        let small = CacheEntry::new(bytes::Bytes::from_static(b"tiny")).compressed(&compression);
+        assert!(!small.is_compressed());
+    }
+
     #[test]
     fn test_cache_entry_debug() {
         let data = bytes::Bytes::from_static(b"Test");
@@ -0,0 +1,815 @@
+//! Eviction Algorithms
+//!
+//! Victim selection for the L1 and L2 tiers, chosen per tier by
+//! [`EvictionPolicy::algorithm`]. Every algorithm picks a victim in bounded
+//! time regardless of how many entries the tier holds.
+//!
+//! # Algorithms
+//!
+//! - **Sampled**: scores a small random sample of entries with the policy
+//!   weights (`EvictionPolicy::calculate_score`) and evicts the worst, so
+//!   `lru()`, `lfu()`, `size_aware()` and `ttl()` keep their meaning
+//! - **W-TinyLFU**: a small LRU window in front of a segmented LRU, with a
+//!   count-min sketch deciding whether a window victim displaces a main one
+//! - **S3-FIFO**: a small probationary FIFO, a main FIFO with lazy
+//!   reinsertion and a ghost FIFO remembering recently evicted keys
+//!
+//! Hits are recorded with `try_lock`, so readers never wait on an eviction
+//! in progress; a hit dropped under contention only makes the ordering a
+//! little less precise.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::Instant;
+
+use parking_lot::{Mutex, RwLock};
+
+use super::entry::CacheKey;
+use super::policy::{EvictionAlgorithm, EvictionPolicy};
+
+/// Most queue entries examined while choosing one victim
+const MAX_EVICT_STEPS: usize = 64;
+
+/// Chooses eviction victims for one cache tier
+pub trait Evictor: Send + Sync {
+    /// Algorithm name
+    fn name(&self) -> &'static str;
+
+    /// Track a new or replaced entry of `size` bytes
+    fn on_insert(&self, key: &CacheKey, size: u64);
+
+    /// Record a hit on a tracked entry
+    fn on_access(&self, key: &CacheKey);
+
+    /// Stop tracking an entry the tier removed
+    fn on_remove(&self, key: &CacheKey);
+
+    /// Choose the next victim and stop tracking it
+    fn evict(&self) -> Option<CacheKey>;
+
+    /// Stop tracking every entry
+    fn clear(&self);
+
+    /// Number of tracked entries
+    fn len(&self) -> usize;
+
+    /// Check if no entries are tracked
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Build the evictor selected by `policy` for a tier of `capacity` bytes
+pub fn new_evictor(policy: &EvictionPolicy, capacity: u64) -> Box<dyn Evictor> {
+    match policy.algorithm {
+        EvictionAlgorithm::Sampled => Box::new(SampledEvictor::new(policy.clone())),
+        EvictionAlgorithm::TinyLfu => Box::new(TinyLfuEvictor::new(capacity)),
+        EvictionAlgorithm::S3Fifo => Box::new(S3FifoEvictor::new(capacity)),
+    }
+}
+
+// =============================================================================
+// Sampled
+// =============================================================================
+
+/// Tracking state of one entry for sampled eviction
+struct SampledSlot {
+    /// Position in `SampledState::keys`
+    pos: usize,
+    size: u64,
+    /// Insertion time (ms since the evictor was created)
+    inserted: u64,
+    /// Last hit (ms since the evictor was created)
+    last_access: AtomicU64,
+    hits: AtomicU32,
+}
+
+#[derive(Default)]
+struct SampledState {
+    keys: Vec<CacheKey>,
+    slots: HashMap<CacheKey, SampledSlot>,
+}
+
+/// Evicts the worst-scoring entry of a random sample
+pub struct SampledEvictor {
+    policy: EvictionPolicy,
+    state: RwLock<SampledState>,
+    epoch: Instant,
+    rng: AtomicU64,
+}
+
+impl SampledEvictor {
+    /// Create a sampled evictor scoring entries with `policy`
+    pub fn new(policy: EvictionPolicy) -> Self {
+        Self {
+            policy,
+            state: RwLock::new(SampledState::default()),
+            epoch: Instant::now(),
+            rng: AtomicU64::new(0x9E37_79B9_7F4A_7C15),
+        }
+    }
+
+    fn now_ms(&self) -> u64 {
+        self.epoch.elapsed().as_millis() as u64
+    }
+
+    /// Next value of a shared xorshift generator
+    fn next_random(&self) -> u64 {
+        let mut x = self.rng.load(Ordering::Relaxed);
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.rng.store(x, Ordering::Relaxed);
+        x
+    }
+
+    fn score(&self, slot: &SampledSlot, now: u64) -> f64 {
+        let since_insert = std::time::Duration::from_millis(now.saturating_sub(slot.inserted));
+        if self.policy.should_evict_by_age(since_insert) {
+            return f64::INFINITY;
+        }
+        let hits = slot.hits.load(Ordering::Relaxed);
+        if self.policy.should_protect(hits) {
+            return f64::NEG_INFINITY;
+        }
+        let age_secs = now.saturating_sub(slot.last_access.load(Ordering::Relaxed)) as f64 / 1000.0;
+        self.policy.calculate_score(age_secs, hits, slot.size)
+    }
+}
+
+impl Evictor for SampledEvictor {
+    fn name(&self) -> &'static str {
+        "sampled"
+    }
+
+    fn on_insert(&self, key: &CacheKey, size: u64) {
+        let now = self.now_ms();
+        let mut state = self.state.write();
+        if let Some(slot) = state.slots.get_mut(key) {
+            slot.size = size;
+            slot.last_access.store(now, Ordering::Relaxed);
+            slot.hits.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let pos = state.keys.len();
+        state.keys.push(key.clone());
+        state.slots.insert(
+            key.clone(),
+            SampledSlot {
+                pos,
+                size,
+                inserted: now,
+                last_access: AtomicU64::new(now),
+                hits: AtomicU32::new(1),
+            },
+        );
+    }
+
+    fn on_access(&self, key: &CacheKey) {
+        let now = self.now_ms();
+        if let Some(slot) = self.state.read().slots.get(key) {
+            slot.last_access.store(now, Ordering::Relaxed);
+            slot.hits.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn on_remove(&self, key: &CacheKey) {
+        let mut state = self.state.write();
+        let Some(slot) = state.slots.remove(key) else {
+            return;
+        };
+        state.keys.swap_remove(slot.pos);
+        if let Some(moved) = state.keys.get(slot.pos).cloned() {
+            if let Some(moved) = state.slots.get_mut(&moved) {
+                moved.pos = slot.pos;
+            }
+        }
+    }
+
+    fn evict(&self) -> Option<CacheKey> {
+        let now = self.now_ms();
+        let victim = {
+            let state = self.state.read();
+            if state.keys.is_empty() {
+                return None;
+            }
+            let mut best: Option<(&CacheKey, f64)> = None;
+            for _ in 0..self.policy.sample_size.max(1) {
+                let key = &state.keys[(self.next_random() % state.keys.len() as u64) as usize];
+                let score = self.score(&state.slots[key], now);
+                if best.is_none_or(|(_, best_score)| score > best_score) {
+                    best = Some((key, score));
+                }
+            }
+            best?.0.clone()
+        };
+        self.on_remove(&victim);
+        Some(victim)
+    }
+
+    fn clear(&self) {
+        *self.state.write() = SampledState::default();
+    }
+
+    fn len(&self) -> usize {
+        self.state.read().keys.len()
+    }
+}
+
+// =============================================================================
+// Queues
+// =============================================================================
+
+/// Tracking state of one queued entry
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    queue: usize,
+    /// Matches the live queue element; older elements are stale
+    stamp: u64,
+    size: u64,
+    freq: u8,
+}
+
+/// FIFO queues with O(1) move-to-tail and removal
+///
+/// Moving or removing an entry leaves its old queue element behind as stale;
+/// stale elements are skipped when they reach the front and dropped in bulk
+/// once they outnumber the live ones.
+struct QueueSet {
+    slots: HashMap<CacheKey, Slot>,
+    queues: Vec<VecDeque<(CacheKey, u64)>>,
+    bytes: Vec<u64>,
+    next_stamp: u64,
+}
+
+impl QueueSet {
+    fn new(queues: usize) -> Self {
+        Self {
+            slots: HashMap::new(),
+            queues: (0..queues).map(|_| VecDeque::new()).collect(),
+            bytes: vec![0; queues],
+            next_stamp: 0,
+        }
+    }
+
+    fn get(&self, key: &CacheKey) -> Option<Slot> {
+        self.slots.get(key).copied()
+    }
+
+    /// Put `key` at the tail of `queue`, replacing its previous position
+    fn push(&mut self, key: &CacheKey, queue: usize, size: u64, freq: u8) {
+        self.remove(key);
+        self.next_stamp += 1;
+        let stamp = self.next_stamp;
+        self.queues[queue].push_back((key.clone(), stamp));
+        self.bytes[queue] += size;
+        self.slots.insert(
+            key.clone(),
+            Slot {
+                queue,
+                stamp,
+                size,
+                freq,
+            },
+        );
+        self.maybe_compact();
+    }
+
+    fn set_freq(&mut self, key: &CacheKey, freq: u8) {
+        if let Some(slot) = self.slots.get_mut(key) {
+            slot.freq = freq;
+        }
+    }
+
+    fn remove(&mut self, key: &CacheKey) -> Option<Slot> {
+        let slot = self.slots.remove(key)?;
+        self.bytes[slot.queue] -= slot.size;
+        Some(slot)
+    }
+
+    /// Live entry at the front of `queue`
+    fn front(&mut self, queue: usize) -> Option<(CacheKey, Slot)> {
+        loop {
+            let (key, stamp) = self.queues[queue].front()?;
+            match self.slots.get(key) {
+                Some(slot) if slot.stamp == *stamp => return Some((key.clone(), *slot)),
+                _ => {
+                    self.queues[queue].pop_front();
+                }
+            }
+        }
+    }
+
+    fn maybe_compact(&mut self) {
+        let elements: usize = self.queues.iter().map(|q| q.len()).sum();
+        if elements > 1024 && elements > self.slots.len() * 2 {
+            let slots = &self.slots;
+            for queue in &mut self.queues {
+                queue.retain(|(key, stamp)| slots.get(key).is_some_and(|s| s.stamp == *stamp));
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        *self = Self::new(self.queues.len());
+    }
+}
+
+// =============================================================================
+// W-TinyLFU
+// =============================================================================
+
+/// Count-min sketch of 4-bit access counters with periodic aging
+struct FrequencySketch {
+    table: Vec<u8>,
+    mask: usize,
+    additions: usize,
+    sample_size: usize,
+}
+
+impl FrequencySketch {
+    const DEPTH: usize = 4;
+    const SEEDS: [u64; 4] = [
+        0xc3a5_c85c_97cb_3127,
+        0xb492_b66f_be98_f273,
+        0x9ae1_6a3b_2f90_404f,
+        0xcbf2_9ce4_8422_2325,
+    ];
+
+    fn new(width: usize) -> Self {
+        let width = width.next_power_of_two();
+        Self {
+            table: vec![0; width * Self::DEPTH],
+            mask: width - 1,
+            additions: 0,
+            sample_size: width * 10,
+        }
+    }
+
+    fn index(&self, hash: u64, row: usize) -> usize {
+        let h = (hash ^ Self::SEEDS[row]).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        row * (self.mask + 1) + ((h >> 32) as usize & self.mask)
+    }
+
+    fn increment(&mut self, hash: u64) {
+        for row in 0..Self::DEPTH {
+            let i = self.index(hash, row);
+            if self.table[i] < 15 {
+                self.table[i] += 1;
+            }
+        }
+        self.additions += 1;
+        if self.additions >= self.sample_size {
+            // Halve every counter so old popularity fades
+            for counter in &mut self.table {
+                *counter >>= 1;
+            }
+            self.additions /= 2;
+        }
+    }
+
+    fn frequency(&self, hash: u64) -> u8 {
+        (0..Self::DEPTH)
+            .map(|row| self.table[self.index(hash, row)])
+            .min()
+            .unwrap_or(0)
+    }
+}
+
+const WINDOW: usize = 0;
+const PROBATION: usize = 1;
+const PROTECTED: usize = 2;
+
+struct TinyLfuState {
+    queues: QueueSet,
+    sketch: FrequencySketch,
+}
+
+/// W-TinyLFU: LRU window, segmented LRU main space and a frequency filter
+pub struct TinyLfuEvictor {
+    state: Mutex<TinyLfuState>,
+    /// Byte budget of the window (1% of capacity)
+    window_bytes: u64,
+    /// Byte budget of the main space (the rest of the capacity)
+    main_bytes: u64,
+    /// Byte budget of the protected segment (80% of the main space)
+    protected_bytes: u64,
+}
+
+impl TinyLfuEvictor {
+    /// Create a W-TinyLFU evictor for a tier of `capacity` bytes
+    pub fn new(capacity: u64) -> Self {
+        // One counter per expected 4KB entry, within 1K..1M counters per row
+        let width = (capacity / 4096).clamp(1024, 1 << 20) as usize;
+        let window_bytes = (capacity / 100).max(1);
+        Self {
+            state: Mutex::new(TinyLfuState {
+                queues: QueueSet::new(3),
+                sketch: FrequencySketch::new(width),
+            }),
+            window_bytes,
+            main_bytes: capacity.saturating_sub(window_bytes),
+            protected_bytes: capacity.saturating_sub(window_bytes) / 5 * 4,
+        }
+    }
+
+    /// Record a hit under the lock, moving the entry up its queues
+    fn touch(&self, state: &mut TinyLfuState, key: &CacheKey) {
+        state.sketch.increment(key.combined_hash());
+        let Some(slot) = state.queues.get(key) else {
+            return;
+        };
+        match slot.queue {
+            PROBATION => {
+                state.queues.push(key, PROTECTED, slot.size, 0);
+                // Demote protected entries beyond its budget
+                while state.queues.bytes[PROTECTED] > self.protected_bytes {
+                    let Some((demoted, slot)) = state.queues.front(PROTECTED) else {
+                        break;
+                    };
+                    state.queues.push(&demoted, PROBATION, slot.size, 0);
+                }
+            }
+            queue => state.queues.push(key, queue, slot.size, 0),
+        }
+    }
+}
+
+impl Evictor for TinyLfuEvictor {
+    fn name(&self) -> &'static str {
+        "w-tinylfu"
+    }
+
+    fn on_insert(&self, key: &CacheKey, size: u64) {
+        let mut state = self.state.lock();
+        match state.queues.get(key) {
+            Some(slot) => {
+                state.queues.push(key, slot.queue, size, 0);
+                self.touch(&mut state, key);
+            }
+            None => {
+                state.sketch.increment(key.combined_hash());
+                state.queues.push(key, WINDOW, size, 0);
+            }
+        }
+    }
+
+    fn on_access(&self, key: &CacheKey) {
+        if let Some(mut state) = self.state.try_lock() {
+            self.touch(&mut state, key);
+        }
+    }
+
+    fn on_remove(&self, key: &CacheKey) {
+        self.state.lock().queues.remove(key);
+    }
+
+    fn evict(&self) -> Option<CacheKey> {
+        let mut state = self.state.lock();
+        let main_victim = |state: &mut TinyLfuState| {
+            state
+                .queues
+                .front(PROBATION)
+                .or_else(|| state.queues.front(PROTECTED))
+        };
+
+        let victim = loop {
+            if state.queues.bytes[WINDOW] <= self.window_bytes {
+                break main_victim(&mut state)
+                    .or_else(|| state.queues.front(WINDOW))?
+                    .0;
+            }
+
+            // The window's LRU entry moves to the main space while it has
+            // room, and otherwise competes with the main space's victim
+            let (candidate, slot) = state.queues.front(WINDOW)?;
+            let main_used = state.queues.bytes[PROBATION] + state.queues.bytes[PROTECTED];
+            if main_used + slot.size <= self.main_bytes {
+                state.queues.push(&candidate, PROBATION, slot.size, 0);
+                continue;
+            }
+            match main_victim(&mut state) {
+                Some((victim, _))
+                    if state.sketch.frequency(candidate.combined_hash())
+                        > state.sketch.frequency(victim.combined_hash()) =>
+                {
+                    state.queues.push(&candidate, PROBATION, slot.size, 0);
+                    break victim;
+                }
+                _ => break candidate,
+            }
+        };
+        state.queues.remove(&victim);
+        Some(victim)
+    }
+
+    fn clear(&self) {
+        self.state.lock().queues.clear();
+    }
+
+    fn len(&self) -> usize {
+        self.state.lock().queues.slots.len()
+    }
+}
+
+// =============================================================================
+// S3-FIFO
+// =============================================================================
+
+const SMALL: usize = 0;
+const MAIN: usize = 1;
+
+/// Highest access frequency S3-FIFO keeps per entry
+const S3_MAX_FREQ: u8 = 3;
+
+struct S3FifoState {
+    queues: QueueSet,
+    ghost: VecDeque<u64>,
+    ghost_set: HashSet<u64>,
+}
+
+/// S3-FIFO: small, main and ghost FIFO queues
+pub struct S3FifoEvictor {
+    state: Mutex<S3FifoState>,
+    /// Byte budget of the small queue (10% of capacity)
+    small_bytes: u64,
+}
+
+impl S3FifoEvictor {
+    /// Create an S3-FIFO evictor for a tier of `capacity` bytes
+    pub fn new(capacity: u64) -> Self {
+        Self {
+            state: Mutex::new(S3FifoState {
+                queues: QueueSet::new(2),
+                ghost: VecDeque::new(),
+                ghost_set: HashSet::new(),
+            }),
+            small_bytes: (capacity / 10).max(1),
+        }
+    }
+
+    /// Remember an evicted key, keeping at most as many ghosts as entries
+    fn add_ghost(state: &mut S3FifoState, hash: u64) {
+        if state.ghost_set.insert(hash) {
+            state.ghost.push_back(hash);
+        }
+        let limit = state.queues.slots.len().max(64);
+        while state.ghost.len() > limit {
+            if let Some(old) = state.ghost.pop_front() {
+                state.ghost_set.remove(&old);
+            }
+        }
+    }
+}
+
+impl Evictor for S3FifoEvictor {
+    fn name(&self) -> &'static str {
+        "s3-fifo"
+    }
+
+    fn on_insert(&self, key: &CacheKey, size: u64) {
+        let mut state = self.state.lock();
+        if let Some(slot) = state.queues.get(key) {
+            // Replacing keeps the queue position
+            state.queues.bytes[slot.queue] += size;
+            state.queues.bytes[slot.queue] -= slot.size;
+            if let Some(s) = state.queues.slots.get_mut(key) {
+                s.size = size;
+                s.freq = (s.freq + 1).min(S3_MAX_FREQ);
+            }
+            return;
+        }
+        let hash = key.combined_hash();
+        let queue = if state.ghost_set.remove(&hash) {
+            MAIN
+        } else {
+            SMALL
+        };
+        state.queues.push(key, queue, size, 0);
+    }
+
+    fn on_access(&self, key: &CacheKey) {
+        if let Some(mut state) = self.state.try_lock() {
+            if let Some(slot) = state.queues.get(key) {
+                state.queues.set_freq(key, (slot.freq + 1).min(S3_MAX_FREQ));
+            }
+        }
+    }
+
+    fn on_remove(&self, key: &CacheKey) {
+        self.state.lock().queues.remove(key);
+    }
+
+    fn evict(&self) -> Option<CacheKey> {
+        let mut state = self.state.lock();
+        for _ in 0..MAX_EVICT_STEPS {
+            let main_empty = state.queues.front(MAIN).is_none();
+            if state.queues.bytes[SMALL] > self.small_bytes || main_empty {
+                let Some((key, slot)) = state.queues.front(SMALL) else {
+                    break;
+                };
+                if slot.freq > 0 {
+                    // Hit while on probation: keep it
+                    state.queues.push(&key, MAIN, slot.size, 0);
+                    continue;
+                }
+                state.queues.remove(&key);
+                Self::add_ghost(&mut state, key.combined_hash());
+                return Some(key);
+            }
+
+            let (key, slot) = state.queues.front(MAIN)?;
+            if slot.freq > 0 {
+                // Lazy promotion: reinsert with one less credit
+                state.queues.push(&key, MAIN, slot.size, slot.freq - 1);
+                continue;
+            }
+            state.queues.remove(&key);
+            return Some(key);
+        }
+
+        // Every examined entry was hot; evict the oldest anyway
+        let (key, _) = state
+            .queues
+            .front(SMALL)
+            .or_else(|| state.queues.front(MAIN))?;
+        state.queues.remove(&key);
+        Some(key)
+    }
+
+    fn clear(&self) {
+        let mut state = self.state.lock();
+        state.queues.clear();
+        state.ghost.clear();
+        state.ghost_set.clear();
+    }
+
+    fn len(&self) -> usize {
+        self.state.lock().queues.slots.len()
+    }
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(i: usize) -> CacheKey {
+        CacheKey::new("bucket", format!("object-{}", i))
+    }
+
+    fn drain(evictor: &dyn Evictor) -> Vec<CacheKey> {
+        std::iter::from_fn(|| evictor.evict()).collect()
+    }
+
+    #[test]
+    fn test_new_evictor_follows_policy() {
+        let cases = [
+            (EvictionPolicy::lru(), "sampled"),
+            (EvictionPolicy::tiny_lfu(), "w-tinylfu"),
+            (EvictionPolicy::s3_fifo(), "s3-fifo"),
+        ];
+        for (policy, name) in cases {
+            assert_eq!(new_evictor(&policy, 1024 * 1024).name(), name);
+        }
+    }
+
+    #[test]
+    fn test_every_evictor_drains_tracked_keys() {
+        for policy in [
+            EvictionPolicy::lru(),
+            EvictionPolicy::tiny_lfu(),
+            EvictionPolicy::s3_fifo(),
+        ] {
+            let evictor = new_evictor(&policy, 100 * 1024);
+            for i in 0..100 {
+                evictor.on_insert(&key(i), 1024);
+            }
+            evictor.on_insert(&key(7), 2048);
+            evictor.on_remove(&key(3));
+            assert_eq!(evictor.len(), 99);
+
+            let mut drained = drain(evictor.as_ref());
+            drained.sort_by_key(|k| k.key().to_string());
+            drained.dedup();
+            assert_eq!(drained.len(), 99, "{}", evictor.name());
+            assert!(!drained.contains(&key(3)));
+            assert!(evictor.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_sampled_lru_prefers_idle_entries() {
+        let mut policy = EvictionPolicy::lru();
+        policy.sample_size = 64;
+        let evictor = SampledEvictor::new(policy);
+        evictor.on_insert(&key(0), 100);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        for i in 1..10 {
+            evictor.on_insert(&key(i), 100);
+        }
+        // 64 samples out of 10 keys all but certainly include key 0
+        assert_eq!(evictor.evict(), Some(key(0)));
+    }
+
+    #[test]
+    fn test_sampled_ttl_evicts_expired_first() {
+        let mut policy = EvictionPolicy::ttl(std::time::Duration::from_millis(10));
+        policy.sample_size = 64;
+        let evictor = SampledEvictor::new(policy);
+        evictor.on_insert(&key(0), 100);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        evictor.on_insert(&key(1), 100);
+        evictor.on_access(&key(0));
+        assert_eq!(evictor.evict(), Some(key(0)));
+    }
+
+    #[test]
+    fn test_tinylfu_keeps_frequent_keys_through_a_scan() {
+        // Room for about 100 entries of 1KB
+        let evictor = TinyLfuEvictor::new(100 * 1024);
+        for i in 0..50 {
+            evictor.on_insert(&key(i), 1024);
+            for _ in 0..5 {
+                evictor.on_access(&key(i));
+            }
+        }
+        for i in 50..100 {
+            evictor.on_insert(&key(i), 1024);
+        }
+
+        // A one-off scan of new keys, each making room for itself
+        for i in 1000..1500 {
+            evictor.on_insert(&key(i), 1024);
+            evictor.evict();
+        }
+        let survivors = (0..50)
+            .filter(|i| evictor.state.lock().queues.get(&key(*i)).is_some())
+            .count();
+        assert!(survivors >= 45, "only {} hot keys survived", survivors);
+    }
+
+    #[test]
+    fn test_frequency_sketch_ages_counters() {
+        let mut sketch = FrequencySketch::new(16);
+        for _ in 0..10 {
+            sketch.increment(42);
+        }
+        assert!(sketch.frequency(42) >= 10);
+        assert_eq!(sketch.frequency(7), 0);
+
+        for i in 0..sketch.sample_size as u64 {
+            sketch.increment(1000 + i);
+        }
+        assert!(sketch.frequency(42) < 10);
+    }
+
+    #[test]
+    fn test_s3fifo_evicts_one_hit_wonders_first() {
+        let evictor = S3FifoEvictor::new(10 * 1024);
+        for i in 0..10 {
+            evictor.on_insert(&key(i), 1024);
+        }
+        evictor.on_access(&key(0));
+        evictor.on_access(&key(1));
+
+        // New keys arriving at a full tier push out the untouched ones
+        let evicted: Vec<CacheKey> = (10..20)
+            .filter_map(|i| {
+                evictor.on_insert(&key(i), 1024);
+                evictor.evict()
+            })
+            .collect();
+        assert_eq!(evicted.len(), 10);
+        assert!(!evicted.contains(&key(0)));
+        assert!(!evicted.contains(&key(1)));
+    }
+
+    #[test]
+    fn test_s3fifo_ghost_hit_goes_to_main() {
+        let evictor = S3FifoEvictor::new(10 * 1024);
+        evictor.on_insert(&key(0), 1024);
+        assert_eq!(evictor.evict(), Some(key(0)));
+
+        evictor.on_insert(&key(0), 1024);
+        let slot = evictor.state.lock().queues.get(&key(0)).unwrap();
+        assert_eq!(slot.queue, MAIN);
+    }
+
+    #[test]
+    fn test_queue_set_drops_stale_elements() {
+        let mut queues = QueueSet::new(1);
+        for round in 0..10 {
+            for i in 0..500 {
+                queues.push(&key(i), 0, 1, round);
+            }
+        }
+        assert_eq!(queues.slots.len(), 500);
+        assert!(queues.queues[0].len() <= 1500);
+        assert_eq!(queues.bytes[0], 500);
+        assert_eq!(queues.front(0).unwrap().0, key(0));
+    }
+}
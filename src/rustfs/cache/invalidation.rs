@@ -0,0 +1,408 @@
+//! Cross-Node Cache Invalidation
+//!
+//! Every node runs its own [`CacheManager`](super::CacheManager) over a
+//! shared L3. When one node writes or deletes an object it broadcasts the
+//! key and the generation of the write, and the other nodes drop their
+//! copies with an older generation.
+//!
+//! # Generations
+//!
+//! Generations are a Lamport clock: a node receiving an invalidation moves
+//! its own counter past the generation it carries, so its later writes
+//! order after the write it heard about.
+//!
+//! # Transports
+//!
+//! - [`TcpInvalidationTransport`]: newline-delimited JSON over a TCP mesh
+//!   of configured peers
+//! - [`InMemoryInvalidationBus`]: nodes within one process, for testing
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc, Mutex};
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::{debug, info, warn};
+
+use super::entry::CacheKey;
+
+/// How long a publish waits for a peer to accept a connection or a message
+const PEER_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Invalidations received but not yet applied
+const INBOX_CAPACITY: usize = 4096;
+
+/// Number of key stripes counting received invalidations
+const EPOCH_STRIPES: usize = 256;
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// A write or delete of a key on another node
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Invalidation {
+    /// Bucket of the key
+    pub bucket: String,
+    /// Object key
+    pub key: String,
+    /// Generation of the write; copies with an older generation are stale
+    pub generation: u64,
+    /// When the invalidation was sent, in milliseconds since the epoch
+    pub sent_at_ms: u64,
+}
+
+impl Invalidation {
+    /// Create an invalidation of `key` sent now
+    pub fn new(key: &CacheKey, generation: u64) -> Self {
+        Self {
+            bucket: key.bucket().to_string(),
+            key: key.key().to_string(),
+            generation,
+            sent_at_ms: now_ms(),
+        }
+    }
+
+    /// Get the invalidated cache key
+    pub fn cache_key(&self) -> CacheKey {
+        CacheKey::new(self.bucket.clone(), self.key.clone())
+    }
+
+    /// Time since the invalidation was sent, by the local clock
+    pub fn lag(&self) -> Duration {
+        Duration::from_millis(now_ms().saturating_sub(self.sent_at_ms))
+    }
+}
+
+/// Carries invalidations between nodes
+///
+/// A transport never delivers a node's own invalidations back to it.
+#[async_trait]
+pub trait InvalidationTransport: Send + Sync {
+    /// Send an invalidation to every other node
+    async fn publish(&self, invalidation: &Invalidation) -> crate::error::Result<()>;
+
+    /// Wait for the next invalidation from another node
+    ///
+    /// Returns `None` once no more invalidations can arrive.
+    async fn next(&self) -> crate::error::Result<Option<Invalidation>>;
+}
+
+/// Counts the invalidations received per key stripe
+///
+/// An L3 read compares the count before and after to tell if another node
+/// may have written the key while the read was in flight.
+pub(crate) struct InvalidationEpochs {
+    stripes: Vec<AtomicU64>,
+}
+
+impl InvalidationEpochs {
+    pub(crate) fn new() -> Self {
+        Self {
+            stripes: (0..EPOCH_STRIPES).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    /// Get the count of invalidations received for `key`'s stripe
+    pub(crate) fn epoch(&self, key: &CacheKey) -> u64 {
+        self.stripes[key.shard_index(EPOCH_STRIPES)].load(Ordering::Acquire)
+    }
+
+    /// Count an invalidation received for `key`
+    pub(crate) fn bump(&self, key: &CacheKey) {
+        self.stripes[key.shard_index(EPOCH_STRIPES)].fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+// =============================================================================
+// In-Process Transport
+// =============================================================================
+
+/// Invalidation bus connecting the nodes of one process
+pub struct InMemoryInvalidationBus {
+    sender: broadcast::Sender<(u64, Invalidation)>,
+    next_node: AtomicU64,
+}
+
+impl InMemoryInvalidationBus {
+    /// Create a bus buffering up to `capacity` invalidations per node
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self {
+            sender,
+            next_node: AtomicU64::new(0),
+        }
+    }
+
+    /// Connect a new node to the bus
+    pub fn connect(&self) -> InMemoryInvalidationTransport {
+        InMemoryInvalidationTransport {
+            node: self.next_node.fetch_add(1, Ordering::Relaxed),
+            sender: self.sender.clone(),
+            receiver: Mutex::new(self.sender.subscribe()),
+        }
+    }
+}
+
+impl Default for InMemoryInvalidationBus {
+    fn default() -> Self {
+        Self::new(INBOX_CAPACITY)
+    }
+}
+
+/// One node's connection to an [`InMemoryInvalidationBus`]
+pub struct InMemoryInvalidationTransport {
+    node: u64,
+    sender: broadcast::Sender<(u64, Invalidation)>,
+    receiver: Mutex<broadcast::Receiver<(u64, Invalidation)>>,
+}
+
+#[async_trait]
+impl InvalidationTransport for InMemoryInvalidationTransport {
+    async fn publish(&self, invalidation: &Invalidation) -> crate::error::Result<()> {
+        // No receivers is not an error: there are no other nodes
+        let _ = self.sender.send((self.node, invalidation.clone()));
+        Ok(())
+    }
+
+    async fn next(&self) -> crate::error::Result<Option<Invalidation>> {
+        let mut receiver = self.receiver.lock().await;
+        loop {
+            match receiver.recv().await {
+                Ok((node, invalidation)) if node != self.node => return Ok(Some(invalidation)),
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!("Invalidation bus dropped {} messages", missed);
+                }
+                Err(broadcast::error::RecvError::Closed) => return Ok(None),
+            }
+        }
+    }
+}
+
+// =============================================================================
+// TCP Transport
+// =============================================================================
+
+/// Invalidation transport over TCP connections to a set of peers
+///
+/// Each node listens for its peers and sends every invalidation to each
+/// peer over a connection it opens on first use and reopens after errors.
+/// Peers that cannot be reached miss the invalidation.
+pub struct TcpInvalidationTransport {
+    local_addr: SocketAddr,
+    /// Outgoing connections, `None` until opened
+    peers: Mutex<HashMap<SocketAddr, Option<TcpStream>>>,
+    inbox: Mutex<mpsc::Receiver<Invalidation>>,
+    acceptor: JoinHandle<()>,
+}
+
+impl TcpInvalidationTransport {
+    /// Listen on `addr` and send invalidations to `peers`
+    pub async fn bind(addr: SocketAddr, peers: Vec<SocketAddr>) -> crate::error::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        let (sender, inbox) = mpsc::channel(INBOX_CAPACITY);
+        info!(
+            "Invalidation transport listening on {} with {} peers",
+            local_addr,
+            peers.len()
+        );
+
+        Ok(Self {
+            local_addr,
+            peers: Mutex::new(peers.into_iter().map(|peer| (peer, None)).collect()),
+            inbox: Mutex::new(inbox),
+            acceptor: tokio::spawn(Self::accept(listener, sender)),
+        })
+    }
+
+    /// Address the transport listens on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Start sending invalidations to `peer`
+    pub async fn add_peer(&self, peer: SocketAddr) {
+        self.peers.lock().await.entry(peer).or_insert(None);
+    }
+
+    /// Accept peers and forward what they send to the inbox
+    ///
+    /// The readers of accepted connections stop with this task.
+    async fn accept(listener: TcpListener, inbox: mpsc::Sender<Invalidation>) {
+        let mut readers = JoinSet::new();
+        loop {
+            while readers.try_join_next().is_some() {}
+            let (stream, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    warn!("Failed to accept invalidation peer: {}", e);
+                    continue;
+                }
+            };
+            let inbox = inbox.clone();
+            readers.spawn(async move {
+                let mut lines = BufReader::new(stream).lines();
+                loop {
+                    match lines.next_line().await {
+                        Ok(Some(line)) => match serde_json::from_str(&line) {
+                            Ok(invalidation) => {
+                                if inbox.send(invalidation).await.is_err() {
+                                    return;
+                                }
+                            }
+                            Err(e) => warn!("Malformed invalidation from {}: {}", peer, e),
+                        },
+                        Ok(None) => break,
+                        Err(e) => {
+                            debug!("Invalidation peer {} dropped: {}", peer, e);
+                            break;
+                        }
+                    }
+                }
+            });
+        }
+    }
+
+    /// Send one message to `peer`, connecting first if needed
+    async fn send(
+        peer: SocketAddr,
+        connection: &mut Option<TcpStream>,
+        message: &[u8],
+    ) -> std::io::Result<()> {
+        let timed_out = || std::io::Error::from(std::io::ErrorKind::TimedOut);
+        if connection.is_none() {
+            let stream = tokio::time::timeout(PEER_TIMEOUT, TcpStream::connect(peer))
+                .await
+                .map_err(|_| timed_out())??;
+            stream.set_nodelay(true)?;
+            *connection = Some(stream);
+        }
+        let Some(stream) = connection.as_mut() else {
+            return Err(std::io::ErrorKind::NotConnected.into());
+        };
+        let result = tokio::time::timeout(PEER_TIMEOUT, stream.write_all(message))
+            .await
+            .map_err(|_| timed_out())
+            .and_then(|r| r);
+        if result.is_err() {
+            *connection = None;
+        }
+        result
+    }
+}
+
+impl Drop for TcpInvalidationTransport {
+    fn drop(&mut self) {
+        self.acceptor.abort();
+    }
+}
+
+#[async_trait]
+impl InvalidationTransport for TcpInvalidationTransport {
+    async fn publish(&self, invalidation: &Invalidation) -> crate::error::Result<()> {
+        let mut message = serde_json::to_vec(invalidation)
+            .map_err(|e| crate::error::Error::Internal(e.to_string()))?;
+        message.push(b'\n');
+
+        let mut failed = 0;
+        let mut peers = self.peers.lock().await;
+        for (peer, connection) in peers.iter_mut() {
+            // A connection the peer closed since the last publish fails
+            // only once written to, so retry once on a fresh one
+            let mut result = Self::send(*peer, connection, &message).await;
+            if result.is_err() {
+                result = Self::send(*peer, connection, &message).await;
+            }
+            if let Err(e) = result {
+                debug!("Failed to send invalidation to {}: {}", peer, e);
+                failed += 1;
+            }
+        }
+
+        if failed > 0 {
+            return Err(crate::error::Error::Internal(format!(
+                "Invalidation of {}/{} did not reach {} of {} peers",
+                invalidation.bucket,
+                invalidation.key,
+                failed,
+                peers.len()
+            )));
+        }
+        Ok(())
+    }
+
+    async fn next(&self) -> crate::error::Result<Option<Invalidation>> {
+        Ok(self.inbox.lock().await.recv().await)
+    }
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_in_memory_bus_skips_own_messages() {
+        let bus = InMemoryInvalidationBus::default();
+        let a = bus.connect();
+        let b = bus.connect();
+
+        let key = CacheKey::new("bucket", "object");
+        a.publish(&Invalidation::new(&key, 7)).await.unwrap();
+        b.publish(&Invalidation::new(&key, 9)).await.unwrap();
+
+        let received = b.next().await.unwrap().unwrap();
+        assert_eq!(received.cache_key(), key);
+        assert_eq!(received.generation, 7);
+        assert_eq!(a.next().await.unwrap().unwrap().generation, 9);
+    }
+
+    #[tokio::test]
+    async fn test_tcp_transport_delivers_to_peers() {
+        let local = "127.0.0.1:0".parse().unwrap();
+        let a = TcpInvalidationTransport::bind(local, vec![]).await.unwrap();
+        let b = TcpInvalidationTransport::bind(local, vec![a.local_addr()])
+            .await
+            .unwrap();
+        a.add_peer(b.local_addr()).await;
+
+        let key = CacheKey::new("bucket", "object");
+        for generation in 1..=3 {
+            a.publish(&Invalidation::new(&key, generation))
+                .await
+                .unwrap();
+        }
+        b.publish(&Invalidation::new(&key, 10)).await.unwrap();
+
+        for generation in 1..=3 {
+            let received = b.next().await.unwrap().unwrap();
+            assert_eq!(received.generation, generation);
+            assert!(received.lag() < Duration::from_secs(5));
+        }
+        assert_eq!(a.next().await.unwrap().unwrap().generation, 10);
+
+        // An unreachable peer fails the publish but not the others
+        let closed = TcpListener::bind(local)
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        a.add_peer(closed).await;
+        assert!(a.publish(&Invalidation::new(&key, 4)).await.is_err());
+        assert_eq!(b.next().await.unwrap().unwrap().generation, 4);
+    }
+}
@@ -10,12 +10,18 @@
 //! # Design
 //!
 //! - ShardedMap with 1024 shards for minimal lock contention
-//! - LRU-K eviction with frequency weighting
+//! - Pluggable eviction (sampled, W-TinyLFU or S3-FIFO) with bounded cost
 //! - Capacity-based eviction with configurable high/low watermarks
+//! - Optional compression; capacity counts the bytes as stored
 
 use std::sync::atomic::{AtomicU64, Ordering};
 
-use super::entry::{CacheEntry, CacheKey};
+use tracing::warn;
+
+use super::compression::{CompressionConfig, CompressionManager};
+use super::entry::{CacheEntry, CacheKey, EntryMetadata};
+use super::eviction::{new_evictor, Evictor};
+use super::policy::EvictionPolicy;
 use super::shard::ShardedMap;
 use super::{DEFAULT_L1_CAPACITY, SHARD_COUNT};
 
@@ -49,8 +55,14 @@ pub struct L1Cache {
     storage: ShardedMap<CacheKey, CacheEntry, SHARD_COUNT>,
     /// Configuration
     config: L1Config,
-    /// Current size in bytes
+    /// Eviction algorithm
+    evictor: Box<dyn Evictor>,
+    /// Compression for stored data, if enabled
+    compression: Option<CompressionManager>,
+    /// Current size in bytes, as stored
     current_size: AtomicU64,
+    /// Current size in bytes, uncompressed
+    logical_size: AtomicU64,
     /// Hit count
     hits: AtomicU64,
     /// Miss count
@@ -69,93 +81,189 @@ impl L1Cache {
     pub fn with_config(config: L1Config) -> Self {
         Self {
             storage: ShardedMap::new(),
+            evictor: new_evictor(&EvictionPolicy::default(), config.capacity),
+            compression: None,
             config,
             current_size: AtomicU64::new(0),
+            logical_size: AtomicU64::new(0),
             hits: AtomicU64::new(0),
             misses: AtomicU64::new(0),
             evictions: AtomicU64::new(0),
         }
     }
 
+    /// Use the eviction algorithm selected by `policy`
+    pub fn with_eviction(mut self, policy: &EvictionPolicy) -> Self {
+        self.evictor = new_evictor(policy, self.config.capacity);
+        for i in 0..SHARD_COUNT {
+            for (key, entry) in self.storage.shard(i).entries() {
+                self.evictor.on_insert(&key, entry.stored_size());
+            }
+        }
+        self
+    }
+
+    /// Compress entries stored from now on with `config`
+    pub fn with_compression(mut self, config: CompressionConfig) -> Self {
+        self.compression = Some(CompressionManager::with_config(config));
+        self
+    }
+
+    /// Get the name of the eviction algorithm
+    pub fn eviction_algorithm(&self) -> &'static str {
+        self.evictor.name()
+    }
+
     /// Get an entry from the cache
     pub fn get(&self, key: &CacheKey) -> Option<CacheEntry> {
-        let entry = self.storage.get(key);
-
-        match &entry {
-            Some(e) => {
-                // Check expiration
-                if e.is_expired() {
-                    // Remove expired entry
-                    let size = e.size();
-                    self.storage.remove(key, size);
-                    self.current_size.fetch_sub(size, Ordering::Relaxed);
-                    self.misses.fetch_add(1, Ordering::Relaxed);
-                    return None;
-                }
-                // Record access for LRU tracking
-                e.record_access();
+        let Some(entry) = self.storage.get(key) else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return None;
+        };
+
+        // Check expiration
+        if entry.is_expired() {
+            // Remove expired entry
+            self.remove(key);
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+
+        match entry.decompressed() {
+            Ok(entry) => {
+                // Record access for eviction ordering, and on the stored
+                // entry, which the returned copy does not share
+                entry.record_access();
+                self.storage.update(key, |stored| {
+                    stored.record_access();
+                });
+                self.evictor.on_access(key);
                 self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(entry)
             }
-            None => {
+            Err(e) => {
+                warn!(
+                    "Dropping corrupt L1 entry {}/{}: {}",
+                    key.bucket(),
+                    key.key(),
+                    e
+                );
+                self.remove(key);
                 self.misses.fetch_add(1, Ordering::Relaxed);
+                None
             }
         }
+    }
 
-        entry
+    /// Get an entry without recording a hit or checking expiry
+    pub fn peek(&self, key: &CacheKey) -> Option<CacheEntry> {
+        self.storage.get(key)?.decompressed().ok()
+    }
+
+    /// Mark an entry as written to L3 if it is still at `generation`
+    pub fn mark_clean(&self, key: &CacheKey, generation: u64) {
+        self.storage.update(key, |entry| {
+            if entry.metadata.generation() == generation {
+                entry.metadata.mark_clean();
+            }
+        });
     }
 
     /// Put an entry into the cache
     pub fn put(&self, key: CacheKey, entry: CacheEntry) -> bool {
-        let size = entry.size();
+        self.put_evicting(key, entry).0
+    }
+
+    /// Put an entry into the cache, returning the entries evicted for it
+    ///
+    /// Lets the caller hand the victims to a lower tier instead of
+    /// dropping them. Victims keep the compression they were stored with.
+    pub fn put_evicting(
+        &self,
+        key: CacheKey,
+        entry: CacheEntry,
+    ) -> (bool, Vec<(CacheKey, CacheEntry)>) {
+        let entry = match &self.compression {
+            Some(compression) => entry.compressed(compression),
+            None => match entry.decompressed() {
+                Ok(entry) => entry,
+                Err(e) => {
+                    warn!(
+                        "Rejecting corrupt entry {}/{}: {}",
+                        key.bucket(),
+                        key.key(),
+                        e
+                    );
+                    return (false, Vec::new());
+                }
+            },
+        };
+        let size = entry.stored_size();
 
         // Check if we need to evict
-        if self.should_evict() {
-            self.evict();
-        }
+        let victims = if self.should_evict() {
+            self.evict()
+        } else {
+            Vec::new()
+        };
 
         // Check if entry fits
         if size > self.config.capacity {
-            return false;
+            return (false, victims);
         }
 
         // Insert (may replace existing)
-        let old = self.storage.insert(key, entry, size);
-
-        if let Some(old_entry) = old {
-            // Update size delta
-            let old_size = old_entry.size();
-            if size > old_size {
-                self.current_size
-                    .fetch_add(size - old_size, Ordering::Relaxed);
-            } else {
-                self.current_size
-                    .fetch_sub(old_size - size, Ordering::Relaxed);
-            }
-        } else {
-            self.current_size.fetch_add(size, Ordering::Relaxed);
+        self.evictor.on_insert(&key, size);
+        self.add_bytes(&entry);
+        if let Some(old_entry) = self.storage.insert(key, entry, size) {
+            self.sub_bytes(&old_entry);
         }
 
-        true
+        (true, victims)
     }
 
     /// Remove an entry from the cache
+    ///
+    /// The entry is returned as stored, possibly compressed.
     pub fn remove(&self, key: &CacheKey) -> Option<CacheEntry> {
         // Need to get size first
         if let Some(entry) = self.storage.get(key) {
-            let size = entry.size();
-            if let Some(removed) = self.storage.remove(key, size) {
-                self.current_size.fetch_sub(size, Ordering::Relaxed);
+            if let Some(removed) = self.storage.remove(key, entry.stored_size()) {
+                self.sub_bytes(&removed);
+                self.evictor.on_remove(key);
                 return Some(removed);
             }
         }
         None
     }
 
+    /// Count an entry's bytes into the cache size
+    fn add_bytes(&self, entry: &CacheEntry) {
+        self.current_size
+            .fetch_add(entry.stored_size(), Ordering::Relaxed);
+        self.logical_size.fetch_add(entry.size(), Ordering::Relaxed);
+    }
+
+    /// Count an entry's bytes out of the cache size
+    fn sub_bytes(&self, entry: &CacheEntry) {
+        self.current_size
+            .fetch_sub(entry.stored_size(), Ordering::Relaxed);
+        self.logical_size.fetch_sub(entry.size(), Ordering::Relaxed);
+    }
+
     /// Check if cache contains a key
     pub fn contains(&self, key: &CacheKey) -> bool {
         self.storage.contains_key(key)
     }
 
+    /// List the keys and metadata of all cached entries
+    pub fn metadata(&self) -> Vec<(CacheKey, EntryMetadata)> {
+        (0..SHARD_COUNT)
+            .flat_map(|i| self.storage.shard(i).entries())
+            .map(|(key, entry)| (key, entry.metadata))
+            .collect()
+    }
+
     /// Check if eviction should be triggered
     fn should_evict(&self) -> bool {
         let current = self.current_size.load(Ordering::Relaxed) as f64;
@@ -171,52 +279,38 @@ impl L1Cache {
     }
 
     /// Evict entries until low watermark is reached
-    fn evict(&self) {
-        // Collect eviction candidates from each shard
-        let mut candidates: Vec<(CacheKey, f64, u64)> = Vec::new();
-
-        for i in 0..SHARD_COUNT {
-            let shard = self.storage.shard(i);
-            let entries = shard.entries();
-
-            for (key, entry) in entries {
-                if entry.is_expired() {
-                    // Always evict expired entries
-                    candidates.push((key, f64::MAX, entry.size()));
-                } else {
-                    let score = entry.metadata.eviction_score();
-                    candidates.push((key, score, entry.size()));
-                }
-            }
-        }
-
-        // Sort by eviction score (highest first = most evictable)
-        candidates.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-
-        // Evict until low watermark
-        let mut evicted = 0;
-        for (key, _, size) in candidates {
-            if !self.should_continue_eviction() {
+    ///
+    /// Each victim costs the evictor's bounded selection plus one shard
+    /// lookup, independent of the number of entries.
+    fn evict(&self) -> Vec<(CacheKey, CacheEntry)> {
+        let mut victims = Vec::new();
+        while self.should_continue_eviction() && victims.len() < self.config.eviction_batch_size {
+            let Some(key) = self.evictor.evict() else {
                 break;
-            }
-
-            if self.storage.remove(&key, size).is_some() {
-                self.current_size.fetch_sub(size, Ordering::Relaxed);
+            };
+            // The victim may have been removed concurrently
+            let Some(entry) = self.storage.get(&key) else {
+                continue;
+            };
+            if let Some(removed) = self.storage.remove(&key, entry.stored_size()) {
+                self.sub_bytes(&removed);
                 self.evictions.fetch_add(1, Ordering::Relaxed);
-                evicted += 1;
-
-                if evicted >= self.config.eviction_batch_size {
-                    break;
-                }
+                victims.push((key, removed));
             }
         }
+        victims
     }
 
-    /// Get current size in bytes
+    /// Get current size in bytes, as stored
     pub fn size(&self) -> u64 {
         self.current_size.load(Ordering::Relaxed)
     }
 
+    /// Get current size in bytes, uncompressed
+    pub fn logical_size(&self) -> u64 {
+        self.logical_size.load(Ordering::Relaxed)
+    }
+
     /// Get capacity
     pub fn capacity(&self) -> u64 {
         self.config.capacity
@@ -261,7 +355,9 @@ impl L1Cache {
     /// Clear the cache
     pub fn clear(&self) {
         self.storage.clear();
+        self.evictor.clear();
         self.current_size.store(0, Ordering::Relaxed);
+        self.logical_size.store(0, Ordering::Relaxed);
     }
 
     /// Get utilization percentage
@@ -279,8 +375,10 @@ impl Default for L1Cache {
 /// L1 cache statistics
 #[derive(Debug, Clone)]
 pub struct L1Stats {
-    /// Current size in bytes
+    /// Current size in bytes, as stored
     pub size: u64,
+    /// Current size in bytes, uncompressed
+    pub logical_size: u64,
     /// Capacity in bytes
     pub capacity: u64,
     /// Number of entries
@@ -302,6 +400,7 @@ impl L1Cache {
     pub fn stats(&self) -> L1Stats {
         L1Stats {
             size: self.size(),
+            logical_size: self.logical_size(),
             capacity: self.capacity(),
             entries: self.len(),
             hits: self.hits(),
@@ -485,6 +584,60 @@ mod tests {
         assert!(cache.evictions() > 0);
     }
 
+    #[test]
+    fn test_l1_cache_put_evicting_returns_victims() {
+        let config = L1Config {
+            capacity: 1000,
+            high_watermark: 0.80,
+            low_watermark: 0.50,
+            eviction_batch_size: 100,
+        };
+        let cache = L1Cache::with_config(config);
+
+        let mut victims = Vec::new();
+        for i in 0..20 {
+            let key = make_key("bucket", &format!("object-{}", i));
+            let (stored, evicted) = cache.put_evicting(key, make_entry(&[i as u8; 100]));
+            assert!(stored);
+            victims.extend(evicted);
+        }
+
+        assert_eq!(victims.len() as u64, cache.evictions());
+        assert_eq!(victims.len() + cache.len(), 20);
+        for (key, entry) in &victims {
+            assert!(!cache.contains(key));
+            assert_eq!(entry.size(), 100);
+            assert!(entry.verify_integrity());
+        }
+    }
+
+    #[test]
+    fn test_l1_cache_compression() {
+        let config = CompressionConfig {
+            min_size_bytes: 512,
+            ..Default::default()
+        };
+        let cache = L1Cache::new().with_compression(config);
+
+        let large = make_key("bucket", "large");
+        let small = make_key("bucket", "small");
+        cache.put(large.clone(), make_entry(&[7u8; 4096]));
+        cache.put(small.clone(), make_entry(b"small"));
+
+        // Stored compressed, returned as written
+        assert!(cache.size() < 4096);
+        assert_eq!(cache.logical_size(), 4096 + 5);
+        let entry = cache.get(&large).unwrap();
+        assert!(!entry.is_compressed());
+        assert_eq!(entry.data().as_ref(), &[7u8; 4096][..]);
+        assert!(entry.verify_integrity());
+        assert_eq!(cache.get(&small).unwrap().data().as_ref(), b"small");
+
+        cache.remove(&large);
+        assert_eq!(cache.size(), 5);
+        assert_eq!(cache.logical_size(), 5);
+    }
+
     #[test]
     fn test_l1_cache_stats() {
         let cache = L1Cache::new();
@@ -560,5 +713,43 @@ mod tests {
 
         // Verify the cache recorded the hits
         assert_eq!(cache.hits(), 10);
+
+        // The stored entry counts every access
+        let metadata = cache.metadata();
+        assert_eq!(metadata.len(), 1);
+        assert_eq!(metadata[0].0, key);
+        assert_eq!(metadata[0].1.access_count(), 11);
+    }
+
+    #[test]
+    fn test_l1_cache_eviction_algorithms() {
+        for policy in [
+            EvictionPolicy::lru(),
+            EvictionPolicy::tiny_lfu(),
+            EvictionPolicy::s3_fifo(),
+        ] {
+            let config = L1Config {
+                capacity: 10_000,
+                high_watermark: 0.9,
+                low_watermark: 0.8,
+                eviction_batch_size: 100,
+            };
+            let cache = L1Cache::with_config(config).with_eviction(&policy);
+
+            let hot = make_key("bucket", "hot");
+            cache.put(hot.clone(), make_entry(&[0u8; 100]));
+            for i in 0..500 {
+                cache.get(&hot);
+                let key = make_key("bucket", &format!("object-{}", i));
+                cache.put(key, make_entry(&[i as u8; 100]));
+            }
+
+            assert!(cache.size() <= 10_000, "{}", cache.eviction_algorithm());
+            assert!(cache.evictions() > 0);
+            assert_eq!(cache.size(), cache.len() as u64 * 100);
+            if policy.algorithm != crate::rustfs::cache::EvictionAlgorithm::Sampled {
+                assert!(cache.contains(&hot), "{}", cache.eviction_algorithm());
+            }
+        }
     }
 }
@@ -1,6 +1,6 @@
 //! L2 Cache - NVMe-based Warm Cache
 //!
-//! Medium-latency cache using memory-mapped files for kernel page cache utilization.
+//! Medium-latency cache storing entries in append-only segment files.
 //!
 //! # Performance Targets
 //!
@@ -9,18 +9,53 @@
 //!
 //! # Design
 //!
-//! - Memory-mapped index file for fast lookups
-//! - Append-only data files for sequential write performance
-//! - Background compaction to reclaim space
+//! - Append-only segment files, rotated at `max_file_size`, for sequential
+//!   write performance
+//! - Checksummed index log (`index.log`) mapping keys to segment locations
+//! - Background compaction rewrites mostly-dead segments to reclaim space
+//!
+//! Entry bytes are appended to the active segment and synced before the
+//! index record naming them is written, so a crash can only lose the newest
+//! entries. When a cache is
+//! opened the index log is replayed up to its first torn record, and every
+//! entry is checked against its `content_hash`; entries whose bytes did not
+//! reach the disk are dropped, as are segments no entry refers to.
+//!
+//! Removed, evicted and overwritten entries leave dead bytes behind in their
+//! segment. The compactor copies the live entries of sealed segments whose
+//! dead fraction reaches `compaction_garbage_ratio` to the active segment,
+//! re-points the index at the copies and then deletes the old file.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info, warn};
+
+use super::compression::{CompressionAlgorithm, CompressionConfig, CompressionManager};
+use super::entry::{CacheEntry, CacheKey, EntryMetadata};
+use super::eviction::{new_evictor, Evictor};
+use super::policy::EvictionPolicy;
+use super::DEFAULT_L2_CAPACITY;
 
-use std::collections::BTreeMap;
-use std::path::PathBuf;
-use std::sync::atomic::{AtomicU64, Ordering};
+/// Index log file name inside the cache directory
+const INDEX_FILE: &str = "index.log";
 
-use parking_lot::RwLock;
+/// Suffix of segment files
+const SEGMENT_SUFFIX: &str = ".seg";
 
-use super::entry::{CacheEntry, CacheKey, EntryMetadata};
-use super::DEFAULT_L2_CAPACITY;
+/// Never rewrite index logs with fewer records than this
+const INDEX_COMPACT_MIN_RECORDS: u64 = 4096;
+
+/// Rewrite the index log once it holds this many records per live entry
+const INDEX_COMPACT_RATIO: u64 = 4;
 
 /// L2 Cache configuration
 #[derive(Debug, Clone)]
@@ -35,6 +70,12 @@ pub struct L2Config {
     pub min_entry_size: usize,
     /// Enable memory mapping
     pub enable_mmap: bool,
+    /// Compact sealed segments whose dead fraction reaches this ratio
+    pub compaction_garbage_ratio: f64,
+    /// Maximum bytes per second copied by the compactor (0 = unlimited)
+    pub compaction_rate_bytes: u64,
+    /// Interval between background compaction passes
+    pub compaction_interval: Duration,
 }
 
 impl Default for L2Config {
@@ -45,14 +86,18 @@ impl Default for L2Config {
             max_file_size: 1024 * 1024 * 1024, // 1GB per file
             min_entry_size: 4 * 1024,          // 4KB minimum
             enable_mmap: true,
+            compaction_garbage_ratio: 0.5,
+            compaction_rate_bytes: 64 * 1024 * 1024, // 64MB/s
+            compaction_interval: Duration::from_secs(60),
         }
     }
 }
 
 /// Index entry for L2 cache
-#[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub struct L2IndexEntry {
+    /// Key of the entry
+    pub key: CacheKey,
     /// File ID containing the data
     pub file_id: u64,
     /// Offset within the file
@@ -63,189 +108,1282 @@ pub struct L2IndexEntry {
     pub metadata: EntryMetadata,
 }
 
+// =============================================================================
+// Index Log
+// =============================================================================
+
+/// One mutation of the L2 index
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum IndexRecord {
+    Put {
+        #[serde(flatten)]
+        entry: StoredEntry,
+    },
+    Remove {
+        #[serde(rename = "b")]
+        bucket: String,
+        #[serde(rename = "k")]
+        key: String,
+    },
+    /// The entry at `generation` was written to L3
+    Clean {
+        #[serde(rename = "b")]
+        bucket: String,
+        #[serde(rename = "k")]
+        key: String,
+        #[serde(rename = "g")]
+        generation: u64,
+    },
+}
+
+/// Index entry as stored in the log (short keys keep records small)
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredEntry {
+    #[serde(rename = "b")]
+    bucket: String,
+    #[serde(rename = "k")]
+    key: String,
+    #[serde(rename = "f")]
+    file_id: u64,
+    #[serde(rename = "o")]
+    offset: u64,
+    #[serde(rename = "l")]
+    size: u64,
+    #[serde(rename = "h")]
+    content_hash: u64,
+    #[serde(rename = "t")]
+    created_at: u64,
+    #[serde(rename = "ttl", default)]
+    ttl_seconds: u32,
+    #[serde(rename = "a", default)]
+    access_count: u32,
+    #[serde(rename = "la", default)]
+    last_access: u64,
+    #[serde(rename = "d", default)]
+    dirty: bool,
+    #[serde(rename = "g", default)]
+    generation: u64,
+    /// Uncompressed size; absent from indexes written without compression
+    #[serde(rename = "n", default)]
+    logical_size: u64,
+    #[serde(rename = "c", default)]
+    compression: CompressionAlgorithm,
+}
+
+impl From<&L2IndexEntry> for StoredEntry {
+    fn from(e: &L2IndexEntry) -> Self {
+        Self {
+            bucket: e.key.bucket().to_string(),
+            key: e.key.key().to_string(),
+            file_id: e.file_id,
+            offset: e.offset,
+            size: e.size,
+            content_hash: e.metadata.content_hash(),
+            created_at: e.metadata.created_at(),
+            ttl_seconds: e.metadata.ttl_seconds(),
+            access_count: e.metadata.access_count(),
+            last_access: e.metadata.last_access(),
+            dirty: e.metadata.is_dirty(),
+            generation: e.metadata.generation(),
+            logical_size: e.metadata.size(),
+            compression: e.metadata.compression(),
+        }
+    }
+}
+
+impl From<StoredEntry> for L2IndexEntry {
+    fn from(e: StoredEntry) -> Self {
+        let logical_size = if e.logical_size > 0 {
+            e.logical_size
+        } else {
+            e.size
+        };
+        let mut metadata = EntryMetadata::restore(
+            logical_size,
+            e.content_hash,
+            e.created_at,
+            e.ttl_seconds,
+            e.access_count,
+            e.last_access,
+        );
+        if e.dirty {
+            metadata.mark_dirty();
+        }
+        if e.generation > 0 {
+            metadata.set_generation(e.generation);
+        }
+        metadata.set_compression(e.compression);
+        Self {
+            metadata,
+            key: CacheKey::new(e.bucket, e.key),
+            file_id: e.file_id,
+            offset: e.offset,
+            size: e.size,
+        }
+    }
+}
+
+/// Encode a record as a checksummed log line
+fn encode_record(record: &IndexRecord) -> std::io::Result<String> {
+    let json = serde_json::to_string(record)?;
+    Ok(format!(
+        "{:08x} {}\n",
+        crc32fast::hash(json.as_bytes()),
+        json
+    ))
+}
+
+/// Decode a log line, returning None if it is torn or corrupt
+fn decode_record(line: &str) -> Option<IndexRecord> {
+    let (crc, json) = line.split_once(' ')?;
+    let crc = u32::from_str_radix(crc, 16).ok()?;
+    if crc32fast::hash(json.as_bytes()) != crc {
+        return None;
+    }
+    serde_json::from_str(json).ok()
+}
+
+/// Append-only log of index mutations
+struct IndexLog {
+    path: PathBuf,
+    file: File,
+    /// Records in the log file, live or superseded
+    records: u64,
+    /// Bytes of whole records in the log file
+    len: u64,
+    /// Set when a failed append could not be undone
+    broken: bool,
+}
+
+impl IndexLog {
+    /// Open the log at `path`, returning it with the entries it describes
+    ///
+    /// Replays up to the first record that fails its checksum and truncates
+    /// the rest, which is what a crash mid-append leaves behind.
+    fn open(path: PathBuf) -> std::io::Result<(Self, HashMap<u64, StoredEntry>)> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let mut entries: HashMap<u64, StoredEntry> = HashMap::new();
+        let mut records = 0u64;
+
+        let mut valid_len = 0u64;
+        let mut reader = BufReader::new(File::open(&path)?);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let read = match reader.read_line(&mut line) {
+                Ok(0) => break,
+                Ok(read) => read,
+                // Invalid UTF-8 is a torn write as well
+                Err(e) if e.kind() == std::io::ErrorKind::InvalidData => break,
+                Err(e) => return Err(e),
+            };
+            let Some(record) = line.strip_suffix('\n').and_then(decode_record) else {
+                break;
+            };
+            match record {
+                IndexRecord::Put { entry } => {
+                    let hash =
+                        CacheKey::new(entry.bucket.as_str(), entry.key.as_str()).combined_hash();
+                    entries.insert(hash, entry);
+                }
+                IndexRecord::Remove { bucket, key } => {
+                    let hash = CacheKey::new(bucket.as_str(), key.as_str()).combined_hash();
+                    if entries
+                        .get(&hash)
+                        .is_some_and(|e| e.bucket == bucket && e.key == key)
+                    {
+                        entries.remove(&hash);
+                    }
+                }
+                IndexRecord::Clean {
+                    bucket,
+                    key,
+                    generation,
+                } => {
+                    let hash = CacheKey::new(bucket.as_str(), key.as_str()).combined_hash();
+                    if let Some(entry) = entries.get_mut(&hash).filter(|e| {
+                        e.bucket == bucket && e.key == key && e.generation == generation
+                    }) {
+                        entry.dirty = false;
+                    }
+                }
+            }
+            records += 1;
+            valid_len += read as u64;
+        }
+
+        let file_len = file.metadata()?.len();
+        if valid_len < file_len {
+            warn!(
+                "Truncating {} bytes of torn or corrupt L2 index at {}",
+                file_len - valid_len,
+                path.display()
+            );
+            file.set_len(valid_len)?;
+        }
+
+        Ok((
+            Self {
+                path,
+                file,
+                records,
+                len: valid_len,
+                broken: false,
+            },
+            entries,
+        ))
+    }
+
+    /// Append a record
+    ///
+    /// A failed write may leave part of a record behind, which would hide
+    /// every later record from replay; it is cut off again, and if that
+    /// fails too the log takes no more appends.
+    fn append(&mut self, record: &IndexRecord) -> std::io::Result<()> {
+        if self.broken {
+            return Err(std::io::Error::other(format!(
+                "L2 index {} is unusable after a failed write",
+                self.path.display()
+            )));
+        }
+        let line = encode_record(record)?;
+        if let Err(e) = self.file.write_all(line.as_bytes()) {
+            if let Err(truncate) = self.file.set_len(self.len) {
+                error!(
+                    "Failed to cut a torn record off L2 index {}: {}",
+                    self.path.display(),
+                    truncate
+                );
+                self.broken = true;
+            }
+            return Err(e);
+        }
+        self.len += line.len() as u64;
+        self.records += 1;
+        Ok(())
+    }
+
+    fn sync(&self) -> std::io::Result<()> {
+        self.file.sync_data()
+    }
+
+    /// Rewrite the log as one record per live entry
+    fn rewrite<'a>(
+        &mut self,
+        entries: impl Iterator<Item = &'a L2IndexEntry>,
+    ) -> std::io::Result<()> {
+        let tmp = self.path.with_extension("log.tmp");
+        let mut records = 0u64;
+        let mut len = 0u64;
+        {
+            let mut out = std::io::BufWriter::new(File::create(&tmp)?);
+            for entry in entries {
+                let record = IndexRecord::Put {
+                    entry: StoredEntry::from(entry),
+                };
+                let line = encode_record(&record)?;
+                out.write_all(line.as_bytes())?;
+                records += 1;
+                len += line.len() as u64;
+            }
+            out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        }
+
+        std::fs::rename(&tmp, &self.path)?;
+        if let Some(dir) = self.path.parent() {
+            File::open(dir)?.sync_all()?;
+        }
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        self.records = records;
+        self.len = len;
+        self.broken = false;
+        Ok(())
+    }
+}
+
+// =============================================================================
+// Segments
+// =============================================================================
+
+/// An append-only segment holding entry bytes
+enum Segment {
+    /// Segment file on disk
+    File(File),
+    /// Segment kept in memory by caches without a directory
+    Memory(RwLock<Vec<u8>>),
+}
+
+impl Segment {
+    fn append(&self, data: &[u8]) -> std::io::Result<()> {
+        match self {
+            Segment::File(file) => (&*file).write_all(data),
+            Segment::Memory(buf) => {
+                buf.write().extend_from_slice(data);
+                Ok(())
+            }
+        }
+    }
+
+    /// Flush appended bytes to stable storage
+    fn sync(&self) -> std::io::Result<()> {
+        match self {
+            Segment::File(file) => file.sync_data(),
+            Segment::Memory(_) => Ok(()),
+        }
+    }
+
+    fn read(&self, offset: u64, size: u64) -> std::io::Result<Vec<u8>> {
+        let mut out = vec![0u8; size as usize];
+        match self {
+            Segment::File(file) => file.read_exact_at(&mut out, offset)?,
+            Segment::Memory(buf) => {
+                let buf = buf.read();
+                let range = offset as usize..(offset + size) as usize;
+                let Some(bytes) = buf.get(range) else {
+                    return Err(std::io::ErrorKind::UnexpectedEof.into());
+                };
+                out.copy_from_slice(bytes);
+            }
+        }
+        Ok(out)
+    }
+}
+
+fn segment_name(file_id: u64) -> String {
+    format!("{:016x}{}", file_id, SEGMENT_SUFFIX)
+}
+
+fn parse_segment_name(name: &str) -> Option<u64> {
+    u64::from_str_radix(name.strip_suffix(SEGMENT_SUFFIX)?, 16).ok()
+}
+
+/// Bytes written to a segment and the part still referenced by the index
+#[derive(Debug, Clone, Copy, Default)]
+struct SegmentUsage {
+    written: u64,
+    live: u64,
+}
+
+impl SegmentUsage {
+    fn dead(&self) -> u64 {
+        self.written - self.live
+    }
+}
+
+/// Paces copies (compaction, warm-state restore) to a byte rate
+pub(crate) struct Throttle {
+    start: Instant,
+    bytes: u64,
+    rate: u64,
+}
+
+impl Throttle {
+    /// Create a throttle for `rate` bytes per second (0 = unthrottled)
+    pub(crate) fn new(rate: u64) -> Self {
+        Self {
+            start: Instant::now(),
+            bytes: 0,
+            rate,
+        }
+    }
+
+    /// Account for `bytes` copied, blocking while ahead of the rate
+    pub(crate) fn consume(&mut self, bytes: u64) {
+        if let Some(ahead) = self.advance(bytes) {
+            std::thread::sleep(ahead);
+        }
+    }
+
+    /// Account for `bytes` copied, sleeping the task while ahead of the rate
+    pub(crate) async fn consume_async(&mut self, bytes: u64) {
+        if let Some(ahead) = self.advance(bytes) {
+            tokio::time::sleep(ahead).await;
+        }
+    }
+
+    /// Record `bytes` and return how far ahead of the rate the copy is
+    fn advance(&mut self, bytes: u64) -> Option<Duration> {
+        if self.rate == 0 {
+            return None;
+        }
+        self.bytes += bytes;
+        let due = Duration::from_secs_f64(self.bytes as f64 / self.rate as f64);
+        due.checked_sub(self.start.elapsed())
+    }
+}
+
+/// Write position of the cache, serializing all mutations
+struct Writer {
+    /// Directory of the segment files, None for in-memory caches
+    dir: Option<PathBuf>,
+    /// Index log, None for in-memory caches
+    log: Option<IndexLog>,
+    /// Current write file ID
+    file_id: u64,
+    /// Current write offset
+    offset: u64,
+    /// Usage of every segment by file ID
+    usage: HashMap<u64, SegmentUsage>,
+}
+
+impl Writer {
+    fn release(&mut self, file_id: u64, size: u64) {
+        if let Some(usage) = self.usage.get_mut(&file_id) {
+            usage.live -= size;
+        }
+    }
+}
+
 /// L2 Cache - NVMe-based warm cache
 pub struct L2Cache {
     /// In-memory index (key -> location)
     index: RwLock<BTreeMap<u64, L2IndexEntry>>,
+    /// Segments by file ID
+    segments: RwLock<HashMap<u64, Arc<Segment>>>,
+    /// Write position and index log
+    writer: Mutex<Writer>,
     /// Configuration
     config: L2Config,
-    /// Current size in bytes
+    /// Eviction algorithm
+    evictor: Box<dyn Evictor>,
+    /// Compression for stored data, if enabled
+    compression: Option<CompressionManager>,
+    /// Current size in bytes, as stored
     current_size: AtomicU64,
-    /// Current write file ID
-    current_file_id: AtomicU64,
-    /// Current write offset
-    current_offset: AtomicU64,
+    /// Current size in bytes, uncompressed
+    logical_size: AtomicU64,
     /// Hit count
     hits: AtomicU64,
     /// Miss count
     misses: AtomicU64,
     /// Eviction count
     evictions: AtomicU64,
+    /// Segments rewritten by the compactor
+    segments_compacted: AtomicU64,
+    /// Live bytes copied by the compactor
+    compaction_bytes_copied: AtomicU64,
+    /// Segment bytes deleted by the compactor
+    compaction_bytes_reclaimed: AtomicU64,
+    /// Compactor shutdown signal
+    compactor_shutdown: AtomicBool,
+    /// Whether entries live in segment files
+    persistent: bool,
 }
 
 impl L2Cache {
-    /// Create a new L2 cache with default configuration
+    /// Create a new in-memory L2 cache with default configuration
     pub fn new() -> Self {
         Self::with_config(L2Config::default())
     }
 
-    /// Create a new L2 cache with custom configuration
+    /// Create a new in-memory L2 cache with custom configuration
+    ///
+    /// Segments are kept in memory and `cache_dir` is not used; see
+    /// [`L2Cache::open`] for a cache that survives restarts.
     pub fn with_config(config: L2Config) -> Self {
+        let segments = HashMap::from([(0, Arc::new(Segment::Memory(RwLock::new(Vec::new()))))]);
         Self {
             index: RwLock::new(BTreeMap::new()),
+            segments: RwLock::new(segments),
+            writer: Mutex::new(Writer {
+                dir: None,
+                log: None,
+                file_id: 0,
+                offset: 0,
+                usage: HashMap::from([(0, SegmentUsage::default())]),
+            }),
+            evictor: new_evictor(&EvictionPolicy::default(), config.capacity),
+            compression: None,
             config,
             current_size: AtomicU64::new(0),
-            current_file_id: AtomicU64::new(0),
-            current_offset: AtomicU64::new(0),
+            logical_size: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            segments_compacted: AtomicU64::new(0),
+            compaction_bytes_copied: AtomicU64::new(0),
+            compaction_bytes_reclaimed: AtomicU64::new(0),
+            compactor_shutdown: AtomicBool::new(false),
+            persistent: false,
+        }
+    }
+
+    /// Open a persistent L2 cache in `config.cache_dir`, creating it if needed
+    ///
+    /// Replays the index log, drops entries whose bytes fail their
+    /// `content_hash` or have expired, deletes unreferenced segments and
+    /// starts writing to a fresh segment.
+    pub fn open(config: L2Config) -> crate::error::Result<Self> {
+        let dir = config.cache_dir.clone();
+        std::fs::create_dir_all(&dir)?;
+        let (mut log, stored) = IndexLog::open(dir.join(INDEX_FILE))?;
+
+        let mut segments = HashMap::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let name = entry?.file_name();
+            if let Some(file_id) = name.to_str().and_then(parse_segment_name) {
+                let file = File::open(dir.join(&name))?;
+                segments.insert(file_id, Arc::new(Segment::File(file)));
+            }
+        }
+
+        let mut index = BTreeMap::new();
+        let mut dropped = 0usize;
+        for (hash, stored) in stored {
+            let entry = L2IndexEntry::from(stored);
+            let valid = !entry.metadata.is_expired()
+                && segments.get(&entry.file_id).is_some_and(|segment| {
+                    segment.read(entry.offset, entry.size).is_ok_and(|data| {
+                        CacheEntry::with_metadata(data.into(), entry.metadata.clone())
+                            .verify_integrity()
+                    })
+                });
+            if valid {
+                index.insert(hash, entry);
+            } else {
+                dropped += 1;
+            }
+        }
+        if dropped > 0 {
+            warn!(
+                "Dropped {} L2 entries that are expired or did not reach {}",
+                dropped,
+                dir.display()
+            );
+        }
+
+        let referenced: std::collections::HashSet<u64> =
+            index.values().map(|e| e.file_id).collect();
+        let mut next_id = 0;
+        for &file_id in segments.keys() {
+            next_id = next_id.max(file_id + 1);
+            if !referenced.contains(&file_id) {
+                std::fs::remove_file(dir.join(segment_name(file_id)))?;
+            }
+        }
+        segments.retain(|file_id, _| referenced.contains(file_id));
+
+        let mut usage: HashMap<u64, SegmentUsage> = HashMap::new();
+        for &file_id in segments.keys() {
+            let written = std::fs::metadata(dir.join(segment_name(file_id)))?.len();
+            usage.entry(file_id).or_default().written = written;
+        }
+        for entry in index.values() {
+            usage.entry(entry.file_id).or_default().live += entry.size;
+        }
+        segments.insert(next_id, Arc::new(Self::create_segment(&dir, next_id)?));
+        usage.insert(next_id, SegmentUsage::default());
+        log.rewrite(index.values())?;
+
+        let size: u64 = index.values().map(|e| e.size).sum();
+        let logical_size: u64 = index.values().map(|e| e.metadata.size()).sum();
+        info!(
+            "Opened L2 cache at {} with {} entries ({} bytes)",
+            dir.display(),
+            index.len(),
+            size
+        );
+
+        let cache = Self {
+            index: RwLock::new(index),
+            segments: RwLock::new(segments),
+            writer: Mutex::new(Writer {
+                dir: Some(dir),
+                log: Some(log),
+                file_id: next_id,
+                offset: 0,
+                usage,
+            }),
+            evictor: new_evictor(&EvictionPolicy::default(), config.capacity),
+            compression: None,
+            config,
+            current_size: AtomicU64::new(size),
+            logical_size: AtomicU64::new(logical_size),
             hits: AtomicU64::new(0),
             misses: AtomicU64::new(0),
             evictions: AtomicU64::new(0),
+            segments_compacted: AtomicU64::new(0),
+            compaction_bytes_copied: AtomicU64::new(0),
+            compaction_bytes_reclaimed: AtomicU64::new(0),
+            compactor_shutdown: AtomicBool::new(false),
+            persistent: true,
+        };
+        cache.track_entries();
+        if size > cache.config.capacity {
+            let mut writer = cache.writer.lock();
+            cache.evict_until_space(&mut writer, 0, None);
+        }
+        Ok(cache)
+    }
+
+    /// Use the eviction algorithm selected by `policy`
+    pub fn with_eviction(mut self, policy: &EvictionPolicy) -> Self {
+        self.evictor = new_evictor(policy, self.config.capacity);
+        self.track_entries();
+        self
+    }
+
+    /// Compress entries stored from now on with `config`
+    ///
+    /// Entries already stored keep their encoding.
+    pub fn with_compression(mut self, config: CompressionConfig) -> Self {
+        self.compression = Some(CompressionManager::with_config(config));
+        self
+    }
+
+    /// Register indexed entries with the evictor, coldest first
+    fn track_entries(&self) {
+        let index = self.index.read();
+        let mut entries: Vec<&L2IndexEntry> = index.values().collect();
+        entries.sort_by_key(|entry| entry.metadata.last_access());
+        for entry in entries {
+            self.evictor.on_insert(&entry.key, entry.size);
         }
     }
 
+    /// Whether entries are stored in segment files
+    ///
+    /// Reads and writes of a persistent cache do blocking file I/O.
+    pub fn is_persistent(&self) -> bool {
+        self.persistent
+    }
+
+    /// Get the name of the eviction algorithm
+    pub fn eviction_algorithm(&self) -> &'static str {
+        self.evictor.name()
+    }
+
+    fn create_segment(dir: &Path, file_id: u64) -> std::io::Result<Segment> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .read(true)
+            .write(true)
+            .open(dir.join(segment_name(file_id)))?;
+        Ok(Segment::File(file))
+    }
+
     /// Get an entry from the cache
     ///
-    /// Note: In a real implementation, this would read from disk.
-    /// Here we return simulated data but preserve metadata for testing.
+    /// Entries whose bytes cannot be read back intact are dropped and
+    /// reported as misses.
     pub fn get(&self, key: &CacheKey) -> Option<CacheEntry> {
         let key_hash = key.combined_hash();
 
-        let index = self.index.read();
-        if let Some(entry) = index.get(&key_hash) {
-            // In real implementation: read from file at (file_id, offset, size)
-            // Record access and track the hit
-            entry.metadata.record_access();
-            self.hits.fetch_add(1, Ordering::Relaxed);
-
-            // Simulate reading data (in real impl, would mmap/read file)
-            let data = vec![0u8; entry.size as usize];
-            // Preserve metadata for promotion logic
-            return Some(CacheEntry::with_metadata(
-                bytes::Bytes::from(data),
+        let location = {
+            let index = self.index.read();
+            index
+                .get(&key_hash)
+                .filter(|entry| entry.key == *key && !entry.metadata.is_expired())
+                .map(|entry| {
+                    entry.metadata.record_access();
+                    (
+                        entry.file_id,
+                        entry.offset,
+                        entry.size,
+                        entry.metadata.clone(),
+                    )
+                })
+        };
+        let Some((file_id, offset, size, metadata)) = location else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return None;
+        };
+
+        match self.read_entry(file_id, offset, size, metadata) {
+            Ok(entry) if entry.verify_integrity() => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                self.evictor.on_access(key);
+                Some(entry)
+            }
+            result => {
+                if let Err(e) = result {
+                    warn!(
+                        "Failed to read L2 entry {}/{}: {}",
+                        key.bucket(),
+                        key.key(),
+                        e
+                    );
+                } else {
+                    warn!("Dropping corrupt L2 entry {}/{}", key.bucket(), key.key());
+                }
+                self.remove(key);
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Get an entry without recording a hit
+    ///
+    /// Returns None if the entry is missing, expired or unreadable.
+    pub fn peek(&self, key: &CacheKey) -> Option<CacheEntry> {
+        let (file_id, offset, size, metadata) = {
+            let index = self.index.read();
+            let entry = index
+                .get(&key.combined_hash())
+                .filter(|entry| entry.key == *key && !entry.metadata.is_expired())?;
+            (
+                entry.file_id,
+                entry.offset,
+                entry.size,
                 entry.metadata.clone(),
-            ));
+            )
+        };
+        self.read_entry(file_id, offset, size, metadata)
+            .ok()
+            .filter(|entry| entry.verify_integrity())
+    }
+
+    /// Get the generation of a cached entry
+    pub fn generation(&self, key: &CacheKey) -> Option<u64> {
+        let index = self.index.read();
+        index
+            .get(&key.combined_hash())
+            .filter(|entry| entry.key == *key)
+            .map(|entry| entry.metadata.generation())
+    }
+
+    /// Mark an entry as written to L3 if it is still at `generation`
+    ///
+    /// The index log records the change, so a reopened cache does not
+    /// write the entry to L3 again. The record is not synced: losing it
+    /// in a power failure only costs a redundant flush.
+    pub fn mark_clean(&self, key: &CacheKey, generation: u64) {
+        let mut writer = self.writer.lock();
+        {
+            let index = self.index.read();
+            let Some(entry) = index.get(&key.combined_hash()).filter(|entry| {
+                entry.key == *key
+                    && entry.metadata.generation() == generation
+                    && entry.metadata.is_dirty()
+            }) else {
+                return;
+            };
+            entry.metadata.mark_clean();
+        }
+
+        if let Some(log) = writer.log.as_mut() {
+            let record = IndexRecord::Clean {
+                bucket: key.bucket().to_string(),
+                key: key.key().to_string(),
+                generation,
+            };
+            if let Err(e) = log.append(&record) {
+                warn!("Failed to log L2 flush: {}", e);
+            }
         }
+        self.maybe_rewrite_log(&mut writer);
+    }
+
+    /// List the keys of all cached entries
+    pub fn keys(&self) -> Vec<CacheKey> {
+        self.index
+            .read()
+            .values()
+            .map(|entry| entry.key.clone())
+            .collect()
+    }
+
+    /// List the keys and metadata of all cached entries
+    pub fn metadata(&self) -> Vec<(CacheKey, EntryMetadata)> {
+        self.index
+            .read()
+            .values()
+            .map(|entry| (entry.key.clone(), entry.metadata.clone()))
+            .collect()
+    }
+
+    /// List the entries not yet written to L3, with their generation and
+    /// uncompressed size
+    pub fn dirty_entries(&self) -> Vec<(CacheKey, u64, u64)> {
+        let index = self.index.read();
+        index
+            .values()
+            .filter(|entry| entry.metadata.is_dirty())
+            .map(|entry| {
+                (
+                    entry.key.clone(),
+                    entry.metadata.generation(),
+                    entry.metadata.size(),
+                )
+            })
+            .collect()
+    }
+
+    /// Get the highest generation among cached entries
+    pub fn max_generation(&self) -> u64 {
+        let index = self.index.read();
+        index
+            .values()
+            .map(|entry| entry.metadata.generation())
+            .max()
+            .unwrap_or(0)
+    }
 
-        self.misses.fetch_add(1, Ordering::Relaxed);
-        None
+    /// Read an entry from its segment and decompress it
+    fn read_entry(
+        &self,
+        file_id: u64,
+        offset: u64,
+        size: u64,
+        metadata: EntryMetadata,
+    ) -> crate::error::Result<CacheEntry> {
+        self.load(file_id, offset, size, metadata)?.decompressed()
+    }
+
+    /// Read an entry's data from its segment, as stored
+    fn load(
+        &self,
+        file_id: u64,
+        offset: u64,
+        size: u64,
+        metadata: EntryMetadata,
+    ) -> std::io::Result<CacheEntry> {
+        let segment = self.segments.read().get(&file_id).cloned();
+        let data = segment
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotFound))?
+            .read(offset, size)?;
+        Ok(CacheEntry::with_metadata(
+            bytes::Bytes::from(data),
+            metadata,
+        ))
     }
 
     /// Check if key exists in cache
     pub fn contains(&self, key: &CacheKey) -> bool {
         let key_hash = key.combined_hash();
         let index = self.index.read();
-        index.contains_key(&key_hash)
+        index.get(&key_hash).is_some_and(|entry| entry.key == *key)
     }
 
     /// Put an entry into the cache
     ///
-    /// Note: In a real implementation, this would write to disk.
+    /// Returns false if the entry is rejected by size or cannot be written.
     pub fn put(&self, key: CacheKey, entry: CacheEntry) -> bool {
-        let size = entry.size();
+        self.insert(key, entry, None)
+    }
+
+    /// Put an entry into the cache, returning the entries evicted for it
+    ///
+    /// Victims are read back from their segments so the caller can hand
+    /// them to a lower tier; a victim that cannot be read is logged and
+    /// left out.
+    pub fn put_evicting(
+        &self,
+        key: CacheKey,
+        entry: CacheEntry,
+    ) -> (bool, Vec<(CacheKey, CacheEntry)>) {
+        let mut victims = Vec::new();
+        let stored = self.insert(key, entry, Some(&mut victims));
+        (stored, victims)
+    }
 
+    fn insert(
+        &self,
+        key: CacheKey,
+        entry: CacheEntry,
+        victims: Option<&mut Vec<(CacheKey, CacheEntry)>>,
+    ) -> bool {
         // Check minimum size
-        if (size as usize) < self.config.min_entry_size {
+        if (entry.size() as usize) < self.config.min_entry_size {
             return false;
         }
 
+        let entry = match &self.compression {
+            Some(compression) => entry.compressed(compression),
+            None => match entry.decompressed() {
+                Ok(entry) => entry,
+                Err(e) => {
+                    warn!("Failed to decompress L2 entry: {}", e);
+                    return false;
+                }
+            },
+        };
+        let size = entry.stored_size();
+
         // Check if entry fits
         if size > self.config.capacity {
             return false;
         }
 
+        let mut writer = self.writer.lock();
+
         // Check capacity and evict if needed
         if self.current_size.load(Ordering::Relaxed) + size > self.config.capacity {
-            self.evict_until_space(size);
+            self.evict_until_space(&mut writer, size, victims);
         }
 
-        let key_hash = key.combined_hash();
-
-        // Allocate space
-        let offset = self.current_offset.fetch_add(size, Ordering::Relaxed);
-        let file_id = self.current_file_id.load(Ordering::Relaxed);
+        match self.append(&mut writer, key, entry) {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("Failed to write L2 entry: {}", e);
+                false
+            }
+        }
+    }
 
-        // Check if we need to rotate to a new file
-        if offset + size > self.config.max_file_size {
-            self.current_file_id.fetch_add(1, Ordering::Relaxed);
-            self.current_offset.store(size, Ordering::Relaxed);
+    /// Append bytes to the active segment, returning their location
+    fn write_bytes(&self, writer: &mut Writer, data: &[u8]) -> std::io::Result<(u64, u64)> {
+        let size = data.len() as u64;
+
+        // Rotate to a new segment if this one is full
+        if writer.offset > 0 && writer.offset + size > self.config.max_file_size {
+            let file_id = writer.file_id + 1;
+            let segment = match &writer.dir {
+                Some(dir) => Self::create_segment(dir, file_id)?,
+                None => Segment::Memory(RwLock::new(Vec::new())),
+            };
+            self.segments.write().insert(file_id, Arc::new(segment));
+            writer.usage.insert(file_id, SegmentUsage::default());
+            writer.file_id = file_id;
+            writer.offset = 0;
         }
 
-        // In real implementation: write data to file
-        // For now, just update index
+        let segment = self.segments.read()[&writer.file_id].clone();
+        segment.append(data)?;
+
+        let location = (writer.file_id, writer.offset);
+        writer.offset += size;
+        let usage = writer.usage.entry(writer.file_id).or_default();
+        usage.written += size;
+        usage.live += size;
+        Ok(location)
+    }
+
+    /// Append an entry to the active segment and index it
+    fn append(&self, writer: &mut Writer, key: CacheKey, entry: CacheEntry) -> std::io::Result<()> {
+        let size = entry.stored_size();
+        let logical_size = entry.size();
+        let (file_id, offset) = self.write_bytes(writer, entry.data())?;
+        if writer.log.is_some() {
+            // The index record must not name bytes that may not be on disk
+            let segment = self.segments.read()[&file_id].clone();
+            if let Err(e) = segment.sync() {
+                writer.release(file_id, size);
+                return Err(e);
+            }
+        }
 
         let index_entry = L2IndexEntry {
+            key,
             file_id,
             offset,
             size,
-            metadata: entry.metadata.clone(),
+            metadata: entry.metadata,
         };
+        if let Some(log) = writer.log.as_mut() {
+            let record = IndexRecord::Put {
+                entry: StoredEntry::from(&index_entry),
+            };
+            if let Err(e) = log.append(&record) {
+                writer.release(file_id, size);
+                return Err(e);
+            }
+        }
 
-        let mut index = self.index.write();
-        let old = index.insert(key_hash, index_entry);
-
-        if let Some(old_entry) = old {
-            // Update size delta
-            if size > old_entry.size {
-                self.current_size
-                    .fetch_add(size - old_entry.size, Ordering::Relaxed);
-            } else {
-                self.current_size
-                    .fetch_sub(old_entry.size - size, Ordering::Relaxed);
+        let key_hash = index_entry.key.combined_hash();
+        self.evictor.on_insert(&index_entry.key, size);
+        let old = self.index.write().insert(key_hash, index_entry);
+        self.logical_size.fetch_add(logical_size, Ordering::Relaxed);
+        match old {
+            Some(old_entry) => {
+                writer.release(old_entry.file_id, old_entry.size);
+                self.logical_size
+                    .fetch_sub(old_entry.metadata.size(), Ordering::Relaxed);
+
+                // Update size delta
+                if size > old_entry.size {
+                    self.current_size
+                        .fetch_add(size - old_entry.size, Ordering::Relaxed);
+                } else {
+                    self.current_size
+                        .fetch_sub(old_entry.size - size, Ordering::Relaxed);
+                }
+            }
+            None => {
+                self.current_size.fetch_add(size, Ordering::Relaxed);
             }
-        } else {
-            self.current_size.fetch_add(size, Ordering::Relaxed);
         }
 
-        true
+        self.maybe_rewrite_log(writer);
+        Ok(())
     }
 
     /// Remove an entry from the cache
     pub fn remove(&self, key: &CacheKey) -> bool {
-        let key_hash = key.combined_hash();
-        let mut index = self.index.write();
+        let mut writer = self.writer.lock();
+        self.remove_locked(&mut writer, key.combined_hash(), Some(key))
+            .is_some()
+    }
+
+    /// Remove the entry at `key_hash`, if present and matching `key`
+    fn remove_locked(
+        &self,
+        writer: &mut Writer,
+        key_hash: u64,
+        key: Option<&CacheKey>,
+    ) -> Option<L2IndexEntry> {
+        let entry = {
+            let mut index = self.index.write();
+            if key.is_some_and(|key| index.get(&key_hash).is_some_and(|e| e.key != *key)) {
+                return None;
+            }
+            index.remove(&key_hash)?
+        };
+        self.current_size.fetch_sub(entry.size, Ordering::Relaxed);
+        self.logical_size
+            .fetch_sub(entry.metadata.size(), Ordering::Relaxed);
+        writer.release(entry.file_id, entry.size);
+        self.evictor.on_remove(&entry.key);
+
+        if let Some(log) = writer.log.as_mut() {
+            let record = IndexRecord::Remove {
+                bucket: entry.key.bucket().to_string(),
+                key: entry.key.key().to_string(),
+            };
+            if let Err(e) = log.append(&record) {
+                warn!("Failed to log L2 removal: {}", e);
+            }
+        }
+        self.maybe_rewrite_log(writer);
+        Some(entry)
+    }
 
-        if let Some(entry) = index.remove(&key_hash) {
-            self.current_size.fetch_sub(entry.size, Ordering::Relaxed);
-            // In real implementation: mark space as reclaimable
-            return true;
+    /// Rewrite the index log once superseded records dominate it
+    fn maybe_rewrite_log(&self, writer: &mut Writer) {
+        let Some(log) = writer.log.as_mut() else {
+            return;
+        };
+        let live = self.index.read().len() as u64;
+        if log.records >= INDEX_COMPACT_MIN_RECORDS
+            && log.records > live.max(1) * INDEX_COMPACT_RATIO
+        {
+            let index = self.index.read();
+            if let Err(e) = log.rewrite(index.values()) {
+                warn!("L2 index log rewrite failed: {}", e);
+            }
         }
-        false
     }
 
-    /// Evict entries until we have enough space
-    fn evict_until_space(&self, needed: u64) {
+    /// Evict entries until we have enough space, collecting them into
+    /// `victims` if given
+    fn evict_until_space(
+        &self,
+        writer: &mut Writer,
+        needed: u64,
+        mut victims: Option<&mut Vec<(CacheKey, CacheEntry)>>,
+    ) {
         let target = self.config.capacity.saturating_sub(needed);
 
-        // Collect eviction candidates
-        let index = self.index.read();
-        let mut candidates: Vec<(u64, f64, u64)> = index
+        while self.current_size.load(Ordering::Relaxed) > target {
+            let Some(key) = self.evictor.evict() else {
+                break;
+            };
+            let Some(removed) = self.remove_locked(writer, key.combined_hash(), Some(&key)) else {
+                continue;
+            };
+            self.evictions.fetch_add(1, Ordering::Relaxed);
+
+            // Segments are only deleted under the writer lock, so the
+            // victim's data is still readable
+            if let Some(victims) = victims.as_deref_mut() {
+                let L2IndexEntry {
+                    key,
+                    file_id,
+                    offset,
+                    size,
+                    metadata,
+                } = removed;
+                match self.load(file_id, offset, size, metadata) {
+                    Ok(entry) if entry.verify_integrity() => victims.push((key, entry)),
+                    Ok(_) => warn!("Dropping corrupt L2 victim {}/{}", key.bucket(), key.key()),
+                    Err(e) => warn!(
+                        "Failed to read L2 victim {}/{}: {}",
+                        key.bucket(),
+                        key.key(),
+                        e
+                    ),
+                }
+            }
+        }
+    }
+
+    /// Compact every sealed segment whose dead fraction reaches
+    /// `compaction_garbage_ratio`, most garbage first
+    ///
+    /// Returns the number of segment bytes reclaimed. Copies are throttled
+    /// to `compaction_rate_bytes` and run on the calling thread.
+    pub fn compact(&self) -> crate::error::Result<u64> {
+        let candidates: Vec<u64> = {
+            let writer = self.writer.lock();
+            let mut candidates: Vec<(u64, f64)> = writer
+                .usage
+                .iter()
+                .filter(|(file_id, usage)| **file_id != writer.file_id && usage.written > 0)
+                .map(|(file_id, usage)| (*file_id, usage.dead() as f64 / usage.written as f64))
+                .filter(|(_, garbage)| *garbage >= self.config.compaction_garbage_ratio)
+                .collect();
+            candidates.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+            candidates.into_iter().map(|(file_id, _)| file_id).collect()
+        };
+
+        let mut throttle = Throttle::new(self.config.compaction_rate_bytes);
+        let mut reclaimed = 0;
+        for file_id in candidates {
+            if self.compactor_shutdown.load(Ordering::SeqCst) {
+                break;
+            }
+            reclaimed += self.compact_segment(file_id, &mut throttle)?;
+        }
+        Ok(reclaimed)
+    }
+
+    /// Move the live entries of a sealed segment to the active one, then
+    /// delete it
+    ///
+    /// Each copy is indexed under the writer lock, and copies plus index are
+    /// synced before the old file goes, so a crash at any point leaves every
+    /// entry readable from one of the two locations.
+    fn compact_segment(&self, file_id: u64, throttle: &mut Throttle) -> crate::error::Result<u64> {
+        let Some(segment) = self.segments.read().get(&file_id).cloned() else {
+            return Ok(0);
+        };
+        let mut live: Vec<(u64, u64, u64)> = self
+            .index
+            .read()
             .iter()
-            .map(|(hash, entry)| (*hash, entry.metadata.eviction_score(), entry.size))
+            .filter(|(_, entry)| entry.file_id == file_id)
+            .map(|(hash, entry)| (*hash, entry.offset, entry.size))
             .collect();
-        drop(index);
+        live.sort_by_key(|(_, offset, _)| *offset);
+
+        let mut targets = std::collections::HashSet::new();
+        for (hash, offset, size) in live {
+            if self.compactor_shutdown.load(Ordering::SeqCst) {
+                return Ok(0);
+            }
+            let data = segment.read(offset, size)?;
+
+            let mut writer = self.writer.lock();
+            // Skip entries removed or overwritten since the scan
+            let metadata = self
+                .index
+                .read()
+                .get(&hash)
+                .filter(|entry| entry.file_id == file_id && entry.offset == offset)
+                .map(|entry| entry.metadata.clone());
+            let Some(metadata) = metadata else {
+                continue;
+            };
+            let data = bytes::Bytes::from(data);
+            if !CacheEntry::with_metadata(data.clone(), metadata).verify_integrity() {
+                warn!("Dropping corrupt L2 entry found by compaction");
+                self.remove_locked(&mut writer, hash, None);
+                continue;
+            }
+
+            let (new_file_id, new_offset) = self.write_bytes(&mut writer, &data)?;
+            let record = {
+                let mut index = self.index.write();
+                let entry = index.get_mut(&hash).expect("entry checked above");
+                entry.file_id = new_file_id;
+                entry.offset = new_offset;
+                IndexRecord::Put {
+                    entry: StoredEntry::from(&*entry),
+                }
+            };
+            writer.release(file_id, size);
+            if let Some(log) = writer.log.as_mut() {
+                log.append(&record)?;
+            }
+            self.maybe_rewrite_log(&mut writer);
+            drop(writer);
 
-        // Sort by eviction score (highest first)
-        candidates.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+            targets.insert(new_file_id);
+            self.compaction_bytes_copied
+                .fetch_add(size, Ordering::Relaxed);
+            throttle.consume(size);
+        }
 
-        // Evict until target reached
-        let mut index = self.index.write();
-        for (hash, _, size) in candidates {
-            if self.current_size.load(Ordering::Relaxed) <= target {
+        let mut writer = self.writer.lock();
+        if writer
+            .usage
+            .get(&file_id)
+            .is_some_and(|usage| usage.live > 0)
+        {
+            warn!(
+                "L2 segment {} still has live entries, not deleting",
+                file_id
+            );
+            return Ok(0);
+        }
+        for target in targets {
+            if let Some(segment) = self.segments.read().get(&target) {
+                segment.sync()?;
+            }
+        }
+        if let Some(log) = writer.log.as_ref() {
+            log.sync()?;
+        }
+
+        let written = writer
+            .usage
+            .remove(&file_id)
+            .map_or(0, |usage| usage.written);
+        self.segments.write().remove(&file_id);
+        if let Some(dir) = &writer.dir {
+            std::fs::remove_file(dir.join(segment_name(file_id)))?;
+        }
+        self.segments_compacted.fetch_add(1, Ordering::Relaxed);
+        self.compaction_bytes_reclaimed
+            .fetch_add(written, Ordering::Relaxed);
+        debug!("Compacted L2 segment {} ({} bytes)", file_id, written);
+        Ok(written)
+    }
+
+    /// Run the background compactor until [`L2Cache::shutdown_compactor`]
+    pub async fn run_compactor(self: Arc<Self>) {
+        info!(
+            "Starting L2 compactor (garbage ratio {}, {} bytes/s)",
+            self.config.compaction_garbage_ratio, self.config.compaction_rate_bytes
+        );
+
+        let mut tick = tokio::time::interval(self.config.compaction_interval);
+        loop {
+            tick.tick().await;
+
+            if self.compactor_shutdown.load(Ordering::SeqCst) {
+                info!("L2 compactor shutting down");
                 break;
             }
 
-            if index.remove(&hash).is_some() {
-                self.current_size.fetch_sub(size, Ordering::Relaxed);
-                self.evictions.fetch_add(1, Ordering::Relaxed);
+            let cache = self.clone();
+            match tokio::task::spawn_blocking(move || cache.compact()).await {
+                Ok(Ok(0)) => {}
+                Ok(Ok(reclaimed)) => info!("L2 compaction reclaimed {} bytes", reclaimed),
+                Ok(Err(e)) => error!("L2 compaction failed: {}", e),
+                Err(e) => error!("L2 compaction task failed: {}", e),
             }
         }
     }
 
-    /// Get current size in bytes
+    /// Signal the background compactor to stop
+    pub fn shutdown_compactor(&self) {
+        self.compactor_shutdown.store(true, Ordering::SeqCst);
+    }
+
+    /// Get current size in bytes, as stored
     pub fn size(&self) -> u64 {
         self.current_size.load(Ordering::Relaxed)
     }
 
+    /// Get current size in bytes, uncompressed
+    pub fn logical_size(&self) -> u64 {
+        self.logical_size.load(Ordering::Relaxed)
+    }
+
     /// Get capacity
     pub fn capacity(&self) -> u64 {
         self.config.capacity
@@ -287,13 +1425,44 @@ impl L2Cache {
         self.evictions.load(Ordering::Relaxed)
     }
 
-    /// Clear the cache
+    /// Clear the cache, deleting its segment files
     pub fn clear(&self) {
+        let mut writer = self.writer.lock();
         let mut index = self.index.write();
         index.clear();
+        self.evictor.clear();
         self.current_size.store(0, Ordering::Relaxed);
-        self.current_offset.store(0, Ordering::Relaxed);
-        // In real implementation: delete cache files
+        self.logical_size.store(0, Ordering::Relaxed);
+
+        let file_id = writer.file_id + 1;
+        let segment = match &writer.dir {
+            Some(dir) => match Self::create_segment(dir, file_id) {
+                Ok(segment) => segment,
+                Err(e) => {
+                    warn!("Failed to create L2 segment: {}", e);
+                    return;
+                }
+            },
+            None => Segment::Memory(RwLock::new(Vec::new())),
+        };
+        let old = std::mem::replace(
+            &mut *self.segments.write(),
+            HashMap::from([(file_id, Arc::new(segment))]),
+        );
+        writer.file_id = file_id;
+        writer.offset = 0;
+        writer.usage = HashMap::from([(file_id, SegmentUsage::default())]);
+
+        if let Some(log) = writer.log.as_mut() {
+            if let Err(e) = log.rewrite(std::iter::empty()) {
+                warn!("L2 index log rewrite failed: {}", e);
+            }
+        }
+        if let Some(dir) = &writer.dir {
+            for old_id in old.keys() {
+                let _ = std::fs::remove_file(dir.join(segment_name(*old_id)));
+            }
+        }
     }
 
     /// Get utilization percentage
@@ -316,8 +1485,10 @@ impl Default for L2Cache {
 /// L2 cache statistics
 #[derive(Debug, Clone)]
 pub struct L2Stats {
-    /// Current size in bytes
+    /// Current size in bytes, as stored
     pub size: u64,
+    /// Current size in bytes, uncompressed
+    pub logical_size: u64,
     /// Capacity in bytes
     pub capacity: u64,
     /// Number of entries
@@ -334,13 +1505,27 @@ pub struct L2Stats {
     pub utilization: f64,
     /// Current file ID
     pub current_file_id: u64,
+    /// Number of segment files
+    pub segments: usize,
+    /// Bytes written to segment files, live or dead
+    pub disk_bytes: u64,
+    /// Segment bytes no longer referenced by the index
+    pub dead_bytes: u64,
+    /// Segments rewritten by the compactor
+    pub segments_compacted: u64,
+    /// Live bytes copied by the compactor
+    pub compaction_bytes_copied: u64,
+    /// Segment bytes deleted by the compactor
+    pub compaction_bytes_reclaimed: u64,
 }
 
 impl L2Cache {
     /// Get cache statistics
     pub fn stats(&self) -> L2Stats {
+        let writer = self.writer.lock();
         L2Stats {
             size: self.size(),
+            logical_size: self.logical_size(),
             capacity: self.capacity(),
             entries: self.len(),
             hits: self.hits(),
@@ -348,7 +1533,13 @@ impl L2Cache {
             hit_ratio: self.hit_ratio(),
             evictions: self.evictions(),
             utilization: self.utilization(),
-            current_file_id: self.current_file_id.load(Ordering::Relaxed),
+            current_file_id: writer.file_id,
+            segments: writer.usage.len(),
+            disk_bytes: writer.usage.values().map(|u| u.written).sum(),
+            dead_bytes: writer.usage.values().map(|u| u.dead()).sum(),
+            segments_compacted: self.segments_compacted.load(Ordering::Relaxed),
+            compaction_bytes_copied: self.compaction_bytes_copied.load(Ordering::Relaxed),
+            compaction_bytes_reclaimed: self.compaction_bytes_reclaimed.load(Ordering::Relaxed),
         }
     }
 }
@@ -540,4 +1731,361 @@ mod tests {
         let stats = cache.stats();
         assert!(stats.current_file_id > 0, "Should have rotated files");
     }
+
+    fn disk_config(dir: &Path) -> L2Config {
+        L2Config {
+            capacity: 1024 * 1024,
+            cache_dir: dir.to_path_buf(),
+            max_file_size: 16 * 1024,
+            min_entry_size: 1024,
+            ..Default::default()
+        }
+    }
+
+    fn make_data_entry(seed: u8, size: usize) -> CacheEntry {
+        CacheEntry::new(Bytes::from(
+            (0..size)
+                .map(|i| seed.wrapping_add(i as u8))
+                .collect::<Vec<u8>>(),
+        ))
+    }
+
+    #[test]
+    fn test_l2_cache_returns_written_data() {
+        let config = L2Config {
+            capacity: 1024 * 1024,
+            max_file_size: 10 * 1024,
+            min_entry_size: 1024,
+            ..Default::default()
+        };
+        let cache = L2Cache::with_config(config);
+
+        for i in 0..5u8 {
+            let key = make_key("bucket", &format!("object-{}", i));
+            cache.put(key, make_data_entry(i, 4096));
+        }
+        cache.put(make_key("bucket", "object-1"), make_data_entry(99, 2048));
+
+        for i in 0..5u8 {
+            let key = make_key("bucket", &format!("object-{}", i));
+            let expected = if i == 1 {
+                make_data_entry(99, 2048)
+            } else {
+                make_data_entry(i, 4096)
+            };
+            let entry = cache.get(&key).unwrap();
+            assert_eq!(entry.data(), expected.data());
+            assert!(entry.verify_integrity());
+        }
+    }
+
+    #[test]
+    fn test_l2_cache_reopen_restores_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let cache = L2Cache::open(disk_config(dir.path())).unwrap();
+            for i in 0..8u8 {
+                let key = make_key("bucket", &format!("object-{}", i));
+                assert!(cache.put(key, make_data_entry(i, 4096)));
+            }
+            assert!(cache.remove(&make_key("bucket", "object-3")));
+            cache.get(&make_key("bucket", "object-5"));
+        }
+
+        let cache = L2Cache::open(disk_config(dir.path())).unwrap();
+        assert!(cache.is_persistent() && !L2Cache::new().is_persistent());
+        assert_eq!(cache.len(), 7);
+        assert_eq!(cache.size(), 7 * 4096);
+        assert!(!cache.contains(&make_key("bucket", "object-3")));
+        for i in [0u8, 1, 2, 4, 5, 6, 7] {
+            let key = make_key("bucket", &format!("object-{}", i));
+            let entry = cache.get(&key).unwrap();
+            assert_eq!(entry.data(), make_data_entry(i, 4096).data());
+        }
+    }
+
+    #[test]
+    fn test_l2_cache_compression_survives_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let cache = L2Cache::open(disk_config(dir.path()))
+                .unwrap()
+                .with_compression(CompressionConfig::default());
+            for i in 0..4u8 {
+                let key = make_key("bucket", &format!("object-{}", i));
+                assert!(cache.put(key, make_data_entry(i, 8192)));
+            }
+            assert!(cache.size() < cache.logical_size());
+            assert_eq!(cache.stats().logical_size, 4 * 8192);
+        }
+
+        // Entries written compressed are read back without compression enabled
+        let cache = L2Cache::open(disk_config(dir.path())).unwrap();
+        assert_eq!(cache.logical_size(), 4 * 8192);
+        assert!(cache.size() < cache.logical_size());
+        assert!(cache.put(make_key("bucket", "plain"), make_data_entry(9, 2048)));
+        assert_eq!(cache.logical_size(), 4 * 8192 + 2048);
+        for i in 0..4u8 {
+            let key = make_key("bucket", &format!("object-{}", i));
+            let entry = cache.get(&key).unwrap();
+            assert!(!entry.is_compressed());
+            assert_eq!(entry.data(), make_data_entry(i, 8192).data());
+        }
+
+        assert!(cache.remove(&make_key("bucket", "object-0")));
+        assert_eq!(cache.logical_size(), 3 * 8192 + 2048);
+    }
+
+    #[test]
+    fn test_l2_cache_put_evicting_returns_victims() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = L2Config {
+            capacity: 16 * 1024,
+            ..disk_config(dir.path())
+        };
+        {
+            let cache = L2Cache::open(config.clone()).unwrap();
+            for i in 0..4u8 {
+                let entry = make_data_entry(i, 4096);
+                entry.metadata.mark_dirty();
+                let key = make_key("bucket", &format!("object-{}", i));
+                assert!(cache.put(key, entry));
+            }
+        }
+
+        // Dirty entries stay dirty across a restart, and are handed back
+        // with their data when evicted
+        let cache = L2Cache::open(config.clone()).unwrap();
+        let key = make_key("bucket", "object-4");
+        let (stored, victims) = cache.put_evicting(key, make_data_entry(4, 4096));
+        assert!(stored);
+        assert_eq!(victims.len(), 1);
+        assert_eq!(cache.len(), 4);
+
+        let (victim_key, victim) = &victims[0];
+        assert!(!cache.contains(victim_key));
+        assert!(victim.metadata.is_dirty());
+        let seed = victim_key
+            .key()
+            .trim_start_matches("object-")
+            .parse()
+            .unwrap();
+        assert_eq!(victim.data(), make_data_entry(seed, 4096).data());
+
+        // Flushed entries come back clean
+        let generations: Vec<(CacheKey, u64)> = cache
+            .dirty_entries()
+            .into_iter()
+            .map(|(key, generation, _)| (key, generation))
+            .collect();
+        assert_eq!(generations.len(), 3);
+        for (key, generation) in &generations {
+            cache.mark_clean(key, *generation);
+        }
+        drop(cache);
+        let cache = L2Cache::open(config).unwrap();
+        assert!(cache.dirty_entries().is_empty());
+        assert_eq!(cache.len(), 4);
+    }
+
+    #[test]
+    fn test_l2_cache_recovers_from_torn_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let last_segment = {
+            let cache = L2Cache::open(disk_config(dir.path())).unwrap();
+            for i in 0..3u8 {
+                let key = make_key("bucket", &format!("object-{}", i));
+                assert!(cache.put(key, make_data_entry(i, 4096)));
+            }
+            dir.path().join(segment_name(cache.stats().current_file_id))
+        };
+
+        // The last entry's bytes only partly reached the disk, and the
+        // index log ends in a half-written record
+        let file = OpenOptions::new().write(true).open(&last_segment).unwrap();
+        let len = file.metadata().unwrap().len();
+        file.set_len(len - 100).unwrap();
+        let mut log = OpenOptions::new()
+            .append(true)
+            .open(dir.path().join(INDEX_FILE))
+            .unwrap();
+        log.write_all(b"0badc0de {\"op\":\"put\",\"b\"").unwrap();
+        std::fs::write(dir.path().join(segment_name(999)), b"orphan").unwrap();
+
+        let cache = L2Cache::open(disk_config(dir.path())).unwrap();
+        assert_eq!(cache.len(), 2);
+        assert!(!cache.contains(&make_key("bucket", "object-2")));
+        let entry = cache.get(&make_key("bucket", "object-1")).unwrap();
+        assert_eq!(entry.data(), make_data_entry(1, 4096).data());
+        assert!(!dir.path().join(segment_name(999)).exists());
+
+        // The index only holds intact records again
+        let index = std::fs::read_to_string(dir.path().join(INDEX_FILE)).unwrap();
+        assert_eq!(index.lines().count(), 2);
+        assert!(index.lines().all(|line| decode_record(line).is_some()));
+    }
+
+    #[test]
+    fn test_l2_cache_failed_index_append() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = L2Cache::open(disk_config(dir.path())).unwrap();
+        assert!(cache.put(make_key("bucket", "kept"), make_data_entry(1, 4096)));
+
+        // A read-only handle fails every write and cannot be truncated
+        cache.writer.lock().log.as_mut().unwrap().file =
+            File::open(dir.path().join(INDEX_FILE)).unwrap();
+        assert!(!cache.put(make_key("bucket", "lost"), make_data_entry(2, 4096)));
+        assert!(cache.writer.lock().log.as_ref().unwrap().broken);
+
+        // The failed entry's bytes count as dead, not live
+        let stats = cache.stats();
+        assert_eq!(stats.disk_bytes, 2 * 4096);
+        assert_eq!(stats.dead_bytes, 4096);
+        drop(cache);
+
+        let cache = L2Cache::open(disk_config(dir.path())).unwrap();
+        assert_eq!(cache.len(), 1);
+        assert!(cache.contains(&make_key("bucket", "kept")));
+    }
+
+    #[test]
+    fn test_l2_cache_drops_corrupt_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = L2Cache::open(disk_config(dir.path())).unwrap();
+        let key = make_key("bucket", "object");
+        cache.put(key.clone(), make_data_entry(7, 4096));
+
+        let segment = dir.path().join(segment_name(cache.stats().current_file_id));
+        let file = OpenOptions::new().write(true).open(&segment).unwrap();
+        file.write_all_at(b"garbage", 100).unwrap();
+
+        assert!(cache.get(&key).is_none());
+        assert!(!cache.contains(&key));
+        assert_eq!(cache.size(), 0);
+    }
+
+    #[test]
+    fn test_l2_cache_tracks_dead_bytes() {
+        let config = L2Config {
+            capacity: 1024 * 1024,
+            max_file_size: 8 * 1024,
+            min_entry_size: 1024,
+            ..Default::default()
+        };
+        let cache = L2Cache::with_config(config);
+
+        let key = make_key("bucket", "object");
+        cache.put(key.clone(), make_entry(4096));
+        cache.put(key.clone(), make_entry(4096));
+        cache.put(make_key("bucket", "other"), make_entry(4096));
+        cache.remove(&make_key("bucket", "other"));
+
+        let stats = cache.stats();
+        assert_eq!(stats.disk_bytes, 3 * 4096);
+        assert_eq!(stats.dead_bytes, 2 * 4096);
+        assert_eq!(stats.size, 4096);
+    }
+
+    #[test]
+    fn test_l2_compaction_rewrites_mostly_dead_segments() {
+        let config = L2Config {
+            capacity: 1024 * 1024,
+            max_file_size: 16 * 1024,
+            min_entry_size: 1024,
+            compaction_garbage_ratio: 0.5,
+            compaction_rate_bytes: 0,
+            ..Default::default()
+        };
+        let cache = L2Cache::with_config(config);
+
+        // Segment 0 holds objects 0-3, segment 1 holds objects 4-7
+        for i in 0..8u8 {
+            let key = make_key("bucket", &format!("object-{}", i));
+            cache.put(key, make_data_entry(i, 4096));
+        }
+        for i in [0u8, 1, 2, 5] {
+            cache.remove(&make_key("bucket", &format!("object-{}", i)));
+        }
+
+        // Segment 0 is 75% dead, segment 1 only 25%
+        let reclaimed = cache.compact().unwrap();
+        assert_eq!(reclaimed, 16 * 1024);
+
+        let stats = cache.stats();
+        assert_eq!(stats.segments_compacted, 1);
+        assert_eq!(stats.compaction_bytes_copied, 4096);
+        assert_eq!(stats.compaction_bytes_reclaimed, 16 * 1024);
+        assert_eq!(stats.dead_bytes, 4096);
+        assert!(!cache.segments.read().contains_key(&0));
+
+        for i in [3u8, 4, 6, 7] {
+            let key = make_key("bucket", &format!("object-{}", i));
+            let entry = cache.get(&key).unwrap();
+            assert_eq!(entry.data(), make_data_entry(i, 4096).data());
+        }
+        assert_eq!(cache.size(), 4 * 4096);
+
+        // Nothing left above the threshold
+        assert_eq!(cache.compact().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_l2_compaction_survives_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = L2Config {
+            compaction_rate_bytes: 0,
+            ..disk_config(dir.path())
+        };
+        {
+            let cache = L2Cache::open(config.clone()).unwrap();
+            for i in 0..8u8 {
+                let key = make_key("bucket", &format!("object-{}", i));
+                cache.put(key, make_data_entry(i, 4096));
+            }
+            for i in 0..4u8 {
+                if i != 2 {
+                    cache.remove(&make_key("bucket", &format!("object-{}", i)));
+                }
+            }
+            assert_eq!(cache.compact().unwrap(), 16 * 1024);
+            assert!(!dir.path().join(segment_name(0)).exists());
+        }
+
+        let cache = L2Cache::open(config).unwrap();
+        assert_eq!(cache.len(), 5);
+        for i in 2..8u8 {
+            let key = make_key("bucket", &format!("object-{}", i));
+            if i == 3 {
+                assert!(!cache.contains(&key));
+                continue;
+            }
+            let entry = cache.get(&key).unwrap();
+            assert_eq!(entry.data(), make_data_entry(i, 4096).data());
+        }
+    }
+
+    #[test]
+    fn test_l2_cache_eviction_algorithms() {
+        for policy in [
+            EvictionPolicy::lru(),
+            EvictionPolicy::tiny_lfu(),
+            EvictionPolicy::s3_fifo(),
+        ] {
+            let config = L2Config {
+                capacity: 64 * 1024,
+                min_entry_size: 1024,
+                ..Default::default()
+            };
+            let cache = L2Cache::with_config(config).with_eviction(&policy);
+
+            for i in 0..100 {
+                let key = make_key("bucket", &format!("object-{}", i));
+                cache.put(key, make_entry(4096));
+            }
+
+            assert_eq!(cache.len(), 16, "{}", cache.eviction_algorithm());
+            assert_eq!(cache.size(), 16 * 4096);
+            assert_eq!(cache.evictions(), 84);
+        }
+    }
 }
@@ -10,14 +10,26 @@
 //!
 //! - Async I/O for non-blocking storage access
 //! - Pluggable backend (local filesystem, S3, etc.)
+//! - Durable filesystem backend with atomic, checksummed writes
+//! - Optional compression, framed so objects stay self-describing
 //! - Erasure coding integration for efficient storage
 
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Write;
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 use async_trait::async_trait;
 use dashmap::DashMap;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
 
+use super::compression::{CompressionAlgorithm, CompressionConfig, CompressionManager};
 use super::entry::{CacheEntry, CacheKey};
 
 /// L3 storage backend trait
@@ -26,6 +38,21 @@ pub trait L3Backend: Send + Sync {
     /// Get an object from storage
     async fn get(&self, bucket: &str, key: &str) -> crate::error::Result<Option<bytes::Bytes>>;
 
+    /// Get up to `len` bytes of an object starting at `offset`
+    ///
+    /// The range is cut short at the end of the object. The default reads
+    /// the whole object; backends that can should read only the range.
+    async fn get_range(
+        &self,
+        bucket: &str,
+        key: &str,
+        offset: u64,
+        len: u64,
+    ) -> crate::error::Result<Option<bytes::Bytes>> {
+        let data = self.get(bucket, key).await?;
+        Ok(data.map(|data| slice_range(&data, offset, len)))
+    }
+
     /// Put an object into storage
     async fn put(&self, bucket: &str, key: &str, data: bytes::Bytes) -> crate::error::Result<()>;
 
@@ -35,10 +62,20 @@ pub trait L3Backend: Send + Sync {
     /// Check if an object exists
     async fn exists(&self, bucket: &str, key: &str) -> crate::error::Result<bool>;
 
+    /// List the keys of a bucket starting with `prefix`, in sorted order
+    async fn list(&self, bucket: &str, prefix: &str) -> crate::error::Result<Vec<String>>;
+
     /// Get storage statistics
     fn stats(&self) -> L3BackendStats;
 }
 
+/// Get up to `len` bytes of `data` starting at `offset`
+pub(crate) fn slice_range(data: &bytes::Bytes, offset: u64, len: u64) -> bytes::Bytes {
+    let start = offset.min(data.len() as u64);
+    let end = offset.saturating_add(len).min(data.len() as u64);
+    data.slice(start as usize..end.max(start) as usize)
+}
+
 /// L3 backend statistics
 #[derive(Debug, Clone, Default)]
 pub struct L3BackendStats {
@@ -101,16 +138,30 @@ impl L3Backend for InMemoryL3Backend {
         Ok(None)
     }
 
+    async fn get_range(
+        &self,
+        bucket: &str,
+        key: &str,
+        offset: u64,
+        len: u64,
+    ) -> crate::error::Result<Option<bytes::Bytes>> {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(bucket_data) = self.storage.get(bucket) {
+            if let Some(data) = bucket_data.get(key) {
+                return Ok(Some(slice_range(&data, offset, len)));
+            }
+        }
+        Ok(None)
+    }
+
     async fn put(&self, bucket: &str, key: &str, data: bytes::Bytes) -> crate::error::Result<()> {
         self.writes.fetch_add(1, Ordering::Relaxed);
 
         let size = data.len() as u64;
 
         // Get or create bucket (lock-free)
-        let bucket_data = self
-            .storage
-            .entry(bucket.to_string())
-            .or_insert_with(DashMap::new);
+        let bucket_data = self.storage.entry(bucket.to_string()).or_default();
 
         // Insert into bucket
         let old = bucket_data.insert(key.to_string(), data);
@@ -158,6 +209,21 @@ impl L3Backend for InMemoryL3Backend {
         Ok(false)
     }
 
+    async fn list(&self, bucket: &str, prefix: &str) -> crate::error::Result<Vec<String>> {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+
+        let Some(bucket_data) = self.storage.get(bucket) else {
+            return Ok(vec![]);
+        };
+        let mut keys: Vec<String> = bucket_data
+            .iter()
+            .filter(|e| e.key().starts_with(prefix))
+            .map(|e| e.key().clone())
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+
     fn stats(&self) -> L3BackendStats {
         L3BackendStats {
             object_count: self.object_count.load(Ordering::Relaxed),
@@ -169,14 +235,505 @@ impl L3Backend for InMemoryL3Backend {
     }
 }
 
+// =============================================================================
+// Filesystem Backend
+// =============================================================================
+
+/// Suffix of object data files
+const DATA_SUFFIX: &str = ".obj";
+
+/// Suffix of object sidecar metadata files
+const META_SUFFIX: &str = ".meta";
+
+/// Prefix of files still being written
+const TMP_PREFIX: &str = ".tmp-";
+
+/// Longest encoded name kept readable; longer names are hashed
+const MAX_ENCODED_NAME: usize = 200;
+
+/// Number of lock stripes serializing access to one object
+const FS_LOCK_STRIPES: usize = 64;
+
+/// Encode a bucket or key as a single file name
+///
+/// Unreserved characters are kept and everything else is percent-encoded,
+/// including `/` and a leading `.`, so names never start with a dot. Names
+/// too long for the filesystem become `%h<sha256>`, and the empty name is
+/// `%e`; neither form can result from percent-encoding.
+fn encode_name(name: &str) -> String {
+    if name.is_empty() {
+        return "%e".to_string();
+    }
+    let mut out = String::with_capacity(name.len());
+    for (i, b) in name.bytes().enumerate() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'~' => out.push(b as char),
+            b'.' if i > 0 => out.push('.'),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    if out.len() > MAX_ENCODED_NAME {
+        out = format!("%h{}", sha256_hex(name.as_bytes()));
+    }
+    out
+}
+
+/// Lowercase hex SHA-256 of `data`
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .fold(String::with_capacity(64), |mut s, b| {
+            s.push_str(&format!("{:02x}", b));
+            s
+        })
+}
+
+/// Decode a name from [`encode_name`], or None for hashed names
+fn decode_name(encoded: &str) -> Option<String> {
+    match encoded {
+        "%e" => Some(String::new()),
+        _ if encoded.starts_with("%h") => None,
+        _ => urlencoding::decode(encoded).ok().map(|d| d.into_owned()),
+    }
+}
+
+/// Sidecar metadata of a stored object
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FsObjectMeta {
+    /// Original key, for names that are hashed
+    key: String,
+    /// Data size in bytes
+    size: u64,
+    /// SHA-256 of the data, hex encoded
+    sha256: String,
+    /// Data file holding this version of the object
+    data_file: String,
+}
+
+/// Write `contents` to a new temporary file in `dir` and fsync it
+fn write_temp(dir: &Path, contents: &[u8]) -> std::io::Result<PathBuf> {
+    let path = dir.join(format!("{}{}", TMP_PREFIX, uuid::Uuid::new_v4().simple()));
+    let mut file = File::create(&path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    Ok(path)
+}
+
+fn sync_dir(dir: &Path) -> std::io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+fn read_meta(path: &Path) -> crate::error::Result<Option<FsObjectMeta>> {
+    match std::fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).map(Some).map_err(|e| {
+            crate::error::Error::Internal(format!("Corrupt L3 metadata {}: {}", path.display(), e))
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Shared state of [`FsL3Backend`], owned by its blocking tasks
+struct FsInner {
+    root: PathBuf,
+    locks: Vec<Mutex<()>>,
+    object_count: AtomicU64,
+    total_bytes: AtomicU64,
+}
+
+impl FsInner {
+    fn lock(&self, bucket: &str, key: &str) -> parking_lot::MutexGuard<'_, ()> {
+        let stripe = CacheKey::new(bucket, key).combined_hash() as usize % FS_LOCK_STRIPES;
+        self.locks[stripe].lock()
+    }
+
+    fn bucket_dir(&self, bucket: &str) -> PathBuf {
+        self.root.join(encode_name(bucket))
+    }
+
+    fn meta_path(&self, bucket: &str, key: &str) -> PathBuf {
+        self.bucket_dir(bucket)
+            .join(format!("{}{}", encode_name(key), META_SUFFIX))
+    }
+
+    fn get(&self, bucket: &str, key: &str) -> crate::error::Result<Option<bytes::Bytes>> {
+        let _guard = self.lock(bucket, key);
+        let Some(meta) = read_meta(&self.meta_path(bucket, key))? else {
+            return Ok(None);
+        };
+        let data = std::fs::read(self.bucket_dir(bucket).join(&meta.data_file))?;
+        if data.len() as u64 != meta.size || sha256_hex(&data) != meta.sha256 {
+            return Err(crate::error::Error::Internal(format!(
+                "Corrupt L3 object {}/{}: data does not match its metadata",
+                bucket, key
+            )));
+        }
+        Ok(Some(bytes::Bytes::from(data)))
+    }
+
+    /// Read part of an object's data
+    ///
+    /// Only the length of the data file is checked; the SHA-256 covers
+    /// the whole object.
+    fn get_range(
+        &self,
+        bucket: &str,
+        key: &str,
+        offset: u64,
+        len: u64,
+    ) -> crate::error::Result<Option<bytes::Bytes>> {
+        let _guard = self.lock(bucket, key);
+        let Some(meta) = read_meta(&self.meta_path(bucket, key))? else {
+            return Ok(None);
+        };
+        let file = File::open(self.bucket_dir(bucket).join(&meta.data_file))?;
+        if file.metadata()?.len() != meta.size {
+            return Err(crate::error::Error::Internal(format!(
+                "Corrupt L3 object {}/{}: data does not match its metadata",
+                bucket, key
+            )));
+        }
+
+        let start = offset.min(meta.size);
+        let end = offset.saturating_add(len).min(meta.size);
+        let mut data = vec![0u8; (end - start) as usize];
+        file.read_exact_at(&mut data, start)?;
+        Ok(Some(bytes::Bytes::from(data)))
+    }
+
+    /// Write the data under a fresh file name, then commit it by renaming
+    /// the sidecar into place, then drop the replaced data file
+    fn put(&self, bucket: &str, key: &str, data: &[u8]) -> crate::error::Result<()> {
+        let _guard = self.lock(bucket, key);
+        let dir = self.bucket_dir(bucket);
+        if !dir.exists() {
+            std::fs::create_dir_all(&dir)?;
+            sync_dir(&self.root)?;
+        }
+
+        let meta_path = self.meta_path(bucket, key);
+        let previous = read_meta(&meta_path)?;
+        let meta = FsObjectMeta {
+            key: key.to_string(),
+            size: data.len() as u64,
+            sha256: sha256_hex(data),
+            data_file: format!(
+                "{}.{}{}",
+                encode_name(key),
+                uuid::Uuid::new_v4().simple(),
+                DATA_SUFFIX
+            ),
+        };
+        let meta_bytes =
+            serde_json::to_vec(&meta).map_err(|e| crate::error::Error::Internal(e.to_string()))?;
+
+        let data_tmp = write_temp(&dir, data)?;
+        std::fs::rename(&data_tmp, dir.join(&meta.data_file))?;
+        let meta_tmp = write_temp(&dir, &meta_bytes)?;
+        std::fs::rename(&meta_tmp, &meta_path)?;
+        sync_dir(&dir)?;
+
+        match previous {
+            Some(previous) => {
+                let _ = std::fs::remove_file(dir.join(&previous.data_file));
+                self.total_bytes.fetch_sub(previous.size, Ordering::Relaxed);
+            }
+            None => {
+                self.object_count.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.total_bytes.fetch_add(meta.size, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Remove the sidecar first, so a crash leaves only an orphaned data
+    /// file for the next startup to clean up
+    fn delete(&self, bucket: &str, key: &str) -> crate::error::Result<bool> {
+        let _guard = self.lock(bucket, key);
+        let meta_path = self.meta_path(bucket, key);
+        let Some(meta) = read_meta(&meta_path)? else {
+            return Ok(false);
+        };
+        let dir = self.bucket_dir(bucket);
+        std::fs::remove_file(&meta_path)?;
+        let _ = std::fs::remove_file(dir.join(&meta.data_file));
+        sync_dir(&dir)?;
+
+        self.object_count.fetch_sub(1, Ordering::Relaxed);
+        self.total_bytes.fetch_sub(meta.size, Ordering::Relaxed);
+        Ok(true)
+    }
+
+    fn list(&self, bucket: &str, prefix: &str) -> crate::error::Result<Vec<String>> {
+        let dir = self.bucket_dir(bucket);
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut keys = Vec::new();
+        for entry in entries {
+            let name = entry?.file_name();
+            let Some(encoded) = name.to_str().and_then(|n| n.strip_suffix(META_SUFFIX)) else {
+                continue;
+            };
+            if encoded.starts_with(TMP_PREFIX) {
+                continue;
+            }
+            let key = match decode_name(encoded) {
+                Some(key) => key,
+                None => match read_meta(&dir.join(&name))? {
+                    Some(meta) => meta.key,
+                    None => continue,
+                },
+            };
+            if key.starts_with(prefix) {
+                keys.push(key);
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+
+    /// Count stored objects, removing files left behind by interrupted
+    /// writes and deletes
+    fn recover(&self) -> crate::error::Result<()> {
+        let (mut objects, mut bytes) = (0u64, 0u64);
+        for bucket in std::fs::read_dir(&self.root)? {
+            let bucket = bucket?;
+            if !bucket.file_type()?.is_dir() {
+                continue;
+            }
+            let dir = bucket.path();
+
+            let mut referenced = HashSet::new();
+            let mut others = Vec::new();
+            for entry in std::fs::read_dir(&dir)? {
+                let name = entry?.file_name().to_string_lossy().into_owned();
+                if name.starts_with(TMP_PREFIX) || !name.ends_with(META_SUFFIX) {
+                    others.push(name);
+                    continue;
+                }
+                match read_meta(&dir.join(&name)) {
+                    Ok(Some(meta)) if dir.join(&meta.data_file).exists() => {
+                        objects += 1;
+                        bytes += meta.size;
+                        referenced.insert(meta.data_file);
+                    }
+                    _ => {
+                        warn!("Removing L3 metadata {} without data", name);
+                        std::fs::remove_file(dir.join(&name))?;
+                    }
+                }
+            }
+
+            for name in others {
+                if !referenced.contains(&name) {
+                    debug!("Removing unreferenced L3 file {}", name);
+                    std::fs::remove_file(dir.join(&name))?;
+                }
+            }
+        }
+
+        self.object_count.store(objects, Ordering::Relaxed);
+        self.total_bytes.store(bytes, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Durable L3 backend storing objects as files under a root directory
+///
+/// Each bucket is a sub-directory. An object is a data file plus a JSON
+/// sidecar with its size and SHA-256, which names the data file; writes go
+/// to a new data file and commit by atomically replacing the sidecar, so
+/// readers and crashes see either the old or the new object.
+pub struct FsL3Backend {
+    inner: Arc<FsInner>,
+    reads: AtomicU64,
+    writes: AtomicU64,
+    deletes: AtomicU64,
+}
+
+impl FsL3Backend {
+    /// Open the backend at `root`, creating it if needed.
+    ///
+    /// Scans the stored objects to rebuild the statistics and cleans up
+    /// after interrupted writes.
+    pub fn open(root: impl Into<PathBuf>) -> crate::error::Result<Self> {
+        let root = root.into();
+        std::fs::create_dir_all(&root)?;
+
+        let inner = FsInner {
+            root,
+            locks: (0..FS_LOCK_STRIPES).map(|_| Mutex::new(())).collect(),
+            object_count: AtomicU64::new(0),
+            total_bytes: AtomicU64::new(0),
+        };
+        inner.recover()?;
+        info!(
+            "Opened L3 store at {} with {} objects ({} bytes)",
+            inner.root.display(),
+            inner.object_count.load(Ordering::Relaxed),
+            inner.total_bytes.load(Ordering::Relaxed)
+        );
+
+        Ok(Self {
+            inner: Arc::new(inner),
+            reads: AtomicU64::new(0),
+            writes: AtomicU64::new(0),
+            deletes: AtomicU64::new(0),
+        })
+    }
+
+    /// Root directory of the store
+    pub fn root(&self) -> &Path {
+        &self.inner.root
+    }
+
+    /// Run a filesystem operation on the blocking pool
+    async fn blocking<T, F>(&self, op: F) -> crate::error::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&FsInner) -> crate::error::Result<T> + Send + 'static,
+    {
+        let inner = self.inner.clone();
+        tokio::task::spawn_blocking(move || op(&inner))
+            .await
+            .map_err(|e| crate::error::Error::Internal(format!("L3 I/O task failed: {}", e)))?
+    }
+}
+
+#[async_trait]
+impl L3Backend for FsL3Backend {
+    async fn get(&self, bucket: &str, key: &str) -> crate::error::Result<Option<bytes::Bytes>> {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        let (bucket, key) = (bucket.to_string(), key.to_string());
+        self.blocking(move |fs| fs.get(&bucket, &key)).await
+    }
+
+    async fn get_range(
+        &self,
+        bucket: &str,
+        key: &str,
+        offset: u64,
+        len: u64,
+    ) -> crate::error::Result<Option<bytes::Bytes>> {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        let (bucket, key) = (bucket.to_string(), key.to_string());
+        self.blocking(move |fs| fs.get_range(&bucket, &key, offset, len))
+            .await
+    }
+
+    async fn put(&self, bucket: &str, key: &str, data: bytes::Bytes) -> crate::error::Result<()> {
+        self.writes.fetch_add(1, Ordering::Relaxed);
+        let (bucket, key) = (bucket.to_string(), key.to_string());
+        self.blocking(move |fs| fs.put(&bucket, &key, &data)).await
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> crate::error::Result<bool> {
+        self.deletes.fetch_add(1, Ordering::Relaxed);
+        let (bucket, key) = (bucket.to_string(), key.to_string());
+        self.blocking(move |fs| fs.delete(&bucket, &key)).await
+    }
+
+    async fn exists(&self, bucket: &str, key: &str) -> crate::error::Result<bool> {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        let path = self.inner.meta_path(bucket, key);
+        self.blocking(move |_| Ok(path.exists())).await
+    }
+
+    async fn list(&self, bucket: &str, prefix: &str) -> crate::error::Result<Vec<String>> {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        let (bucket, prefix) = (bucket.to_string(), prefix.to_string());
+        self.blocking(move |fs| fs.list(&bucket, &prefix)).await
+    }
+
+    fn stats(&self) -> L3BackendStats {
+        L3BackendStats {
+            object_count: self.inner.object_count.load(Ordering::Relaxed),
+            total_bytes: self.inner.total_bytes.load(Ordering::Relaxed),
+            reads: self.reads.load(Ordering::Relaxed),
+            writes: self.writes.load(Ordering::Relaxed),
+            deletes: self.deletes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Leading bytes of an object stored in a compression frame
+const FRAME_MAGIC: &[u8; 4] = b"\x89CZ1";
+
+/// Frame header: magic, algorithm, uncompressed size
+const FRAME_HEADER_LEN: usize = FRAME_MAGIC.len() + 1 + 8;
+
+/// Store `data`, compressed with `algorithm`, as a backend object
+///
+/// Uncompressed data is stored as is unless it could be mistaken for a frame.
+fn encode_frame(data: bytes::Bytes, algorithm: CompressionAlgorithm, size: u64) -> bytes::Bytes {
+    if algorithm == CompressionAlgorithm::None && !data.starts_with(FRAME_MAGIC) {
+        return data;
+    }
+    let tag = match algorithm {
+        CompressionAlgorithm::None => 0,
+        CompressionAlgorithm::Lz4 => 1,
+    };
+    let mut out = Vec::with_capacity(FRAME_HEADER_LEN + data.len());
+    out.extend_from_slice(FRAME_MAGIC);
+    out.push(tag);
+    out.extend_from_slice(&size.to_le_bytes());
+    out.extend_from_slice(&data);
+    bytes::Bytes::from(out)
+}
+
+/// Get the algorithm of a framed object from its first bytes
+fn frame_algorithm(object: &[u8]) -> Option<CompressionAlgorithm> {
+    if object.len() < FRAME_HEADER_LEN || !object.starts_with(FRAME_MAGIC) {
+        return None;
+    }
+    match object[FRAME_MAGIC.len()] {
+        0 => Some(CompressionAlgorithm::None),
+        1 => Some(CompressionAlgorithm::Lz4),
+        _ => None,
+    }
+}
+
+/// Recover the data of a backend object written by [`encode_frame`]
+fn decode_frame(object: bytes::Bytes) -> crate::error::Result<bytes::Bytes> {
+    let Some(algorithm) = frame_algorithm(&object) else {
+        return Ok(object);
+    };
+    let mut size = [0u8; 8];
+    size.copy_from_slice(&object[FRAME_MAGIC.len() + 1..FRAME_HEADER_LEN]);
+    let size = u64::from_le_bytes(size);
+
+    let payload = object.slice(FRAME_HEADER_LEN..);
+    let data = match algorithm {
+        CompressionAlgorithm::None => payload,
+        _ => CompressionManager::new().decompress(&payload, algorithm)?,
+    };
+    if data.len() as u64 != size {
+        return Err(crate::error::Error::DecompressionFailed {
+            algorithm: algorithm.name().into(),
+            reason: format!("expected {} bytes, got {}", size, data.len()),
+        });
+    }
+    Ok(data)
+}
+
 /// L3 Cache - cold storage tier
 pub struct L3Cache {
     /// Storage backend
     backend: Arc<dyn L3Backend>,
+    /// Compression for stored objects, if enabled
+    compression: Option<CompressionManager>,
     /// Hit count
     hits: AtomicU64,
     /// Miss count
     misses: AtomicU64,
+    /// Bytes written to the backend, as stored
+    bytes_written: AtomicU64,
+    /// Bytes written to the backend, uncompressed
+    logical_bytes_written: AtomicU64,
 }
 
 impl L3Cache {
@@ -184,8 +741,11 @@ impl L3Cache {
     pub fn new(backend: Arc<dyn L3Backend>) -> Self {
         Self {
             backend,
+            compression: None,
             hits: AtomicU64::new(0),
             misses: AtomicU64::new(0),
+            bytes_written: AtomicU64::new(0),
+            logical_bytes_written: AtomicU64::new(0),
         }
     }
 
@@ -194,12 +754,21 @@ impl L3Cache {
         Self::new(Arc::new(InMemoryL3Backend::new()))
     }
 
+    /// Compress objects written from now on with `config`
+    ///
+    /// Objects are read back whether or not they were compressed.
+    pub fn with_compression(mut self, config: CompressionConfig) -> Self {
+        self.compression = Some(CompressionManager::with_config(config));
+        self
+    }
+
     /// Get an entry from cold storage
     pub async fn get(&self, key: &CacheKey) -> crate::error::Result<Option<CacheEntry>> {
         let result = self.backend.get(key.bucket(), key.key()).await?;
 
         match result {
-            Some(data) => {
+            Some(object) => {
+                let data = decode_frame(object)?;
                 self.hits.fetch_add(1, Ordering::Relaxed);
                 Ok(Some(CacheEntry::new(data)))
             }
@@ -210,11 +779,59 @@ impl L3Cache {
         }
     }
 
+    /// Get up to `len` bytes of an entry starting at `offset`
+    ///
+    /// Reads the first bytes of the object to see if it is stored in a
+    /// frame. Framed objects are read whole, others only in the range.
+    pub async fn get_range(
+        &self,
+        key: &CacheKey,
+        offset: u64,
+        len: u64,
+    ) -> crate::error::Result<Option<bytes::Bytes>> {
+        let (bucket, name) = (key.bucket(), key.key());
+        let header = self
+            .backend
+            .get_range(bucket, name, 0, FRAME_HEADER_LEN as u64)
+            .await?;
+        let data = match header {
+            Some(header) if frame_algorithm(&header).is_some() => {
+                match self.backend.get(bucket, name).await? {
+                    Some(object) => Some(slice_range(&decode_frame(object)?, offset, len)),
+                    None => None,
+                }
+            }
+            Some(_) => self.backend.get_range(bucket, name, offset, len).await?,
+            None => None,
+        };
+
+        match data {
+            Some(data) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Ok(Some(data))
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                Ok(None)
+            }
+        }
+    }
+
     /// Put an entry into cold storage
     pub async fn put(&self, key: &CacheKey, entry: &CacheEntry) -> crate::error::Result<()> {
-        self.backend
-            .put(key.bucket(), key.key(), entry.data().clone())
-            .await
+        let entry = match &self.compression {
+            Some(compression) => entry.clone().compressed(compression),
+            None => entry.clone().decompressed()?,
+        };
+        let size = entry.size();
+        let object = encode_frame(entry.data().clone(), entry.metadata.compression(), size);
+        let stored = object.len() as u64;
+
+        self.backend.put(key.bucket(), key.key(), object).await?;
+        self.bytes_written.fetch_add(stored, Ordering::Relaxed);
+        self.logical_bytes_written
+            .fetch_add(size, Ordering::Relaxed);
+        Ok(())
     }
 
     /// Delete an entry from cold storage
@@ -227,6 +844,11 @@ impl L3Cache {
         self.backend.exists(key.bucket(), key.key()).await
     }
 
+    /// List the keys of a bucket in cold storage starting with `prefix`
+    pub async fn list(&self, bucket: &str, prefix: &str) -> crate::error::Result<Vec<String>> {
+        self.backend.list(bucket, prefix).await
+    }
+
     /// Get hit count
     pub fn hits(&self) -> u64 {
         self.hits.load(Ordering::Relaxed)
@@ -248,6 +870,16 @@ impl L3Cache {
         }
     }
 
+    /// Get bytes written to the backend, as stored
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written.load(Ordering::Relaxed)
+    }
+
+    /// Get bytes written to the backend, uncompressed
+    pub fn logical_bytes_written(&self) -> u64 {
+        self.logical_bytes_written.load(Ordering::Relaxed)
+    }
+
     /// Get backend statistics
     pub fn backend_stats(&self) -> L3BackendStats {
         self.backend.stats()
@@ -263,6 +895,10 @@ pub struct L3Stats {
     pub misses: u64,
     /// Hit ratio (0.0 - 1.0)
     pub hit_ratio: f64,
+    /// Bytes written to the backend, as stored
+    pub bytes_written: u64,
+    /// Bytes written to the backend, uncompressed
+    pub logical_bytes_written: u64,
     /// Backend statistics
     pub backend: L3BackendStats,
 }
@@ -274,6 +910,8 @@ impl L3Cache {
             hits: self.hits(),
             misses: self.misses(),
             hit_ratio: self.hit_ratio(),
+            bytes_written: self.bytes_written(),
+            logical_bytes_written: self.logical_bytes_written(),
             backend: self.backend_stats(),
         }
     }
@@ -338,6 +976,25 @@ mod tests {
         assert!(backend.exists("bucket", "key").await.unwrap());
     }
 
+    #[tokio::test]
+    async fn test_in_memory_backend_list() {
+        let backend = InMemoryL3Backend::new();
+
+        for key in ["logs/b", "logs/a", "data/x"] {
+            backend
+                .put("bucket", key, bytes::Bytes::from_static(b"data"))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            backend.list("bucket", "logs/").await.unwrap(),
+            vec!["logs/a", "logs/b"]
+        );
+        assert_eq!(backend.list("bucket", "").await.unwrap().len(), 3);
+        assert!(backend.list("missing", "").await.unwrap().is_empty());
+    }
+
     #[tokio::test]
     async fn test_in_memory_backend_stats() {
         let backend = InMemoryL3Backend::new();
@@ -375,6 +1032,39 @@ mod tests {
         assert_eq!(result.unwrap().data().as_ref(), b"Hello, World!");
     }
 
+    #[tokio::test]
+    async fn test_l3_cache_get_range() {
+        let backend = Arc::new(InMemoryL3Backend::new());
+        let plain = L3Cache::new(backend.clone());
+        let compressed =
+            L3Cache::new(backend.clone()).with_compression(CompressionConfig::default());
+
+        let data: Vec<u8> = (0..8192u32).map(|i| (i % 251) as u8).collect();
+        plain
+            .put(&make_key("bucket", "plain"), &make_entry(&data))
+            .await
+            .unwrap();
+        compressed
+            .put(&make_key("bucket", "framed"), &make_entry(&data))
+            .await
+            .unwrap();
+
+        for name in ["plain", "framed"] {
+            let key = make_key("bucket", name);
+            let range = plain.get_range(&key, 100, 1000).await.unwrap().unwrap();
+            assert_eq!(range.as_ref(), &data[100..1100]);
+            // Cut short at the end of the object
+            let tail = plain.get_range(&key, 8000, 1000).await.unwrap().unwrap();
+            assert_eq!(tail.as_ref(), &data[8000..]);
+            let past = plain.get_range(&key, 9000, 10).await.unwrap().unwrap();
+            assert!(past.is_empty());
+        }
+        let missing = make_key("bucket", "missing");
+        assert!(plain.get_range(&missing, 0, 10).await.unwrap().is_none());
+        assert_eq!(plain.hits(), 6);
+        assert_eq!(plain.misses(), 1);
+    }
+
     #[tokio::test]
     async fn test_l3_cache_miss() {
         let cache = L3Cache::in_memory();
@@ -437,4 +1127,208 @@ mod tests {
         assert_eq!(stats.hit_ratio, 0.5);
         assert_eq!(stats.backend.object_count, 1);
     }
+
+    #[test]
+    fn test_name_encoding() {
+        for name in ["plain", "a/b/../c", ".hidden", "", "sp ace%", "日本"] {
+            let encoded = encode_name(name);
+            assert!(!encoded.starts_with('.'));
+            assert!(!encoded.contains('/'));
+            assert_eq!(decode_name(&encoded).as_deref(), Some(name));
+        }
+        assert_eq!(encode_name(".meta.bucket"), "%2Emeta.bucket");
+
+        let long = "k".repeat(1024);
+        let encoded = encode_name(&long);
+        assert!(encoded.starts_with("%h") && encoded.len() < MAX_ENCODED_NAME);
+        assert_eq!(decode_name(&encoded), None);
+    }
+
+    #[tokio::test]
+    async fn test_fs_backend_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = FsL3Backend::open(dir.path()).unwrap();
+
+        assert!(backend.get("bucket", "key").await.unwrap().is_none());
+        backend
+            .put("bucket", "key", bytes::Bytes::from_static(b"first"))
+            .await
+            .unwrap();
+        backend
+            .put("bucket", "key", bytes::Bytes::from_static(b"second!"))
+            .await
+            .unwrap();
+        assert_eq!(
+            backend.get("bucket", "key").await.unwrap(),
+            Some(bytes::Bytes::from_static(b"second!"))
+        );
+        assert!(backend.exists("bucket", "key").await.unwrap());
+
+        // Only the current data file is kept
+        let bucket_dir = dir.path().join("bucket");
+        assert_eq!(std::fs::read_dir(&bucket_dir).unwrap().count(), 2);
+
+        let stats = backend.stats();
+        assert_eq!(stats.object_count, 1);
+        assert_eq!(stats.total_bytes, 7);
+
+        assert!(backend.delete("bucket", "key").await.unwrap());
+        assert!(!backend.delete("bucket", "key").await.unwrap());
+        assert!(!backend.exists("bucket", "key").await.unwrap());
+        assert_eq!(std::fs::read_dir(&bucket_dir).unwrap().count(), 0);
+        assert_eq!(backend.stats().total_bytes, 0);
+    }
+
+    #[tokio::test]
+    async fn test_fs_backend_list_unusual_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = FsL3Backend::open(dir.path()).unwrap();
+        let long = format!("logs/{}", "x".repeat(1000));
+        let keys = ["logs/a b", "logs/../escape", &long, "other", "logs/%h"];
+        for key in keys {
+            backend
+                .put(".meta.bucket", key, bytes::Bytes::from(key.to_string()))
+                .await
+                .unwrap();
+        }
+
+        let listed = backend.list(".meta.bucket", "logs/").await.unwrap();
+        assert_eq!(
+            listed,
+            vec!["logs/%h", "logs/../escape", "logs/a b", long.as_str()]
+        );
+        for key in keys {
+            let data = backend.get(".meta.bucket", key).await.unwrap().unwrap();
+            assert_eq!(&data[..], key.as_bytes());
+        }
+        assert!(backend.list("missing", "").await.unwrap().is_empty());
+
+        // Everything stays inside the bucket directory
+        let entries: Vec<_> = std::fs::read_dir(dir.path()).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fs_backend_reopen_rebuilds_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let backend = FsL3Backend::open(dir.path()).unwrap();
+            for (bucket, key) in [("a", "1"), ("a", "2"), ("b", "1")] {
+                backend
+                    .put(bucket, key, bytes::Bytes::from_static(b"12345"))
+                    .await
+                    .unwrap();
+            }
+        }
+
+        // Leftovers of interrupted writes and deletes
+        let bucket_dir = dir.path().join("a");
+        std::fs::write(bucket_dir.join(".tmp-123"), b"partial").unwrap();
+        std::fs::write(bucket_dir.join("3.deadbeef.obj"), b"uncommitted").unwrap();
+        std::fs::write(
+            bucket_dir.join("4.meta"),
+            br#"{"key":"4","size":1,"sha256":"","data_file":"4.gone.obj"}"#,
+        )
+        .unwrap();
+
+        let backend = FsL3Backend::open(dir.path()).unwrap();
+        let stats = backend.stats();
+        assert_eq!(stats.object_count, 3);
+        assert_eq!(stats.total_bytes, 15);
+        assert_eq!(backend.list("a", "").await.unwrap(), vec!["1", "2"]);
+        assert_eq!(std::fs::read_dir(&bucket_dir).unwrap().count(), 4);
+        assert_eq!(
+            backend.get("b", "1").await.unwrap(),
+            Some(bytes::Bytes::from_static(b"12345"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fs_backend_detects_corruption() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = FsL3Backend::open(dir.path()).unwrap();
+        backend
+            .put("bucket", "key", bytes::Bytes::from_static(b"payload"))
+            .await
+            .unwrap();
+
+        let data_file = std::fs::read_dir(dir.path().join("bucket"))
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .find(|p| p.extension().is_some_and(|e| e == "obj"))
+            .unwrap();
+        std::fs::write(&data_file, b"PAYLOAD").unwrap();
+        assert!(backend.get("bucket", "key").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_l3_cache_compression() {
+        let backend = Arc::new(InMemoryL3Backend::new());
+        let cache = L3Cache::new(backend.clone()).with_compression(CompressionConfig::default());
+        let plain = L3Cache::new(backend.clone());
+
+        let key = make_key("bucket", "object");
+        let data = vec![3u8; 16 * 1024];
+        cache.put(&key, &make_entry(&data)).await.unwrap();
+        let stored = backend.get("bucket", "object").await.unwrap().unwrap();
+        assert!(stored.len() < data.len());
+        assert_eq!(cache.logical_bytes_written(), data.len() as u64);
+        assert!(cache.bytes_written() < cache.logical_bytes_written());
+
+        // Readable whichever way the reader is configured
+        for reader in [&cache, &plain] {
+            let entry = reader.get(&key).await.unwrap().unwrap();
+            assert_eq!(entry.data().as_ref(), &data[..]);
+        }
+
+        // Data that looks like a frame is framed itself
+        let tricky = make_key("bucket", "tricky");
+        let mut data = FRAME_MAGIC.to_vec();
+        data.extend_from_slice(&[1u8; 16]);
+        plain.put(&tricky, &make_entry(&data)).await.unwrap();
+        let entry = cache.get(&tricky).await.unwrap().unwrap();
+        assert_eq!(entry.data().as_ref(), &data[..]);
+    }
+
+    #[tokio::test]
+    async fn test_fs_backend_get_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = FsL3Backend::open(dir.path()).unwrap();
+        backend
+            .put("bucket", "key", bytes::Bytes::from_static(b"0123456789"))
+            .await
+            .unwrap();
+
+        let range = backend.get_range("bucket", "key", 2, 5).await.unwrap();
+        assert_eq!(range.unwrap().as_ref(), b"23456");
+        let tail = backend.get_range("bucket", "key", 8, 5).await.unwrap();
+        assert_eq!(tail.unwrap().as_ref(), b"89");
+        let past = backend.get_range("bucket", "key", 20, 5).await.unwrap();
+        assert!(past.unwrap().is_empty());
+        assert!(backend
+            .get_range("bucket", "none", 0, 5)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_fs_backend_behind_cache_manager() {
+        use crate::rustfs::cache::CacheManager;
+
+        let dir = tempfile::tempdir().unwrap();
+        let key = make_key("bucket", "object");
+        {
+            let backend = Arc::new(FsL3Backend::open(dir.path()).unwrap());
+            let manager = CacheManager::new(backend);
+            manager
+                .put(key.clone(), make_entry(b"durable"))
+                .await
+                .unwrap();
+        }
+
+        let manager = CacheManager::new(Arc::new(FsL3Backend::open(dir.path()).unwrap()));
+        let result = manager.get(&key).await.unwrap();
+        assert_eq!(&result.entry.data()[..], b"durable");
+    }
 }
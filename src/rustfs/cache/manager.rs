@@ -2,16 +2,40 @@
 //!
 //! Orchestrates L1 (RAM), L2 (NVMe), and L3 (Cold Storage) caches with
 //! automatic promotion/demotion based on access patterns.
+//!
+//! Entries evicted from L1 are demoted into L2, and L2's victims fall
+//! through to L3. A victim that is dirty (written while `write_through` is
+//! off) is written to L3 before it is dropped.
+//!
+//! # Write-back
+//!
+//! With `write_through` off, puts return once the entry is in L1 or L2. A
+//! background flusher ([`CacheManager::run_flusher`]) writes dirty entries
+//! to L3 once they reach `max_dirty_age`, or sooner while more than
+//! `max_dirty_bytes` are dirty. [`CacheManager::flush`] and
+//! [`CacheManager::sync`] flush on demand, and [`CacheManager::shutdown`]
+//! flushes everything before the process exits.
 
 use std::sync::Arc;
 use std::time::Instant;
 
-use super::entry::{CacheEntry, CacheKey};
+use bytes::Bytes;
+use tokio::time::interval;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use super::chunk::{chunk_key, parse_chunk_key, ChunkIndex};
+use super::compression::CompressionConfig;
+use super::entry::{CacheEntry, CacheKey, EntryMetadata};
+use super::invalidation::{Invalidation, InvalidationEpochs, InvalidationTransport};
 use super::l1::{L1Cache, L1Config};
-use super::l2::{L2Cache, L2Config};
-use super::l3::{L3Backend, L3Cache};
+use super::l2::{L2Cache, L2Config, Throttle};
+use super::l3::{slice_range, L3Backend, L3Cache};
 use super::metrics::{CacheMetrics, LatencyTracker, MetricsSnapshot};
 use super::policy::{EvictionPolicy, PromotionPolicy, TargetTier};
+use super::singleflight::SingleFlight;
+use super::warmstate::{HotEntry, HotSetManifest, WarmStateConfig};
+use super::writeback::{WriteBackConfig, WriteBackTracker};
 
 /// Cache tier enum
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -41,14 +65,28 @@ pub struct CacheConfig {
     pub l1: L1Config,
     /// L2 configuration
     pub l2: L2Config,
-    /// Eviction policy
+    /// Eviction policy for L1
     pub eviction_policy: EvictionPolicy,
+    /// Eviction policy for L2
+    pub l2_eviction_policy: EvictionPolicy,
     /// Promotion policy
     pub promotion_policy: PromotionPolicy,
     /// Enable automatic promotion
     pub auto_promotion: bool,
-    /// Enable write-through (write to L3 immediately)
+    /// Enable write-through (write to L3 immediately); write-back otherwise
     pub write_through: bool,
+    /// Write-back flushing, used when `write_through` is off
+    pub write_back: WriteBackConfig,
+    /// Compression of L1 data; off when `None`
+    pub l1_compression: Option<CompressionConfig>,
+    /// Compression of L2 data; off when `None`
+    pub l2_compression: Option<CompressionConfig>,
+    /// Compression of L3 objects; off when `None`
+    pub l3_compression: Option<CompressionConfig>,
+    /// Size of the chunk entries ranged reads cache objects in
+    pub chunk_size: u64,
+    /// Hot-set snapshots restored after a restart
+    pub warm_state: WarmStateConfig,
 }
 
 impl Default for CacheConfig {
@@ -57,9 +95,16 @@ impl Default for CacheConfig {
             l1: L1Config::default(),
             l2: L2Config::default(),
             eviction_policy: EvictionPolicy::default(),
+            l2_eviction_policy: EvictionPolicy::default(),
             promotion_policy: PromotionPolicy::default(),
             auto_promotion: true,
             write_through: true,
+            write_back: WriteBackConfig::default(),
+            l1_compression: None,
+            l2_compression: None,
+            l3_compression: None,
+            chunk_size: 4 * 1024 * 1024, // 4MB
+            warm_state: WarmStateConfig::default(),
         }
     }
 }
@@ -80,13 +125,27 @@ pub struct CacheManager {
     /// L1 (RAM) cache
     l1: L1Cache,
     /// L2 (NVMe) cache
-    l2: L2Cache,
+    l2: Arc<L2Cache>,
     /// L3 (Cold storage) cache
     l3: L3Cache,
     /// Configuration
     config: CacheConfig,
     /// Metrics collector
     metrics: Arc<CacheMetrics>,
+    /// Dirty keys and key locks
+    writeback: WriteBackTracker,
+    /// L3 reads in flight, shared by concurrent misses
+    l3_reads: SingleFlight<Option<CacheEntry>>,
+    /// Objects with chunks cached by ranged reads
+    chunks: ChunkIndex,
+    /// L3 chunk reads in flight; errors are shared as messages
+    chunk_reads: SingleFlight<Result<Option<Bytes>, String>>,
+    /// Bus to the other nodes sharing L3, if any
+    invalidation: Option<Arc<dyn InvalidationTransport>>,
+    /// Invalidations received per key stripe
+    remote_writes: InvalidationEpochs,
+    /// Stops the invalidation listener and warm-state snapshots
+    background_shutdown: CancellationToken,
 }
 
 impl CacheManager {
@@ -97,13 +156,52 @@ impl CacheManager {
 
     /// Create a new cache manager with custom configuration
     pub fn with_config(config: CacheConfig, l3_backend: Arc<dyn L3Backend>) -> Self {
+        let l2 = L2Cache::with_config(config.l2.clone());
         Self {
-            l1: L1Cache::with_config(config.l1.clone()),
-            l2: L2Cache::with_config(config.l2.clone()),
-            l3: L3Cache::new(l3_backend),
+            l1: Self::l1_tier(&config),
+            l2: Arc::new(Self::configure_l2(l2, &config)),
+            l3: Self::l3_tier(&config, l3_backend),
             config,
             metrics: Arc::new(CacheMetrics::new()),
+            writeback: WriteBackTracker::new(0),
+            l3_reads: SingleFlight::new(),
+            chunks: ChunkIndex::new(),
+            chunk_reads: SingleFlight::new(),
+            invalidation: None,
+            remote_writes: InvalidationEpochs::new(),
+            background_shutdown: CancellationToken::new(),
+        }
+    }
+
+    /// Create a cache manager whose L2 tier persists in `config.l2.cache_dir`
+    ///
+    /// Entries cached in L2 by a previous run are served again, and those
+    /// it had not written to L3 are flushed again.
+    pub fn open(config: CacheConfig, l3_backend: Arc<dyn L3Backend>) -> crate::error::Result<Self> {
+        let l2 = Self::configure_l2(L2Cache::open(config.l2.clone())?, &config);
+        let writeback = WriteBackTracker::new(l2.max_generation());
+        for (key, generation, size) in l2.dirty_entries() {
+            writeback.mark_dirty(&key, generation, size);
+        }
+        let chunks = ChunkIndex::new();
+        for (key, index) in l2.keys().iter().filter_map(parse_chunk_key) {
+            let epoch = chunks.epoch(&key);
+            chunks.record(&key, epoch, index);
         }
+        Ok(Self {
+            l1: Self::l1_tier(&config),
+            l2: Arc::new(l2),
+            l3: Self::l3_tier(&config, l3_backend),
+            config,
+            metrics: Arc::new(CacheMetrics::new()),
+            writeback,
+            l3_reads: SingleFlight::new(),
+            chunks,
+            chunk_reads: SingleFlight::new(),
+            invalidation: None,
+            remote_writes: InvalidationEpochs::new(),
+            background_shutdown: CancellationToken::new(),
+        })
     }
 
     /// Create with in-memory L3 backend (for testing)
@@ -111,6 +209,39 @@ impl CacheManager {
         Self::new(Arc::new(super::l3::InMemoryL3Backend::new()))
     }
 
+    /// Keep the caches of other nodes sharing L3 coherent over `transport`
+    ///
+    /// Every write and delete reaching L3 is broadcast to the other nodes,
+    /// which apply it in [`Self::run_invalidation_listener`].
+    pub fn with_invalidation(mut self, transport: Arc<dyn InvalidationTransport>) -> Self {
+        self.invalidation = Some(transport);
+        self
+    }
+
+    fn l1_tier(config: &CacheConfig) -> L1Cache {
+        let l1 = L1Cache::with_config(config.l1.clone()).with_eviction(&config.eviction_policy);
+        match &config.l1_compression {
+            Some(compression) => l1.with_compression(compression.clone()),
+            None => l1,
+        }
+    }
+
+    fn configure_l2(l2: L2Cache, config: &CacheConfig) -> L2Cache {
+        let l2 = l2.with_eviction(&config.l2_eviction_policy);
+        match &config.l2_compression {
+            Some(compression) => l2.with_compression(compression.clone()),
+            None => l2,
+        }
+    }
+
+    fn l3_tier(config: &CacheConfig, backend: Arc<dyn L3Backend>) -> L3Cache {
+        let l3 = L3Cache::new(backend);
+        match &config.l3_compression {
+            Some(compression) => l3.with_compression(compression.clone()),
+            None => l3,
+        }
+    }
+
     /// Get an entry from the cache (searches all tiers)
     pub async fn get(&self, key: &CacheKey) -> Option<CacheResult> {
         let start = Instant::now();
@@ -130,13 +261,13 @@ impl CacheManager {
 
         // Try L2
         let tracker = LatencyTracker::start();
-        if let Some(entry) = self.l2.get(key) {
+        if let Some(entry) = self.l2_get(key).await {
             self.metrics.record_l2_hit();
             self.metrics.record_l2_read_latency(tracker.elapsed());
 
             // Consider promotion to L1
             if self.config.auto_promotion {
-                self.maybe_promote_to_l1(key, &entry);
+                self.maybe_promote_to_l1(key, &entry).await;
             }
 
             return Some(CacheResult {
@@ -147,17 +278,25 @@ impl CacheManager {
         }
         self.metrics.record_l2_miss();
 
-        // Try L3
+        // An entry evicted before it could be written to L3
+        if let Some(Ok(entry)) = self.writeback.evicted(key).map(CacheEntry::decompressed) {
+            return Some(CacheResult {
+                entry,
+                tier: CacheTier::L3,
+                latency: start.elapsed(),
+            });
+        }
+
+        // Try L3, sharing one read between concurrent misses of the key
         let tracker = LatencyTracker::start();
-        if let Ok(Some(entry)) = self.l3.get(key).await {
+        let (entry, coalesced) = self.l3_reads.run(key, || self.fetch_l3(key)).await;
+        if coalesced {
+            self.metrics.record_l3_coalesced();
+        }
+        if let Some(entry) = entry {
             self.metrics.record_l3_hit();
             self.metrics.record_l3_read_latency(tracker.elapsed());
 
-            // Consider promotion to higher tiers
-            if self.config.auto_promotion {
-                self.maybe_promote(key, &entry);
-            }
-
             return Some(CacheResult {
                 entry,
                 tier: CacheTier::L3,
@@ -169,32 +308,295 @@ impl CacheManager {
         None
     }
 
+    /// Read an entry from L3 and promote it
+    ///
+    /// Errors count as misses; being shared only with concurrent lookups,
+    /// they are not remembered.
+    async fn fetch_l3(&self, key: &CacheKey) -> Option<CacheEntry> {
+        let epoch = self.remote_writes.epoch(key);
+        let entry = self.l3.get(key).await.ok()??;
+
+        // Consider promotion to higher tiers
+        if self.config.auto_promotion {
+            self.maybe_promote(key, &entry, epoch).await;
+        }
+        Some(entry)
+    }
+
+    /// Get up to `len` bytes of an object starting at `offset`
+    ///
+    /// Served from the whole object if a tier holds it. Otherwise the range
+    /// is assembled from chunk entries of `chunk_size` bytes, reading the
+    /// chunks no tier holds from L3 and caching them. Returns `None` if the
+    /// object does not exist.
+    pub async fn get_range(
+        &self,
+        key: &CacheKey,
+        offset: u64,
+        len: u64,
+    ) -> crate::error::Result<Option<Bytes>> {
+        if let Some(entry) = self.get_cached(key).await {
+            return Ok(Some(slice_range(entry.data(), offset, len)));
+        }
+
+        let chunk_size = self.config.chunk_size.max(1);
+        let end = offset.saturating_add(len);
+        let mut parts = Vec::new();
+        let mut index = offset / chunk_size;
+        loop {
+            let Some(chunk) = self.get_chunk(key, index).await? else {
+                return Ok(None);
+            };
+            let start = index * chunk_size;
+            let last = (chunk.len() as u64) < chunk_size;
+            parts.push(slice_range(
+                &chunk,
+                offset.saturating_sub(start),
+                end - start.max(offset),
+            ));
+            index += 1;
+            if last || index * chunk_size >= end {
+                break;
+            }
+        }
+
+        if parts.len() == 1 {
+            return Ok(parts.pop());
+        }
+        Ok(Some(Bytes::from(parts.concat())))
+    }
+
+    /// Get an entry from L1, L2 or the evicted entries not yet in L3
+    async fn get_cached(&self, key: &CacheKey) -> Option<CacheEntry> {
+        if let Some(entry) = self.l1.get(key) {
+            self.metrics.record_l1_hit();
+            return Some(entry);
+        }
+        if let Some(entry) = self.l2_get(key).await {
+            self.metrics.record_l2_hit();
+            return Some(entry);
+        }
+        self.writeback
+            .evicted(key)
+            .and_then(|entry| entry.decompressed().ok())
+    }
+
+    /// Get chunk `index` of the object at `key`, cut short if it is the last
+    async fn get_chunk(&self, key: &CacheKey, index: u64) -> crate::error::Result<Option<Bytes>> {
+        let chunk = chunk_key(key, index);
+        if let Some(entry) = self.l1.get(&chunk) {
+            self.metrics.record_l1_hit();
+            return Ok(Some(entry.data().clone()));
+        }
+        self.metrics.record_l1_miss();
+        if let Some(entry) = self.l2_get(&chunk).await {
+            self.metrics.record_l2_hit();
+            if self.config.auto_promotion {
+                self.maybe_promote_to_l1(&chunk, &entry).await;
+            }
+            return Ok(Some(entry.data().clone()));
+        }
+        self.metrics.record_l2_miss();
+
+        let tracker = LatencyTracker::start();
+        let (result, coalesced) = self
+            .chunk_reads
+            .run(&chunk, || async {
+                self.fetch_chunk(key, index, chunk.clone())
+                    .await
+                    .map_err(|e| e.to_string())
+            })
+            .await;
+        if coalesced {
+            self.metrics.record_l3_coalesced();
+        }
+        match result.map_err(crate::error::Error::Internal)? {
+            Some(data) => {
+                self.metrics.record_l3_hit();
+                self.metrics.record_l3_read_latency(tracker.elapsed());
+                Ok(Some(data))
+            }
+            None => {
+                self.metrics.record_l3_miss();
+                Ok(None)
+            }
+        }
+    }
+
+    /// Read a chunk from L3 and cache it
+    async fn fetch_chunk(
+        &self,
+        key: &CacheKey,
+        index: u64,
+        chunk: CacheKey,
+    ) -> crate::error::Result<Option<Bytes>> {
+        let chunk_size = self.config.chunk_size.max(1);
+        let epoch = self.chunks.epoch(key);
+        let Some(data) = self
+            .l3
+            .get_range(key, index * chunk_size, chunk_size)
+            .await?
+        else {
+            return Ok(None);
+        };
+        if data.is_empty() {
+            return Ok(Some(data));
+        }
+
+        let victims = {
+            let _guard = self.writeback.lock(key).await;
+            // L3 is behind a dirty object, and a put since the read has
+            // dropped the object's chunks
+            if self.writeback.contains(key) || !self.chunks.record(key, epoch, index) {
+                return Ok(Some(data));
+            }
+            let entry = CacheEntry::new(data.clone());
+            entry
+                .metadata
+                .set_generation(self.writeback.next_generation());
+            self.store(self.determine_target_tier(entry.size()), chunk, entry)
+                .await
+        };
+        self.flush_victims(victims).await;
+        Ok(Some(data))
+    }
+
+    /// Run an L2 operation, on the blocking pool if it does file I/O
+    ///
+    /// Returns None if the blocking task fails.
+    async fn l2_io<T, F>(&self, op: F) -> Option<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&L2Cache) -> T + Send + 'static,
+    {
+        if !self.l2.is_persistent() {
+            return Some(op(&self.l2));
+        }
+        let l2 = self.l2.clone();
+        match tokio::task::spawn_blocking(move || op(&l2)).await {
+            Ok(result) => Some(result),
+            Err(e) => {
+                warn!("L2 I/O task failed: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Get an entry from L2
+    async fn l2_get(&self, key: &CacheKey) -> Option<CacheEntry> {
+        let key = key.clone();
+        self.l2_io(move |l2| l2.get(&key)).await.flatten()
+    }
+
+    /// Get an entry from L2 without recording a hit
+    async fn l2_peek(&self, key: &CacheKey) -> Option<CacheEntry> {
+        let key = key.clone();
+        self.l2_io(move |l2| l2.peek(&key)).await.flatten()
+    }
+
+    /// Remove an entry from L2, returning whether it was present
+    async fn l2_remove(&self, key: &CacheKey) -> bool {
+        let key = key.clone();
+        self.l2_io(move |l2| l2.remove(&key)).await.unwrap_or(false)
+    }
+
+    /// Put an entry into L2, returning whether it was stored and the
+    /// entries evicted for it
+    async fn l2_put_evicting(
+        &self,
+        key: CacheKey,
+        entry: CacheEntry,
+    ) -> (bool, Vec<(CacheKey, CacheEntry)>) {
+        self.l2_io(move |l2| l2.put_evicting(key, entry))
+            .await
+            .unwrap_or_default()
+    }
+
+    /// Write an entry to L3 and tell the other nodes
+    async fn write_l3(&self, key: &CacheKey, entry: &CacheEntry) -> crate::error::Result<()> {
+        self.l3.put(key, entry).await?;
+        self.broadcast(key, entry.metadata.generation()).await;
+        Ok(())
+    }
+
+    /// Tell the other nodes that L3 holds `generation` of `key`
+    ///
+    /// Best effort: nodes the broadcast does not reach keep stale copies.
+    async fn broadcast(&self, key: &CacheKey, generation: u64) {
+        let Some(transport) = &self.invalidation else {
+            return;
+        };
+        match transport.publish(&Invalidation::new(key, generation)).await {
+            Ok(()) => self.metrics.record_invalidation_sent(),
+            Err(e) => warn!("Failed to broadcast invalidation: {}", e),
+        }
+    }
+
+    /// Drop the cached chunks of the object at `key`
+    ///
+    /// Called with the key lock held.
+    async fn invalidate_chunks(&self, key: &CacheKey) {
+        for index in 0..self.chunks.invalidate(key) {
+            let chunk = chunk_key(key, index);
+            self.l1.remove(&chunk);
+            self.l2_remove(&chunk).await;
+        }
+    }
+
     /// Put an entry into the cache
+    ///
+    /// In write-back mode the entry reaches L3 later; see [`Self::flush`].
     pub async fn put(&self, key: CacheKey, entry: CacheEntry) -> crate::error::Result<CacheTier> {
         let size = entry.size();
         let target = self.determine_target_tier(size);
 
+        let guard = self.writeback.lock(&key).await;
+        let generation = self.writeback.next_generation();
+        entry.metadata.set_generation(generation);
+        self.invalidate_chunks(&key).await;
+
         // Write-through: always persist to L3
         if self.config.write_through {
-            self.l3.put(&key, &entry).await?;
+            self.write_l3(&key, &entry).await?;
+        } else if target != CacheTier::L3 {
+            self.writeback.mark_dirty(&key, generation, size);
+            entry.metadata.mark_dirty();
         }
 
+        // A copy left in the other tier by an earlier put would overwrite
+        // this one when it is demoted
         let tracker = LatencyTracker::start();
-        match target {
+        let victims = match target {
             CacheTier::L1 => {
-                self.l1.put(key, entry);
+                self.l2_remove(&key).await;
+                let victims = self.store(CacheTier::L1, key, entry).await;
                 self.metrics.record_l1_write_latency(tracker.elapsed());
+                victims
             }
             CacheTier::L2 => {
-                self.l2.put(key, entry);
+                self.l1.remove(&key);
+                let victims = self.store(CacheTier::L2, key, entry).await;
                 self.metrics.record_l2_write_latency(tracker.elapsed());
+                victims
             }
             CacheTier::L3 => {
+                self.l1.remove(&key);
+                self.l2_remove(&key).await;
+                self.writeback.remove(&key);
                 // Already written above if write-through enabled
                 if !self.config.write_through {
-                    self.l3.put(&key, &entry).await?;
+                    self.write_l3(&key, &entry).await?;
                 }
+                Vec::new()
             }
+        };
+        drop(guard);
+
+        self.flush_victims(victims).await;
+        if !self.config.write_through
+            && self.writeback.dirty_bytes() > self.config.write_back.max_dirty_bytes
+        {
+            self.writeback.wake();
         }
 
         // Update stats
@@ -205,19 +607,24 @@ impl CacheManager {
 
     /// Delete an entry from all tiers
     pub async fn delete(&self, key: &CacheKey) -> crate::error::Result<bool> {
+        let _guard = self.writeback.lock(key).await;
+        self.writeback.remove(key);
+        self.invalidate_chunks(key).await;
+
         let mut deleted = false;
 
         if self.l1.remove(key).is_some() {
             deleted = true;
         }
 
-        if self.l2.remove(key) {
+        if self.l2_remove(key).await {
             deleted = true;
         }
 
         if self.l3.delete(key).await? {
             deleted = true;
         }
+        self.broadcast(key, self.writeback.next_generation()).await;
 
         Ok(deleted)
     }
@@ -233,6 +640,14 @@ impl CacheManager {
         self.l3.exists(key).await
     }
 
+    /// List the keys of a bucket starting with `prefix`, in sorted order
+    ///
+    /// Lists the L3 tier, which holds every entry when write-through is
+    /// enabled.
+    pub async fn list(&self, bucket: &str, prefix: &str) -> crate::error::Result<Vec<String>> {
+        self.l3.list(bucket, prefix).await
+    }
+
     /// Determine target tier for a new entry
     fn determine_target_tier(&self, size: u64) -> CacheTier {
         let policy = &self.config.promotion_policy;
@@ -250,39 +665,458 @@ impl CacheManager {
         CacheTier::L1
     }
 
+    /// Store an entry in L1 or L2 and demote whatever L1 evicts for it
+    ///
+    /// Returns the entries that fall through to L3: L2's victims, and the
+    /// entry itself if the tier rejects it.
+    async fn store(
+        &self,
+        tier: CacheTier,
+        key: CacheKey,
+        entry: CacheEntry,
+    ) -> Vec<(CacheKey, CacheEntry)> {
+        let rejected = (key.clone(), entry.clone());
+        let (stored, l1_victims, mut l2_victims) = match tier {
+            CacheTier::L1 => {
+                let (stored, victims) = self.l1.put_evicting(key, entry);
+                (stored, victims, Vec::new())
+            }
+            CacheTier::L2 => {
+                let (stored, victims) = self.l2_put_evicting(key, entry).await;
+                (stored, Vec::new(), victims)
+            }
+            CacheTier::L3 => (false, Vec::new(), Vec::new()),
+        };
+        if !stored {
+            l2_victims.push(rejected);
+        }
+
+        for (key, entry) in l1_victims {
+            // L2 already holds the same version if the entry was promoted
+            // from it
+            if self.l2.contains(&key) {
+                self.metrics.record_demotion_l1_to_l2();
+                continue;
+            }
+            let (stored, victims) = self.l2_put_evicting(key.clone(), entry.clone()).await;
+            if stored {
+                self.metrics.record_demotion_l1_to_l2();
+            } else {
+                l2_victims.push((key, entry));
+            }
+            l2_victims.extend(victims);
+        }
+
+        l2_victims
+    }
+
+    /// Write the dirty entries among `victims` to L3
+    ///
+    /// Clean victims are already in L3 and are dropped, as are victims a
+    /// newer put has superseded. A victim that cannot be written is kept
+    /// for the flusher.
+    async fn flush_victims(&self, victims: Vec<(CacheKey, CacheEntry)>) {
+        for (key, entry) in victims {
+            if !entry.metadata.is_dirty() {
+                continue;
+            }
+            let _guard = self.writeback.lock(&key).await;
+            let generation = entry.metadata.generation();
+            if !self.writeback.is_pending(&key, generation) {
+                continue;
+            }
+            match self.write_l3(&key, &entry).await {
+                Ok(()) => {
+                    self.writeback.mark_clean(&key, generation);
+                    self.metrics.record_demotion_l2_to_l3();
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to write evicted entry {}/{} to L3, keeping it for the flusher: {}",
+                        key.bucket(),
+                        key.key(),
+                        e
+                    );
+                    self.writeback.keep_evicted(&key, entry);
+                }
+            }
+        }
+    }
+
     /// Maybe promote an entry from L3 to higher tiers
-    fn maybe_promote(&self, key: &CacheKey, entry: &CacheEntry) {
+    ///
+    /// `epoch` is the invalidation epoch of `key` before the L3 read.
+    async fn maybe_promote(&self, key: &CacheKey, entry: &CacheEntry, epoch: u64) {
         let access_count = entry.metadata.access_count();
         let size = entry.size();
-        let target = self.config.promotion_policy.target_tier(access_count, size);
-
-        match target {
-            TargetTier::L1 => {
-                self.l1.put(key.clone(), entry.clone());
-                self.metrics.record_promotion_l3_to_l2();
-                self.metrics.record_promotion_l2_to_l1();
+        let target = match self.config.promotion_policy.target_tier(access_count, size) {
+            TargetTier::L1 => CacheTier::L1,
+            TargetTier::L2 => CacheTier::L2,
+            TargetTier::L3 => return,
+        };
+
+        let victims = {
+            let _guard = self.writeback.lock(key).await;
+            // A put since the L3 read holds newer data, and a write on
+            // another node may have made the read stale
+            if self.l1.contains(key)
+                || self.l2.contains(key)
+                || self.writeback.contains(key)
+                || self.remote_writes.epoch(key) != epoch
+            {
+                return;
             }
-            TargetTier::L2 => {
-                self.l2.put(key.clone(), entry.clone());
-                self.metrics.record_promotion_l3_to_l2();
+            self.metrics.record_promotion_l3_to_l2();
+            if target == CacheTier::L1 {
+                self.metrics.record_promotion_l2_to_l1();
             }
-            TargetTier::L3 => {}
-        }
+            let entry = entry.clone();
+            entry
+                .metadata
+                .set_generation(self.writeback.next_generation());
+            self.store(target, key.clone(), entry).await
+        };
+        self.flush_victims(victims).await;
     }
 
     /// Maybe promote an entry from L2 to L1
-    fn maybe_promote_to_l1(&self, key: &CacheKey, entry: &CacheEntry) {
+    async fn maybe_promote_to_l1(&self, key: &CacheKey, entry: &CacheEntry) {
         let access_count = entry.metadata.access_count();
         let size = entry.size();
 
-        if self
+        if !self
             .config
             .promotion_policy
             .should_promote_to_l1(access_count, size)
         {
-            self.l1.put(key.clone(), entry.clone());
+            return;
+        }
+
+        let victims = {
+            let _guard = self.writeback.lock(key).await;
+            // A put since the L2 read holds newer data
+            if self.l1.contains(key) || self.l2.generation(key) != Some(entry.metadata.generation())
+            {
+                return;
+            }
             self.metrics.record_promotion_l2_to_l1();
+            self.store(CacheTier::L1, key.clone(), entry.clone()).await
+        };
+        self.flush_victims(victims).await;
+    }
+
+    /// Write every dirty entry to L3
+    ///
+    /// Every entry is tried; the first failure is returned and the failed
+    /// entries stay dirty.
+    pub async fn flush(&self) -> crate::error::Result<()> {
+        self.flush_keys(self.writeback.keys()).await
+    }
+
+    /// Write `key` to L3 if it is dirty
+    pub async fn sync(&self, key: &CacheKey) -> crate::error::Result<()> {
+        self.flush_key(key).await
+    }
+
+    /// Write the entries due by age or by the dirty bytes limit to L3
+    async fn flush_due(&self) -> crate::error::Result<()> {
+        let config = &self.config.write_back;
+        self.flush_keys(
+            self.writeback
+                .due(config.max_dirty_age, config.max_dirty_bytes),
+        )
+        .await
+    }
+
+    async fn flush_keys(&self, keys: Vec<CacheKey>) -> crate::error::Result<()> {
+        let mut result = Ok(());
+        for key in keys {
+            if let Err(e) = self.flush_key(&key).await {
+                warn!(
+                    "Failed to flush {}/{} to L3: {}",
+                    key.bucket(),
+                    key.key(),
+                    e
+                );
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+
+    async fn flush_key(&self, key: &CacheKey) -> crate::error::Result<()> {
+        let _guard = self.writeback.lock(key).await;
+        let Some((generation, evicted)) = self.writeback.pending(key) else {
+            return Ok(());
+        };
+        // Not finding the entry means it was just evicted, and the evicting
+        // put flushes it
+        let mut entry = evicted
+            .or_else(|| self.l1.peek(key))
+            .filter(|entry| entry.metadata.generation() == generation);
+        if entry.is_none() {
+            entry = self
+                .l2_peek(key)
+                .await
+                .filter(|entry| entry.metadata.generation() == generation);
+        }
+        let Some(entry) = entry else {
+            return Ok(());
+        };
+
+        self.write_l3(key, &entry).await?;
+        self.writeback.mark_clean(key, generation);
+        self.l1.mark_clean(key, generation);
+        let key = key.clone();
+        self.l2_io(move |l2| l2.mark_clean(&key, generation)).await;
+        Ok(())
+    }
+
+    /// Flush dirty entries in the background until [`Self::shutdown`]
+    pub async fn run_flusher(self: Arc<Self>) {
+        info!(
+            "Starting write-back flusher with {:?}",
+            self.config.write_back
+        );
+
+        let mut tick = interval(self.config.write_back.flush_interval);
+
+        loop {
+            tokio::select! {
+                _ = tick.tick() => {}
+                _ = self.writeback.woken() => {}
+            }
+
+            if self.writeback.is_shutdown() {
+                info!("Write-back flusher shutting down");
+                break;
+            }
+
+            if let Err(e) = self.flush_due().await {
+                warn!("Write-back flush failed: {}", e);
+            }
+        }
+    }
+
+    /// Drop the copies of a key another node wrote or deleted
+    ///
+    /// Clean copies mirror L3, which the write changed, so they are dropped
+    /// whatever their generation. A dirty copy is kept if it is newer.
+    async fn apply_invalidation(&self, invalidation: &Invalidation) {
+        self.metrics
+            .record_invalidation_received(invalidation.lag());
+        self.writeback.observe_generation(invalidation.generation);
+
+        let key = invalidation.cache_key();
+        let _guard = self.writeback.lock(&key).await;
+        self.remote_writes.bump(&key);
+        self.invalidate_chunks(&key).await;
+        if let Some((generation, _)) = self.writeback.pending(&key) {
+            if generation > invalidation.generation {
+                return;
+            }
+            self.writeback.remove(&key);
+        }
+        self.l1.remove(&key);
+        self.l2_remove(&key).await;
+    }
+
+    /// Apply invalidations from other nodes until [`Self::shutdown`]
+    ///
+    /// Returns at once if the manager has no invalidation transport.
+    pub async fn run_invalidation_listener(self: Arc<Self>) {
+        let Some(transport) = self.invalidation.clone() else {
+            return;
+        };
+        info!("Starting invalidation listener");
+
+        loop {
+            let received = tokio::select! {
+                _ = self.background_shutdown.cancelled() => break,
+                received = transport.next() => received,
+            };
+            match received {
+                Ok(Some(invalidation)) => {
+                    debug!(
+                        "Invalidating {}/{} at generation {}",
+                        invalidation.bucket, invalidation.key, invalidation.generation
+                    );
+                    self.apply_invalidation(&invalidation).await;
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    warn!("Failed to receive invalidation: {}", e);
+                    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                }
+            }
+        }
+        info!("Invalidation listener shutting down");
+    }
+
+    /// Record the hot set and restore it in the background until
+    /// [`Self::shutdown`]
+    ///
+    /// Returns at once if `config.warm_state.manifest_path` is not set.
+    pub async fn run_warm_state(self: Arc<Self>) {
+        if self.config.warm_state.manifest_path.is_none() {
+            return;
+        }
+
+        match self.restore_warm_state().await {
+            Ok(restored) => info!("Restored {} hot entries", restored),
+            Err(e) => warn!("Failed to restore warm state: {}", e),
+        }
+
+        let mut tick = interval(self.config.warm_state.snapshot_interval);
+        // The first tick completes at once
+        tick.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = self.background_shutdown.cancelled() => break,
+                _ = tick.tick() => {}
+            }
+            if let Err(e) = self.save_warm_state() {
+                warn!("Failed to save warm state: {}", e);
+            }
+        }
+        info!("Warm-state snapshots shutting down");
+    }
+
+    /// Get the keys cached in L1 and L2, hottest first
+    pub fn hot_set(&self) -> HotSetManifest {
+        let l1 = self.l1.metadata().into_iter().map(|m| (CacheTier::L1, m));
+        let l2 = self.l2.metadata().into_iter().map(|m| (CacheTier::L2, m));
+        let entries = l1
+            .chain(l2)
+            .filter(|(_, (_, metadata))| !metadata.is_expired())
+            .map(|(tier, (key, metadata))| HotEntry::new(&key, tier, &metadata))
+            .collect();
+        HotSetManifest::new(entries, self.config.warm_state.max_entries)
+    }
+
+    /// Record the hot set in `config.warm_state.manifest_path`, if set
+    pub fn save_warm_state(&self) -> crate::error::Result<()> {
+        let Some(path) = &self.config.warm_state.manifest_path else {
+            return Ok(());
+        };
+        let manifest = self.hot_set();
+        manifest.save(path)?;
+        debug!("Saved {} hot entries to {}", manifest.len(), path.display());
+        Ok(())
+    }
+
+    /// Read the recorded hot set back into the tiers it was cached in
+    ///
+    /// Entries are read from L2 if it still holds them and from L3
+    /// otherwise, hottest first and at `config.warm_state.restore_rate`.
+    /// Entries that no longer fit in their tier are skipped, as are keys
+    /// written since the restart. Returns the number of entries restored.
+    pub async fn restore_warm_state(&self) -> crate::error::Result<usize> {
+        let Some(path) = &self.config.warm_state.manifest_path else {
+            return Ok(0);
+        };
+        let Some(manifest) = HotSetManifest::load(path)? else {
+            return Ok(0);
+        };
+
+        let mut throttle = Throttle::new(self.config.warm_state.restore_rate);
+        let mut restored = 0;
+        for hot in manifest.entries() {
+            if self.background_shutdown.is_cancelled() {
+                break;
+            }
+            if let Some(size) = self.restore_entry(hot).await {
+                self.metrics.record_warm_restored(size);
+                throttle.consume_async(size).await;
+                restored += 1;
+            }
+        }
+        Ok(restored)
+    }
+
+    /// Restore one entry of the hot set, returning the bytes read
+    async fn restore_entry(&self, hot: &HotEntry) -> Option<u64> {
+        let key = hot.cache_key();
+        let tier = hot.tier();
+        let (used, capacity) = match tier {
+            CacheTier::L1 => (self.l1.size(), self.l1.capacity()),
+            _ => (self.l2.size(), self.l2.capacity()),
+        };
+        if used + hot.size > capacity
+            || self.l1.contains(&key)
+            || (tier == CacheTier::L2 && self.l2.contains(&key))
+        {
+            return None;
         }
+
+        let epoch = self.remote_writes.epoch(&key);
+        // Chunks are only cached along with their object's chunk index
+        // entry, which is rebuilt from L2
+        let (entry, generation) = match self.l2_peek(&key).await {
+            Some(entry) => {
+                let generation = entry.metadata.generation();
+                (entry, Some(generation))
+            }
+            None if parse_chunk_key(&key).is_none() => (self.l3.get(&key).await.ok()??, None),
+            None => return None,
+        };
+
+        let metadata = EntryMetadata::restore(
+            entry.size(),
+            entry.metadata.content_hash(),
+            entry.metadata.created_at(),
+            entry.metadata.ttl_seconds(),
+            hot.access_count,
+            hot.last_access,
+        );
+        let size = entry.size();
+        let entry = CacheEntry::with_metadata(entry.data().clone(), metadata);
+
+        let victims = {
+            let _guard = self.writeback.lock(&key).await;
+            // A put since the read holds newer data
+            if self.l1.contains(&key)
+                || self.writeback.contains(&key)
+                || self.remote_writes.epoch(&key) != epoch
+                || self.l2.generation(&key) != generation
+            {
+                return None;
+            }
+            entry.metadata.set_generation(match generation {
+                Some(generation) => generation,
+                None => self.writeback.next_generation(),
+            });
+            self.store(tier, key, entry).await
+        };
+        self.flush_victims(victims).await;
+        Some(size)
+    }
+
+    /// Stop the background tasks and write every dirty entry to L3
+    ///
+    /// The hot set is recorded last, if warm state is configured.
+    pub async fn shutdown(&self) -> crate::error::Result<()> {
+        self.writeback.shutdown();
+        self.background_shutdown.cancel();
+        self.l2.shutdown_compactor();
+        self.flush().await?;
+        if let Err(e) = self.save_warm_state() {
+            warn!("Failed to save warm state: {}", e);
+        }
+        Ok(())
+    }
+
+    /// Get the number of bytes not yet written to L3
+    pub fn dirty_bytes(&self) -> u64 {
+        self.writeback.dirty_bytes()
+    }
+
+    /// Get the number of entries not yet written to L3
+    pub fn dirty_entries(&self) -> usize {
+        self.writeback.len()
     }
 
     /// Update internal statistics
@@ -309,6 +1143,13 @@ impl CacheManager {
         &self.l2
     }
 
+    /// Start compacting L2 segments in the background
+    ///
+    /// Stop it with `l2().shutdown_compactor()`.
+    pub fn spawn_l2_compactor(&self) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(self.l2.clone().run_compactor())
+    }
+
     /// Get reference to L3 cache
     pub fn l3(&self) -> &L3Cache {
         &self.l3
@@ -320,9 +1161,27 @@ impl CacheManager {
     }
 
     /// Clear all caches
+    ///
+    /// Dirty entries are flushed first; those that cannot be are kept for
+    /// the flusher.
     pub async fn clear(&self) {
+        if let Err(e) = self.flush().await {
+            warn!("Failed to flush before clearing the cache: {}", e);
+        }
+        for key in self.writeback.keys() {
+            let _guard = self.writeback.lock(&key).await;
+            let entry = match self.l1.peek(&key) {
+                Some(entry) => Some(entry),
+                None => self.l2_peek(&key).await,
+            };
+            if let Some(entry) = entry {
+                self.writeback.keep_evicted(&key, entry);
+            }
+        }
+
         self.l1.clear();
-        self.l2.clear();
+        self.l2_io(|l2| l2.clear()).await;
+        self.chunks.clear();
         // Note: L3 clear would need to be implemented based on backend
     }
 
@@ -454,8 +1313,10 @@ mod tests {
 
     #[tokio::test]
     async fn test_write_through() {
-        let mut config = CacheConfig::default();
-        config.write_through = true;
+        let config = CacheConfig {
+            write_through: true,
+            ..Default::default()
+        };
 
         let backend = Arc::new(super::super::l3::InMemoryL3Backend::new());
         let manager = CacheManager::with_config(config, backend.clone());
@@ -492,8 +1353,10 @@ mod tests {
 
     #[tokio::test]
     async fn test_auto_promotion() {
-        let mut config = CacheConfig::default();
-        config.auto_promotion = true;
+        let mut config = CacheConfig {
+            auto_promotion: true,
+            ..Default::default()
+        };
         config.promotion_policy.l1_promotion_threshold = 1; // Promote on first access
 
         let manager =
@@ -519,8 +1382,10 @@ mod tests {
     #[tokio::test]
     async fn test_integration_l3_to_l2_to_l1_promotion_flow() {
         // Test complete promotion flow: L3 → L2 → L1 based on access patterns
-        let mut config = CacheConfig::default();
-        config.auto_promotion = true;
+        let mut config = CacheConfig {
+            auto_promotion: true,
+            ..Default::default()
+        };
         config.promotion_policy.l1_promotion_threshold = 3; // Need 3 accesses for L1
         config.promotion_policy.l2_promotion_threshold = 1; // Need 1 access for L2
         config.promotion_policy.l1_max_size = 10_000; // Allow small objects in L1
@@ -673,8 +1538,10 @@ mod tests {
     #[tokio::test]
     async fn test_integration_write_through_to_l3() {
         // Test that write-through mode persists all writes to L3
-        let mut config = CacheConfig::default();
-        config.write_through = true;
+        let config = CacheConfig {
+            write_through: true,
+            ..Default::default()
+        };
 
         let backend = Arc::new(super::super::l3::InMemoryL3Backend::new());
         let manager = CacheManager::with_config(config, backend.clone());
@@ -737,4 +1604,596 @@ mod tests {
         assert!(metrics.l1_size_bytes <= config.l1.capacity * 2); // Allow some overflow
         assert!(metrics.l1_entries <= 20);
     }
+
+    fn small_tiers_config(write_through: bool) -> CacheConfig {
+        CacheConfig {
+            l1: L1Config {
+                capacity: 64 * 1024,
+                ..Default::default()
+            },
+            l2: L2Config {
+                capacity: 64 * 1024,
+                min_entry_size: 1024,
+                ..Default::default()
+            },
+            write_through,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_l1_eviction_demotes_to_l2() {
+        let manager = CacheManager::with_config(
+            small_tiers_config(true),
+            Arc::new(super::super::l3::InMemoryL3Backend::new()),
+        );
+
+        for i in 0..24u8 {
+            let key = make_key("bucket", &format!("object-{}", i));
+            manager.put(key, make_entry(&[i; 4096])).await.unwrap();
+        }
+
+        let metrics = manager.metrics();
+        assert!(manager.l1().evictions() > 0);
+        assert_eq!(metrics.demotions_l1_to_l2, manager.l1().evictions());
+        assert_eq!(metrics.demotions_l2_to_l3, 0);
+        for i in 0..24u8 {
+            let key = make_key("bucket", &format!("object-{}", i));
+            assert!(manager.l1().contains(&key) || manager.l2().contains(&key));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dirty_victims_are_written_to_l3() {
+        let backend = Arc::new(super::super::l3::InMemoryL3Backend::new());
+        let manager = CacheManager::with_config(small_tiers_config(false), backend.clone());
+
+        for i in 0..64u8 {
+            let key = make_key("bucket", &format!("object-{}", i));
+            manager.put(key, make_entry(&[i; 4096])).await.unwrap();
+        }
+
+        // Nothing written with write-through off is lost to eviction
+        let metrics = manager.metrics();
+        assert!(manager.l2().evictions() > 0);
+        assert_eq!(metrics.demotions_l2_to_l3, manager.l2().evictions());
+        for i in 0..64u8 {
+            let key = make_key("bucket", &format!("object-{}", i));
+            if manager.l1().contains(&key) || manager.l2().contains(&key) {
+                assert!(!backend.exists("bucket", key.key()).await.unwrap());
+            } else {
+                let data = backend.get("bucket", key.key()).await.unwrap().unwrap();
+                assert_eq!(data.as_ref(), &[i; 4096]);
+            }
+        }
+    }
+
+    /// In-memory L3 whose writes fail while `failing` is set
+    struct FlakyBackend {
+        inner: super::super::l3::InMemoryL3Backend,
+        failing: std::sync::atomic::AtomicBool,
+    }
+
+    #[async_trait::async_trait]
+    impl L3Backend for FlakyBackend {
+        async fn get(&self, bucket: &str, key: &str) -> crate::error::Result<Option<Bytes>> {
+            self.inner.get(bucket, key).await
+        }
+
+        async fn put(&self, bucket: &str, key: &str, data: Bytes) -> crate::error::Result<()> {
+            if self.failing.load(std::sync::atomic::Ordering::SeqCst) {
+                return Err(crate::error::Error::Internal("L3 unavailable".into()));
+            }
+            self.inner.put(bucket, key, data).await
+        }
+
+        async fn delete(&self, bucket: &str, key: &str) -> crate::error::Result<bool> {
+            self.inner.delete(bucket, key).await
+        }
+
+        async fn exists(&self, bucket: &str, key: &str) -> crate::error::Result<bool> {
+            self.inner.exists(bucket, key).await
+        }
+
+        async fn list(&self, bucket: &str, prefix: &str) -> crate::error::Result<Vec<String>> {
+            self.inner.list(bucket, prefix).await
+        }
+
+        fn stats(&self) -> super::super::l3::L3BackendStats {
+            self.inner.stats()
+        }
+    }
+
+    /// Backend whose reads take a while
+    struct SlowBackend {
+        inner: super::super::l3::InMemoryL3Backend,
+    }
+
+    #[async_trait::async_trait]
+    impl L3Backend for SlowBackend {
+        async fn get(&self, bucket: &str, key: &str) -> crate::error::Result<Option<Bytes>> {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            self.inner.get(bucket, key).await
+        }
+
+        async fn put(&self, bucket: &str, key: &str, data: Bytes) -> crate::error::Result<()> {
+            self.inner.put(bucket, key, data).await
+        }
+
+        async fn delete(&self, bucket: &str, key: &str) -> crate::error::Result<bool> {
+            self.inner.delete(bucket, key).await
+        }
+
+        async fn exists(&self, bucket: &str, key: &str) -> crate::error::Result<bool> {
+            self.inner.exists(bucket, key).await
+        }
+
+        async fn list(&self, bucket: &str, prefix: &str) -> crate::error::Result<Vec<String>> {
+            self.inner.list(bucket, prefix).await
+        }
+
+        fn stats(&self) -> super::super::l3::L3BackendStats {
+            self.inner.stats()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_l3_misses_share_one_read() {
+        let backend = Arc::new(SlowBackend {
+            inner: super::super::l3::InMemoryL3Backend::new(),
+        });
+        backend
+            .put("bucket", "hot", Bytes::from_static(b"hot data"))
+            .await
+            .unwrap();
+        let manager = Arc::new(CacheManager::new(backend.clone()));
+
+        let key = make_key("bucket", "hot");
+        let lookups: Vec<_> = (0..16)
+            .map(|_| {
+                let manager = manager.clone();
+                let key = key.clone();
+                tokio::spawn(async move { manager.get(&key).await })
+            })
+            .collect();
+        for lookup in lookups {
+            let result = lookup.await.unwrap().unwrap();
+            assert_eq!(result.entry.data().as_ref(), b"hot data");
+        }
+
+        assert_eq!(backend.stats().reads, 1);
+        let metrics = manager.metrics();
+        assert_eq!(metrics.l3_hits, 16);
+        assert_eq!(metrics.l3_coalesced, 15);
+
+        // Later misses read L3 again
+        let missing = make_key("bucket", "missing");
+        assert!(manager.get(&missing).await.is_none());
+        assert!(manager.get(&missing).await.is_none());
+        assert_eq!(backend.stats().reads, 3);
+    }
+
+    #[tokio::test]
+    async fn test_get_range_caches_chunks() {
+        let backend = Arc::new(super::super::l3::InMemoryL3Backend::new());
+        let config = CacheConfig {
+            chunk_size: 1024,
+            ..Default::default()
+        };
+        let manager = CacheManager::with_config(config, backend.clone());
+
+        let key = make_key("bucket", "large");
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        backend
+            .put("bucket", "large", Bytes::from(data.clone()))
+            .await
+            .unwrap();
+
+        // Spans chunks 1 to 4
+        let range = manager.get_range(&key, 1500, 3000).await.unwrap().unwrap();
+        assert_eq!(range.as_ref(), &data[1500..4500]);
+        let reads = backend.stats().reads;
+        assert_eq!(manager.l1().len(), 4);
+        assert!(!manager.l1().contains(&key));
+
+        // Served from the cached chunks
+        let range = manager.get_range(&key, 2000, 100).await.unwrap().unwrap();
+        assert_eq!(range.as_ref(), &data[2000..2100]);
+        assert_eq!(backend.stats().reads, reads);
+
+        // The last chunk is short, and ranges stop at the end of the object
+        let tail = manager.get_range(&key, 9000, 5000).await.unwrap().unwrap();
+        assert_eq!(tail.as_ref(), &data[9000..]);
+        assert!(manager
+            .get_range(&make_key("bucket", "missing"), 0, 10)
+            .await
+            .unwrap()
+            .is_none());
+
+        // A put drops the chunks of the old version
+        assert_eq!(manager.l1().len(), 6);
+        manager
+            .put(key.clone(), make_entry(b"replaced"))
+            .await
+            .unwrap();
+        assert_eq!(manager.l1().len(), 1);
+        let range = manager.get_range(&key, 2, 4).await.unwrap().unwrap();
+        assert_eq!(range.as_ref(), b"plac");
+    }
+
+    #[tokio::test]
+    async fn test_write_back_defers_l3_writes() {
+        let backend = Arc::new(super::super::l3::InMemoryL3Backend::new());
+        let config = CacheConfig {
+            write_through: false,
+            ..Default::default()
+        };
+        let manager = CacheManager::with_config(config, backend.clone());
+
+        let key = make_key("bucket", "object");
+        manager.put(key.clone(), make_entry(b"v1")).await.unwrap();
+        manager.put(key.clone(), make_entry(b"v2")).await.unwrap();
+        assert!(!backend.exists("bucket", "object").await.unwrap());
+        assert_eq!(manager.dirty_entries(), 1);
+        assert_eq!(manager.dirty_bytes(), 2);
+
+        // Only the latest version reaches L3
+        manager.sync(&key).await.unwrap();
+        let data = backend.get("bucket", "object").await.unwrap().unwrap();
+        assert_eq!(data.as_ref(), b"v2");
+        assert_eq!(manager.dirty_entries(), 0);
+        assert!(!manager.l1().peek(&key).unwrap().metadata.is_dirty());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_flushes_dirty_entries() {
+        let backend = Arc::new(super::super::l3::InMemoryL3Backend::new());
+        let config = CacheConfig {
+            write_through: false,
+            ..Default::default()
+        };
+        let manager = CacheManager::with_config(config, backend.clone());
+
+        for i in 0..10u8 {
+            let key = make_key("bucket", &format!("object-{}", i));
+            manager.put(key, make_entry(&[i; 100])).await.unwrap();
+        }
+        manager.shutdown().await.unwrap();
+
+        assert_eq!(manager.dirty_bytes(), 0);
+        for i in 0..10u8 {
+            let data = backend
+                .get("bucket", &format!("object-{}", i))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(data.as_ref(), &[i; 100]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalidation_drops_stale_copies_on_other_nodes() {
+        let backend = Arc::new(super::super::l3::InMemoryL3Backend::new());
+        let bus = super::super::invalidation::InMemoryInvalidationBus::default();
+        let node = |write_through| {
+            let config = CacheConfig {
+                write_through,
+                ..Default::default()
+            };
+            Arc::new(
+                CacheManager::with_config(config, backend.clone())
+                    .with_invalidation(Arc::new(bus.connect())),
+            )
+        };
+        let a = node(true);
+        let b = node(false);
+        let listeners = [
+            tokio::spawn(a.clone().run_invalidation_listener()),
+            tokio::spawn(b.clone().run_invalidation_listener()),
+        ];
+        async fn received(node: &CacheManager, count: u64) {
+            tokio::time::timeout(std::time::Duration::from_secs(5), async {
+                while node.metrics().invalidations_received < count {
+                    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+                }
+            })
+            .await
+            .unwrap();
+        }
+
+        let key = make_key("bucket", "object");
+        b.put(key.clone(), make_entry(b"v1")).await.unwrap();
+        b.sync(&key).await.unwrap();
+        received(&a, 1).await;
+        assert!(b.l1().contains(&key));
+
+        // A write on A drops B's clean copy
+        a.put(key.clone(), make_entry(b"v2")).await.unwrap();
+        received(&b, 1).await;
+        assert!(!b.l1().contains(&key));
+        assert_eq!(b.get(&key).await.unwrap().entry.data().as_ref(), b"v2");
+
+        // B's unflushed write is newer than A's and survives it
+        b.put(key.clone(), make_entry(b"v3")).await.unwrap();
+        b.apply_invalidation(&Invalidation::new(&key, 1)).await;
+        assert_eq!(b.get(&key).await.unwrap().entry.data().as_ref(), b"v3");
+        assert_eq!(b.dirty_entries(), 1);
+
+        b.shutdown().await.unwrap();
+        received(&a, 2).await;
+        assert_eq!(a.get(&key).await.unwrap().entry.data().as_ref(), b"v3");
+        assert_eq!(a.metrics().invalidations_sent, 1);
+        assert_eq!(b.metrics().invalidations_sent, 2);
+
+        a.shutdown().await.unwrap();
+        for listener in listeners {
+            listener.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_warm_state_restored_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = Arc::new(super::super::l3::InMemoryL3Backend::new());
+        let config = CacheConfig {
+            warm_state: WarmStateConfig {
+                manifest_path: Some(dir.path().join("hotset")),
+                restore_rate: 0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let manager = CacheManager::with_config(config.clone(), backend.clone());
+        for i in 0..4u8 {
+            let key = make_key("bucket", &format!("object-{}", i));
+            manager.put(key, make_entry(&[i; 100])).await.unwrap();
+        }
+        let hot = make_key("bucket", "object-2");
+        for _ in 0..5 {
+            manager.get(&hot).await.unwrap();
+        }
+        assert_eq!(manager.hot_set().entries()[0].cache_key(), hot);
+        manager.shutdown().await.unwrap();
+
+        // A key written before the restore keeps its new data
+        let manager = Arc::new(CacheManager::with_config(config, backend));
+        let fresh = make_key("bucket", "object-0");
+        manager
+            .put(fresh.clone(), make_entry(b"fresh"))
+            .await
+            .unwrap();
+        let warm = tokio::spawn(manager.clone().run_warm_state());
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while manager.metrics().warm_restored < 3 {
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(manager.metrics().warm_restored_bytes, 300);
+        let restored = manager.l1().peek(&hot).unwrap();
+        assert_eq!(restored.data().as_ref(), &[2; 100]);
+        assert!(restored.metadata.access_count() > 5);
+        let data = manager.get(&fresh).await.unwrap().entry;
+        assert_eq!(data.data().as_ref(), b"fresh");
+
+        manager.shutdown().await.unwrap();
+        warm.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_flusher_writes_aged_entries() {
+        let backend = Arc::new(super::super::l3::InMemoryL3Backend::new());
+        let config = CacheConfig {
+            write_through: false,
+            write_back: WriteBackConfig {
+                max_dirty_age: std::time::Duration::from_millis(20),
+                flush_interval: std::time::Duration::from_millis(10),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let manager = Arc::new(CacheManager::with_config(config, backend.clone()));
+        let flusher = tokio::spawn(manager.clone().run_flusher());
+
+        manager
+            .put(make_key("bucket", "object"), make_entry(b"data"))
+            .await
+            .unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while manager.dirty_entries() > 0 {
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert!(backend.exists("bucket", "object").await.unwrap());
+
+        manager.shutdown().await.unwrap();
+        flusher.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_flusher_bounds_dirty_bytes() {
+        let backend = Arc::new(super::super::l3::InMemoryL3Backend::new());
+        let config = CacheConfig {
+            write_through: false,
+            write_back: WriteBackConfig {
+                max_dirty_age: std::time::Duration::from_secs(3600),
+                max_dirty_bytes: 1000,
+                flush_interval: std::time::Duration::from_secs(3600),
+            },
+            ..Default::default()
+        };
+        let manager = Arc::new(CacheManager::with_config(config, backend.clone()));
+        let flusher = tokio::spawn(manager.clone().run_flusher());
+
+        for i in 0..10u8 {
+            let key = make_key("bucket", &format!("object-{}", i));
+            manager.put(key, make_entry(&[i; 400])).await.unwrap();
+        }
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while manager.dirty_bytes() > 1000 {
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+        // The oldest entries went first
+        assert!(backend.exists("bucket", "object-0").await.unwrap());
+
+        manager.shutdown().await.unwrap();
+        flusher.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_evicted_dirty_entry_survives_l3_failure() {
+        let backend = Arc::new(FlakyBackend {
+            inner: super::super::l3::InMemoryL3Backend::new(),
+            failing: std::sync::atomic::AtomicBool::new(true),
+        });
+        let manager = CacheManager::with_config(small_tiers_config(false), backend.clone());
+
+        for i in 0..64u8 {
+            let key = make_key("bucket", &format!("object-{}", i));
+            manager.put(key, make_entry(&[i; 4096])).await.unwrap();
+        }
+        assert!(manager.l2().evictions() > 0);
+        assert_eq!(manager.dirty_entries(), 64);
+
+        // Evicted entries are still served, then flushed once L3 is back
+        let evicted = make_key("bucket", "object-0");
+        assert!(!manager.l1().contains(&evicted) && !manager.l2().contains(&evicted));
+        let result = manager.get(&evicted).await.unwrap();
+        assert_eq!(result.entry.data().as_ref(), &[0u8; 4096]);
+
+        assert!(manager.flush().await.is_err());
+        backend
+            .failing
+            .store(false, std::sync::atomic::Ordering::SeqCst);
+        manager.flush().await.unwrap();
+        assert_eq!(manager.dirty_entries(), 0);
+        for i in 0..64u8 {
+            let data = backend
+                .get("bucket", &format!("object-{}", i))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(data.as_ref(), &[i; 4096]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_per_tier_compression() {
+        let backend = Arc::new(super::super::l3::InMemoryL3Backend::new());
+        let config = CacheConfig {
+            l2_compression: Some(CompressionConfig::default()),
+            l3_compression: Some(CompressionConfig::default()),
+            ..small_tiers_config(true)
+        };
+        let manager = CacheManager::with_config(config, backend.clone());
+
+        // Enough entries to push some out of the uncompressed L1 into L2
+        for i in 0..48u8 {
+            let key = make_key("bucket", &format!("object-{}", i));
+            manager.put(key, make_entry(&[i; 4096])).await.unwrap();
+        }
+        assert_eq!(manager.l1().size(), manager.l1().logical_size());
+        assert!(manager.l2().size() < manager.l2().logical_size());
+        assert!(manager.l3().bytes_written() < manager.l3().logical_bytes_written());
+
+        for i in 0..48u8 {
+            let key = make_key("bucket", &format!("object-{}", i));
+            let result = manager.get(&key).await.unwrap();
+            assert!(!result.entry.is_compressed());
+            assert_eq!(result.entry.data().as_ref(), &[i; 4096][..]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dirty_l2_entries_flush_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = CacheConfig {
+            write_through: false,
+            ..Default::default()
+        };
+        config.promotion_policy.l1_max_size = 1024;
+        config.l2.cache_dir = dir.path().to_path_buf();
+        config.l2.min_entry_size = 0;
+
+        let key = make_key("bucket", "unflushed");
+        {
+            let manager = CacheManager::open(
+                config.clone(),
+                Arc::new(super::super::l3::InMemoryL3Backend::new()),
+            )
+            .unwrap();
+            manager
+                .put(key.clone(), make_entry(&[7u8; 8192]))
+                .await
+                .unwrap();
+        }
+
+        let backend = Arc::new(super::super::l3::InMemoryL3Backend::new());
+        {
+            let manager = CacheManager::open(config.clone(), backend.clone()).unwrap();
+            assert_eq!(manager.dirty_entries(), 1);
+            manager.flush().await.unwrap();
+            let data = backend.get("bucket", "unflushed").await.unwrap().unwrap();
+            assert_eq!(data.as_ref(), &[7u8; 8192]);
+        }
+
+        // Once flushed, the entry is not written to L3 again
+        let manager = CacheManager::open(config, backend).unwrap();
+        assert_eq!(manager.dirty_entries(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_persistent_l2_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = CacheConfig {
+            write_through: false,
+            ..Default::default()
+        };
+        config.promotion_policy.l1_max_size = 1024;
+        config.l2.cache_dir = dir.path().to_path_buf();
+        config.l2.min_entry_size = 0;
+
+        let key = make_key("bucket", "warm");
+        let data = vec![42u8; 8192];
+        {
+            let manager = CacheManager::open(
+                config.clone(),
+                Arc::new(super::super::l3::InMemoryL3Backend::new()),
+            )
+            .unwrap();
+            assert!(manager.l2().is_persistent());
+            let tier = manager.put(key.clone(), make_entry(&data)).await.unwrap();
+            assert_eq!(tier, CacheTier::L2);
+        }
+
+        // L3 is empty, so the entry can only come from the reopened L2
+        let manager =
+            CacheManager::open(config, Arc::new(super::super::l3::InMemoryL3Backend::new()))
+                .unwrap();
+        let result = manager.get(&key).await.unwrap();
+        assert_eq!(result.tier, CacheTier::L2);
+        assert_eq!(result.entry.data().as_ref(), &data[..]);
+    }
+
+    #[tokio::test]
+    async fn test_eviction_policy_per_tier() {
+        let config = CacheConfig {
+            eviction_policy: EvictionPolicy::tiny_lfu(),
+            l2_eviction_policy: EvictionPolicy::s3_fifo(),
+            ..Default::default()
+        };
+        let manager =
+            CacheManager::with_config(config, Arc::new(super::super::l3::InMemoryL3Backend::new()));
+
+        assert_eq!(manager.l1().eviction_algorithm(), "w-tinylfu");
+        assert_eq!(manager.l2().eviction_algorithm(), "s3-fifo");
+    }
 }
@@ -25,6 +25,7 @@ pub struct CacheMetrics {
     // L3 metrics
     l3_hits: AtomicU64,
     l3_misses: AtomicU64,
+    l3_coalesced: AtomicU64,
 
     // Operation latencies (microseconds, using exponential moving average)
     l1_read_latency_us: AtomicU64,
@@ -39,6 +40,15 @@ pub struct CacheMetrics {
     demotions_l1_to_l2: AtomicU64,
     demotions_l2_to_l3: AtomicU64,
 
+    // Cross-node invalidation metrics
+    invalidations_sent: AtomicU64,
+    invalidations_received: AtomicU64,
+    invalidation_lag_us: AtomicU64,
+
+    // Warm-state restore metrics
+    warm_restored: AtomicU64,
+    warm_restored_bytes: AtomicU64,
+
     // Throughput (bytes per second, sampled)
     read_throughput_bps: AtomicU64,
     write_throughput_bps: AtomicU64,
@@ -139,6 +149,15 @@ impl CacheMetrics {
         self.l3_misses.load(Ordering::Relaxed)
     }
 
+    /// Record a lookup served by another lookup's L3 read
+    pub fn record_l3_coalesced(&self) {
+        self.l3_coalesced.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn l3_coalesced(&self) -> u64 {
+        self.l3_coalesced.load(Ordering::Relaxed)
+    }
+
     // Latency tracking
     pub fn record_l1_read_latency(&self, duration: Duration) {
         self.update_latency_ema(&self.l1_read_latency_us, duration);
@@ -218,6 +237,44 @@ impl CacheMetrics {
         self.demotions_l2_to_l3.fetch_add(1, Ordering::Relaxed);
     }
 
+    // Cross-node invalidation
+    pub fn record_invalidation_sent(&self) {
+        self.invalidations_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record an invalidation received `lag` after another node sent it
+    pub fn record_invalidation_received(&self, lag: Duration) {
+        self.invalidations_received.fetch_add(1, Ordering::Relaxed);
+        self.update_latency_ema(&self.invalidation_lag_us, lag);
+    }
+
+    pub fn invalidations_sent(&self) -> u64 {
+        self.invalidations_sent.load(Ordering::Relaxed)
+    }
+
+    pub fn invalidations_received(&self) -> u64 {
+        self.invalidations_received.load(Ordering::Relaxed)
+    }
+
+    pub fn invalidation_lag(&self) -> Duration {
+        Duration::from_micros(self.invalidation_lag_us.load(Ordering::Relaxed))
+    }
+
+    // Warm-state restore
+    /// Record an entry of the hot set restored after a restart
+    pub fn record_warm_restored(&self, bytes: u64) {
+        self.warm_restored.fetch_add(1, Ordering::Relaxed);
+        self.warm_restored_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn warm_restored(&self) -> u64 {
+        self.warm_restored.load(Ordering::Relaxed)
+    }
+
+    pub fn warm_restored_bytes(&self) -> u64 {
+        self.warm_restored_bytes.load(Ordering::Relaxed)
+    }
+
     // Throughput
     pub fn update_throughput(&self, read_bps: u64, write_bps: u64) {
         self.read_throughput_bps.store(read_bps, Ordering::Relaxed);
@@ -269,6 +326,7 @@ impl CacheMetrics {
 
             l3_hits: self.l3_hits(),
             l3_misses: self.l3_misses(),
+            l3_coalesced: self.l3_coalesced(),
             l3_read_latency: self.l3_read_latency(),
 
             promotions_l3_to_l2: self.promotions_l3_to_l2.load(Ordering::Relaxed),
@@ -276,6 +334,13 @@ impl CacheMetrics {
             demotions_l1_to_l2: self.demotions_l1_to_l2.load(Ordering::Relaxed),
             demotions_l2_to_l3: self.demotions_l2_to_l3.load(Ordering::Relaxed),
 
+            invalidations_sent: self.invalidations_sent(),
+            invalidations_received: self.invalidations_received(),
+            invalidation_lag: self.invalidation_lag(),
+
+            warm_restored: self.warm_restored(),
+            warm_restored_bytes: self.warm_restored_bytes(),
+
             overall_hit_ratio: self.overall_hit_ratio(),
             read_throughput_bps: self.read_throughput(),
             write_throughput_bps: self.write_throughput(),
@@ -292,10 +357,15 @@ impl CacheMetrics {
         self.l2_evictions.store(0, Ordering::Relaxed);
         self.l3_hits.store(0, Ordering::Relaxed);
         self.l3_misses.store(0, Ordering::Relaxed);
+        self.l3_coalesced.store(0, Ordering::Relaxed);
         self.promotions_l3_to_l2.store(0, Ordering::Relaxed);
         self.promotions_l2_to_l1.store(0, Ordering::Relaxed);
         self.demotions_l1_to_l2.store(0, Ordering::Relaxed);
         self.demotions_l2_to_l3.store(0, Ordering::Relaxed);
+        self.invalidations_sent.store(0, Ordering::Relaxed);
+        self.invalidations_received.store(0, Ordering::Relaxed);
+        self.warm_restored.store(0, Ordering::Relaxed);
+        self.warm_restored_bytes.store(0, Ordering::Relaxed);
     }
 }
 
@@ -325,6 +395,7 @@ pub struct MetricsSnapshot {
     // L3
     pub l3_hits: u64,
     pub l3_misses: u64,
+    pub l3_coalesced: u64,
     pub l3_read_latency: Duration,
 
     // Tier movement
@@ -333,6 +404,15 @@ pub struct MetricsSnapshot {
     pub demotions_l1_to_l2: u64,
     pub demotions_l2_to_l3: u64,
 
+    // Cross-node invalidation
+    pub invalidations_sent: u64,
+    pub invalidations_received: u64,
+    pub invalidation_lag: Duration,
+
+    // Warm-state restore
+    pub warm_restored: u64,
+    pub warm_restored_bytes: u64,
+
     // Overall
     pub overall_hit_ratio: f64,
     pub read_throughput_bps: u64,
@@ -416,6 +496,20 @@ mod tests {
         assert_eq!(snapshot.demotions_l2_to_l3, 1);
     }
 
+    #[test]
+    fn test_invalidation_tracking() {
+        let metrics = CacheMetrics::new();
+
+        metrics.record_invalidation_sent();
+        metrics.record_invalidation_received(Duration::from_millis(4));
+        metrics.record_invalidation_received(Duration::from_millis(4));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.invalidations_sent, 1);
+        assert_eq!(snapshot.invalidations_received, 2);
+        assert_eq!(snapshot.invalidation_lag, Duration::from_millis(4));
+    }
+
     #[test]
     fn test_overall_hit_ratio() {
         let metrics = CacheMetrics::new();
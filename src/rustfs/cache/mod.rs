@@ -10,7 +10,7 @@
 //! ├──────────────────────────────────────────────────────────────────────────┤
 //! │  L1 Cache (RAM)       │ L2 Cache (NVMe)     │ L3 Cache (Cold Storage)   │
 //! │  ┌────────────────┐   │ ┌────────────────┐  │ ┌────────────────────┐    │
-//! │  │ ShardedHashMap │   │ │ MappedFile     │  │ │ Async Storage      │    │
+//! │  │ ShardedHashMap │   │ │ Segment files  │  │ │ Async Storage      │    │
 //! │  │ (1024-way)     │   │ │ + Index        │  │ │ Backend            │    │
 //! │  │ Capacity: 50GB │   │ │ Capacity: 500GB│  │ │ Capacity: 10TB+    │    │
 //! │  └────────────────┘   │ └────────────────┘  │ └────────────────────┘    │
@@ -18,7 +18,7 @@
 //! │         └─────────────┴─────────┴───────────┴───────────┘               │
 //! │                              │                                           │
 //! │                    Promotion/Demotion Engine                            │
-//! │                    (LRU + Frequency-based)                              │
+//! │                    (Sampled / W-TinyLFU / S3-FIFO)                      │
 //! └──────────────────────────────────────────────────────────────────────────┘
 //! ```
 //!
@@ -35,14 +35,18 @@
 //! - Lock-free reads via 1024-way sharding
 //! - Zero-copy data paths where possible
 //! - Cache-line aligned data structures (64 bytes)
-//! - Memory-mapped L2 for kernel page cache utilization
+//! - Append-only L2 segments that survive restarts
 //!
 //! # Community Edition
 //!
 //! - Compression: LZ4 only
 //! - No async prefetch (Enterprise feature)
 
+mod chunk;
+pub mod compression;
 mod entry;
+mod eviction;
+mod invalidation;
 mod l1;
 mod l2;
 mod l3;
@@ -50,17 +54,26 @@ mod manager;
 mod metrics;
 mod policy;
 mod shard;
-pub mod compression;
+mod singleflight;
+mod warmstate;
+mod writeback;
 
 pub use compression::{CompressionAlgorithm, CompressionConfig, CompressionManager, Compressor};
 pub use entry::{CacheEntry, CacheKey, EntryMetadata};
+pub use eviction::{new_evictor, Evictor, S3FifoEvictor, SampledEvictor, TinyLfuEvictor};
+pub use invalidation::{
+    InMemoryInvalidationBus, InMemoryInvalidationTransport, Invalidation, InvalidationTransport,
+    TcpInvalidationTransport,
+};
 pub use l1::L1Cache;
 pub use l2::L2Cache;
-pub use l3::{InMemoryL3Backend, L3Backend, L3Cache};
+pub use l3::{FsL3Backend, InMemoryL3Backend, L3Backend, L3Cache};
 pub use manager::{CacheConfig, CacheManager, CacheTier};
 pub use metrics::CacheMetrics;
-pub use policy::{EvictionPolicy, PromotionPolicy};
+pub use policy::{EvictionAlgorithm, EvictionPolicy, PromotionPolicy};
 pub use shard::{Shard, ShardedMap};
+pub use warmstate::{HotEntry, HotSetManifest, WarmStateConfig};
+pub use writeback::WriteBackConfig;
 
 /// Number of shards for lock-free concurrent access
 pub const SHARD_COUNT: usize = 1024;
@@ -4,11 +4,27 @@
 
 use std::time::Duration;
 
+/// Algorithm choosing eviction victims
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EvictionAlgorithm {
+    /// Evict the worst entry of a random sample, scored with the policy weights
+    #[default]
+    Sampled,
+    /// W-TinyLFU (LRU window + segmented LRU behind a frequency filter)
+    TinyLfu,
+    /// S3-FIFO (small, main and ghost FIFO queues)
+    S3Fifo,
+}
+
 /// Eviction policy configuration
 #[derive(Debug, Clone)]
 pub struct EvictionPolicy {
     /// Name of the policy
     pub name: String,
+    /// Algorithm choosing victims
+    pub algorithm: EvictionAlgorithm,
+    /// Entries scored per victim by the sampled algorithm
+    pub sample_size: usize,
     /// High watermark (trigger eviction)
     pub high_watermark: f64,
     /// Low watermark (stop eviction)
@@ -38,6 +54,8 @@ impl EvictionPolicy {
     pub fn lru_k() -> Self {
         Self {
             name: "LRU-K".to_string(),
+            algorithm: EvictionAlgorithm::Sampled,
+            sample_size: 5,
             high_watermark: 0.90,
             low_watermark: 0.80,
             max_age: None,
@@ -54,6 +72,8 @@ impl EvictionPolicy {
     pub fn lru() -> Self {
         Self {
             name: "LRU".to_string(),
+            algorithm: EvictionAlgorithm::Sampled,
+            sample_size: 5,
             high_watermark: 0.90,
             low_watermark: 0.80,
             max_age: None,
@@ -70,6 +90,8 @@ impl EvictionPolicy {
     pub fn lfu() -> Self {
         Self {
             name: "LFU".to_string(),
+            algorithm: EvictionAlgorithm::Sampled,
+            sample_size: 5,
             high_watermark: 0.90,
             low_watermark: 0.80,
             max_age: None,
@@ -86,6 +108,8 @@ impl EvictionPolicy {
     pub fn size_aware() -> Self {
         Self {
             name: "Size-Aware".to_string(),
+            algorithm: EvictionAlgorithm::Sampled,
+            sample_size: 5,
             high_watermark: 0.90,
             low_watermark: 0.80,
             max_age: None,
@@ -102,6 +126,8 @@ impl EvictionPolicy {
     pub fn ttl(max_age: Duration) -> Self {
         Self {
             name: "TTL".to_string(),
+            algorithm: EvictionAlgorithm::Sampled,
+            sample_size: 5,
             high_watermark: 0.90,
             low_watermark: 0.80,
             max_age: Some(max_age),
@@ -112,6 +138,29 @@ impl EvictionPolicy {
         }
     }
 
+    /// W-TinyLFU eviction policy
+    ///
+    /// Admits new items only if they are accessed more often than the items
+    /// they would displace, which keeps scans from flushing hot items.
+    pub fn tiny_lfu() -> Self {
+        Self {
+            name: "W-TinyLFU".to_string(),
+            algorithm: EvictionAlgorithm::TinyLfu,
+            ..Self::lru_k()
+        }
+    }
+
+    /// S3-FIFO eviction policy
+    ///
+    /// Evicts items not accessed again soon after insertion first.
+    pub fn s3_fifo() -> Self {
+        Self {
+            name: "S3-FIFO".to_string(),
+            algorithm: EvictionAlgorithm::S3Fifo,
+            ..Self::lru_k()
+        }
+    }
+
     /// Calculate eviction score for an entry
     ///
     /// Higher score = more likely to evict
@@ -285,6 +334,23 @@ mod tests {
         assert_eq!(policy.frequency_weight, 1.0);
     }
 
+    #[test]
+    fn test_eviction_policy_algorithms() {
+        assert_eq!(
+            EvictionPolicy::default().algorithm,
+            EvictionAlgorithm::Sampled
+        );
+        assert_eq!(EvictionPolicy::lfu().algorithm, EvictionAlgorithm::Sampled);
+        assert_eq!(
+            EvictionPolicy::tiny_lfu().algorithm,
+            EvictionAlgorithm::TinyLfu
+        );
+        assert_eq!(
+            EvictionPolicy::s3_fifo().algorithm,
+            EvictionAlgorithm::S3Fifo
+        );
+    }
+
     #[test]
     fn test_eviction_score_calculation() {
         let policy = EvictionPolicy::lru_k();
@@ -558,7 +558,7 @@ mod tests {
                 thread::spawn(move || {
                     for i in 0..1000 {
                         let key = format!("key-{}-{}", t, i);
-                        map.insert(key.clone(), i as i32, 4);
+                        map.insert(key.clone(), i, 4);
                         map.get(&key);
                     }
                 })
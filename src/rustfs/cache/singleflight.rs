@@ -0,0 +1,195 @@
+//! Single-Flight Request Coalescing
+//!
+//! Deduplicates concurrent fetches of the same [`CacheKey`]: the first caller
+//! runs the fetch and every caller arriving while it is in flight waits for
+//! and shares its result.
+//!
+//! # Errors and cancellation
+//!
+//! Results are shared with the callers already waiting and then forgotten,
+//! so a failed fetch is retried by the next caller rather than cached. If
+//! the caller running a fetch is cancelled, one of the waiters takes over
+//! and fetches again.
+
+use std::collections::HashMap;
+use std::future::Future;
+
+use parking_lot::Mutex;
+use tokio::sync::watch;
+
+use super::entry::CacheKey;
+
+/// In-flight fetches keyed by cache key
+pub(crate) struct SingleFlight<T> {
+    inflight: Mutex<HashMap<CacheKey, watch::Receiver<Option<T>>>>,
+}
+
+/// Removes a fetch from the in-flight map, even if the fetch is cancelled
+struct Flight<'a, T> {
+    group: &'a SingleFlight<T>,
+    key: &'a CacheKey,
+    sender: watch::Sender<Option<T>>,
+}
+
+impl<T> Drop for Flight<'_, T> {
+    fn drop(&mut self) {
+        let mut inflight = self.group.inflight.lock();
+        if inflight
+            .get(self.key)
+            .is_some_and(|receiver| receiver.same_channel(&self.sender.subscribe()))
+        {
+            inflight.remove(self.key);
+        }
+    }
+}
+
+impl<T: Clone> SingleFlight<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            inflight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Run `fetch` for `key` unless a fetch of `key` is already in flight,
+    /// in which case wait for that one's result
+    ///
+    /// Returns the result and whether it came from another caller's fetch.
+    pub(crate) async fn run<F, Fut>(&self, key: &CacheKey, fetch: F) -> (T, bool)
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let mut fetch = Some(fetch);
+        loop {
+            let existing = {
+                let mut inflight = self.inflight.lock();
+                match inflight.get(key) {
+                    Some(receiver) => Err(receiver.clone()),
+                    None => {
+                        let (sender, receiver) = watch::channel(None);
+                        inflight.insert(key.clone(), receiver);
+                        Ok(sender)
+                    }
+                }
+            };
+
+            match existing {
+                Ok(sender) => {
+                    let flight = Flight {
+                        group: self,
+                        key,
+                        sender,
+                    };
+                    // Only a caller that never ran the fetch can get here twice
+                    let fetch = fetch.take().expect("fetch runs once");
+                    let value = fetch().await;
+                    flight.sender.send_replace(Some(value.clone()));
+                    return (value, false);
+                }
+                Err(mut receiver) => {
+                    if let Ok(value) = receiver.wait_for(Option::is_some).await {
+                        if let Some(value) = value.as_ref() {
+                            return (value.clone(), true);
+                        }
+                    }
+                    // The fetching caller was cancelled; try again
+                }
+            }
+        }
+    }
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn key(i: usize) -> CacheKey {
+        CacheKey::new("bucket", format!("object-{}", i))
+    }
+
+    #[tokio::test]
+    async fn test_single_flight_shares_one_fetch() {
+        let group = Arc::new(SingleFlight::new());
+        let fetches = Arc::new(AtomicU64::new(0));
+
+        let tasks: Vec<_> = (0..16)
+            .map(|_| {
+                let group = group.clone();
+                let fetches = fetches.clone();
+                tokio::spawn(async move {
+                    group
+                        .run(&key(0), || async {
+                            fetches.fetch_add(1, Ordering::SeqCst);
+                            tokio::time::sleep(Duration::from_millis(50)).await;
+                            42u32
+                        })
+                        .await
+                })
+            })
+            .collect();
+        let mut coalesced = 0;
+        for task in tasks {
+            let (value, waited) = task.await.unwrap();
+            assert_eq!(value, 42);
+            coalesced += waited as usize;
+        }
+
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+        assert_eq!(coalesced, 15);
+        assert!(group.inflight.lock().is_empty());
+
+        // Finished fetches are not reused
+        assert_eq!(group.run(&key(0), || async { 7 }).await, (7, false));
+        assert_eq!(group.run(&key(1), || async { 8 }).await, (8, false));
+    }
+
+    #[tokio::test]
+    async fn test_single_flight_does_not_cache_errors() {
+        let group: SingleFlight<Result<u32, String>> = SingleFlight::new();
+        let (failed, _) = group
+            .run(&key(0), || async { Err("backend down".to_string()) })
+            .await;
+        assert!(failed.is_err());
+        assert_eq!(group.run(&key(0), || async { Ok(1) }).await.0, Ok(1));
+    }
+
+    #[tokio::test]
+    async fn test_single_flight_survives_cancelled_leader() {
+        let group = Arc::new(SingleFlight::new());
+
+        let leader = {
+            let group = group.clone();
+            tokio::spawn(async move {
+                group
+                    .run(&key(0), || async {
+                        tokio::time::sleep(Duration::from_secs(60)).await;
+                        0u32
+                    })
+                    .await
+                    .0
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let waiter = {
+            let group = group.clone();
+            tokio::spawn(async move { group.run(&key(0), || async { 1u32 }).await.0 })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        leader.abort();
+
+        let value = tokio::time::timeout(Duration::from_secs(5), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(value, 1);
+        assert!(group.inflight.lock().is_empty());
+    }
+}
@@ -0,0 +1,298 @@
+//! Warm-State Snapshots
+//!
+//! [`CacheManager`](super::CacheManager) periodically records the hot set,
+//! the keys cached in L1 and L2 with their access counts and last access
+//! times, in a manifest file. After a restart it reads the entries back
+//! into the tiers they were in, hottest first and at a bounded byte rate,
+//! so hit ratios recover without waiting for clients to touch every key.
+//!
+//! Unlike prefetching this predicts nothing: only keys that were cached
+//! before the restart are restored.
+//!
+//! # Format
+//!
+//! The manifest holds a header line and one line per entry, hottest first,
+//! each a checksummed JSON record like the L2 index log. It is replaced
+//! atomically; corrupt lines are skipped.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::entry::{CacheKey, EntryMetadata};
+use super::manager::CacheTier;
+
+/// Manifest format version
+const MANIFEST_VERSION: u32 = 1;
+
+/// Warm-state configuration
+#[derive(Debug, Clone)]
+pub struct WarmStateConfig {
+    /// Manifest file; no warm state is kept when `None`
+    pub manifest_path: Option<PathBuf>,
+    /// How often the hot set is recorded
+    pub snapshot_interval: Duration,
+    /// Most entries recorded, hottest first
+    pub max_entries: usize,
+    /// Bytes per second read while restoring (0 = unthrottled)
+    pub restore_rate: u64,
+}
+
+impl Default for WarmStateConfig {
+    fn default() -> Self {
+        Self {
+            manifest_path: None,
+            snapshot_interval: Duration::from_secs(300),
+            max_entries: 100_000,
+            restore_rate: 64 * 1024 * 1024, // 64MB/s
+        }
+    }
+}
+
+/// A cached key and how hot it was
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HotEntry {
+    #[serde(rename = "b")]
+    pub bucket: String,
+    #[serde(rename = "k")]
+    pub key: String,
+    /// Highest tier the entry was cached in: 1 or 2
+    #[serde(rename = "l")]
+    pub level: u8,
+    /// Uncompressed size
+    #[serde(rename = "s")]
+    pub size: u64,
+    #[serde(rename = "a")]
+    pub access_count: u32,
+    /// Last access (epoch seconds)
+    #[serde(rename = "la")]
+    pub last_access: u64,
+}
+
+impl HotEntry {
+    pub(crate) fn new(key: &CacheKey, tier: CacheTier, metadata: &EntryMetadata) -> Self {
+        Self {
+            bucket: key.bucket().to_string(),
+            key: key.key().to_string(),
+            level: match tier {
+                CacheTier::L1 => 1,
+                _ => 2,
+            },
+            size: metadata.size(),
+            access_count: metadata.access_count(),
+            last_access: metadata.last_access(),
+        }
+    }
+
+    /// Get the key of the entry
+    pub fn cache_key(&self) -> CacheKey {
+        CacheKey::new(self.bucket.as_str(), self.key.as_str())
+    }
+
+    /// Get the tier to restore the entry to
+    pub fn tier(&self) -> CacheTier {
+        match self.level {
+            1 => CacheTier::L1,
+            _ => CacheTier::L2,
+        }
+    }
+}
+
+/// Manifest header
+#[derive(Debug, Serialize, Deserialize)]
+struct Header {
+    #[serde(rename = "v")]
+    version: u32,
+    /// When the hot set was recorded (epoch seconds)
+    #[serde(rename = "t")]
+    taken_at: u64,
+}
+
+/// Hot set of a cache, hottest entry first
+#[derive(Debug, Clone, Default)]
+pub struct HotSetManifest {
+    /// When the hot set was recorded (epoch seconds)
+    pub taken_at: u64,
+    entries: Vec<HotEntry>,
+}
+
+impl HotSetManifest {
+    /// Build a manifest of the `max_entries` hottest of `entries`
+    ///
+    /// Entries are ordered by access count, then by last access. Only the
+    /// first entry of a key is kept, so list the L1 entries first.
+    pub fn new(entries: Vec<HotEntry>, max_entries: usize) -> Self {
+        let mut seen = std::collections::HashSet::new();
+        let mut entries: Vec<_> = entries
+            .into_iter()
+            .filter(|entry| seen.insert((entry.bucket.clone(), entry.key.clone())))
+            .collect();
+        entries.sort_by(|a, b| {
+            b.access_count
+                .cmp(&a.access_count)
+                .then(b.last_access.cmp(&a.last_access))
+        });
+        entries.truncate(max_entries);
+
+        Self {
+            taken_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            entries,
+        }
+    }
+
+    /// Get the entries, hottest first
+    pub fn entries(&self) -> &[HotEntry] {
+        &self.entries
+    }
+
+    /// Get the number of entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if the manifest is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Write the manifest to `path`, replacing any previous one
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let tmp = path.with_extension("tmp");
+        {
+            let mut out = std::io::BufWriter::new(File::create(&tmp)?);
+            let header = Header {
+                version: MANIFEST_VERSION,
+                taken_at: self.taken_at,
+            };
+            out.write_all(encode_line(&header)?.as_bytes())?;
+            for entry in &self.entries {
+                out.write_all(encode_line(entry)?.as_bytes())?;
+            }
+            out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        }
+
+        std::fs::rename(&tmp, path)?;
+        if let Some(dir) = path.parent() {
+            File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    }
+
+    /// Read the manifest at `path`
+    ///
+    /// Returns None if there is none or it has an unknown version.
+    pub fn load(path: &Path) -> std::io::Result<Option<Self>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut lines = BufReader::new(file).lines();
+
+        let Some(header) = lines.next().transpose()? else {
+            return Ok(None);
+        };
+        let Some(header) = decode_line::<Header>(&header) else {
+            return Ok(None);
+        };
+        if header.version != MANIFEST_VERSION {
+            return Ok(None);
+        }
+
+        let mut entries = Vec::new();
+        for line in lines {
+            if let Some(entry) = decode_line(&line?) {
+                entries.push(entry);
+            }
+        }
+        Ok(Some(Self {
+            taken_at: header.taken_at,
+            entries,
+        }))
+    }
+}
+
+/// Encode a record as a checksummed line
+fn encode_line<T: Serialize>(record: &T) -> std::io::Result<String> {
+    let json = serde_json::to_string(record)?;
+    Ok(format!(
+        "{:08x} {}\n",
+        crc32fast::hash(json.as_bytes()),
+        json
+    ))
+}
+
+/// Decode a line, returning None if it is corrupt
+fn decode_line<T: for<'de> Deserialize<'de>>(line: &str) -> Option<T> {
+    let (crc, json) = line.split_once(' ')?;
+    let crc = u32::from_str_radix(crc, 16).ok()?;
+    if crc32fast::hash(json.as_bytes()) != crc {
+        return None;
+    }
+    serde_json::from_str(json).ok()
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hot(key: &str, level: u8, access_count: u32, last_access: u64) -> HotEntry {
+        HotEntry {
+            bucket: "bucket".to_string(),
+            key: key.to_string(),
+            level,
+            size: 100,
+            access_count,
+            last_access,
+        }
+    }
+
+    #[test]
+    fn test_manifest_orders_by_hotness() {
+        let manifest = HotSetManifest::new(
+            vec![
+                hot("warm", 2, 5, 10),
+                hot("hot", 1, 9, 10),
+                hot("recent", 1, 5, 20),
+                hot("hot", 2, 9, 10),
+                hot("cold", 2, 1, 30),
+            ],
+            3,
+        );
+
+        let keys: Vec<_> = manifest.entries().iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, vec!["hot", "recent", "warm"]);
+        assert_eq!(manifest.entries()[0].tier(), CacheTier::L1);
+    }
+
+    #[test]
+    fn test_manifest_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hotset");
+        assert!(HotSetManifest::load(&path).unwrap().is_none());
+
+        let manifest = HotSetManifest::new(vec![hot("a", 1, 3, 7), hot("b", 2, 2, 8)], 10);
+        manifest.save(&path).unwrap();
+
+        // A torn line is skipped
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b"0000 {\"b\":").unwrap();
+
+        let loaded = HotSetManifest::load(&path).unwrap().unwrap();
+        assert_eq!(loaded.taken_at, manifest.taken_at);
+        assert_eq!(loaded.entries(), manifest.entries());
+    }
+}
@@ -0,0 +1,306 @@
+//! Write-Back Tracking
+//!
+//! Bookkeeping for [`CacheManager`](super::CacheManager) when
+//! `write_through` is off: which keys hold data that has not reached L3, at
+//! which generation and since when.
+//!
+//! # Ordering
+//!
+//! Every put assigns the entry a new generation. A key's L3 writes happen
+//! under its key lock and only for the generation the tracker holds, so L3
+//! never goes back to an older version of a key.
+//!
+//! # Evicted entries
+//!
+//! A dirty entry evicted from every tier whose L3 write fails is kept here
+//! until the flusher writes it, so eviction never drops unflushed data.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use tokio::sync::{MutexGuard, Notify};
+
+use super::entry::{CacheEntry, CacheKey};
+
+/// Number of key lock stripes
+const LOCK_STRIPES: usize = 256;
+
+/// Write-back configuration
+#[derive(Debug, Clone)]
+pub struct WriteBackConfig {
+    /// Flush an entry once it has been dirty this long
+    pub max_dirty_age: Duration,
+    /// Flush the oldest entries while more bytes than this are dirty
+    pub max_dirty_bytes: u64,
+    /// How often the flusher looks for due entries
+    pub flush_interval: Duration,
+}
+
+impl Default for WriteBackConfig {
+    fn default() -> Self {
+        Self {
+            max_dirty_age: Duration::from_secs(30),
+            max_dirty_bytes: 64 * 1024 * 1024, // 64MB
+            flush_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// A key whose latest data is not in L3 yet
+struct DirtyEntry {
+    generation: u64,
+    size: u64,
+    /// When the key became dirty; later puts keep it
+    since: Instant,
+    /// Data of an entry no tier holds any more
+    evicted: Option<CacheEntry>,
+}
+
+/// Dirty keys, generations and key locks for write-back mode
+pub(crate) struct WriteBackTracker {
+    dirty: Mutex<HashMap<CacheKey, DirtyEntry>>,
+    dirty_bytes: AtomicU64,
+    next_generation: AtomicU64,
+    locks: Vec<tokio::sync::Mutex<()>>,
+    /// Wakes the flusher early
+    wake: Notify,
+    shutdown: AtomicBool,
+}
+
+impl WriteBackTracker {
+    /// Create a tracker handing out generations above `last_generation`
+    pub(crate) fn new(last_generation: u64) -> Self {
+        Self {
+            dirty: Mutex::new(HashMap::new()),
+            dirty_bytes: AtomicU64::new(0),
+            next_generation: AtomicU64::new(last_generation + 1),
+            locks: (0..LOCK_STRIPES)
+                .map(|_| tokio::sync::Mutex::new(()))
+                .collect(),
+            wake: Notify::new(),
+            shutdown: AtomicBool::new(false),
+        }
+    }
+
+    /// Allocate the generation of a new write
+    pub(crate) fn next_generation(&self) -> u64 {
+        self.next_generation.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Hand out only generations above `generation` from now on
+    ///
+    /// Keeps generations ordered after writes made on other nodes.
+    pub(crate) fn observe_generation(&self, generation: u64) {
+        self.next_generation
+            .fetch_max(generation + 1, Ordering::Relaxed);
+    }
+
+    /// Lock `key` against concurrent puts and flushes
+    pub(crate) async fn lock(&self, key: &CacheKey) -> MutexGuard<'_, ()> {
+        self.locks[key.shard_index(LOCK_STRIPES)].lock().await
+    }
+
+    /// Record that `key` holds `size` unflushed bytes at `generation`
+    pub(crate) fn mark_dirty(&self, key: &CacheKey, generation: u64, size: u64) {
+        let mut dirty = self.dirty.lock();
+        let since = match dirty.get(key) {
+            Some(old) => {
+                self.dirty_bytes.fetch_sub(old.size, Ordering::Relaxed);
+                old.since
+            }
+            None => Instant::now(),
+        };
+        dirty.insert(
+            key.clone(),
+            DirtyEntry {
+                generation,
+                size,
+                since,
+                evicted: None,
+            },
+        );
+        self.dirty_bytes.fetch_add(size, Ordering::Relaxed);
+    }
+
+    /// Get the dirty generation of `key` and its data if no tier holds it
+    pub(crate) fn pending(&self, key: &CacheKey) -> Option<(u64, Option<CacheEntry>)> {
+        let dirty = self.dirty.lock();
+        let entry = dirty.get(key)?;
+        Some((entry.generation, entry.evicted.clone()))
+    }
+
+    /// Check if `generation` is the unflushed generation of `key`
+    pub(crate) fn is_pending(&self, key: &CacheKey, generation: u64) -> bool {
+        self.dirty
+            .lock()
+            .get(key)
+            .is_some_and(|entry| entry.generation == generation)
+    }
+
+    /// Check if `key` has unflushed data
+    pub(crate) fn contains(&self, key: &CacheKey) -> bool {
+        self.dirty.lock().contains_key(key)
+    }
+
+    /// Get the data of an evicted entry waiting to be flushed
+    pub(crate) fn evicted(&self, key: &CacheKey) -> Option<CacheEntry> {
+        self.dirty.lock().get(key)?.evicted.clone()
+    }
+
+    /// Keep an evicted entry's data until it is flushed
+    pub(crate) fn keep_evicted(&self, key: &CacheKey, entry: CacheEntry) {
+        let mut dirty = self.dirty.lock();
+        if let Some(pending) = dirty.get_mut(key) {
+            if pending.generation == entry.metadata.generation() {
+                pending.evicted = Some(entry);
+            }
+        }
+    }
+
+    /// Record that `generation` of `key` reached L3
+    pub(crate) fn mark_clean(&self, key: &CacheKey, generation: u64) -> bool {
+        let mut dirty = self.dirty.lock();
+        if dirty.get(key).is_some_and(|e| e.generation == generation) {
+            if let Some(entry) = dirty.remove(key) {
+                self.dirty_bytes.fetch_sub(entry.size, Ordering::Relaxed);
+            }
+            return true;
+        }
+        false
+    }
+
+    /// Forget `key`, e.g. because it was deleted
+    pub(crate) fn remove(&self, key: &CacheKey) {
+        if let Some(entry) = self.dirty.lock().remove(key) {
+            self.dirty_bytes.fetch_sub(entry.size, Ordering::Relaxed);
+        }
+    }
+
+    /// Dirty keys, oldest first
+    pub(crate) fn keys(&self) -> Vec<CacheKey> {
+        self.due(Duration::ZERO, 0)
+    }
+
+    /// Keys to flush now, oldest first: those dirty for `max_age`, then
+    /// the oldest others until no more than `max_bytes` stay dirty
+    pub(crate) fn due(&self, max_age: Duration, max_bytes: u64) -> Vec<CacheKey> {
+        let dirty = self.dirty.lock();
+        let mut entries: Vec<_> = dirty.iter().collect();
+        entries.sort_by_key(|(_, entry)| (entry.since, entry.generation));
+
+        let mut remaining = self.dirty_bytes.load(Ordering::Relaxed);
+        let mut due = Vec::new();
+        for (key, entry) in entries {
+            if entry.since.elapsed() < max_age && remaining <= max_bytes {
+                break;
+            }
+            remaining = remaining.saturating_sub(entry.size);
+            due.push(key.clone());
+        }
+        due
+    }
+
+    /// Bytes not yet written to L3
+    pub(crate) fn dirty_bytes(&self) -> u64 {
+        self.dirty_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Number of keys not yet written to L3
+    pub(crate) fn len(&self) -> usize {
+        self.dirty.lock().len()
+    }
+
+    /// Wait until the flusher is woken early
+    pub(crate) async fn woken(&self) {
+        self.wake.notified().await
+    }
+
+    /// Wake the flusher
+    pub(crate) fn wake(&self) {
+        self.wake.notify_one();
+    }
+
+    /// Ask the flusher to stop
+    pub(crate) fn shutdown(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        self.wake.notify_one();
+    }
+
+    /// Check if the flusher should stop
+    pub(crate) fn is_shutdown(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(i: usize) -> CacheKey {
+        CacheKey::new("bucket", format!("object-{}", i))
+    }
+
+    #[test]
+    fn test_tracker_follows_latest_generation() {
+        let tracker = WriteBackTracker::new(0);
+        let first = tracker.next_generation();
+        let second = tracker.next_generation();
+        assert!(second > first);
+        tracker.observe_generation(100);
+        assert_eq!(tracker.next_generation(), 101);
+        tracker.observe_generation(50);
+        assert_eq!(tracker.next_generation(), 102);
+
+        tracker.mark_dirty(&key(0), first, 100);
+        tracker.mark_dirty(&key(0), second, 300);
+        assert_eq!(tracker.dirty_bytes(), 300);
+        assert_eq!(tracker.len(), 1);
+
+        // Flushing the superseded generation leaves the key dirty
+        assert!(!tracker.mark_clean(&key(0), first));
+        assert!(tracker.is_pending(&key(0), second));
+        assert!(tracker.mark_clean(&key(0), second));
+        assert_eq!(tracker.dirty_bytes(), 0);
+        assert!(!tracker.contains(&key(0)));
+    }
+
+    #[test]
+    fn test_tracker_keeps_evicted_data() {
+        let tracker = WriteBackTracker::new(0);
+        let entry = CacheEntry::new(bytes::Bytes::from_static(b"data"));
+        entry.metadata.set_generation(7);
+
+        // Data of a superseded generation is not kept
+        tracker.mark_dirty(&key(0), 8, 4);
+        tracker.keep_evicted(&key(0), entry.clone());
+        assert!(tracker.evicted(&key(0)).is_none());
+
+        tracker.mark_dirty(&key(0), 7, 4);
+        tracker.keep_evicted(&key(0), entry);
+        let (generation, evicted) = tracker.pending(&key(0)).unwrap();
+        assert_eq!(generation, 7);
+        assert_eq!(evicted.unwrap().data().as_ref(), b"data");
+    }
+
+    #[test]
+    fn test_tracker_due_by_age_and_bytes() {
+        let tracker = WriteBackTracker::new(0);
+        for i in 0..4 {
+            tracker.mark_dirty(&key(i), tracker.next_generation(), 100);
+        }
+
+        assert!(tracker.due(Duration::from_secs(60), 1000).is_empty());
+        assert_eq!(
+            tracker.due(Duration::from_secs(60), 250),
+            vec![key(0), key(1)]
+        );
+        assert_eq!(tracker.due(Duration::ZERO, 1000).len(), 4);
+        assert_eq!(tracker.keys()[0], key(0));
+    }
+}
@@ -28,6 +28,7 @@
 //! - Cache: L1 (RAM), L2 (NVMe), L3 (Cold Storage)
 //! - Compression: LZ4
 //! - Monitoring: Prometheus metrics, health checks
+//! - Object API: S3-compatible HTTP server with SigV4 authentication
 //!
 //! For Enterprise features (multi-tenancy, replication, Zstd/Snappy, prefetch),
 //! see CoucheStor Enterprise Edition.
@@ -39,10 +40,12 @@
 
 pub mod cache;
 pub mod monitoring;
+pub mod s3;
 
 // Re-export primary types
 pub use cache::{CacheConfig, CacheManager, CacheTier};
 pub use monitoring::{MetricsCollector, ObservabilityConfig};
+pub use s3::{S3Config, S3Server};
 
 /// RustFS version
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
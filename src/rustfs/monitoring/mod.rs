@@ -42,6 +42,6 @@ pub use health::{HealthCheck, HealthCheckResult, HealthResponse, HealthStatus};
 mod tests {
     #[test]
     fn test_module_exists() {
-        assert!(true);
+        let _ = std::mem::size_of::<super::HealthStatus>();
     }
 }
@@ -0,0 +1,216 @@
+//! S3-Compatible Object API
+//!
+//! A minimal S3 HTTP frontend over the [`CacheManager`](super::cache::CacheManager),
+//! so standard S3 SDKs and tools can store objects in the tiered cache.
+//!
+//! # Supported Operations
+//!
+//! - Buckets: `CreateBucket`, `HeadBucket`, `DeleteBucket`, `ListBuckets`,
+//!   `GetBucketLocation`, `ListObjects` / `ListObjectsV2`
+//! - Objects: `GetObject` (with single byte ranges), `HeadObject`,
+//!   `PutObject`, `DeleteObject`
+//! - Multipart: `CreateMultipartUpload`, `UploadPart`,
+//!   `CompleteMultipartUpload`, `AbortMultipartUpload`
+//!
+//! Requests are authenticated with AWS Signature Version 4 against static
+//! credentials, in the `Authorization` header or a presigned URL, including
+//! `aws-chunked` streaming payloads with signed chunks or checksum trailers.
+//! Path-style addressing is always accepted; virtual-hosted style needs
+//! `domain` to be set.
+//!
+//! Without credentials, authentication is disabled, so the server refuses
+//! to listen on anything but a loopback address unless `allow_anonymous`
+//! is set.
+//!
+//! # Layout
+//!
+//! Everything lives in the cache under reserved bucket names, which cannot
+//! clash with S3 bucket names:
+//!
+//! ```text
+//! .buckets/<bucket>                       bucket record
+//! .meta.<bucket>/<key>                    object metadata, pointing to its data
+//! .data.<bucket>/<data id>                object bodies and uploaded parts
+//! .uploads.<bucket>/<upload id>           multipart upload records
+//! .parts.<bucket>/<upload id>/<part>      uploaded part records
+//! ```
+//!
+//! An object's metadata record is written after its body, so a reader
+//! never sees metadata for a body that is not stored yet. Listings come
+//! from the L3 tier, so the cache must run in write-through mode.
+
+mod auth;
+mod server;
+mod store;
+mod xml;
+
+pub use auth::S3Credentials;
+pub use server::S3Server;
+pub use store::{ObjectInfo, ObjectStore};
+
+use std::time::Duration;
+
+use hyper::StatusCode;
+
+// =============================================================================
+// Configuration
+// =============================================================================
+
+/// Configuration for the S3 server
+#[derive(Debug, Clone)]
+pub struct S3Config {
+    /// Address to listen on
+    pub listen_addr: String,
+
+    /// Region reported by `GetBucketLocation`
+    pub region: String,
+
+    /// Accepted access keys; empty disables authentication
+    pub credentials: Vec<S3Credentials>,
+
+    /// Serve without credentials on a non-loopback address
+    pub allow_anonymous: bool,
+
+    /// Base domain for virtual-hosted style requests (`<bucket>.<domain>`)
+    pub domain: Option<String>,
+
+    /// Largest request body accepted (single PUT or part)
+    pub max_object_bytes: u64,
+
+    /// Smallest part allowed in a multipart upload, except the last one
+    pub min_part_bytes: u64,
+
+    /// Largest difference between the request date and the server clock
+    pub max_clock_skew: Duration,
+}
+
+impl Default for S3Config {
+    fn default() -> Self {
+        Self {
+            listen_addr: "127.0.0.1:9000".to_string(),
+            region: "us-east-1".to_string(),
+            credentials: vec![],
+            allow_anonymous: false,
+            domain: None,
+            max_object_bytes: 5 * 1024 * 1024 * 1024,
+            min_part_bytes: 5 * 1024 * 1024,
+            max_clock_skew: Duration::from_secs(15 * 60),
+        }
+    }
+}
+
+// =============================================================================
+// Errors
+// =============================================================================
+
+/// S3 error codes returned to clients
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum S3ErrorCode {
+    AccessDenied,
+    AuthorizationHeaderMalformed,
+    BadDigest,
+    BucketNotEmpty,
+    EntityTooLarge,
+    EntityTooSmall,
+    IncompleteBody,
+    InternalError,
+    InvalidAccessKeyId,
+    InvalidArgument,
+    InvalidBucketName,
+    InvalidPart,
+    InvalidPartOrder,
+    InvalidRange,
+    InvalidRequest,
+    MalformedXML,
+    MethodNotAllowed,
+    NoSuchBucket,
+    NoSuchKey,
+    NoSuchUpload,
+    NotImplemented,
+    RequestTimeTooSkewed,
+    SignatureDoesNotMatch,
+    XAmzContentSHA256Mismatch,
+}
+
+impl S3ErrorCode {
+    /// HTTP status the error is returned with
+    pub fn status(self) -> StatusCode {
+        match self {
+            Self::AccessDenied
+            | Self::InvalidAccessKeyId
+            | Self::RequestTimeTooSkewed
+            | Self::SignatureDoesNotMatch => StatusCode::FORBIDDEN,
+            Self::BucketNotEmpty => StatusCode::CONFLICT,
+            Self::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::InvalidRange => StatusCode::RANGE_NOT_SATISFIABLE,
+            Self::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            Self::NoSuchBucket | Self::NoSuchKey | Self::NoSuchUpload => StatusCode::NOT_FOUND,
+            Self::NotImplemented => StatusCode::NOT_IMPLEMENTED,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl std::fmt::Display for S3ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+/// An error response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct S3Error {
+    /// Error code
+    pub code: S3ErrorCode,
+
+    /// Human-readable message
+    pub message: String,
+}
+
+impl S3Error {
+    /// Create an error with a message
+    pub fn new(code: S3ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for S3Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl From<crate::error::Error> for S3Error {
+    fn from(e: crate::error::Error) -> Self {
+        Self::new(S3ErrorCode::InternalError, e.to_string())
+    }
+}
+
+/// Result of an S3 operation
+pub type S3Result<T> = std::result::Result<T, S3Error>;
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_codes_map_to_statuses() {
+        assert_eq!(S3ErrorCode::NoSuchKey.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            S3ErrorCode::SignatureDoesNotMatch.status(),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(S3ErrorCode::MalformedXML.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            S3ErrorCode::XAmzContentSHA256Mismatch.to_string(),
+            "XAmzContentSHA256Mismatch"
+        );
+    }
+}
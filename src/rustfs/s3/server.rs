@@ -0,0 +1,1039 @@
+//! S3 HTTP Server
+//!
+//! Routes S3 REST requests to the [`ObjectStore`] over HTTP/1.1.
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use base64::Engine;
+use bytes::Bytes;
+use chrono::Utc;
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+use hyper::body::Body;
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::http::request::Parts;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{header, Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpListener;
+use tracing::{debug, info, warn};
+
+use super::auth::{authenticate, query_params, uri_decode, verify_payload, Checksum};
+use super::store::{ListParams, ObjectInfo, ObjectStore};
+use super::xml::{self, ListResult, ListVersion};
+use super::{S3Config, S3Error, S3ErrorCode, S3Result};
+use crate::error::{Error, Result};
+use crate::rustfs::cache::CacheManager;
+
+/// Largest page of a listing
+const MAX_LIST_KEYS: usize = 1000;
+
+/// Owner reported in bucket listings
+const OWNER: &str = "couchestor";
+
+/// Query parameters that do not select an operation
+fn is_passive_param(name: &str) -> bool {
+    name == "x-id" || name.starts_with("X-Amz-") || name.starts_with("x-amz-")
+}
+
+/// Query parameters of a request
+struct Query(Vec<(String, String)>);
+
+impl Query {
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn has(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Reject parameters naming operations this server does not implement
+    fn only(&self, allowed: &[&str]) -> S3Result<()> {
+        match self
+            .0
+            .iter()
+            .find(|(k, _)| !is_passive_param(k) && !allowed.contains(&k.as_str()))
+        {
+            Some((name, _)) => Err(S3Error::new(
+                S3ErrorCode::NotImplemented,
+                format!("'{}' is not supported", name),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Single byte range of a `Range` header, resolved against the object size
+///
+/// Returns `Ok(None)` for headers that do not name one usable range, which
+/// are ignored as in S3.
+fn parse_range(value: &str, size: u64) -> S3Result<Option<(u64, u64)>> {
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return Ok(None);
+    };
+    if spec.contains(',') {
+        return Ok(None);
+    }
+    let Some((start, end)) = spec.split_once('-') else {
+        return Ok(None);
+    };
+    let (start, end) = (start.trim(), end.trim());
+
+    let unsatisfiable = || {
+        S3Error::new(
+            S3ErrorCode::InvalidRange,
+            "the requested range is not satisfiable",
+        )
+    };
+    let range = match (start.parse::<u64>().ok(), end.parse::<u64>().ok()) {
+        (Some(start), _) if start >= size => return Err(unsatisfiable()),
+        (Some(start), Some(end)) if end >= start => (start, end.min(size - 1)),
+        (Some(start), None) if end.is_empty() => (start, size - 1),
+        (None, Some(0)) if start.is_empty() => return Err(unsatisfiable()),
+        (None, Some(suffix)) if start.is_empty() => {
+            if size == 0 {
+                return Err(unsatisfiable());
+            }
+            (size.saturating_sub(suffix), size - 1)
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(range))
+}
+
+fn header_str(parts: &Parts, name: impl header::AsHeaderName) -> Option<&str> {
+    parts.headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+/// `x-amz-meta-*` request headers, without the prefix
+fn user_metadata(parts: &Parts) -> BTreeMap<String, String> {
+    parts
+        .headers
+        .iter()
+        .filter_map(|(name, value)| {
+            let name = name.as_str().strip_prefix("x-amz-meta-")?;
+            Some((name.to_string(), value.to_str().ok()?.to_string()))
+        })
+        .collect()
+}
+
+fn content_type(parts: &Parts) -> Option<String> {
+    header_str(parts, header::CONTENT_TYPE).map(str::to_string)
+}
+
+fn ok() -> hyper::http::response::Builder {
+    Response::builder().status(StatusCode::OK)
+}
+
+fn xml_response(body: String) -> Response<Full<Bytes>> {
+    ok().header(header::CONTENT_TYPE, "application/xml")
+        .body(Full::new(Bytes::from(body)))
+        .unwrap()
+}
+
+fn no_content() -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .body(Full::default())
+        .unwrap()
+}
+
+/// Response headers describing an object
+fn object_headers(
+    mut builder: hyper::http::response::Builder,
+    info: &ObjectInfo,
+    with_checksum: bool,
+) -> hyper::http::response::Builder {
+    builder = builder
+        .header(header::ETAG, &info.etag)
+        .header(
+            header::LAST_MODIFIED,
+            info.last_modified
+                .format("%a, %d %b %Y %H:%M:%S GMT")
+                .to_string(),
+        )
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(
+            header::CONTENT_TYPE,
+            info.content_type
+                .as_deref()
+                .unwrap_or("application/octet-stream"),
+        );
+    for (name, value) in &info.metadata {
+        builder = builder.header(format!("x-amz-meta-{}", name), value);
+    }
+    if let (true, Some((name, value))) = (with_checksum, &info.checksum) {
+        builder = builder.header(name, value);
+    }
+    builder
+}
+
+// =============================================================================
+// S3 Server
+// =============================================================================
+
+/// S3-compatible HTTP server over a [`CacheManager`]
+pub struct S3Server {
+    config: S3Config,
+    store: ObjectStore,
+}
+
+impl S3Server {
+    /// Create a server storing objects in `cache`
+    pub fn new(config: S3Config, cache: Arc<CacheManager>) -> Result<Arc<Self>> {
+        let store = ObjectStore::new(cache, config.min_part_bytes)?;
+        Ok(Arc::new(Self { config, store }))
+    }
+
+    /// The object store behind the server
+    pub fn store(&self) -> &ObjectStore {
+        &self.store
+    }
+
+    /// Listen on the configured address and serve clients until an error
+    pub async fn run(self: Arc<Self>) -> Result<()> {
+        let listener = TcpListener::bind(&self.config.listen_addr).await?;
+        self.check_exposure(listener.local_addr()?)?;
+        info!(
+            "S3 server listening on {} ({})",
+            self.config.listen_addr,
+            if self.config.credentials.is_empty() {
+                "authentication disabled"
+            } else {
+                "SigV4 authentication"
+            }
+        );
+        self.serve(listener).await
+    }
+
+    /// Refuse to serve unauthenticated requests beyond the local host
+    fn check_exposure(&self, addr: SocketAddr) -> Result<()> {
+        if self.config.credentials.is_empty()
+            && !self.config.allow_anonymous
+            && !addr.ip().is_loopback()
+        {
+            return Err(Error::Config(format!(
+                "S3 server on {} has no credentials; configure credentials or allow anonymous access",
+                addr
+            )));
+        }
+        Ok(())
+    }
+
+    /// Accept and serve clients from `listener`
+    pub async fn serve(self: Arc<Self>, listener: TcpListener) -> Result<()> {
+        loop {
+            let (stream, peer) = listener.accept().await?;
+            let server = self.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |request| {
+                    let server = server.clone();
+                    async move { Ok::<_, Infallible>(server.handle(request).await) }
+                });
+                if let Err(e) = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    debug!("S3 client {} dropped: {}", peer, e);
+                }
+            });
+        }
+    }
+
+    /// Serve one request
+    pub async fn handle<B>(&self, request: Request<B>) -> Response<Full<Bytes>>
+    where
+        B: Body<Data = Bytes>,
+        B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let request_id = uuid::Uuid::new_v4().simple().to_string();
+        let (parts, body) = request.into_parts();
+
+        let mut response = match self.dispatch(&parts, body).await {
+            Ok(response) => response,
+            Err(error) => {
+                if error.code == S3ErrorCode::InternalError {
+                    warn!("S3 {} {} failed: {}", parts.method, parts.uri, error);
+                }
+                let builder = Response::builder().status(error.code.status());
+                if parts.method == Method::HEAD {
+                    builder.body(Full::default()).unwrap()
+                } else {
+                    let body = xml::error(&error, parts.uri.path(), &request_id);
+                    builder
+                        .header(header::CONTENT_TYPE, "application/xml")
+                        .body(Full::new(Bytes::from(body)))
+                        .unwrap()
+                }
+            }
+        };
+        debug!("S3 {} {} -> {}", parts.method, parts.uri, response.status());
+        response
+            .headers_mut()
+            .insert("x-amz-request-id", request_id.parse().unwrap());
+        response
+    }
+
+    /// Bucket and key addressed by a request, path-style or virtual-hosted
+    fn address(&self, parts: &Parts) -> (Option<String>, Option<String>) {
+        let path = uri_decode(parts.uri.path());
+        let path = path.strip_prefix('/').unwrap_or(&path);
+
+        let host = header_str(parts, header::HOST)
+            .or_else(|| parts.uri.host())
+            .map(|h| h.rsplit_once(':').map_or(h, |(host, _)| host));
+        let virtual_bucket = match (host, &self.config.domain) {
+            (Some(host), Some(domain)) => host
+                .strip_suffix(domain.as_str())
+                .and_then(|h| h.strip_suffix('.'))
+                .filter(|b| !b.is_empty()),
+            _ => None,
+        };
+
+        let non_empty = |s: &str| (!s.is_empty()).then(|| s.to_string());
+        match virtual_bucket {
+            Some(bucket) => (Some(bucket.to_string()), non_empty(path)),
+            None => match path.split_once('/') {
+                Some((bucket, key)) => (non_empty(bucket), non_empty(key)),
+                None => (non_empty(path), None),
+            },
+        }
+    }
+
+    /// Authenticate a request, read and verify its body
+    async fn read_body<B>(&self, parts: &Parts, body: B) -> S3Result<(Bytes, Option<Checksum>)>
+    where
+        B: Body<Data = Bytes>,
+        B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let auth = authenticate(
+            parts,
+            &self.config.credentials,
+            self.config.max_clock_skew,
+            Utc::now(),
+        )?;
+        if let Some(access_key) = &auth.access_key {
+            debug!("S3 {} {} signed by {}", parts.method, parts.uri, access_key);
+        }
+
+        let too_large = || {
+            S3Error::new(
+                S3ErrorCode::EntityTooLarge,
+                format!(
+                    "request body exceeds the {} byte limit",
+                    self.config.max_object_bytes
+                ),
+            )
+        };
+        let declared =
+            header_str(parts, header::CONTENT_LENGTH).and_then(|l| l.parse::<u64>().ok());
+        if declared.is_some_and(|l| l > self.config.max_object_bytes) {
+            return Err(too_large());
+        }
+        let limit = usize::try_from(self.config.max_object_bytes).unwrap_or(usize::MAX);
+        let raw = match Limited::new(body, limit).collect().await {
+            Ok(collected) => collected.to_bytes(),
+            Err(e) if e.is::<LengthLimitError>() => return Err(too_large()),
+            Err(e) => {
+                return Err(S3Error::new(
+                    S3ErrorCode::IncompleteBody,
+                    format!("failed to read the request body: {}", e),
+                ))
+            }
+        };
+        verify_payload(&auth, &parts.headers, raw)
+    }
+
+    async fn dispatch<B>(&self, parts: &Parts, body: B) -> S3Result<Response<Full<Bytes>>>
+    where
+        B: Body<Data = Bytes>,
+        B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let (body, checksum) = self.read_body(parts, body).await?;
+        let query = Query(query_params(parts));
+
+        match self.address(parts) {
+            (None, _) => match parts.method {
+                Method::GET => {
+                    query.only(&[])?;
+                    let buckets = self.store.list_buckets().await?;
+                    Ok(xml_response(xml::list_buckets(&buckets, OWNER)))
+                }
+                _ => Err(method_not_allowed()),
+            },
+            (Some(bucket), None) => self.bucket_request(parts, &query, &bucket).await,
+            (Some(bucket), Some(key)) => {
+                self.object_request(parts, &query, &bucket, &key, body, checksum)
+                    .await
+            }
+        }
+    }
+
+    // =========================================================================
+    // Bucket Operations
+    // =========================================================================
+
+    async fn bucket_request(
+        &self,
+        parts: &Parts,
+        query: &Query,
+        bucket: &str,
+    ) -> S3Result<Response<Full<Bytes>>> {
+        match parts.method {
+            Method::PUT => {
+                query.only(&[])?;
+                self.store.create_bucket(bucket).await?;
+                Ok(ok()
+                    .header(header::LOCATION, format!("/{}", bucket))
+                    .body(Full::default())
+                    .unwrap())
+            }
+            Method::HEAD => {
+                query.only(&[])?;
+                self.store.head_bucket(bucket).await?;
+                Ok(ok()
+                    .header("x-amz-bucket-region", &self.config.region)
+                    .body(Full::default())
+                    .unwrap())
+            }
+            Method::DELETE => {
+                query.only(&[])?;
+                self.store.delete_bucket(bucket).await?;
+                Ok(no_content())
+            }
+            Method::GET if query.has("location") => {
+                query.only(&["location"])?;
+                self.store.head_bucket(bucket).await?;
+                Ok(xml_response(xml::location_constraint(&self.config.region)))
+            }
+            Method::GET => self.list_objects(query, bucket).await,
+            _ => Err(method_not_allowed()),
+        }
+    }
+
+    async fn list_objects(&self, query: &Query, bucket: &str) -> S3Result<Response<Full<Bytes>>> {
+        query.only(&[
+            "list-type",
+            "prefix",
+            "delimiter",
+            "max-keys",
+            "marker",
+            "continuation-token",
+            "start-after",
+            "encoding-type",
+            "fetch-owner",
+        ])?;
+        let invalid = |message: &str| S3Error::new(S3ErrorCode::InvalidArgument, message);
+
+        let max_keys = match query.get("max-keys") {
+            Some(n) => n
+                .parse::<usize>()
+                .map_err(|_| invalid("max-keys must be a non-negative integer"))?
+                .min(MAX_LIST_KEYS),
+            None => MAX_LIST_KEYS,
+        };
+        let url_encoded = match query.get("encoding-type") {
+            None => false,
+            Some("url") => true,
+            Some(_) => return Err(invalid("encoding-type must be 'url'")),
+        };
+        let v2 = match query.get("list-type") {
+            None | Some("1") => false,
+            Some("2") => true,
+            Some(_) => return Err(invalid("list-type must be 1 or 2")),
+        };
+
+        let token = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        let continuation_token = query.get("continuation-token").filter(|_| v2);
+        let start_after = match continuation_token {
+            Some(t) => Some(
+                token
+                    .decode(t)
+                    .ok()
+                    .and_then(|k| String::from_utf8(k).ok())
+                    .ok_or_else(|| invalid("invalid continuation token"))?,
+            ),
+            None if v2 => query.get("start-after").map(str::to_string),
+            None => query.get("marker").map(str::to_string),
+        };
+
+        let params = ListParams {
+            prefix: query.get("prefix").unwrap_or("").to_string(),
+            delimiter: query
+                .get("delimiter")
+                .filter(|d| !d.is_empty())
+                .map(str::to_string),
+            start_after,
+            max_keys,
+        };
+        let page = self.store.list_objects(bucket, &params).await?;
+
+        let version = if v2 {
+            ListVersion::V2 {
+                continuation_token: continuation_token.map(str::to_string),
+                next_continuation_token: page
+                    .last
+                    .as_ref()
+                    .filter(|_| page.truncated)
+                    .map(|last| token.encode(last)),
+                start_after: query.get("start-after").map(str::to_string),
+            }
+        } else {
+            ListVersion::V1 {
+                marker: query.get("marker").unwrap_or("").to_string(),
+            }
+        };
+        Ok(xml_response(xml::list_objects(&ListResult {
+            bucket,
+            prefix: &params.prefix,
+            delimiter: params.delimiter.as_deref(),
+            max_keys,
+            url_encoded,
+            version,
+            page: &page,
+        })))
+    }
+
+    // =========================================================================
+    // Object Operations
+    // =========================================================================
+
+    async fn object_request(
+        &self,
+        parts: &Parts,
+        query: &Query,
+        bucket: &str,
+        key: &str,
+        body: Bytes,
+        checksum: Option<Checksum>,
+    ) -> S3Result<Response<Full<Bytes>>> {
+        if parts.headers.contains_key("x-amz-copy-source") {
+            return Err(S3Error::new(
+                S3ErrorCode::NotImplemented,
+                "copying objects is not supported",
+            ));
+        }
+
+        match parts.method {
+            Method::PUT if query.has("uploadId") => {
+                query.only(&["uploadId", "partNumber"])?;
+                let part_number = query
+                    .get("partNumber")
+                    .and_then(|n| n.parse().ok())
+                    .ok_or_else(|| {
+                        S3Error::new(S3ErrorCode::InvalidArgument, "invalid partNumber")
+                    })?;
+                let upload_id = query.get("uploadId").unwrap_or_default();
+                let etag = self
+                    .store
+                    .upload_part(bucket, key, upload_id, part_number, body)
+                    .await?;
+                Ok(ok()
+                    .header(header::ETAG, etag)
+                    .body(Full::default())
+                    .unwrap())
+            }
+            Method::PUT => {
+                query.only(&[])?;
+                let info = self
+                    .store
+                    .put_object(
+                        bucket,
+                        key,
+                        body,
+                        content_type(parts),
+                        user_metadata(parts),
+                        checksum,
+                    )
+                    .await?;
+                let mut builder = ok().header(header::ETAG, &info.etag);
+                if let Some((name, value)) = &info.checksum {
+                    builder = builder.header(name, value);
+                }
+                Ok(builder.body(Full::default()).unwrap())
+            }
+            Method::GET => {
+                query.only(&[
+                    "response-cache-control",
+                    "response-content-disposition",
+                    "response-content-encoding",
+                    "response-content-language",
+                    "response-content-type",
+                    "response-expires",
+                ])?;
+                self.get_object(parts, query, bucket, key).await
+            }
+            Method::HEAD => {
+                query.only(&[])?;
+                let info = self.store.head_object(bucket, key).await?;
+                let checksum = header_str(parts, "x-amz-checksum-mode") == Some("ENABLED");
+                Ok(object_headers(ok(), &info, checksum)
+                    .header(header::CONTENT_LENGTH, info.size)
+                    .body(Full::default())
+                    .unwrap())
+            }
+            Method::DELETE if query.has("uploadId") => {
+                query.only(&["uploadId"])?;
+                let upload_id = query.get("uploadId").unwrap_or_default();
+                self.store
+                    .abort_multipart_upload(bucket, key, upload_id)
+                    .await?;
+                Ok(no_content())
+            }
+            Method::DELETE => {
+                query.only(&[])?;
+                self.store.delete_object(bucket, key).await?;
+                Ok(no_content())
+            }
+            Method::POST if query.has("uploads") => {
+                query.only(&["uploads"])?;
+                let upload_id = self
+                    .store
+                    .create_multipart_upload(bucket, key, content_type(parts), user_metadata(parts))
+                    .await?;
+                Ok(xml_response(xml::initiate_multipart_upload(
+                    bucket, key, &upload_id,
+                )))
+            }
+            Method::POST if query.has("uploadId") => {
+                query.only(&["uploadId"])?;
+                let upload_id = query.get("uploadId").unwrap_or_default();
+                let listed = std::str::from_utf8(&body).map_err(|_| {
+                    S3Error::new(S3ErrorCode::MalformedXML, "body is not valid UTF-8")
+                })?;
+                let listed = xml::parse_complete_multipart_upload(listed)?;
+                let info = self
+                    .store
+                    .complete_multipart_upload(bucket, key, upload_id, &listed)
+                    .await?;
+                let host = header_str(parts, header::HOST).unwrap_or("localhost");
+                let location = format!("http://{}/{}/{}", host, bucket, key);
+                Ok(xml_response(xml::complete_multipart_upload(
+                    &location, bucket, &info,
+                )))
+            }
+            _ => Err(method_not_allowed()),
+        }
+    }
+
+    async fn get_object(
+        &self,
+        parts: &Parts,
+        query: &Query,
+        bucket: &str,
+        key: &str,
+    ) -> S3Result<Response<Full<Bytes>>> {
+        // Resolve the range against the current size, then read only that
+        let range = match header_str(parts, header::RANGE) {
+            Some(value) => {
+                let size = self.store.head_object(bucket, key).await?.size;
+                parse_range(value, size).map_err(|e| {
+                    S3Error::new(e.code, format!("{} (object is {} bytes)", e.message, size))
+                })?
+            }
+            None => None,
+        };
+        let (info, data) = match range {
+            Some((start, end)) => self.store.get_object_range(bucket, key, start, end).await?,
+            None => self.store.get_object(bucket, key).await?,
+        };
+
+        let checksum = header_str(parts, "x-amz-checksum-mode") == Some("ENABLED");
+        let mut builder = object_headers(ok(), &info, checksum && range.is_none());
+        for (name, value) in &query.0 {
+            if let Some(header_name) = name.strip_prefix("response-") {
+                let (Ok(header_name), Ok(value)) = (
+                    HeaderName::from_bytes(header_name.as_bytes()),
+                    HeaderValue::from_str(value),
+                ) else {
+                    return Err(S3Error::new(
+                        S3ErrorCode::InvalidArgument,
+                        format!("invalid value for {}", name),
+                    ));
+                };
+                builder = builder.header(header_name, value);
+            }
+        }
+
+        if let Some((start, end)) = range {
+            builder = builder.status(StatusCode::PARTIAL_CONTENT).header(
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{}", start, end, info.size),
+            );
+        }
+        Ok(builder.body(Full::new(data)).unwrap())
+    }
+}
+
+fn method_not_allowed() -> S3Error {
+    S3Error::new(
+        S3ErrorCode::MethodNotAllowed,
+        "the method is not allowed against this resource",
+    )
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::super::auth::{canonical_request, hex, hmac_sha256, sha256_hex, signing_key};
+    use super::super::S3Credentials;
+    use super::*;
+
+    fn server(config: S3Config) -> Arc<S3Server> {
+        S3Server::new(config, Arc::new(CacheManager::in_memory())).unwrap()
+    }
+
+    fn open_server() -> Arc<S3Server> {
+        server(S3Config {
+            min_part_bytes: 4,
+            ..Default::default()
+        })
+    }
+
+    async fn send(
+        server: &S3Server,
+        method: Method,
+        uri: &str,
+        body: &'static [u8],
+    ) -> Response<Full<Bytes>> {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::HOST, "localhost:9000")
+            .body(Full::new(Bytes::from_static(body)))
+            .unwrap();
+        server.handle(request).await
+    }
+
+    async fn body_text(response: Response<Full<Bytes>>) -> String {
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    /// Text of the first `<tag>` element
+    fn element(xml: &str, tag: &str) -> String {
+        let open = format!("<{}>", tag);
+        let start = xml.find(&open).unwrap() + open.len();
+        let end = xml[start..].find(&format!("</{}>", tag)).unwrap();
+        xml[start..start + end].to_string()
+    }
+
+    #[test]
+    fn test_anonymous_access_limited_to_loopback() {
+        let loopback: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let public: SocketAddr = "0.0.0.0:9000".parse().unwrap();
+
+        let anonymous = server(S3Config::default());
+        assert!(anonymous.check_exposure(loopback).is_ok());
+        assert!(anonymous.check_exposure(public).is_err());
+
+        let authenticated = server(S3Config {
+            credentials: vec![S3Credentials::new("AKID", "secret")],
+            ..Default::default()
+        });
+        assert!(authenticated.check_exposure(public).is_ok());
+
+        let opted_out = server(S3Config {
+            allow_anonymous: true,
+            ..Default::default()
+        });
+        assert!(opted_out.check_exposure(public).is_ok());
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-9", 100).unwrap(), Some((0, 9)));
+        assert_eq!(parse_range("bytes=90-200", 100).unwrap(), Some((90, 99)));
+        assert_eq!(parse_range("bytes=10-", 100).unwrap(), Some((10, 99)));
+        assert_eq!(parse_range("bytes=-10", 100).unwrap(), Some((90, 99)));
+        assert_eq!(parse_range("bytes=-500", 100).unwrap(), Some((0, 99)));
+        assert_eq!(parse_range("bytes=0-1,5-6", 100).unwrap(), None);
+        assert_eq!(parse_range("items=0-1", 100).unwrap(), None);
+        assert_eq!(parse_range("bytes=5-1", 100).unwrap(), None);
+        assert!(parse_range("bytes=100-", 100).is_err());
+        assert!(parse_range("bytes=-0", 100).is_err());
+        assert!(parse_range("bytes=0-", 0).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_object_round_trip() {
+        let server = open_server();
+        let response = send(&server, Method::PUT, "/bucket", b"").await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request = Request::put("/bucket/dir/hello%20world.txt")
+            .header(header::CONTENT_TYPE, "text/plain")
+            .header("x-amz-meta-color", "blue")
+            .body(Full::new(Bytes::from_static(b"hello, world")))
+            .unwrap();
+        let response = server.handle(request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()[header::ETAG].clone();
+        assert!(response.headers().contains_key("x-amz-request-id"));
+
+        let response = send(&server, Method::GET, "/bucket/dir/hello%20world.txt", b"").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::ETAG], etag);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/plain");
+        assert_eq!(response.headers()["x-amz-meta-color"], "blue");
+        assert_eq!(body_text(response).await, "hello, world");
+
+        let response = send(&server, Method::HEAD, "/bucket/dir/hello%20world.txt", b"").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], "12");
+        assert!(body_text(response).await.is_empty());
+
+        let request = Request::get("/bucket/dir/hello%20world.txt")
+            .header(header::RANGE, "bytes=7-")
+            .body(Full::default())
+            .unwrap();
+        let response = server.handle(request).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 7-11/12");
+        assert_eq!(body_text(response).await, "world");
+
+        let response = send(
+            &server,
+            Method::GET,
+            "/bucket/dir/hello%20world.txt?response-content-type=%0A",
+            b"",
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            element(&body_text(response).await, "Code"),
+            "InvalidArgument"
+        );
+
+        let response = send(
+            &server,
+            Method::DELETE,
+            "/bucket/dir/hello%20world.txt",
+            b"",
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = send(&server, Method::GET, "/bucket/dir/hello%20world.txt", b"").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let text = body_text(response).await;
+        assert_eq!(element(&text, "Code"), "NoSuchKey");
+        assert_eq!(element(&text, "Resource"), "/bucket/dir/hello%20world.txt");
+    }
+
+    #[tokio::test]
+    async fn test_list_objects_v2_pagination() {
+        let server = open_server();
+        send(&server, Method::PUT, "/bucket", b"").await;
+        for key in ["a", "b", "c", "dir/x", "dir/y"] {
+            let response = send(&server, Method::PUT, &format!("/bucket/{}", key), b"x").await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let mut uri = "/bucket?list-type=2&delimiter=%2F&max-keys=2".to_string();
+        let mut pages = Vec::new();
+        loop {
+            let text = body_text(send(&server, Method::GET, &uri, b"").await).await;
+            pages.push(text.clone());
+            if element(&text, "IsTruncated") == "false" {
+                break;
+            }
+            let token = element(&text, "NextContinuationToken");
+            uri = format!(
+                "/bucket?list-type=2&delimiter=%2F&max-keys=2&continuation-token={}",
+                token
+            );
+        }
+        assert_eq!(pages.len(), 2);
+        assert!(pages[0].contains("<Key>a</Key><LastModified>"));
+        assert!(pages[0].contains("<Key>b</Key>"));
+        assert!(pages[1].contains("<Key>c</Key>"));
+        assert!(pages[1].contains("<CommonPrefixes><Prefix>dir/</Prefix></CommonPrefixes>"));
+
+        let text = body_text(send(&server, Method::GET, "/", b"").await).await;
+        assert_eq!(element(&text, "Name"), "bucket");
+
+        let response = send(&server, Method::GET, "/bucket?acl", b"").await;
+        assert_eq!(response.status(), StatusCode::NOT_IMPLEMENTED);
+    }
+
+    #[tokio::test]
+    async fn test_multipart_upload_over_http() {
+        let server = open_server();
+        send(&server, Method::PUT, "/bucket", b"").await;
+
+        let text = body_text(send(&server, Method::POST, "/bucket/big?uploads", b"").await).await;
+        let upload_id = element(&text, "UploadId");
+
+        let mut etags = Vec::new();
+        for (part, data) in [(1, &b"first "[..]), (2, &b"second"[..])] {
+            let uri = format!("/bucket/big?partNumber={}&uploadId={}", part, upload_id);
+            let response = send(&server, Method::PUT, &uri, data).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            etags.push(
+                response.headers()[header::ETAG]
+                    .to_str()
+                    .unwrap()
+                    .to_string(),
+            );
+        }
+
+        let body = format!(
+            "<CompleteMultipartUpload>\
+             <Part><PartNumber>1</PartNumber><ETag>{}</ETag></Part>\
+             <Part><PartNumber>2</PartNumber><ETag>{}</ETag></Part>\
+             </CompleteMultipartUpload>",
+            etags[0], etags[1]
+        );
+        let request = Request::post(format!("/bucket/big?uploadId={}", upload_id))
+            .body(Full::new(Bytes::from(body)))
+            .unwrap();
+        let response = server.handle(request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let text = body_text(response).await;
+        assert!(element(&text, "ETag").ends_with("-2&quot;"));
+
+        let response = send(&server, Method::GET, "/bucket/big", b"").await;
+        assert_eq!(body_text(response).await, "first second");
+
+        let uri = format!("/bucket/big?uploadId={}", upload_id);
+        let response = send(&server, Method::DELETE, &uri, b"").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_virtual_hosted_addressing() {
+        let server = server(S3Config {
+            domain: Some("s3.example.com".to_string()),
+            ..Default::default()
+        });
+        let request = Request::put("/")
+            .header(header::HOST, "photos.s3.example.com")
+            .body(Full::default())
+            .unwrap();
+        assert_eq!(server.handle(request).await.status(), StatusCode::OK);
+
+        let request = Request::put("/cat.jpg")
+            .header(header::HOST, "photos.s3.example.com:9000")
+            .body(Full::new(Bytes::from_static(b"meow")))
+            .unwrap();
+        assert_eq!(server.handle(request).await.status(), StatusCode::OK);
+
+        // The same object, path-style
+        let request = Request::get("/photos/cat.jpg")
+            .header(header::HOST, "s3.example.com")
+            .body(Full::default())
+            .unwrap();
+        assert_eq!(body_text(server.handle(request).await).await, "meow");
+    }
+
+    #[tokio::test]
+    async fn test_body_limit() {
+        let server = server(S3Config {
+            max_object_bytes: 4,
+            ..Default::default()
+        });
+        send(&server, Method::PUT, "/bucket", b"").await;
+        let response = send(&server, Method::PUT, "/bucket/key", b"too large").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            element(&body_text(response).await, "Code"),
+            "EntityTooLarge"
+        );
+    }
+
+    /// Sign a request the way an SDK does, with the payload hash in
+    /// `x-amz-content-sha256`
+    fn signed(
+        method: Method,
+        uri: &str,
+        body: &'static [u8],
+        secret: &str,
+    ) -> Request<Full<Bytes>> {
+        let amz_date = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        let date = &amz_date[..8];
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::HOST, "localhost:9000")
+            .header("x-amz-content-sha256", sha256_hex(body))
+            .header("x-amz-date", &amz_date)
+            .body(Full::new(Bytes::from_static(body)))
+            .unwrap();
+        let (mut parts, full) = request.into_parts();
+
+        let signed_headers = ["host", "x-amz-content-sha256", "x-amz-date"].map(str::to_string);
+        let canonical = canonical_request(&parts, &signed_headers, &sha256_hex(body), false);
+        let scope = format!("{}/us-east-1/s3/aws4_request", date);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            sha256_hex(canonical.as_bytes())
+        );
+        let key = signing_key(secret, date, "us-east-1", "s3");
+        let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+        parts.headers.insert(
+            header::AUTHORIZATION,
+            format!(
+                "AWS4-HMAC-SHA256 Credential=AKID/{},SignedHeaders={},Signature={}",
+                scope,
+                signed_headers.join(";"),
+                signature
+            )
+            .parse()
+            .unwrap(),
+        );
+        Request::from_parts(parts, full)
+    }
+
+    #[tokio::test]
+    async fn test_sigv4_authentication() {
+        let server = server(S3Config {
+            credentials: vec![S3Credentials::new("AKID", "secret")],
+            ..Default::default()
+        });
+
+        let response = server
+            .handle(signed(Method::PUT, "/bucket", b"", "secret"))
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = server
+            .handle(signed(
+                Method::PUT,
+                "/bucket/key?x-id=PutObject",
+                b"payload",
+                "secret",
+            ))
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = server
+            .handle(signed(Method::PUT, "/bucket/key", b"payload", "wrong"))
+            .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(
+            element(&body_text(response).await, "Code"),
+            "SignatureDoesNotMatch"
+        );
+
+        let response = send(&server, Method::GET, "/bucket/key", b"").await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(element(&body_text(response).await, "Code"), "AccessDenied");
+
+        // HEAD errors carry no body
+        let response = send(&server, Method::HEAD, "/bucket/key", b"").await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(body_text(response).await.is_empty());
+    }
+}
@@ -0,0 +1,1093 @@
+//! Object Store
+//!
+//! Buckets, objects and multipart uploads on top of the [`CacheManager`].
+//!
+//! Object bodies are immutable: every write stores its body under a fresh
+//! data ID, then commits the metadata record that points to it, and only
+//! then deletes the body it replaced. Readers therefore always find the
+//! body their metadata names, except when racing an overwrite, in which
+//! case the read is retried with the new metadata.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use bytes::{Bytes, BytesMut};
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::{Mutex, MutexGuard};
+use tracing::{debug, warn};
+
+use super::auth::hex;
+use super::{S3Error, S3ErrorCode, S3Result};
+use crate::rustfs::cache::{CacheEntry, CacheKey, CacheManager};
+use crate::rustfs::MAX_PARTS;
+
+/// Reserved cache bucket holding bucket records
+const BUCKETS: &str = ".buckets";
+
+/// Longest object key, in bytes
+const MAX_KEY_BYTES: usize = 1024;
+
+/// Number of lock stripes serializing record updates
+const LOCK_STRIPES: usize = 64;
+
+fn meta_ns(bucket: &str) -> String {
+    format!(".meta.{}", bucket)
+}
+
+fn data_ns(bucket: &str) -> String {
+    format!(".data.{}", bucket)
+}
+
+fn uploads_ns(bucket: &str) -> String {
+    format!(".uploads.{}", bucket)
+}
+
+fn parts_ns(bucket: &str) -> String {
+    format!(".parts.{}", bucket)
+}
+
+fn part_key(upload_id: &str, part_number: u32) -> String {
+    format!("{}/{:05}", upload_id, part_number)
+}
+
+/// Quoted ETag of an object body: the first 16 bytes of its SHA-256
+///
+/// S3 uses MD5 here; clients treat ETags as opaque, so any digest of the
+/// same width works.
+fn etag(data: &[u8]) -> String {
+    format!("\"{}\"", hex(&Sha256::digest(data)[..16]))
+}
+
+/// Strip the quotes clients may or may not send around an ETag
+fn unquote(etag: &str) -> &str {
+    etag.trim().trim_matches('"')
+}
+
+/// Validate a bucket name against the S3 naming rules
+pub(super) fn validate_bucket_name(name: &str) -> S3Result<()> {
+    let valid = (3..=63).contains(&name.len())
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'.' || b == b'-')
+        && name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && name.ends_with(|c: char| c.is_ascii_alphanumeric())
+        && !name.contains("..");
+    if valid {
+        Ok(())
+    } else {
+        Err(S3Error::new(
+            S3ErrorCode::InvalidBucketName,
+            format!("invalid bucket name '{}'", name),
+        ))
+    }
+}
+
+fn validate_key(key: &str) -> S3Result<()> {
+    if key.is_empty() || key.len() > MAX_KEY_BYTES {
+        return Err(S3Error::new(
+            S3ErrorCode::InvalidArgument,
+            format!("object keys must be 1 to {} bytes", MAX_KEY_BYTES),
+        ));
+    }
+    Ok(())
+}
+
+fn no_such_upload(upload_id: &str) -> S3Error {
+    S3Error::new(
+        S3ErrorCode::NoSuchUpload,
+        format!("upload '{}' does not exist", upload_id),
+    )
+}
+
+// =============================================================================
+// Records
+// =============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BucketRecord {
+    created: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ObjectRecord {
+    size: u64,
+    etag: String,
+    last_modified: DateTime<Utc>,
+    content_type: Option<String>,
+    metadata: BTreeMap<String, String>,
+    checksum: Option<(String, String)>,
+    data_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct UploadRecord {
+    key: String,
+    initiated: DateTime<Utc>,
+    content_type: Option<String>,
+    metadata: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PartRecord {
+    etag: String,
+    size: u64,
+    data_id: String,
+}
+
+/// Metadata of a stored object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectInfo {
+    /// Object key
+    pub key: String,
+
+    /// Body size in bytes
+    pub size: u64,
+
+    /// Quoted entity tag
+    pub etag: String,
+
+    /// Time of the write that created this version
+    pub last_modified: DateTime<Utc>,
+
+    /// `Content-Type` given on upload
+    pub content_type: Option<String>,
+
+    /// `x-amz-meta-*` user metadata, without the prefix
+    pub metadata: BTreeMap<String, String>,
+
+    /// Checksum supplied on upload, as `(header name, base64 value)`
+    pub checksum: Option<(String, String)>,
+}
+
+impl ObjectInfo {
+    fn from_record(key: &str, record: ObjectRecord) -> Self {
+        Self {
+            key: key.to_string(),
+            size: record.size,
+            etag: record.etag,
+            last_modified: record.last_modified,
+            content_type: record.content_type,
+            metadata: record.metadata,
+            checksum: record.checksum,
+        }
+    }
+}
+
+/// Parameters of an object listing
+#[derive(Debug, Clone)]
+pub(super) struct ListParams {
+    /// Only keys starting with this prefix
+    pub prefix: String,
+
+    /// Roll up keys sharing a prefix up to this delimiter
+    pub delimiter: Option<String>,
+
+    /// Only keys (or common prefixes) after this one
+    pub start_after: Option<String>,
+
+    /// Largest number of keys and common prefixes returned
+    pub max_keys: usize,
+}
+
+/// One page of an object listing
+#[derive(Debug, Clone, Default)]
+pub(super) struct ListPage {
+    /// Objects, in key order
+    pub objects: Vec<ObjectInfo>,
+
+    /// Rolled-up prefixes, in order
+    pub common_prefixes: Vec<String>,
+
+    /// Whether more results follow
+    pub truncated: bool,
+
+    /// Last key or common prefix returned, to resume from
+    pub last: Option<String>,
+}
+
+// =============================================================================
+// Object Store
+// =============================================================================
+
+/// S3 buckets and objects stored in a [`CacheManager`]
+pub struct ObjectStore {
+    cache: Arc<CacheManager>,
+    min_part_bytes: u64,
+    locks: Vec<Mutex<()>>,
+}
+
+impl ObjectStore {
+    /// Create a store over `cache`
+    ///
+    /// The cache must be write-through, since listings come from L3.
+    pub fn new(cache: Arc<CacheManager>, min_part_bytes: u64) -> crate::error::Result<Self> {
+        if !cache.config().write_through {
+            return Err(crate::error::Error::Config(
+                "the S3 object store needs a write-through cache".to_string(),
+            ));
+        }
+        Ok(Self {
+            cache,
+            min_part_bytes,
+            locks: (0..LOCK_STRIPES).map(|_| Mutex::new(())).collect(),
+        })
+    }
+
+    fn stripe(namespace: &str, key: &str) -> usize {
+        CacheKey::new(namespace, key).combined_hash() as usize % LOCK_STRIPES
+    }
+
+    /// Serialize updates of one record
+    async fn lock(&self, namespace: &str, key: &str) -> MutexGuard<'_, ()> {
+        self.locks[Self::stripe(namespace, key)].lock().await
+    }
+
+    /// Serialize updates of two records, locking stripes in order
+    async fn lock_both(
+        &self,
+        first: (&str, &str),
+        second: (&str, &str),
+    ) -> (MutexGuard<'_, ()>, Option<MutexGuard<'_, ()>>) {
+        let a = Self::stripe(first.0, first.1);
+        let b = Self::stripe(second.0, second.1);
+        match a.cmp(&b) {
+            std::cmp::Ordering::Equal => (self.locks[a].lock().await, None),
+            std::cmp::Ordering::Less => {
+                let guard = self.locks[a].lock().await;
+                (guard, Some(self.locks[b].lock().await))
+            }
+            std::cmp::Ordering::Greater => {
+                let guard = self.locks[b].lock().await;
+                (guard, Some(self.locks[a].lock().await))
+            }
+        }
+    }
+
+    async fn get_bytes(&self, namespace: &str, key: &str) -> Option<Bytes> {
+        self.cache
+            .get(&CacheKey::new(namespace, key))
+            .await
+            .map(|r| r.entry.data().clone())
+    }
+
+    async fn put_bytes(&self, namespace: &str, key: &str, data: Bytes) -> S3Result<()> {
+        self.cache
+            .put(CacheKey::new(namespace, key), CacheEntry::new(data))
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_bytes(&self, namespace: &str, key: &str) -> S3Result<()> {
+        self.cache.delete(&CacheKey::new(namespace, key)).await?;
+        Ok(())
+    }
+
+    async fn get_record<T: DeserializeOwned>(
+        &self,
+        namespace: &str,
+        key: &str,
+    ) -> S3Result<Option<T>> {
+        match self.get_bytes(namespace, key).await {
+            Some(data) => serde_json::from_slice(&data).map(Some).map_err(|e| {
+                S3Error::new(
+                    S3ErrorCode::InternalError,
+                    format!("corrupt record {}/{}: {}", namespace, key, e),
+                )
+            }),
+            None => Ok(None),
+        }
+    }
+
+    async fn put_record<T: Serialize>(
+        &self,
+        namespace: &str,
+        key: &str,
+        record: &T,
+    ) -> S3Result<()> {
+        let data = serde_json::to_vec(record)
+            .map_err(|e| S3Error::new(S3ErrorCode::InternalError, e.to_string()))?;
+        self.put_bytes(namespace, key, Bytes::from(data)).await
+    }
+
+    /// Store a body under a fresh data ID
+    async fn put_data(&self, bucket: &str, data: Bytes) -> S3Result<String> {
+        let data_id = uuid::Uuid::new_v4().to_string();
+        self.put_bytes(&data_ns(bucket), &data_id, data).await?;
+        Ok(data_id)
+    }
+
+    /// Delete a body, logging rather than failing since it is unreferenced
+    async fn delete_data(&self, bucket: &str, data_id: &str) {
+        if let Err(e) = self.delete_bytes(&data_ns(bucket), data_id).await {
+            warn!("Failed to delete object data {}/{}: {}", bucket, data_id, e);
+        }
+    }
+
+    // =========================================================================
+    // Buckets
+    // =========================================================================
+
+    /// Create a bucket; creating an existing bucket succeeds
+    pub async fn create_bucket(&self, bucket: &str) -> S3Result<()> {
+        validate_bucket_name(bucket)?;
+        let _guard = self.lock(BUCKETS, bucket).await;
+        if self.get_bytes(BUCKETS, bucket).await.is_none() {
+            self.put_record(
+                BUCKETS,
+                bucket,
+                &BucketRecord {
+                    created: Utc::now(),
+                },
+            )
+            .await?;
+            debug!("Created bucket {}", bucket);
+        }
+        Ok(())
+    }
+
+    /// Fail with `NoSuchBucket` unless the bucket exists
+    pub async fn head_bucket(&self, bucket: &str) -> S3Result<()> {
+        match self.get_bytes(BUCKETS, bucket).await {
+            Some(_) => Ok(()),
+            None => Err(S3Error::new(
+                S3ErrorCode::NoSuchBucket,
+                format!("bucket '{}' does not exist", bucket),
+            )),
+        }
+    }
+
+    /// List buckets with their creation times, by name
+    pub async fn list_buckets(&self) -> S3Result<Vec<(String, DateTime<Utc>)>> {
+        let mut buckets = Vec::new();
+        for name in self.cache.list(BUCKETS, "").await? {
+            if let Some(record) = self.get_record::<BucketRecord>(BUCKETS, &name).await? {
+                buckets.push((name, record.created));
+            }
+        }
+        Ok(buckets)
+    }
+
+    /// Delete an empty bucket
+    pub async fn delete_bucket(&self, bucket: &str) -> S3Result<()> {
+        self.head_bucket(bucket).await?;
+        let _guard = self.lock(BUCKETS, bucket).await;
+        let has_objects = !self.cache.list(&meta_ns(bucket), "").await?.is_empty();
+        let has_uploads = !self.cache.list(&uploads_ns(bucket), "").await?.is_empty();
+        if has_objects || has_uploads {
+            return Err(S3Error::new(
+                S3ErrorCode::BucketNotEmpty,
+                format!("bucket '{}' is not empty", bucket),
+            ));
+        }
+        self.delete_bytes(BUCKETS, bucket).await?;
+        debug!("Deleted bucket {}", bucket);
+        Ok(())
+    }
+
+    // =========================================================================
+    // Objects
+    // =========================================================================
+
+    /// Commit `record` as the current version of `key`, deleting the body
+    /// it replaces
+    async fn commit_object(&self, bucket: &str, key: &str, record: &ObjectRecord) -> S3Result<()> {
+        let meta = meta_ns(bucket);
+        let previous = self.get_record::<ObjectRecord>(&meta, key).await?;
+        self.put_record(&meta, key, record).await?;
+        if let Some(previous) = previous {
+            self.delete_data(bucket, &previous.data_id).await;
+        }
+        Ok(())
+    }
+
+    /// Store an object, replacing any existing one
+    pub async fn put_object(
+        &self,
+        bucket: &str,
+        key: &str,
+        data: Bytes,
+        content_type: Option<String>,
+        metadata: BTreeMap<String, String>,
+        checksum: Option<(String, String)>,
+    ) -> S3Result<ObjectInfo> {
+        validate_key(key)?;
+        self.head_bucket(bucket).await?;
+
+        let record = ObjectRecord {
+            size: data.len() as u64,
+            etag: etag(&data),
+            last_modified: Utc::now(),
+            content_type,
+            metadata,
+            checksum,
+            data_id: self.put_data(bucket, data).await?,
+        };
+
+        let _guard = self.lock(&meta_ns(bucket), key).await;
+        self.commit_object(bucket, key, &record).await?;
+        Ok(ObjectInfo::from_record(key, record))
+    }
+
+    async fn object_record(&self, bucket: &str, key: &str) -> S3Result<ObjectRecord> {
+        self.head_bucket(bucket).await?;
+        self.get_record::<ObjectRecord>(&meta_ns(bucket), key)
+            .await?
+            .ok_or_else(|| {
+                S3Error::new(
+                    S3ErrorCode::NoSuchKey,
+                    format!("key '{}' does not exist", key),
+                )
+            })
+    }
+
+    /// Read an object's metadata
+    pub async fn head_object(&self, bucket: &str, key: &str) -> S3Result<ObjectInfo> {
+        let record = self.object_record(bucket, key).await?;
+        Ok(ObjectInfo::from_record(key, record))
+    }
+
+    /// Read an object
+    pub async fn get_object(&self, bucket: &str, key: &str) -> S3Result<(ObjectInfo, Bytes)> {
+        // A concurrent overwrite may delete the body between the two reads
+        for _ in 0..2 {
+            let record = self.object_record(bucket, key).await?;
+            if let Some(data) = self.get_bytes(&data_ns(bucket), &record.data_id).await {
+                if data.len() as u64 != record.size {
+                    return Err(S3Error::new(
+                        S3ErrorCode::InternalError,
+                        format!(
+                            "object '{}' is {} bytes, expected {}",
+                            key,
+                            data.len(),
+                            record.size
+                        ),
+                    ));
+                }
+                return Ok((ObjectInfo::from_record(key, record), data));
+            }
+        }
+        Err(S3Error::new(
+            S3ErrorCode::InternalError,
+            format!("data of object '{}' is missing", key),
+        ))
+    }
+
+    /// Read bytes `start..=end` of an object without loading the rest of it
+    ///
+    /// Large bodies are read through the cache in chunks, so only the chunks
+    /// covering the range are fetched.
+    pub async fn get_object_range(
+        &self,
+        bucket: &str,
+        key: &str,
+        start: u64,
+        end: u64,
+    ) -> S3Result<(ObjectInfo, Bytes)> {
+        // A concurrent overwrite may delete the body between the two reads
+        for _ in 0..2 {
+            let record = self.object_record(bucket, key).await?;
+            if end >= record.size {
+                return Err(S3Error::new(
+                    S3ErrorCode::InvalidRange,
+                    "the requested range is not satisfiable",
+                ));
+            }
+            let data_key = CacheKey::new(data_ns(bucket), record.data_id.as_str());
+            let len = end - start + 1;
+            if let Some(data) = self.cache.get_range(&data_key, start, len).await? {
+                if data.len() as u64 != len {
+                    return Err(S3Error::new(
+                        S3ErrorCode::InternalError,
+                        format!(
+                            "read {} bytes of object '{}' at {}, expected {}",
+                            data.len(),
+                            key,
+                            start,
+                            len
+                        ),
+                    ));
+                }
+                return Ok((ObjectInfo::from_record(key, record), data));
+            }
+        }
+        Err(S3Error::new(
+            S3ErrorCode::InternalError,
+            format!("data of object '{}' is missing", key),
+        ))
+    }
+
+    /// Delete an object; deleting a missing key succeeds
+    pub async fn delete_object(&self, bucket: &str, key: &str) -> S3Result<()> {
+        self.head_bucket(bucket).await?;
+        let meta = meta_ns(bucket);
+        let _guard = self.lock(&meta, key).await;
+        if let Some(record) = self.get_record::<ObjectRecord>(&meta, key).await? {
+            self.delete_bytes(&meta, key).await?;
+            self.delete_data(bucket, &record.data_id).await;
+        }
+        Ok(())
+    }
+
+    /// List objects, rolling keys up into common prefixes when a delimiter
+    /// is given
+    pub(super) async fn list_objects(
+        &self,
+        bucket: &str,
+        params: &ListParams,
+    ) -> S3Result<ListPage> {
+        self.head_bucket(bucket).await?;
+        let meta = meta_ns(bucket);
+        let keys = self.cache.list(&meta, &params.prefix).await?;
+        let after = params.start_after.as_deref().unwrap_or("");
+
+        let mut page = ListPage::default();
+        let mut returned = 0;
+        for key in keys.iter().filter(|k| k.as_str() > after) {
+            let common_prefix = params.delimiter.as_deref().and_then(|d| {
+                key[params.prefix.len()..]
+                    .find(d)
+                    .map(|at| key[..params.prefix.len() + at + d.len()].to_string())
+            });
+            if let Some(common_prefix) = &common_prefix {
+                let seen = page.common_prefixes.last() == Some(common_prefix);
+                if seen || common_prefix.as_str() <= after {
+                    continue;
+                }
+            }
+            if returned == params.max_keys {
+                page.truncated = true;
+                break;
+            }
+
+            match common_prefix {
+                Some(common_prefix) => {
+                    page.last = Some(common_prefix.clone());
+                    page.common_prefixes.push(common_prefix);
+                }
+                None => {
+                    // Deleted since it was listed
+                    let Some(record) = self.get_record::<ObjectRecord>(&meta, key).await? else {
+                        continue;
+                    };
+                    page.last = Some(key.clone());
+                    page.objects.push(ObjectInfo::from_record(key, record));
+                }
+            }
+            returned += 1;
+        }
+        Ok(page)
+    }
+
+    // =========================================================================
+    // Multipart Uploads
+    // =========================================================================
+
+    async fn upload_record(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+    ) -> S3Result<UploadRecord> {
+        self.head_bucket(bucket).await?;
+        match self
+            .get_record::<UploadRecord>(&uploads_ns(bucket), upload_id)
+            .await?
+        {
+            Some(upload) if upload.key == key => Ok(upload),
+            _ => Err(no_such_upload(upload_id)),
+        }
+    }
+
+    /// Start a multipart upload, returning its ID
+    pub async fn create_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        content_type: Option<String>,
+        metadata: BTreeMap<String, String>,
+    ) -> S3Result<String> {
+        validate_key(key)?;
+        self.head_bucket(bucket).await?;
+        let upload_id = uuid::Uuid::new_v4().simple().to_string();
+        self.put_record(
+            &uploads_ns(bucket),
+            &upload_id,
+            &UploadRecord {
+                key: key.to_string(),
+                initiated: Utc::now(),
+                content_type,
+                metadata,
+            },
+        )
+        .await?;
+        debug!("Started upload {} of {}/{}", upload_id, bucket, key);
+        Ok(upload_id)
+    }
+
+    /// Store one part of an upload, returning its ETag
+    pub async fn upload_part(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_number: u32,
+        data: Bytes,
+    ) -> S3Result<String> {
+        if !(1..=MAX_PARTS).contains(&part_number) {
+            return Err(S3Error::new(
+                S3ErrorCode::InvalidArgument,
+                format!("part numbers must be between 1 and {}", MAX_PARTS),
+            ));
+        }
+        self.upload_record(bucket, key, upload_id).await?;
+
+        let record = PartRecord {
+            etag: etag(&data),
+            size: data.len() as u64,
+            data_id: self.put_data(bucket, data).await?,
+        };
+
+        // The upload may have completed or been aborted meanwhile
+        let uploads = uploads_ns(bucket);
+        let parts = parts_ns(bucket);
+        let part_key = part_key(upload_id, part_number);
+        let _guards = self
+            .lock_both((&uploads, upload_id), (&parts, &part_key))
+            .await;
+        if self.get_bytes(&uploads, upload_id).await.is_none() {
+            self.delete_data(bucket, &record.data_id).await;
+            return Err(no_such_upload(upload_id));
+        }
+        let previous = self.get_record::<PartRecord>(&parts, &part_key).await?;
+        self.put_record(&parts, &part_key, &record).await?;
+        if let Some(previous) = previous {
+            self.delete_data(bucket, &previous.data_id).await;
+        }
+        Ok(record.etag)
+    }
+
+    /// Delete the part records and data of an upload, then the upload
+    async fn remove_upload(&self, bucket: &str, upload_id: &str) -> S3Result<()> {
+        let parts = parts_ns(bucket);
+        for part_key in self.cache.list(&parts, &format!("{}/", upload_id)).await? {
+            if let Some(part) = self.get_record::<PartRecord>(&parts, &part_key).await? {
+                self.delete_data(bucket, &part.data_id).await;
+            }
+            self.delete_bytes(&parts, &part_key).await?;
+        }
+        self.delete_bytes(&uploads_ns(bucket), upload_id).await
+    }
+
+    /// Assemble the listed parts into the object
+    ///
+    /// `parts` are `(part number, ETag)` pairs in ascending part order.
+    pub async fn complete_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        parts: &[(u32, String)],
+    ) -> S3Result<ObjectInfo> {
+        if parts.is_empty() {
+            return Err(S3Error::new(
+                S3ErrorCode::MalformedXML,
+                "at least one part must be specified",
+            ));
+        }
+        if parts.windows(2).any(|w| w[0].0 >= w[1].0) {
+            return Err(S3Error::new(
+                S3ErrorCode::InvalidPartOrder,
+                "parts must be listed in ascending order",
+            ));
+        }
+
+        let uploads = uploads_ns(bucket);
+        let meta = meta_ns(bucket);
+        let _guards = self.lock_both((&uploads, upload_id), (&meta, key)).await;
+        let upload = self.upload_record(bucket, key, upload_id).await?;
+
+        let parts_ns = parts_ns(bucket);
+        let mut body = BytesMut::new();
+        let mut digests = Vec::with_capacity(parts.len() * 16);
+        for (i, (part_number, claimed)) in parts.iter().enumerate() {
+            let invalid = || {
+                S3Error::new(
+                    S3ErrorCode::InvalidPart,
+                    format!(
+                        "part {} was not uploaded or its ETag does not match",
+                        part_number
+                    ),
+                )
+            };
+            let part = self
+                .get_record::<PartRecord>(&parts_ns, &part_key(upload_id, *part_number))
+                .await?
+                .ok_or_else(invalid)?;
+            if unquote(&part.etag) != unquote(claimed) {
+                return Err(invalid());
+            }
+            if part.size < self.min_part_bytes && i + 1 < parts.len() {
+                return Err(S3Error::new(
+                    S3ErrorCode::EntityTooSmall,
+                    format!(
+                        "part {} is {} bytes, below the {} byte minimum",
+                        part_number, part.size, self.min_part_bytes
+                    ),
+                ));
+            }
+            let data = self
+                .get_bytes(&data_ns(bucket), &part.data_id)
+                .await
+                .ok_or_else(|| {
+                    S3Error::new(
+                        S3ErrorCode::InternalError,
+                        format!("data of part {} is missing", part_number),
+                    )
+                })?;
+            body.extend_from_slice(&data);
+            digests.extend_from_slice(&Sha256::digest(&data)[..16]);
+        }
+
+        let size = body.len() as u64;
+        let record = ObjectRecord {
+            size,
+            etag: format!(
+                "\"{}-{}\"",
+                hex(&Sha256::digest(&digests)[..16]),
+                parts.len()
+            ),
+            last_modified: Utc::now(),
+            content_type: upload.content_type,
+            metadata: upload.metadata,
+            checksum: None,
+            data_id: self.put_data(bucket, body.freeze()).await?,
+        };
+        self.commit_object(bucket, key, &record).await?;
+        self.remove_upload(bucket, upload_id).await?;
+        debug!(
+            "Completed upload {} of {}/{} ({} parts, {} bytes)",
+            upload_id,
+            bucket,
+            key,
+            parts.len(),
+            size
+        );
+        Ok(ObjectInfo::from_record(key, record))
+    }
+
+    /// Abort an upload, discarding its parts
+    pub async fn abort_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+    ) -> S3Result<()> {
+        let uploads = uploads_ns(bucket);
+        let _guard = self.lock(&uploads, upload_id).await;
+        self.upload_record(bucket, key, upload_id).await?;
+        self.remove_upload(bucket, upload_id).await?;
+        debug!("Aborted upload {} of {}/{}", upload_id, bucket, key);
+        Ok(())
+    }
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(min_part_bytes: u64) -> ObjectStore {
+        ObjectStore::new(Arc::new(CacheManager::in_memory()), min_part_bytes).unwrap()
+    }
+
+    fn list_params(prefix: &str, delimiter: Option<&str>, max_keys: usize) -> ListParams {
+        ListParams {
+            prefix: prefix.to_string(),
+            delimiter: delimiter.map(str::to_string),
+            start_after: None,
+            max_keys,
+        }
+    }
+
+    async fn put(store: &ObjectStore, key: &str, data: &'static [u8]) -> ObjectInfo {
+        store
+            .put_object(
+                "bucket",
+                key,
+                Bytes::from_static(data),
+                None,
+                BTreeMap::new(),
+                None,
+            )
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn test_bucket_name_validation() {
+        for name in ["my-bucket", "a.b.c", "abc", "123"] {
+            assert!(validate_bucket_name(name).is_ok(), "{}", name);
+        }
+        for name in ["ab", "My-Bucket", "-abc", "abc-", "a..b", ".meta.x", "a_b"] {
+            assert!(validate_bucket_name(name).is_err(), "{}", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_requires_write_through_cache() {
+        let config = crate::rustfs::cache::CacheConfig {
+            write_through: false,
+            ..Default::default()
+        };
+        let cache = CacheManager::with_config(
+            config,
+            Arc::new(crate::rustfs::cache::InMemoryL3Backend::new()),
+        );
+        assert!(ObjectStore::new(Arc::new(cache), 0).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bucket_lifecycle() {
+        let store = store(0);
+        let err = store.head_bucket("bucket").await.unwrap_err();
+        assert_eq!(err.code, S3ErrorCode::NoSuchBucket);
+
+        store.create_bucket("bucket").await.unwrap();
+        store.create_bucket("bucket").await.unwrap();
+        store.create_bucket("other").await.unwrap();
+        let names: Vec<String> = store
+            .list_buckets()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, vec!["bucket", "other"]);
+
+        put(&store, "key", b"data").await;
+        let err = store.delete_bucket("bucket").await.unwrap_err();
+        assert_eq!(err.code, S3ErrorCode::BucketNotEmpty);
+
+        store.delete_object("bucket", "key").await.unwrap();
+        store.delete_bucket("bucket").await.unwrap();
+        assert!(store.head_bucket("bucket").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_object_overwrite_and_delete() {
+        let store = store(0);
+        store.create_bucket("bucket").await.unwrap();
+
+        let first = put(&store, "key", b"first").await;
+        let second = put(&store, "key", b"second version").await;
+        assert_ne!(first.etag, second.etag);
+
+        let (info, data) = store.get_object("bucket", "key").await.unwrap();
+        assert_eq!(&data[..], b"second version");
+        assert_eq!(info.size, 14);
+        assert_eq!(info.etag, second.etag);
+
+        // The replaced body is gone, only the current one is stored
+        let bodies = store.cache.list(&data_ns("bucket"), "").await.unwrap();
+        assert_eq!(bodies.len(), 1);
+
+        store.delete_object("bucket", "key").await.unwrap();
+        store.delete_object("bucket", "key").await.unwrap();
+        let err = store.get_object("bucket", "key").await.unwrap_err();
+        assert_eq!(err.code, S3ErrorCode::NoSuchKey);
+        assert!(store
+            .cache
+            .list(&data_ns("bucket"), "")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_objects_with_delimiter_and_pages() {
+        let store = store(0);
+        store.create_bucket("bucket").await.unwrap();
+        for key in [
+            "a.txt",
+            "photos/1.jpg",
+            "photos/2.jpg",
+            "photos/x/3.jpg",
+            "z.txt",
+        ] {
+            put(&store, key, b"x").await;
+        }
+
+        let page = store
+            .list_objects("bucket", &list_params("", Some("/"), 1000))
+            .await
+            .unwrap();
+        let keys: Vec<&str> = page.objects.iter().map(|o| o.key.as_str()).collect();
+        assert_eq!(keys, vec!["a.txt", "z.txt"]);
+        assert_eq!(page.common_prefixes, vec!["photos/"]);
+        assert!(!page.truncated);
+
+        let page = store
+            .list_objects("bucket", &list_params("photos/", Some("/"), 1000))
+            .await
+            .unwrap();
+        let keys: Vec<&str> = page.objects.iter().map(|o| o.key.as_str()).collect();
+        assert_eq!(keys, vec!["photos/1.jpg", "photos/2.jpg"]);
+        assert_eq!(page.common_prefixes, vec!["photos/x/"]);
+
+        // Two at a time, resuming after the last entry of each page
+        let mut params = list_params("", Some("/"), 2);
+        let mut entries = Vec::new();
+        loop {
+            let page = store.list_objects("bucket", &params).await.unwrap();
+            entries.extend(page.objects.iter().map(|o| o.key.clone()));
+            entries.extend(page.common_prefixes.iter().cloned());
+            if !page.truncated {
+                break;
+            }
+            params.start_after = page.last;
+        }
+        entries.sort();
+        assert_eq!(entries, vec!["a.txt", "photos/", "z.txt"]);
+    }
+
+    #[tokio::test]
+    async fn test_multipart_upload() {
+        let store = store(4);
+        store.create_bucket("bucket").await.unwrap();
+        let upload_id = store
+            .create_multipart_upload(
+                "bucket",
+                "big",
+                Some("text/plain".to_string()),
+                BTreeMap::new(),
+            )
+            .await
+            .unwrap();
+
+        let e1 = store
+            .upload_part(
+                "bucket",
+                "big",
+                &upload_id,
+                1,
+                Bytes::from_static(b"hello "),
+            )
+            .await
+            .unwrap();
+        let e2 = store
+            .upload_part("bucket", "big", &upload_id, 2, Bytes::from_static(b"mp"))
+            .await
+            .unwrap();
+        // Re-uploading a part replaces it
+        let e2 = {
+            let again = store
+                .upload_part("bucket", "big", &upload_id, 2, Bytes::from_static(b"world"))
+                .await
+                .unwrap();
+            assert_ne!(again, e2);
+            again
+        };
+
+        let err = store
+            .complete_multipart_upload(
+                "bucket",
+                "big",
+                &upload_id,
+                &[(2, e2.clone()), (1, e1.clone())],
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, S3ErrorCode::InvalidPartOrder);
+        let err = store
+            .complete_multipart_upload(
+                "bucket",
+                "big",
+                &upload_id,
+                &[(1, e2.clone()), (2, e2.clone())],
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, S3ErrorCode::InvalidPart);
+
+        let info = store
+            .complete_multipart_upload("bucket", "big", &upload_id, &[(1, e1), (2, e2)])
+            .await
+            .unwrap();
+        assert!(info.etag.ends_with("-2\""));
+        assert_eq!(info.content_type.as_deref(), Some("text/plain"));
+
+        let (_, data) = store.get_object("bucket", "big").await.unwrap();
+        assert_eq!(&data[..], b"hello world");
+
+        // Parts and the upload are cleaned up
+        assert!(store
+            .cache
+            .list(&parts_ns("bucket"), "")
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            store
+                .cache
+                .list(&data_ns("bucket"), "")
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        let err = store
+            .upload_part("bucket", "big", &upload_id, 3, Bytes::from_static(b"late"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, S3ErrorCode::NoSuchUpload);
+    }
+
+    #[tokio::test]
+    async fn test_multipart_part_size_and_abort() {
+        let store = store(4);
+        store.create_bucket("bucket").await.unwrap();
+        let upload_id = store
+            .create_multipart_upload("bucket", "key", None, BTreeMap::new())
+            .await
+            .unwrap();
+        let e1 = store
+            .upload_part("bucket", "key", &upload_id, 1, Bytes::from_static(b"ab"))
+            .await
+            .unwrap();
+        let e2 = store
+            .upload_part("bucket", "key", &upload_id, 2, Bytes::from_static(b"cd"))
+            .await
+            .unwrap();
+
+        let err = store
+            .complete_multipart_upload("bucket", "key", &upload_id, &[(1, e1), (2, e2)])
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, S3ErrorCode::EntityTooSmall);
+
+        let err = store.delete_bucket("bucket").await.unwrap_err();
+        assert_eq!(err.code, S3ErrorCode::BucketNotEmpty);
+
+        store
+            .abort_multipart_upload("bucket", "key", &upload_id)
+            .await
+            .unwrap();
+        assert!(store
+            .cache
+            .list(&data_ns("bucket"), "")
+            .await
+            .unwrap()
+            .is_empty());
+        let err = store
+            .abort_multipart_upload("bucket", "key", &upload_id)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, S3ErrorCode::NoSuchUpload);
+        store.delete_bucket("bucket").await.unwrap();
+    }
+}
@@ -0,0 +1,352 @@
+//! S3 XML Bodies
+//!
+//! Renders response documents and parses the `CompleteMultipartUpload`
+//! request body. The documents are small and flat, so they are written by
+//! hand rather than through a serializer.
+
+use std::fmt::Write;
+
+use chrono::{DateTime, Utc};
+
+use super::auth::uri_encode;
+use super::store::{ListPage, ObjectInfo};
+use super::{S3Error, S3ErrorCode, S3Result};
+
+const HEADER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>"#;
+const NAMESPACE: &str = "http://s3.amazonaws.com/doc/2006-03-01/";
+
+/// Escape text for element content
+pub(super) fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// ISO 8601 timestamp as S3 writes it
+pub(super) fn timestamp(time: &DateTime<Utc>) -> String {
+    time.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
+}
+
+fn element(out: &mut String, name: &str, value: &str) {
+    let _ = write!(out, "<{0}>{1}</{0}>", name, escape(value));
+}
+
+// =============================================================================
+// Responses
+// =============================================================================
+
+/// `ListAllMyBucketsResult`
+pub(super) fn list_buckets(buckets: &[(String, DateTime<Utc>)], owner: &str) -> String {
+    let mut out = format!("{}<ListAllMyBucketsResult xmlns=\"{}\">", HEADER, NAMESPACE);
+    out.push_str("<Owner>");
+    element(&mut out, "ID", owner);
+    element(&mut out, "DisplayName", owner);
+    out.push_str("</Owner><Buckets>");
+    for (name, created) in buckets {
+        out.push_str("<Bucket>");
+        element(&mut out, "Name", name);
+        element(&mut out, "CreationDate", &timestamp(created));
+        out.push_str("</Bucket>");
+    }
+    out.push_str("</Buckets></ListAllMyBucketsResult>");
+    out
+}
+
+/// Version-specific fields of a listing
+#[derive(Debug, Clone)]
+pub(super) enum ListVersion {
+    /// `ListObjects`
+    V1 { marker: String },
+    /// `ListObjectsV2`
+    V2 {
+        continuation_token: Option<String>,
+        next_continuation_token: Option<String>,
+        start_after: Option<String>,
+    },
+}
+
+/// Everything a `ListBucketResult` echoes back
+#[derive(Debug, Clone)]
+pub(super) struct ListResult<'a> {
+    pub bucket: &'a str,
+    pub prefix: &'a str,
+    pub delimiter: Option<&'a str>,
+    pub max_keys: usize,
+    pub url_encoded: bool,
+    pub version: ListVersion,
+    pub page: &'a ListPage,
+}
+
+/// `ListBucketResult`
+pub(super) fn list_objects(result: &ListResult<'_>) -> String {
+    let key = |s: &str| {
+        if result.url_encoded {
+            uri_encode(s, false)
+        } else {
+            s.to_string()
+        }
+    };
+    let page = result.page;
+
+    let mut out = format!("{}<ListBucketResult xmlns=\"{}\">", HEADER, NAMESPACE);
+    element(&mut out, "Name", result.bucket);
+    element(&mut out, "Prefix", &key(result.prefix));
+    if let Some(delimiter) = result.delimiter {
+        element(&mut out, "Delimiter", &key(delimiter));
+    }
+    element(&mut out, "MaxKeys", &result.max_keys.to_string());
+    if result.url_encoded {
+        element(&mut out, "EncodingType", "url");
+    }
+    element(&mut out, "IsTruncated", &page.truncated.to_string());
+
+    match &result.version {
+        ListVersion::V1 { marker } => {
+            element(&mut out, "Marker", &key(marker));
+            if let (true, Some(last)) = (page.truncated, &page.last) {
+                element(&mut out, "NextMarker", &key(last));
+            }
+        }
+        ListVersion::V2 {
+            continuation_token,
+            next_continuation_token,
+            start_after,
+        } => {
+            let count = page.objects.len() + page.common_prefixes.len();
+            element(&mut out, "KeyCount", &count.to_string());
+            if let Some(token) = continuation_token {
+                element(&mut out, "ContinuationToken", token);
+            }
+            if let Some(token) = next_continuation_token {
+                element(&mut out, "NextContinuationToken", token);
+            }
+            if let Some(start_after) = start_after {
+                element(&mut out, "StartAfter", &key(start_after));
+            }
+        }
+    }
+
+    for object in &page.objects {
+        contents(&mut out, object, &key(&object.key));
+    }
+    for prefix in &page.common_prefixes {
+        out.push_str("<CommonPrefixes>");
+        element(&mut out, "Prefix", &key(prefix));
+        out.push_str("</CommonPrefixes>");
+    }
+    out.push_str("</ListBucketResult>");
+    out
+}
+
+fn contents(out: &mut String, object: &ObjectInfo, key: &str) {
+    out.push_str("<Contents>");
+    element(out, "Key", key);
+    element(out, "LastModified", &timestamp(&object.last_modified));
+    element(out, "ETag", &object.etag);
+    element(out, "Size", &object.size.to_string());
+    element(out, "StorageClass", "STANDARD");
+    out.push_str("</Contents>");
+}
+
+/// `InitiateMultipartUploadResult`
+pub(super) fn initiate_multipart_upload(bucket: &str, key: &str, upload_id: &str) -> String {
+    let mut out = format!(
+        "{}<InitiateMultipartUploadResult xmlns=\"{}\">",
+        HEADER, NAMESPACE
+    );
+    element(&mut out, "Bucket", bucket);
+    element(&mut out, "Key", key);
+    element(&mut out, "UploadId", upload_id);
+    out.push_str("</InitiateMultipartUploadResult>");
+    out
+}
+
+/// `CompleteMultipartUploadResult`
+pub(super) fn complete_multipart_upload(
+    location: &str,
+    bucket: &str,
+    object: &ObjectInfo,
+) -> String {
+    let mut out = format!(
+        "{}<CompleteMultipartUploadResult xmlns=\"{}\">",
+        HEADER, NAMESPACE
+    );
+    element(&mut out, "Location", location);
+    element(&mut out, "Bucket", bucket);
+    element(&mut out, "Key", &object.key);
+    element(&mut out, "ETag", &object.etag);
+    out.push_str("</CompleteMultipartUploadResult>");
+    out
+}
+
+/// `LocationConstraint`, empty for `us-east-1` as in S3
+pub(super) fn location_constraint(region: &str) -> String {
+    let region = if region == "us-east-1" { "" } else { region };
+    format!(
+        "{}<LocationConstraint xmlns=\"{}\">{}</LocationConstraint>",
+        HEADER,
+        NAMESPACE,
+        escape(region)
+    )
+}
+
+/// `Error`
+pub(super) fn error(error: &S3Error, resource: &str, request_id: &str) -> String {
+    let mut out = format!("{}<Error>", HEADER);
+    element(&mut out, "Code", &error.code.to_string());
+    element(&mut out, "Message", &error.message);
+    element(&mut out, "Resource", resource);
+    element(&mut out, "RequestId", request_id);
+    out.push_str("</Error>");
+    out
+}
+
+// =============================================================================
+// Requests
+// =============================================================================
+
+/// Text of every `<tag>` element directly in `xml`, in order
+fn elements<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        let after = &rest[start + open.len()..];
+        let Some(end) = after.find(&close) else {
+            break;
+        };
+        found.push(&after[..end]);
+        rest = &after[end + close.len()..];
+    }
+    found
+}
+
+/// Parse a `CompleteMultipartUpload` body into `(part number, ETag)` pairs
+pub(super) fn parse_complete_multipart_upload(body: &str) -> S3Result<Vec<(u32, String)>> {
+    let malformed = || {
+        S3Error::new(
+            S3ErrorCode::MalformedXML,
+            "invalid CompleteMultipartUpload body",
+        )
+    };
+    if !body.contains("<CompleteMultipartUpload") {
+        return Err(malformed());
+    }
+
+    elements(body, "Part")
+        .into_iter()
+        .map(|part| {
+            let number = elements(part, "PartNumber")
+                .first()
+                .and_then(|n| n.trim().parse().ok())
+                .ok_or_else(malformed)?;
+            let etag = elements(part, "ETag")
+                .first()
+                .map(|e| unescape(e.trim()))
+                .ok_or_else(malformed)?;
+            Ok((number, etag))
+        })
+        .collect()
+}
+
+// =============================================================================
+// Tests
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape(r#"a<b>&"c'"#), "a&lt;b&gt;&amp;&quot;c&apos;");
+        assert_eq!(unescape(&escape(r#"a<b>&"c'"#)), r#"a<b>&"c'"#);
+    }
+
+    #[test]
+    fn test_parse_complete_multipart_upload() {
+        let body = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <CompleteMultipartUpload xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+              <Part><ETag>&quot;aaa&quot;</ETag><PartNumber>1</PartNumber></Part>
+              <Part><PartNumber>2</PartNumber><ETag>"bbb"</ETag></Part>
+            </CompleteMultipartUpload>"#;
+        let parts = parse_complete_multipart_upload(body).unwrap();
+        assert_eq!(
+            parts,
+            vec![(1, "\"aaa\"".to_string()), (2, "\"bbb\"".to_string())]
+        );
+
+        let err = parse_complete_multipart_upload("<Other/>").unwrap_err();
+        assert_eq!(err.code, S3ErrorCode::MalformedXML);
+        let err = parse_complete_multipart_upload(
+            "<CompleteMultipartUpload><Part><PartNumber>x</PartNumber></Part></CompleteMultipartUpload>",
+        )
+        .unwrap_err();
+        assert_eq!(err.code, S3ErrorCode::MalformedXML);
+    }
+
+    #[test]
+    fn test_list_objects_v2_rendering() {
+        let page = ListPage {
+            objects: vec![ObjectInfo {
+                key: "a b&c".to_string(),
+                size: 3,
+                etag: "\"abc\"".to_string(),
+                last_modified: DateTime::from_timestamp(0, 0).unwrap(),
+                content_type: None,
+                metadata: BTreeMap::new(),
+                checksum: None,
+            }],
+            common_prefixes: vec!["dir/".to_string()],
+            truncated: true,
+            last: Some("dir/".to_string()),
+        };
+        let result = ListResult {
+            bucket: "bucket",
+            prefix: "",
+            delimiter: Some("/"),
+            max_keys: 2,
+            url_encoded: false,
+            version: ListVersion::V2 {
+                continuation_token: None,
+                next_continuation_token: Some("token".to_string()),
+                start_after: None,
+            },
+            page: &page,
+        };
+        let xml = list_objects(&result);
+        assert!(xml.contains("<KeyCount>2</KeyCount>"));
+        assert!(xml.contains("<IsTruncated>true</IsTruncated>"));
+        assert!(xml.contains("<NextContinuationToken>token</NextContinuationToken>"));
+        assert!(xml.contains("<Key>a b&amp;c</Key>"));
+        assert!(xml.contains("<ETag>&quot;abc&quot;</ETag>"));
+        assert!(xml.contains("<LastModified>1970-01-01T00:00:00.000Z</LastModified>"));
+        assert!(xml.contains("<CommonPrefixes><Prefix>dir/</Prefix></CommonPrefixes>"));
+
+        let xml = list_objects(&ListResult {
+            url_encoded: true,
+            ..result
+        });
+        assert!(xml.contains("<Key>a%20b%26c</Key>"));
+        assert!(xml.contains("<EncodingType>url</EncodingType>"));
+    }
+}
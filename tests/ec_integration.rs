@@ -107,6 +107,7 @@ fn test_encode_decode_beyond_max_loss_fails() {
 }
 
 #[test]
+#[allow(clippy::needless_range_loop)]
 fn test_different_ec_configurations() {
     // Test various EC configurations
     let configs = vec![
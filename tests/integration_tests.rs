@@ -22,6 +22,7 @@ mod cache_tests {
     };
 
     #[tokio::test]
+    #[allow(clippy::len_zero)]
     async fn test_three_tier_structure() {
         let manager = CacheManager::in_memory();
 
@@ -75,6 +76,7 @@ mod cache_tests {
     }
 
     #[tokio::test]
+    #[allow(clippy::field_reassign_with_default)]
     async fn test_l3_lookup_with_promotion() {
         let mut config = CacheConfig::default();
         config.auto_promotion = true;